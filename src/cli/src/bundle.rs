@@ -1,9 +1,12 @@
 //! Bundling logic for Gigli web output
+use gigli_core::ast::Type;
+use gigli_core::ir::IRExtern;
 use std::fs;
 use std::path::Path;
 
 /// Bundles compiled WASM, loader JS, and HTML template into the output directory.
-pub fn bundle_for_web(wasm_path: &str, output_dir: &str) {
+pub fn bundle_for_web(wasm_path: &str, output_dir: &str, externs: &[IRExtern]) {
+    let _timer = gigli_core::profiling::scope("bundle");
     // Ensure output directory exists
     fs::create_dir_all(output_dir).expect("Failed to create output directory");
 
@@ -14,13 +17,17 @@ pub fn bundle_for_web(wasm_path: &str, output_dir: &str) {
         panic!("WASM file not found in output directory: {}", wasm_dest.display());
     }
 
+    write_ffi_glue(output_dir, externs);
+
     // Write enhanced loader.js with DOM operations and reactive features
-    let loader_js = r#"
+    let loader_js = ffi_bindings_js(externs) + r#"
 // Gigli v2.0 Runtime for WebAssembly
 class GigliRuntime {
     constructor() {
         this.memory = null;
         this.instance = null;
+        this.encoder = new TextEncoder();
+        this.decoder = new TextDecoder();
     }
 
     // Initialize the runtime
@@ -29,12 +36,14 @@ class GigliRuntime {
             const response = await fetch('main.wasm');
             const bytes = await response.arrayBuffer();
 
-            // Create import object with DOM operations
+            // Create import object with DOM operations, io, time and extern "js"
+            // functions. Runtime imports take each string as its address and length.
             const importObject = {
+                js: await this.loadForeignFunctions(),
                 dom: {
-                    set_inner_html: (elementIdPtr, htmlPtr) => {
-                        const elementId = this.readString(elementIdPtr);
-                        const html = this.readString(htmlPtr);
+                    set_inner_html: (elementIdPtr, elementIdLen, htmlPtr, htmlLen) => {
+                        const elementId = this.readString(elementIdPtr, elementIdLen);
+                        const html = this.readString(htmlPtr, htmlLen);
                         const element = document.getElementById(elementId);
                         if (element) {
                             element.innerHTML = html;
@@ -42,9 +51,9 @@ class GigliRuntime {
                         return 0;
                     },
 
-                    add_event_listener: (elementIdPtr, eventTypePtr, handlerFuncIndex) => {
-                        const elementId = this.readString(elementIdPtr);
-                        const eventType = this.readString(eventTypePtr);
+                    add_event_listener: (elementIdPtr, elementIdLen, eventTypePtr, eventTypeLen, handlerFuncIndex) => {
+                        const elementId = this.readString(elementIdPtr, elementIdLen);
+                        const eventType = this.readString(eventTypePtr, eventTypeLen);
                         const element = document.getElementById(elementId);
                         if (element) {
                             const handler = this.instance.exports.__indirect_function_table.get(handlerFuncIndex);
@@ -53,26 +62,34 @@ class GigliRuntime {
                         return 0;
                     },
 
-                    get_element_by_id: (elementIdPtr) => {
-                        const elementId = this.readString(elementIdPtr);
+                    get_element_by_id: (elementIdPtr, elementIdLen) => {
+                        const elementId = this.readString(elementIdPtr, elementIdLen);
                         const element = document.getElementById(elementId);
                         return element ? 1 : 0;
                     },
                     // New DOM manipulation for reactive updates
-                    update_text: (nodeIdPtr, textPtr) => {
-                        const nodeId = this.readString(nodeIdPtr);
-                        const text = this.readString(textPtr);
+                    update_text: (nodeIdPtr, nodeIdLen, textPtr, textLen) => {
+                        const nodeId = this.readString(nodeIdPtr, nodeIdLen);
+                        const text = this.readString(textPtr, textLen);
                         const node = document.getElementById(nodeId);
                         if (node) node.textContent = text;
                     },
-                    update_attribute: (nodeIdPtr, attrPtr, valuePtr) => {
-                        const nodeId = this.readString(nodeIdPtr);
-                        const attr = this.readString(attrPtr);
-                        const value = this.readString(valuePtr);
+                    update_attribute: (nodeIdPtr, nodeIdLen, attrPtr, attrLen, valuePtr, valueLen) => {
+                        const nodeId = this.readString(nodeIdPtr, nodeIdLen);
+                        const attr = this.readString(attrPtr, attrLen);
+                        const value = this.readString(valuePtr, valueLen);
                         const node = document.getElementById(nodeId);
                         if (node) node.setAttribute(attr, value);
                     },
-                }
+                },
+                io: {
+                    print: (textPtr, textLen) => {
+                        console.log(this.readString(textPtr, textLen));
+                    },
+                },
+                time: {
+                    now: () => Date.now(),
+                },
             };
 
             // Instantiate WASM module
@@ -88,40 +105,45 @@ class GigliRuntime {
         }
     }
 
-    // Read string from WASM memory
-    readString(ptr) {
+    // Load the ffi.js glue for extern "js" functions, if any are declared
+    async loadForeignFunctions() {
+        if (Object.keys(GIGLI_FFI).length === 0) return {};
+        const glue = await import('./ffi.js');
+        return bindForeignFunctions(glue, (ptr) => this.readString(ptr), (str) => this.writeString(str));
+    }
+
+    // Read a UTF-8 string of `len` bytes from WASM memory, or up to its NUL
+    // terminator if no length is given
+    readString(ptr, len) {
         if (!this.memory) return '';
 
         const view = new Uint8Array(this.memory.buffer);
-        let str = '';
-        let i = ptr;
-
-        while (view[i] !== 0) {
-            str += String.fromCharCode(view[i]);
-            i++;
+        let end = ptr + len;
+        if (len === undefined) {
+            end = ptr;
+            while (view[end] !== 0) end++;
         }
 
-        return str;
+        return this.decoder.decode(view.subarray(ptr, end));
     }
 
     // Write string to WASM memory
     writeString(str) {
         if (!this.memory) return 0;
 
+        const bytes = this.encoder.encode(str);
         const view = new Uint8Array(this.memory.buffer);
-        const ptr = this.findFreeMemory(str.length + 1);
+        const ptr = this.findFreeMemory(bytes.length + 1);
 
-        for (let i = 0; i < str.length; i++) {
-            view[ptr + i] = str.charCodeAt(i);
-        }
-        view[ptr + str.length] = 0; // null terminator
+        view.set(bytes, ptr);
+        view[ptr + bytes.length] = 0; // null terminator
 
         return ptr;
     }
 
     // Find free memory location (simple stub)
     findFreeMemory(size) {
-        return 1024; // Start after initial data
+        return this.instance.exports.__heap_base.value; // Start after the data segment
     }
 
     // Run the main function
@@ -203,3 +225,353 @@ body {
     fs::write(&css_path, css_content).expect("Failed to write style.css");
     println!("Generated style.css at {}", css_path.display());
 }
+
+/// Bundles compiled WASM as an npm package: an ESM wrapper, its TypeScript
+/// declarations, `package.json` and a README stub.
+pub fn bundle_for_npm(wasm_path: &str, output_dir: &str, package_name: &str, dts: &str, externs: &[IRExtern]) {
+    let _timer = gigli_core::profiling::scope("bundle");
+    fs::create_dir_all(output_dir).expect("Failed to create output directory");
+
+    // The wrapper loads the binary relative to itself, so it is always shipped as main.wasm
+    let wasm_dest = Path::new(output_dir).join("main.wasm");
+    if Path::new(wasm_path) != wasm_dest {
+        fs::copy(wasm_path, &wasm_dest).expect("Failed to copy WASM binary");
+    }
+
+    write_ffi_glue(output_dir, externs);
+
+    // ESM wrapper; unlike loader.js it neither touches `window` nor runs on load
+    let index_js = String::from("// Generated by the Gigli compiler. Do not edit.\n") + &ffi_bindings_js(externs) + r#"
+let instance = null;
+let memory = null;
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+function createImports() {
+    return {
+        // Runtime imports take each string as its address and length
+        dom: {
+            set_inner_html: (elementIdPtr, elementIdLen, htmlPtr, htmlLen) => {
+                const element = document.getElementById(readString(elementIdPtr, elementIdLen));
+                if (element) {
+                    element.innerHTML = readString(htmlPtr, htmlLen);
+                }
+                return 0;
+            },
+            add_event_listener: (elementIdPtr, elementIdLen, eventTypePtr, eventTypeLen, handlerFuncIndex) => {
+                const element = document.getElementById(readString(elementIdPtr, elementIdLen));
+                if (element) {
+                    const handler = instance.exports.__indirect_function_table.get(handlerFuncIndex);
+                    element.addEventListener(readString(eventTypePtr, eventTypeLen), handler);
+                }
+                return 0;
+            },
+            get_element_by_id: (elementIdPtr, elementIdLen) => {
+                return document.getElementById(readString(elementIdPtr, elementIdLen)) ? 1 : 0;
+            },
+            update_text: (nodeIdPtr, nodeIdLen, textPtr, textLen) => {
+                const node = document.getElementById(readString(nodeIdPtr, nodeIdLen));
+                if (node) node.textContent = readString(textPtr, textLen);
+            },
+            update_attribute: (nodeIdPtr, nodeIdLen, attrPtr, attrLen, valuePtr, valueLen) => {
+                const node = document.getElementById(readString(nodeIdPtr, nodeIdLen));
+                if (node) node.setAttribute(readString(attrPtr, attrLen), readString(valuePtr, valueLen));
+            },
+        },
+        io: {
+            print: (textPtr, textLen) => {
+                console.log(readString(textPtr, textLen));
+            },
+        },
+        time: {
+            now: () => Date.now(),
+        },
+    };
+}
+
+async function loadForeignFunctions() {
+    if (Object.keys(GIGLI_FFI).length === 0) return {};
+    return bindForeignFunctions(await import('./ffi.js'), readString, writeString);
+}
+
+// Instantiates the module once. `input` may be a URL or the binary itself.
+export default async function init(input = new URL('./main.wasm', import.meta.url)) {
+    if (instance) return instance.exports;
+    const bytes = input instanceof ArrayBuffer || ArrayBuffer.isView(input)
+        ? input
+        : await (await fetch(input)).arrayBuffer();
+    const imports = createImports();
+    imports.js = await loadForeignFunctions();
+    const result = await WebAssembly.instantiate(bytes, imports);
+    instance = result.instance;
+    memory = instance.exports.memory;
+    return instance.exports;
+}
+
+// Calls the module's main function
+export function run() {
+    if (!instance) {
+        throw new Error('Gigli module is not initialized; await init() first');
+    }
+    instance.exports.main();
+}
+
+// Read a UTF-8 string of `len` bytes from WASM memory, or up to its NUL
+// terminator if no length is given
+export function readString(ptr, len) {
+    if (!memory) return '';
+    const view = new Uint8Array(memory.buffer);
+    let end = ptr + len;
+    if (len === undefined) {
+        end = ptr;
+        while (view[end] !== 0) end++;
+    }
+    return decoder.decode(view.subarray(ptr, end));
+}
+
+// Write a string to WASM memory
+export function writeString(str) {
+    if (!memory) return 0;
+    const bytes = encoder.encode(str);
+    const view = new Uint8Array(memory.buffer);
+    const ptr = instance.exports.__heap_base.value; // Start after the data segment, as in loader.js
+    view.set(bytes, ptr);
+    view[ptr + bytes.length] = 0;
+    return ptr;
+}
+"#;
+    let index_path = Path::new(output_dir).join("index.js");
+    fs::write(&index_path, index_js).expect("Failed to write index.js");
+    println!("Generated index.js at {}", index_path.display());
+
+    let dts_path = Path::new(output_dir).join("index.d.ts");
+    fs::write(&dts_path, dts).expect("Failed to write index.d.ts");
+    println!("Generated index.d.ts at {}", dts_path.display());
+
+    let mut files = vec!["index.js", "index.d.ts", "main.wasm", "README.md"];
+    if !externs.is_empty() {
+        files.push("ffi.js");
+    }
+    let files = files.iter().map(|f| format!("    \"{}\"", f)).collect::<Vec<_>>().join(",\n");
+
+    // Package names are sanitized by `npm_package_name`, so no JSON escaping is needed
+    let package_json = format!(
+        r#"{{
+  "name": "{name}",
+  "version": "0.1.0",
+  "description": "Gigli component library compiled to WebAssembly",
+  "type": "module",
+  "main": "./index.js",
+  "module": "./index.js",
+  "types": "./index.d.ts",
+  "exports": {{
+    ".": {{
+      "types": "./index.d.ts",
+      "import": "./index.js",
+      "default": "./index.js"
+    }},
+    "./main.wasm": "./main.wasm",
+    "./package.json": "./package.json"
+  }},
+  "files": [
+{files}
+  ],
+  "sideEffects": false
+}}
+"#,
+        name = package_name,
+        files = files
+    );
+    let package_path = Path::new(output_dir).join("package.json");
+    fs::write(&package_path, package_json).expect("Failed to write package.json");
+    println!("Generated package.json at {}", package_path.display());
+
+    let readme = format!(
+        r#"# {name}
+
+Gigli component library compiled to WebAssembly.
+
+## Install
+
+```sh
+npm install {name}
+```
+
+## Usage
+
+```js
+import init, {{ run }} from '{name}';
+
+await init();
+run();
+```
+
+Bundlers that do not resolve `new URL('./main.wasm', import.meta.url)` can pass
+the binary's URL or bytes to `init` instead.
+
+### React
+
+```jsx
+import {{ useEffect }} from 'react';
+import init, {{ run }} from '{name}';
+
+export function App() {{
+    useEffect(() => {{
+        init().then(run);
+    }}, []);
+    return <div id="app-root" />;
+}}
+```
+
+### Vue
+
+```vue
+<script setup>
+import {{ onMounted }} from 'vue';
+import init, {{ run }} from '{name}';
+
+onMounted(async () => {{
+    await init();
+    run();
+}});
+</script>
+
+<template>
+    <div id="app-root"></div>
+</template>
+```
+"#,
+        name = package_name
+    );
+    let readme_path = Path::new(output_dir).join("README.md");
+    fs::write(&readme_path, readme).expect("Failed to write README.md");
+    println!("Generated README.md at {}", readme_path.display());
+}
+
+/// Derives an npm package name from a source file name: lowercase, with
+/// characters npm does not allow replaced by `-`.
+pub fn npm_package_name(input: &str) -> String {
+    let stem = Path::new(input).file_stem().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '-' })
+        .collect();
+    let name = name.trim_start_matches(['.', '_']);
+    if name.is_empty() {
+        "gigli-app".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// The JS type name an `extern "js"` value is converted to.
+fn ffi_type_name(ty: Option<&Type>) -> &'static str {
+    match ty {
+        Some(Type::Number) => "number",
+        Some(Type::Boolean) => "boolean",
+        Some(Type::String) => "string",
+        _ => "void",
+    }
+}
+
+/// Generates the extern signatures and the code that adapts the functions in
+/// `ffi.js` to WASM: strings cross as pointers to NUL-terminated strings and
+/// booleans as 0 or 1, so glue code only ever sees plain JS values.
+fn ffi_bindings_js(externs: &[IRExtern]) -> String {
+    let signatures = externs
+        .iter()
+        .map(|ext| {
+            let params = ext
+                .params
+                .iter()
+                .map(|p| format!("\"{}\"", ffi_type_name(p.type_annotation.as_ref())))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                "    {}: {{ params: [{}], returns: \"{}\" }},\n",
+                ext.name,
+                params,
+                ffi_type_name(ext.return_type.as_ref())
+            )
+        })
+        .collect::<String>();
+    format!(
+        r#"
+// Signatures of the extern "js" functions declared in the Gigli source
+const GIGLI_FFI = {{
+{signatures}}};
+
+// Wraps the functions exported by ffi.js as WASM imports
+function bindForeignFunctions(glue, readString, writeString) {{
+    const imports = {{}};
+    for (const [name, signature] of Object.entries(GIGLI_FFI)) {{
+        const implementation = glue[name];
+        if (typeof implementation !== 'function') {{
+            throw new Error(`ffi.js does not export a function named '${{name}}'`);
+        }}
+        imports[name] = (...args) => {{
+            const values = args.map((arg, i) => {{
+                switch (signature.params[i]) {{
+                    case 'string': return readString(arg);
+                    case 'boolean': return arg !== 0;
+                    default: return arg;
+                }}
+            }});
+            const result = implementation(...values);
+            switch (signature.returns) {{
+                case 'string': return writeString(String(result));
+                case 'boolean': return result ? 1 : 0;
+                case 'number': return Number(result);
+                default: return undefined;
+            }}
+        }};
+    }}
+    return imports;
+}}
+"#,
+        signatures = signatures
+    )
+}
+
+/// Writes `ffi.js`, the user-editable module implementing `extern "js"`
+/// functions. An existing file is never overwritten; instead, externs it does
+/// not appear to implement are reported.
+fn write_ffi_glue(output_dir: &str, externs: &[IRExtern]) {
+    if externs.is_empty() {
+        return;
+    }
+    let glue_path = Path::new(output_dir).join("ffi.js");
+    if let Ok(existing) = fs::read_to_string(&glue_path) {
+        for ext in externs {
+            if !existing.contains(&format!("function {}", ext.name)) {
+                println!("Warning: {} does not implement extern function '{}'", glue_path.display(), ext.name);
+            }
+        }
+        return;
+    }
+
+    let mut glue = String::from(
+        "// Implementations of the extern \"js\" functions declared in Gigli code.\n\
+         // `gigli bundle` creates this file once and never overwrites it, so edit it freely\n\
+         // and import any JS library you need. Arguments and return values are plain JS values.\n",
+    );
+    for ext in externs {
+        let params = ext.params.iter().map(|p| p.name.to_string()).collect::<Vec<_>>();
+        let param_docs = ext
+            .params
+            .iter()
+            .map(|p| format!(" * @param {{{}}} {}\n", ffi_type_name(p.type_annotation.as_ref()), p.name))
+            .collect::<String>();
+        glue.push_str(&format!(
+            "\n/**\n{} * @returns {{{}}}\n */\nexport function {}({}) {{\n    throw new Error('extern \"js\" fn {} is not implemented');\n}}\n",
+            param_docs,
+            ffi_type_name(ext.return_type.as_ref()),
+            ext.name,
+            params.join(", "),
+            ext.name
+        ));
+    }
+    fs::write(&glue_path, glue).expect("Failed to write ffi.js");
+    println!("Generated ffi.js at {}", glue_path.display());
+}
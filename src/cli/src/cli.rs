@@ -7,6 +7,20 @@ pub fn build_cli() -> Command {
         .about("Gigli Programming Language Compiler")
         .subcommand_negates_reqs(true)
         .arg_required_else_help(true)
+        .arg(
+            Arg::new("TIMINGS")
+                .help("Print time and allocations spent in each compiler pass")
+                .long("timings")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("TRACE")
+                .help("Write a chrome://tracing profile of the compiler to FILE")
+                .long("trace")
+                .value_name("FILE")
+                .global(true)
+        )
         .subcommand(
             Command::new("build")
                 .about("Compile a Gigli project")
@@ -42,6 +56,14 @@ pub fn build_cli() -> Command {
                         .value_parser(["debug", "release"])
                         .default_value("debug")
                 )
+                .arg(
+                    Arg::new("EMIT")
+                        .help("What to write: the WASM module, or the optimized IR in its textual form")
+                        .long("emit")
+                        .value_name("KIND")
+                        .value_parser(["wasm", "ir"])
+                        .default_value("wasm")
+                )
                 .arg(
                     Arg::new("WATCH")
                         .help("Watch for changes and rebuild")
@@ -144,6 +166,14 @@ pub fn build_cli() -> Command {
                         .long("source-map")
                         .action(clap::ArgAction::SetTrue)
                 )
+                .arg(
+                    Arg::new("FORMAT")
+                        .help("Output format: a web app, or an npm package with an ESM wrapper")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .value_parser(["web", "npm"])
+                        .default_value("web")
+                )
         )
         .subcommand(
             Command::new("fmt")
@@ -178,10 +208,18 @@ pub fn build_cli() -> Command {
                         .long("fix")
                         .action(clap::ArgAction::SetTrue)
                 )
+                .arg(
+                    Arg::new("ALLOW")
+                        .help("Hide warnings with this code, e.g. W0001")
+                        .short('A')
+                        .long("allow")
+                        .value_name("CODE")
+                        .action(clap::ArgAction::Append)
+                )
         )
         .subcommand(
             Command::new("test")
-                .about("Run tests")
+                .about("Check that the @test functions compile; running them is not supported yet")
                 .arg(
                     Arg::new("INPUT")
                         .help("Input file or directory")
@@ -190,14 +228,14 @@ pub fn build_cli() -> Command {
                 )
                 .arg(
                     Arg::new("WATCH")
-                        .help("Watch for changes")
+                        .help("Watch for changes (not supported yet)")
                         .short('w')
                         .long("watch")
                         .action(clap::ArgAction::SetTrue)
                 )
                 .arg(
                     Arg::new("COVERAGE")
-                        .help("Generate coverage report")
+                        .help("Generate coverage report (not supported yet)")
                         .short('c')
                         .long("coverage")
                         .action(clap::ArgAction::SetTrue)
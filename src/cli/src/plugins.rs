@@ -0,0 +1,286 @@
+//! Build pipeline plugins
+//!
+//! Plugins hook into `gigli bundle` and `gigli dev` at three stages: before the
+//! source is parsed, after IR generation, and after the bundle is written.
+//! Projects declare command plugins in `gigli.toml`:
+//!
+//! ```toml
+//! [[plugins]]
+//! name = "markdown"
+//! command = "node plugins/md-to-gx.js"
+//! hooks = ["pre-parse"]
+//! ```
+//!
+//! A command runs once per hook it subscribes to, from the manifest's directory,
+//! with `GIGLI_HOOK`, `GIGLI_INPUT` and `GIGLI_OUTPUT_DIR` set. For `pre-parse`
+//! it receives the source on stdin and its stdout replaces it. For `post-ir` it
+//! receives the IR in its textual form (see `gigli_core::ir::text`) on stdin,
+//! and whatever it writes to stdout is read back in that form as the new IR; a
+//! command that only inspects the IR writes nothing. For `post-bundle` its
+//! stdout is passed on for the CLI to show.
+
+use gigli_core::diagnostics::Diagnostic;
+use gigli_core::ir::{text, IRModule};
+use gigli_core::profiling;
+use serde::Deserialize;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// File name of the project manifest.
+pub const MANIFEST_FILE: &str = "gigli.toml";
+
+/// A stage of the build pipeline plugins can hook into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PreParse,
+    PostIr,
+    PostBundle,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 3] = [Hook::PreParse, Hook::PostIr, Hook::PostBundle];
+
+    fn from_name(name: &str) -> Option<Hook> {
+        Hook::ALL.into_iter().find(|hook| hook.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreParse => "pre-parse",
+            Hook::PostIr => "post-ir",
+            Hook::PostBundle => "post-bundle",
+        }
+    }
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a hook is running for.
+pub struct HookContext<'a> {
+    /// The source file being compiled.
+    pub input: &'a Path,
+    /// Where the bundle is written.
+    pub output_dir: &'a Path,
+}
+
+/// A build pipeline plugin. Every hook defaults to doing nothing.
+pub trait Plugin {
+    fn name(&self) -> &str;
+
+    /// Transforms the source before it is lexed.
+    fn pre_parse(&mut self, _ctx: &HookContext, source: String) -> Result<String, Diagnostic> {
+        Ok(source)
+    }
+
+    /// Inspects or rewrites the generated IR before code generation.
+    fn post_ir(&mut self, _ctx: &HookContext, _ir: &mut IRModule) -> Result<(), Diagnostic> {
+        Ok(())
+    }
+
+    /// Runs once the bundle has been written to `ctx.output_dir`. Returns a
+    /// report for the user, if any.
+    fn post_bundle(&mut self, _ctx: &HookContext) -> Result<String, Diagnostic> {
+        Ok(String::new())
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct Manifest {
+    #[serde(default)]
+    plugins: Vec<PluginConfig>,
+}
+
+#[derive(Deserialize)]
+struct PluginConfig {
+    name: String,
+    command: String,
+    /// Hook names; every hook when omitted.
+    hooks: Option<Vec<String>>,
+}
+
+/// A plugin declared in the manifest, implemented by an external command.
+pub struct CommandPlugin {
+    name: String,
+    command: String,
+    hooks: Vec<Hook>,
+    /// Directory the command runs in: the manifest's.
+    dir: PathBuf,
+}
+
+impl CommandPlugin {
+    /// Runs the command for `hook`, passing `stdin`, and returns its stdout.
+    fn run(&self, hook: Hook, ctx: &HookContext, stdin: &[u8]) -> Result<Vec<u8>, Diagnostic> {
+        let _timer = profiling::scope_with("plugin", format!("{} ({})", self.name, hook));
+        let failed = |message: String| Diagnostic::error(format!("Plugin '{}' failed in its {} hook", self.name, hook)).with_note(message);
+
+        let mut command = if cfg!(target_os = "windows") {
+            let mut command = Command::new("cmd");
+            command.args(["/C", &self.command]);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.args(["-c", &self.command]);
+            command
+        };
+        let mut child = command
+            .current_dir(&self.dir)
+            .env("GIGLI_HOOK", hook.name())
+            .env("GIGLI_INPUT", ctx.input)
+            .env("GIGLI_OUTPUT_DIR", ctx.output_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| failed(format!("could not run `{}`: {}", self.command, e)))?;
+
+        // A command may exit without reading its input; that is not an error
+        if let Some(mut pipe) = child.stdin.take() {
+            let _ = pipe.write_all(stdin);
+        }
+        let output = child.wait_with_output().map_err(|e| failed(e.to_string()))?;
+        if !output.status.success() {
+            return Err(failed(format!("`{}` exited with {}", self.command, output.status)));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl Plugin for CommandPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn pre_parse(&mut self, ctx: &HookContext, source: String) -> Result<String, Diagnostic> {
+        if !self.hooks.contains(&Hook::PreParse) {
+            return Ok(source);
+        }
+        let output = self.run(Hook::PreParse, ctx, source.as_bytes())?;
+        String::from_utf8(output).map_err(|_| {
+            Diagnostic::error(format!("Plugin '{}' produced invalid UTF-8 in its pre-parse hook", self.name))
+        })
+    }
+
+    fn post_ir(&mut self, ctx: &HookContext, ir: &mut IRModule) -> Result<(), Diagnostic> {
+        if !self.hooks.contains(&Hook::PostIr) {
+            return Ok(());
+        }
+        let output = self.run(Hook::PostIr, ctx, ir.to_string().as_bytes())?;
+        let output = String::from_utf8_lossy(&output);
+        if output.trim().is_empty() {
+            return Ok(());
+        }
+        // The error's span is in the plugin's output, not in any source file
+        *ir = text::parse(&output).map_err(|e| {
+            Diagnostic::error(format!("Plugin '{}' produced invalid IR in its post-ir hook", self.name)).with_note(e.message)
+        })?;
+        Ok(())
+    }
+
+    fn post_bundle(&mut self, ctx: &HookContext) -> Result<String, Diagnostic> {
+        if !self.hooks.contains(&Hook::PostBundle) {
+            return Ok(String::new());
+        }
+        let output = self.run(Hook::PostBundle, ctx, &[])?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+/// The plugins of a project, run in the order they were declared.
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl PluginHost {
+    /// Loads the plugins declared in the nearest `gigli.toml` at or above the
+    /// input file's directory. No manifest means no plugins.
+    pub fn load(input: &Path) -> Result<Self, Diagnostic> {
+        let mut host = PluginHost::default();
+        let manifest_path = match find_manifest(input) {
+            Some(path) => path,
+            None => return Ok(host),
+        };
+        let invalid = |message: String| {
+            Diagnostic::error(format!("Invalid manifest '{}'", manifest_path.display())).with_note(message)
+        };
+        let text = std::fs::read_to_string(&manifest_path).map_err(|e| invalid(e.to_string()))?;
+        let manifest: Manifest = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+
+        let dir = manifest_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        for config in manifest.plugins {
+            let hooks = match &config.hooks {
+                None => Hook::ALL.to_vec(),
+                Some(names) => names
+                    .iter()
+                    .map(|name| {
+                        Hook::from_name(name).ok_or_else(|| {
+                            invalid(format!(
+                                "plugin '{}' has unknown hook '{}'; expected pre-parse, post-ir or post-bundle",
+                                config.name, name
+                            ))
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            };
+            host.register(Box::new(CommandPlugin {
+                name: config.name,
+                command: config.command,
+                hooks,
+                dir: dir.clone(),
+            }));
+        }
+        Ok(host)
+    }
+
+    pub fn register(&mut self, plugin: Box<dyn Plugin>) {
+        self.plugins.push(plugin);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.plugins.iter().map(|p| p.name()).collect()
+    }
+
+    /// Passes the source through every plugin's `pre_parse` hook in turn.
+    pub fn pre_parse(&mut self, ctx: &HookContext, mut source: String) -> Result<String, Diagnostic> {
+        for plugin in &mut self.plugins {
+            source = plugin.pre_parse(ctx, source)?;
+        }
+        Ok(source)
+    }
+
+    pub fn post_ir(&mut self, ctx: &HookContext, ir: &mut IRModule) -> Result<(), Diagnostic> {
+        for plugin in &mut self.plugins {
+            plugin.post_ir(ctx, ir)?;
+        }
+        Ok(())
+    }
+
+    /// Runs every plugin's `post_bundle` hook and returns their reports, in order.
+    pub fn post_bundle(&mut self, ctx: &HookContext) -> Result<String, Diagnostic> {
+        let mut reports = String::new();
+        for plugin in &mut self.plugins {
+            reports.push_str(&plugin.post_bundle(ctx)?);
+        }
+        Ok(reports)
+    }
+}
+
+/// Searches the input's directory and its ancestors for `gigli.toml`.
+fn find_manifest(input: &Path) -> Option<PathBuf> {
+    let start = input.canonicalize().ok()?;
+    start
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join(MANIFEST_FILE))
+        .find(|candidate| candidate.is_file())
+}
@@ -0,0 +1,183 @@
+//! TypeScript declarations for compiled Gigli modules
+//!
+//! Describes the runtime wrapper (`loader.js`, or the ESM wrapper of an npm
+//! package) and the functions the WASM module exports, using the parameter and
+//! return types recorded in the IR.
+
+use gigli_core::ast::{Symbol, Type};
+use gigli_core::diagnostics::Diagnostic;
+use gigli_core::ir::{IRFunction, IRModule};
+use std::collections::BTreeSet;
+
+/// Generates the contents of a `.d.ts` file for the module loaded through `loader.js`.
+pub fn generate_dts(module: &IRModule) -> String {
+    let mut out = exports_declarations(module);
+    out.push_str(RUNTIME_DECLARATIONS);
+    out
+}
+
+/// Generates the `.d.ts` file for the ESM wrapper of an npm package.
+pub fn generate_esm_dts(module: &IRModule) -> String {
+    let mut out = exports_declarations(module);
+    out.push_str(ESM_DECLARATIONS);
+    out
+}
+
+/// Declares the custom types used in signatures and the `GigliExports` interface.
+fn exports_declarations(module: &IRModule) -> String {
+    let mut custom = BTreeSet::new();
+    let mut functions = Vec::new();
+    let mut names = BTreeSet::from(["main"]);
+    for function in module.functions.iter().filter(|f| f.export_name.is_some()) {
+        // The backend skips an export whose name is already taken
+        if names.insert(function.export_name.unwrap_or(function.name).as_str()) {
+            functions.push(function_signature(function, &mut custom));
+        }
+    }
+
+    let mut out = String::from("// Generated by the Gigli compiler. Do not edit.\n\n");
+    if !custom.is_empty() {
+        out.push_str("/** Gigli types without a TypeScript equivalent; values are passed through unchanged. */\n");
+        for name in &custom {
+            out.push_str(&format!("export type {} = unknown;\n", name));
+        }
+        out.push('\n');
+    }
+
+    out.push_str("/** Functions exported by the compiled WASM module. */\n");
+    out.push_str("export interface GigliExports {\n");
+    out.push_str("    readonly memory: WebAssembly.Memory;\n");
+    out.push_str("    /** The first address after the data segment, where free memory begins. */\n");
+    out.push_str("    readonly __heap_base: WebAssembly.Global;\n");
+    out.push_str("    /** The module's functions, in order; event handlers are passed as indices into it. */\n");
+    out.push_str("    readonly __indirect_function_table: WebAssembly.Table;\n");
+    out.push_str("    main(): void;\n");
+    if !functions.is_empty() {
+        out.push_str("    // `@export` Gigli functions\n");
+    }
+    for signature in &functions {
+        out.push_str(&format!("    {};\n", signature));
+    }
+    out.push_str("}\n\n");
+    out
+}
+
+/// Writes the declarations for the module to `path`.
+pub fn emit_dts(module: &IRModule, path: &str) -> Result<(), Diagnostic> {
+    std::fs::write(path, generate_dts(module)).map_err(|e| {
+        Diagnostic::error(format!("Failed to write TypeScript declarations '{}'", path)).with_note(e.to_string())
+    })
+}
+
+/// The API of `loader.js`, which instantiates the module and runs `main`.
+const RUNTIME_DECLARATIONS: &str = r#"/** The runtime wrapper created by loader.js. */
+export declare class GigliRuntime {
+    memory: WebAssembly.Memory | null;
+    instance: (WebAssembly.Instance & { readonly exports: GigliExports }) | null;
+    /** Fetches and instantiates main.wasm; resolves to false if loading failed. */
+    init(): Promise<boolean>;
+    /** Reads a UTF-8 string of `len` bytes from WASM memory, or up to its NUL terminator. */
+    readString(ptr: number, len?: number): string;
+    /** Copies a string into WASM memory and returns its address. */
+    writeString(str: string): number;
+    /** Calls the module's `main` export. */
+    run(): void;
+}
+
+declare global {
+    interface Window {
+        gigliRuntime: GigliRuntime;
+    }
+}
+"#;
+
+/// The API of the ESM wrapper generated by `gigli bundle --format npm`.
+const ESM_DECLARATIONS: &str = r#"/** Where to load the WASM binary from, or its bytes. Defaults to the packaged main.wasm. */
+export type InitInput = string | URL | ArrayBuffer | ArrayBufferView;
+
+/** Instantiates the module once and resolves to its exports. */
+export default function init(input?: InitInput): Promise<GigliExports>;
+/** Calls the module's `main` export. Requires `init` to have resolved. */
+export function run(): void;
+/** Reads a UTF-8 string of `len` bytes from WASM memory, or up to its NUL terminator. */
+export function readString(ptr: number, len?: number): string;
+/** Copies a string into WASM memory and returns its address. */
+export function writeString(str: string): number;
+"#;
+
+fn function_signature(function: &IRFunction, custom: &mut BTreeSet<Symbol>) -> String {
+    let name = function.export_name.unwrap_or(function.name);
+    let params = function
+        .params
+        .iter()
+        .map(|p| format!("{}: {}", p.name, ts_type(p.type_annotation.as_ref(), custom)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut ret = match &function.return_type {
+        Some(ty) => ts_type(Some(ty), custom),
+        None => "void".to_string(),
+    };
+    if function.is_async {
+        ret = format!("Promise<{}>", ret);
+    }
+    format!("{}({}): {}", name, params, ret)
+}
+
+/// Maps a Gigli type to TypeScript. Unannotated values are `any`; `Result<T, E>`
+/// becomes `T`, since the runtime throws on `Err`.
+fn ts_type(ty: Option<&Type>, custom: &mut BTreeSet<Symbol>) -> String {
+    let ty = match ty {
+        Some(ty) => ty,
+        None => return "any".to_string(),
+    };
+    match ty {
+        Type::String | Type::Char => "string".to_string(),
+        Type::Number => "number".to_string(),
+        Type::Boolean => "boolean".to_string(),
+        Type::Void => "void".to_string(),
+        Type::Any => "any".to_string(),
+        Type::Array(inner) => format!("{}[]", ts_element(inner, custom)),
+        Type::Object(props) => {
+            let fields = props
+                .iter()
+                .map(|p| format!("{}{}: {}", p.name, if p.optional { "?" } else { "" }, ts_type(Some(&p.type_), custom)))
+                .collect::<Vec<_>>();
+            format!("{{ {} }}", fields.join("; "))
+        }
+        Type::Function { params, return_type } => {
+            let params = params
+                .iter()
+                .enumerate()
+                .map(|(i, p)| format!("arg{}: {}", i, ts_type(Some(p), custom)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("({}) => {}", params, ts_type(Some(return_type), custom))
+        }
+        Type::Union(types) => types.iter().map(|t| ts_type(Some(t), custom)).collect::<Vec<_>>().join(" | "),
+        Type::Generic { name, type_args } => match (name.as_str(), type_args.as_slice()) {
+            ("List", [item]) | ("Set", [item]) => format!("{}[]", ts_element(item, custom)),
+            ("Map", [key, value]) => format!("Map<{}, {}>", ts_type(Some(key), custom), ts_type(Some(value), custom)),
+            _ => {
+                custom.insert(*name);
+                name.to_string()
+            }
+        },
+        Type::Custom(name) => {
+            custom.insert(*name);
+            name.to_string()
+        }
+        Type::Option(inner) => format!("{} | null", ts_type(Some(inner), custom)),
+        Type::Result(ok, _) => ts_type(Some(ok), custom),
+        Type::Ref(inner) | Type::MutRef(inner) => ts_type(Some(inner), custom),
+    }
+}
+
+/// An array element type, parenthesized when it would otherwise bind wrongly.
+fn ts_element(ty: &Type, custom: &mut BTreeSet<Symbol>) -> String {
+    let inner = ts_type(Some(ty), custom);
+    if matches!(ty, Type::Union(_) | Type::Option(_) | Type::Function { .. }) {
+        format!("({})", inner)
+    } else {
+        inner
+    }
+}
@@ -1,462 +1,988 @@
 //! WASM backend code generation for Gigli
 
-use gigli_core::ir::IRModule;
+pub mod dts;
+
+use std::collections::HashMap;
+
+use gigli_core::diagnostics::Diagnostic;
+use gigli_core::ast::{BinaryOp, Symbol, Type, UnaryOp};
+use gigli_core::ir::{DataLayout, DataRef, IRExpr, IRExprKind, IRFunction, IRModule, IRParam, IRStmt, IRStmtKind};
+use gigli_core::profiling;
 
 
 /// Emits WebAssembly code from the given IRModule.
-pub fn emit_wasm(module: &IRModule, output_path: &str) {
+pub fn emit_wasm(module: &IRModule, output_path: &str) -> Result<(), Diagnostic> {
+    let _timer = profiling::scope("wasm codegen");
     println!("[WASM backend] Generating WASM for {} functions", module.functions.len());
 
     // Generate WASM binary with DOM operations and reactive features
     let wasm_bytes = generate_wasm_binary(module);
 
-    std::fs::write(output_path, &wasm_bytes).expect("Failed to write WASM file");
+    std::fs::write(output_path, &wasm_bytes).map_err(|e| {
+        Diagnostic::error(format!("Failed to write WASM file '{}'", output_path)).with_note(e.to_string())
+    })?;
     println!("[WASM backend] Emitted WASM to {}", output_path);
+    Ok(())
 }
 
+/// Value types.
+const I32: u8 = 0x7f;
+const F64: u8 = 0x7c;
+
+/// The functions the runtime (`loader.js`) provides, imported before the
+/// externs, with their parameter and result types. Strings are passed as
+/// (pointer, length).
+const RUNTIME_IMPORTS: [(&str, &str, &[u8], Option<u8>); 5] = [
+    ("dom", "set_inner_html", &[I32; 4], Some(I32)),     // (id, html) -> i32
+    ("dom", "add_event_listener", &[I32; 5], Some(I32)), // (id, event, handler) -> i32
+    ("dom", "get_element_by_id", &[I32; 2], Some(I32)),  // (id) -> i32
+    ("io", "print", &[I32; 2], None),                    // (text) -> ()
+    ("time", "now", &[], Some(F64)),                     // () -> number
+];
+
+/// The indices of the runtime imports generated code calls by itself.
+const SET_INNER_HTML: u32 = 0;
+const ADD_EVENT_LISTENER: u32 = 1;
+
+/// The id of the element components render into, as in the generated index.html.
+const ROOT_ID: &str = "app-root";
+
 fn generate_wasm_binary(module: &IRModule) -> Vec<u8> {
-    // Create a minimal working WASM binary
     let mut wasm = Vec::new();
+    let mut data = DataLayout::of(module);
+    data.intern(ROOT_ID);
+    let cx = ModuleContext { data: &data, callees: callees(module) };
+
+    // Imports and functions add the types they use, so the type section is
+    // written once their sections are generated
+    let mut types = TypeSection::default();
+    let import_section = create_import_section(module, &mut types);
+    let function_section = create_function_section(module, &mut types);
 
     // WASM header
     wasm.extend_from_slice(&[0x00, 0x61, 0x73, 0x6d]); // \0asm
     wasm.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]); // version 1
 
-    // Type section - just one function type: () -> ()
-    let type_section = vec![
-        0x01, // type section
-        0x04, // section size
-        0x01, // num types
-        0x60, 0x00, 0x00, // () -> ()
-    ];
-    wasm.extend_from_slice(&type_section);
-
-    // Function section - declare one function
-    let function_section = vec![
-        0x03, // function section
-        0x02, // section size
-        0x01, // num functions
-        0x00, // type index 0
-    ];
-    wasm.extend_from_slice(&function_section);
-
-    // Memory section - declare memory
-    let memory_section = vec![
-        0x05, // memory section
-        0x03, // section size
-        0x01, // num memories
-        0x00, 0x01, // memory limits: min=1 page (64KB), max=unlimited
-    ];
-    wasm.extend_from_slice(&memory_section);
-
-    // Export section - export memory and main function
-    let export_section = vec![
-        0x07, // export section
-        0x0f, // section size
-        0x02, // num exports
-        // export memory
-        0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, // "memory"
-        0x02, 0x00, // memory index 0
-        // export main function
-        0x04, 0x6d, 0x61, 0x69, 0x6e, // "main"
-        0x00, 0x00, // function index 0
-    ];
-    wasm.extend_from_slice(&export_section);
-
-    // Code section - function body
-    let code_section = vec![
-        0x0a, // code section
-        0x04, // section size
-        0x01, // num functions
-        0x02, // function body size
-        0x00, // local decl count
-        0x0b, // end
-    ];
-    wasm.extend_from_slice(&code_section);
+    write_section(&mut wasm, 0x01, &types.content());
+    write_section(&mut wasm, 0x02, &import_section);
+    write_section(&mut wasm, 0x03, &function_section);
+    write_section(&mut wasm, 0x04, &create_table_section(module));
+    write_section(&mut wasm, 0x05, &create_memory_section(&data));
+    write_section(&mut wasm, 0x06, &create_global_section(&data));
+    write_section(&mut wasm, 0x07, &create_export_section(module));
+    write_section(&mut wasm, 0x09, &create_element_section(module));
+    write_section(&mut wasm, 0x0a, &create_code_section(module, &cx));
+
+    // Data section - the module's string literals
+    if !data.bytes().is_empty() {
+        write_section(&mut wasm, 0x0b, &create_data_section(&data));
+    }
 
     wasm
 }
 
-fn create_type_section() -> Vec<u8> {
-    let mut section = Vec::new();
-    section.push(0x01); // type section
-
-    // Function types:
-    // - (i32, i32) -> i32 for DOM operations
-    // - () -> () for main function
-    // - (i32) -> () for event handlers
-    // - (i32, i32) -> () for io.print (pointer, length)
-    // - () -> i32 for time.now
-    let content = vec![
-        0x13, // section size (19 bytes)
-        0x05, // num types
-        0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // 0: (i32, i32) -> i32 (DOM)
-        0x60, 0x00, 0x00,                   // 1: () -> () (main)
-        0x60, 0x01, 0x7f, 0x00,             // 2: (i32) -> () (event handler)
-        0x60, 0x02, 0x7f, 0x7f, 0x00,       // 3: (i32, i32) -> () (io.print)
-        0x60, 0x00, 0x01, 0x7f,             // 4: () -> i32 (time.now)
-    ];
-    section.extend_from_slice(&content);
-    section
-}
-
-fn create_import_section() -> Vec<u8> {
-    let mut section = Vec::new();
-    section.push(0x02); // import section
-
-    // Import DOM functions from JavaScript, plus io.print and time.now
-    let content = vec![
-        0x4b, // section size (75 bytes)
-        0x05, // num imports
-        // import "dom" "set_inner_html"
-        0x03, 0x64, 0x6f, 0x6d, // "dom"
-        0x0d, 0x73, 0x65, 0x74, 0x5f, 0x69, 0x6e, 0x6e, 0x65, 0x72, 0x5f, 0x68, 0x74, 0x6d, 0x6c, // "set_inner_html"
-        0x00, 0x00, // type index 0: (i32, i32) -> i32
-        // import "dom" "add_event_listener"
-        0x03, 0x64, 0x6f, 0x6d, // "dom"
-        0x12, 0x61, 0x64, 0x64, 0x5f, 0x65, 0x76, 0x65, 0x6e, 0x74, 0x5f, 0x6c, 0x69, 0x73, 0x74, 0x65, 0x6e, 0x65, 0x72, // "add_event_listener"
-        0x00, 0x00, // type index 0: (i32, i32) -> i32
-        // import "dom" "get_element_by_id"
-        0x03, 0x64, 0x6f, 0x6d, // "dom"
-        0x0f, 0x67, 0x65, 0x74, 0x5f, 0x65, 0x6c, 0x65, 0x6d, 0x65, 0x6e, 0x74, 0x5f, 0x62, 0x79, 0x5f, 0x69, 0x64, // "get_element_by_id"
-        0x00, 0x01, // type index 1: (i32) -> i32
-        // import "io" "print"
-        0x02, 0x69, 0x6f, // "io"
-        0x05, 0x70, 0x72, 0x69, 0x6e, 0x74, // "print"
-        0x00, 0x03, // type index 3: (i32, i32) -> ()
-        // import "time" "now"
-        0x04, 0x74, 0x69, 0x6d, 0x65, // "time"
-        0x03, 0x6e, 0x6f, 0x77, // "now"
-        0x00, 0x04, // type index 4: () -> i32
-    ];
-    section.extend_from_slice(&content);
-    section
+/// The index of `main`. Imported functions take the first indices, and the
+/// module's functions follow `main` in order.
+fn main_index(module: &IRModule) -> u32 {
+    (RUNTIME_IMPORTS.len() + module.externs.len()) as u32
 }
 
-fn create_function_section(module: &IRModule) -> Vec<u8> {
-    let mut section = Vec::new();
-    section.push(0x03); // function section
+/// A function calls can name directly: an extern or a function of the module.
+struct Callee {
+    index: u32,
+    params: Vec<u8>,
+    result: Option<u8>,
+    /// Its index in the function table, for functions of the module.
+    slot: Option<u32>,
+}
 
-    let num_functions = module.functions.len() + 1; // +1 for main function
+/// The externs and functions of `module` by name.
+fn callees(module: &IRModule) -> HashMap<Symbol, Callee> {
+    let mut callees = HashMap::new();
+    for (i, ext) in module.externs.iter().enumerate() {
+        let (params, result) = signature(&ext.params, ext.return_type.as_ref());
+        let index = (RUNTIME_IMPORTS.len() + i) as u32;
+        callees.insert(ext.name, Callee { index, params, result, slot: None });
+    }
+    for (i, function) in module.functions.iter().enumerate() {
+        let (params, result) = signature(&function.params, function.return_type.as_ref());
+        let index = main_index(module) + 1 + i as u32;
+        callees.insert(function.name, Callee { index, params, result, slot: Some(i as u32) });
+    }
+    callees
+}
 
-    // Calculate content size: 1 byte for num_functions + num_functions bytes for type indices
-    let content_size = 1 + num_functions;
+/// What code generation needs to know of the module around a function.
+struct ModuleContext<'a> {
+    data: &'a DataLayout,
+    callees: HashMap<Symbol, Callee>,
+}
 
-    // Encode section size as LEB128
-    let mut size_bytes = Vec::new();
-    encode_leb128(content_size as u32, &mut size_bytes);
+/// The function code is being generated for.
+struct FunctionContext<'a> {
+    module: &'a ModuleContext<'a>,
+    /// Its locals and their value types, parameters first.
+    locals: Vec<(Symbol, u8)>,
+    result: Option<u8>,
+}
+
+impl FunctionContext<'_> {
+    /// The index and value type of the local `name`, if it is one.
+    fn local(&self, name: Symbol) -> Option<(u32, u8)> {
+        let index = self.locals.iter().position(|(local, _)| *local == name)?;
+        Some((index as u32, self.locals[index].1))
+    }
+}
 
-    section.extend_from_slice(&size_bytes);
-    section.push(num_functions as u8); // num functions
+/// Appends a section: its id, its size in LEB128 and its content.
+fn write_section(wasm: &mut Vec<u8>, id: u8, content: &[u8]) {
+    wasm.push(id);
+    wasm.extend_from_slice(&encode_leb128(content.len() as u32, &mut Vec::new()));
+    wasm.extend_from_slice(content);
+}
+
+/// Appends a function body for the code section: its size in LEB128, then
+/// `body`, which starts with its local declarations and ends with `end`.
+fn write_function_body(code: &mut Vec<u8>, body: &[u8]) {
+    code.extend_from_slice(&encode_leb128(body.len() as u32, &mut Vec::new()));
+    code.extend_from_slice(body);
+}
 
-    // All functions use type index 1 (() -> ())
-    for _ in 0..num_functions {
-        section.push(0x01); // type index
+/// Appends a length-prefixed UTF-8 name.
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    out.extend_from_slice(&encode_leb128(name.len() as u32, &mut Vec::new()));
+    out.extend_from_slice(name.as_bytes());
+}
+
+/// Appends a function import of `module.name` with the type at `type_index`.
+fn write_import(out: &mut Vec<u8>, module: &str, name: &str, type_index: u32) {
+    write_name(out, module);
+    write_name(out, name);
+    out.push(0x00); // function import
+    out.extend_from_slice(&encode_leb128(type_index, &mut Vec::new()));
+}
+
+/// The function types of a module, each listed once; imports and functions
+/// refer to them by index.
+#[derive(Default)]
+struct TypeSection {
+    types: Vec<Vec<u8>>,
+}
+
+impl TypeSection {
+    /// The index of the encoded function type `ty`, adding it unless an
+    /// identical type is already listed.
+    fn index(&mut self, ty: Vec<u8>) -> u32 {
+        match self.types.iter().position(|listed| *listed == ty) {
+            Some(index) => index as u32,
+            None => {
+                self.types.push(ty);
+                self.types.len() as u32 - 1
+            }
+        }
     }
 
-    section
+    fn content(&self) -> Vec<u8> {
+        let mut content = encode_leb128(self.types.len() as u32, &mut Vec::new());
+        for ty in &self.types {
+            content.extend_from_slice(ty);
+        }
+        content
+    }
+}
+
+/// Encodes the function type taking `params` and returning `result`, given
+/// as value types.
+fn encode_function_type(params: &[u8], result: Option<u8>) -> Vec<u8> {
+    let mut encoded = vec![0x60];
+    encoded.extend_from_slice(&encode_leb128(params.len() as u32, &mut Vec::new()));
+    encoded.extend_from_slice(params);
+    encoded.extend_from_slice(&encode_leb128(result.is_some() as u32, &mut Vec::new()));
+    encoded.extend(result);
+    encoded
+}
+
+/// The value types of a signature's parameters and result (see `value_type`).
+/// Untyped parameters are i32, and functions returning nothing or `void` have
+/// no result.
+fn signature(params: &[IRParam], return_type: Option<&Type>) -> (Vec<u8>, Option<u8>) {
+    let params = params.iter().map(|param| param.type_annotation.as_ref().map_or(I32, value_type)).collect();
+    let result = match return_type {
+        None | Some(Type::Void) => None,
+        Some(ty) => Some(value_type(ty)),
+    };
+    (params, result)
+}
+
+// The `create_*` helpers return a section's content; `write_section` prefixes
+// it with the section id and its size once the content is complete.
+
+fn create_import_section(module: &IRModule, types: &mut TypeSection) -> Vec<u8> {
+    let mut content = encode_leb128((RUNTIME_IMPORTS.len() + module.externs.len()) as u32, &mut Vec::new());
+    for (import_module, name, params, result) in RUNTIME_IMPORTS {
+        write_import(&mut content, import_module, name, types.index(encode_function_type(params, result)));
+    }
+    // Extern functions, imported as `<abi>.<name>` (e.g. `js.confetti`)
+    for ext in &module.externs {
+        let (params, result) = signature(&ext.params, ext.return_type.as_ref());
+        write_import(&mut content, ext.module.as_str(), ext.name.as_str(), types.index(encode_function_type(&params, result)));
+    }
+    content
+}
+
+fn create_function_section(module: &IRModule, types: &mut TypeSection) -> Vec<u8> {
+    let num_functions = module.functions.len() + 1; // +1 for main function
+    let mut content = encode_leb128(num_functions as u32, &mut Vec::new());
+
+    // main takes and returns nothing; the IR functions' types follow their signatures
+    content.extend_from_slice(&encode_leb128(types.index(encode_function_type(&[], None)), &mut Vec::new()));
+    for func in &module.functions {
+        let (params, result) = signature(&func.params, func.return_type.as_ref());
+        content.extend_from_slice(&encode_leb128(types.index(encode_function_type(&params, result)), &mut Vec::new()));
+    }
+
+    content
+}
+
+/// The function table, which holds the module's functions in order, so that a
+/// function value (an event handler or a closure) is its index there.
+fn create_table_section(module: &IRModule) -> Vec<u8> {
+    let mut content = vec![
+        0x01, // num tables
+        0x70, // funcref
+        0x00, // limits: max=unlimited
+    ];
+    content.extend_from_slice(&encode_leb128(module.functions.len() as u32, &mut Vec::new())); // min
+    content
 }
 
-fn create_memory_section() -> Vec<u8> {
-    let mut section = Vec::new();
-    section.push(0x05); // memory section
+/// The size of a page of linear memory.
+const PAGE_SIZE: u32 = 0x10000;
 
-    let content = vec![
-        0x03, // section size
+fn create_memory_section(data: &DataLayout) -> Vec<u8> {
+    // At least one page (64KB), and enough to hold the data segment
+    let pages = data.end().div_ceil(PAGE_SIZE).max(1);
+    let mut content = vec![
         0x01, // num memories
-        0x00, 0x01, // memory limits: min=1 page (64KB), max=unlimited
+        0x00, // memory limits: max=unlimited
     ];
-    section.extend_from_slice(&content);
-    section
-}
-
-fn create_export_section() -> Vec<u8> {
-    let mut section = Vec::new();
-    section.push(0x07); // export section
-
-    let content = vec![
-        0x0f, // section size
-        0x02, // num exports
-        // export memory
-        0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, // "memory"
-        0x02, 0x00, // memory index 0
-        // export main function
-        0x04, 0x6d, 0x61, 0x69, 0x6e, // "main"
-        0x00, 0x03, // function index 3 (after imports)
+    content.extend_from_slice(&encode_leb128(pages, &mut Vec::new())); // min pages
+    content
+}
+
+/// The `__heap_base` global: the first address after the data segment,
+/// rounded up to 8 so that anything can be allocated there.
+fn create_global_section(data: &DataLayout) -> Vec<u8> {
+    let mut content = vec![
+        0x01, // num globals
+        0x7f, 0x00, // immutable i32
+        0x41, // i32.const
     ];
-    section.extend_from_slice(&content);
-    section
+    content.extend_from_slice(&encode_sleb128(data.end().next_multiple_of(8) as i32));
+    content.push(0x0b); // end
+    content
 }
 
-fn create_code_section(module: &IRModule) -> Vec<u8> {
-    let mut section = Vec::new();
-    section.push(0x0a); // code section
+/// Exports memory, the heap base, the function table, main and the `@export`
+/// functions under their export names.
+fn create_export_section(module: &IRModule) -> Vec<u8> {
+    let mut exports = vec![
+        ("memory", 0x02, 0),                    // memory index 0
+        ("__heap_base", 0x03, 0),               // global index 0
+        ("__indirect_function_table", 0x01, 0), // table index 0
+        ("main", 0x00, main_index(module)),
+    ];
+    for (i, function) in module.functions.iter().enumerate() {
+        if let Some(name) = function.export_name {
+            // Names are unique across exports, so one already taken is left out
+            if !exports.iter().any(|(exported, ..)| *exported == name.as_str()) {
+                exports.push((name.as_str(), 0x00, main_index(module) + 1 + i as u32));
+            }
+        }
+    }
+    let mut content = encode_leb128(exports.len() as u32, &mut Vec::new());
+    for (name, kind, index) in exports {
+        write_name(&mut content, name);
+        content.push(kind);
+        content.extend_from_slice(&encode_leb128(index, &mut Vec::new()));
+    }
+    content
+}
 
-    let mut function_bodies = Vec::new();
+/// One active segment that fills the function table with the module's functions.
+fn create_element_section(module: &IRModule) -> Vec<u8> {
+    let mut content = vec![
+        0x01, // num element segments
+        0x00, // active, table index 0
+        0x41, 0x00, 0x0b, // offset: i32.const 0, end
+    ];
+    content.extend_from_slice(&encode_leb128(module.functions.len() as u32, &mut Vec::new()));
+    for i in 0..module.functions.len() as u32 {
+        content.extend_from_slice(&encode_leb128(main_index(module) + 1 + i, &mut Vec::new()));
+    }
+    content
+}
+
+fn create_code_section(module: &IRModule, cx: &ModuleContext) -> Vec<u8> {
+    let mut content = encode_leb128(module.functions.len() as u32 + 1, &mut Vec::new()); // +1 for main function
 
     // Generate main function
-    let main_body = generate_main_function(module);
-    function_bodies.push(main_body);
+    write_function_body(&mut content, &generate_main_function(module, cx));
 
     // Generate IR functions
     for func in &module.functions {
-        let body = generate_function_body(func);
-        function_bodies.push(body);
+        write_function_body(&mut content, &generate_function_body(func, cx));
     }
 
-    // Calculate section size: 1 byte for num functions + sum of all function body sizes
-    let total_size = 1 + function_bodies.iter().map(|b| b.len()).sum::<usize>();
-
-    // Encode section size as LEB128
-    let mut size_bytes = Vec::new();
-    encode_leb128(total_size as u32, &mut size_bytes);
+    content
+}
 
-    section.extend_from_slice(&size_bytes);
-    section.push(module.functions.len() as u8 + 1); // num functions
+/// `main` runs the program: it renders each component, then calls the
+/// program's `main` function, if it has one.
+fn generate_main_function(module: &IRModule, cx: &ModuleContext) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.push(0x00); // local decl count
 
-    // Add function bodies
-    for body in function_bodies {
-        section.extend_from_slice(&body);
+    let components = module.functions.iter().filter(|func| func.name.as_str().starts_with("component_"));
+    let entry = module.functions.iter().filter(|func| func.name.as_str() == "fn_main");
+    for func in components.chain(entry) {
+        let callee = &cx.callees[&func.name];
+        // Nothing passes arguments to an entry point, so its parameters get zeros
+        for param in &callee.params {
+            push_zero(*param, &mut body);
+        }
+        push_call(callee.index, &mut body);
+        if callee.result.is_some() {
+            body.push(0x1a); // drop
+        }
     }
 
-    section
+    // End function
+    body.push(0x0b); // end
+
+    body
 }
 
-fn generate_main_function(module: &IRModule) -> Vec<u8> {
+fn generate_function_body(func: &IRFunction, module: &ModuleContext) -> Vec<u8> {
+    let (params, result) = signature(&func.params, func.return_type.as_ref());
+
+    // Parameters are the function's first locals, in order, followed by the
+    // variables it stores to
+    let mut locals: Vec<(Symbol, u8)> = func.params.iter().map(|param| param.name).zip(params).collect();
+    let num_params = locals.len();
+    collect_locals(&func.body, &mut locals);
     let mut body = Vec::new();
+    write_local_decls(&locals[num_params..], &mut body);
 
-    // Function body size (placeholder)
-    body.push(0x00); // local decl count
+    // Generate code for each statement
+    let cx = FunctionContext { module, locals, result };
+    generate_statements(&func.body, &cx, &mut body, &mut Vec::new());
 
-    // Call each function in the module
-    for (i, _func) in module.functions.iter().enumerate() {
-        // call function index (3 + i, since first 3 are imports)
-        body.push(0x10); // call
-        body.extend_from_slice(&encode_leb128(3 + i as u32, &mut Vec::new()));
+    // A function that runs off its end without returning a value returns zero
+    if let Some(result) = result {
+        push_zero(result, &mut body);
     }
 
     // End function
     body.push(0x0b); // end
 
-    // Update body size
-    let size = body.len() - 1; // -1 for the placeholder
-    body[0] = size as u8;
-
     body
 }
 
-fn generate_function_body(func: &gigli_core::ir::IRFunction) -> Vec<u8> {
-    let mut body = Vec::new();
+/// Adds the variables `stmts` store to, outside closures, to `locals`, each
+/// with the value type of the first value stored to it.
+fn collect_locals(stmts: &[IRStmt], locals: &mut Vec<(Symbol, u8)>) {
+    for stmt in stmts {
+        if let IRStmtKind::Store { target, value } | IRStmtKind::Reactive { name: target, expr: value } = &stmt.kind {
+            if !locals.iter().any(|(local, _)| local == target) {
+                locals.push((*target, value_type(&value.ty)));
+            }
+        }
+        for block in stmt.blocks() {
+            collect_locals(block, locals);
+        }
+    }
+}
 
-    // Function body size (placeholder)
-    body.push(0x00); // local decl count
+/// Appends the local declarations of a function body, which group consecutive
+/// locals of the same value type.
+fn write_local_decls(locals: &[(Symbol, u8)], body: &mut Vec<u8>) {
+    let mut groups: Vec<(u32, u8)> = Vec::new();
+    for (_, ty) in locals {
+        match groups.last_mut() {
+            Some((count, last)) if last == ty => *count += 1,
+            _ => groups.push((1, *ty)),
+        }
+    }
+    body.extend_from_slice(&encode_leb128(groups.len() as u32, &mut Vec::new()));
+    for (count, ty) in groups {
+        body.extend_from_slice(&encode_leb128(count, &mut Vec::new()));
+        body.push(ty);
+    }
+}
 
-    // Generate code for each statement
-    for stmt in &func.body {
-        match stmt {
-            gigli_core::ir::IRStmt::Call { func: func_name, args } => {
-                // Handle different function calls
-                match func_name.as_str() {
-                    "dom.set_inner_html" | "dom::set_inner_html" => {
-                        // Call imported DOM function
-                        for arg in args {
-                            generate_expression(arg, &mut body);
-                        }
-                        body.push(0x10); // call
-                        body.extend_from_slice(&encode_leb128(0, &mut Vec::new())); // import index 0
-                    }
-                    "dom.add_event_listener" | "dom::add_event_listener" => {
-                        // Call imported event listener function
-                        for arg in args {
-                            generate_expression(arg, &mut body);
-                        }
-                        body.push(0x10); // call
-                        body.extend_from_slice(&encode_leb128(1, &mut Vec::new())); // import index 1
-                    }
-                    "cell_create" => {
-                        // Create a reactive cell (simplified - just store in memory)
-                        for arg in args {
-                            generate_expression(arg, &mut body);
-                        }
-                        // Store in memory (simplified implementation)
-                        body.push(0x21); // global.set (placeholder)
-                        body.push(0x00); // global index
+/// A structured block the code being generated is nested in, innermost last;
+/// `br` counts outwards through these to find its target.
+#[derive(PartialEq)]
+enum Frame {
+    /// The block around a loop, which `Break` branches to the end of.
+    LoopExit,
+    /// The block around a loop body, which `Continue` branches to the end of.
+    LoopContinue,
+    /// Any other block, `loop` or `if`.
+    Other,
+}
+
+/// The `br` depth of the innermost enclosing `target` frame. Analysis rejects
+/// `break` and `continue` outside a loop, so one is always found.
+fn branch_depth(frames: &[Frame], target: Frame) -> u32 {
+    frames.iter().rev().position(|frame| *frame == target).unwrap_or(0) as u32
+}
+
+/// Pushes a branch instruction (`br` or `br_if`) to the innermost `target` frame.
+fn push_branch(opcode: u8, frames: &[Frame], target: Frame, body: &mut Vec<u8>) {
+    body.push(opcode);
+    body.extend_from_slice(&encode_leb128(branch_depth(frames, target), &mut Vec::new()));
+}
+
+/// Pushes a call of the function at `index`.
+fn push_call(index: u32, body: &mut Vec<u8>) {
+    body.push(0x10); // call
+    body.extend_from_slice(&encode_leb128(index, &mut Vec::new()));
+}
+
+/// Generates `stmts` inside the blocks in `frames`. Each statement leaves the
+/// stack as it found it.
+fn generate_statements(stmts: &[IRStmt], cx: &FunctionContext, body: &mut Vec<u8>, frames: &mut Vec<Frame>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            IRStmtKind::Call { func, args } => match generate_call(*func, args, cx, body) {
+                Some(result) => {
+                    if result.is_some() {
+                        body.push(0x1a); // drop
                     }
-                    "render_view" => {
-                        // Render a view (simplified - just call set_inner_html)
-                        for arg in args {
-                            generate_expression(arg, &mut body);
-                        }
-                        body.push(0x10); // call
-                        body.extend_from_slice(&encode_leb128(0, &mut Vec::new())); // import index 0
+                }
+                // Not a function, such as the calls lowering stands in for
+                // `try` and `switch` with: just evaluate the arguments
+                None => generate_dropped(args, cx, body),
+            },
+            IRStmtKind::Store { target, value } | IRStmtKind::Reactive { name: target, expr: value } => {
+                match cx.local(*target) {
+                    Some((index, ty)) => {
+                        generate_as(value, ty, cx, body);
+                        body.push(0x21); // local.set
+                        body.extend_from_slice(&encode_leb128(index, &mut Vec::new()));
                     }
-                    _ => {
-                        // Unknown function - just generate expressions
-                        for arg in args {
-                            generate_expression(arg, &mut body);
-                        }
-                        // Drop the result
+                    None => {
+                        // Store in memory (placeholder: evaluate and drop)
+                        generate_expression(value, cx, body);
                         body.push(0x1a); // drop
                     }
                 }
-            }
-            gigli_core::ir::IRStmt::Assign { target, value } => {
-                // WASM code for assignment (placeholder)
-                generate_expression(value, &mut body);
-                // Store in memory (simplified)
-                body.push(0x21); // global.set (placeholder)
-                body.push(0x00); // global index
             },
-            gigli_core::ir::IRStmt::Await(expr) => {
+            IRStmtKind::Expr(expr) => {
+                generate_expression(expr, cx, body);
+                body.push(0x1a); // drop
+            },
+            IRStmtKind::Await(expr) => {
                 // WASM code for await (placeholder: just evaluate expr)
-                generate_expression(expr, &mut body);
+                generate_expression(expr, cx, body);
+                body.push(0x1a); // drop
                 // In real WASM, would yield or await a promise
             },
-            gigli_core::ir::IRStmt::Reactive { name, expr } => {
-                // WASM code for reactivity (placeholder: evaluate and store)
-                generate_expression(expr, &mut body);
-                body.push(0x21); // global.set (placeholder)
-                body.push(0x00); // global index for reactive var
-            },
-            gigli_core::ir::IRStmt::Comprehension { target, iter, filter, expr } => {
+            IRStmtKind::Comprehension { iter, filter, expr, .. } => {
                 // WASM code for list comprehension (placeholder)
-                generate_expression(iter, &mut body);
-                if let Some(f) = filter { generate_expression(f, &mut body); }
-                generate_expression(expr, &mut body);
+                generate_expression(iter, cx, body);
+                body.push(0x1a); // drop
+                if let Some(f) = filter { generate_expression(f, cx, body); body.push(0x1a); /* drop */ }
+                generate_expression(expr, cx, body);
+                body.push(0x1a); // drop
                 // In real WASM, would loop and build array
             },
-            gigli_core::ir::IRStmt::Render(expr) => {
-                // WASM code for rendering (call JS glue to update DOM)
-                generate_expression(expr, &mut body);
-                body.push(0x10); // call
-                body.extend_from_slice(&encode_leb128(0, &mut Vec::new())); // import index 0 (set_inner_html)
+            IRStmtKind::Render(expr) => {
+                // Replace the contents of the root element with the markup
+                push_data(cx.module.data, ROOT_ID, body);
+                generate_string(expr, cx, body);
+                push_call(SET_INNER_HTML, body);
+                body.push(0x1a); // drop
             },
-            gigli_core::ir::IRStmt::EventBind { target, event, handler } => {
-                // WASM code for event binding (call JS glue)
-                body.push(0x41); // i32.const (placeholder for target)
-                body.extend_from_slice(&encode_leb128(0, &mut Vec::new()));
-                body.push(0x41); // i32.const (placeholder for event)
-                body.extend_from_slice(&encode_leb128(0, &mut Vec::new()));
-                body.push(0x10); // call
-                body.extend_from_slice(&encode_leb128(1, &mut Vec::new())); // import index 1 (add_event_listener)
+            IRStmtKind::EventBind { target, event, handler } => {
+                // The handler is passed as its index in the function table
+                let slot = cx.module.callees.get(handler).and_then(|callee| callee.slot).unwrap_or(0);
+                push_data(cx.module.data, target.as_str(), body);
+                push_data(cx.module.data, event.as_str(), body);
+                body.push(0x41); // i32.const
+                body.extend_from_slice(&encode_sleb128(slot as i32));
+                push_call(ADD_EVENT_LISTENER, body);
+                body.push(0x1a); // drop
             },
-            gigli_core::ir::IRStmt::DomOp { op, args } => {
-                // WASM code for DOM operation (call JS glue)
-                for arg in args { generate_expression(arg, &mut body); }
-                body.push(0x10); // call
-                body.extend_from_slice(&encode_leb128(0, &mut Vec::new())); // import index 0 (set_inner_html or similar)
+            IRStmtKind::DomOp { op, args } => match generate_runtime_call("dom", op, args, cx, body) {
+                Some(result) => {
+                    if result.is_some() {
+                        body.push(0x1a); // drop
+                    }
+                }
+                None => generate_dropped(args, cx, body),
             },
-            gigli_core::ir::IRStmt::Return(opt) => {
-                if let Some(expr) = opt { generate_expression(expr, &mut body); }
-                // WASM return (end function)
+            IRStmtKind::Return(value) => {
+                match (value, cx.result) {
+                    (Some(value), Some(result)) => generate_as(value, result, cx, body),
+                    (Some(value), None) => {
+                        generate_expression(value, cx, body);
+                        body.push(0x1a); // drop
+                    }
+                    (None, Some(result)) => {
+                        push_zero(result, body);
+                    }
+                    (None, None) => {}
+                }
                 body.push(0x0f); // return
             },
-            // ... handle other IRStmt variants as needed ...
+            IRStmtKind::If { condition, then_block, else_block } => {
+                generate_condition(condition, cx, body);
+                body.extend_from_slice(&[0x04, 0x40]); // if, no result
+                frames.push(Frame::Other);
+                generate_statements(then_block, cx, body, frames);
+                if !else_block.is_empty() {
+                    body.push(0x05); // else
+                    generate_statements(else_block, cx, body, frames);
+                }
+                frames.pop();
+                body.push(0x0b); // end
+            }
+            // block $exit
+            //   loop $top
+            //     br_if $exit (i32.eqz condition)
+            //     block $continue  body  end
+            //     update
+            //     br $top
+            //   end
+            // end
+            IRStmtKind::Loop { condition, body: loop_body, update } => {
+                body.extend_from_slice(&[0x02, 0x40]); // block, no result
+                frames.push(Frame::LoopExit);
+                body.extend_from_slice(&[0x03, 0x40]); // loop, no result
+                frames.push(Frame::Other);
+                generate_condition(condition, cx, body);
+                body.push(0x45); // i32.eqz
+                push_branch(0x0d, frames, Frame::LoopExit, body); // br_if
+                body.extend_from_slice(&[0x02, 0x40]); // block, no result
+                frames.push(Frame::LoopContinue);
+                generate_statements(loop_body, cx, body, frames);
+                frames.pop();
+                body.push(0x0b); // end
+                generate_statements(update, cx, body, frames);
+                body.extend_from_slice(&[0x0c, 0x00]); // br to the loop start
+                frames.pop();
+                body.push(0x0b); // end
+                frames.pop();
+                body.push(0x0b); // end
+            }
+            IRStmtKind::Block(stmts) => {
+                body.extend_from_slice(&[0x02, 0x40]); // block, no result
+                frames.push(Frame::Other);
+                generate_statements(stmts, cx, body, frames);
+                frames.pop();
+                body.push(0x0b); // end
+            }
+            IRStmtKind::Break => push_branch(0x0c, frames, Frame::LoopExit, body), // br
+            IRStmtKind::Continue => push_branch(0x0c, frames, Frame::LoopContinue, body), // br
         }
     }
+}
 
-    // End function
-    body.push(0x0b); // end
+/// Generates `expr`, leaving its value in the value type of its type.
+fn generate_expression(expr: &IRExpr, cx: &FunctionContext, body: &mut Vec<u8>) {
+    let pushed = generate_value(expr, cx, body);
+    convert(pushed, value_type(&expr.ty), body);
+}
 
-    // Update body size
-    let size = body.len() - 1; // -1 for the placeholder
-    body[0] = size as u8;
+/// Generates `expr`, leaving its value converted to the value type `ty`.
+fn generate_as(expr: &IRExpr, ty: u8, cx: &FunctionContext, body: &mut Vec<u8>) {
+    generate_expression(expr, cx, body);
+    convert(value_type(&expr.ty), ty, body);
+}
 
-    body
+/// Generates `expr` as a condition: an i32 that is nonzero if it holds.
+fn generate_condition(expr: &IRExpr, cx: &FunctionContext, body: &mut Vec<u8>) {
+    generate_expression(expr, cx, body);
+    if value_type(&expr.ty) == F64 {
+        push_zero(F64, body);
+        body.push(0x62); // f64.ne
+    }
+}
+
+/// Generates `exprs` for their effects, dropping their values.
+fn generate_dropped(exprs: &[IRExpr], cx: &FunctionContext, body: &mut Vec<u8>) {
+    for expr in exprs {
+        generate_expression(expr, cx, body);
+        body.push(0x1a); // drop
+    }
 }
 
-fn generate_expression(expr: &gigli_core::ir::IRExpr, body: &mut Vec<u8>) {
-    match expr {
-        gigli_core::ir::IRExpr::StringLiteral(_s) => {
-            // Load string from memory (simplified - just load a constant offset)
+/// Generates `expr` and returns the value type of the value it leaves, which
+/// `generate_expression` converts to that of its type where they differ.
+fn generate_value(expr: &IRExpr, cx: &FunctionContext, body: &mut Vec<u8>) -> u8 {
+    let ty = value_type(&expr.ty);
+    match &expr.kind {
+        IRExprKind::StringLiteral(string) => {
+            // A string is its address in the data segment, which has every literal of the module
+            let ptr = cx.module.data.get(string).map_or(0, |string| string.ptr);
             body.push(0x41); // i32.const
-            body.extend_from_slice(&encode_leb128(0, &mut Vec::new())); // memory offset
+            body.extend_from_slice(&encode_sleb128(ptr as i32));
+            I32
         }
-        gigli_core::ir::IRExpr::Identifier(_s) => {
-            // Load variable from memory (simplified - just load a constant)
+        IRExprKind::NumberLiteral(n) => {
+            body.push(0x44); // f64.const
+            body.extend_from_slice(&n.to_le_bytes());
+            F64
+        }
+        IRExprKind::BooleanLiteral(b) => {
             body.push(0x41); // i32.const
-            body.extend_from_slice(&encode_leb128(0, &mut Vec::new())); // constant value
+            body.push(*b as u8);
+            I32
         }
-        gigli_core::ir::IRExpr::NumberLiteral(_n) => {
-            // Placeholder: push 0 for number literals
+        IRExprKind::CharLiteral(c) => {
             body.push(0x41); // i32.const
-            body.extend_from_slice(&encode_leb128(0, &mut Vec::new()));
+            body.extend_from_slice(&encode_sleb128(*c as i32));
+            I32
         }
-        gigli_core::ir::IRExpr::Await(inner) => {
-            generate_expression(inner, body);
-            // In real WASM, would yield/await
-        },
-        gigli_core::ir::IRExpr::Option(inner) => {
-            generate_expression(inner, body);
-            // Option handling (placeholder)
+        IRExprKind::Null => {
+            body.push(0x41); // i32.const
+            body.push(0x00);
+            I32
+        }
+        IRExprKind::Load(name) => match cx.local(*name) {
+            Some((index, local_ty)) => {
+                body.push(0x20); // local.get
+                body.extend_from_slice(&encode_leb128(index, &mut Vec::new()));
+                local_ty
+            }
+            // Load variable from memory (simplified - just load a constant)
+            None => push_zero(ty, body),
         },
-        gigli_core::ir::IRExpr::Result { ok, err } => {
-            generate_expression(ok, body);
-            generate_expression(err, body);
-            // Result handling (placeholder)
+        IRExprKind::DomRef(_) => push_zero(ty, body),
+        IRExprKind::BinaryOp { op: op @ (BinaryOp::And | BinaryOp::Or), left, right }
+            if left.ty == Type::Boolean && right.ty == Type::Boolean =>
+        {
+            // `right` is only evaluated if `left` does not decide the result
+            generate_condition(left, cx, body);
+            body.extend_from_slice(&[0x04, I32]); // if, i32 result
+            if *op == BinaryOp::And {
+                generate_condition(right, cx, body);
+                body.push(0x05); // else
+                body.extend_from_slice(&[0x41, 0x00]); // i32.const 0
+            } else {
+                body.extend_from_slice(&[0x41, 0x01]); // i32.const 1
+                body.push(0x05); // else
+                generate_condition(right, cx, body);
+            }
+            body.push(0x0b); // end
+            I32
+        }
+        IRExprKind::BinaryOp { op, left, right } => {
+            generate_expression(left, cx, body);
+            generate_expression(right, cx, body);
+            match f64_binary_opcode(op) {
+                // Arithmetic gives a number, and comparisons an i32 boolean
+                Some(opcode) if left.ty == Type::Number && right.ty == Type::Number => {
+                    body.push(opcode);
+                    if opcode >= 0xa0 { F64 } else { I32 }
+                }
+                _ if left.ty == right.ty && matches!(left.ty, Type::Boolean | Type::Char) => match op {
+                    BinaryOp::Equal | BinaryOp::StrictEqual => {
+                        body.push(0x46); // i32.eq
+                        I32
+                    }
+                    BinaryOp::NotEqual | BinaryOp::StrictNotEqual => {
+                        body.push(0x47); // i32.ne
+                        I32
+                    }
+                    _ => {
+                        body.extend_from_slice(&[0x1a, 0x1a]); // drop, drop
+                        push_zero(ty, body)
+                    }
+                },
+                _ => {
+                    // Placeholder: drop both operands
+                    body.extend_from_slice(&[0x1a, 0x1a]); // drop, drop
+                    push_zero(ty, body)
+                }
+            }
+        }
+        IRExprKind::UnaryOp { op, operand } => match op {
+            UnaryOp::Not => {
+                generate_condition(operand, cx, body);
+                body.push(0x45); // i32.eqz
+                I32
+            }
+            UnaryOp::Minus if operand.ty == Type::Number => {
+                generate_expression(operand, cx, body);
+                body.push(0x9a); // f64.neg
+                F64
+            }
+            UnaryOp::Plus if operand.ty == Type::Number => {
+                generate_expression(operand, cx, body);
+                F64
+            }
+            _ => {
+                generate_expression(operand, cx, body);
+                body.push(0x1a); // drop
+                push_zero(ty, body)
+            }
         },
-        gigli_core::ir::IRExpr::List(elements) => {
+        IRExprKind::If { condition, then, else_ } => {
+            generate_condition(condition, cx, body);
+            body.push(0x04); // if
+            body.push(ty);
+            generate_as(then, ty, cx, body);
+            body.push(0x05); // else
+            generate_as(else_, ty, cx, body);
+            body.push(0x0b); // end
+            ty
+        }
+        IRExprKind::Await(inner) | IRExprKind::Cast(inner) => {
+            generate_expression(inner, cx, body);
+            // In real WASM, would yield/await or convert the value
+            value_type(&inner.ty)
+        }
+        IRExprKind::Option(inner) => {
+            generate_expression(inner, cx, body);
+            // Option handling (placeholder)
+            value_type(&inner.ty)
+        }
+        IRExprKind::Result { ok, err } => {
+            // Result handling (placeholder: evaluate both and drop)
+            for part in [ok, err] {
+                generate_expression(part, cx, body);
+                body.push(0x1a); // drop
+            }
+            push_zero(ty, body)
+        }
+        IRExprKind::List(elements) | IRExprKind::Concat(elements) => {
             // Placeholder: just evaluate all elements and drop
-            for el in elements { generate_expression(el, body); body.push(0x1a); /* drop */ }
-            // In real WASM, would allocate and store array
-        },
-        gigli_core::ir::IRExpr::Map(pairs) => {
+            generate_dropped(elements, cx, body);
+            // In real WASM, would allocate and store the array or string
+            push_zero(ty, body)
+        }
+        IRExprKind::Map(pairs) => {
             // Placeholder: evaluate all key-value pairs and drop
-            for (k, v) in pairs { generate_expression(k, body); generate_expression(v, body); body.push(0x1a); /* drop */ }
+            for (k, v) in pairs {
+                generate_expression(k, cx, body);
+                generate_expression(v, cx, body);
+                body.extend_from_slice(&[0x1a, 0x1a]); // drop, drop
+            }
             // In real WASM, would allocate and store map
+            push_zero(ty, body)
+        }
+        IRExprKind::FieldAccess { object, .. } => {
+            generate_expression(object, cx, body);
+            body.push(0x1a); // drop
+            // In real WASM, would load the field at its offset in the object
+            push_zero(ty, body)
+        }
+        IRExprKind::Index { object, index } => {
+            generate_expression(object, cx, body);
+            generate_expression(index, cx, body);
+            body.extend_from_slice(&[0x1a, 0x1a]); // drop, drop
+            push_zero(ty, body)
+        }
+        IRExprKind::StdCall { module, func, args } => match generate_runtime_call(module.as_str(), func.as_str(), args, cx, body) {
+            Some(Some(result)) => result,
+            Some(None) => push_zero(ty, body),
+            None => generate_placeholder_call(args, ty, cx, body),
         },
-        gigli_core::ir::IRExpr::StdCall { module, func, args } => {
-            // WASM import indices:
-            // 0: dom.set_inner_html
-            // 1: dom.add_event_listener
-            // 2: dom.get_element_by_id
-            // 3: io.print
-            // 4: time.now
-            if module == "io" && func == "print" {
-                // Assume args: (ptr, len)
-                for arg in args { generate_expression(arg, body); }
-                body.push(0x10); // call
-                body.extend_from_slice(&encode_leb128(3, &mut Vec::new())); // import index 3
-            } else if module == "time" && func == "now" {
-                body.push(0x10); // call
-                body.extend_from_slice(&encode_leb128(4, &mut Vec::new())); // import index 4
-            } else {
-                // Placeholder: evaluate all args and drop
-                for arg in args { generate_expression(arg, body); }
+        IRExprKind::Call { func, args } => match generate_call(*func, args, cx, body) {
+            Some(Some(result)) => result,
+            Some(None) => push_zero(ty, body),
+            None => generate_placeholder_call(args, ty, cx, body),
+        },
+        IRExprKind::CallIndirect { callee, args } | IRExprKind::MethodCall { object: callee, args, .. } => {
+            generate_expression(callee, cx, body);
+            body.push(0x1a); // drop
+            generate_placeholder_call(args, ty, cx, body)
+        }
+        IRExprKind::Closure { .. } => {
+            // In real WASM, would be a table index of the lifted function
+            body.push(0x41); // i32.const
+            body.push(0x00);
+            I32
+        }
+        IRExprKind::LiftedClosure { func, captures } => {
+            // Placeholder: evaluate the captures and drop them
+            for (_, value) in captures {
+                generate_expression(value, cx, body);
                 body.push(0x1a); // drop
             }
-        },
-        gigli_core::ir::IRExpr::Comprehension { target, iter, filter, expr } => {
-            generate_expression(iter, body);
-            if let Some(f) = filter { generate_expression(f, body); }
-            generate_expression(expr, body);
-            // In real WASM, would loop and build array
-        },
-        gigli_core::ir::IRExpr::DomRef(_s) => {
-            // Reference to DOM node (placeholder)
+            // The function's table index; in real WASM, its environment would be stored with it
+            let slot = cx.module.callees.get(func).and_then(|callee| callee.slot).unwrap_or(0);
             body.push(0x41); // i32.const
-            body.extend_from_slice(&encode_leb128(0, &mut Vec::new()));
-        },
+            body.extend_from_slice(&encode_sleb128(slot as i32));
+            I32
+        }
+        IRExprKind::Comprehension { iter, filter, expr: element, .. } => {
+            generate_expression(iter, cx, body);
+            body.push(0x1a); // drop
+            if let Some(f) = filter { generate_expression(f, cx, body); body.push(0x1a); /* drop */ }
+            generate_expression(element, cx, body);
+            body.push(0x1a); // drop
+            // In real WASM, would loop and build array
+            push_zero(ty, body)
+        }
+        IRExprKind::Match { scrutinee, arms } => {
+            // Placeholder: evaluate the scrutinee, then every guard and arm body, and drop
+            generate_expression(scrutinee, cx, body);
+            body.push(0x1a); // drop
+            for arm in arms {
+                if let Some(guard) = &arm.guard { generate_expression(guard, cx, body); body.push(0x1a); /* drop */ }
+                generate_expression(&arm.body, cx, body);
+                body.push(0x1a); // drop
+            }
+            // In real WASM, would test each pattern and branch to the first matching arm
+            push_zero(ty, body)
+        }
+        IRExprKind::Try(inner) => {
+            generate_expression(inner, cx, body);
+            // In real WASM, would test the tag, return the Err/None if set, and unwrap the value
+            value_type(&inner.ty)
+        }
         // ... handle other IRExpr variants as needed ...
     }
 }
 
-fn create_data_section(_module: &IRModule) -> Vec<u8> {
-    let mut section = Vec::new();
-    section.push(0x0b); // data section
+/// Calls the extern, module function or runtime import (as
+/// `dom.set_inner_html`) `func` names, and returns its result type. Generates
+/// nothing, returning None, if `func` names none of these.
+fn generate_call(func: Symbol, args: &[IRExpr], cx: &FunctionContext, body: &mut Vec<u8>) -> Option<Option<u8>> {
+    let Some(callee) = cx.module.callees.get(&func) else {
+        let (module, name) = func.as_str().split_once("::").or_else(|| func.as_str().split_once('.'))?;
+        return generate_runtime_call(module, name, args, cx, body);
+    };
+    for (i, param) in callee.params.iter().enumerate() {
+        match args.get(i) {
+            Some(arg) => generate_as(arg, *param, cx, body),
+            None => {
+                push_zero(*param, body);
+            }
+        }
+    }
+    // Arguments beyond the parameters are still evaluated
+    generate_dropped(args.get(callee.params.len()..).unwrap_or_default(), cx, body);
+    push_call(callee.index, body);
+    Some(callee.result)
+}
+
+/// Calls the runtime import `name` of `module` with `args`, strings passed as
+/// their address and length, and returns its result type. Generates nothing,
+/// returning None, if there is no such import or the arguments do not fit it.
+fn generate_runtime_call(module: &str, name: &str, args: &[IRExpr], cx: &FunctionContext, body: &mut Vec<u8>) -> Option<Option<u8>> {
+    let index = RUNTIME_IMPORTS.iter().position(|(import_module, import_name, ..)| *import_module == module && *import_name == name)?;
+    let (_, _, params, result) = RUNTIME_IMPORTS[index];
+    let arity: usize = args.iter().map(|arg| if arg.ty == Type::String { 2 } else { 1 }).sum();
+    if arity != params.len() {
+        return None;
+    }
+    let mut params = params.iter();
+    for arg in args {
+        if arg.ty == Type::String {
+            generate_string(arg, cx, body);
+            params.nth(1);
+        } else {
+            generate_as(arg, *params.next().unwrap_or(&I32), cx, body);
+        }
+    }
+    push_call(index as u32, body);
+    Some(result)
+}
+
+/// Pushes the address and the length in bytes of the string `expr`.
+fn generate_string(expr: &IRExpr, cx: &FunctionContext, body: &mut Vec<u8>) {
+    match &expr.kind {
+        IRExprKind::StringLiteral(string) => push_data(cx.module.data, string, body),
+        _ => {
+            generate_as(expr, I32, cx, body);
+            // In real WASM, would load the length stored with the string
+            body.push(0x41); // i32.const
+            body.push(0x00);
+        }
+    }
+}
+
+/// Pushes the address and length of `string` in the data segment.
+fn push_data(data: &DataLayout, string: &str, body: &mut Vec<u8>) {
+    let string = data.get(string).unwrap_or(DataRef { ptr: 0, len: 0 });
+    body.push(0x41); // i32.const
+    body.extend_from_slice(&encode_sleb128(string.ptr as i32));
+    body.push(0x41); // i32.const
+    body.extend_from_slice(&encode_sleb128(string.len as i32));
+}
+
+/// Placeholder for a call not yet compiled: evaluates and drops the
+/// arguments, then pushes a zero of the value type `ty`, which it returns.
+fn generate_placeholder_call(args: &[IRExpr], ty: u8, cx: &FunctionContext, body: &mut Vec<u8>) -> u8 {
+    generate_dropped(args, cx, body);
+    push_zero(ty, body)
+}
+
+/// The value type an expression of type `ty` is held in: numbers are f64, and
+/// everything else (booleans, chars and pointers to strings and objects) is i32.
+fn value_type(ty: &Type) -> u8 {
+    match ty {
+        Type::Number => F64,
+        _ => I32,
+    }
+}
+
+/// Pushes the zero value of the value type `ty`, which it returns.
+fn push_zero(ty: u8, body: &mut Vec<u8>) -> u8 {
+    match ty {
+        F64 => {
+            body.push(0x44); // f64.const
+            body.extend_from_slice(&0f64.to_le_bytes());
+        }
+        _ => body.extend_from_slice(&[0x41, 0x00]), // i32.const 0
+    }
+    ty
+}
 
-    // For now, just add a simple data section with some strings
-    let content = vec![
-        0x07, // section size
+/// Converts the value on top of the stack from the value type `from` to `to`.
+fn convert(from: u8, to: u8, body: &mut Vec<u8>) {
+    match (from, to) {
+        (F64, I32) => body.extend_from_slice(&[0xfc, 0x02]), // i32.trunc_sat_f64_s
+        (I32, F64) => body.push(0xb7),                       // f64.convert_i32_s
+        _ => {}
+    }
+}
+
+/// The instruction for `op` on two f64 operands, if it maps onto one.
+fn f64_binary_opcode(op: &BinaryOp) -> Option<u8> {
+    Some(match op {
+        BinaryOp::Add => 0xa0,
+        BinaryOp::Subtract => 0xa1,
+        BinaryOp::Multiply => 0xa2,
+        BinaryOp::Divide => 0xa3,
+        BinaryOp::Equal | BinaryOp::StrictEqual => 0x61,
+        BinaryOp::NotEqual | BinaryOp::StrictNotEqual => 0x62,
+        BinaryOp::LessThan => 0x63,
+        BinaryOp::GreaterThan => 0x64,
+        BinaryOp::LessThanEqual => 0x65,
+        BinaryOp::GreaterThanEqual => 0x66,
+        _ => return None,
+    })
+}
+
+/// One active segment that places the strings of `data` at its base address.
+fn create_data_section(data: &DataLayout) -> Vec<u8> {
+    let mut content = vec![
         0x01, // num data segments
-        0x00, // memory index
-        0x41, 0x00, // i32.const 0
-        0x0b, // end
-        0x05, // data size
-        0x48, 0x65, 0x6c, 0x6c, 0x6f, // "Hello"
+        0x00, // active, memory index 0
+        0x41, // i32.const
     ];
-    section.extend_from_slice(&content);
-    section
+    content.extend_from_slice(&encode_sleb128(data.base() as i32));
+    content.push(0x0b); // end
+    content.extend_from_slice(&encode_leb128(data.bytes().len() as u32, &mut Vec::new()));
+    content.extend_from_slice(data.bytes());
+    content
 }
 
 fn encode_leb128(mut value: u32, _bytes: &mut Vec<u8>) -> Vec<u8> {
@@ -474,3 +1000,18 @@ fn encode_leb128(mut value: u32, _bytes: &mut Vec<u8>) -> Vec<u8> {
     }
     result
 }
+
+/// Encodes a signed value in LEB128, as `i32.const` takes its operand.
+fn encode_sleb128(mut value: i32) -> Vec<u8> {
+    let mut result = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        result.push(if done { byte } else { byte | 0x80 });
+        if done {
+            break;
+        }
+    }
+    result
+}
@@ -1,6 +1,10 @@
 //! Abstract Syntax Tree (AST) definitions for Gigli
 
+pub use crate::diagnostics::Span;
+pub use crate::symbol::Symbol;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 
 /// AST node for a program (list of modules, functions, classes and components)
 #[derive(Debug)]
@@ -10,13 +14,15 @@ pub struct AST {
     pub classes: Vec<Class>,
     pub components: Vec<ComponentNode>, // NEW: replaces views
     pub imports: Vec<Import>,
+    pub externs: Vec<ExternFunction>,
 }
 
 /// AST node for a module
 #[derive(Debug)]
 pub struct Module {
-    pub name: String,
+    pub name: Symbol,
     pub items: Vec<ModuleItem>,
+    pub span: Span,
 }
 
 /// Module item types
@@ -31,37 +37,42 @@ pub enum ModuleItem {
 /// AST node for a constant
 #[derive(Debug)]
 pub struct Constant {
-    pub name: String,
+    pub name: Symbol,
     pub value: Expr,
     pub type_annotation: Option<Type>,
+    pub span: Span,
 }
 
 /// AST node for a class
 #[derive(Debug)]
 pub struct Class {
-    pub name: String,
+    pub name: Symbol,
     pub fields: Vec<Field>,
     pub methods: Vec<Method>,
     pub constructor: Option<Constructor>,
+    pub attributes: Vec<Attribute>,
+    pub span: Span,
 }
 
 /// AST node for a field
 #[derive(Debug)]
 pub struct Field {
-    pub name: String,
+    pub name: Symbol,
     pub type_annotation: Option<Type>,
     pub initial_value: Option<Expr>,
     pub is_public: bool,
+    pub span: Span,
 }
 
 /// AST node for a method
 #[derive(Debug)]
 pub struct Method {
-    pub name: String,
+    pub name: Symbol,
     pub params: Vec<Parameter>,
     pub return_type: Option<Type>,
     pub body: Vec<Stmt>,
     pub is_public: bool,
+    pub span: Span,
 }
 
 /// AST node for a constructor
@@ -69,81 +80,124 @@ pub struct Method {
 pub struct Constructor {
     pub params: Vec<Parameter>,
     pub body: Vec<Stmt>,
+    pub span: Span,
 }
 
 /// AST node for a parameter
 #[derive(Debug, Clone)]
 pub struct Parameter {
-    pub name: String,
+    pub name: Symbol,
     pub type_annotation: Option<Type>,
     pub default_value: Option<Expr>,
     pub is_ref: bool,      // NEW: & reference
     pub is_mut_ref: bool,  // NEW: &mut reference
+    /// For a destructured parameter like `{ name, age }: User`; `name` is then the pattern's text.
+    pub pattern: Option<Pattern>,
+    pub span: Span,
 }
 
 /// AST node for an import
 #[derive(Debug)]
 pub struct Import {
     pub module: String,
-    pub items: Vec<String>,
-    pub alias: Option<String>,
+    pub items: Vec<Symbol>,
+    pub alias: Option<Symbol>,
+    pub span: Span,
 }
 
 /// AST node for a function
 #[derive(Debug, Clone)]
 pub struct Function {
-    pub name: String,
+    pub name: Symbol,
     pub params: Vec<Parameter>,
     pub return_type: Option<Type>,
     pub body: Vec<Stmt>,
     pub is_public: bool,
     pub is_async: bool, // NEW: async fn support
+    pub attributes: Vec<Attribute>,
+    pub span: Span,
+}
+
+/// An attribute before a function, component or class: `@test`, `@export` or
+/// `@deprecated("use b")`
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: Symbol,
+    pub args: Vec<Expr>,
+    pub span: Span,
+}
+
+impl Attribute {
+    /// The attribute named `name` in `attributes`, if present.
+    pub fn find<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+        attributes.iter().find(|a| a.name == name)
+    }
+
+    /// The first argument, if it is a string literal: the message of `@deprecated("...")`.
+    pub fn string_arg(&self) -> Option<&str> {
+        match self.args.first().map(|a| &a.kind) {
+            Some(ExprKind::StringLiteral(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// AST node for a foreign function: `extern "js" fn confetti(count: number);`
+#[derive(Debug, Clone)]
+pub struct ExternFunction {
+    /// The ABI string; only `"js"` is supported.
+    pub abi: String,
+    pub name: Symbol,
+    pub params: Vec<Parameter>,
+    pub return_type: Option<Type>,
+    pub span: Span,
 }
 
 /// AST node for a component (unified logic, markup, style)
 #[derive(Debug, Clone)]
 pub struct ComponentNode {
-    pub name: String,
+    pub name: Symbol,
     pub state_vars: Vec<StateVar>,
     pub let_vars: Vec<LetVar>,
+    /// `$: name = value;` statements, rerun when a value they read changes.
+    pub reactive: Vec<Stmt>,
     pub functions: Vec<Function>,
     pub markup: Vec<MarkupNode>,
     pub style: Option<String>, // raw CSS block
-}
-
-impl ComponentNode {
-    pub fn to_string_formatted(&self) -> String {
-        let mut s = String::new();
-        s.push_str(&format!("component {} {{\n", self.name));
-        // TODO: Add formatted output for state, let, fn, markup, style
-        s.push_str("}}\n");
-        s
-    }
+    /// The whole `style { ... }` block, whose source the formatter keeps as written.
+    pub style_span: Option<Span>,
+    pub attributes: Vec<Attribute>,
+    pub span: Span,
 }
 
 /// AST node for a state variable (reactive)
 #[derive(Debug, Clone)]
 pub struct StateVar {
-    pub name: String,
+    pub name: Symbol,
     pub type_annotation: Option<Type>,
     pub initial_value: Expr,
+    pub span: Span,
 }
 
 /// AST node for a let variable (derived, immutable or computed)
 #[derive(Debug, Clone)]
 pub struct LetVar {
-    pub name: String,
+    pub name: Symbol,
     pub type_annotation: Option<Type>,
     pub value: Expr,
+    /// Declared with `mut`, so it can be assigned to.
+    pub mutable: bool,
+    pub span: Span,
 }
 
 /// Markup node (HTML-like structure, including control flow blocks)
 #[derive(Debug, Clone)]
 pub enum MarkupNode {
     Element {
-        tag: String,
-        attributes: HashMap<String, Expr>,
+        tag: Symbol,
+        attributes: HashMap<Symbol, Expr>,
         children: Vec<MarkupNode>,
+        span: Span,
     },
     Text(Expr),
     IfBlock(IfBlockNode),
@@ -157,21 +211,79 @@ pub struct IfBlockNode {
     pub condition: Expr,
     pub then_branch: Vec<MarkupNode>,
     pub else_branch: Option<Vec<MarkupNode>>,
+    pub span: Span,
 }
 
 /// For loop block node for {#for ...}{/for}
 #[derive(Debug, Clone)]
 pub struct ForLoopBlockNode {
-    pub iterator: String,
+    pub iterator: Symbol,
     pub iterable: Expr,
     pub body: Vec<MarkupNode>,
+    pub span: Span,
 }
 
 /// AST node for a statement
 #[derive(Debug, Clone)]
-pub enum Stmt {
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Stmt { kind, span }
+    }
+
+    /// The expressions directly in this statement, not in the statements nested in it.
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match &self.kind {
+            StmtKind::Expr(expr) | StmtKind::Throw(expr) | StmtKind::Return(Some(expr)) => vec![expr],
+            StmtKind::Assign { value, .. } | StmtKind::Destructure { value, .. } => vec![value],
+            StmtKind::If { condition, .. } => vec![condition],
+            StmtKind::Loop { condition, .. } => condition.iter().collect(),
+            StmtKind::ForIn { iterable, .. } | StmtKind::ForOf { iterable, .. } => vec![iterable],
+            StmtKind::StateVarDecl(state) => vec![&state.initial_value],
+            StmtKind::LetVarDecl(var) => vec![&var.value],
+            StmtKind::Switch { expression, cases, .. } => {
+                std::iter::once(expression).chain(cases.iter().map(|case| &case.value)).collect()
+            }
+            StmtKind::Reactive { expr, .. } => vec![expr],
+            StmtKind::Comprehension { iter, filter, expr, .. } => std::iter::once(iter).chain(filter).chain(std::iter::once(expr)).collect(),
+            StmtKind::Return(None) | StmtKind::Block(_) | StmtKind::Try { .. } | StmtKind::Break(_) | StmtKind::Continue(_) => Vec::new(),
+        }
+    }
+
+    /// The statement lists nested in this statement: branches, loop bodies and
+    /// blocks. A loop's initializer and update are lists of one.
+    pub fn blocks(&self) -> Vec<&[Stmt]> {
+        match &self.kind {
+            StmtKind::If { then, else_, .. } => std::iter::once(then.as_slice()).chain(else_.as_deref()).collect(),
+            StmtKind::Loop { init, update, body, .. } => init
+                .iter()
+                .chain(update)
+                .map(|stmt| std::slice::from_ref(stmt.as_ref()))
+                .chain(std::iter::once(body.as_slice()))
+                .collect(),
+            StmtKind::ForIn { body, .. } | StmtKind::ForOf { body, .. } | StmtKind::Block(body) => vec![body],
+            StmtKind::Destructure { else_branch, .. } => else_branch.as_deref().into_iter().collect(),
+            StmtKind::Try { body, catch, finally } => std::iter::once(body.as_slice())
+                .chain(catch.iter().map(|catch| catch.body.as_slice()))
+                .chain(finally.as_deref())
+                .collect(),
+            StmtKind::Switch { cases, default, .. } => {
+                cases.iter().map(|case| case.body.as_slice()).chain(default.as_deref()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The kinds of statement
+#[derive(Debug, Clone)]
+pub enum StmtKind {
     Expr(Expr),
-    Assign { target: String, value: Expr },
+    Assign { target: Symbol, value: Expr },
     If { condition: Expr, then: Vec<Stmt>, else_: Option<Vec<Stmt>> },
     Loop {
         init: Option<Box<Stmt>>,
@@ -179,30 +291,32 @@ pub enum Stmt {
         update: Option<Box<Stmt>>,
         body: Vec<Stmt>
     },
-    ForIn { variable: String, iterable: Expr, body: Vec<Stmt> },
-    ForOf { variable: String, iterable: Expr, body: Vec<Stmt> },
+    ForIn { variable: Symbol, iterable: Expr, body: Vec<Stmt> },
+    ForOf { variable: Symbol, iterable: Expr, body: Vec<Stmt> },
     Return(Option<Expr>),
     StateVarDecl(StateVar), // NEW: state variable declaration
     LetVarDecl(LetVar),    // NEW: let variable declaration
+    Destructure { pattern: Pattern, type_annotation: Option<Type>, value: Expr, else_branch: Option<Vec<Stmt>>, mutable: bool }, // let { a, b } = value; or let Some(x) = value else { ... }
     Block(Vec<Stmt>),
     Try { body: Vec<Stmt>, catch: Option<CatchBlock>, finally: Option<Vec<Stmt>> },
     Throw(Expr),
-    Break(Option<String>), // label for labeled break
-    Continue(Option<String>), // label for labeled continue
+    Break(Option<Symbol>), // label for labeled break
+    Continue(Option<Symbol>), // label for labeled continue
     Switch {
         expression: Expr,
         cases: Vec<SwitchCase>,
         default: Option<Vec<Stmt>>,
     },
-    Reactive { name: String, expr: Expr }, // NEW: $: reactivity
-    Comprehension { target: String, iter: Expr, filter: Option<Expr>, expr: Expr }, // NEW: list comprehensions
+    Reactive { name: Symbol, expr: Expr }, // NEW: $: reactivity
+    Comprehension { target: Symbol, iter: Expr, filter: Option<Expr>, expr: Expr }, // NEW: list comprehensions
 }
 
 /// Catch block for try-catch statements
 #[derive(Debug, Clone)]
 pub struct CatchBlock {
-    pub error_var: String,
+    pub error_var: Symbol,
     pub body: Vec<Stmt>,
+    pub span: Span,
 }
 
 /// Switch case
@@ -210,13 +324,80 @@ pub struct CatchBlock {
 pub struct SwitchCase {
     pub value: Expr,
     pub body: Vec<Stmt>,
+    pub span: Span,
 }
 
 /// AST node for an expression
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+
+    /// The expressions directly inside this one. The statements of an arrow
+    /// function body and the arms' patterns are not expressions, so they are left out.
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::ArrayLiteral(items) => items.iter().collect(),
+            ExprKind::ObjectLiteral(props) => props
+                .iter()
+                .flat_map(|prop| match &prop.key {
+                    PropertyKey::Computed(key) => vec![key, &prop.value],
+                    PropertyKey::Name(_) => vec![&prop.value],
+                })
+                .collect(),
+            ExprKind::BinaryOp { left, right, .. }
+            | ExprKind::Concat { left, right }
+            | ExprKind::Coalesce { value: left, default: right } => vec![left, right],
+            ExprKind::UnaryOp { operand: inner, .. }
+            | ExprKind::PropertyAccess { object: inner, .. }
+            | ExprKind::TypeAssert { value: inner, .. }
+            | ExprKind::Await(inner)
+            | ExprKind::Try(inner) => vec![inner],
+            ExprKind::Call { func: callee, args } | ExprKind::New { class: callee, args } => {
+                std::iter::once(callee.as_ref()).chain(args).collect()
+            }
+            ExprKind::MethodCall { object, args, .. } => std::iter::once(object.as_ref()).chain(args).collect(),
+            ExprKind::OptionalChain { object, args, .. } => std::iter::once(object.as_ref()).chain(args.iter().flatten()).collect(),
+            ExprKind::If { condition, then, else_ } => vec![condition, then, else_],
+            ExprKind::ArrayAccess { array, index } => vec![array, index],
+            ExprKind::TemplateLiteral { parts } => parts
+                .iter()
+                .filter_map(|part| match part {
+                    TemplatePart::Expression(expr) => Some(expr),
+                    TemplatePart::String(_) => None,
+                })
+                .collect(),
+            ExprKind::Comprehension { iter, filter, expr, .. } => {
+                std::iter::once(iter.as_ref()).chain(filter.as_deref()).chain(std::iter::once(expr.as_ref())).collect()
+            }
+            ExprKind::Match { scrutinee, arms } => std::iter::once(scrutinee.as_ref())
+                .chain(arms.iter().flat_map(|arm| arm.guard.iter().chain(std::iter::once(&arm.body))))
+                .collect(),
+            ExprKind::StringLiteral(_)
+            | ExprKind::CharLiteral(_)
+            | ExprKind::NumberLiteral(_)
+            | ExprKind::BooleanLiteral(_)
+            | ExprKind::NullLiteral
+            | ExprKind::UndefinedLiteral
+            | ExprKind::Identifier(_)
+            | ExprKind::CellAccess(_)
+            | ExprKind::ArrowFunction { .. } => Vec::new(),
+        }
+    }
+}
+
+/// The kinds of expression
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     // Literals
     StringLiteral(String),
+    CharLiteral(char),
     NumberLiteral(f64),
     BooleanLiteral(bool),
     NullLiteral,
@@ -225,8 +406,8 @@ pub enum Expr {
     ObjectLiteral(Vec<ObjectProperty>),
 
     // Variables and identifiers
-    Identifier(String),
-    CellAccess(String),
+    Identifier(Symbol),
+    CellAccess(Symbol),
 
     // Binary operations
     BinaryOp { left: Box<Expr>, op: BinaryOp, right: Box<Expr> },
@@ -238,7 +419,7 @@ pub enum Expr {
     Call { func: Box<Expr>, args: Vec<Expr> },
 
     // Method calls
-    MethodCall { object: Box<Expr>, method: String, args: Vec<Expr> },
+    MethodCall { object: Box<Expr>, method: Symbol, args: Vec<Expr> },
 
     // Conditional expressions
     If { condition: Box<Expr>, then: Box<Expr>, else_: Box<Expr> },
@@ -247,7 +428,7 @@ pub enum Expr {
     Concat { left: Box<Expr>, right: Box<Expr> },
 
     // Property access
-    PropertyAccess { object: Box<Expr>, property: String },
+    PropertyAccess { object: Box<Expr>, property: Symbol },
 
     // Array access
     ArrayAccess { array: Box<Expr>, index: Box<Expr> },
@@ -264,15 +445,143 @@ pub enum Expr {
     // Type assertion
     TypeAssert { value: Box<Expr>, type_: Type },
     Await(Box<Expr>), // NEW: await expr
-    Comprehension { target: String, iter: Box<Expr>, filter: Option<Box<Expr>>, expr: Box<Expr> }, // NEW: list comprehensions
+    /// `value?`: the `Ok`/`Some` value, or an early return of the `Err`/`None`.
+    Try(Box<Expr>),
+    /// `object?.property`, or `object?.method(args)` when `args` is set: `None` if
+    /// `object` is `None`, otherwise the member of the `Some` value.
+    OptionalChain { object: Box<Expr>, property: Symbol, args: Option<Vec<Expr>> },
+    /// `value ?? default`: the `Some` value, or `default` if `value` is `None`.
+    Coalesce { value: Box<Expr>, default: Box<Expr> },
+    Comprehension { target: Symbol, iter: Box<Expr>, filter: Option<Box<Expr>>, expr: Box<Expr> }, // NEW: list comprehensions
+
+    // Pattern matching
+    Match { scrutinee: Box<Expr>, arms: Vec<MatchArm> },
+}
+
+/// One `pattern [if guard] => body` arm of a `match` expression
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+    pub span: Span,
+}
+
+/// A pattern in a `match` arm
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum PatternKind {
+    /// `_`
+    Wildcard,
+    /// A name that binds the matched value.
+    Binding(Symbol),
+    Number(f64),
+    String(String),
+    Char(char),
+    Boolean(bool),
+    /// `Some(p)`, `Ok(p)`, `Err(p)` or `None`.
+    Constructor { name: Symbol, args: Vec<Pattern> },
+    /// `{ name, age: years }`; a shorthand field binds a variable of the same name.
+    Object { fields: Vec<(Symbol, Pattern)> },
+    /// `[first, second, ...rest]`
+    Array { elements: Vec<Pattern>, rest: Option<Symbol> },
+}
+
+impl Pattern {
+    /// Names bound by this pattern, in source order.
+    pub fn bindings(&self) -> Vec<Symbol> {
+        let mut names = Vec::new();
+        self.collect_bindings(&mut names);
+        names
+    }
+
+    /// True if some value of the matched type fails to match, as with a literal
+    /// or a constructor pattern. Such a pattern needs an `else` in a `let`.
+    pub fn is_refutable(&self) -> bool {
+        match &self.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => false,
+            PatternKind::Object { fields } => fields.iter().any(|(_, field)| field.is_refutable()),
+            PatternKind::Array { elements, .. } => elements.iter().any(Pattern::is_refutable),
+            _ => true,
+        }
+    }
+
+    fn collect_bindings(&self, names: &mut Vec<Symbol>) {
+        match &self.kind {
+            PatternKind::Binding(name) => names.push(*name),
+            PatternKind::Constructor { args, .. } => {
+                for arg in args {
+                    arg.collect_bindings(names);
+                }
+            }
+            PatternKind::Object { fields } => {
+                for (_, field) in fields {
+                    field.collect_bindings(names);
+                }
+            }
+            PatternKind::Array { elements, rest } => {
+                for element in elements {
+                    element.collect_bindings(names);
+                }
+                names.extend(rest);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |patterns: &[Pattern]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ");
+        match &self.kind {
+            PatternKind::Wildcard => write!(f, "_"),
+            PatternKind::Binding(name) => write!(f, "{}", name),
+            PatternKind::Number(n) => write!(f, "{}", n),
+            PatternKind::String(s) => write!(f, "{:?}", s),
+            PatternKind::Char(c) => write!(f, "{:?}", c),
+            PatternKind::Boolean(b) => write!(f, "{}", b),
+            PatternKind::Constructor { name, args } if args.is_empty() => write!(f, "{}", name),
+            PatternKind::Constructor { name, args } => write!(f, "{}({})", name, list(args)),
+            PatternKind::Object { fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(key, pattern)| match &pattern.kind {
+                        PatternKind::Binding(name) if name == key => key.to_string(),
+                        _ => format!("{}: {}", key, pattern),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{{ {} }}", fields.join(", "))
+            }
+            PatternKind::Array { elements, rest } => {
+                let mut items = elements.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                items.extend(rest.iter().map(|name| format!("...{}", name)));
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
 }
 
 /// Object property for object literals
 #[derive(Debug, Clone)]
 pub struct ObjectProperty {
-    pub key: String,
+    pub key: PropertyKey,
     pub value: Expr,
     pub shorthand: bool,
+    pub span: Span,
+}
+
+/// The key of an object literal property
+#[derive(Debug, Clone)]
+pub enum PropertyKey {
+    /// `name: value`, `"name": value` or the shorthand `name`
+    Name(Symbol),
+    /// `[expr]: value`
+    Computed(Expr),
 }
 
 /// Template literal part
@@ -283,7 +592,7 @@ pub enum TemplatePart {
 }
 
 /// Binary operators
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BinaryOp {
     Add,
     Subtract,
@@ -310,7 +619,7 @@ pub enum BinaryOp {
 }
 
 /// Unary operators
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum UnaryOp {
     Plus,
     Minus,
@@ -324,9 +633,10 @@ pub enum UnaryOp {
 }
 
 /// Type system
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Type {
     String,
+    Char,
     Number,
     Boolean,
     Void,
@@ -338,22 +648,73 @@ pub enum Type {
         return_type: Box<Type>,
     },
     Union(Vec<Type>),
-    Generic { name: String, type_args: Vec<Type> },
-    Custom(String),
+    Generic { name: Symbol, type_args: Vec<Type> },
+    Custom(Symbol),
     Option(Box<Type>), // NEW: Option<T>
     Result(Box<Type>, Box<Type>), // NEW: Result<T, E>
     Ref(Box<Type>),    // NEW: &T
     MutRef(Box<Type>), // NEW: &mut T
 }
 
+/// Formats a type the way it is written in Gigli source.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn list(types: &[Type], sep: &str) -> String {
+            types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(sep)
+        }
+        match self {
+            Type::String => write!(f, "string"),
+            Type::Char => write!(f, "char"),
+            Type::Number => write!(f, "number"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Void => write!(f, "void"),
+            Type::Any => write!(f, "any"),
+            Type::Array(inner) => write!(f, "{}[]", inner),
+            Type::Object(props) => {
+                let fields = props
+                    .iter()
+                    .map(|p| format!("{}{}: {}", p.name, if p.optional { "?" } else { "" }, p.type_))
+                    .collect::<Vec<_>>();
+                write!(f, "{{ {} }}", fields.join(", "))
+            }
+            Type::Function { params, return_type } => write!(f, "({}) => {}", list(params, ", "), return_type),
+            Type::Union(types) => write!(f, "{}", list(types, " | ")),
+            Type::Generic { name, type_args } => write!(f, "{}<{}>", name, list(type_args, ", ")),
+            Type::Custom(name) => write!(f, "{}", name),
+            Type::Option(inner) => write!(f, "Option<{}>", inner),
+            Type::Result(ok, err) => write!(f, "Result<{}, {}>", ok, err),
+            Type::Ref(inner) => write!(f, "&{}", inner),
+            Type::MutRef(inner) => write!(f, "&mut {}", inner),
+        }
+    }
+}
+
 /// Object type property
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ObjectTypeProperty {
-    pub name: String,
+    pub name: Symbol,
     pub type_: Type,
     pub optional: bool,
 }
 
+/// A piece of a template literal token: literal text, or the tokens of a `${...}` expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateToken {
+    String(String),
+    Expression(Vec<SpannedToken>),
+}
+
+/// A token with the location of its source text
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    /// Byte range of the token in the source.
+    pub span: Span,
+    /// 1-based line and column (in characters) where the token starts.
+    pub line: usize,
+    pub col: usize,
+}
+
 /// Token types for the lexer
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -406,6 +767,10 @@ pub enum Token {
     Type,
     Const,
     Var,
+    Async,
+    Await,
+    Match,
+    Extern,
 
     // Control flow blocks (NEW for v2.0)
     HashIf,            // {#if ...}
@@ -415,16 +780,18 @@ pub enum Token {
     ForwardSlashFor,   // {/for}
 
     // Identifiers and literals
-    Identifier(String),
+    Identifier(Symbol),
     StringLiteral(String),
+    CharLiteral(char),
     NumberLiteral(f64),
     BooleanLiteral(bool),
-    TemplateLiteral(String),
+    TemplateLiteral(Vec<TemplateToken>),
 
     // Operators
     Plus,
     Minus,
     Star,
+    StarStar,
     Slash,
     Percent,
     Caret,
@@ -465,12 +832,180 @@ pub enum Token {
     Semicolon,
     Comma,
     Dot,
+    DotDot,
+    DotDotEqual,
+    Ellipsis,
     Colon,
     Arrow,
+    FatArrow,
     QuestionMark,
+    QuestionDot,
+    DoubleQuestion,
     DoubleColon,
     At,
 
     // Special
     EOF,
 }
+
+/// Every reserved word and the token it lexes to.
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("fn", Token::Fn),
+    ("class", Token::Class),
+    ("constructor", Token::Constructor),
+    ("extends", Token::Extends),
+    ("super", Token::Super),
+    ("this", Token::This),
+    ("new", Token::New),
+    ("component", Token::Component),
+    ("state", Token::State),
+    ("struct", Token::Struct),
+    ("enum", Token::Enum),
+    ("on", Token::On),
+    ("style", Token::Style),
+    ("if", Token::If),
+    ("then", Token::Then),
+    ("else", Token::Else),
+    ("let", Token::Let),
+    ("mut", Token::Mut),
+    ("return", Token::Return),
+    ("try", Token::Try),
+    ("catch", Token::Catch),
+    ("finally", Token::Finally),
+    ("throw", Token::Throw),
+    ("break", Token::Break),
+    ("continue", Token::Continue),
+    ("switch", Token::Switch),
+    ("case", Token::Case),
+    ("default", Token::Default),
+    ("for", Token::For),
+    ("in", Token::In),
+    ("of", Token::Of),
+    ("while", Token::While),
+    ("do", Token::Do),
+    ("loop", Token::Loop),
+    ("import", Token::Import),
+    ("export", Token::Export),
+    ("from", Token::From),
+    ("as", Token::As),
+    ("module", Token::Module),
+    ("public", Token::Public),
+    ("private", Token::Private),
+    ("protected", Token::Protected),
+    ("static", Token::Static),
+    ("abstract", Token::Abstract),
+    ("interface", Token::Interface),
+    ("type", Token::Type),
+    ("const", Token::Const),
+    ("var", Token::Var),
+    ("async", Token::Async),
+    ("await", Token::Await),
+    ("match", Token::Match),
+    ("extern", Token::Extern),
+];
+
+impl Token {
+    /// The token a reserved word lexes to, if `word` is one.
+    pub fn keyword(word: &str) -> Option<Token> {
+        KEYWORDS.iter().find(|(text, _)| *text == word).map(|(_, token)| token.clone())
+    }
+
+    /// The source text of a keyword token.
+    pub fn keyword_text(&self) -> Option<&'static str> {
+        KEYWORDS.iter().find(|(_, token)| token == self).map(|(text, _)| *text)
+    }
+}
+
+/// Writes the token as it would appear in source. String and template literals
+/// are written as their contents, since that is how markup text uses them.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(text) = self.keyword_text() {
+            return f.write_str(text);
+        }
+        let text = match self {
+            Token::Identifier(name) => return write!(f, "{}", name),
+            Token::StringLiteral(s) => return f.write_str(s),
+            Token::CharLiteral(c) => return write!(f, "{}", c),
+            Token::NumberLiteral(n) => return write!(f, "{}", n),
+            Token::BooleanLiteral(b) => return write!(f, "{}", b),
+            Token::TemplateLiteral(parts) => {
+                for part in parts {
+                    match part {
+                        TemplateToken::String(text) => f.write_str(text)?,
+                        TemplateToken::Expression(tokens) => {
+                            f.write_str("${")?;
+                            for token in tokens {
+                                write!(f, "{}", token.token)?;
+                            }
+                            f.write_str("}")?;
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            Token::HashIf => "{#if",
+            Token::HashFor => "{#for",
+            Token::HashElse => "{:else",
+            Token::ForwardSlashIf => "{/if",
+            Token::ForwardSlashFor => "{/for",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::StarStar => "**",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::Caret => "^",
+            Token::Equal => "==",
+            Token::NotEqual => "!=",
+            Token::StrictEqual => "===",
+            Token::StrictNotEqual => "!==",
+            Token::LessThan => "<",
+            Token::LessThanEqual => "<=",
+            Token::GreaterThan => ">",
+            Token::GreaterThanEqual => ">=",
+            Token::Assign => "=",
+            Token::PlusAssign => "+=",
+            Token::MinusAssign => "-=",
+            Token::StarAssign => "*=",
+            Token::SlashAssign => "/=",
+            Token::PercentAssign => "%=",
+            Token::CaretAssign => "^=",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Not => "!",
+            Token::BitwiseAnd => "&",
+            Token::BitwiseOr => "|",
+            Token::BitwiseXor => "^",
+            Token::LeftShift => "<<",
+            Token::RightShift => ">>",
+            Token::UnsignedRightShift => ">>>",
+            Token::Increment => "++",
+            Token::Decrement => "--",
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::LeftBrace => "{",
+            Token::RightBrace => "}",
+            Token::LeftBracket => "[",
+            Token::RightBracket => "]",
+            Token::Semicolon => ";",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::DotDot => "..",
+            Token::DotDotEqual => "..=",
+            Token::Ellipsis => "...",
+            Token::Colon => ":",
+            Token::Arrow => "->",
+            Token::FatArrow => "=>",
+            Token::QuestionMark => "?",
+            Token::QuestionDot => "?.",
+            Token::DoubleQuestion => "??",
+            Token::DoubleColon => "::",
+            Token::At => "@",
+            Token::EOF => "",
+            // Every other token is a keyword, handled above
+            _ => "",
+        };
+        f.write_str(text)
+    }
+}
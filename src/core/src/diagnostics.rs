@@ -0,0 +1,516 @@
+//! Diagnostics shared by every compiler phase
+//!
+//! A `Diagnostic` carries a severity, a message, labelled source spans, notes
+//! and suggested fixes. It can be rendered for the terminal, serialized as
+//! JSON, or converted to LSP positions through a `LineIndex`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A byte range in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end: end.max(start) }
+    }
+
+    /// The smallest span covering both.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// The span moved `by` bytes later.
+    pub fn offset(self, by: usize) -> Span {
+        Span::new(self.start + by, self.end + by)
+    }
+
+    /// The span relative to a text starting `base` bytes in.
+    pub fn relative_to(self, base: usize) -> Span {
+        Span::new(self.start.saturating_sub(base), self.end.saturating_sub(base))
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Stable codes for diagnostics, so tools can filter them and users can search
+/// for them. Errors start with `E` and warnings with `W`; a code is never reused.
+pub mod codes {
+    /// A character or literal the lexer cannot read.
+    pub const INVALID_TOKEN: &str = "E0001";
+    /// Tokens that do not form a valid program.
+    pub const SYNTAX_ERROR: &str = "E0002";
+    /// A `match` whose arms do not cover every value.
+    pub const NON_EXHAUSTIVE: &str = "E0004";
+    /// A refutable `let` pattern without an `else` block.
+    pub const REFUTABLE_PATTERN: &str = "E0005";
+    /// A constructor pattern with the wrong number of fields.
+    pub const PATTERN_ARITY: &str = "E0023";
+    /// A call with the wrong number of arguments.
+    pub const ARGUMENT_COUNT: &str = "E0061";
+    /// A value whose type does not fit where it is used.
+    pub const TYPE_MISMATCH: &str = "E0308";
+    /// `?` outside a function returning `Result` or `Option`, or on another type.
+    pub const INVALID_TRY: &str = "E0277";
+    /// A use of or assignment to a name that is not in scope.
+    pub const UNDECLARED_VARIABLE: &str = "E0412";
+    /// A name declared twice in the same scope.
+    pub const DUPLICATE_DEFINITION: &str = "E0428";
+    /// An unknown or misused attribute.
+    pub const INVALID_ATTRIBUTE: &str = "E0452";
+    /// A constructor pattern other than `Some`, `None`, `Ok` or `Err`.
+    pub const UNKNOWN_PATTERN: &str = "E0532";
+    /// An `extern` declaration with an unsupported ABI or signature.
+    pub const INVALID_EXTERN: &str = "E0570";
+    /// A `let ... else` block that falls through to the bindings.
+    pub const LET_ELSE_FALLTHROUGH: &str = "E0571";
+    /// An assignment to, or mutation of, a binding that is not mutable.
+    pub const IMMUTABLE_ASSIGNMENT: &str = "E0384";
+    /// A function with a return type that can end without returning a value.
+    pub const MISSING_RETURN: &str = "E0069";
+    /// An argument for a `&mut` parameter that is not a mutable variable.
+    pub const INVALID_BORROW: &str = "E0596";
+    /// A variable passed as `&mut` and as another argument of the same call.
+    pub const ALIASED_BORROW: &str = "E0499";
+    /// An event handler that is not a function.
+    pub const INVALID_HANDLER: &str = "E0618";
+    /// `await` outside an `async` function.
+    pub const AWAIT_OUTSIDE_ASYNC: &str = "E0728";
+    /// A regex literal that does not compile.
+    pub const INVALID_REGEX: &str = "E0901";
+    /// An import of a module or name that cannot be resolved.
+    pub const UNRESOLVED_IMPORT: &str = "E0432";
+    /// An import of a `private` function.
+    pub const PRIVATE_IMPORT: &str = "E0603";
+    /// A name imported twice, or imported and also defined in the same file.
+    pub const DUPLICATE_IMPORT: &str = "E0252";
+    /// Modules that import each other.
+    pub const IMPORT_CYCLE: &str = "E0391";
+    /// Derived values or `$:` statements that depend on each other in a cycle.
+    pub const REACTIVE_CYCLE: &str = "E0392";
+    /// An attribute on a component that is not one of its props.
+    pub const UNKNOWN_PROP: &str = "E0560";
+    /// A capitalized markup tag that is not a declared or imported component.
+    pub const UNKNOWN_COMPONENT: &str = "E0422";
+
+    /// A use of a `@deprecated` item.
+    pub const DEPRECATED: &str = "W0001";
+    /// An `else` block on an irrefutable `let` pattern.
+    pub const IRREFUTABLE_LET_ELSE: &str = "W0002";
+    /// `?.` or `??` on a value that is never `None`.
+    pub const NEVER_NONE: &str = "W0003";
+    /// A variable that is never read.
+    pub const UNUSED_VARIABLE: &str = "W0004";
+    /// A parameter that is never read.
+    pub const UNUSED_PARAMETER: &str = "W0005";
+    /// A private function that is never called.
+    pub const UNUSED_FUNCTION: &str = "W0006";
+    /// A `match` arm or `switch` case that earlier ones already cover.
+    pub const UNREACHABLE_PATTERN: &str = "W0007";
+    /// A call to an `async` function that is not awaited.
+    pub const UNAWAITED_ASYNC: &str = "W0008";
+    /// An `async` function run by `time.interval` that awaits, so its runs can overlap.
+    pub const OVERLAPPING_INTERVAL: &str = "W0009";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Severity {
+    /// The LSP `DiagnosticSeverity` value: 1 error, 2 warning, 3 information, 4 hint.
+    pub fn lsp_value(self) -> u8 {
+        match self {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+            Severity::Note => 3,
+            Severity::Help => 4,
+        }
+    }
+
+    fn ansi_color(self) -> &'static str {
+        match self {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+            Severity::Note => "\x1b[1;36m",
+            Severity::Help => "\x1b[1;32m",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A span with an explanation. The primary label marks where the problem is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// A suggested fix: replace the text at `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A stable identifier such as `E0001`, if the diagnostic has one.
+    pub code: Option<String>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Adds the primary label, marking where the problem is.
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: true });
+        self
+    }
+
+    /// Adds a secondary label pointing at related code.
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion(mut self, span: Span, replacement: impl Into<String>, message: impl Into<String>) -> Self {
+        self.suggestions.push(Suggestion {
+            span,
+            replacement: replacement.into(),
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn has_code(&self, code: &str) -> bool {
+        self.code.as_deref() == Some(code)
+    }
+
+    /// The diagnostic with every span made relative to a text starting `base`
+    /// bytes in, such as one file of several laid out one after another.
+    pub fn relative_to(mut self, base: usize) -> Self {
+        for label in &mut self.labels {
+            label.span = label.span.relative_to(base);
+        }
+        for suggestion in &mut self.suggestions {
+            suggestion.span = suggestion.span.relative_to(base);
+        }
+        self
+    }
+
+    /// The primary span, falling back to the first label.
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .or_else(|| self.labels.first())
+            .map(|label| label.span)
+    }
+
+    /// Renders the diagnostic for a terminal, quoting the labelled source lines.
+    pub fn render(&self, file_name: &str, source: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{}{}\x1b[0m", style, text)
+            } else {
+                text.to_string()
+            }
+        };
+        let index = LineIndex::new(source);
+        let mut out = String::new();
+
+        let header = match &self.code {
+            Some(code) => format!("{}[{}]", self.severity, code),
+            None => self.severity.to_string(),
+        };
+        out.push_str(&paint(self.severity.ansi_color(), &header));
+        out.push_str(&paint("\x1b[1m", &format!(": {}", self.message)));
+        out.push('\n');
+
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|label| (!label.primary, label.span.start));
+        let gutter = labels
+            .iter()
+            .map(|label| (index.line_col(label.span.start).0 + 1).to_string().len())
+            .max()
+            .unwrap_or(1);
+        let bar = paint("\x1b[1;34m", &format!("{} |", " ".repeat(gutter)));
+
+        match self.primary_span() {
+            Some(span) => {
+                let (line, col) = index.line_col(span.start);
+                out.push_str(&format!("{}{} {}:{}:{}\n", " ".repeat(gutter), paint("\x1b[1;34m", "-->"), file_name, line + 1, col + 1));
+            }
+            None => out.push_str(&format!("{}{} {}\n", " ".repeat(gutter), paint("\x1b[1;34m", "-->"), file_name)),
+        }
+
+        if !labels.is_empty() {
+            out.push_str(&bar);
+            out.push('\n');
+        }
+        for label in &labels {
+            let (line, col) = index.line_col(label.span.start);
+            let text = index.line_text(source, line);
+            let width = text.chars().count();
+            let start = col.min(width);
+            // Underline to the end of the span, or just its first line for multi-line spans.
+            let (end_line, end_col) = index.line_col(label.span.end);
+            let end = if end_line == line { end_col.min(width) } else { width };
+            let carets = (if label.primary { "^" } else { "-" }).repeat((end.saturating_sub(start)).max(1));
+            let style = if label.primary { self.severity.ansi_color() } else { "\x1b[1;34m" };
+            out.push_str(&paint("\x1b[1;34m", &format!("{:>width$} |", line + 1, width = gutter)));
+            out.push_str(&format!(" {}\n", text));
+            out.push_str(&bar);
+            out.push_str(&format!(" {}{}", " ".repeat(start), paint(style, &carets)));
+            if !label.message.is_empty() {
+                out.push_str(&format!(" {}", paint(style, &label.message)));
+            }
+            out.push('\n');
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("{} = {}: {}\n", " ".repeat(gutter), paint("\x1b[1m", "note"), note));
+        }
+        for suggestion in &self.suggestions {
+            out.push_str(&format!(
+                "{} = {}: {}: `{}`\n",
+                " ".repeat(gutter),
+                paint(Severity::Help.ansi_color(), "help"),
+                suggestion.message,
+                suggestion.replacement
+            ));
+        }
+        out
+    }
+
+    /// Serializes the diagnostic as JSON, adding 1-based line and column
+    /// numbers to every span.
+    pub fn to_json(&self, file_name: &str, source: &str) -> serde_json::Value {
+        let index = LineIndex::new(source);
+        let json = JsonDiagnostic {
+            file: file_name,
+            severity: self.severity,
+            code: self.code.as_deref(),
+            message: &self.message,
+            labels: self
+                .labels
+                .iter()
+                .map(|label| JsonLabel {
+                    span: JsonSpan::new(&index, label.span),
+                    message: &label.message,
+                    primary: label.primary,
+                })
+                .collect(),
+            notes: &self.notes,
+            suggestions: self
+                .suggestions
+                .iter()
+                .map(|suggestion| JsonSuggestion {
+                    span: JsonSpan::new(&index, suggestion.span),
+                    replacement: &suggestion.replacement,
+                    message: &suggestion.message,
+                })
+                .collect(),
+        };
+        serde_json::to_value(json).unwrap_or(serde_json::Value::Null)
+    }
+}
+
+#[derive(Serialize)]
+struct JsonSpan {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+impl JsonSpan {
+    fn new(index: &LineIndex, span: Span) -> Self {
+        let (line, column) = index.line_col(span.start);
+        let (end_line, end_column) = index.line_col(span.end);
+        JsonSpan {
+            start: span.start,
+            end: span.end,
+            line: line + 1,
+            column: column + 1,
+            end_line: end_line + 1,
+            end_column: end_column + 1,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLabel<'a> {
+    span: JsonSpan,
+    message: &'a str,
+    primary: bool,
+}
+
+#[derive(Serialize)]
+struct JsonSuggestion<'a> {
+    span: JsonSpan,
+    replacement: &'a str,
+    message: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    file: &'a str,
+    severity: Severity,
+    code: Option<&'a str>,
+    message: &'a str,
+    labels: Vec<JsonLabel<'a>>,
+    notes: &'a [String],
+    suggestions: Vec<JsonSuggestion<'a>>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Unspanned errors from code that still reports plain strings.
+impl From<String> for Diagnostic {
+    fn from(message: String) -> Self {
+        Diagnostic::error(message)
+    }
+}
+
+impl From<&str> for Diagnostic {
+    fn from(message: &str) -> Self {
+        Diagnostic::error(message)
+    }
+}
+
+/// Maps byte offsets to zero-based lines and columns.
+pub struct LineIndex {
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    source: String,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        LineIndex {
+            line_starts,
+            source: source.to_string(),
+        }
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+
+    /// The slice of the line before `offset`, clamped to a char boundary.
+    fn prefix(&self, offset: usize) -> (usize, &str) {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_of(offset);
+        (line, &self.source[self.line_starts[line]..offset])
+    }
+
+    /// Zero-based line and column, counting columns in characters.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let (line, prefix) = self.prefix(offset);
+        (line, prefix.chars().count())
+    }
+
+    /// Zero-based line and column, counting columns in UTF-16 code units as LSP expects.
+    pub fn line_col_utf16(&self, offset: usize) -> (u32, u32) {
+        let (line, prefix) = self.prefix(offset);
+        (line as u32, prefix.encode_utf16().count() as u32)
+    }
+
+    /// The text of a line without its line break.
+    pub fn line_text<'a>(&self, source: &'a str, line: usize) -> &'a str {
+        let start = self.line_starts.get(line).copied().unwrap_or(source.len()).min(source.len());
+        let end = self.line_starts.get(line + 1).map(|next| next - 1).unwrap_or(source.len()).min(source.len());
+        source[start..end.max(start)].trim_end_matches('\r')
+    }
+}
+
+/// Renders several diagnostics, separated by blank lines.
+pub fn render_all(diagnostics: &[Diagnostic], file_name: &str, source: &str, color: bool) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(file_name, source, color))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,1071 @@
+//! Typed high-level IR: the program as semantic analysis understands it
+//!
+//! The HIR has the shape of the AST, but every expression carries its `Type`
+//! and every name refers to its definition by `DefId`, so the IR generator
+//! never works out what a name means from its spelling. Types are inferred
+//! bottom-up from literals, annotations and signatures; an expression whose
+//! type cannot be inferred has type `any`.
+//!
+//! Conditions such as `x.is_some()` and `match` arms narrow `Option` and
+//! `Result` variables: where `x` is known to be `Some`, `x.name` reads the
+//! property of the value inside, through an explicit `Unwrap`.
+
+use crate::ast::*;
+use crate::diagnostics::Span;
+use crate::profiling;
+use crate::semantic::{assigned, function_type, narrowings, narrows_after, pattern_variant, variant_type, GLOBALS, STD_MODULES};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Index of a definition in `Hir::defs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DefId(u32);
+
+impl DefId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// What a name is defined as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    /// A top-level or component function, or a class method.
+    Function,
+    Extern,
+    Component,
+    /// A runtime global, standard library module or imported item, or a name
+    /// with no declaration, which analysis reports as an error.
+    Global,
+    Param,
+    /// A `let` or `mut` variable, derived value, loop variable or pattern binding.
+    Local,
+    State,
+}
+
+/// A definition of a name.
+#[derive(Debug, Clone)]
+pub struct Def {
+    pub name: Symbol,
+    pub kind: DefKind,
+    pub ty: Type,
+    pub span: Span,
+}
+
+/// A whole program after semantic analysis.
+#[derive(Debug)]
+pub struct Hir {
+    /// Every definition, indexed by `DefId`.
+    pub defs: Vec<Def>,
+    /// Each use of a name and the definition it refers to. The span is the
+    /// name's, or for an assignment the whole statement's.
+    pub references: Vec<(Span, DefId)>,
+    pub functions: Vec<HirFunction>,
+    pub components: Vec<HirComponent>,
+    pub classes: Vec<HirClass>,
+    pub externs: Vec<ExternFunction>,
+}
+
+impl Hir {
+    pub fn def(&self, id: DefId) -> &Def {
+        &self.defs[id.index()]
+    }
+
+    pub fn name(&self, id: DefId) -> Symbol {
+        self.def(id).name
+    }
+
+    /// Every definition with its id.
+    pub fn definitions(&self) -> impl Iterator<Item = (DefId, &Def)> {
+        self.defs.iter().enumerate().map(|(i, def)| (DefId(i as u32), def))
+    }
+}
+
+#[derive(Debug)]
+pub struct HirFunction {
+    pub def: DefId,
+    pub params: Vec<HirParam>,
+    pub return_type: Option<Type>,
+    pub body: Vec<HirStmt>,
+    pub is_async: bool,
+    pub attributes: Vec<Attribute>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct HirParam {
+    /// For a destructured parameter, a definition named after the pattern's text.
+    pub def: DefId,
+    pub type_annotation: Option<Type>,
+    pub default_value: Option<HirExpr>,
+    pub pattern: Option<HirPattern>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct HirComponent {
+    pub def: DefId,
+    pub state_vars: Vec<HirVar>,
+    pub let_vars: Vec<HirVar>,
+    pub reactive: Vec<HirStmt>,
+    pub functions: Vec<HirFunction>,
+    pub markup: Vec<HirMarkup>,
+    pub span: Span,
+}
+
+/// A component's state variable or derived value.
+#[derive(Debug)]
+pub struct HirVar {
+    pub def: DefId,
+    pub value: HirExpr,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct HirClass {
+    pub name: Symbol,
+    pub methods: Vec<HirFunction>,
+    pub constructor: Option<HirFunction>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum HirMarkup {
+    Element {
+        tag: Symbol,
+        /// Sorted by name.
+        attributes: Vec<(Symbol, HirExpr)>,
+        children: Vec<HirMarkup>,
+        span: Span,
+    },
+    Text(HirExpr),
+    IfBlock {
+        condition: HirExpr,
+        then_branch: Vec<HirMarkup>,
+        else_branch: Option<Vec<HirMarkup>>,
+        span: Span,
+    },
+    ForLoop {
+        iterator: DefId,
+        iterable: HirExpr,
+        body: Vec<HirMarkup>,
+        span: Span,
+    },
+}
+
+#[derive(Debug)]
+pub struct HirStmt {
+    pub kind: HirStmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum HirStmtKind {
+    Expr(HirExpr),
+    Assign { target: DefId, value: HirExpr },
+    If { condition: HirExpr, then: Vec<HirStmt>, else_: Option<Vec<HirStmt>> },
+    Loop {
+        init: Option<Box<HirStmt>>,
+        condition: Option<HirExpr>,
+        update: Option<Box<HirStmt>>,
+        body: Vec<HirStmt>,
+    },
+    ForIn { variable: DefId, iterable: HirExpr, body: Vec<HirStmt> },
+    ForOf { variable: DefId, iterable: HirExpr, body: Vec<HirStmt> },
+    Return(Option<HirExpr>),
+    StateVarDecl { def: DefId, value: HirExpr },
+    LetVarDecl { def: DefId, value: HirExpr },
+    Destructure { pattern: HirPattern, value: HirExpr, else_branch: Option<Vec<HirStmt>> },
+    Block(Vec<HirStmt>),
+    Try { body: Vec<HirStmt>, catch: Option<HirCatch>, finally: Option<Vec<HirStmt>> },
+    Throw(HirExpr),
+    Break(Option<Symbol>),
+    Continue(Option<Symbol>),
+    Switch { expression: HirExpr, cases: Vec<HirSwitchCase>, default: Option<Vec<HirStmt>> },
+    Reactive { name: DefId, expr: HirExpr },
+    Comprehension { target: DefId, iter: HirExpr, filter: Option<HirExpr>, expr: HirExpr },
+}
+
+#[derive(Debug)]
+pub struct HirCatch {
+    pub error_var: DefId,
+    pub body: Vec<HirStmt>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct HirSwitchCase {
+    pub value: HirExpr,
+    pub body: Vec<HirStmt>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct HirExpr {
+    pub kind: HirExprKind,
+    pub ty: Type,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum HirExprKind {
+    StringLiteral(String),
+    CharLiteral(char),
+    NumberLiteral(f64),
+    BooleanLiteral(bool),
+    NullLiteral,
+    UndefinedLiteral,
+    ArrayLiteral(Vec<HirExpr>),
+    ObjectLiteral(Vec<HirProperty>),
+    /// A name, resolved to its definition.
+    Name(DefId),
+    CellAccess(Symbol),
+    BinaryOp { left: Box<HirExpr>, op: BinaryOp, right: Box<HirExpr> },
+    UnaryOp { op: UnaryOp, operand: Box<HirExpr> },
+    Call { func: Box<HirExpr>, args: Vec<HirExpr> },
+    MethodCall { object: Box<HirExpr>, method: Symbol, args: Vec<HirExpr> },
+    If { condition: Box<HirExpr>, then: Box<HirExpr>, else_: Box<HirExpr> },
+    Concat { left: Box<HirExpr>, right: Box<HirExpr> },
+    PropertyAccess { object: Box<HirExpr>, property: Symbol },
+    ArrayAccess { array: Box<HirExpr>, index: Box<HirExpr> },
+    TemplateLiteral { parts: Vec<HirTemplatePart> },
+    ArrowFunction { params: Vec<HirParam>, body: Vec<HirStmt> },
+    New { class: Box<HirExpr>, args: Vec<HirExpr> },
+    /// `value as T`; the expression's type is `T`.
+    TypeAssert { value: Box<HirExpr> },
+    /// The value inside `value`, which narrowing proved is the `variant` (`Some`, `Ok` or `Err`).
+    Unwrap { value: Box<HirExpr>, variant: Symbol },
+    Await(Box<HirExpr>),
+    Try(Box<HirExpr>),
+    OptionalChain { object: Box<HirExpr>, property: Symbol, args: Option<Vec<HirExpr>> },
+    Coalesce { value: Box<HirExpr>, default: Box<HirExpr> },
+    Comprehension { target: DefId, iter: Box<HirExpr>, filter: Option<Box<HirExpr>>, expr: Box<HirExpr> },
+    Match { scrutinee: Box<HirExpr>, arms: Vec<HirMatchArm> },
+}
+
+#[derive(Debug)]
+pub struct HirProperty {
+    pub key: HirPropertyKey,
+    pub value: HirExpr,
+    pub shorthand: bool,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum HirPropertyKey {
+    Name(Symbol),
+    Computed(HirExpr),
+}
+
+#[derive(Debug)]
+pub enum HirTemplatePart {
+    String(String),
+    Expression(HirExpr),
+}
+
+#[derive(Debug)]
+pub struct HirMatchArm {
+    pub pattern: HirPattern,
+    pub guard: Option<HirExpr>,
+    pub body: HirExpr,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct HirPattern {
+    pub kind: HirPatternKind,
+    pub span: Span,
+}
+
+/// A name bound by a pattern, with the definition it introduces.
+#[derive(Debug, Clone, Copy)]
+pub struct HirBinding {
+    pub def: DefId,
+    pub name: Symbol,
+}
+
+#[derive(Debug)]
+pub enum HirPatternKind {
+    Wildcard,
+    Binding(HirBinding),
+    Number(f64),
+    String(String),
+    Char(char),
+    Boolean(bool),
+    Constructor { name: Symbol, args: Vec<HirPattern> },
+    Object { fields: Vec<(Symbol, HirPattern)> },
+    Array { elements: Vec<HirPattern>, rest: Option<HirBinding> },
+}
+
+impl HirPattern {
+    /// Names bound by this pattern, in source order.
+    pub fn bindings(&self) -> Vec<HirBinding> {
+        let mut bindings = Vec::new();
+        self.collect_bindings(&mut bindings);
+        bindings
+    }
+
+    fn collect_bindings(&self, bindings: &mut Vec<HirBinding>) {
+        match &self.kind {
+            HirPatternKind::Binding(binding) => bindings.push(*binding),
+            HirPatternKind::Constructor { args: patterns, .. } | HirPatternKind::Array { elements: patterns, .. } => {
+                for pattern in patterns {
+                    pattern.collect_bindings(bindings);
+                }
+            }
+            HirPatternKind::Object { fields } => {
+                for (_, field) in fields {
+                    field.collect_bindings(bindings);
+                }
+            }
+            _ => {}
+        }
+        if let HirPatternKind::Array { rest: Some(rest), .. } = &self.kind {
+            bindings.push(*rest);
+        }
+    }
+}
+
+/// Formats a pattern the way it is written in Gigli source.
+impl fmt::Display for HirPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |patterns: &[HirPattern]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ");
+        match &self.kind {
+            HirPatternKind::Wildcard => write!(f, "_"),
+            HirPatternKind::Binding(binding) => write!(f, "{}", binding.name),
+            HirPatternKind::Number(n) => write!(f, "{}", n),
+            HirPatternKind::String(s) => write!(f, "{:?}", s),
+            HirPatternKind::Char(c) => write!(f, "{:?}", c),
+            HirPatternKind::Boolean(b) => write!(f, "{}", b),
+            HirPatternKind::Constructor { name, args } if args.is_empty() => write!(f, "{}", name),
+            HirPatternKind::Constructor { name, args } => write!(f, "{}({})", name, list(args)),
+            HirPatternKind::Object { fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(key, pattern)| match &pattern.kind {
+                        HirPatternKind::Binding(binding) if binding.name == *key => key.to_string(),
+                        _ => format!("{}: {}", key, pattern),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "{{ {} }}", fields.join(", "))
+            }
+            HirPatternKind::Array { elements, rest } => {
+                let mut items = elements.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                items.extend(rest.iter().map(|rest| format!("...{}", rest.name)));
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+/// Lowers an AST to HIR, resolving names the way `SemanticAnalyzer` scopes them.
+pub fn lower(ast: &AST) -> Hir {
+    let _timer = profiling::scope("hir lowering");
+    let mut cx = Lowerer { defs: Vec::new(), references: Vec::new(), scopes: vec![HashMap::new()], narrowed: HashMap::new() };
+    for name in GLOBALS.iter().chain(STD_MODULES) {
+        cx.define(Symbol::intern(name), DefKind::Global, global_type(name), Span::default());
+    }
+    for ext in &ast.externs {
+        cx.define(ext.name, DefKind::Extern, function_type(&ext.params, ext.return_type.as_ref()), ext.span);
+    }
+    for import in &ast.imports {
+        for item in &import.items {
+            cx.define(*item, DefKind::Global, Type::Any, import.span);
+        }
+    }
+    let function_defs: Vec<DefId> = ast
+        .functions
+        .iter()
+        .map(|f| cx.define(f.name, DefKind::Function, function_type(&f.params, f.return_type.as_ref()), f.span))
+        .collect();
+    let component_defs: Vec<DefId> = ast
+        .components
+        .iter()
+        .map(|c| cx.define(c.name, DefKind::Component, Type::Custom(c.name), c.span))
+        .collect();
+
+    let functions = ast.functions.iter().zip(function_defs).map(|(f, def)| cx.function(f, def)).collect();
+    let components = ast.components.iter().zip(component_defs).map(|(c, def)| cx.component(c, def)).collect();
+    let classes = ast.classes.iter().map(|class| cx.class(class)).collect();
+    Hir { defs: cx.defs, references: cx.references, functions, components, classes, externs: ast.externs.clone() }
+}
+
+struct Lowerer {
+    defs: Vec<Def>,
+    references: Vec<(Span, DefId)>,
+    /// Innermost last; the first holds globals and top-level items.
+    scopes: Vec<HashMap<Symbol, DefId>>,
+    /// The variants that the conditions around the code being lowered prove variables have.
+    narrowed: HashMap<DefId, &'static str>,
+}
+
+/// Methods of `Option` and `Result` themselves, which apply to a narrowed
+/// variable rather than to the value inside it.
+const VARIANT_METHODS: &[&str] = &[
+    "and", "and_then", "err", "expect", "inspect", "inspect_err", "is_err", "is_none", "is_ok", "is_some", "map", "map_err", "ok",
+    "ok_or", "ok_or_else", "or", "or_else", "unwrap", "unwrap_err", "unwrap_or", "unwrap_or_else",
+];
+
+impl Lowerer {
+    fn define(&mut self, name: Symbol, kind: DefKind, ty: Type, span: Span) -> DefId {
+        let id = DefId(self.defs.len() as u32);
+        self.defs.push(Def { name, kind, ty, span });
+        self.scopes.last_mut().expect("the global scope is never popped").insert(name, id);
+        id
+    }
+
+    /// The definition `name` refers to. A name without one becomes a global,
+    /// so that uses of the same undeclared name share a definition.
+    fn resolve(&mut self, name: Symbol, span: Span) -> DefId {
+        let id = match self.lookup(name) {
+            Some(id) => id,
+            None => {
+                let id = DefId(self.defs.len() as u32);
+                self.defs.push(Def { name, kind: DefKind::Global, ty: Type::Any, span });
+                self.scopes[0].insert(name, id);
+                id
+            }
+        };
+        self.references.push((span, id));
+        id
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<DefId> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name)).copied()
+    }
+
+    /// Records what `condition` proves when it evaluates to `holds`.
+    fn narrow(&mut self, condition: &Expr, holds: bool) {
+        for (name, variant) in narrowings(condition, holds) {
+            if let Some(def) = self.lookup(name) {
+                self.narrowed.insert(def, variant);
+            }
+        }
+    }
+
+    /// Drops what is known about the variables `stmts` assign to.
+    fn forget(&mut self, stmts: &[Stmt]) {
+        for name in assigned(stmts) {
+            if let Some(def) = self.lookup(name) {
+                self.narrowed.remove(&def);
+            }
+        }
+    }
+
+    /// Lowers code that only runs when `condition` evaluates to `holds`.
+    fn narrowed<T>(&mut self, condition: &Expr, holds: bool, lower: impl FnOnce(&mut Self) -> T) -> T {
+        let saved = self.narrowed.clone();
+        self.narrow(condition, holds);
+        let lowered = lower(self);
+        self.narrowed = saved;
+        lowered
+    }
+
+    /// Lowers the object of a property access or method call, reading the value
+    /// inside it if it is a variable narrowed to `Some`, `Ok` or `Err`.
+    fn object(&mut self, expr: &Expr) -> Box<HirExpr> {
+        let object = self.expr(expr);
+        let HirExprKind::Name(def) = object.kind else { return Box::new(object) };
+        let Some(&variant) = self.narrowed.get(&def) else { return Box::new(object) };
+        match variant_type(&object.ty, variant) {
+            Some(ty) => {
+                let span = object.span;
+                Box::new(HirExpr { kind: HirExprKind::Unwrap { value: Box::new(object), variant: Symbol::intern(variant) }, ty, span })
+            }
+            None => Box::new(object),
+        }
+    }
+
+    fn push(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn pop(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn function(&mut self, f: &Function, def: DefId) -> HirFunction {
+        self.push();
+        let params = self.params(&f.params);
+        let body = self.block(&f.body);
+        self.pop();
+        HirFunction {
+            def,
+            params,
+            return_type: f.return_type.clone(),
+            body,
+            is_async: f.is_async,
+            attributes: f.attributes.clone(),
+            span: f.span,
+        }
+    }
+
+    /// Defines parameters, or the bindings of destructured ones, in the innermost scope.
+    fn params(&mut self, params: &[Parameter]) -> Vec<HirParam> {
+        let mut lowered = Vec::new();
+        for param in params {
+            let default_value = param.default_value.as_ref().map(|value| self.expr(value));
+            let ty = match &param.type_annotation {
+                Some(Type::Ref(inner) | Type::MutRef(inner)) => inner.as_ref().clone(),
+                Some(ty) => ty.clone(),
+                None => Type::Any,
+            };
+            let def = self.define(param.name, DefKind::Param, ty.clone(), param.span);
+            let pattern = param.pattern.as_ref().map(|pattern| self.pattern(pattern, &ty, DefKind::Param));
+            lowered.push(HirParam {
+                def,
+                type_annotation: param.type_annotation.clone(),
+                default_value,
+                pattern,
+                span: param.span,
+            });
+        }
+        lowered
+    }
+
+    fn component(&mut self, component: &ComponentNode, def: DefId) -> HirComponent {
+        self.push();
+        let function_defs: Vec<DefId> = component
+            .functions
+            .iter()
+            .map(|f| self.define(f.name, DefKind::Function, function_type(&f.params, f.return_type.as_ref()), f.span))
+            .collect();
+        let mut state_vars = Vec::new();
+        for state in &component.state_vars {
+            let value = self.expr(&state.initial_value);
+            let ty = state.type_annotation.clone().unwrap_or_else(|| value.ty.clone());
+            let def = self.define(state.name, DefKind::State, ty, state.span);
+            state_vars.push(HirVar { def, value, span: state.span });
+        }
+        // Derived values can read each other in any order, so all are defined
+        // first; those without an annotation get the type of their value after
+        let let_defs: Vec<DefId> = component
+            .let_vars
+            .iter()
+            .map(|letv| self.define(letv.name, DefKind::Local, letv.type_annotation.clone().unwrap_or(Type::Any), letv.span))
+            .collect();
+        for stmt in &component.reactive {
+            if let StmtKind::Reactive { name, .. } = &stmt.kind {
+                if !self.scopes.iter().any(|scope| scope.contains_key(name)) {
+                    self.define(*name, DefKind::Local, Type::Any, stmt.span);
+                }
+            }
+        }
+        let mut let_vars = Vec::new();
+        for (letv, def) in component.let_vars.iter().zip(let_defs) {
+            let value = self.expr(&letv.value);
+            if letv.type_annotation.is_none() {
+                self.defs[def.index()].ty = value.ty.clone();
+            }
+            let_vars.push(HirVar { def, value, span: letv.span });
+        }
+        let reactive = component.reactive.iter().map(|stmt| self.stmt(stmt)).collect();
+        let functions = component.functions.iter().zip(function_defs).map(|(f, def)| self.function(f, def)).collect();
+        let markup = component.markup.iter().map(|node| self.markup(node)).collect();
+        self.pop();
+        HirComponent { def, state_vars, let_vars, reactive, functions, markup, span: component.span }
+    }
+
+    /// Methods and the constructor are not in scope by name; they are reached through objects.
+    fn class(&mut self, class: &Class) -> HirClass {
+        let method = |cx: &mut Self, name: Symbol, params: &[Parameter], return_type: Option<&Type>, body: &[Stmt], span: Span| {
+            let def = cx.defs.len() as u32;
+            cx.defs.push(Def { name, kind: DefKind::Function, ty: function_type(params, return_type), span });
+            let function = Function {
+                name,
+                params: params.to_vec(),
+                return_type: return_type.cloned(),
+                body: body.to_vec(),
+                is_public: true,
+                is_async: false,
+                attributes: Vec::new(),
+                span,
+            };
+            cx.function(&function, DefId(def))
+        };
+        let methods = class
+            .methods
+            .iter()
+            .map(|m| method(self, m.name, &m.params, m.return_type.as_ref(), &m.body, m.span))
+            .collect();
+        let constructor = class
+            .constructor
+            .as_ref()
+            .map(|c| method(self, Symbol::intern("constructor"), &c.params, None, &c.body, c.span));
+        HirClass { name: class.name, methods, constructor, span: class.span }
+    }
+
+    fn markup(&mut self, node: &MarkupNode) -> HirMarkup {
+        match node {
+            MarkupNode::Element { tag, attributes, children, span } => {
+                let mut names: Vec<&Symbol> = attributes.keys().collect();
+                names.sort();
+                let attributes = names.into_iter().map(|name| (*name, self.expr(&attributes[name]))).collect();
+                let children = children.iter().map(|child| self.markup(child)).collect();
+                HirMarkup::Element { tag: *tag, attributes, children, span: *span }
+            }
+            MarkupNode::Text(expr) => HirMarkup::Text(self.expr(expr)),
+            MarkupNode::IfBlock(block) => HirMarkup::IfBlock {
+                condition: self.expr(&block.condition),
+                then_branch: block.then_branch.iter().map(|n| self.markup(n)).collect(),
+                else_branch: block.else_branch.as_ref().map(|nodes| nodes.iter().map(|n| self.markup(n)).collect()),
+                span: block.span,
+            },
+            MarkupNode::ForLoop(block) => {
+                let iterable = self.expr(&block.iterable);
+                self.push();
+                let iterator = self.define(block.iterator, DefKind::Local, element_type(&iterable.ty), block.span);
+                let body = block.body.iter().map(|n| self.markup(n)).collect();
+                self.pop();
+                HirMarkup::ForLoop { iterator, iterable, body, span: block.span }
+            }
+        }
+    }
+
+    /// Lowers `stmts` in a new scope, so their definitions end with the block,
+    /// as does what is learned about variables from the conditions in it.
+    fn block(&mut self, stmts: &[Stmt]) -> Vec<HirStmt> {
+        self.push();
+        let saved = self.narrowed.clone();
+        let mut lowered = Vec::new();
+        for stmt in stmts {
+            // A loop body runs again after its own assignments
+            if matches!(stmt.kind, StmtKind::Loop { .. } | StmtKind::ForIn { .. } | StmtKind::ForOf { .. }) {
+                self.forget(std::slice::from_ref(stmt));
+            }
+            lowered.push(self.stmt(stmt));
+            self.forget(std::slice::from_ref(stmt));
+            // After `if (x.is_none()) { return; }`, `x` is `Some`
+            if let Some((condition, holds)) = narrows_after(stmt) {
+                self.narrow(condition, holds);
+            }
+        }
+        self.narrowed = saved;
+        self.pop();
+        lowered
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> HirStmt {
+        let kind = match &stmt.kind {
+            StmtKind::Expr(expr) => HirStmtKind::Expr(self.expr(expr)),
+            StmtKind::Throw(expr) => HirStmtKind::Throw(self.expr(expr)),
+            StmtKind::Return(value) => HirStmtKind::Return(value.as_ref().map(|value| self.expr(value))),
+            StmtKind::Assign { target, value } => {
+                let value = self.expr(value);
+                HirStmtKind::Assign { target: self.resolve(*target, stmt.span), value }
+            }
+            StmtKind::StateVarDecl(state) => {
+                let value = self.expr(&state.initial_value);
+                let ty = state.type_annotation.clone().unwrap_or_else(|| value.ty.clone());
+                HirStmtKind::StateVarDecl { def: self.define(state.name, DefKind::State, ty, state.span), value }
+            }
+            // The value is lowered first, so `let x = x + 1;` refers to an outer `x`
+            StmtKind::LetVarDecl(letv) => {
+                let value = self.expr(&letv.value);
+                let ty = letv.type_annotation.clone().unwrap_or_else(|| value.ty.clone());
+                HirStmtKind::LetVarDecl { def: self.define(letv.name, DefKind::Local, ty, letv.span), value }
+            }
+            StmtKind::Destructure { pattern, type_annotation, value, else_branch, .. } => {
+                let value = self.expr(value);
+                // The bindings are not in scope in the `else` block
+                let else_branch = else_branch.as_ref().map(|body| self.block(body));
+                let ty = type_annotation.clone().unwrap_or_else(|| value.ty.clone());
+                let pattern = self.pattern(pattern, &ty, DefKind::Local);
+                HirStmtKind::Destructure { pattern, value, else_branch }
+            }
+            StmtKind::Block(stmts) => HirStmtKind::Block(self.block(stmts)),
+            StmtKind::If { condition: ast_condition, then, else_ } => HirStmtKind::If {
+                condition: self.expr(ast_condition),
+                then: self.narrowed(ast_condition, true, |cx| cx.block(then)),
+                else_: else_.as_ref().map(|stmts| self.narrowed(ast_condition, false, |cx| cx.block(stmts))),
+            },
+            StmtKind::Loop { init, condition, update, body } => {
+                // The initializer's variables are scoped to the loop
+                self.push();
+                let init = init.as_ref().map(|init| Box::new(self.stmt(init)));
+                let condition = condition.as_ref().map(|condition| self.expr(condition));
+                let body = self.block(body);
+                let update = update.as_ref().map(|update| Box::new(self.stmt(update)));
+                self.pop();
+                HirStmtKind::Loop { init, condition, update, body }
+            }
+            StmtKind::ForIn { variable, iterable, body } | StmtKind::ForOf { variable, iterable, body } => {
+                let iterable = self.expr(iterable);
+                self.push();
+                let variable = self.define(*variable, DefKind::Local, element_type(&iterable.ty), stmt.span);
+                let body = self.block(body);
+                self.pop();
+                match &stmt.kind {
+                    StmtKind::ForIn { .. } => HirStmtKind::ForIn { variable, iterable, body },
+                    _ => HirStmtKind::ForOf { variable, iterable, body },
+                }
+            }
+            StmtKind::Try { body, catch, finally } => {
+                let body = self.block(body);
+                let catch = catch.as_ref().map(|catch| {
+                    self.push();
+                    let error_var = self.define(catch.error_var, DefKind::Local, Type::Any, catch.span);
+                    let body = self.block(&catch.body);
+                    self.pop();
+                    HirCatch { error_var, body, span: catch.span }
+                });
+                let finally = finally.as_ref().map(|stmts| self.block(stmts));
+                HirStmtKind::Try { body, catch, finally }
+            }
+            StmtKind::Break(label) => HirStmtKind::Break(*label),
+            StmtKind::Continue(label) => HirStmtKind::Continue(*label),
+            StmtKind::Switch { expression, cases, default } => HirStmtKind::Switch {
+                expression: self.expr(expression),
+                cases: cases
+                    .iter()
+                    .map(|case| HirSwitchCase { value: self.expr(&case.value), body: self.block(&case.body), span: case.span })
+                    .collect(),
+                default: default.as_ref().map(|stmts| self.block(stmts)),
+            },
+            StmtKind::Reactive { name, expr } => {
+                let expr = self.expr(expr);
+                HirStmtKind::Reactive { name: self.resolve(*name, stmt.span), expr }
+            }
+            StmtKind::Comprehension { target, iter, filter, expr } => {
+                let (target, iter, filter, expr) = self.comprehension(*target, iter, filter.as_ref(), expr);
+                HirStmtKind::Comprehension { target, iter, filter, expr }
+            }
+        };
+        HirStmt { kind, span: stmt.span }
+    }
+
+    fn comprehension(&mut self, target: Symbol, iter: &Expr, filter: Option<&Expr>, expr: &Expr) -> (DefId, HirExpr, Option<HirExpr>, HirExpr) {
+        let iter = self.expr(iter);
+        self.push();
+        let target = self.define(target, DefKind::Local, element_type(&iter.ty), iter.span);
+        let filter = filter.map(|filter| self.expr(filter));
+        let expr = self.expr(expr);
+        self.pop();
+        (target, iter, filter, expr)
+    }
+
+    /// Lowers a pattern matched against a value of type `ty`, defining its bindings.
+    fn pattern(&mut self, pattern: &Pattern, ty: &Type, kind: DefKind) -> HirPattern {
+        let lowered = match &pattern.kind {
+            PatternKind::Wildcard => HirPatternKind::Wildcard,
+            PatternKind::Binding(name) => {
+                HirPatternKind::Binding(HirBinding { def: self.define(*name, kind, ty.clone(), pattern.span), name: *name })
+            }
+            PatternKind::Number(n) => HirPatternKind::Number(*n),
+            PatternKind::String(s) => HirPatternKind::String(s.clone()),
+            PatternKind::Char(c) => HirPatternKind::Char(*c),
+            PatternKind::Boolean(b) => HirPatternKind::Boolean(*b),
+            PatternKind::Constructor { name, args } => {
+                let inner = variant_type(ty, name.as_str()).unwrap_or(Type::Any);
+                HirPatternKind::Constructor { name: *name, args: args.iter().map(|arg| self.pattern(arg, &inner, kind)).collect() }
+            }
+            PatternKind::Object { fields } => HirPatternKind::Object {
+                fields: fields
+                    .iter()
+                    .map(|(key, field)| (*key, self.pattern(field, &property_type(ty, *key), kind)))
+                    .collect(),
+            },
+            PatternKind::Array { elements, rest } => {
+                let element = element_type(ty);
+                let elements = elements.iter().map(|p| self.pattern(p, &element, kind)).collect();
+                let rest = rest.map(|name| HirBinding {
+                    def: self.define(name, kind, Type::Array(Box::new(element.clone())), pattern.span),
+                    name,
+                });
+                HirPatternKind::Array { elements, rest }
+            }
+        };
+        HirPattern { kind: lowered, span: pattern.span }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Vec<HirExpr> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn boxed(&mut self, expr: &Expr) -> Box<HirExpr> {
+        Box::new(self.expr(expr))
+    }
+
+    fn expr(&mut self, expr: &Expr) -> HirExpr {
+        let (kind, ty) = match &expr.kind {
+            ExprKind::StringLiteral(s) => (HirExprKind::StringLiteral(s.clone()), Type::String),
+            ExprKind::CharLiteral(c) => (HirExprKind::CharLiteral(*c), Type::Char),
+            ExprKind::NumberLiteral(n) => (HirExprKind::NumberLiteral(*n), Type::Number),
+            ExprKind::BooleanLiteral(b) => (HirExprKind::BooleanLiteral(*b), Type::Boolean),
+            ExprKind::NullLiteral => (HirExprKind::NullLiteral, Type::Any),
+            ExprKind::UndefinedLiteral => (HirExprKind::UndefinedLiteral, Type::Any),
+            ExprKind::ArrayLiteral(items) => {
+                let items = self.exprs(items);
+                let ty = Type::Array(Box::new(common_type(items.iter().map(|item| &item.ty))));
+                (HirExprKind::ArrayLiteral(items), ty)
+            }
+            ExprKind::ObjectLiteral(props) => {
+                let props: Vec<HirProperty> = props
+                    .iter()
+                    .map(|prop| HirProperty {
+                        key: match &prop.key {
+                            PropertyKey::Name(name) => HirPropertyKey::Name(*name),
+                            PropertyKey::Computed(key) => HirPropertyKey::Computed(self.expr(key)),
+                        },
+                        value: self.expr(&prop.value),
+                        shorthand: prop.shorthand,
+                        span: prop.span,
+                    })
+                    .collect();
+                let fields: Option<Vec<ObjectTypeProperty>> = props
+                    .iter()
+                    .map(|prop| match prop.key {
+                        HirPropertyKey::Name(name) => Some(ObjectTypeProperty { name, type_: prop.value.ty.clone(), optional: false }),
+                        HirPropertyKey::Computed(_) => None,
+                    })
+                    .collect();
+                (HirExprKind::ObjectLiteral(props), fields.map_or(Type::Any, Type::Object))
+            }
+            ExprKind::Identifier(name) => {
+                let def = self.resolve(*name, expr.span);
+                (HirExprKind::Name(def), self.defs[def.index()].ty.clone())
+            }
+            ExprKind::CellAccess(name) => (HirExprKind::CellAccess(*name), Type::Any),
+            // The right side of `a && b` only runs when `a` holds, and of `a || b` when it does not
+            ExprKind::BinaryOp { left: ast_left, op: op @ (BinaryOp::And | BinaryOp::Or), right } => {
+                let left = self.boxed(ast_left);
+                let right = self.narrowed(ast_left, *op == BinaryOp::And, |cx| cx.boxed(right));
+                (HirExprKind::BinaryOp { left, op: op.clone(), right }, Type::Boolean)
+            }
+            ExprKind::BinaryOp { left, op, right } => {
+                let (left, right) = (self.boxed(left), self.boxed(right));
+                let ty = binary_type(op, &left.ty, &right.ty);
+                (HirExprKind::BinaryOp { left, op: op.clone(), right }, ty)
+            }
+            ExprKind::UnaryOp { op, operand } => {
+                let ty = match op {
+                    UnaryOp::Not | UnaryOp::Delete => Type::Boolean,
+                    UnaryOp::TypeOf => Type::String,
+                    UnaryOp::Void => Type::Void,
+                    UnaryOp::Plus | UnaryOp::Minus | UnaryOp::BitwiseNot | UnaryOp::Increment | UnaryOp::Decrement => Type::Number,
+                };
+                (HirExprKind::UnaryOp { op: op.clone(), operand: self.boxed(operand) }, ty)
+            }
+            ExprKind::Call { func, args } => {
+                let (func, args) = (self.boxed(func), self.exprs(args));
+                let ty = self.call_type(&func, &args);
+                (HirExprKind::Call { func, args }, ty)
+            }
+            ExprKind::MethodCall { object, method, args } => {
+                let object = if VARIANT_METHODS.contains(&method.as_str()) { self.boxed(object) } else { self.object(object) };
+                (HirExprKind::MethodCall { object, method: *method, args: self.exprs(args) }, Type::Any)
+            }
+            ExprKind::If { condition: ast_condition, then, else_ } => {
+                let condition = self.boxed(ast_condition);
+                let then = self.narrowed(ast_condition, true, |cx| cx.boxed(then));
+                let else_ = self.narrowed(ast_condition, false, |cx| cx.boxed(else_));
+                let ty = common_type([&then.ty, &else_.ty]);
+                (HirExprKind::If { condition, then, else_ }, ty)
+            }
+            ExprKind::Concat { left, right } => {
+                (HirExprKind::Concat { left: self.boxed(left), right: self.boxed(right) }, Type::String)
+            }
+            ExprKind::PropertyAccess { object, property } => {
+                let object = self.object(object);
+                let ty = property_type(&object.ty, *property);
+                (HirExprKind::PropertyAccess { object, property: *property }, ty)
+            }
+            ExprKind::ArrayAccess { array, index } => {
+                let (array, index) = (self.boxed(array), self.boxed(index));
+                let ty = element_type(&array.ty);
+                (HirExprKind::ArrayAccess { array, index }, ty)
+            }
+            ExprKind::TemplateLiteral { parts } => {
+                let parts = parts
+                    .iter()
+                    .map(|part| match part {
+                        TemplatePart::String(s) => HirTemplatePart::String(s.clone()),
+                        TemplatePart::Expression(e) => HirTemplatePart::Expression(self.expr(e)),
+                    })
+                    .collect();
+                (HirExprKind::TemplateLiteral { parts }, Type::String)
+            }
+            ExprKind::ArrowFunction { params: ast_params, body } => {
+                // The function may run after the variables it captures have changed
+                let saved = std::mem::take(&mut self.narrowed);
+                self.push();
+                let params = self.params(ast_params);
+                let body = self.block(body);
+                self.pop();
+                self.narrowed = saved;
+                let return_type = match body.last().map(|stmt| &stmt.kind) {
+                    Some(HirStmtKind::Return(Some(value))) => value.ty.clone(),
+                    _ => Type::Any,
+                };
+                let ty = Type::Function {
+                    params: ast_params.iter().map(|p| p.type_annotation.clone().unwrap_or(Type::Any)).collect(),
+                    return_type: Box::new(return_type),
+                };
+                (HirExprKind::ArrowFunction { params, body }, ty)
+            }
+            ExprKind::New { class, args } => {
+                let ty = match &class.kind {
+                    ExprKind::Identifier(name) => Type::Custom(*name),
+                    _ => Type::Any,
+                };
+                (HirExprKind::New { class: self.boxed(class), args: self.exprs(args) }, ty)
+            }
+            ExprKind::TypeAssert { value, type_ } => (HirExprKind::TypeAssert { value: self.boxed(value) }, type_.clone()),
+            ExprKind::Await(inner) => {
+                let inner = self.boxed(inner);
+                let ty = inner.ty.clone();
+                (HirExprKind::Await(inner), ty)
+            }
+            ExprKind::Try(inner) => {
+                let inner = self.boxed(inner);
+                let ty = match &inner.ty {
+                    Type::Result(value, _) | Type::Option(value) => value.as_ref().clone(),
+                    _ => Type::Any,
+                };
+                (HirExprKind::Try(inner), ty)
+            }
+            ExprKind::OptionalChain { object, property, args } => {
+                let object = self.boxed(object);
+                let args = args.as_ref().map(|args| self.exprs(args));
+                let ty = match (&object.ty, &args) {
+                    (Type::Option(inner), None) => Type::Option(Box::new(property_type(inner, *property))),
+                    _ => Type::Option(Box::new(Type::Any)),
+                };
+                (HirExprKind::OptionalChain { object, property: *property, args }, ty)
+            }
+            ExprKind::Coalesce { value, default } => {
+                let (value, default) = (self.boxed(value), self.boxed(default));
+                let ty = match &value.ty {
+                    Type::Option(inner) => inner.as_ref().clone(),
+                    _ => default.ty.clone(),
+                };
+                (HirExprKind::Coalesce { value, default }, ty)
+            }
+            ExprKind::Comprehension { target, iter, filter, expr: element } => {
+                let (target, iter, filter, element) = self.comprehension(*target, iter, filter.as_deref(), element);
+                let ty = Type::Array(Box::new(element.ty.clone()));
+                let kind = HirExprKind::Comprehension {
+                    target,
+                    iter: Box::new(iter),
+                    filter: filter.map(Box::new),
+                    expr: Box::new(element),
+                };
+                (kind, ty)
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee = self.boxed(scrutinee);
+                let arms: Vec<HirMatchArm> = arms
+                    .iter()
+                    .map(|arm| {
+                        // Pattern bindings are only in scope for their own arm, where the
+                        // scrutinee is known to be the variant the pattern matches
+                        self.push();
+                        let saved = self.narrowed.clone();
+                        if let (HirExprKind::Name(def), Some(variant)) = (&scrutinee.kind, pattern_variant(&arm.pattern)) {
+                            self.narrowed.insert(*def, variant);
+                        }
+                        let pattern = self.pattern(&arm.pattern, &scrutinee.ty, DefKind::Local);
+                        let guard = arm.guard.as_ref().map(|guard| self.expr(guard));
+                        let body = self.expr(&arm.body);
+                        self.narrowed = saved;
+                        self.pop();
+                        HirMatchArm { pattern, guard, body, span: arm.span }
+                    })
+                    .collect();
+                let ty = common_type(arms.iter().map(|arm| &arm.body.ty));
+                (HirExprKind::Match { scrutinee, arms }, ty)
+            }
+        };
+        HirExpr { kind, ty, span: expr.span }
+    }
+
+    /// The type of a call: what the callee's signature returns, or for the
+    /// `Some`, `Ok` and `Err` constructors, an `Option` or `Result` of the argument.
+    fn call_type(&self, func: &HirExpr, args: &[HirExpr]) -> Type {
+        if let HirExprKind::Name(def) = &func.kind {
+            let def = &self.defs[def.index()];
+            let arg = || Box::new(args.first().map_or(Type::Any, |arg| arg.ty.clone()));
+            if def.kind == DefKind::Global {
+                match def.name.as_str() {
+                    "Some" => return Type::Option(arg()),
+                    "Ok" => return Type::Result(arg(), Box::new(Type::Any)),
+                    "Err" => return Type::Result(Box::new(Type::Any), arg()),
+                    _ => {}
+                }
+            }
+        }
+        match &func.ty {
+            Type::Function { return_type, .. } => return_type.as_ref().clone(),
+            _ => Type::Any,
+        }
+    }
+}
+
+/// The types of the runtime globals the analyzer knows.
+fn global_type(name: &str) -> Type {
+    let function = |params: Vec<Type>, return_type: Type| Type::Function { params, return_type: Box::new(return_type) };
+    match name {
+        "print" => function(vec![Type::Any], Type::Void),
+        "Some" => function(vec![Type::Any], Type::Option(Box::new(Type::Any))),
+        "None" => Type::Option(Box::new(Type::Any)),
+        "Ok" | "Err" => function(vec![Type::Any], Type::Result(Box::new(Type::Any), Box::new(Type::Any))),
+        _ => Type::Any,
+    }
+}
+
+/// The type every one of `types` has, or `any` if they differ or there are none.
+fn common_type<'a>(types: impl IntoIterator<Item = &'a Type>) -> Type {
+    let mut types = types.into_iter();
+    match types.next() {
+        Some(first) if types.all(|ty| ty == first) => first.clone(),
+        _ => Type::Any,
+    }
+}
+
+/// The type of an element of a value of type `ty`, as iterated or indexed.
+fn element_type(ty: &Type) -> Type {
+    match ty {
+        Type::Array(inner) => inner.as_ref().clone(),
+        Type::String => Type::String,
+        _ => Type::Any,
+    }
+}
+
+fn property_type(ty: &Type, property: Symbol) -> Type {
+    match ty {
+        Type::Object(fields) => fields.iter().find(|field| field.name == property).map_or(Type::Any, |field| field.type_.clone()),
+        Type::Array(_) | Type::String if property == "length" => Type::Number,
+        _ => Type::Any,
+    }
+}
+
+fn binary_type(op: &BinaryOp, left: &Type, right: &Type) -> Type {
+    match op {
+        BinaryOp::Add => match (left, right) {
+            (Type::String, _) | (_, Type::String) => Type::String,
+            (Type::Number, Type::Number) => Type::Number,
+            _ => Type::Any,
+        },
+        BinaryOp::Subtract
+        | BinaryOp::Multiply
+        | BinaryOp::Divide
+        | BinaryOp::Modulo
+        | BinaryOp::Power
+        | BinaryOp::BitwiseAnd
+        | BinaryOp::BitwiseOr
+        | BinaryOp::BitwiseXor
+        | BinaryOp::LeftShift
+        | BinaryOp::RightShift
+        | BinaryOp::UnsignedRightShift => Type::Number,
+        BinaryOp::Equal
+        | BinaryOp::NotEqual
+        | BinaryOp::StrictEqual
+        | BinaryOp::StrictNotEqual
+        | BinaryOp::LessThan
+        | BinaryOp::LessThanEqual
+        | BinaryOp::GreaterThan
+        | BinaryOp::GreaterThanEqual
+        | BinaryOp::And
+        | BinaryOp::Or => Type::Boolean,
+    }
+}
@@ -0,0 +1,126 @@
+//! An on-disk cache of lowered IR modules, so a rebuild of unchanged sources
+//! can skip analysis and lowering.
+//!
+//! Each source file is hashed on its own with FNV-1a, which gives the same hash
+//! on every platform and toolchain, unlike the standard library's hasher. A
+//! program's key combines the hashes of its files with its entry file and the
+//! plugins run on it. Storing an entry evicts those stored for earlier
+//! versions of the same program, so the cache does not grow with every edit.
+//! Entries keep the warnings analysis reported, to report them again on a hit.
+//!
+//! An entry that is missing or cannot be read is a miss, never an error: the
+//! cache can always be deleted.
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::diagnostics::Diagnostic;
+use crate::profiling;
+
+use super::generator::IRModule;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continues the FNV-1a hash `hash` over `bytes`.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Hashes `parts`, each followed by a byte that never occurs in UTF-8, so that
+/// moving text from one part to the next changes the hash.
+fn hash_parts<'a>(parts: impl IntoIterator<Item = &'a str>) -> u64 {
+    parts.into_iter().fold(FNV_OFFSET_BASIS, |hash, part| fnv1a(fnv1a(hash, part.as_bytes()), &[0xff]))
+}
+
+/// A cached module and the warnings reported while analyzing its sources.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub module: IRModule,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// `CacheEntry` as stored, borrowing what it is made of.
+#[derive(Serialize)]
+struct StoredEntry<'a> {
+    module: &'a IRModule,
+    diagnostics: &'a [Diagnostic],
+}
+
+#[derive(Debug, Clone)]
+pub struct IrCache {
+    dir: PathBuf,
+}
+
+impl IrCache {
+    /// A cache keeping its entries in `dir`, which is created on the first store.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        IrCache { dir: dir.into() }
+    }
+
+    /// The hash of a source file. The compiler version is part of it, so an
+    /// upgrade never reads IR an older compiler wrote.
+    pub fn file_hash(path: &str, source: &str) -> u64 {
+        hash_parts([env!("CARGO_PKG_VERSION"), path, source])
+    }
+
+    /// The key of the program whose entry file is `entry`, made of files with
+    /// `file_hashes` (see `file_hash`) and lowered with `plugins`.
+    pub fn key<'a>(entry: &str, file_hashes: impl IntoIterator<Item = u64>, plugins: impl IntoIterator<Item = &'a str>) -> String {
+        let program = hash_parts([entry]);
+        let contents = file_hashes.into_iter().fold(hash_parts(plugins), |hash, file| fnv1a(hash, &file.to_le_bytes()));
+        format!("{:016x}-{:016x}", program, contents)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The entry stored under `key`, if there is a readable one.
+    pub fn load(&self, key: &str) -> Option<CacheEntry> {
+        let _timer = profiling::scope("ir cache load");
+        let bytes = std::fs::read(self.path(key)).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Stores `module` and the warnings analysis reported under `key`,
+    /// replacing any entry stored there before, and evicts the entries of
+    /// other versions of the same program.
+    pub fn store(&self, key: &str, module: &IRModule, diagnostics: &[Diagnostic]) -> Result<(), Diagnostic> {
+        let _timer = profiling::scope("ir cache store");
+        let failed = |e: &dyn std::fmt::Display| {
+            Diagnostic::warning(format!("Failed to write the IR cache in '{}'", self.dir.display())).with_note(e.to_string())
+        };
+        std::fs::create_dir_all(&self.dir).map_err(|e| failed(&e))?;
+        let bytes = serde_json::to_vec(&StoredEntry { module, diagnostics }).map_err(|e| failed(&e))?;
+        std::fs::write(self.path(key), bytes).map_err(|e| failed(&e))?;
+        self.evict(key);
+        Ok(())
+    }
+
+    /// Removes the entries stored for the program of `key` under other keys,
+    /// and any not named like a key. They would never be read again.
+    fn evict(&self, key: &str) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let program = key.split_once('-').map_or(key, |(program, _)| program);
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(stem) = name.to_str().and_then(|name| name.strip_suffix(".json")) else {
+                continue;
+            };
+            let stale = match stem.split_once('-') {
+                Some((other, _)) => other == program && stem != key,
+                None => true,
+            };
+            if stale {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
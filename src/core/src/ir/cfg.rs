@@ -0,0 +1,252 @@
+//! The control flow graph of a function, built over its SSA form, with the
+//! analyses passes and code generation ask of it: the edges between blocks,
+//! reachability, dominators and the registers live at each block boundary.
+use std::collections::HashSet;
+
+use crate::profiling;
+
+use super::generator::IRFunction;
+use super::ssa::{BlockId, SsaFunction, Value};
+
+/// The entry block of every function.
+pub const ENTRY: BlockId = BlockId(0);
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    function: SsaFunction,
+    /// Indexed by `BlockId`, like the rest of the tables.
+    successors: Vec<Vec<BlockId>>,
+    predecessors: Vec<Vec<BlockId>>,
+    /// The blocks reachable from the entry, each before its successors
+    /// except along back edges.
+    reverse_postorder: Vec<BlockId>,
+    /// The immediate dominator of each reachable block; the entry is its own.
+    idom: Vec<Option<BlockId>>,
+}
+
+/// The registers live on entry to and exit from each block.
+///
+/// A phi's result is defined on entry to its block, so it is never live into
+/// it. The value a phi takes from a predecessor is live out of that
+/// predecessor, and not out of its other ones.
+#[derive(Debug, Clone)]
+pub struct Liveness {
+    live_in: Vec<HashSet<Value>>,
+    live_out: Vec<HashSet<Value>>,
+}
+
+impl Cfg {
+    /// Converts `function` to SSA form and builds the graph of its blocks.
+    pub fn build(function: &IRFunction) -> Cfg {
+        Cfg::from_ssa(SsaFunction::build(function))
+    }
+
+    /// Builds the graph of a function already in SSA form.
+    pub fn from_ssa(function: SsaFunction) -> Cfg {
+        let _timer = profiling::scope("control flow graph");
+        let successors: Vec<Vec<BlockId>> = function.blocks.iter().map(|block| block.terminator.successors()).collect();
+        let predecessors = function.predecessors();
+        let reverse_postorder = reverse_postorder(&successors);
+        let idom = dominators(&predecessors, &reverse_postorder);
+        Cfg { function, successors, predecessors, reverse_postorder, idom }
+    }
+
+    pub fn function(&self) -> &SsaFunction {
+        &self.function
+    }
+
+    /// Gives back the function the graph was built over.
+    pub fn into_function(self) -> SsaFunction {
+        self.function
+    }
+
+    /// Every block, in order, reachable or not.
+    pub fn blocks(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.successors.len() as u32).map(BlockId)
+    }
+
+    pub fn successors(&self, block: BlockId) -> &[BlockId] {
+        &self.successors[block.index()]
+    }
+
+    /// The blocks that jump or branch to `block`, including unreachable ones.
+    pub fn predecessors(&self, block: BlockId) -> &[BlockId] {
+        &self.predecessors[block.index()]
+    }
+
+    /// The reachable blocks, each before its successors except where an edge
+    /// loops back, which is the order forward dataflow analyses converge in.
+    pub fn reverse_postorder(&self) -> &[BlockId] {
+        &self.reverse_postorder
+    }
+
+    /// Whether a path from the entry reaches `block`.
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        self.idom[block.index()].is_some()
+    }
+
+    /// The blocks no path from the entry reaches, in order.
+    pub fn unreachable_blocks(&self) -> Vec<BlockId> {
+        self.blocks().filter(|block| !self.is_reachable(*block)).collect()
+    }
+
+    /// The closest block other than `block` that every path from the entry to
+    /// it goes through. The entry and unreachable blocks have none.
+    pub fn immediate_dominator(&self, block: BlockId) -> Option<BlockId> {
+        self.idom[block.index()].filter(|_| block != ENTRY)
+    }
+
+    /// Whether every path from the entry to `block` goes through `dominator`.
+    /// Every reachable block dominates itself; an unreachable one is dominated
+    /// by nothing.
+    pub fn dominates(&self, dominator: BlockId, block: BlockId) -> bool {
+        if !self.is_reachable(block) {
+            return false;
+        }
+        let mut current = block;
+        loop {
+            if current == dominator {
+                return true;
+            }
+            match self.immediate_dominator(current) {
+                Some(idom) => current = idom,
+                None => return false,
+            }
+        }
+    }
+
+    /// The edges whose target dominates their source, which close loops.
+    pub fn back_edges(&self) -> Vec<(BlockId, BlockId)> {
+        self.reverse_postorder
+            .iter()
+            .flat_map(|from| self.successors(*from).iter().map(move |to| (*from, *to)))
+            .filter(|(from, to)| self.dominates(*to, *from))
+            .collect()
+    }
+
+    /// Computes which registers are live at the boundaries of each block.
+    pub fn liveness(&self) -> Liveness {
+        let _timer = profiling::scope("liveness analysis");
+        let blocks = &self.function.blocks;
+
+        // What each block reads before defining it, and what it defines
+        let mut uses = vec![HashSet::new(); blocks.len()];
+        let mut defs = vec![HashSet::new(); blocks.len()];
+        for (i, block) in blocks.iter().enumerate() {
+            // A phi's operands are read at the end of its predecessors, not here
+            defs[i].extend(block.phis.iter().map(|phi| phi.dest));
+            for instruction in &block.instructions {
+                uses[i].extend(instruction.op.operands().into_iter().filter(|value| !defs[i].contains(value)));
+                defs[i].extend(instruction.dest);
+            }
+            uses[i].extend(block.terminator.operand().filter(|value| !defs[i].contains(value)));
+        }
+
+        let mut live_in: Vec<HashSet<Value>> = uses.clone();
+        let mut live_out: Vec<HashSet<Value>> = vec![HashSet::new(); blocks.len()];
+        // Backward analysis converges fastest visiting successors first
+        let order: Vec<BlockId> = self.reverse_postorder.iter().rev().copied().collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &order {
+                let mut out = HashSet::new();
+                for successor in self.successors(*block) {
+                    out.extend(&live_in[successor.index()]);
+                    for phi in &blocks[successor.index()].phis {
+                        out.extend(phi.incoming.iter().filter(|(from, _)| from == block).map(|(_, value)| *value));
+                    }
+                }
+                let i = block.index();
+                let mut into = uses[i].clone();
+                into.extend(out.iter().filter(|value| !defs[i].contains(value)));
+                if out != live_out[i] || into != live_in[i] {
+                    live_out[i] = out;
+                    live_in[i] = into;
+                    changed = true;
+                }
+            }
+        }
+        Liveness { live_in, live_out }
+    }
+}
+
+impl Liveness {
+    /// The registers `block` or a block after it reads, that are defined
+    /// before it.
+    pub fn live_in(&self, block: BlockId) -> &HashSet<Value> {
+        &self.live_in[block.index()]
+    }
+
+    /// The registers a block after `block` reads, that are defined by the end
+    /// of it.
+    pub fn live_out(&self, block: BlockId) -> &HashSet<Value> {
+        &self.live_out[block.index()]
+    }
+
+    /// Whether `value` must be kept from the end of `block` onwards.
+    pub fn is_live_out(&self, value: Value, block: BlockId) -> bool {
+        self.live_out[block.index()].contains(&value)
+    }
+}
+
+/// Orders the blocks reachable from the entry in reverse postorder.
+fn reverse_postorder(successors: &[Vec<BlockId>]) -> Vec<BlockId> {
+    let mut visited = vec![false; successors.len()];
+    let mut postorder = Vec::with_capacity(successors.len());
+    // Each frame is a block and how many of its successors have been visited
+    let mut stack = vec![(ENTRY, 0)];
+    visited[ENTRY.index()] = true;
+    while let Some((block, next)) = stack.last_mut() {
+        match successors[block.index()].get(*next) {
+            Some(successor) => {
+                *next += 1;
+                if !std::mem::replace(&mut visited[successor.index()], true) {
+                    stack.push((*successor, 0));
+                }
+            }
+            None => {
+                postorder.push(*block);
+                stack.pop();
+            }
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+/// The immediate dominator of each block reachable from the entry, by the
+/// iterative algorithm of Cooper, Harvey and Kennedy.
+fn dominators(predecessors: &[Vec<BlockId>], reverse_postorder: &[BlockId]) -> Vec<Option<BlockId>> {
+    let mut order = vec![usize::MAX; predecessors.len()];
+    for (position, block) in reverse_postorder.iter().enumerate() {
+        order[block.index()] = position;
+    }
+    let mut idom = vec![None; predecessors.len()];
+    idom[ENTRY.index()] = Some(ENTRY);
+    let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b: BlockId| {
+        while a != b {
+            while order[a.index()] > order[b.index()] {
+                a = idom[a.index()].unwrap();
+            }
+            while order[b.index()] > order[a.index()] {
+                b = idom[b.index()].unwrap();
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in reverse_postorder.iter().skip(1) {
+            let mut processed = predecessors[block.index()].iter().filter(|pred| idom[pred.index()].is_some());
+            let Some(first) = processed.next() else { continue };
+            let new = processed.fold(*first, |new, pred| intersect(&idom, *pred, new));
+            if idom[block.index()] != Some(new) {
+                idom[block.index()] = Some(new);
+                changed = true;
+            }
+        }
+    }
+    idom
+}
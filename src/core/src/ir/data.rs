@@ -0,0 +1,157 @@
+//! The layout of a module's data segment: the strings its code uses, each
+//! stored once at an address in linear memory.
+//!
+//! Strings are laid out in the order they first appear in the module, so the
+//! same IR always gets the same layout. Each one is followed by a NUL byte
+//! that its length does not count, so its address can also be passed where a
+//! NUL-terminated string is expected.
+use std::collections::HashMap;
+
+use crate::profiling;
+
+use super::generator::{IRExpr, IRExprKind, IRModule, IRPattern, IRStmt, IRStmtKind};
+
+/// The address the data segment starts at. Address 0 is kept free, so that a
+/// null pointer is never the address of a string.
+pub const DATA_BASE: u32 = 8;
+
+/// Where a string is stored: its address and its length in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DataRef {
+    pub ptr: u32,
+    pub len: u32,
+}
+
+/// The strings of a module and the bytes of the data segment they make up.
+#[derive(Debug, Clone)]
+pub struct DataLayout {
+    base: u32,
+    bytes: Vec<u8>,
+    strings: HashMap<String, DataRef>,
+}
+
+impl DataLayout {
+    /// An empty data segment starting at `base`.
+    pub fn new(base: u32) -> Self {
+        DataLayout { base, bytes: Vec::new(), strings: HashMap::new() }
+    }
+
+    /// Lays out the string literals of `module`, including those in patterns,
+    /// in reactive graphs, and the element and event names of event bindings.
+    pub fn of(module: &IRModule) -> Self {
+        let _timer = profiling::scope("data layout");
+        let mut layout = DataLayout::default();
+        for function in &module.functions {
+            layout.intern_stmts(&function.body);
+            for node in &function.reactive {
+                layout.intern_expr(&node.expr);
+            }
+        }
+        layout
+    }
+
+    /// The address and length of `string`, adding it to the segment unless an
+    /// identical string is already there.
+    pub fn intern(&mut self, string: &str) -> DataRef {
+        if let Some(data) = self.strings.get(string) {
+            return *data;
+        }
+        let data = DataRef { ptr: self.end(), len: string.len() as u32 };
+        self.bytes.extend_from_slice(string.as_bytes());
+        self.bytes.push(0);
+        self.strings.insert(string.to_string(), data);
+        data
+    }
+
+    /// The address and length of `string`, if it has been laid out.
+    pub fn get(&self, string: &str) -> Option<DataRef> {
+        self.strings.get(string).copied()
+    }
+
+    /// The address of the first byte of the segment.
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    /// The address just past the segment, where memory that is free to
+    /// allocate begins.
+    pub fn end(&self) -> u32 {
+        self.base + self.bytes.len() as u32
+    }
+
+    /// The contents of the segment, to be placed at `base`.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The strings in the segment, in address order.
+    pub fn strings(&self) -> Vec<(&str, DataRef)> {
+        let mut strings: Vec<_> = self.strings.iter().map(|(string, data)| (string.as_str(), *data)).collect();
+        strings.sort_by_key(|(_, data)| data.ptr);
+        strings
+    }
+
+    fn intern_stmts(&mut self, stmts: &[IRStmt]) {
+        for stmt in stmts {
+            if let IRStmtKind::EventBind { target, event, .. } = &stmt.kind {
+                self.intern(target.as_str());
+                self.intern(event.as_str());
+            }
+            for expr in stmt.exprs() {
+                self.intern_expr(expr);
+            }
+            for block in stmt.blocks() {
+                self.intern_stmts(block);
+            }
+        }
+    }
+
+    fn intern_expr(&mut self, expr: &IRExpr) {
+        match &expr.kind {
+            IRExprKind::StringLiteral(string) => {
+                self.intern(string);
+            }
+            IRExprKind::Closure { body, .. } => self.intern_stmts(body),
+            IRExprKind::Match { scrutinee, arms } => {
+                self.intern_expr(scrutinee);
+                for arm in arms {
+                    self.intern_pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.intern_expr(guard);
+                    }
+                    self.intern_expr(&arm.body);
+                }
+                return;
+            }
+            _ => {}
+        }
+        for child in expr.children() {
+            self.intern_expr(child);
+        }
+    }
+
+    fn intern_pattern(&mut self, pattern: &IRPattern) {
+        match pattern {
+            IRPattern::StringLiteral(string) => {
+                self.intern(string);
+            }
+            IRPattern::Constructor { args: patterns, .. } | IRPattern::Array { elements: patterns, .. } => {
+                for pattern in patterns {
+                    self.intern_pattern(pattern);
+                }
+            }
+            IRPattern::Object { fields } => {
+                for (_, pattern) in fields {
+                    self.intern_pattern(pattern);
+                }
+            }
+            IRPattern::Wildcard | IRPattern::Binding(_) | IRPattern::NumberLiteral(_) => {}
+        }
+    }
+}
+
+impl Default for DataLayout {
+    fn default() -> Self {
+        DataLayout::new(DATA_BASE)
+    }
+}
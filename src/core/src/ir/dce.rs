@@ -0,0 +1,100 @@
+//! Dead code elimination over SSA functions.
+
+use std::collections::HashSet;
+
+use super::pass::Pass;
+use super::ssa::{BlockId, SsaFunction, Terminator, Value};
+
+/// Removes blocks no path from the entry reaches, and phis and instructions
+/// without effects whose values are never read.
+#[derive(Debug, Default)]
+pub struct DeadCodeElimination;
+
+impl Pass<SsaFunction> for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "dead code elimination"
+    }
+
+    fn run(&mut self, function: &mut SsaFunction) -> bool {
+        let removed_blocks = remove_unreachable_blocks(function);
+        let removed_values = remove_unused_values(function);
+        removed_blocks || removed_values
+    }
+}
+
+fn remove_unreachable_blocks(function: &mut SsaFunction) -> bool {
+    let mut reached = vec![false; function.blocks.len()];
+    let mut pending = vec![BlockId(0)];
+    while let Some(block) = pending.pop() {
+        if !std::mem::replace(&mut reached[block.index()], true) {
+            pending.extend(function.block(block).terminator.successors());
+        }
+    }
+    if reached.iter().all(|reached| *reached) {
+        return false;
+    }
+
+    // Number the remaining blocks in their current order
+    let mut renumbered = Vec::with_capacity(reached.len());
+    let mut next = 0;
+    for reached in &reached {
+        renumbered.push(BlockId(next));
+        next += *reached as u32;
+    }
+    let blocks = std::mem::take(&mut function.blocks);
+    for (block, _) in blocks.into_iter().zip(&reached).filter(|(_, reached)| **reached) {
+        function.blocks.push(block);
+    }
+    for block in &mut function.blocks {
+        for phi in &mut block.phis {
+            phi.incoming.retain(|(from, _)| reached[from.index()]);
+            for (from, _) in &mut phi.incoming {
+                *from = renumbered[from.index()];
+            }
+        }
+        match &mut block.terminator {
+            Terminator::Jump(target) => *target = renumbered[target.index()],
+            Terminator::Branch { then, else_, .. } => {
+                *then = renumbered[then.index()];
+                *else_ = renumbered[else_.index()];
+            }
+            Terminator::Return(_) | Terminator::Unreachable => {}
+        }
+    }
+    true
+}
+
+/// Removes unused values until only used ones remain, as removing one can
+/// leave the values it read unused.
+fn remove_unused_values(function: &mut SsaFunction) -> bool {
+    let mut changed = false;
+    loop {
+        let mut used: HashSet<Value> = HashSet::new();
+        for block in &function.blocks {
+            for phi in &block.phis {
+                // A phi that only feeds itself around a loop is not a use
+                used.extend(phi.incoming.iter().map(|(_, value)| *value).filter(|value| *value != phi.dest));
+            }
+            for instruction in &block.instructions {
+                used.extend(instruction.op.operands());
+            }
+            used.extend(block.terminator.operand());
+        }
+
+        let mut removed = false;
+        for block in &mut function.blocks {
+            let phis = block.phis.len();
+            block.phis.retain(|phi| used.contains(&phi.dest));
+            let instructions = block.instructions.len();
+            block.instructions.retain(|instruction| match instruction.dest {
+                Some(dest) => instruction.op.has_effects() || used.contains(&dest),
+                None => true,
+            });
+            removed |= block.phis.len() != phis || block.instructions.len() != instructions;
+        }
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
@@ -0,0 +1,284 @@
+//! Constant folding and propagation over IR modules.
+//!
+//! Arithmetic and comparisons on literals are evaluated, string concatenations
+//! of literals become one literal, and branches on literal conditions are
+//! replaced by the branch taken. A variable stored once, with a literal, at the
+//! top level of a function is replaced by that literal after the store, which
+//! often makes more of the function foldable.
+use std::collections::HashMap;
+
+use crate::ast::*;
+
+use super::generator::{concat, IRExpr, IRExprKind, IRFunction, IRModule, IRStmt, IRStmtKind};
+use super::pass::Pass;
+
+#[derive(Debug, Default)]
+pub struct ConstantFolding;
+
+impl Pass<IRModule> for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "constant folding"
+    }
+
+    fn run(&mut self, module: &mut IRModule) -> bool {
+        // Stores are counted across the module, as component state is stored by
+        // the component and by the functions that update it
+        let mut stores = HashMap::new();
+        for function in &module.functions {
+            count_stores(&function.body, &mut stores);
+        }
+        let mut changed = false;
+        for function in &mut module.functions {
+            changed |= propagate(function, &stores);
+            changed |= fold_stmts(&mut function.body);
+        }
+        changed
+    }
+}
+
+/// Counts the stores to each name in `stmts`, including in closures.
+fn count_stores(stmts: &[IRStmt], stores: &mut HashMap<Symbol, usize>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            IRStmtKind::Store { target, .. } | IRStmtKind::Reactive { name: target, .. } | IRStmtKind::Comprehension { target, .. } => {
+                *stores.entry(*target).or_default() += 1
+            }
+            _ => {}
+        }
+        let mut pending = stmt.exprs();
+        while let Some(expr) = pending.pop() {
+            if let IRExprKind::Closure { body, .. } = &expr.kind {
+                count_stores(body, stores);
+            }
+            pending.extend(expr.children());
+        }
+        for block in stmt.blocks() {
+            count_stores(block, stores);
+        }
+    }
+}
+
+/// Replaces the loads of variables that are only ever stored a literal, after
+/// that store.
+fn propagate(function: &mut IRFunction, stores: &HashMap<Symbol, usize>) -> bool {
+    let mut changed = false;
+    for i in 0..function.body.len() {
+        let (name, value) = match &function.body[i].kind {
+            IRStmtKind::Store { target, value }
+                if value.is_constant()
+                    && stores.get(target) == Some(&1)
+                    && !function.params.iter().any(|param| param.name == *target) =>
+            {
+                (*target, value.clone())
+            }
+            _ => continue,
+        };
+        for stmt in &mut function.body[i + 1..] {
+            changed |= substitute_stmt(stmt, name, &value);
+        }
+    }
+    changed
+}
+
+pub(crate) fn substitute_stmt(stmt: &mut IRStmt, name: Symbol, value: &IRExpr) -> bool {
+    let mut changed = false;
+    for expr in stmt.exprs_mut() {
+        changed |= substitute_expr(expr, name, value);
+    }
+    for block in stmt.blocks_mut() {
+        for stmt in block {
+            changed |= substitute_stmt(stmt, name, value);
+        }
+    }
+    changed
+}
+
+/// Replaces loads of `name` in `expr` with `value`, except where a closure
+/// parameter, match arm or comprehension binds another variable of that name.
+pub(crate) fn substitute_expr(expr: &mut IRExpr, name: Symbol, value: &IRExpr) -> bool {
+    match &mut expr.kind {
+        IRExprKind::Load(loaded) if *loaded == name => {
+            // The load's type is kept, as code generation chose its representation by it
+            expr.kind = value.kind.clone();
+            true
+        }
+        IRExprKind::Closure { params, body } => {
+            if params.iter().any(|param| param.name == name) {
+                return false;
+            }
+            let mut changed = false;
+            for stmt in body {
+                changed |= substitute_stmt(stmt, name, value);
+            }
+            changed
+        }
+        IRExprKind::Match { scrutinee, arms } => {
+            let mut changed = substitute_expr(scrutinee, name, value);
+            for arm in arms.iter_mut().filter(|arm| !arm.pattern.bindings().contains(&name)) {
+                if let Some(guard) = &mut arm.guard {
+                    changed |= substitute_expr(guard, name, value);
+                }
+                changed |= substitute_expr(&mut arm.body, name, value);
+            }
+            changed
+        }
+        IRExprKind::Comprehension { target, iter, .. } if *target == name => substitute_expr(iter, name, value),
+        _ => {
+            let mut changed = false;
+            for child in expr.children_mut() {
+                changed |= substitute_expr(child, name, value);
+            }
+            changed
+        }
+    }
+}
+
+fn fold_stmts(stmts: &mut Vec<IRStmt>) -> bool {
+    let mut changed = false;
+    let mut folded = Vec::with_capacity(stmts.len());
+    for mut stmt in stmts.drain(..) {
+        for expr in stmt.exprs_mut() {
+            changed |= fold_expr(expr);
+        }
+        for block in stmt.blocks_mut() {
+            changed |= fold_stmts(block);
+        }
+        match stmt.kind {
+            IRStmtKind::If { condition: IRExpr { kind: IRExprKind::BooleanLiteral(holds), .. }, then_block, else_block } => {
+                folded.extend(if holds { then_block } else { else_block });
+                changed = true;
+            }
+            // A loop whose condition is false from the start never runs
+            IRStmtKind::Loop { condition: IRExpr { kind: IRExprKind::BooleanLiteral(false), .. }, .. } => changed = true,
+            _ => folded.push(stmt),
+        }
+    }
+    *stmts = folded;
+    changed
+}
+
+/// Folds `expr` and the expressions in it, bottom up.
+fn fold_expr(expr: &mut IRExpr) -> bool {
+    let mut changed = false;
+    for child in expr.children_mut() {
+        changed |= fold_expr(child);
+    }
+    if let IRExprKind::Closure { body, .. } = &mut expr.kind {
+        changed |= fold_stmts(body);
+    }
+    if let Some(folded) = evaluate(expr) {
+        // What it folds to stands for the whole expression in the source
+        *expr = IRExpr { loc: expr.loc, ..folded };
+        changed = true;
+    }
+    changed
+}
+
+/// The simpler expression `expr` folds to, given that its operands are folded.
+fn evaluate(expr: &IRExpr) -> Option<IRExpr> {
+    match &expr.kind {
+        // `+` on strings joins them like a template literal does
+        IRExprKind::BinaryOp { op: BinaryOp::Add, left, right }
+            if expr.ty == Type::String && [left, right].iter().any(|side| side.is_constant() || matches!(side.kind, IRExprKind::Concat(_))) =>
+        {
+            Some(join(vec![(**left).clone(), (**right).clone()]))
+        }
+        IRExprKind::Concat(parts) => {
+            let renders = parts.iter().any(|part| part.is_constant() && !matches!(part.kind, IRExprKind::StringLiteral(_)) && render(part).is_some());
+            let joined = join(parts.clone());
+            match &joined.kind {
+                IRExprKind::Concat(merged) if !renders && merged.len() == parts.len() => None,
+                _ => Some(joined),
+            }
+        }
+        IRExprKind::BinaryOp { op, left, right } => match (op, &left.kind, &right.kind) {
+            (_, IRExprKind::NumberLiteral(a), IRExprKind::NumberLiteral(b)) => fold_numbers(op, *a, *b),
+            (BinaryOp::And, IRExprKind::BooleanLiteral(true), _) | (BinaryOp::Or, IRExprKind::BooleanLiteral(false), _) => {
+                Some((**right).clone())
+            }
+            (BinaryOp::And, IRExprKind::BooleanLiteral(false), _) | (BinaryOp::Or, IRExprKind::BooleanLiteral(true), _) => {
+                Some((**left).clone())
+            }
+            (_, IRExprKind::BooleanLiteral(a), IRExprKind::BooleanLiteral(b)) => fold_equality(op, a == b),
+            (_, IRExprKind::StringLiteral(a), IRExprKind::StringLiteral(b)) => fold_equality(op, a == b),
+            (_, IRExprKind::CharLiteral(a), IRExprKind::CharLiteral(b)) => fold_equality(op, a == b),
+            _ => None,
+        },
+        IRExprKind::UnaryOp { op, operand } => match (op, &operand.kind) {
+            (UnaryOp::Minus, IRExprKind::NumberLiteral(n)) => Some(IRExpr::number(-n)),
+            (UnaryOp::Plus, IRExprKind::NumberLiteral(n)) => Some(IRExpr::number(*n)),
+            (UnaryOp::BitwiseNot, IRExprKind::NumberLiteral(n)) => Some(IRExpr::number(!to_int32(*n) as f64)),
+            (UnaryOp::Not, IRExprKind::BooleanLiteral(b)) => Some(IRExpr::boolean(!b)),
+            _ => None,
+        },
+        IRExprKind::If { condition, then, else_ } => match condition.kind {
+            IRExprKind::BooleanLiteral(holds) => Some(if holds { (**then).clone() } else { (**else_).clone() }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Concatenates `parts`, rendering the literals among them as strings.
+fn join(parts: Vec<IRExpr>) -> IRExpr {
+    concat(parts.into_iter().map(|part| render(&part).map(IRExpr::string).unwrap_or(part)).collect())
+}
+
+/// The string a literal converts to when concatenated, where it is known:
+/// numbers are only rendered where Rust and JavaScript render them alike.
+fn render(expr: &IRExpr) -> Option<String> {
+    match &expr.kind {
+        IRExprKind::StringLiteral(s) => Some(s.clone()),
+        IRExprKind::CharLiteral(c) => Some(c.to_string()),
+        IRExprKind::BooleanLiteral(b) => Some(b.to_string()),
+        IRExprKind::NumberLiteral(n) if *n == 0.0 => Some("0".to_string()),
+        IRExprKind::NumberLiteral(n) if (1e-6..1e21).contains(&n.abs()) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Evaluates a binary operator on two numbers. Results that are not finite are
+/// left to run time, so division by zero is not hidden in a constant.
+fn fold_numbers(op: &BinaryOp, a: f64, b: f64) -> Option<IRExpr> {
+    let number = |n: f64| n.is_finite().then(|| IRExpr::number(n));
+    let shift = (to_int32(b) as u32) & 31;
+    match op {
+        BinaryOp::Add => number(a + b),
+        BinaryOp::Subtract => number(a - b),
+        BinaryOp::Multiply => number(a * b),
+        BinaryOp::Divide => number(a / b),
+        BinaryOp::Modulo => number(a % b),
+        BinaryOp::Power => number(a.powf(b)),
+        BinaryOp::Equal | BinaryOp::StrictEqual => Some(IRExpr::boolean(a == b)),
+        BinaryOp::NotEqual | BinaryOp::StrictNotEqual => Some(IRExpr::boolean(a != b)),
+        BinaryOp::LessThan => Some(IRExpr::boolean(a < b)),
+        BinaryOp::LessThanEqual => Some(IRExpr::boolean(a <= b)),
+        BinaryOp::GreaterThan => Some(IRExpr::boolean(a > b)),
+        BinaryOp::GreaterThanEqual => Some(IRExpr::boolean(a >= b)),
+        BinaryOp::BitwiseAnd => number((to_int32(a) & to_int32(b)) as f64),
+        BinaryOp::BitwiseOr => number((to_int32(a) | to_int32(b)) as f64),
+        BinaryOp::BitwiseXor => number((to_int32(a) ^ to_int32(b)) as f64),
+        BinaryOp::LeftShift => number(to_int32(a).wrapping_shl(shift) as f64),
+        BinaryOp::RightShift => number((to_int32(a) >> shift) as f64),
+        BinaryOp::UnsignedRightShift => number(((to_int32(a) as u32) >> shift) as f64),
+        BinaryOp::And | BinaryOp::Or => None,
+    }
+}
+
+/// Evaluates an equality operator on two literals of the same kind, given
+/// whether they are equal.
+fn fold_equality(op: &BinaryOp, equal: bool) -> Option<IRExpr> {
+    match op {
+        BinaryOp::Equal | BinaryOp::StrictEqual => Some(IRExpr::boolean(equal)),
+        BinaryOp::NotEqual | BinaryOp::StrictNotEqual => Some(IRExpr::boolean(!equal)),
+        _ => None,
+    }
+}
+
+/// Converts a number to a 32-bit integer as JavaScript's bitwise operators do.
+fn to_int32(n: f64) -> i32 {
+    if !n.is_finite() {
+        return 0;
+    }
+    (n.trunc() % 4_294_967_296.0) as i64 as u32 as i32
+}
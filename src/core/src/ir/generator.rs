@@ -1,569 +1,1276 @@
-//! IR generation for Gigli
+//! IR generation for Gigli, from the HIR the semantic analyzer produces
 use crate::ast::*;
+use crate::hir::*;
+use crate::profiling;
+use crate::semantic::STD_MODULES;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct IRModule {
     pub functions: Vec<IRFunction>,
+    /// Functions implemented by the host, which the compiled module imports.
+    pub externs: Vec<IRExtern>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct IRFunction {
-    pub name: String,
+    pub name: Symbol,
+    pub params: Vec<IRParam>,
+    pub return_type: Option<Type>,
+    pub is_async: bool,
+    /// Name the compiled module exports an `@export` top-level function under:
+    /// its source name, or the one given as `@export("name")`.
+    pub export_name: Option<Symbol>,
     pub body: Vec<IRStmt>,
+    /// A component's reactive dependency graph, in the order its nodes first
+    /// run; empty for other functions.
+    pub reactive: Vec<IRReactiveNode>,
+    /// The span of the declaration the function was lowered from, if any.
+    pub loc: Option<Span>,
 }
 
-#[derive(Debug)]
-pub enum IRStmt {
-    Call { func: String, args: Vec<IRExpr> },
-    Assign { target: String, value: IRExpr }, // assignment
+/// A node of a component's reactive dependency graph: a state variable, or a
+/// value or piece of the view computed from state, which only has to be
+/// recomputed when a variable it reads is written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IRReactiveNode {
+    pub kind: IRReactiveKind,
+    /// The component's variables `expr` reads, sorted by name. A state
+    /// variable reads none, as only its initial value is computed.
+    pub reads: Vec<Symbol>,
+    /// The component's variables the node assigns.
+    pub writes: Vec<Symbol>,
+    /// The initial value of a state variable; otherwise what is recomputed.
+    pub expr: IRExpr,
+}
+
+/// What a reactive node is. The parts of the view are numbered by `slot`, in
+/// the order they appear in the markup, so a backend can update one without
+/// rendering the rest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum IRReactiveKind {
+    /// A state variable, which event handlers write.
+    Cell,
+    /// A derived value (`let`).
+    Derived,
+    /// A `$:` statement.
+    Effect,
+    /// Text interpolated into the markup.
+    Text { slot: u32 },
+    /// The value of an element's attribute.
+    Attribute { slot: u32, name: Symbol },
+    /// A `{#if}` or `{#for}` block, whose HTML is rendered again as a whole.
+    Block { slot: u32 },
+}
+
+impl IRFunction {
+    /// The reactive nodes to recompute, in an order where each comes after
+    /// those it reads from, when the variables `changed` are written: those
+    /// reading them, and in turn those reading what they write.
+    pub fn affected_by(&self, changed: &[Symbol]) -> Vec<&IRReactiveNode> {
+        let mut dirty: HashSet<Symbol> = changed.iter().copied().collect();
+        let mut affected = vec![false; self.reactive.len()];
+        let mut grew = true;
+        while grew {
+            grew = false;
+            for (node, affected) in self.reactive.iter().zip(&mut affected) {
+                if !*affected && node.reads.iter().any(|name| dirty.contains(name)) {
+                    *affected = true;
+                    dirty.extend(&node.writes);
+                    grew = true;
+                }
+            }
+        }
+
+        // Derived values may read each other in any order, so nodes are taken
+        // once none of the others left writes what they read. Analysis rejects
+        // cycles, but a node in one is still taken, in graph order.
+        let mut pending: Vec<&IRReactiveNode> = self.reactive.iter().zip(affected).filter(|(_, affected)| *affected).map(|(node, _)| node).collect();
+        let mut ordered = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            let ready = (0..pending.len())
+                .find(|&i| {
+                    let node = pending[i];
+                    !pending.iter().any(|other| !std::ptr::eq(*other, node) && other.writes.iter().any(|name| node.reads.contains(name)))
+                })
+                .unwrap_or(0);
+            ordered.push(pending.remove(ready));
+        }
+        ordered
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IRParam {
+    pub name: Symbol,
+    pub type_annotation: Option<Type>,
+}
+
+/// An `extern` declaration, imported from the host module named by its ABI (`"js"`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IRExtern {
+    pub module: Symbol,
+    pub name: Symbol,
+    pub params: Vec<IRParam>,
+    pub return_type: Option<Type>,
+}
+
+/// A statement, with the span of the source it was lowered from, if any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IRStmt {
+    pub kind: IRStmtKind,
+    pub loc: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IRStmtKind {
+    Call { func: Symbol, args: Vec<IRExpr> },
+    /// Writes `value` to the variable `target`.
+    Store { target: Symbol, value: IRExpr },
+    /// Evaluates an expression for its effects, discarding the value.
+    Expr(IRExpr),
+    /// Runs `then_block` if `condition` holds, otherwise `else_block`.
+    If { condition: IRExpr, then_block: Vec<IRStmt>, else_block: Vec<IRStmt> },
+    /// Runs `body` and then `update` for as long as `condition` holds, testing it
+    /// before each iteration. `Continue` skips to `update`; `Break` leaves the loop.
+    Loop { condition: IRExpr, body: Vec<IRStmt>, update: Vec<IRStmt> },
+    /// A nested statement list, such as a `{ ... }` block or a loop with its setup.
+    Block(Vec<IRStmt>),
+    Break,
+    Continue,
     Await(IRExpr), // async/await
-    Reactive { name: String, expr: IRExpr }, // $: reactivity
-    Comprehension { target: String, iter: IRExpr, filter: Option<IRExpr>, expr: IRExpr },
+    Reactive { name: Symbol, expr: IRExpr }, // $: reactivity
+    Comprehension { target: Symbol, iter: IRExpr, filter: Option<IRExpr>, expr: IRExpr },
     Render(IRExpr), // UI render
-    EventBind { target: String, event: String, handler: String }, // event binding
+    EventBind { target: Symbol, event: Symbol, handler: Symbol }, // event binding
     DomOp { op: String, args: Vec<IRExpr> }, // DOM operation
     Return(Option<IRExpr>),
     // ... add more as needed ...
 }
 
-#[derive(Debug)]
-pub enum IRExpr {
+/// An expression, with the type analysis gave it (`any` where it could not tell)
+/// and the span of the source it was lowered from, if any.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IRExpr {
+    pub kind: IRExprKind,
+    pub ty: Type,
+    pub loc: Option<Span>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IRExprKind {
     StringLiteral(String),
     NumberLiteral(f64),
-    Identifier(String),
+    BooleanLiteral(bool),
+    CharLiteral(char),
+    /// `null` or `undefined`.
+    Null,
+    /// Reads a variable, parameter or function by name.
+    Load(Symbol),
+    BinaryOp { op: BinaryOp, left: Box<IRExpr>, right: Box<IRExpr> },
+    UnaryOp { op: UnaryOp, operand: Box<IRExpr> },
+    /// The parts, each converted to a string, joined into one string.
+    Concat(Vec<IRExpr>),
+    FieldAccess { object: Box<IRExpr>, field: Symbol },
+    Index { object: Box<IRExpr>, index: Box<IRExpr> },
+    /// `then` or `else_`, whichever `condition` selects; only that one is evaluated.
+    If { condition: Box<IRExpr>, then: Box<IRExpr>, else_: Box<IRExpr> },
     Await(Box<IRExpr>),
     Option(Box<IRExpr>),
     Result { ok: Box<IRExpr>, err: Box<IRExpr> },
     List(Vec<IRExpr>), // NEW: List<T>
     Map(Vec<(IRExpr, IRExpr)>), // NEW: Map<K, V>
-    StdCall { module: String, func: String, args: Vec<IRExpr> }, // NEW: stdlib or external call
-    Comprehension { target: String, iter: Box<IRExpr>, filter: Option<Box<IRExpr>>, expr: Box<IRExpr> },
-    DomRef(String), // reference to DOM node
+    StdCall { module: Symbol, func: Symbol, args: Vec<IRExpr> }, // NEW: stdlib or external call
+    Call { func: Symbol, args: Vec<IRExpr> }, // call to a named function or extern
+    /// A call to a function value, such as a closure held in a variable.
+    CallIndirect { callee: Box<IRExpr>, args: Vec<IRExpr> },
+    MethodCall { object: Box<IRExpr>, method: Symbol, args: Vec<IRExpr> },
+    /// An arrow function; it reads the variables around it by name.
+    Closure { params: Vec<IRParam>, body: Vec<IRStmt> },
+    /// A closure lambda lifting made the module function `func`, which takes an
+    /// environment object with a field for each of `captures` before the
+    /// closure's own parameters.
+    LiftedClosure { func: Symbol, captures: Vec<(Symbol, IRExpr)> },
+    /// `value as T`, where `T` is the expression's type.
+    Cast(Box<IRExpr>),
+    Comprehension { target: Symbol, iter: Box<IRExpr>, filter: Option<Box<IRExpr>>, expr: Box<IRExpr> },
+    DomRef(Symbol), // reference to DOM node
+    Match { scrutinee: Box<IRExpr>, arms: Vec<IRMatchArm> }, // arms are tried in order
+    Try(Box<IRExpr>), // the Ok/Some value, or return the Err/None from the function
     // ... add more as needed ...
 }
 
-pub fn generate_ir(ast: &AST) -> IRModule {
+impl IRExpr {
+    pub fn new(kind: IRExprKind, ty: Type) -> Self {
+        IRExpr { kind, ty, loc: None }
+    }
+
+    /// This expression, as lowered from the source at `loc`.
+    pub fn at(self, loc: Span) -> Self {
+        IRExpr { loc: Some(loc), ..self }
+    }
+
+    pub fn string(value: impl Into<String>) -> Self {
+        IRExpr::new(IRExprKind::StringLiteral(value.into()), Type::String)
+    }
+
+    pub fn number(value: f64) -> Self {
+        IRExpr::new(IRExprKind::NumberLiteral(value), Type::Number)
+    }
+
+    pub fn boolean(value: bool) -> Self {
+        IRExpr::new(IRExprKind::BooleanLiteral(value), Type::Boolean)
+    }
+
+    pub fn load(name: Symbol, ty: Type) -> Self {
+        IRExpr::new(IRExprKind::Load(name), ty)
+    }
+
+    /// The expressions directly inside this one. A closure's body is statements,
+    /// and so is left out.
+    pub fn children(&self) -> Vec<&IRExpr> {
+        match &self.kind {
+            IRExprKind::BinaryOp { left, right, .. } | IRExprKind::Index { object: left, index: right } => vec![left, right],
+            IRExprKind::Result { ok, err } => vec![ok, err],
+            IRExprKind::UnaryOp { operand: inner, .. }
+            | IRExprKind::FieldAccess { object: inner, .. }
+            | IRExprKind::Await(inner)
+            | IRExprKind::Option(inner)
+            | IRExprKind::Cast(inner)
+            | IRExprKind::Try(inner) => vec![inner],
+            IRExprKind::Concat(items) | IRExprKind::List(items) => items.iter().collect(),
+            IRExprKind::Map(pairs) => pairs.iter().flat_map(|(key, value)| [key, value]).collect(),
+            IRExprKind::LiftedClosure { captures, .. } => captures.iter().map(|(_, value)| value).collect(),
+            IRExprKind::If { condition, then, else_ } => vec![condition, then, else_],
+            IRExprKind::StdCall { args, .. } | IRExprKind::Call { args, .. } => args.iter().collect(),
+            IRExprKind::CallIndirect { callee: object, args } | IRExprKind::MethodCall { object, args, .. } => {
+                std::iter::once(object.as_ref()).chain(args).collect()
+            }
+            IRExprKind::Comprehension { iter, filter, expr, .. } => {
+                std::iter::once(iter.as_ref()).chain(filter.as_deref()).chain(std::iter::once(expr.as_ref())).collect()
+            }
+            IRExprKind::Match { scrutinee, arms } => std::iter::once(scrutinee.as_ref())
+                .chain(arms.iter().flat_map(|arm| arm.guard.iter().chain(std::iter::once(&arm.body))))
+                .collect(),
+            IRExprKind::StringLiteral(_)
+            | IRExprKind::NumberLiteral(_)
+            | IRExprKind::BooleanLiteral(_)
+            | IRExprKind::CharLiteral(_)
+            | IRExprKind::Null
+            | IRExprKind::Load(_)
+            | IRExprKind::DomRef(_)
+            | IRExprKind::Closure { .. } => Vec::new(),
+        }
+    }
+
+    /// Like `children`, for rewriting them in place.
+    pub fn children_mut(&mut self) -> Vec<&mut IRExpr> {
+        match &mut self.kind {
+            IRExprKind::BinaryOp { left, right, .. } | IRExprKind::Index { object: left, index: right } => vec![left, right],
+            IRExprKind::Result { ok, err } => vec![ok, err],
+            IRExprKind::UnaryOp { operand: inner, .. }
+            | IRExprKind::FieldAccess { object: inner, .. }
+            | IRExprKind::Await(inner)
+            | IRExprKind::Option(inner)
+            | IRExprKind::Cast(inner)
+            | IRExprKind::Try(inner) => vec![inner],
+            IRExprKind::Concat(items) | IRExprKind::List(items) => items.iter_mut().collect(),
+            IRExprKind::Map(pairs) => pairs.iter_mut().flat_map(|(key, value)| [key, value]).collect(),
+            IRExprKind::LiftedClosure { captures, .. } => captures.iter_mut().map(|(_, value)| value).collect(),
+            IRExprKind::If { condition, then, else_ } => vec![condition, then, else_],
+            IRExprKind::StdCall { args, .. } | IRExprKind::Call { args, .. } => args.iter_mut().collect(),
+            IRExprKind::CallIndirect { callee: object, args } | IRExprKind::MethodCall { object, args, .. } => {
+                std::iter::once(object.as_mut()).chain(args).collect()
+            }
+            IRExprKind::Comprehension { iter, filter, expr, .. } => {
+                std::iter::once(iter.as_mut()).chain(filter.as_deref_mut()).chain(std::iter::once(expr.as_mut())).collect()
+            }
+            IRExprKind::Match { scrutinee, arms } => std::iter::once(scrutinee.as_mut())
+                .chain(arms.iter_mut().flat_map(|arm| arm.guard.iter_mut().chain(std::iter::once(&mut arm.body))))
+                .collect(),
+            IRExprKind::StringLiteral(_)
+            | IRExprKind::NumberLiteral(_)
+            | IRExprKind::BooleanLiteral(_)
+            | IRExprKind::CharLiteral(_)
+            | IRExprKind::Null
+            | IRExprKind::Load(_)
+            | IRExprKind::DomRef(_)
+            | IRExprKind::Closure { .. } => Vec::new(),
+        }
+    }
+
+    /// Whether this is a literal or `null`.
+    pub fn is_constant(&self) -> bool {
+        matches!(
+            self.kind,
+            IRExprKind::StringLiteral(_)
+                | IRExprKind::NumberLiteral(_)
+                | IRExprKind::BooleanLiteral(_)
+                | IRExprKind::CharLiteral(_)
+                | IRExprKind::Null
+        )
+    }
+}
+
+impl IRStmt {
+    pub fn new(kind: IRStmtKind) -> Self {
+        IRStmt { kind, loc: None }
+    }
+
+    /// This statement, as lowered from the source at `loc`.
+    pub fn at(self, loc: Span) -> Self {
+        IRStmt { loc: Some(loc), ..self }
+    }
+
+    /// The expressions directly in this statement, not in the statements nested in it.
+    pub fn exprs(&self) -> Vec<&IRExpr> {
+        match &self.kind {
+            IRStmtKind::Call { args, .. } | IRStmtKind::DomOp { args, .. } => args.iter().collect(),
+            IRStmtKind::Store { value: expr, .. }
+            | IRStmtKind::Expr(expr)
+            | IRStmtKind::Await(expr)
+            | IRStmtKind::Reactive { expr, .. }
+            | IRStmtKind::Render(expr)
+            | IRStmtKind::If { condition: expr, .. }
+            | IRStmtKind::Loop { condition: expr, .. }
+            | IRStmtKind::Return(Some(expr)) => vec![expr],
+            IRStmtKind::Comprehension { iter, filter, expr, .. } => std::iter::once(iter).chain(filter).chain(std::iter::once(expr)).collect(),
+            IRStmtKind::Block(_) | IRStmtKind::Break | IRStmtKind::Continue | IRStmtKind::EventBind { .. } | IRStmtKind::Return(None) => Vec::new(),
+        }
+    }
+
+    /// Like `exprs`, for rewriting them in place.
+    pub fn exprs_mut(&mut self) -> Vec<&mut IRExpr> {
+        match &mut self.kind {
+            IRStmtKind::Call { args, .. } | IRStmtKind::DomOp { args, .. } => args.iter_mut().collect(),
+            IRStmtKind::Store { value: expr, .. }
+            | IRStmtKind::Expr(expr)
+            | IRStmtKind::Await(expr)
+            | IRStmtKind::Reactive { expr, .. }
+            | IRStmtKind::Render(expr)
+            | IRStmtKind::If { condition: expr, .. }
+            | IRStmtKind::Loop { condition: expr, .. }
+            | IRStmtKind::Return(Some(expr)) => vec![expr],
+            IRStmtKind::Comprehension { iter, filter, expr, .. } => std::iter::once(iter).chain(filter).chain(std::iter::once(expr)).collect(),
+            IRStmtKind::Block(_) | IRStmtKind::Break | IRStmtKind::Continue | IRStmtKind::EventBind { .. } | IRStmtKind::Return(None) => Vec::new(),
+        }
+    }
+
+    /// The statement lists nested in this statement: branches, a loop's body and
+    /// update, and blocks.
+    pub fn blocks(&self) -> Vec<&[IRStmt]> {
+        match &self.kind {
+            IRStmtKind::If { then_block, else_block, .. } => vec![then_block, else_block],
+            IRStmtKind::Loop { body, update, .. } => vec![body, update],
+            IRStmtKind::Block(stmts) => vec![stmts],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Like `blocks`, for rewriting them in place.
+    pub fn blocks_mut(&mut self) -> Vec<&mut Vec<IRStmt>> {
+        match &mut self.kind {
+            IRStmtKind::If { then_block, else_block, .. } => vec![then_block, else_block],
+            IRStmtKind::Loop { body, update, .. } => vec![body, update],
+            IRStmtKind::Block(stmts) => vec![stmts],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IRMatchArm {
+    pub pattern: IRPattern,
+    pub guard: Option<IRExpr>,
+    pub body: IRExpr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IRPattern {
+    Wildcard,
+    Binding(Symbol),
+    NumberLiteral(f64),
+    StringLiteral(String), // also chars and booleans, as they are lowered as strings
+    Constructor { name: Symbol, args: Vec<IRPattern> },
+    Object { fields: Vec<(Symbol, IRPattern)> },
+    Array { elements: Vec<IRPattern>, rest: Option<Symbol> },
+}
+
+impl IRPattern {
+    /// The names this pattern binds, in source order.
+    pub fn bindings(&self) -> Vec<Symbol> {
+        match self {
+            IRPattern::Binding(name) => vec![*name],
+            IRPattern::Constructor { args, .. } => args.iter().flat_map(IRPattern::bindings).collect(),
+            IRPattern::Object { fields } => fields.iter().flat_map(|(_, field)| field.bindings()).collect(),
+            IRPattern::Array { elements, rest } => elements.iter().flat_map(IRPattern::bindings).chain(*rest).collect(),
+            IRPattern::Wildcard | IRPattern::NumberLiteral(_) | IRPattern::StringLiteral(_) => Vec::new(),
+        }
+    }
+}
+
+pub fn generate_ir(hir: &Hir) -> IRModule {
+    let _timer = profiling::scope("ir generation");
     let mut functions = Vec::new();
 
     // Convert functions
-    for function in &ast.functions {
-        functions.push(lower_function(function));
+    // Tests are only compiled by `gigli test`
+    for function in hir.functions.iter().filter(|f| Attribute::find(&f.attributes, "test").is_none()) {
+        let name = hir.name(function.def);
+        let mut lowered = lower_function(hir, function, name);
+        if let Some(export) = Attribute::find(&function.attributes, "export") {
+            lowered.export_name = Some(export.string_arg().map_or(name, Symbol::intern));
+        }
+        functions.push(lowered);
     }
 
     // Convert components
-    for component in &ast.components {
-        functions.push(lower_component(component));
+    for component in &hir.components {
+        functions.push(lower_component(hir, component));
     }
 
     // Convert classes
-    for class in &ast.classes {
-        functions.extend(lower_class(class));
+    for class in &hir.classes {
+        functions.extend(lower_class(hir, class));
     }
 
-    IRModule { functions }
+    let externs = hir
+        .externs
+        .iter()
+        .map(|ext| IRExtern {
+            module: Symbol::intern(&ext.abi),
+            name: ext.name,
+            params: ext.params.iter().map(|p| IRParam { name: p.name, type_annotation: p.type_annotation.clone() }).collect(),
+            return_type: ext.return_type.clone(),
+        })
+        .collect();
+
+    IRModule { functions, externs }
 }
 
-fn lower_function(f: &Function) -> IRFunction {
-    let mut body = Vec::new();
+/// The name of the IR function a Gigli function named `name` lowers to.
+fn function_symbol(name: Symbol) -> Symbol {
+    Symbol::intern(&format!("fn_{}", name))
+}
+
+/// The name a call to `def` refers to: the IR function for a Gigli function,
+/// otherwise the name itself, as for externs and runtime globals.
+fn callee_symbol(hir: &Hir, def: DefId) -> Symbol {
+    match hir.def(def).kind {
+        DefKind::Function => function_symbol(hir.name(def)),
+        _ => hir.name(def),
+    }
+}
 
-    // Convert function body to statements
-    for stmt in &f.body {
-        body.push(lower_stmt(stmt));
+/// Whether `expr` names a standard library module.
+fn std_module(hir: &Hir, expr: &HirExpr) -> Option<Symbol> {
+    match &expr.kind {
+        HirExprKind::Name(def) if hir.def(*def).kind == DefKind::Global && STD_MODULES.contains(&hir.name(*def).as_str()) => {
+            Some(hir.name(*def))
+        }
+        _ => None,
     }
+}
 
+fn lower_function(hir: &Hir, f: &HirFunction, name: Symbol) -> IRFunction {
     IRFunction {
-        name: format!("fn_{}", f.name),
-        body,
+        name: function_symbol(name),
+        params: lower_params(hir, &f.params),
+        return_type: f.return_type.clone(),
+        is_async: f.is_async,
+        export_name: None,
+        body: lower_body(hir, &f.params, &f.body),
+        reactive: Vec::new(),
+        loc: Some(f.span),
+    }
+}
+
+fn lower_params(hir: &Hir, params: &[HirParam]) -> Vec<IRParam> {
+    params
+        .iter()
+        .map(|p| IRParam {
+            name: hir.name(p.def),
+            type_annotation: p.type_annotation.clone(),
+        })
+        .collect()
+}
+
+/// Lowers the body of a function or arrow function, first unpacking its
+/// destructured parameters, which are passed under the pattern's text.
+fn lower_body(hir: &Hir, params: &[HirParam], body: &[HirStmt]) -> Vec<IRStmt> {
+    let mut lowered = Vec::new();
+    for param in params {
+        if let Some(pattern) = &param.pattern {
+            let def = hir.def(param.def);
+            lower_destructure(hir, pattern, IRExpr::load(def.name, def.ty.clone()), &mut lowered);
+        }
     }
+    lowered.extend(lower_stmts(hir, body));
+    lowered
 }
 
-fn lower_component(component: &ComponentNode) -> IRFunction {
+fn lower_component(hir: &Hir, component: &HirComponent) -> IRFunction {
     let mut body = Vec::new();
 
     // Lower state vars (reactive)
     for state in &component.state_vars {
-        body.push(IRStmt::Assign {
-            target: state.name.clone(),
-            value: lower_expr(&state.initial_value),
-        });
+        body.push(
+            IRStmt::new(IRStmtKind::Store {
+                target: hir.name(state.def),
+                value: lower_expr(hir, &state.value),
+            })
+            .at(state.span),
+        );
     }
 
     // Lower let vars (derived)
     for letv in &component.let_vars {
-        body.push(IRStmt::Assign {
-            target: letv.name.clone(),
-            value: lower_expr(&letv.value),
-        });
+        body.push(
+            IRStmt::new(IRStmtKind::Store {
+                target: hir.name(letv.def),
+                value: lower_expr(hir, &letv.value),
+            })
+            .at(letv.span),
+        );
     }
 
+    // Lower reactive statements, which rerun when a value they read changes
+    body.extend(lower_stmts(hir, &component.reactive));
+
     // Lower functions
     for func in &component.functions {
-        body.push(IRStmt::Call {
-            func: format!("fn_{}", func.name),
-            args: vec![], // TODO: handle params/args
-        });
+        body.push(
+            IRStmt::new(IRStmtKind::Call {
+                func: function_symbol(hir.name(func.def)),
+                args: vec![], // TODO: handle params/args
+            })
+            .at(func.span),
+        );
     }
 
     // Lower markup (UI render)
     for node in &component.markup {
-        body.push(IRStmt::Render(lower_markup(node)));
+        let markup = lower_markup(hir, node);
+        let loc = markup.loc;
+        body.push(IRStmt { kind: IRStmtKind::Render(markup), loc });
     }
 
     IRFunction {
-        name: format!("component_{}", component.name),
+        name: Symbol::intern(&format!("component_{}", hir.name(component.def))),
+        params: Vec::new(),
+        return_type: None,
+        is_async: false,
+        export_name: None,
         body,
+        reactive: lower_reactive_graph(hir, component),
+        loc: Some(component.span),
+    }
+}
+
+/// Builds a component's reactive dependency graph from its state variables,
+/// derived values, `$:` statements and the parts of its markup that read them.
+fn lower_reactive_graph(hir: &Hir, component: &HirComponent) -> Vec<IRReactiveNode> {
+    let effects: Vec<(Symbol, &HirExpr)> = component
+        .reactive
+        .iter()
+        .filter_map(|stmt| match &stmt.kind {
+            HirStmtKind::Reactive { name, expr } => Some((hir.name(*name), expr)),
+            _ => None,
+        })
+        .collect();
+    let variables: HashSet<Symbol> = component
+        .state_vars
+        .iter()
+        .chain(&component.let_vars)
+        .map(|var| hir.name(var.def))
+        .chain(effects.iter().map(|(name, _)| *name))
+        .collect();
+
+    let mut nodes = Vec::new();
+    for state in &component.state_vars {
+        let expr = lower_expr(hir, &state.value);
+        nodes.push(IRReactiveNode { kind: IRReactiveKind::Cell, reads: Vec::new(), writes: vec![hir.name(state.def)], expr });
+    }
+    for letv in &component.let_vars {
+        let expr = lower_expr(hir, &letv.value);
+        nodes.push(IRReactiveNode { kind: IRReactiveKind::Derived, reads: reads(&expr, &variables), writes: vec![hir.name(letv.def)], expr });
+    }
+    for (name, expr) in effects {
+        let expr = lower_expr(hir, expr);
+        nodes.push(IRReactiveNode { kind: IRReactiveKind::Effect, reads: reads(&expr, &variables), writes: vec![name], expr });
+    }
+    let mut slot = 0;
+    for node in &component.markup {
+        lower_markup_bindings(hir, node, &variables, &mut slot, &mut nodes);
+    }
+    nodes
+}
+
+/// Adds a node for each part of `node` that reads a component variable,
+/// numbering them from `slot`. Blocks are one node, with whatever is in them.
+fn lower_markup_bindings(hir: &Hir, node: &HirMarkup, variables: &HashSet<Symbol>, slot: &mut u32, nodes: &mut Vec<IRReactiveNode>) {
+    match node {
+        HirMarkup::Element { attributes, children, .. } => {
+            for (name, value) in attributes {
+                let kind = IRReactiveKind::Attribute { slot: *slot, name: *name };
+                push_binding(kind, lower_expr(hir, value), variables, slot, nodes);
+            }
+            for child in children {
+                lower_markup_bindings(hir, child, variables, slot, nodes);
+            }
+        }
+        HirMarkup::Text(expr) => push_binding(IRReactiveKind::Text { slot: *slot }, lower_expr(hir, expr), variables, slot, nodes),
+        HirMarkup::IfBlock { .. } | HirMarkup::ForLoop { .. } => {
+            push_binding(IRReactiveKind::Block { slot: *slot }, lower_markup(hir, node), variables, slot, nodes)
+        }
+    }
+}
+
+/// Adds the node for a part of the markup, taking the next slot, unless the
+/// part reads no component variable and so never changes.
+fn push_binding(kind: IRReactiveKind, expr: IRExpr, variables: &HashSet<Symbol>, slot: &mut u32, nodes: &mut Vec<IRReactiveNode>) {
+    let reads = reads(&expr, variables);
+    if !reads.is_empty() {
+        nodes.push(IRReactiveNode { kind, reads, writes: Vec::new(), expr });
+        *slot += 1;
+    }
+}
+
+/// The variables among `variables` that `expr` loads, sorted by name.
+fn reads(expr: &IRExpr, variables: &HashSet<Symbol>) -> Vec<Symbol> {
+    fn visit(expr: &IRExpr, variables: &HashSet<Symbol>, reads: &mut Vec<Symbol>) {
+        match &expr.kind {
+            IRExprKind::Load(name) if variables.contains(name) => reads.push(*name),
+            IRExprKind::Closure { body, .. } => visit_stmts(body, variables, reads),
+            _ => {}
+        }
+        for child in expr.children() {
+            visit(child, variables, reads);
+        }
     }
+    fn visit_stmts(stmts: &[IRStmt], variables: &HashSet<Symbol>, reads: &mut Vec<Symbol>) {
+        for stmt in stmts {
+            for expr in stmt.exprs() {
+                visit(expr, variables, reads);
+            }
+            for block in stmt.blocks() {
+                visit_stmts(block, variables, reads);
+            }
+        }
+    }
+    let mut reads = Vec::new();
+    visit(expr, variables, &mut reads);
+    reads.sort();
+    reads.dedup();
+    reads
 }
 
-fn lower_markup(node: &MarkupNode) -> IRExpr {
+/// Lowers markup to the HTML it renders, built with `Concat`: `{#if}` blocks
+/// become an `If`, and `{#for}` blocks join the body rendered for each item.
+fn lower_markup(hir: &Hir, node: &HirMarkup) -> IRExpr {
+    let lower_nodes = |nodes: &[HirMarkup]| concat(nodes.iter().map(|n| lower_markup(hir, n)).collect());
     match node {
-        MarkupNode::Element { tag, attributes, children } => {
-            let tag_str = tag.clone();
-            let attrs_str = attributes.iter().map(|(k, v)| format!("{}=\"{}\"", k, lower_expr_to_string(v))).collect::<Vec<_>>().join(" ");
-            let children_str = children.iter().map(|c| lower_markup(c)).map(|e| match e { IRExpr::StringLiteral(s) => s, _ => String::from("<unsupported>") }).collect::<Vec<_>>().join("");
-            IRExpr::StringLiteral(format!("<{} {}>{}</{}>", tag_str, attrs_str, children_str, tag_str))
+        HirMarkup::Element { tag, attributes, children, span } => {
+            let mut parts = vec![IRExpr::string(format!("<{}", tag))];
+            for (name, value) in attributes {
+                parts.push(IRExpr::string(format!(" {}=\"", name)));
+                parts.push(lower_expr(hir, value));
+                parts.push(IRExpr::string("\""));
+            }
+            parts.push(IRExpr::string(">"));
+            parts.push(lower_nodes(children));
+            parts.push(IRExpr::string(format!("</{}>", tag)));
+            concat(parts).at(*span)
         }
-        MarkupNode::Text(expr) => IRExpr::StringLiteral(lower_expr_to_string(expr)),
-        MarkupNode::IfBlock(ifblock) => {
-            let cond_str = lower_expr_to_string(&ifblock.condition);
-            let then_str = ifblock.then_branch.iter().map(|n| lower_markup(n)).map(|e| match e { IRExpr::StringLiteral(s) => s, _ => String::from("<unsupported>") }).collect::<Vec<_>>().join("");
-            let else_str = ifblock.else_branch.as_ref().map(|b| b.iter().map(|n| lower_markup(n)).map(|e| match e { IRExpr::StringLiteral(s) => s, _ => String::from("<unsupported>") }).collect::<Vec<_>>().join("")).unwrap_or_default();
-            IRExpr::StringLiteral(format!("if({}){{{}}}else{{{}}}", cond_str, then_str, else_str))
+        HirMarkup::Text(expr) => lower_expr(hir, expr),
+        HirMarkup::IfBlock { condition, then_branch, else_branch, span } => IRExpr::new(
+            IRExprKind::If {
+                condition: Box::new(lower_expr(hir, condition)),
+                then: Box::new(lower_nodes(then_branch)),
+                else_: Box::new(else_branch.as_deref().map_or_else(|| IRExpr::string(""), lower_nodes)),
+            },
+            Type::String,
+        )
+        .at(*span),
+        HirMarkup::ForLoop { iterator, iterable, body, span } => {
+            let items = IRExpr::new(
+                IRExprKind::Comprehension {
+                    target: hir.name(*iterator),
+                    iter: Box::new(lower_expr(hir, iterable)),
+                    filter: None,
+                    expr: Box::new(lower_nodes(body)),
+                },
+                Type::Array(Box::new(Type::String)),
+            );
+            std_call("string", "join", vec![items, IRExpr::string("")], Type::String).at(*span)
         }
-        MarkupNode::ForLoop(forblock) => {
-            let iter_str = forblock.iterator.clone();
-            let iterable_str = lower_expr_to_string(&forblock.iterable);
-            let body_str = forblock.body.iter().map(|n| lower_markup(n)).map(|e| match e { IRExpr::StringLiteral(s) => s, _ => String::from("<unsupported>") }).collect::<Vec<_>>().join("");
-            IRExpr::StringLiteral(format!("for({} in {}){{{}}}", iter_str, iterable_str, body_str))
+    }
+}
+
+/// Joins `parts` into one string, flattening nested `Concat`s and merging
+/// adjacent string literals. A lone string part is returned as it is.
+pub(crate) fn concat(parts: Vec<IRExpr>) -> IRExpr {
+    let mut merged: Vec<IRExpr> = Vec::new();
+    for part in parts {
+        let flattened = match part {
+            IRExpr { kind: IRExprKind::Concat(inner), .. } => inner,
+            part => vec![part],
+        };
+        for part in flattened {
+            match (&part.kind, merged.last_mut()) {
+                (IRExprKind::StringLiteral(s), _) if s.is_empty() => {}
+                (IRExprKind::StringLiteral(s), Some(IRExpr { kind: IRExprKind::StringLiteral(last), .. })) => last.push_str(s),
+                _ => merged.push(part),
+            }
         }
     }
+    match merged.len() {
+        0 => IRExpr::string(""),
+        1 if merged[0].ty == Type::String => merged.remove(0),
+        _ => IRExpr::new(IRExprKind::Concat(merged), Type::String),
+    }
+}
+
+/// A call to `module.func` in the standard library.
+fn std_call(module: &str, func: &str, args: Vec<IRExpr>, ty: Type) -> IRExpr {
+    IRExpr::new(
+        IRExprKind::StdCall { module: Symbol::intern(module), func: Symbol::intern(func), args },
+        ty,
+    )
 }
 
-fn lower_class(class: &Class) -> Vec<IRFunction> {
+fn lower_class(hir: &Hir, class: &HirClass) -> Vec<IRFunction> {
     let mut functions = Vec::new();
 
     // Convert methods
     for method in &class.methods {
-        functions.push(lower_function(&Function {
-            name: format!("{}_{}", class.name, method.name),
-            params: method.params.clone(),
-            return_type: method.return_type.clone(),
-            body: method.body.clone(),
-            is_public: method.is_public,
-            is_async: false, // Remove method.is_async, default to false
-        }));
+        functions.push(lower_function(hir, method, Symbol::intern(&format!("{}_{}", class.name, hir.name(method.def)))));
     }
 
     // Convert constructor
     if let Some(constructor) = &class.constructor {
-        functions.push(lower_function(&Function {
-            name: format!("{}_constructor", class.name),
-            params: constructor.params.clone(),
-            return_type: None,
-            body: constructor.body.clone(),
-            is_public: true,
-            is_async: false,
-        }));
+        functions.push(lower_function(hir, constructor, Symbol::intern(&format!("{}_constructor", class.name))));
     }
 
     functions
 }
 
-fn lower_expr_to_string(expr: &Expr) -> String {
-    match expr {
-        Expr::StringLiteral(s) => s.clone(),
-        Expr::NumberLiteral(n) => n.to_string(),
-        Expr::BooleanLiteral(b) => b.to_string(),
-        Expr::NullLiteral => "null".to_string(),
-        Expr::UndefinedLiteral => "undefined".to_string(),
-        Expr::Identifier(s) => s.clone(),
-        Expr::BinaryOp { left, op, right } => {
-            let op_str = match op {
-                BinaryOp::Add => "+",
-                BinaryOp::Subtract => "-",
-                BinaryOp::Multiply => "*",
-                BinaryOp::Divide => "/",
-                BinaryOp::Modulo => "%",
-                BinaryOp::Power => "**",
-                BinaryOp::Equal => "==",
-                BinaryOp::NotEqual => "!=",
-                BinaryOp::StrictEqual => "===",
-                BinaryOp::StrictNotEqual => "!==",
-                BinaryOp::LessThan => "<",
-                BinaryOp::LessThanEqual => "<=",
-                BinaryOp::GreaterThan => ">",
-                BinaryOp::GreaterThanEqual => ">=",
-                BinaryOp::And => "&&",
-                BinaryOp::Or => "||",
-                BinaryOp::BitwiseAnd => "&",
-                BinaryOp::BitwiseOr => "|",
-                BinaryOp::BitwiseXor => "^",
-                BinaryOp::LeftShift => "<<",
-                BinaryOp::RightShift => ">>",
-                BinaryOp::UnsignedRightShift => ">>>",
-            };
-            format!("({} {} {})", lower_expr_to_string(left), op_str, lower_expr_to_string(right))
-        }
-        Expr::UnaryOp { op, operand } => {
-            let op_str = match op {
-                UnaryOp::Plus => "+",
-                UnaryOp::Minus => "-",
-                UnaryOp::Not => "!",
-                UnaryOp::BitwiseNot => "~",
-                UnaryOp::Increment => "++",
-                UnaryOp::Decrement => "--",
-                UnaryOp::TypeOf => "typeof ",
-                UnaryOp::Void => "void ",
-                UnaryOp::Delete => "delete ",
-            };
-            format!("{}{}", op_str, lower_expr_to_string(operand))
-        }
-        Expr::Call { func, args } => {
-            let args_str = args.iter().map(|a| lower_expr_to_string(a)).collect::<Vec<_>>().join(", ");
-            format!("{}({})", lower_expr_to_string(func), args_str)
-        }
-        Expr::MethodCall { object, method, args } => {
-            let args_str = args.iter().map(|a| lower_expr_to_string(a)).collect::<Vec<_>>().join(", ");
-            format!("{}.{}({})", lower_expr_to_string(object), method, args_str)
-        }
-        Expr::If { condition, then, else_ } => {
-            let else_str = lower_expr_to_string(else_);
-            format!("if({}) {{ {} }} else {{ {} }}", lower_expr_to_string(condition), lower_expr_to_string(then), else_str)
-        }
-        Expr::Concat { left, right } => {
-            format!("{}+{}", lower_expr_to_string(left), lower_expr_to_string(right))
-        }
-        Expr::PropertyAccess { object, property } => {
-            format!("{}.{}", lower_expr_to_string(object), property)
-        }
-        Expr::ArrayAccess { array, index } => {
-            format!("{}[{}]", lower_expr_to_string(array), lower_expr_to_string(index))
+fn binary_op_str(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Modulo => "%",
+        BinaryOp::Power => "**",
+        BinaryOp::Equal => "==",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::StrictEqual => "===",
+        BinaryOp::StrictNotEqual => "!==",
+        BinaryOp::LessThan => "<",
+        BinaryOp::LessThanEqual => "<=",
+        BinaryOp::GreaterThan => ">",
+        BinaryOp::GreaterThanEqual => ">=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::BitwiseAnd => "&",
+        BinaryOp::BitwiseOr => "|",
+        BinaryOp::BitwiseXor => "^",
+        BinaryOp::LeftShift => "<<",
+        BinaryOp::RightShift => ">>",
+        BinaryOp::UnsignedRightShift => ">>>",
+    }
+}
+
+fn unary_op_str(op: &UnaryOp) -> &'static str {
+    match op {
+        UnaryOp::Plus => "+",
+        UnaryOp::Minus => "-",
+        UnaryOp::Not => "!",
+        UnaryOp::BitwiseNot => "~",
+        UnaryOp::Increment => "++",
+        UnaryOp::Decrement => "--",
+        UnaryOp::TypeOf => "typeof ",
+        UnaryOp::Void => "void ",
+        UnaryOp::Delete => "delete ",
+    }
+}
+
+fn lower_expr_to_string(hir: &Hir, expr: &HirExpr) -> String {
+    let to_string = |e: &HirExpr| lower_expr_to_string(hir, e);
+    let list = |exprs: &[HirExpr]| exprs.iter().map(to_string).collect::<Vec<_>>().join(", ");
+    match &expr.kind {
+        HirExprKind::StringLiteral(s) => s.clone(),
+        HirExprKind::NumberLiteral(n) => n.to_string(),
+        HirExprKind::BooleanLiteral(b) => b.to_string(),
+        HirExprKind::CharLiteral(c) => c.to_string(),
+        HirExprKind::NullLiteral => "null".to_string(),
+        HirExprKind::UndefinedLiteral => "undefined".to_string(),
+        HirExprKind::Name(def) => hir.name(*def).to_string(),
+        HirExprKind::BinaryOp { left, op, right } => format!("({} {} {})", to_string(left), binary_op_str(op), to_string(right)),
+        HirExprKind::UnaryOp { op, operand } => format!("{}{}", unary_op_str(op), to_string(operand)),
+        HirExprKind::Call { func, args } => format!("{}({})", to_string(func), list(args)),
+        HirExprKind::MethodCall { object, method, args } => format!("{}.{}({})", to_string(object), method, list(args)),
+        HirExprKind::If { condition, then, else_ } => {
+            format!("if({}) {{ {} }} else {{ {} }}", to_string(condition), to_string(then), to_string(else_))
         }
-        Expr::TemplateLiteral { parts } => {
+        HirExprKind::Concat { left, right } => format!("{}+{}", to_string(left), to_string(right)),
+        HirExprKind::PropertyAccess { object, property } => format!("{}.{}", to_string(object), property),
+        HirExprKind::ArrayAccess { array, index } => format!("{}[{}]", to_string(array), to_string(index)),
+        HirExprKind::TemplateLiteral { parts } => {
             let mut result = String::new();
             for part in parts {
                 match part {
-                    TemplatePart::String(s) => result.push_str(s),
-                    TemplatePart::Expression(expr) => {
-                        result.push_str(&format!("${{{}}}", lower_expr_to_string(expr)));
+                    HirTemplatePart::String(s) => result.push_str(s),
+                    HirTemplatePart::Expression(expr) => {
+                        result.push_str(&format!("${{{}}}", to_string(expr)));
                     }
                 }
             }
             format!("`{}`", result)
         }
-        Expr::ArrowFunction { params, body } => {
-            let params_str = params.iter().map(|p| p.name.clone()).collect::<Vec<_>>().join(", ");
+        HirExprKind::ArrowFunction { params, body } => {
+            let params_str = params.iter().map(|p| hir.name(p.def).to_string()).collect::<Vec<_>>().join(", ");
             let body_str = body.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join("; ");
             format!("({}) => {{ {} }}", params_str, body_str)
         }
-        Expr::New { class, args } => {
-            let args_str = args.iter().map(|a| lower_expr_to_string(a)).collect::<Vec<_>>().join(", ");
-            format!("new {}({})", lower_expr_to_string(class), args_str)
-        }
-        Expr::TypeAssert { value, type_ } => {
-            format!("{} as {:?}", lower_expr_to_string(value), type_)
-        }
-        Expr::ArrayLiteral(elements) => {
-            let elements_str = elements.iter().map(|e| lower_expr_to_string(e)).collect::<Vec<_>>().join(", ");
-            format!("[{}]", elements_str)
-        }
-        Expr::ObjectLiteral(properties) => {
+        HirExprKind::New { class, args } => format!("new {}({})", to_string(class), list(args)),
+        HirExprKind::TypeAssert { value } => format!("{} as {:?}", to_string(value), expr.ty),
+        HirExprKind::Unwrap { value, .. } => to_string(value),
+        HirExprKind::ArrayLiteral(elements) => format!("[{}]", list(elements)),
+        HirExprKind::ObjectLiteral(properties) => {
             let props_str = properties.iter().map(|p| {
+                let key = match &p.key {
+                    HirPropertyKey::Name(name) => name.to_string(),
+                    HirPropertyKey::Computed(expr) => format!("[{}]", to_string(expr)),
+                };
                 if p.shorthand {
-                    p.key.clone()
+                    key
                 } else {
-                    format!("{}: {}", p.key, lower_expr_to_string(&p.value))
+                    format!("{}: {}", key, to_string(&p.value))
                 }
             }).collect::<Vec<_>>().join(", ");
             format!("{{ {} }}", props_str)
         }
-        Expr::Await(inner) => format!("await({})", lower_expr_to_string(inner)),
-        Expr::Comprehension { target, iter, filter, expr } => {
-            let filter_str = filter.as_ref().map(|f| format!(" if {}", lower_expr_to_string(f))).unwrap_or_default();
-            format!("[{} for {} in {}{}]", lower_expr_to_string(expr), target, lower_expr_to_string(iter), filter_str)
+        HirExprKind::Await(inner) => format!("await({})", to_string(inner)),
+        HirExprKind::Try(inner) => format!("{}?", to_string(inner)),
+        HirExprKind::OptionalChain { object, property, args } => {
+            let args_str = args.as_ref().map(|args| format!("({})", list(args)));
+            format!("{}?.{}{}", to_string(object), property, args_str.unwrap_or_default())
+        }
+        HirExprKind::Coalesce { value, default } => format!("{} ?? {}", to_string(value), to_string(default)),
+        HirExprKind::Comprehension { target, iter, filter, expr } => {
+            let filter_str = filter.as_ref().map(|f| format!(" if {}", to_string(f))).unwrap_or_default();
+            format!("[{} for {} in {}{}]", to_string(expr), hir.name(*target), to_string(iter), filter_str)
         },
-        Expr::CellAccess(_) => String::from("<unsupported: cell access>"),
+        HirExprKind::Match { scrutinee, arms } => {
+            let arms_str = arms.iter().map(|arm| {
+                let guard_str = arm.guard.as_ref().map(|g| format!(" if {}", to_string(g))).unwrap_or_default();
+                format!("{}{} => {}", arm.pattern, guard_str, to_string(&arm.body))
+            }).collect::<Vec<_>>().join(", ");
+            format!("match {} {{ {} }}", to_string(scrutinee), arms_str)
+        }
+        HirExprKind::CellAccess(_) => String::from("<unsupported: cell access>"),
     }
 }
 
-fn lower_stmt(s: &Stmt) -> IRStmt {
-    match s {
-        Stmt::Expr(e) => match e {
-            Expr::Call { func, args } => IRStmt::Call {
-                func: lower_expr_to_string(func),
-                args: args.iter().map(|a| lower_expr(a)).collect(),
-            },
-            _ => IRStmt::Call {
-                func: "expr".to_string(),
-                args: vec![lower_expr(e)],
-            },
-        },
-        Stmt::Assign { target, value } => IRStmt::Assign {
-            target: target.clone(),
-            value: lower_expr(value),
+/// `Some(name)`, binding the value inside an `Option`.
+fn some_pattern(name: Symbol) -> IRPattern {
+    IRPattern::Constructor { name: Symbol::intern("Some"), args: vec![IRPattern::Binding(name)] }
+}
+
+/// The type inside `Option<T>`, or `any` for any other type.
+fn option_inner(ty: &Type) -> Type {
+    match ty {
+        Type::Option(inner) => (**inner).clone(),
+        _ => Type::Any,
+    }
+}
+
+/// Lowers `object?.property` (or `?.method(args)`) to a `match` that applies the
+/// access to the `Some` value and gives `None` otherwise.
+fn lower_optional_chain(hir: &Hir, e: &HirExpr, object: &HirExpr, property: Symbol, args: Option<&[HirExpr]>) -> IRExpr {
+    let some = Symbol::intern(&format!("{}?", lower_expr_to_string(hir, object)));
+    let value = Box::new(IRExpr::load(some, option_inner(&object.ty)));
+    let access = match args {
+        Some(args) => IRExprKind::MethodCall {
+            object: value,
+            method: property,
+            args: args.iter().map(|arg| lower_expr(hir, arg)).collect(),
         },
-        Stmt::If { condition, then, else_ } => IRStmt::Call {
-            func: "if".to_string(),
-            args: vec![
-                lower_expr(condition),
-                IRExpr::StringLiteral(then.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")),
-                IRExpr::StringLiteral(else_.as_ref().map(|stmts| stmts.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")).unwrap_or_default()),
+        None => IRExprKind::FieldAccess { object: value, field: property },
+    };
+    IRExpr::new(
+        IRExprKind::Match {
+            scrutinee: Box::new(lower_expr(hir, object)),
+            arms: vec![
+                IRMatchArm { pattern: some_pattern(some), guard: None, body: IRExpr::new(access, option_inner(&e.ty)) },
+                IRMatchArm { pattern: IRPattern::Wildcard, guard: None, body: IRExpr::load(Symbol::intern("None"), e.ty.clone()) },
             ],
         },
-        Stmt::Loop { init, condition, update, body } => IRStmt::Call {
-            func: "loop".to_string(),
-            args: vec![
-                IRExpr::StringLiteral(init.as_ref().map(|s| format!("{:?}", s)).unwrap_or_default()),
-                IRExpr::StringLiteral(condition.as_ref().map(|e| lower_expr_to_string(e)).unwrap_or_default()),
-                IRExpr::StringLiteral(update.as_ref().map(|s| format!("{:?}", s)).unwrap_or_default()),
-                IRExpr::StringLiteral(body.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")),
-            ],
+        e.ty.clone(),
+    )
+}
+
+fn lower_property_key(hir: &Hir, key: &HirPropertyKey) -> IRExpr {
+    match key {
+        HirPropertyKey::Name(name) => IRExpr::string(name.to_string()),
+        HirPropertyKey::Computed(expr) => lower_expr(hir, expr),
+    }
+}
+
+fn lower_pattern(pattern: &HirPattern) -> IRPattern {
+    match &pattern.kind {
+        HirPatternKind::Wildcard => IRPattern::Wildcard,
+        HirPatternKind::Binding(binding) => IRPattern::Binding(binding.name),
+        HirPatternKind::Number(n) => IRPattern::NumberLiteral(*n),
+        HirPatternKind::String(s) => IRPattern::StringLiteral(s.clone()),
+        HirPatternKind::Char(c) => IRPattern::StringLiteral(c.to_string()),
+        HirPatternKind::Boolean(b) => IRPattern::StringLiteral(b.to_string()),
+        HirPatternKind::Constructor { name, args } => IRPattern::Constructor {
+            name: *name,
+            args: args.iter().map(lower_pattern).collect(),
         },
-        Stmt::ForIn { variable, iterable, body } => IRStmt::Call {
-            func: "forin".to_string(),
-            args: vec![
-                IRExpr::StringLiteral(variable.clone()),
-                lower_expr(iterable),
-                IRExpr::StringLiteral(body.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")),
-            ],
+        HirPatternKind::Object { fields } => IRPattern::Object {
+            fields: fields.iter().map(|(key, field)| (*key, lower_pattern(field))).collect(),
         },
-        Stmt::ForOf { variable, iterable, body } => IRStmt::Call {
-            func: "forof".to_string(),
-            args: vec![
-                IRExpr::StringLiteral(variable.clone()),
-                lower_expr(iterable),
-                IRExpr::StringLiteral(body.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")),
-            ],
+        HirPatternKind::Array { elements, rest } => IRPattern::Array {
+            elements: elements.iter().map(lower_pattern).collect(),
+            rest: rest.map(|rest| rest.name),
+        },
+    }
+}
+
+/// Lowers a statement list. Destructuring expands to one store per binding.
+fn lower_stmts(hir: &Hir, stmts: &[HirStmt]) -> Vec<IRStmt> {
+    let mut lowered = Vec::new();
+    for stmt in stmts {
+        match &stmt.kind {
+            HirStmtKind::Destructure { pattern, value, else_branch: Some(body) } => {
+                lower_let_else(hir, pattern, lower_expr(hir, value), body, &mut lowered)
+            }
+            HirStmtKind::Destructure { pattern, value, .. } => lower_destructure(hir, pattern, lower_expr(hir, value), &mut lowered),
+            _ => lowered.push(lower_stmt(hir, stmt)),
+        }
+    }
+    lowered
+}
+
+/// The variable holding `value`: `value` itself if it is a load, otherwise a
+/// temporary named after `pattern` that `value` is first stored in.
+fn store_temp(pattern: &HirPattern, value: IRExpr, out: &mut Vec<IRStmt>) -> IRExpr {
+    match value.kind {
+        IRExprKind::Load(_) => value,
+        _ => {
+            let temp = Symbol::intern(&pattern.to_string());
+            let ty = value.ty.clone();
+            out.push(IRStmt::new(IRStmtKind::Store { target: temp, value }).at(pattern.span));
+            IRExpr::load(temp, ty)
+        }
+    }
+}
+
+/// Assigns each name bound by `pattern` its part of `value`: object fields through
+/// `object.get`, array elements through `list.get` and the rest through `list.slice`.
+/// Literal and constructor patterns bind nothing here; they only matter in `match`.
+fn lower_destructure(hir: &Hir, pattern: &HirPattern, value: IRExpr, out: &mut Vec<IRStmt>) {
+    let binding_type = |binding: &HirBinding| hir.def(binding.def).ty.clone();
+    // The type a part is read as: a binding's own type, otherwise unknown
+    let part_type = |pattern: &HirPattern| match &pattern.kind {
+        HirPatternKind::Binding(binding) => binding_type(binding),
+        _ => Type::Any,
+    };
+    match &pattern.kind {
+        HirPatternKind::Binding(binding) => out.push(IRStmt::new(IRStmtKind::Store { target: binding.name, value }).at(pattern.span)),
+        HirPatternKind::Object { fields } => {
+            let object = store_temp(pattern, value, out);
+            for (key, field) in fields {
+                let part = std_call("object", "get", vec![object.clone(), IRExpr::string(key.to_string())], part_type(field));
+                lower_destructure(hir, field, part, out);
+            }
+        }
+        HirPatternKind::Array { elements, rest } => {
+            let list = store_temp(pattern, value, out);
+            for (i, element) in elements.iter().enumerate() {
+                let part = std_call("list", "get", vec![list.clone(), IRExpr::number(i as f64)], part_type(element));
+                lower_destructure(hir, element, part, out);
+            }
+            if let Some(rest) = rest {
+                let part = std_call("list", "slice", vec![list.clone(), IRExpr::number(elements.len() as f64)], binding_type(rest));
+                out.push(IRStmt::new(IRStmtKind::Store { target: rest.name, value: part }).at(pattern.span));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Lowers `let pattern = value else { body };`: `body` runs when `value` does not
+/// match, and each bound name is then read out through a one-arm `match`.
+fn lower_let_else(hir: &Hir, pattern: &HirPattern, value: IRExpr, body: &[HirStmt], out: &mut Vec<IRStmt>) {
+    let subject = store_temp(pattern, value, out);
+    let arm = |pattern: IRPattern, body: IRExpr| IRMatchArm { pattern, guard: None, body };
+    let matches = IRExpr::new(
+        IRExprKind::Match {
+            scrutinee: Box::new(subject.clone()),
+            arms: vec![arm(lower_pattern(pattern), IRExpr::boolean(true)), arm(IRPattern::Wildcard, IRExpr::boolean(false))],
         },
-        Stmt::Return(value) => IRStmt::Return(value.as_ref().map(|v| lower_expr(v))),
-        Stmt::StateVarDecl(s) => IRStmt::Assign {
-            target: s.name.clone(),
-            value: lower_expr(&s.initial_value),
+        Type::Boolean,
+    );
+    out.push(IRStmt::new(IRStmtKind::If { condition: matches, then_block: Vec::new(), else_block: lower_stmts(hir, body) }).at(pattern.span));
+    for binding in pattern.bindings() {
+        let ty = hir.def(binding.def).ty.clone();
+        let value = IRExpr::new(
+            IRExprKind::Match {
+                scrutinee: Box::new(subject.clone()),
+                arms: vec![arm(lower_pattern(pattern), IRExpr::load(binding.name, ty.clone()))],
+            },
+            ty,
+        );
+        out.push(IRStmt::new(IRStmtKind::Store { target: binding.name, value }).at(pattern.span));
+    }
+}
+
+fn lower_stmt(hir: &Hir, s: &HirStmt) -> IRStmt {
+    let debug_str = |stmts: &[HirStmt]| stmts.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";");
+    let debug = |stmts: &[HirStmt]| IRExpr::string(debug_str(stmts));
+    let kind = match &s.kind {
+        // A call of a named function stays a call; anything else is evaluated and discarded
+        HirStmtKind::Expr(e) => match lower_expr(hir, e) {
+            IRExpr { kind: IRExprKind::Call { func, args }, .. } => IRStmtKind::Call { func, args },
+            value => IRStmtKind::Expr(value),
         },
-        Stmt::LetVarDecl(l) => IRStmt::Assign {
-            target: l.name.clone(),
-            value: lower_expr(&l.value),
+        HirStmtKind::Assign { target, value } => IRStmtKind::Store {
+            target: hir.name(*target),
+            value: lower_expr(hir, value),
         },
-        Stmt::Block(statements) => IRStmt::Call {
-            func: "block".to_string(),
-            args: vec![
-                IRExpr::StringLiteral(statements.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")),
-            ],
+        HirStmtKind::If { condition, then, else_ } => IRStmtKind::If {
+            condition: lower_expr(hir, condition),
+            then_block: lower_stmts(hir, then),
+            else_block: else_.as_deref().map(|else_| lower_stmts(hir, else_)).unwrap_or_default(),
         },
-        Stmt::Try { body, catch, finally } => IRStmt::Call {
-            func: "try".to_string(),
-            args: vec![
-                IRExpr::StringLiteral(body.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")),
-                IRExpr::StringLiteral(catch.as_ref().map(|c| format!("catch({}) {{ {} }}", c.error_var, c.body.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";"))).unwrap_or_default()),
-                IRExpr::StringLiteral(finally.as_ref().map(|stmts| stmts.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")).unwrap_or_default()),
-            ],
+        // The loop is wrapped in a block with its initializer, which it scopes
+        HirStmtKind::Loop { init, condition, update, body } => {
+            let mut block: Vec<IRStmt> = init.as_deref().map(|init| lower_stmts(hir, std::slice::from_ref(init))).unwrap_or_default();
+            let loop_ = IRStmtKind::Loop {
+                // No condition loops until something breaks out
+                condition: condition.as_ref().map_or_else(|| IRExpr::boolean(true), |e| lower_expr(hir, e)),
+                body: lower_stmts(hir, body),
+                update: update.as_deref().map(|update| lower_stmts(hir, std::slice::from_ref(update))).unwrap_or_default(),
+            };
+            block.push(IRStmt::new(loop_).at(s.span));
+            IRStmtKind::Block(block)
+        }
+        HirStmtKind::ForIn { variable, iterable, body } | HirStmtKind::ForOf { variable, iterable, body } => {
+            lower_for_each(hir, *variable, iterable, body, s.span)
+        }
+        HirStmtKind::Return(value) => IRStmtKind::Return(value.as_ref().map(|v| lower_expr(hir, v))),
+        HirStmtKind::StateVarDecl { def, value } | HirStmtKind::LetVarDecl { def, value } => IRStmtKind::Store {
+            target: hir.name(*def),
+            value: lower_expr(hir, value),
         },
-        Stmt::Throw(expr) => IRStmt::Call {
-            func: "throw".to_string(),
-            args: vec![lower_expr(expr)],
+        // `lower_stmts` expands these into stores; this is only reached for a lone statement
+        HirStmtKind::Destructure { pattern, value, .. } => IRStmtKind::Call {
+            func: Symbol::intern("destructure"),
+            args: vec![IRExpr::string(pattern.to_string()), lower_expr(hir, value)],
         },
-        Stmt::Break(label) => IRStmt::Call {
-            func: "break".to_string(),
+        HirStmtKind::Block(statements) => IRStmtKind::Block(lower_stmts(hir, statements)),
+        HirStmtKind::Try { body, catch, finally } => IRStmtKind::Call {
+            func: Symbol::intern("try"),
             args: vec![
-                IRExpr::StringLiteral(label.as_ref().map(|s| s.clone()).unwrap_or_default()),
+                debug(body),
+                IRExpr::string(catch.as_ref().map(|c| format!("catch({}) {{ {} }}", hir.name(c.error_var), debug_str(&c.body))).unwrap_or_default()),
+                finally.as_deref().map_or_else(|| IRExpr::string(""), debug),
             ],
         },
-        Stmt::Continue(label) => IRStmt::Call {
-            func: "continue".to_string(),
-            args: vec![
-                IRExpr::StringLiteral(label.as_ref().map(|s| s.clone()).unwrap_or_default()),
-            ],
+        HirStmtKind::Throw(expr) => IRStmtKind::Call {
+            func: Symbol::intern("throw"),
+            args: vec![lower_expr(hir, expr)],
         },
-        Stmt::Switch { expression, cases, default } => IRStmt::Call {
-            func: "switch".to_string(),
+        HirStmtKind::Break(_) => IRStmtKind::Break,
+        HirStmtKind::Continue(_) => IRStmtKind::Continue,
+        HirStmtKind::Switch { expression, cases, default } => IRStmtKind::Call {
+            func: Symbol::intern("switch"),
             args: vec![
-                lower_expr(expression),
-                IRExpr::StringLiteral(cases.iter().map(|c| format!("case {}: {{ {} }}", lower_expr_to_string(&c.value), c.body.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";"))).collect::<Vec<_>>().join(";")),
-                IRExpr::StringLiteral(default.as_ref().map(|stmts| stmts.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join(";")).unwrap_or_default()),
+                lower_expr(hir, expression),
+                IRExpr::string(cases.iter().map(|c| format!("case {}: {{ {} }}", lower_expr_to_string(hir, &c.value), debug_str(&c.body))).collect::<Vec<_>>().join(";")),
+                default.as_deref().map_or_else(|| IRExpr::string(""), debug),
             ],
         },
-        Stmt::Reactive { name, expr } => IRStmt::Reactive {
-            name: name.clone(),
-            expr: lower_expr(expr),
+        HirStmtKind::Reactive { name, expr } => IRStmtKind::Reactive {
+            name: hir.name(*name),
+            expr: lower_expr(hir, expr),
         },
-        Stmt::Comprehension { target, iter, filter, expr } => IRStmt::Comprehension {
-            target: target.clone(),
-            iter: lower_expr(iter),
-            filter: filter.as_ref().map(|f| lower_expr(f)),
-            expr: lower_expr(expr),
+        HirStmtKind::Comprehension { target, iter, filter, expr } => IRStmtKind::Comprehension {
+            target: hir.name(*target),
+            iter: lower_expr(hir, iter),
+            filter: filter.as_ref().map(|f| lower_expr(hir, f)),
+            expr: lower_expr(hir, expr),
         },
-    }
+    };
+    IRStmt::new(kind).at(s.span)
 }
 
-fn lower_expr(e: &Expr) -> IRExpr {
-    match e {
-        Expr::StringLiteral(s) => IRExpr::StringLiteral(s.clone()),
-        Expr::NumberLiteral(n) => IRExpr::NumberLiteral(*n),
-        Expr::BooleanLiteral(b) => IRExpr::StringLiteral(b.to_string()),
-        Expr::NullLiteral => IRExpr::StringLiteral("null".to_string()),
-        Expr::UndefinedLiteral => IRExpr::StringLiteral("undefined".to_string()),
-        Expr::Identifier(s) => IRExpr::Identifier(s.clone()),
+/// Lowers `for (variable of iterable)` (or `in`) to a loop over the indices of
+/// the list, which is evaluated once into a temporary before the loop.
+fn lower_for_each(hir: &Hir, variable: DefId, iterable: &HirExpr, body: &[HirStmt], span: Span) -> IRStmtKind {
+    let variable = hir.def(variable);
+    let list = Symbol::intern(&format!("{}$list", variable.name));
+    let index = Symbol::intern(&format!("{}$index", variable.name));
+    let load_list = || IRExpr::load(list, iterable.ty.clone());
+    let load_index = || IRExpr::load(index, Type::Number);
+    let binary = |op: BinaryOp, left: IRExpr, right: IRExpr, ty: Type| {
+        IRExpr::new(IRExprKind::BinaryOp { op, left: Box::new(left), right: Box::new(right) }, ty)
+    };
+
+    let store = |target: Symbol, value: IRExpr| IRStmt::new(IRStmtKind::Store { target, value }).at(span);
+
+    let mut loop_body = vec![store(variable.name, std_call("list", "get", vec![load_list(), load_index()], variable.ty.clone()))];
+    loop_body.extend(lower_stmts(hir, body));
+    let length = std_call("list", "len", vec![load_list()], Type::Number);
+    IRStmtKind::Block(vec![
+        store(list, lower_expr(hir, iterable)),
+        store(index, IRExpr::number(0.0)),
+        IRStmt::new(IRStmtKind::Loop {
+            condition: binary(BinaryOp::LessThan, load_index(), length, Type::Boolean),
+            body: loop_body,
+            update: vec![store(index, binary(BinaryOp::Add, load_index(), IRExpr::number(1.0), Type::Number))],
+        })
+        .at(span),
+    ])
+}
+
+fn lower_expr(hir: &Hir, e: &HirExpr) -> IRExpr {
+    let lower = |e: &HirExpr| Box::new(lower_expr(hir, e));
+    let lower_all = |exprs: &[HirExpr]| exprs.iter().map(|e| lower_expr(hir, e)).collect::<Vec<_>>();
+    let kind = match &e.kind {
+        HirExprKind::StringLiteral(s) => IRExprKind::StringLiteral(s.clone()),
+        HirExprKind::NumberLiteral(n) => IRExprKind::NumberLiteral(*n),
+        HirExprKind::BooleanLiteral(b) => IRExprKind::BooleanLiteral(*b),
+        HirExprKind::CharLiteral(c) => IRExprKind::CharLiteral(*c),
+        HirExprKind::NullLiteral | HirExprKind::UndefinedLiteral => IRExprKind::Null,
+        HirExprKind::Name(def) => IRExprKind::Load(hir.name(*def)),
+        HirExprKind::CellAccess(name) => IRExprKind::Load(*name),
         // Lower List<T> construction: new List(args)
-        Expr::New { class, args } => {
-            if let Expr::Identifier(class_name) = &**class {
+        HirExprKind::New { class, args } => {
+            if let HirExprKind::Name(class_def) = &class.kind {
+                let class_name = hir.name(*class_def);
                 match class_name.as_str() {
-                    "List" => IRExpr::List(args.iter().map(lower_expr).collect()),
+                    "List" => IRExprKind::List(lower_all(args)),
                     "Map" => {
                         // Expect args as array of pairs or object literal
                         if args.len() == 1 {
-                            match &args[0] {
-                                Expr::ArrayLiteral(elements) => {
+                            match &args[0].kind {
+                                HirExprKind::ArrayLiteral(elements) => {
                                     let pairs = elements.iter().filter_map(|el| {
-                                        if let Expr::ArrayLiteral(pair) = el {
+                                        if let HirExprKind::ArrayLiteral(pair) = &el.kind {
                                             if pair.len() == 2 {
-                                                Some((lower_expr(&pair[0]), lower_expr(&pair[1])))
+                                                Some((lower_expr(hir, &pair[0]), lower_expr(hir, &pair[1])))
                                             } else { None }
                                         } else { None }
                                     }).collect();
-                                    IRExpr::Map(pairs)
+                                    IRExprKind::Map(pairs)
                                 }
-                                Expr::ObjectLiteral(props) => {
-                                    let pairs = props.iter().map(|p| (IRExpr::StringLiteral(p.key.clone()), lower_expr(&p.value))).collect();
-                                    IRExpr::Map(pairs)
+                                HirExprKind::ObjectLiteral(props) => {
+                                    let pairs = props.iter().map(|p| (lower_property_key(hir, &p.key), lower_expr(hir, &p.value))).collect();
+                                    IRExprKind::Map(pairs)
                                 }
-                                _ => IRExpr::StdCall { module: "map".to_string(), func: "new".to_string(), args: args.iter().map(lower_expr).collect() },
+                                _ => IRExprKind::StdCall { module: Symbol::intern("map"), func: Symbol::intern("new"), args: lower_all(args) },
                             }
                         } else {
-                            IRExpr::StdCall { module: "map".to_string(), func: "new".to_string(), args: args.iter().map(lower_expr).collect() }
+                            IRExprKind::StdCall { module: Symbol::intern("map"), func: Symbol::intern("new"), args: lower_all(args) }
                         }
                     }
-                    _ => IRExpr::StdCall { module: class_name.clone(), func: "new".to_string(), args: args.iter().map(lower_expr).collect() },
+                    // A class in this program is built by its lowered constructor
+                    _ if hir.classes.iter().any(|c| c.name == class_name) => IRExprKind::Call {
+                        func: function_symbol(Symbol::intern(&format!("{}_constructor", class_name))),
+                        args: lower_all(args),
+                    },
+                    _ => IRExprKind::StdCall { module: class_name, func: Symbol::intern("new"), args: lower_all(args) },
                 }
             } else {
-                IRExpr::StdCall { module: "<dynamic>".to_string(), func: "new".to_string(), args: args.iter().map(lower_expr).collect() }
+                IRExprKind::MethodCall { object: lower(class), method: Symbol::intern("new"), args: lower_all(args) }
             }
         }
-        // Lower method calls on stdlib types
-        Expr::MethodCall { object, method, args } => {
-            // Try to detect stdlib types by identifier
-            match &**object {
-                Expr::Identifier(obj_name) if obj_name == "io" || obj_name == "time" => {
-                    IRExpr::StdCall {
-                        module: obj_name.clone(),
-                        func: method.clone(),
-                        args: args.iter().map(lower_expr).collect(),
-                    }
-                }
-                _ => {
-                    // For List/Map/Option/Result, treat as stdcall with type as module
-                    let module = match &**object {
-                        Expr::Identifier(name) => name.clone(),
-                        _ => "<object>".to_string(),
-                    };
-                    IRExpr::StdCall {
-                        module,
-                        func: method.clone(),
-                        args: std::iter::once(lower_expr(object)).chain(args.iter().map(lower_expr)).collect(),
-                    }
+        // Lower method calls on stdlib modules; other objects keep the method call
+        HirExprKind::MethodCall { object, method, args } => match std_module(hir, object) {
+            Some(module) => IRExprKind::StdCall { module, func: *method, args: lower_all(args) },
+            None => IRExprKind::MethodCall { object: lower(object), method: *method, args: lower_all(args) },
+        },
+        // Lower direct stdlib calls (e.g., io::print, time::now, string::split)
+        HirExprKind::Call { func, args } => {
+            let std_function = match &func.kind {
+                HirExprKind::PropertyAccess { object, property } => std_module(hir, object).map(|module| (module, *property)),
+                _ => None,
+            };
+            match (std_function, &func.kind) {
+                (Some((module, func)), _) => IRExprKind::StdCall { module, func, args: lower_all(args) },
+                // Variables and parameters may hold a closure, which is called through its value
+                (None, HirExprKind::Name(def)) if matches!(hir.def(*def).kind, DefKind::Function | DefKind::Extern | DefKind::Global) => {
+                    IRExprKind::Call { func: callee_symbol(hir, *def), args: lower_all(args) }
                 }
+                _ => IRExprKind::CallIndirect { callee: lower(func), args: lower_all(args) },
             }
         }
-        // Lower direct stdlib calls (e.g., io::print, time::now)
-        Expr::Call { func, args } => {
-            if let Expr::PropertyAccess { object, property } = &**func {
-                if let Expr::Identifier(obj_name) = &**object {
-                    if obj_name == "io" || obj_name == "time" {
-                        return IRExpr::StdCall {
-                            module: obj_name.clone(),
-                            func: property.clone(),
-                            args: args.iter().map(lower_expr).collect(),
-                        };
-                    }
-                }
-            }
-            IRExpr::StringLiteral(format!("{}({})", lower_expr_to_string(func), args.iter().map(|a| lower_expr_to_string(a)).collect::<Vec<_>>().join(", ")))
+        HirExprKind::Comprehension { target, iter, filter, expr } => IRExprKind::Comprehension {
+            target: hir.name(*target),
+            iter: lower(iter),
+            filter: filter.as_deref().map(lower),
+            expr: lower(expr),
+        },
+        HirExprKind::Match { scrutinee, arms } => IRExprKind::Match {
+            scrutinee: lower(scrutinee),
+            arms: arms.iter().map(|arm| IRMatchArm {
+                pattern: lower_pattern(&arm.pattern),
+                guard: arm.guard.as_ref().map(|guard| lower_expr(hir, guard)),
+                body: lower_expr(hir, &arm.body),
+            }).collect(),
+        },
+        HirExprKind::BinaryOp { left, op, right } => IRExprKind::BinaryOp { op: op.clone(), left: lower(left), right: lower(right) },
+        HirExprKind::UnaryOp { op, operand } => IRExprKind::UnaryOp { op: op.clone(), operand: lower(operand) },
+        HirExprKind::If { condition, then, else_ } => IRExprKind::If { condition: lower(condition), then: lower(then), else_: lower(else_) },
+        HirExprKind::Concat { left, right } => return concat(vec![lower_expr(hir, left), lower_expr(hir, right)]).at(e.span),
+        HirExprKind::TemplateLiteral { parts } => {
+            return concat(parts.iter().map(|part| match part {
+                HirTemplatePart::String(s) => IRExpr::string(s.clone()),
+                HirTemplatePart::Expression(expr) => lower_expr(hir, expr),
+            }).collect())
+            .at(e.span)
         }
-        Expr::Comprehension { target, iter, filter, expr } => IRExpr::Comprehension {
-            target: target.clone(),
-            iter: Box::new(lower_expr(iter)),
-            filter: filter.as_ref().map(|f| Box::new(lower_expr(f))),
-            expr: Box::new(lower_expr(expr)),
+        HirExprKind::PropertyAccess { object, property } => IRExprKind::FieldAccess { object: lower(object), field: *property },
+        HirExprKind::ArrayAccess { array, index } => IRExprKind::Index { object: lower(array), index: lower(index) },
+        HirExprKind::ArrowFunction { params, body } => IRExprKind::Closure {
+            params: lower_params(hir, params),
+            body: lower_body(hir, params, body),
         },
-        Expr::CellAccess(_) => IRExpr::StringLiteral("<unsupported: cell access>".to_string()),
-        _ => {
-            // Fallback to previous lowering logic
-            // (copy the rest of the match arms from the original lower_expr)
-            match e {
-                Expr::BinaryOp { left, op, right } => IRExpr::StringLiteral(format!("({} {} {})",
-                    lower_expr_to_string(left),
-                    match op {
-                        BinaryOp::Add => "+",
-                        BinaryOp::Subtract => "-",
-                        BinaryOp::Multiply => "*",
-                        BinaryOp::Divide => "/",
-                        BinaryOp::Modulo => "%",
-                        BinaryOp::Power => "**",
-                        BinaryOp::Equal => "==",
-                        BinaryOp::NotEqual => "!=",
-                        BinaryOp::StrictEqual => "===",
-                        BinaryOp::StrictNotEqual => "!==",
-                        BinaryOp::LessThan => "<",
-                        BinaryOp::LessThanEqual => "<=",
-                        BinaryOp::GreaterThan => ">",
-                        BinaryOp::GreaterThanEqual => ">=",
-                        BinaryOp::And => "&&",
-                        BinaryOp::Or => "||",
-                        BinaryOp::BitwiseAnd => "&",
-                        BinaryOp::BitwiseOr => "|",
-                        BinaryOp::BitwiseXor => "^",
-                        BinaryOp::LeftShift => "<<",
-                        BinaryOp::RightShift => ">>",
-                        BinaryOp::UnsignedRightShift => ">>>",
-                    },
-                    lower_expr_to_string(right)
-                )),
-                Expr::UnaryOp { op, operand } => IRExpr::StringLiteral(format!("{}{}",
-                    match op {
-                        UnaryOp::Plus => "+",
-                        UnaryOp::Minus => "-",
-                        UnaryOp::Not => "!",
-                        UnaryOp::BitwiseNot => "~",
-                        UnaryOp::Increment => "++",
-                        UnaryOp::Decrement => "--",
-                        UnaryOp::TypeOf => "typeof ",
-                        UnaryOp::Void => "void ",
-                        UnaryOp::Delete => "delete ",
-                    },
-                    lower_expr_to_string(operand)
-                )),
-                Expr::If { condition, then, else_ } => IRExpr::StringLiteral(format!("if({}) {{ {} }} else {{ {} }}",
-                    lower_expr_to_string(condition),
-                    lower_expr_to_string(then),
-                    lower_expr_to_string(else_)
-                )),
-                Expr::Concat { left, right } => IRExpr::StringLiteral(format!("{}+{}",
-                    lower_expr_to_string(left),
-                    lower_expr_to_string(right)
-                )),
-                Expr::PropertyAccess { object, property } => IRExpr::StringLiteral(format!("{}.{}",
-                    lower_expr_to_string(object),
-                    property
-                )),
-                Expr::ArrayAccess { array, index } => IRExpr::StringLiteral(format!("{}[{}]",
-                    lower_expr_to_string(array),
-                    lower_expr_to_string(index)
-                )),
-                Expr::TemplateLiteral { parts } => IRExpr::StringLiteral(format!("`{}`",
-                    parts.iter().map(|p| match p {
-                        TemplatePart::String(s) => s.clone(),
-                        TemplatePart::Expression(expr) => format!("${{{}}}", lower_expr_to_string(expr)),
-                    }).collect::<Vec<_>>().join("")
-                )),
-                Expr::ArrowFunction { params, body } => IRExpr::StringLiteral(format!("({}) => {{ {} }}",
-                    params.iter().map(|p| p.name.clone()).collect::<Vec<_>>().join(", "),
-                    body.iter().map(|s| format!("{:?}", s)).collect::<Vec<_>>().join("; ")
-                )),
-                Expr::ArrayLiteral(elements) => IRExpr::List(elements.iter().map(lower_expr).collect()),
-                Expr::ObjectLiteral(properties) => IRExpr::Map(properties.iter().map(|p| (IRExpr::StringLiteral(p.key.clone()), lower_expr(&p.value))).collect()),
-                Expr::Await(inner) => IRExpr::Await(Box::new(lower_expr(inner))),
-                Expr::CellAccess(_) => IRExpr::StringLiteral("<unsupported: cell access>".to_string()),
-                _ => IRExpr::StringLiteral("<unsupported: expr>".to_string()),
+        HirExprKind::TypeAssert { value } => IRExprKind::Cast(lower(value)),
+        HirExprKind::ArrayLiteral(elements) => IRExprKind::List(lower_all(elements)),
+        HirExprKind::ObjectLiteral(properties) => IRExprKind::Map(properties.iter().map(|p| (lower_property_key(hir, &p.key), lower_expr(hir, &p.value))).collect()),
+        HirExprKind::Await(inner) => IRExprKind::Await(lower(inner)),
+        HirExprKind::Try(inner) => IRExprKind::Try(lower(inner)),
+        HirExprKind::OptionalChain { object, property, args } => return lower_optional_chain(hir, e, object, *property, args.as_deref()).at(e.span),
+        HirExprKind::Coalesce { value, default } => {
+            let some = Symbol::intern(&format!("{}?", lower_expr_to_string(hir, value)));
+            IRExprKind::Match {
+                scrutinee: lower(value),
+                arms: vec![
+                    IRMatchArm { pattern: some_pattern(some), guard: None, body: IRExpr::load(some, option_inner(&value.ty)) },
+                    IRMatchArm { pattern: IRPattern::Wildcard, guard: None, body: lower_expr(hir, default) },
+                ],
             }
         }
-    }
+        // A one-arm `match`, which narrowing proved the value matches
+        HirExprKind::Unwrap { value, variant } => {
+            let inner = Symbol::intern(&format!("{}?", lower_expr_to_string(hir, value)));
+            IRExprKind::Match {
+                scrutinee: lower(value),
+                arms: vec![IRMatchArm {
+                    pattern: IRPattern::Constructor { name: *variant, args: vec![IRPattern::Binding(inner)] },
+                    guard: None,
+                    body: IRExpr::load(inner, e.ty.clone()),
+                }],
+            }
+        }
+    };
+    IRExpr::new(kind, e.ty.clone()).at(e.span)
 }
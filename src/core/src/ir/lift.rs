@@ -0,0 +1,350 @@
+//! Lambda lifting: closures become functions of the module, so backends can
+//! compile them like any other function and refer to them through the
+//! function table.
+//!
+//! A lifted closure takes an environment object as its first parameter, with a
+//! field for each variable of the enclosing function it reads, holding the
+//! value the variable had when the closure was created: the same values a
+//! closure captures in SSA form. A closure that assigns a variable of the
+//! enclosing function, or reads one a closure assigns, shares it rather than
+//! copying it, so it is left for the backend as it is.
+//!
+//! Component state is not captured: it stays where the component's functions
+//! read and write it by name.
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+
+use super::fold::substitute_stmt;
+use super::generator::{IRExpr, IRExprKind, IRFunction, IRModule, IRParam, IRStmt, IRStmtKind};
+use super::pass::Pass;
+
+/// The name of a lifted closure's environment parameter.
+const ENV: &str = "$env";
+
+#[derive(Debug, Default)]
+pub struct LambdaLifting;
+
+impl Pass<IRModule> for LambdaLifting {
+    fn name(&self) -> &'static str {
+        "lambda lifting"
+    }
+
+    fn run(&mut self, module: &mut IRModule) -> bool {
+        // Names more than one function stores to, and component state, are
+        // shared rather than local to a function
+        let mut storers: HashMap<Symbol, usize> = HashMap::new();
+        for function in &module.functions {
+            let mut stored = HashSet::new();
+            stores_in(&function.body, &mut stored);
+            for name in stored {
+                *storers.entry(name).or_default() += 1;
+            }
+        }
+        let mut shared: HashSet<Symbol> = storers.into_iter().filter(|(_, functions)| *functions > 1).map(|(name, _)| name).collect();
+        shared.extend(module.functions.iter().flat_map(|function| function.reactive.iter().flat_map(|node| node.writes.iter().copied())));
+
+        let mut names: HashSet<Symbol> = module.functions.iter().map(|function| function.name).collect();
+        names.extend(module.externs.iter().map(|ext| ext.name));
+        let mut lifted = Vec::new();
+        for function in &mut module.functions {
+            let mut scope: HashSet<Symbol> = function.params.iter().map(|param| param.name).collect();
+            bindings_in(&function.body, &mut scope);
+            let mut lifter = Lifter {
+                function: function.name,
+                shared: &shared,
+                closure_stores: closure_stores(&function.body),
+                scopes: vec![scope],
+                names: &mut names,
+                lifted: &mut lifted,
+            };
+            let mut assigned: HashSet<Symbol> = function.params.iter().map(|param| param.name).collect();
+            lifter.stmts(&mut function.body, &mut assigned);
+        }
+        let changed = !lifted.is_empty();
+        module.functions.extend(lifted);
+        changed
+    }
+}
+
+/// The names `stmts` store to, including in closures.
+fn stores_in(stmts: &[IRStmt], out: &mut HashSet<Symbol>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            IRStmtKind::Store { target, .. } | IRStmtKind::Reactive { name: target, .. } | IRStmtKind::Comprehension { target, .. } => {
+                out.insert(*target);
+            }
+            _ => {}
+        }
+        let mut pending = stmt.exprs();
+        while let Some(expr) = pending.pop() {
+            if let IRExprKind::Closure { body, .. } = &expr.kind {
+                stores_in(body, out);
+            }
+            pending.extend(expr.children());
+        }
+        for block in stmt.blocks() {
+            stores_in(block, out);
+        }
+    }
+}
+
+/// The names closures in `stmts` store to.
+fn closure_stores(stmts: &[IRStmt]) -> HashSet<Symbol> {
+    let mut names = HashSet::new();
+    let mut pending: Vec<&[IRStmt]> = vec![stmts];
+    while let Some(stmts) = pending.pop() {
+        for stmt in stmts {
+            let mut exprs = stmt.exprs();
+            while let Some(expr) = exprs.pop() {
+                if let IRExprKind::Closure { body, .. } = &expr.kind {
+                    stores_in(body, &mut names);
+                }
+                exprs.extend(expr.children());
+            }
+            pending.extend(stmt.blocks());
+        }
+    }
+    names
+}
+
+/// The variables `stmts` bind outside closures: the names they store to, and
+/// those bound by match arms and comprehensions.
+fn bindings_in(stmts: &[IRStmt], out: &mut HashSet<Symbol>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            IRStmtKind::Store { target, .. } | IRStmtKind::Reactive { name: target, .. } | IRStmtKind::Comprehension { target, .. } => {
+                out.insert(*target);
+            }
+            _ => {}
+        }
+        let mut pending = stmt.exprs();
+        while let Some(expr) = pending.pop() {
+            match &expr.kind {
+                IRExprKind::Match { arms, .. } => out.extend(arms.iter().flat_map(|arm| arm.pattern.bindings())),
+                IRExprKind::Comprehension { target, .. } => {
+                    out.insert(*target);
+                }
+                _ => {}
+            }
+            pending.extend(expr.children());
+        }
+        for block in stmt.blocks() {
+            bindings_in(block, out);
+        }
+    }
+}
+
+/// The names loaded in `stmts` that `bound` does not hold and nothing in them
+/// binds around the load, with the types they are loaded at.
+fn free_loads(stmts: &[IRStmt], bound: &HashSet<Symbol>, out: &mut Vec<(Symbol, Type)>) {
+    for stmt in stmts {
+        for expr in stmt.exprs() {
+            free_loads_in_expr(expr, bound, out);
+        }
+        for block in stmt.blocks() {
+            free_loads(block, bound, out);
+        }
+    }
+}
+
+fn free_loads_in_expr(expr: &IRExpr, bound: &HashSet<Symbol>, out: &mut Vec<(Symbol, Type)>) {
+    match &expr.kind {
+        IRExprKind::Load(name) if !bound.contains(name) => out.push((*name, expr.ty.clone())),
+        IRExprKind::Closure { params, body } => {
+            let mut inner = bound.clone();
+            inner.extend(params.iter().map(|param| param.name));
+            stores_in(body, &mut inner);
+            return free_loads(body, &inner, out);
+        }
+        IRExprKind::Match { scrutinee, arms } => {
+            free_loads_in_expr(scrutinee, bound, out);
+            for arm in arms {
+                let mut inner = bound.clone();
+                inner.extend(arm.pattern.bindings());
+                if let Some(guard) = &arm.guard {
+                    free_loads_in_expr(guard, &inner, out);
+                }
+                free_loads_in_expr(&arm.body, &inner, out);
+            }
+            return;
+        }
+        IRExprKind::Comprehension { target, iter, filter, expr: element } => {
+            free_loads_in_expr(iter, bound, out);
+            let mut inner = bound.clone();
+            inner.insert(*target);
+            if let Some(filter) = filter {
+                free_loads_in_expr(filter, &inner, out);
+            }
+            return free_loads_in_expr(element, &inner, out);
+        }
+        _ => {}
+    }
+    for child in expr.children() {
+        free_loads_in_expr(child, bound, out);
+    }
+}
+
+struct Lifter<'a> {
+    /// The function whose closures are being lifted, which names them.
+    function: Symbol,
+    shared: &'a HashSet<Symbol>,
+    /// The variables of the function closures assign, which it keeps in memory.
+    closure_stores: HashSet<Symbol>,
+    /// The variables of the function, then of each closure around the one
+    /// being lifted.
+    scopes: Vec<HashSet<Symbol>>,
+    /// The names of the module's functions and externs, lifted ones included.
+    names: &'a mut HashSet<Symbol>,
+    lifted: &'a mut Vec<IRFunction>,
+}
+
+impl Lifter<'_> {
+    /// Lifts the closures in `stmts`, where `assigned` holds the variables
+    /// certainly assigned before them.
+    fn stmts(&mut self, stmts: &mut [IRStmt], assigned: &mut HashSet<Symbol>) {
+        for stmt in stmts {
+            for expr in stmt.exprs_mut() {
+                self.expr(expr, assigned);
+            }
+            match &mut stmt.kind {
+                IRStmtKind::Store { target, .. } | IRStmtKind::Reactive { name: target, .. } | IRStmtKind::Comprehension { target, .. } => {
+                    assigned.insert(*target);
+                }
+                // A block always runs; branches and loop bodies may not
+                IRStmtKind::Block(stmts) => self.stmts(stmts, assigned),
+                _ => {
+                    for block in stmt.blocks_mut() {
+                        self.stmts(block, &mut assigned.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &mut IRExpr, assigned: &HashSet<Symbol>) {
+        let loc = expr.loc;
+        match &mut expr.kind {
+            IRExprKind::Closure { params, body } => {
+                // What the closure stores is found before the closures inside it
+                // are lifted out of its body
+                let mut stored = HashSet::new();
+                stores_in(body, &mut stored);
+                let mut scope: HashSet<Symbol> = params.iter().map(|param| param.name).collect();
+                bindings_in(body, &mut scope);
+
+                // Closures inside this one are lifted first, so their captures
+                // are loads this one may capture in turn
+                let mut inner = assigned.clone();
+                inner.extend(params.iter().map(|param| param.name));
+                self.scopes.push(scope);
+                self.stmts(body, &mut inner);
+                self.scopes.pop();
+                if let Some(lifted) = self.lift(params, body, &expr.ty, assigned, &stored, loc) {
+                    expr.kind = lifted;
+                }
+                return;
+            }
+            IRExprKind::Match { scrutinee, arms } => {
+                self.expr(scrutinee, assigned);
+                for arm in arms {
+                    let mut bound = assigned.clone();
+                    bound.extend(arm.pattern.bindings());
+                    if let Some(guard) = &mut arm.guard {
+                        self.expr(guard, &bound);
+                    }
+                    self.expr(&mut arm.body, &bound);
+                }
+                return;
+            }
+            IRExprKind::Comprehension { target, iter, filter, expr: element } => {
+                self.expr(iter, assigned);
+                let mut bound = assigned.clone();
+                bound.insert(*target);
+                if let Some(filter) = filter {
+                    self.expr(filter, &bound);
+                }
+                self.expr(element, &bound);
+                return;
+            }
+            _ => {}
+        }
+        for child in expr.children_mut() {
+            self.expr(child, assigned);
+        }
+    }
+
+    /// Whether `name` is a variable of the function or of a closure around the
+    /// one being lifted, which the closure can be given a copy of.
+    fn is_outer(&self, name: Symbol) -> bool {
+        !self.shared.contains(&name) && self.scopes.iter().any(|scope| scope.contains(&name))
+    }
+
+    /// Lifts a closure, which stores to the names in `stored` and was lowered
+    /// from the source at `loc`, into a function of the module, returning what
+    /// replaces it, unless it shares a variable with the code around it.
+    fn lift(
+        &mut self,
+        params: &[IRParam],
+        body: &mut Vec<IRStmt>,
+        ty: &Type,
+        assigned: &HashSet<Symbol>,
+        stored: &HashSet<Symbol>,
+        loc: Option<Span>,
+    ) -> Option<IRExprKind> {
+        if stored.iter().any(|name| self.is_outer(*name)) {
+            return None;
+        }
+        let mut bound: HashSet<Symbol> = params.iter().map(|param| param.name).collect();
+        bound.extend(stored);
+        let mut loads = Vec::new();
+        free_loads(body, &bound, &mut loads);
+        let mut captures: Vec<(Symbol, Type)> = Vec::new();
+        for (name, ty) in loads {
+            if !self.is_outer(name) || captures.iter().any(|(captured, _)| *captured == name) {
+                continue;
+            }
+            // One a closure assigns has no value to copy, and one not yet
+            // assigned has none yet
+            if self.closure_stores.contains(&name) || !assigned.contains(&name) {
+                return None;
+            }
+            captures.push((name, ty));
+        }
+        captures.sort_by_key(|(name, _)| *name);
+
+        let env = Symbol::intern(ENV);
+        let env_type = Type::Object(
+            captures.iter().map(|(name, ty)| ObjectTypeProperty { name: *name, type_: ty.clone(), optional: false }).collect(),
+        );
+        let mut lifted_body = std::mem::take(body);
+        for (name, ty) in &captures {
+            let field = IRExprKind::FieldAccess { object: Box::new(IRExpr::load(env, env_type.clone())), field: *name };
+            for stmt in &mut lifted_body {
+                substitute_stmt(stmt, *name, &IRExpr::new(field.clone(), ty.clone()));
+            }
+        }
+
+        let name = (0..)
+            .map(|i| Symbol::intern(&format!("{}$closure{}", self.function, i)))
+            .find(|name| !self.names.contains(name))
+            .expect("some name is free");
+        self.names.insert(name);
+        let return_type = match ty {
+            Type::Function { return_type, .. } => Some((**return_type).clone()),
+            _ => None,
+        };
+        self.lifted.push(IRFunction {
+            name,
+            params: std::iter::once(IRParam { name: env, type_annotation: Some(env_type) }).chain(params.iter().cloned()).collect(),
+            return_type,
+            is_async: false,
+            export_name: None,
+            body: lifted_body,
+            reactive: Vec::new(),
+            loc,
+        });
+        let captures = captures.into_iter().map(|(name, ty)| (name, IRExpr::load(name, ty))).collect();
+        Some(IRExprKind::LiftedClosure { func: name, captures })
+    }
+}
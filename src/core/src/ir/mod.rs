@@ -1,5 +1,24 @@
 //! Intermediate Representation (IR) module for Gigli
 
 pub mod generator;
+pub mod ssa;
+pub mod pass;
+pub mod dce;
+pub mod fold;
+pub mod text;
+pub mod verify;
+pub mod data;
+pub mod cfg;
+pub mod cache;
+pub mod lift;
 
-pub use generator::{IRModule, IRFunction, IRStmt, IRExpr};
+pub use generator::{IRModule, IRFunction, IRExtern, IRParam, IRStmt, IRStmtKind, IRExpr, IRExprKind, IRMatchArm, IRPattern};
+pub use ssa::SsaFunction;
+pub use pass::{Pass, PassManager};
+pub use dce::DeadCodeElimination;
+pub use fold::ConstantFolding;
+pub use verify::{verify, VerifyError};
+pub use data::{DataLayout, DataRef};
+pub use cfg::{Cfg, Liveness};
+pub use cache::{CacheEntry, IrCache};
+pub use lift::LambdaLifting;
@@ -0,0 +1,59 @@
+//! Passes that transform IR, and a manager that runs them in order.
+
+use crate::profiling;
+
+/// A transformation of some unit of IR, such as an `IRModule` or an `SsaFunction`.
+pub trait Pass<T> {
+    /// A short name for the pass, shown in profiles.
+    fn name(&self) -> &'static str;
+
+    /// Transforms `unit` in place, returning whether anything changed.
+    fn run(&mut self, unit: &mut T) -> bool;
+}
+
+/// Runs registered passes over a unit of IR, in the order they were added.
+pub struct PassManager<T> {
+    passes: Vec<Box<dyn Pass<T>>>,
+}
+
+impl<T> PassManager<T> {
+    pub fn new() -> Self {
+        PassManager { passes: Vec::new() }
+    }
+
+    pub fn add(&mut self, pass: impl Pass<T> + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Runs every pass once, returning whether any of them changed `unit`.
+    pub fn run(&mut self, unit: &mut T) -> bool {
+        let mut changed = false;
+        for pass in &mut self.passes {
+            let _timer = profiling::scope_with("ir pass", pass.name());
+            changed |= pass.run(unit);
+        }
+        changed
+    }
+
+    /// Runs the passes until none of them changes `unit`, as one pass often
+    /// exposes work for another, or for at most `max_rounds` rounds. Returns
+    /// the number of rounds that changed something.
+    pub fn run_to_fixpoint(&mut self, unit: &mut T, max_rounds: usize) -> usize {
+        let mut rounds = 0;
+        while rounds < max_rounds && self.run(unit) {
+            rounds += 1;
+        }
+        rounds
+    }
+}
+
+impl<T> Default for PassManager<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,994 @@
+//! SSA form of IR functions, for optimization and for backends that want a
+//! control flow graph rather than structured statements. Every value is
+//! computed once, into a virtual register, by an instruction in a basic block;
+//! where control flow joins, phi nodes pick the value a variable had on the
+//! edge that was taken.
+//!
+//! Variables are a function's parameters and the names it stores to. Other
+//! names it reads (functions, externs and runtime values) are `Load`ed, as are
+//! variables a closure assigns, which must stay in memory to be shared with it.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::*;
+use crate::profiling;
+
+use super::generator::{IRExpr, IRExprKind, IRFunction, IRModule, IRParam, IRPattern, IRStmt, IRStmtKind};
+
+/// A virtual register, written `%n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Value(pub u32);
+
+/// A basic block, written `bbn`. `bb0` is the entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockId(pub u32);
+
+impl Value {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl BlockId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SsaFunction {
+    pub name: Symbol,
+    /// Each parameter, with the register holding its value on entry.
+    pub params: Vec<(Symbol, Value)>,
+    pub return_type: Option<Type>,
+    /// Indexed by `BlockId`.
+    pub blocks: Vec<BasicBlock>,
+    /// The type of each register, indexed by `Value`.
+    pub value_types: Vec<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub phis: Vec<Phi>,
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+/// Merges the values `variable` has at the end of each predecessor.
+#[derive(Debug, Clone)]
+pub struct Phi {
+    pub dest: Value,
+    pub variable: Symbol,
+    pub incoming: Vec<(BlockId, Value)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    /// Where the result goes; `None` for an instruction run only for its effects.
+    pub dest: Option<Value>,
+    pub op: Op,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    Char(char),
+    Null,
+}
+
+#[derive(Debug, Clone)]
+pub enum Op {
+    Const(Constant),
+    /// Reads a name that is not a register: a function, extern or runtime value,
+    /// or a variable a closure assigns.
+    Load(Symbol),
+    /// Writes a variable a closure assigns.
+    Store { name: Symbol, value: Value },
+    Binary { op: BinaryOp, left: Value, right: Value },
+    Unary { op: UnaryOp, operand: Value },
+    Concat(Vec<Value>),
+    FieldAccess { object: Value, field: Symbol },
+    Index { object: Value, index: Value },
+    Call { func: Symbol, args: Vec<Value> },
+    StdCall { module: Symbol, func: Symbol, args: Vec<Value> },
+    CallIndirect { callee: Value, args: Vec<Value> },
+    MethodCall { object: Value, method: Symbol, args: Vec<Value> },
+    /// An arrow function, with the values of the registers it reads, which it captures by value.
+    Closure { params: Vec<IRParam>, body: Vec<IRStmt>, captures: Vec<(Symbol, Value)> },
+    /// A lifted closure: the function `func`, with the environment it is called with.
+    LiftedClosure { func: Symbol, captures: Vec<(Symbol, Value)> },
+    Cast(Value),
+    Await(Value),
+    Option(Value),
+    Result { ok: Value, err: Value },
+    List(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    DomRef(Symbol),
+    /// Whether `value` matches `pattern`.
+    Matches { value: Value, pattern: IRPattern },
+    /// What `binding` is bound to when `value`, which matches `pattern`, is matched against it.
+    Extract { value: Value, pattern: IRPattern, binding: Symbol },
+    Render(Value),
+    EventBind { target: Symbol, event: Symbol, handler: Symbol },
+    DomOp { op: String, args: Vec<Value> },
+}
+
+#[derive(Debug, Clone)]
+pub enum Terminator {
+    Return(Option<Value>),
+    Jump(BlockId),
+    Branch { condition: Value, then: BlockId, else_: BlockId },
+    /// Ends a block no path leaves: one nothing reaches, or one after which a
+    /// `match` with no matching arm would fall out.
+    Unreachable,
+}
+
+impl Op {
+    /// The registers this operation reads.
+    pub fn operands(&self) -> Vec<Value> {
+        let mut operands = Vec::new();
+        self.visit_operands(|value| operands.push(value));
+        operands
+    }
+
+    /// Calls `f` with each register this operation reads, in order.
+    fn visit_operands(&self, mut f: impl FnMut(Value)) {
+        match self {
+            Op::Store { value, .. }
+            | Op::Unary { operand: value, .. }
+            | Op::FieldAccess { object: value, .. }
+            | Op::Cast(value)
+            | Op::Await(value)
+            | Op::Option(value)
+            | Op::Render(value)
+            | Op::Matches { value, .. }
+            | Op::Extract { value, .. } => f(*value),
+            Op::Binary { left, right, .. } | Op::Index { object: left, index: right } | Op::Result { ok: left, err: right } => {
+                f(*left);
+                f(*right);
+            }
+            Op::Concat(values) | Op::List(values) | Op::Call { args: values, .. } | Op::StdCall { args: values, .. } | Op::DomOp { args: values, .. } => {
+                values.iter().copied().for_each(f)
+            }
+            Op::CallIndirect { callee: first, args } | Op::MethodCall { object: first, args, .. } => {
+                f(*first);
+                args.iter().copied().for_each(f);
+            }
+            Op::Closure { captures, .. } | Op::LiftedClosure { captures, .. } => captures.iter().for_each(|(_, value)| f(*value)),
+            Op::Map(pairs) => pairs.iter().for_each(|(key, value)| {
+                f(*key);
+                f(*value);
+            }),
+            Op::Const(_) | Op::Load(_) | Op::DomRef(_) | Op::EventBind { .. } => {}
+        }
+    }
+
+    /// Replaces each register this operation reads with `f` of it.
+    pub fn map_operands(&mut self, mut f: impl FnMut(Value) -> Value) {
+        let mut map = |value: &mut Value| *value = f(*value);
+        match self {
+            Op::Store { value, .. }
+            | Op::Unary { operand: value, .. }
+            | Op::FieldAccess { object: value, .. }
+            | Op::Cast(value)
+            | Op::Await(value)
+            | Op::Option(value)
+            | Op::Render(value)
+            | Op::Matches { value, .. }
+            | Op::Extract { value, .. } => map(value),
+            Op::Binary { left, right, .. } | Op::Index { object: left, index: right } | Op::Result { ok: left, err: right } => {
+                map(left);
+                map(right);
+            }
+            Op::Concat(values) | Op::List(values) | Op::Call { args: values, .. } | Op::StdCall { args: values, .. } | Op::DomOp { args: values, .. } => {
+                values.iter_mut().for_each(map)
+            }
+            Op::CallIndirect { callee: first, args } | Op::MethodCall { object: first, args, .. } => {
+                map(first);
+                args.iter_mut().for_each(map);
+            }
+            Op::Closure { captures, .. } | Op::LiftedClosure { captures, .. } => {
+                captures.iter_mut().for_each(|(_, value)| map(value))
+            }
+            Op::Map(pairs) => pairs.iter_mut().for_each(|(key, value)| {
+                map(key);
+                map(value);
+            }),
+            Op::Const(_) | Op::Load(_) | Op::DomRef(_) | Op::EventBind { .. } => {}
+        }
+    }
+
+    /// Whether running this operation can be observed other than through its
+    /// result: calls, awaits, stores and DOM updates. Others may be removed when
+    /// their result is unused.
+    pub fn has_effects(&self) -> bool {
+        matches!(
+            self,
+            Op::Store { .. }
+                | Op::Call { .. }
+                | Op::StdCall { .. }
+                | Op::CallIndirect { .. }
+                | Op::MethodCall { .. }
+                | Op::Await(_)
+                | Op::Render(_)
+                | Op::EventBind { .. }
+                | Op::DomOp { .. }
+        )
+    }
+}
+
+impl Terminator {
+    /// The blocks control may go to next.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch { then, else_, .. } => vec![*then, *else_],
+            Terminator::Return(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    /// The register this terminator reads, if any.
+    pub fn operand(&self) -> Option<Value> {
+        match self {
+            Terminator::Return(value) => *value,
+            Terminator::Branch { condition, .. } => Some(*condition),
+            Terminator::Jump(_) | Terminator::Unreachable => None,
+        }
+    }
+
+    fn map_operand(&mut self, f: impl FnOnce(Value) -> Value) {
+        match self {
+            Terminator::Return(Some(value)) | Terminator::Branch { condition: value, .. } => *value = f(*value),
+            _ => {}
+        }
+    }
+}
+
+impl SsaFunction {
+    /// Converts a function to SSA form.
+    pub fn build(function: &IRFunction) -> SsaFunction {
+        let mut builder = Builder {
+            function: SsaFunction {
+                name: function.name,
+                params: Vec::new(),
+                return_type: function.return_type.clone(),
+                blocks: Vec::new(),
+                value_types: Vec::new(),
+            },
+            current: BlockId(0),
+            live: true,
+            env: HashMap::new(),
+            memory: closure_stores(&function.body),
+            loops: Vec::new(),
+        };
+        builder.current = builder.new_block();
+        for param in &function.params {
+            let value = builder.new_value(param.type_annotation.clone().unwrap_or(Type::Any));
+            builder.function.params.push((param.name, value));
+            builder.define(param.name, value);
+        }
+        builder.stmts(&function.body);
+        // Falling off the end returns nothing
+        builder.terminate(Terminator::Return(None));
+        let mut function = builder.function;
+        function.remove_trivial_phis();
+        function
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id.index()]
+    }
+
+    pub fn value_type(&self, value: Value) -> &Type {
+        &self.value_types[value.index()]
+    }
+
+    /// The predecessors of every block, indexed by `BlockId`.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (i, block) in self.blocks.iter().enumerate() {
+            for successor in block.terminator.successors() {
+                predecessors[successor.index()].push(BlockId(i as u32));
+            }
+        }
+        predecessors
+    }
+
+    /// Replaces each register read anywhere in the function with `f` of it.
+    pub fn map_operands(&mut self, mut f: impl FnMut(Value) -> Value) {
+        for block in &mut self.blocks {
+            for phi in &mut block.phis {
+                for (_, value) in &mut phi.incoming {
+                    *value = f(*value);
+                }
+            }
+            for instruction in &mut block.instructions {
+                instruction.op.map_operands(&mut f);
+            }
+            block.terminator.map_operand(&mut f);
+        }
+    }
+
+    /// Removes phis that merge a single value (other than themselves), which
+    /// construction leaves at joins and loop headers where a variable did not
+    /// change, and reads that value instead.
+    fn remove_trivial_phis(&mut self) {
+        loop {
+            let mut replaced = HashMap::new();
+            for block in &mut self.blocks {
+                block.phis.retain(|phi| {
+                    let mut sources = phi.incoming.iter().map(|(_, value)| *value).filter(|value| *value != phi.dest);
+                    match sources.next() {
+                        Some(first) if sources.all(|value| value == first) => {
+                            replaced.insert(phi.dest, first);
+                            false
+                        }
+                        _ => true,
+                    }
+                });
+            }
+            if replaced.is_empty() {
+                return;
+            }
+            self.map_operands(|mut value| {
+                while let Some(&next) = replaced.get(&value) {
+                    value = next;
+                }
+                value
+            });
+        }
+    }
+}
+
+/// Converts every function in a module to SSA form.
+pub fn build_module(module: &IRModule) -> Vec<SsaFunction> {
+    let _timer = profiling::scope("ssa construction");
+    module.functions.iter().map(SsaFunction::build).collect()
+}
+
+/// Variables in scope, with the register holding each one's current value.
+type Env = HashMap<Symbol, Value>;
+
+/// The edges out of a loop being built, with the variables on each.
+struct LoopFrame {
+    continue_block: BlockId,
+    exit_block: BlockId,
+    continues: Vec<(BlockId, Env)>,
+    breaks: Vec<(BlockId, Env)>,
+}
+
+struct Builder {
+    function: SsaFunction,
+    current: BlockId,
+    /// Whether any path reaches the current block; code after a `return` or
+    /// `break` is built into a block nothing jumps to.
+    live: bool,
+    env: Env,
+    /// Variables that closures assign, which are loaded and stored by name.
+    memory: HashSet<Symbol>,
+    loops: Vec<LoopFrame>,
+}
+
+impl Builder {
+    fn new_block(&mut self) -> BlockId {
+        self.function.blocks.push(BasicBlock { phis: Vec::new(), instructions: Vec::new(), terminator: Terminator::Unreachable });
+        BlockId(self.function.blocks.len() as u32 - 1)
+    }
+
+    fn new_value(&mut self, ty: Type) -> Value {
+        self.function.value_types.push(ty);
+        Value(self.function.value_types.len() as u32 - 1)
+    }
+
+    fn block_mut(&mut self, id: BlockId) -> &mut BasicBlock {
+        &mut self.function.blocks[id.index()]
+    }
+
+    fn emit(&mut self, op: Op, ty: Type) -> Value {
+        let dest = self.new_value(ty);
+        let current = self.current;
+        self.block_mut(current).instructions.push(Instruction { dest: Some(dest), op });
+        dest
+    }
+
+    fn emit_effect(&mut self, op: Op) {
+        let current = self.current;
+        self.block_mut(current).instructions.push(Instruction { dest: None, op });
+    }
+
+    fn define(&mut self, name: Symbol, value: Value) {
+        if self.memory.contains(&name) {
+            self.emit_effect(Op::Store { name, value });
+        } else {
+            self.env.insert(name, value);
+        }
+    }
+
+    /// Ends the current block, unless nothing reaches it: an unreachable block
+    /// keeps its `Unreachable` terminator, so it is nobody's predecessor.
+    fn terminate(&mut self, terminator: Terminator) {
+        if self.live {
+            let current = self.current;
+            self.block_mut(current).terminator = terminator;
+        }
+    }
+
+    /// Jumps to `target`, returning the edge taken with the variables on it.
+    fn jump_to(&mut self, target: BlockId) -> Option<(BlockId, Env)> {
+        let edge = self.live.then(|| (self.current, self.env.clone()));
+        self.terminate(Terminator::Jump(target));
+        edge
+    }
+
+    fn branch(&mut self, condition: Value, then: BlockId, else_: BlockId) {
+        self.terminate(Terminator::Branch { condition, then, else_ });
+    }
+
+    /// Continues building in `block`, which is reached only from the current block.
+    fn start(&mut self, block: BlockId, env: Env, live: bool) {
+        self.current = block;
+        self.env = env;
+        self.live = live;
+    }
+
+    /// Continues building after an unconditional exit, in a block nothing reaches.
+    fn start_dead(&mut self) {
+        self.current = self.new_block();
+        self.live = false;
+    }
+
+    /// Continues building in `block`, reached by the `incoming` edges: a variable
+    /// in scope on every edge stays in scope, through a phi if its value differs.
+    fn join(&mut self, block: BlockId, incoming: Vec<(BlockId, Env)>) {
+        self.current = block;
+        self.live = !incoming.is_empty();
+        let Some((_, first)) = incoming.first() else { return };
+        let mut names: Vec<Symbol> = first.keys().copied().filter(|name| incoming.iter().all(|(_, env)| env.contains_key(name))).collect();
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        let mut env = Env::new();
+        for name in names {
+            let sources: Vec<(BlockId, Value)> = incoming.iter().map(|(from, env)| (*from, env[&name])).collect();
+            let value = self.merge(name, sources);
+            env.insert(name, value);
+        }
+        self.env = env;
+    }
+
+    /// The value of `variable` in the current block, which is entered along the
+    /// `incoming` edges: the one value if they all carry it, otherwise a new phi.
+    fn merge(&mut self, variable: Symbol, incoming: Vec<(BlockId, Value)>) -> Value {
+        let first = incoming[0].1;
+        if incoming.iter().all(|(_, value)| *value == first) {
+            return first;
+        }
+        let dest = self.new_value(self.function.value_type(first).clone());
+        let current = self.current;
+        self.block_mut(current).phis.push(Phi { dest, variable, incoming });
+        dest
+    }
+
+    fn stmts(&mut self, stmts: &[IRStmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &IRStmt) {
+        match &stmt.kind {
+            IRStmtKind::Call { func, args } => {
+                let args = self.exprs(args);
+                self.emit_effect(Op::Call { func: *func, args });
+            }
+            IRStmtKind::Store { target, value } | IRStmtKind::Reactive { name: target, expr: value } => {
+                let value = self.expr(value);
+                self.define(*target, value);
+            }
+            IRStmtKind::Expr(expr) => {
+                self.expr(expr);
+            }
+            IRStmtKind::Await(expr) => {
+                let value = self.expr(expr);
+                self.emit_effect(Op::Await(value));
+            }
+            IRStmtKind::Comprehension { target, iter, filter, expr } => {
+                let value = self.comprehension(*target, iter, filter.as_ref(), expr, Type::Array(Box::new(expr.ty.clone())));
+                self.define(*target, value);
+            }
+            IRStmtKind::Render(expr) => {
+                let value = self.expr(expr);
+                self.emit_effect(Op::Render(value));
+            }
+            IRStmtKind::EventBind { target, event, handler } => {
+                self.emit_effect(Op::EventBind { target: *target, event: *event, handler: *handler })
+            }
+            IRStmtKind::DomOp { op, args } => {
+                let args = self.exprs(args);
+                self.emit_effect(Op::DomOp { op: op.clone(), args });
+            }
+            IRStmtKind::Return(value) => {
+                let value = value.as_ref().map(|value| self.expr(value));
+                self.terminate(Terminator::Return(value));
+                self.start_dead();
+            }
+            IRStmtKind::If { condition, then_block, else_block } => {
+                let condition = self.expr(condition);
+                let (then, else_, after) = (self.new_block(), self.new_block(), self.new_block());
+                self.branch(condition, then, else_);
+                let (env, live) = (self.env.clone(), self.live);
+                self.start(then, env.clone(), live);
+                self.stmts(then_block);
+                let from_then = self.jump_to(after);
+                self.start(else_, env, live);
+                self.stmts(else_block);
+                let from_else = self.jump_to(after);
+                self.join(after, from_then.into_iter().chain(from_else).collect());
+            }
+            IRStmtKind::Loop { condition, body, update } => self.build_loop(condition, body, update),
+            IRStmtKind::Block(stmts) => self.stmts(stmts),
+            IRStmtKind::Break | IRStmtKind::Continue => {
+                let edge = self.live.then(|| (self.current, self.env.clone()));
+                let Some(frame) = self.loops.last_mut() else { return };
+                let (target, edges) = match &stmt.kind {
+                    IRStmtKind::Break => (frame.exit_block, &mut frame.breaks),
+                    _ => (frame.continue_block, &mut frame.continues),
+                };
+                edges.extend(edge);
+                self.terminate(Terminator::Jump(target));
+                self.start_dead();
+            }
+        }
+    }
+
+    /// Builds a loop as a header that tests the condition, the body, a block
+    /// for the update that the body and `continue` reach, and an exit that the
+    /// header and `break` reach. Variables the loop assigns get a phi in the
+    /// header, whose second input is only known once the update is built.
+    fn build_loop(&mut self, condition: &IRExpr, body: &[IRStmt], update: &[IRStmt]) {
+        let header = self.new_block();
+        let body_block = self.new_block();
+        let continue_block = self.new_block();
+        let exit_block = self.new_block();
+
+        let entry = self.jump_to(header);
+        self.current = header;
+        let mut assigned = Vec::new();
+        stores_in(body, &mut assigned);
+        stores_in(update, &mut assigned);
+        assigned.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        assigned.dedup();
+        let mut header_phis = Vec::new();
+        for name in assigned {
+            let Some(&initial) = self.env.get(&name) else { continue };
+            let dest = self.new_value(self.function.value_type(initial).clone());
+            let incoming = entry.iter().map(|(from, _)| (*from, initial)).collect();
+            self.block_mut(header).phis.push(Phi { dest, variable: name, incoming });
+            self.env.insert(name, dest);
+            header_phis.push((name, dest));
+        }
+
+        let condition = self.expr(condition);
+        let leaves = self.live.then(|| (self.current, self.env.clone()));
+        self.branch(condition, body_block, exit_block);
+
+        self.loops.push(LoopFrame { continue_block, exit_block, continues: Vec::new(), breaks: Vec::new() });
+        let (env, live) = (self.env.clone(), self.live);
+        self.start(body_block, env, live);
+        self.stmts(body);
+        let from_body = self.jump_to(continue_block);
+        let frame = self.loops.pop().expect("the loop's frame was pushed above");
+
+        self.join(continue_block, from_body.into_iter().chain(frame.continues).collect());
+        self.stmts(update);
+        if let Some((from, env)) = self.jump_to(header) {
+            let phis = &mut self.function.blocks[header.index()].phis;
+            for (name, dest) in header_phis {
+                // A variable that went out of scope in the body keeps its header value
+                let value = env.get(&name).copied().unwrap_or(dest);
+                if let Some(phi) = phis.iter_mut().find(|phi| phi.dest == dest) {
+                    phi.incoming.push((from, value));
+                }
+            }
+        }
+
+        self.join(exit_block, leaves.into_iter().chain(frame.breaks).collect());
+    }
+
+    fn exprs(&mut self, exprs: &[IRExpr]) -> Vec<Value> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn expr(&mut self, expr: &IRExpr) -> Value {
+        let ty = expr.ty.clone();
+        let op = match &expr.kind {
+            IRExprKind::StringLiteral(s) => Op::Const(Constant::String(s.clone())),
+            IRExprKind::NumberLiteral(n) => Op::Const(Constant::Number(*n)),
+            IRExprKind::BooleanLiteral(b) => Op::Const(Constant::Boolean(*b)),
+            IRExprKind::CharLiteral(c) => Op::Const(Constant::Char(*c)),
+            IRExprKind::Null => Op::Const(Constant::Null),
+            IRExprKind::Load(name) => match self.env.get(name) {
+                Some(value) => return *value,
+                None => Op::Load(*name),
+            },
+            // `&&` and `||` only evaluate the right operand when the left does not decide
+            IRExprKind::BinaryOp { op: BinaryOp::And, left, right } => {
+                let left = self.expr(left);
+                return self.select(left, |b| b.expr(right), |_| left, ty);
+            }
+            IRExprKind::BinaryOp { op: BinaryOp::Or, left, right } => {
+                let left = self.expr(left);
+                return self.select(left, |_| left, |b| b.expr(right), ty);
+            }
+            IRExprKind::BinaryOp { op, left, right } => Op::Binary { op: op.clone(), left: self.expr(left), right: self.expr(right) },
+            IRExprKind::UnaryOp { op, operand } => Op::Unary { op: op.clone(), operand: self.expr(operand) },
+            IRExprKind::Concat(parts) => Op::Concat(self.exprs(parts)),
+            IRExprKind::FieldAccess { object, field } => Op::FieldAccess { object: self.expr(object), field: *field },
+            IRExprKind::Index { object, index } => Op::Index { object: self.expr(object), index: self.expr(index) },
+            IRExprKind::If { condition, then, else_ } => {
+                let condition = self.expr(condition);
+                return self.select(condition, |b| b.expr(then), |b| b.expr(else_), ty);
+            }
+            IRExprKind::Await(inner) => Op::Await(self.expr(inner)),
+            IRExprKind::Option(inner) => Op::Option(self.expr(inner)),
+            IRExprKind::Result { ok, err } => Op::Result { ok: self.expr(ok), err: self.expr(err) },
+            IRExprKind::List(items) => Op::List(self.exprs(items)),
+            IRExprKind::Map(pairs) => Op::Map(pairs.iter().map(|(key, value)| (self.expr(key), self.expr(value))).collect()),
+            IRExprKind::StdCall { module, func, args } => Op::StdCall { module: *module, func: *func, args: self.exprs(args) },
+            IRExprKind::Call { func, args } => Op::Call { func: *func, args: self.exprs(args) },
+            IRExprKind::CallIndirect { callee, args } => Op::CallIndirect { callee: self.expr(callee), args: self.exprs(args) },
+            IRExprKind::MethodCall { object, method, args } => {
+                Op::MethodCall { object: self.expr(object), method: *method, args: self.exprs(args) }
+            }
+            IRExprKind::Closure { params, body } => {
+                let mut names = Vec::new();
+                loads_in(body, &mut names);
+                let mut captures: Vec<(Symbol, Value)> = names
+                    .into_iter()
+                    .filter(|name| !params.iter().any(|param| param.name == *name))
+                    .filter_map(|name| self.env.get(&name).map(|value| (name, *value)))
+                    .collect();
+                captures.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+                captures.dedup_by_key(|(name, _)| *name);
+                Op::Closure { params: params.clone(), body: body.clone(), captures }
+            }
+            IRExprKind::LiftedClosure { func, captures } => Op::LiftedClosure {
+                func: *func,
+                captures: captures.iter().map(|(name, value)| (*name, self.expr(value))).collect(),
+            },
+            IRExprKind::Cast(inner) => Op::Cast(self.expr(inner)),
+            IRExprKind::Comprehension { target, iter, filter, expr } => {
+                return self.comprehension(*target, iter, filter.as_deref(), expr, ty)
+            }
+            IRExprKind::DomRef(name) => Op::DomRef(*name),
+            IRExprKind::Match { scrutinee, arms } => {
+                let scrutinee = self.expr(scrutinee);
+                let after = self.new_block();
+                let mut results = Vec::new();
+                for arm in arms {
+                    let (env, live) = (self.env.clone(), self.live);
+                    let next = self.new_block();
+                    // Binding and wildcard patterns match anything, so they need no test
+                    let refutable = !matches!(arm.pattern, IRPattern::Wildcard | IRPattern::Binding(_));
+                    if refutable {
+                        let test = self.emit(Op::Matches { value: scrutinee, pattern: arm.pattern.clone() }, Type::Boolean);
+                        let matched = self.new_block();
+                        self.branch(test, matched, next);
+                        self.start(matched, env.clone(), live);
+                    }
+                    for name in arm.pattern.bindings() {
+                        let value = match arm.pattern {
+                            IRPattern::Binding(_) => scrutinee,
+                            _ => {
+                                let op = Op::Extract { value: scrutinee, pattern: arm.pattern.clone(), binding: name };
+                                self.emit(op, load_type(name, &arm.body))
+                            }
+                        };
+                        self.env.insert(name, value);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        let guard = self.expr(guard);
+                        let guarded = self.new_block();
+                        self.branch(guard, guarded, next);
+                        let (env, live) = (self.env.clone(), self.live);
+                        self.start(guarded, env, live);
+                    }
+                    let value = self.expr(&arm.body);
+                    if self.live {
+                        results.push((self.current, value));
+                    }
+                    self.jump_to(after);
+                    // The next arm is tried when this one does not match
+                    self.start(next, env, live && (refutable || arm.guard.is_some()));
+                }
+                // Analysis proved some arm matches, so the last `next` keeps its `Unreachable`
+                self.live = false;
+                let edges = results.iter().map(|(from, _)| (*from, self.env.clone())).collect();
+                self.join(after, edges);
+                return self.result(results, ty);
+            }
+            IRExprKind::Try(inner) => {
+                let value = self.expr(inner);
+                let variant = if matches!(inner.ty, Type::Option(_)) { "Some" } else { "Ok" };
+                let binding = Symbol::intern("value");
+                let pattern = IRPattern::Constructor { name: Symbol::intern(variant), args: vec![IRPattern::Binding(binding)] };
+                let test = self.emit(Op::Matches { value, pattern: pattern.clone() }, Type::Boolean);
+                let (ok, failed) = (self.new_block(), self.new_block());
+                self.branch(test, ok, failed);
+                let (env, live) = (self.env.clone(), self.live);
+                // The `Err` or `None` is returned as it is
+                self.start(failed, env.clone(), live);
+                self.terminate(Terminator::Return(Some(value)));
+                self.start(ok, env, live);
+                Op::Extract { value, pattern, binding }
+            }
+        };
+        self.emit(op, ty)
+    }
+
+    /// Evaluates `then` if `condition` holds and `else_` otherwise, giving the
+    /// value of the one that ran.
+    fn select(
+        &mut self,
+        condition: Value,
+        then: impl FnOnce(&mut Self) -> Value,
+        else_: impl FnOnce(&mut Self) -> Value,
+        ty: Type,
+    ) -> Value {
+        let (then_block, else_block, after) = (self.new_block(), self.new_block(), self.new_block());
+        self.branch(condition, then_block, else_block);
+        let (env, live) = (self.env.clone(), self.live);
+        let mut results = Vec::new();
+        self.start(then_block, env.clone(), live);
+        let value = then(self);
+        results.extend(self.live.then_some((self.current, value)));
+        self.jump_to(after);
+        self.start(else_block, env.clone(), live);
+        let value = else_(self);
+        results.extend(self.live.then_some((self.current, value)));
+        self.jump_to(after);
+        let edges = results.iter().map(|(from, _)| (*from, env.clone())).collect();
+        self.join(after, edges);
+        self.result(results, ty)
+    }
+
+    /// The value of an expression whose branches, now joined, gave `results`.
+    fn result(&mut self, results: Vec<(BlockId, Value)>, ty: Type) -> Value {
+        match results.first() {
+            Some(_) if results.iter().all(|(_, value)| *value == results[0].1) => results[0].1,
+            Some(_) => {
+                let dest = self.new_value(ty);
+                let current = self.current;
+                self.block_mut(current).phis.push(Phi { dest, variable: Symbol::intern("result"), incoming: results });
+                dest
+            }
+            // No branch finishes, so the value is never used
+            None => self.emit(Op::Const(Constant::Null), ty),
+        }
+    }
+
+    /// Builds `[expr for target in iter if filter]` as a loop over the indices
+    /// of the list that pushes each kept element onto a new list.
+    fn comprehension(&mut self, target: Symbol, iter: &IRExpr, filter: Option<&IRExpr>, expr: &IRExpr, ty: Type) -> Value {
+        let std_call = |b: &mut Self, func: &str, args: Vec<Value>, ty: Type| {
+            b.emit(Op::StdCall { module: Symbol::intern("list"), func: Symbol::intern(func), args }, ty)
+        };
+        let list = self.expr(iter);
+        let result = self.emit(Op::List(Vec::new()), ty);
+        let zero = self.emit(Op::Const(Constant::Number(0.0)), Type::Number);
+        let length = std_call(self, "len", vec![list], Type::Number);
+        let (header, body, next, exit) = (self.new_block(), self.new_block(), self.new_block(), self.new_block());
+
+        let entry = self.jump_to(header);
+        let (env, live) = (self.env.clone(), self.live);
+        self.start(header, env.clone(), live);
+        let index = self.new_value(Type::Number);
+        let incoming = entry.iter().map(|(from, _)| (*from, zero)).collect();
+        self.block_mut(header).phis.push(Phi { dest: index, variable: Symbol::intern(&format!("{}$index", target)), incoming });
+        let more = self.emit(Op::Binary { op: BinaryOp::LessThan, left: index, right: length }, Type::Boolean);
+        self.branch(more, body, exit);
+
+        self.start(body, env.clone(), live);
+        let element_type = match &iter.ty {
+            Type::Array(element) => (**element).clone(),
+            _ => Type::Any,
+        };
+        let item = std_call(self, "get", vec![list, index], element_type);
+        self.env.insert(target, item);
+        if let Some(filter) = filter {
+            let keep = self.expr(filter);
+            let kept = self.new_block();
+            self.branch(keep, kept, next);
+            let (env, live) = (self.env.clone(), self.live);
+            self.start(kept, env, live);
+        }
+        let value = self.expr(expr);
+        self.emit_effect(Op::StdCall { module: Symbol::intern("list"), func: Symbol::intern("push"), args: vec![result, value] });
+        self.jump_to(next);
+
+        self.start(next, env.clone(), live);
+        let one = self.emit(Op::Const(Constant::Number(1.0)), Type::Number);
+        let advanced = self.emit(Op::Binary { op: BinaryOp::Add, left: index, right: one }, Type::Number);
+        if let Some((from, _)) = self.jump_to(header) {
+            self.block_mut(header).phis[0].incoming.push((from, advanced));
+        }
+        self.start(exit, env, live);
+        result
+    }
+}
+
+/// The names `stmts` store to, outside closures.
+fn stores_in(stmts: &[IRStmt], out: &mut Vec<Symbol>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            IRStmtKind::Store { target, .. } | IRStmtKind::Reactive { name: target, .. } | IRStmtKind::Comprehension { target, .. } => out.push(*target),
+            _ => {}
+        }
+        for block in stmt.blocks() {
+            stores_in(block, out);
+        }
+    }
+}
+
+/// The names read anywhere in `stmts`, including in closures nested in them.
+fn loads_in(stmts: &[IRStmt], out: &mut Vec<Symbol>) {
+    for stmt in stmts {
+        let mut pending = stmt.exprs();
+        while let Some(expr) = pending.pop() {
+            match &expr.kind {
+                IRExprKind::Load(name) => out.push(*name),
+                IRExprKind::Closure { body, .. } => loads_in(body, out),
+                _ => {}
+            }
+            pending.extend(expr.children());
+        }
+        for block in stmt.blocks() {
+            loads_in(block, out);
+        }
+    }
+}
+
+/// The names closures in `stmts` store to.
+fn closure_stores(stmts: &[IRStmt]) -> HashSet<Symbol> {
+    let mut names = HashSet::new();
+    let mut pending: Vec<&[IRStmt]> = vec![stmts];
+    while let Some(stmts) = pending.pop() {
+        for stmt in stmts {
+            let mut exprs = stmt.exprs();
+            while let Some(expr) = exprs.pop() {
+                if let IRExprKind::Closure { body, .. } = &expr.kind {
+                    let mut stored = Vec::new();
+                    stores_in(body, &mut stored);
+                    names.extend(stored);
+                    pending.push(body);
+                }
+                exprs.extend(expr.children());
+            }
+            pending.extend(stmt.blocks());
+        }
+    }
+    names
+}
+
+/// The type `expr` reads `name` as, or `any` if it does not read it.
+fn load_type(name: Symbol, expr: &IRExpr) -> Type {
+    let mut pending = vec![expr];
+    while let Some(expr) = pending.pop() {
+        if let IRExprKind::Load(loaded) = expr.kind {
+            if loaded == name {
+                return expr.ty.clone();
+            }
+        }
+        pending.extend(expr.children());
+    }
+    Type::Any
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constant::Number(n) => write!(f, "{}", n),
+            Constant::String(s) => write!(f, "{:?}", s),
+            Constant::Boolean(b) => write!(f, "{}", b),
+            Constant::Char(c) => write!(f, "{:?}", c),
+            Constant::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// Formats an operation as `name operands`, e.g. `binary add %1, %2`.
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn list(values: &[Value]) -> String {
+            values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+        }
+        match self {
+            Op::Const(constant) => write!(f, "const {}", constant),
+            Op::Load(name) => write!(f, "load {}", name),
+            Op::Store { name, value } => write!(f, "store {}, {}", name, value),
+            Op::Binary { op, left, right } => write!(f, "binary {:?} {}, {}", op, left, right),
+            Op::Unary { op, operand } => write!(f, "unary {:?} {}", op, operand),
+            Op::Concat(parts) => write!(f, "concat {}", list(parts)),
+            Op::FieldAccess { object, field } => write!(f, "field {}.{}", object, field),
+            Op::Index { object, index } => write!(f, "index {}[{}]", object, index),
+            Op::Call { func, args } => write!(f, "call {}({})", func, list(args)),
+            Op::StdCall { module, func, args } => write!(f, "call {}.{}({})", module, func, list(args)),
+            Op::CallIndirect { callee, args } => write!(f, "call {}({})", callee, list(args)),
+            Op::MethodCall { object, method, args } => write!(f, "call {}.{}({})", object, method, list(args)),
+            Op::Closure { params, captures, .. } => {
+                let params = params.iter().map(|p| p.name.to_string()).collect::<Vec<_>>().join(", ");
+                let captures = captures.iter().map(|(name, value)| format!("{} = {}", name, value)).collect::<Vec<_>>().join(", ");
+                write!(f, "closure ({}) [{}]", params, captures)
+            }
+            Op::LiftedClosure { func, captures } => {
+                let captures = captures.iter().map(|(name, value)| format!("{} = {}", name, value)).collect::<Vec<_>>().join(", ");
+                write!(f, "closure {} [{}]", func, captures)
+            }
+            Op::Cast(value) => write!(f, "cast {}", value),
+            Op::Await(value) => write!(f, "await {}", value),
+            Op::Option(value) => write!(f, "some {}", value),
+            Op::Result { ok, err } => write!(f, "result {}, {}", ok, err),
+            Op::List(items) => write!(f, "list [{}]", list(items)),
+            Op::Map(pairs) => {
+                let pairs = pairs.iter().map(|(key, value)| format!("{}: {}", key, value)).collect::<Vec<_>>().join(", ");
+                write!(f, "map {{{}}}", pairs)
+            }
+            Op::DomRef(name) => write!(f, "domref {}", name),
+            Op::Matches { value, pattern } => write!(f, "matches {}, {:?}", value, pattern),
+            Op::Extract { value, pattern, binding } => write!(f, "extract {} from {}, {:?}", binding, value, pattern),
+            Op::Render(value) => write!(f, "render {}", value),
+            Op::EventBind { target, event, handler } => write!(f, "bind {}.{} -> {}", target, event, handler),
+            Op::DomOp { op, args } => write!(f, "dom {}({})", op, list(args)),
+        }
+    }
+}
+
+/// Formats the function one block at a time, e.g.
+///
+/// ```text
+/// fn fn_abs(%0: number) {
+/// bb0:
+///     %1 = const 0
+///     %2 = binary LessThan %0, %1
+///     branch %2, bb1, bb2
+/// ...
+/// ```
+impl fmt::Display for SsaFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = self.params.iter().map(|(_, value)| format!("{}: {}", value, self.value_type(*value))).collect::<Vec<_>>().join(", ");
+        writeln!(f, "fn {}({}) {{", self.name, params)?;
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(i as u32))?;
+            for phi in &block.phis {
+                let incoming = phi.incoming.iter().map(|(from, value)| format!("[{}: {}]", from, value)).collect::<Vec<_>>().join(", ");
+                writeln!(f, "    {} = phi {} {}", phi.dest, phi.variable, incoming)?;
+            }
+            for instruction in &block.instructions {
+                match instruction.dest {
+                    Some(dest) => writeln!(f, "    {} = {}", dest, instruction.op)?,
+                    None => writeln!(f, "    {}", instruction.op)?,
+                }
+            }
+            match &block.terminator {
+                Terminator::Return(Some(value)) => writeln!(f, "    return {}", value)?,
+                Terminator::Return(None) => writeln!(f, "    return")?,
+                Terminator::Jump(target) => writeln!(f, "    jump {}", target)?,
+                Terminator::Branch { condition, then, else_ } => writeln!(f, "    branch {}, {}, {}", condition, then, else_)?,
+                Terminator::Unreachable => writeln!(f, "    unreachable")?,
+            }
+        }
+        write!(f, "}}")
+    }
+}
@@ -0,0 +1,1003 @@
+//! Textual form of the IR, for inspecting lowering output and writing IR-level
+//! tests. `gigli build --emit ir` writes it, and `parse` reads it back into the
+//! same module.
+//!
+//! The syntax is S-expressions, in the style of the WebAssembly text format.
+//! Every expression gives its type right after its operator:
+//!
+//! ```text
+//! (module
+//!   (extern "js" alert (param message string) (result void))
+//!   (func fn_add (export "add") (param a number) (param b number) (result number)
+//!     (return (add number (load number a) (mul number (load number b) (const number 2.0))))))
+//! ```
+//!
+//! A component's reactive dependency graph comes before its statements, one
+//! `(node ...)` per node. Names that are not plain identifiers are written as
+//! strings. `;;` starts a comment that runs to the end of the line.
+//!
+//! Source locations are not written, so IR read back has none.
+use std::fmt::{self, Write as _};
+
+use crate::ast::*;
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::profiling;
+
+use super::generator::{
+    IRExpr, IRExprKind, IRExtern, IRFunction, IRMatchArm, IRModule, IRParam, IRPattern, IRReactiveKind, IRReactiveNode, IRStmt,
+    IRStmtKind,
+};
+
+const BINARY_OPS: [(BinaryOp, &str); 22] = [
+    (BinaryOp::Add, "add"),
+    (BinaryOp::Subtract, "sub"),
+    (BinaryOp::Multiply, "mul"),
+    (BinaryOp::Divide, "div"),
+    (BinaryOp::Modulo, "rem"),
+    (BinaryOp::Power, "pow"),
+    (BinaryOp::Equal, "eq"),
+    (BinaryOp::NotEqual, "ne"),
+    (BinaryOp::StrictEqual, "strict_eq"),
+    (BinaryOp::StrictNotEqual, "strict_ne"),
+    (BinaryOp::LessThan, "lt"),
+    (BinaryOp::LessThanEqual, "le"),
+    (BinaryOp::GreaterThan, "gt"),
+    (BinaryOp::GreaterThanEqual, "ge"),
+    (BinaryOp::And, "and"),
+    (BinaryOp::Or, "or"),
+    (BinaryOp::BitwiseAnd, "bit_and"),
+    (BinaryOp::BitwiseOr, "bit_or"),
+    (BinaryOp::BitwiseXor, "bit_xor"),
+    (BinaryOp::LeftShift, "shl"),
+    (BinaryOp::RightShift, "shr"),
+    (BinaryOp::UnsignedRightShift, "shr_u"),
+];
+
+const UNARY_OPS: [(UnaryOp, &str); 9] = [
+    (UnaryOp::Plus, "plus"),
+    (UnaryOp::Minus, "neg"),
+    (UnaryOp::Not, "not"),
+    (UnaryOp::BitwiseNot, "bit_not"),
+    (UnaryOp::Increment, "inc"),
+    (UnaryOp::Decrement, "dec"),
+    (UnaryOp::TypeOf, "typeof"),
+    (UnaryOp::Void, "void"),
+    (UnaryOp::Delete, "delete"),
+];
+
+/// Types written as a bare word; any other bare word is a custom type.
+const PRIMITIVE_TYPES: [(Type, &str); 6] = [
+    (Type::String, "string"),
+    (Type::Char, "char"),
+    (Type::Number, "number"),
+    (Type::Boolean, "boolean"),
+    (Type::Void, "void"),
+    (Type::Any, "any"),
+];
+
+fn binary_op_name(op: &BinaryOp) -> &'static str {
+    BINARY_OPS.iter().find(|(known, _)| known == op).map(|(_, name)| *name).expect("every operator is listed")
+}
+
+fn unary_op_name(op: &UnaryOp) -> &'static str {
+    UNARY_OPS.iter().find(|(known, _)| known == op).map(|(_, name)| *name).expect("every operator is listed")
+}
+
+/// Formats the module in the textual IR syntax, which `parse` reads back.
+impl fmt::Display for IRModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "(module")?;
+        for ext in &self.externs {
+            writeln!(f, "  (extern {:?} {}{}{})", ext.module.as_str(), name(ext.name), params(&ext.params), result(&ext.return_type))?;
+        }
+        for function in &self.functions {
+            write!(f, "  (func {}", name(function.name))?;
+            if let Some(export) = function.export_name {
+                write!(f, " (export {:?})", export.as_str())?;
+            }
+            if function.is_async {
+                write!(f, " async")?;
+            }
+            write!(f, "{}{}", params(&function.params), result(&function.return_type))?;
+            let mut body = String::new();
+            for node in &function.reactive {
+                let _ = write!(body, "\n    {}", reactive_node(node));
+            }
+            write_stmts(&mut body, &function.body, 2);
+            writeln!(f, "{})", body)?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn name(symbol: Symbol) -> String {
+    let s = symbol.as_str();
+    let plain = s.chars().next().map_or(false, |c| c.is_alphabetic() || c == '_' || c == '$')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if plain {
+        s.to_string()
+    } else {
+        format!("{:?}", s)
+    }
+}
+
+fn params(params: &[IRParam]) -> String {
+    params
+        .iter()
+        .map(|param| match &param.type_annotation {
+            Some(ty) => format!(" (param {} {})", name(param.name), type_text(ty)),
+            None => format!(" (param {})", name(param.name)),
+        })
+        .collect()
+}
+
+fn result(return_type: &Option<Type>) -> String {
+    return_type.as_ref().map(|ty| format!(" (result {})", type_text(ty))).unwrap_or_default()
+}
+
+/// `(node kind (reads name...) (writes name...) expr)`, where the kind of a
+/// part of the view is followed by its slot, and an attribute's by its name.
+fn reactive_node(node: &IRReactiveNode) -> String {
+    let kind = match &node.kind {
+        IRReactiveKind::Cell => "cell".to_string(),
+        IRReactiveKind::Derived => "derived".to_string(),
+        IRReactiveKind::Effect => "effect".to_string(),
+        IRReactiveKind::Text { slot } => format!("text {}", slot),
+        IRReactiveKind::Attribute { slot, name: attribute } => format!("attribute {} {}", slot, name(*attribute)),
+        IRReactiveKind::Block { slot } => format!("block {}", slot),
+    };
+    let names = |names: &[Symbol]| names.iter().map(|n| format!(" {}", name(*n))).collect::<String>();
+    format!("(node {} (reads{}) (writes{}) {})", kind, names(&node.reads), names(&node.writes), expr(&node.expr))
+}
+
+fn type_text(ty: &Type) -> String {
+    let list = |types: &[Type]| types.iter().map(|ty| format!(" {}", type_text(ty))).collect::<String>();
+    if let Some((_, word)) = PRIMITIVE_TYPES.iter().find(|(primitive, _)| primitive == ty) {
+        return word.to_string();
+    }
+    match ty {
+        Type::Array(inner) => format!("(array {})", type_text(inner)),
+        Type::Object(props) => {
+            let fields: String = props
+                .iter()
+                .map(|p| format!(" ({} {} {})", if p.optional { "optional" } else { "field" }, name(p.name), type_text(&p.type_)))
+                .collect();
+            format!("(object{})", fields)
+        }
+        Type::Function { params, return_type } => format!("(fn (params{}) {})", list(params), type_text(return_type)),
+        Type::Union(types) => format!("(union{})", list(types)),
+        Type::Generic { name: generic, type_args } => format!("(generic {}{})", name(*generic), list(type_args)),
+        // A custom type named like a primitive or written as a string would read back as something else
+        Type::Custom(custom) if PRIMITIVE_TYPES.iter().any(|(_, word)| *word == custom.as_str()) || name(*custom) != custom.as_str() => {
+            format!("(custom {})", name(*custom))
+        }
+        Type::Custom(custom) => custom.to_string(),
+        Type::Option(inner) => format!("(option {})", type_text(inner)),
+        Type::Result(ok, err) => format!("(result {} {})", type_text(ok), type_text(err)),
+        Type::Ref(inner) => format!("(ref {})", type_text(inner)),
+        Type::MutRef(inner) => format!("(mut_ref {})", type_text(inner)),
+        _ => unreachable!("primitive types are handled above"),
+    }
+}
+
+fn write_stmts(out: &mut String, stmts: &[IRStmt], depth: usize) {
+    for stmt in stmts {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+        write_stmt(out, stmt, depth);
+    }
+}
+
+/// Writes a statement, with the statements nested in it on lines of their own.
+fn write_stmt(out: &mut String, stmt: &IRStmt, depth: usize) {
+    let block = |out: &mut String, head: &str, stmts: &[IRStmt]| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth + 1));
+        out.push('(');
+        out.push_str(head);
+        write_stmts(out, stmts, depth + 2);
+        out.push(')');
+    };
+    let _ = match &stmt.kind {
+        IRStmtKind::Call { func, args } => write!(out, "(call {}{})", name(*func), exprs(args)),
+        IRStmtKind::Store { target, value } => write!(out, "(store {} {})", name(*target), expr(value)),
+        IRStmtKind::Expr(value) => write!(out, "(expr {})", expr(value)),
+        IRStmtKind::If { condition, then_block, else_block } => {
+            let _ = write!(out, "(if {}", expr(condition));
+            block(out, "then", then_block);
+            block(out, "else", else_block);
+            write!(out, ")")
+        }
+        IRStmtKind::Loop { condition, body, update } => {
+            let _ = write!(out, "(loop {}", expr(condition));
+            block(out, "body", body);
+            block(out, "update", update);
+            write!(out, ")")
+        }
+        IRStmtKind::Block(stmts) => {
+            out.push_str("(block");
+            write_stmts(out, stmts, depth + 1);
+            write!(out, ")")
+        }
+        IRStmtKind::Break => write!(out, "(break)"),
+        IRStmtKind::Continue => write!(out, "(continue)"),
+        IRStmtKind::Await(value) => write!(out, "(await {})", expr(value)),
+        IRStmtKind::Reactive { name: target, expr: value } => write!(out, "(reactive {} {})", name(*target), expr(value)),
+        IRStmtKind::Comprehension { target, iter, filter, expr: element } => {
+            write!(out, "(comprehension {} {}{} {})", name(*target), expr(iter), filter_text(filter.as_ref()), expr(element))
+        }
+        IRStmtKind::Render(value) => write!(out, "(render {})", expr(value)),
+        IRStmtKind::EventBind { target, event, handler } => {
+            write!(out, "(event_bind {} {} {})", name(*target), name(*event), name(*handler))
+        }
+        IRStmtKind::DomOp { op, args } => write!(out, "(dom_op {:?}{})", op, exprs(args)),
+        IRStmtKind::Return(Some(value)) => write!(out, "(return {})", expr(value)),
+        IRStmtKind::Return(None) => write!(out, "(return)"),
+    };
+}
+
+fn filter_text(filter: Option<&IRExpr>) -> String {
+    filter.map(|filter| format!(" (where {})", expr(filter))).unwrap_or_default()
+}
+
+fn exprs(exprs: &[IRExpr]) -> String {
+    exprs.iter().map(|e| format!(" {}", expr(e))).collect()
+}
+
+/// Formats an expression on one line, closure bodies included.
+fn expr(e: &IRExpr) -> String {
+    let ty = type_text(&e.ty);
+    match &e.kind {
+        IRExprKind::StringLiteral(s) => format!("(const {} {:?})", ty, s),
+        IRExprKind::NumberLiteral(n) => format!("(const {} {:?})", ty, n),
+        IRExprKind::BooleanLiteral(b) => format!("(const {} {})", ty, b),
+        IRExprKind::CharLiteral(c) => format!("(const {} {:?})", ty, c),
+        IRExprKind::Null => format!("(const {} null)", ty),
+        IRExprKind::Load(loaded) => format!("(load {} {})", ty, name(*loaded)),
+        IRExprKind::BinaryOp { op, left, right } => format!("({} {} {} {})", binary_op_name(op), ty, expr(left), expr(right)),
+        IRExprKind::UnaryOp { op, operand } => format!("({} {} {})", unary_op_name(op), ty, expr(operand)),
+        IRExprKind::Concat(parts) => format!("(concat {}{})", ty, exprs(parts)),
+        IRExprKind::FieldAccess { object, field } => format!("(field {} {} {})", ty, expr(object), name(*field)),
+        IRExprKind::Index { object, index } => format!("(index {} {} {})", ty, expr(object), expr(index)),
+        IRExprKind::If { condition, then, else_ } => format!("(if {} {} {} {})", ty, expr(condition), expr(then), expr(else_)),
+        IRExprKind::Await(inner) => format!("(await {} {})", ty, expr(inner)),
+        IRExprKind::Option(inner) => format!("(some {} {})", ty, expr(inner)),
+        IRExprKind::Result { ok, err } => format!("(result {} {} {})", ty, expr(ok), expr(err)),
+        IRExprKind::List(items) => format!("(list {}{})", ty, exprs(items)),
+        IRExprKind::Map(pairs) => {
+            let entries: String = pairs.iter().map(|(key, value)| format!(" (entry {} {})", expr(key), expr(value))).collect();
+            format!("(map {}{})", ty, entries)
+        }
+        IRExprKind::StdCall { module, func, args } => format!("(std_call {} {} {}{})", ty, name(*module), name(*func), exprs(args)),
+        IRExprKind::Call { func, args } => format!("(call {} {}{})", ty, name(*func), exprs(args)),
+        IRExprKind::CallIndirect { callee, args } => format!("(call_indirect {} {}{})", ty, expr(callee), exprs(args)),
+        IRExprKind::MethodCall { object, method, args } => {
+            format!("(method_call {} {} {}{})", ty, expr(object), name(*method), exprs(args))
+        }
+        IRExprKind::Closure { params: closure_params, body } => {
+            let mut stmts = String::new();
+            for stmt in body {
+                stmts.push(' ');
+                // Nested blocks go on lines of their own; keep the closure on one
+                let mut text = String::new();
+                write_stmt(&mut text, stmt, 0);
+                stmts.push_str(&text.split('\n').map(str::trim).collect::<Vec<_>>().join(" "));
+            }
+            format!("(closure {}{} (body{}))", ty, params(closure_params), stmts)
+        }
+        IRExprKind::LiftedClosure { func, captures } => {
+            let captures: String = captures.iter().map(|(field, value)| format!(" (capture {} {})", name(*field), expr(value))).collect();
+            format!("(lifted_closure {} {}{})", ty, name(*func), captures)
+        }
+        IRExprKind::Cast(inner) => format!("(cast {} {})", ty, expr(inner)),
+        IRExprKind::Comprehension { target, iter, filter, expr: element } => {
+            format!("(comprehension {} {} {}{} {})", ty, name(*target), expr(iter), filter_text(filter.as_deref()), expr(element))
+        }
+        IRExprKind::DomRef(node) => format!("(dom_ref {} {})", ty, name(*node)),
+        IRExprKind::Match { scrutinee, arms } => {
+            let arms: String = arms
+                .iter()
+                .map(|arm| {
+                    let guard = arm.guard.as_ref().map(|guard| format!(" (guard {})", expr(guard))).unwrap_or_default();
+                    format!(" (arm {}{} {})", pattern(&arm.pattern), guard, expr(&arm.body))
+                })
+                .collect();
+            format!("(match {} {}{})", ty, expr(scrutinee), arms)
+        }
+        IRExprKind::Try(inner) => format!("(try {} {})", ty, expr(inner)),
+    }
+}
+
+fn pattern(p: &IRPattern) -> String {
+    let list = |patterns: &[IRPattern]| patterns.iter().map(|p| format!(" {}", pattern(p))).collect::<String>();
+    match p {
+        IRPattern::Wildcard => "_".to_string(),
+        IRPattern::Binding(binding) => format!("(bind {})", name(*binding)),
+        IRPattern::NumberLiteral(n) => format!("(number {:?})", n),
+        IRPattern::StringLiteral(s) => format!("(string {:?})", s),
+        IRPattern::Constructor { name: constructor, args } => format!("(constructor {}{})", name(*constructor), list(args)),
+        IRPattern::Object { fields } => {
+            let fields: String = fields.iter().map(|(field, p)| format!(" (field {} {})", name(*field), pattern(p))).collect();
+            format!("(object{})", fields)
+        }
+        IRPattern::Array { elements, rest } => {
+            let rest = rest.map(|rest| format!(" (rest {})", name(rest))).unwrap_or_default();
+            format!("(array{}{})", list(elements), rest)
+        }
+    }
+}
+
+/// Reads a module in the textual IR syntax. Errors point at the form that
+/// could not be read.
+pub fn parse(source: &str) -> Result<IRModule, Diagnostic> {
+    let _timer = profiling::scope("ir parse");
+    let mut tokens = tokenize(source)?.into_iter().peekable();
+    let tree = read_sexp(&mut tokens, source.len())?;
+    if let Some((_, span)) = tokens.next() {
+        return Err(error(span, "expected the end of the file after the module"));
+    }
+    read_module(&tree)
+}
+
+fn error(span: Span, message: impl Into<String>) -> Diagnostic {
+    Diagnostic::error(message).with_code(codes::SYNTAX_ERROR).with_primary(span, "here")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Atom(String),
+    String(String),
+    Char(char),
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, Span)>, Diagnostic> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            ';' if matches!(chars.peek(), Some((_, ';'))) => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            '(' => tokens.push((Token::Open, Span::new(start, start + 1))),
+            ')' => tokens.push((Token::Close, Span::new(start, start + 1))),
+            '"' | '\'' => {
+                let quote = c;
+                let mut text = String::new();
+                let mut closed = false;
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            let escaped = match chars.next() {
+                                Some((_, 'n')) => '\n',
+                                Some((_, 't')) => '\t',
+                                Some((_, 'r')) => '\r',
+                                Some((_, '0')) => '\0',
+                                Some((_, 'u')) => {
+                                    // `\u{1f600}`, as Rust escapes it
+                                    let mut digits = String::new();
+                                    while let Some((_, c)) = chars.next_if(|(_, c)| *c != '}') {
+                                        if c != '{' {
+                                            digits.push(c);
+                                        }
+                                    }
+                                    chars.next();
+                                    u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+                                }
+                                Some((_, other)) => other,
+                                None => break,
+                            };
+                            text.push(escaped);
+                        }
+                        c if c == quote => {
+                            closed = true;
+                            break;
+                        }
+                        c => text.push(c),
+                    }
+                }
+                let span = Span::new(start, chars.peek().map_or(source.len(), |(i, _)| *i));
+                let invalid = |message: &str| Diagnostic::error(message).with_code(codes::INVALID_TOKEN).with_primary(span, "here");
+                if !closed {
+                    return Err(invalid("unterminated literal"));
+                }
+                let token = if quote == '"' {
+                    Token::String(text)
+                } else {
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Token::Char(c),
+                        _ => return Err(invalid("a char literal holds one character")),
+                    }
+                };
+                tokens.push((token, span));
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.next_if(|(_, c)| !c.is_whitespace() && !"()\"';".contains(*c)) {
+                    end = i + c.len_utf8();
+                }
+                tokens.push((Token::Atom(source[start..end].to_string()), Span::new(start, end)));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// A parsed S-expression: a parenthesized list, a bare word, or a literal.
+enum Sexp {
+    List(Vec<Sexp>, Span),
+    Atom(String, Span),
+    String(String, Span),
+    Char(char, Span),
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<(Token, Span)>>;
+
+fn read_sexp(tokens: &mut Tokens, end: usize) -> Result<Sexp, Diagnostic> {
+    match tokens.next() {
+        None => Err(error(Span::new(end, end), "expected '(', found the end of the file")),
+        Some((Token::Open, open)) => {
+            let mut items = Vec::new();
+            loop {
+                match tokens.peek() {
+                    None => return Err(error(open, "this '(' is never closed")),
+                    Some((Token::Close, close)) => {
+                        let span = open.to(*close);
+                        tokens.next();
+                        return Ok(Sexp::List(items, span));
+                    }
+                    Some(_) => items.push(read_sexp(tokens, end)?),
+                }
+            }
+        }
+        Some((Token::Close, span)) => Err(error(span, "unexpected ')'")),
+        Some((Token::Atom(atom), span)) => Ok(Sexp::Atom(atom, span)),
+        Some((Token::String(s), span)) => Ok(Sexp::String(s, span)),
+        Some((Token::Char(c), span)) => Ok(Sexp::Char(c, span)),
+    }
+}
+
+impl Sexp {
+    fn span(&self) -> Span {
+        match self {
+            Sexp::List(_, span) | Sexp::Atom(_, span) | Sexp::String(_, span) | Sexp::Char(_, span) => *span,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Sexp::List(items, _) => match items.first() {
+                Some(Sexp::Atom(head, _)) => format!("'({} ...)'", head),
+                _ => "a list".to_string(),
+            },
+            Sexp::Atom(atom, _) => format!("'{}'", atom),
+            Sexp::String(..) => "a string".to_string(),
+            Sexp::Char(..) => "a char".to_string(),
+        }
+    }
+
+    fn expected(&self, what: &str) -> Diagnostic {
+        error(self.span(), format!("expected {}, found {}", what, self.describe()))
+    }
+
+    /// The items of a list whose first item is a word, and that word.
+    fn form(&self, what: &str) -> Result<(&str, Form<'_>), Diagnostic> {
+        match self {
+            Sexp::List(items, span) => match items.first() {
+                Some(Sexp::Atom(head, _)) => Ok((head, Form { items: items[1..].iter().peekable(), span: *span })),
+                _ => Err(self.expected(what)),
+            },
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    fn word(&self, what: &str) -> Result<&str, Diagnostic> {
+        match self {
+            Sexp::Atom(atom, _) => Ok(atom),
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    /// A name, bare or written as a string.
+    fn name(&self, what: &str) -> Result<Symbol, Diagnostic> {
+        match self {
+            Sexp::Atom(atom, _) => Ok(Symbol::intern(atom)),
+            Sexp::String(s, _) => Ok(Symbol::intern(s)),
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    fn string(&self, what: &str) -> Result<&str, Diagnostic> {
+        match self {
+            Sexp::String(s, _) => Ok(s),
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    fn number(&self, what: &str) -> Result<f64, Diagnostic> {
+        self.word(what)?.parse().map_err(|_| self.expected(what))
+    }
+}
+
+/// The items of a list after its head, read from the front.
+struct Form<'a> {
+    items: std::iter::Peekable<std::slice::Iter<'a, Sexp>>,
+    span: Span,
+}
+
+impl<'a> Form<'a> {
+    fn next(&mut self, what: &str) -> Result<&'a Sexp, Diagnostic> {
+        self.items.next().ok_or_else(|| error(Span::new(self.span.end - 1, self.span.end), format!("expected {}", what)))
+    }
+
+    fn next_opt(&mut self) -> Option<&'a Sexp> {
+        self.items.next()
+    }
+
+    /// Takes the next item if it is a list headed by `head`.
+    fn next_if_head(&mut self, head: &str) -> Option<&'a Sexp> {
+        self.items.next_if(|item| matches!(item, Sexp::List(items, _) if matches!(items.first(), Some(Sexp::Atom(word, _)) if word == head)))
+    }
+
+    fn finish(mut self) -> Result<(), Diagnostic> {
+        match self.items.next() {
+            Some(extra) => Err(error(extra.span(), format!("unexpected {}", extra.describe()))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn read_module(tree: &Sexp) -> Result<IRModule, Diagnostic> {
+    let (head, mut form) = tree.form("'(module ...)'")?;
+    if head != "module" {
+        return Err(tree.expected("'(module ...)'"));
+    }
+    let mut module = IRModule { functions: Vec::new(), externs: Vec::new() };
+    while let Some(item) = form.next_opt() {
+        let (head, mut form) = item.form("a function or extern")?;
+        match head {
+            "func" => module.functions.push(read_function(form)?),
+            "extern" => {
+                let abi = form.next("the extern's module")?.string("the extern's module")?;
+                let ext_name = form.next("the extern's name")?.name("the extern's name")?;
+                let (params, return_type) = read_signature(&mut form)?;
+                form.finish()?;
+                module.externs.push(IRExtern { module: Symbol::intern(abi), name: ext_name, params, return_type });
+            }
+            _ => return Err(item.expected("'(func ...)' or '(extern ...)'")),
+        }
+    }
+    Ok(module)
+}
+
+fn read_function(mut form: Form) -> Result<IRFunction, Diagnostic> {
+    let name = form.next("the function's name")?.name("the function's name")?;
+    let export_name = match form.next_if_head("export") {
+        Some(export) => {
+            let (_, mut export) = export.form("an export")?;
+            let export_name = export.next("the exported name")?.string("the exported name")?;
+            export.finish()?;
+            Some(Symbol::intern(export_name))
+        }
+        None => None,
+    };
+    let is_async = form.items.next_if(|item| matches!(item, Sexp::Atom(word, _) if word == "async")).is_some();
+    let (params, return_type) = read_signature(&mut form)?;
+    let mut reactive = Vec::new();
+    while let Some(node) = form.next_if_head("node") {
+        reactive.push(read_reactive_node(node)?);
+    }
+    let body = read_stmts(form)?;
+    Ok(IRFunction { name, params, return_type, is_async, export_name, body, reactive, loc: None })
+}
+
+fn read_reactive_node(sexp: &Sexp) -> Result<IRReactiveNode, Diagnostic> {
+    let (_, mut form) = sexp.form("a reactive node")?;
+    let kind_item = form.next("the node's kind")?;
+    let mut slot = || -> Result<u32, Diagnostic> {
+        let slot = form.next("the slot")?;
+        slot.word("the slot")?.parse().map_err(|_| slot.expected("the slot"))
+    };
+    let kind = match kind_item.word("the node's kind")? {
+        "cell" => IRReactiveKind::Cell,
+        "derived" => IRReactiveKind::Derived,
+        "effect" => IRReactiveKind::Effect,
+        "text" => IRReactiveKind::Text { slot: slot()? },
+        "attribute" => {
+            let slot = slot()?;
+            IRReactiveKind::Attribute { slot, name: form.next("the attribute's name")?.name("the attribute's name")? }
+        }
+        "block" => IRReactiveKind::Block { slot: slot()? },
+        _ => return Err(kind_item.expected("'cell', 'derived', 'effect', 'text', 'attribute' or 'block'")),
+    };
+    let reads = read_names(&mut form, "reads")?;
+    let writes = read_names(&mut form, "writes")?;
+    let expr = read_expr(form.next("the node's expression")?)?;
+    form.finish()?;
+    Ok(IRReactiveNode { kind, reads, writes, expr })
+}
+
+/// Reads `(head name...)`, the next item of `form`.
+fn read_names(form: &mut Form, head: &str) -> Result<Vec<Symbol>, Diagnostic> {
+    let what = format!("'({} ...)'", head);
+    let list = form.next(&what)?;
+    match list.form(&what)? {
+        (found, mut names) if found == head => {
+            let mut read = Vec::new();
+            while let Some(name) = names.next_opt() {
+                read.push(name.name("a variable")?);
+            }
+            Ok(read)
+        }
+        _ => Err(list.expected(&what)),
+    }
+}
+
+/// Reads `(param name type?)...` and an optional `(result type)`.
+fn read_signature(form: &mut Form) -> Result<(Vec<IRParam>, Option<Type>), Diagnostic> {
+    let params = read_params(form)?;
+    let return_type = match form.next_if_head("result") {
+        Some(result) => {
+            let (_, mut result) = result.form("a result type")?;
+            let ty = read_type(result.next("the result type")?)?;
+            result.finish()?;
+            Some(ty)
+        }
+        None => None,
+    };
+    Ok((params, return_type))
+}
+
+fn read_params(form: &mut Form) -> Result<Vec<IRParam>, Diagnostic> {
+    let mut params = Vec::new();
+    while let Some(param) = form.next_if_head("param") {
+        let (_, mut param) = param.form("a parameter")?;
+        let name = param.next("the parameter's name")?.name("the parameter's name")?;
+        let type_annotation = param.next_opt().map(read_type).transpose()?;
+        param.finish()?;
+        params.push(IRParam { name, type_annotation });
+    }
+    Ok(params)
+}
+
+fn read_type(sexp: &Sexp) -> Result<Type, Diagnostic> {
+    if let Sexp::Atom(word, _) = sexp {
+        return Ok(match PRIMITIVE_TYPES.iter().find(|(_, primitive)| primitive == word) {
+            Some((ty, _)) => ty.clone(),
+            None => Type::Custom(Symbol::intern(word)),
+        });
+    }
+    let (head, mut form) = sexp.form("a type")?;
+    let ty = match head {
+        "array" => Type::Array(Box::new(read_type(form.next("the element type")?)?)),
+        "object" => {
+            let mut props = Vec::new();
+            while let Some(field) = form.next_opt() {
+                let (head, mut field_form) = field.form("a field")?;
+                let optional = match head {
+                    "field" => false,
+                    "optional" => true,
+                    _ => return Err(field.expected("'(field ...)' or '(optional ...)'")),
+                };
+                let name = field_form.next("the field's name")?.name("the field's name")?;
+                let type_ = read_type(field_form.next("the field's type")?)?;
+                field_form.finish()?;
+                props.push(ObjectTypeProperty { name, type_, optional });
+            }
+            Type::Object(props)
+        }
+        "fn" => {
+            let params = form.next("the parameter types")?;
+            let (head, mut params_form) = params.form("'(params ...)'")?;
+            if head != "params" {
+                return Err(params.expected("'(params ...)'"));
+            }
+            let mut params = Vec::new();
+            while let Some(param) = params_form.next_opt() {
+                params.push(read_type(param)?);
+            }
+            Type::Function { params, return_type: Box::new(read_type(form.next("the return type")?)?) }
+        }
+        "union" => Type::Union(read_types(&mut form)?),
+        "generic" => {
+            let name = form.next("the generic type's name")?.name("the generic type's name")?;
+            Type::Generic { name, type_args: read_types(&mut form)? }
+        }
+        "custom" => Type::Custom(form.next("the type's name")?.name("the type's name")?),
+        "option" => Type::Option(Box::new(read_type(form.next("the inner type")?)?)),
+        "result" => {
+            let ok = read_type(form.next("the ok type")?)?;
+            Type::Result(Box::new(ok), Box::new(read_type(form.next("the error type")?)?))
+        }
+        "ref" => Type::Ref(Box::new(read_type(form.next("the referenced type")?)?)),
+        "mut_ref" => Type::MutRef(Box::new(read_type(form.next("the referenced type")?)?)),
+        _ => return Err(sexp.expected("a type")),
+    };
+    form.finish()?;
+    Ok(ty)
+}
+
+fn read_types(form: &mut Form) -> Result<Vec<Type>, Diagnostic> {
+    let mut types = Vec::new();
+    while let Some(ty) = form.next_opt() {
+        types.push(read_type(ty)?);
+    }
+    Ok(types)
+}
+
+fn read_stmts(mut form: Form) -> Result<Vec<IRStmt>, Diagnostic> {
+    let mut stmts = Vec::new();
+    while let Some(stmt) = form.next_opt() {
+        stmts.push(read_stmt(stmt)?);
+    }
+    Ok(stmts)
+}
+
+/// Reads `(head stmt...)`, the next item of `form`.
+fn read_block(form: &mut Form, head: &str) -> Result<Vec<IRStmt>, Diagnostic> {
+    let what = format!("'({} ...)'", head);
+    let block = form.next(&what)?;
+    match block.form(&what)? {
+        (found, stmts) if found == head => read_stmts(stmts),
+        _ => Err(block.expected(&what)),
+    }
+}
+
+fn read_stmt(sexp: &Sexp) -> Result<IRStmt, Diagnostic> {
+    let (head, mut form) = sexp.form("a statement")?;
+    let stmt = match head {
+        "call" => {
+            let func = form.next("the function's name")?.name("the function's name")?;
+            IRStmtKind::Call { func, args: read_exprs(&mut form)? }
+        }
+        "store" => {
+            let target = form.next("the variable")?.name("the variable")?;
+            IRStmtKind::Store { target, value: read_expr(form.next("the value")?)? }
+        }
+        "expr" => IRStmtKind::Expr(read_expr(form.next("an expression")?)?),
+        "if" => {
+            let condition = read_expr(form.next("the condition")?)?;
+            let then_block = read_block(&mut form, "then")?;
+            IRStmtKind::If { condition, then_block, else_block: read_block(&mut form, "else")? }
+        }
+        "loop" => {
+            let condition = read_expr(form.next("the condition")?)?;
+            let body = read_block(&mut form, "body")?;
+            IRStmtKind::Loop { condition, body, update: read_block(&mut form, "update")? }
+        }
+        "block" => return Ok(IRStmt::new(IRStmtKind::Block(read_stmts(form)?))),
+        "break" => IRStmtKind::Break,
+        "continue" => IRStmtKind::Continue,
+        "await" => IRStmtKind::Await(read_expr(form.next("an expression")?)?),
+        "reactive" => {
+            let name = form.next("the variable")?.name("the variable")?;
+            IRStmtKind::Reactive { name, expr: read_expr(form.next("the value")?)? }
+        }
+        "comprehension" => {
+            let (target, iter, filter, expr) = read_comprehension(&mut form)?;
+            IRStmtKind::Comprehension { target, iter, filter, expr }
+        }
+        "render" => IRStmtKind::Render(read_expr(form.next("an expression")?)?),
+        "event_bind" => {
+            let target = form.next("the target")?.name("the target")?;
+            let event = form.next("the event")?.name("the event")?;
+            IRStmtKind::EventBind { target, event, handler: form.next("the handler")?.name("the handler")? }
+        }
+        "dom_op" => {
+            let op = form.next("the operation")?.string("the operation")?.to_string();
+            IRStmtKind::DomOp { op, args: read_exprs(&mut form)? }
+        }
+        "return" => IRStmtKind::Return(form.next_opt().map(read_expr).transpose()?),
+        _ => return Err(sexp.expected("a statement")),
+    };
+    form.finish()?;
+    Ok(IRStmt::new(stmt))
+}
+
+/// Reads `target iter (where filter)? expr`.
+fn read_comprehension(form: &mut Form) -> Result<(Symbol, IRExpr, Option<IRExpr>, IRExpr), Diagnostic> {
+    let target = form.next("the loop variable")?.name("the loop variable")?;
+    let iter = read_expr(form.next("the list")?)?;
+    let filter = match form.next_if_head("where") {
+        Some(filter) => {
+            let (_, mut filter) = filter.form("a filter")?;
+            let condition = read_expr(filter.next("the filter's condition")?)?;
+            filter.finish()?;
+            Some(condition)
+        }
+        None => None,
+    };
+    Ok((target, iter, filter, read_expr(form.next("the element")?)?))
+}
+
+fn read_exprs(form: &mut Form) -> Result<Vec<IRExpr>, Diagnostic> {
+    let mut exprs = Vec::new();
+    while let Some(expr) = form.next_opt() {
+        exprs.push(read_expr(expr)?);
+    }
+    Ok(exprs)
+}
+
+fn read_expr(sexp: &Sexp) -> Result<IRExpr, Diagnostic> {
+    let (head, mut form) = sexp.form("an expression")?;
+    let ty = read_type(form.next("the expression's type")?)?;
+    let operand = |form: &mut Form, what: &str| -> Result<Box<IRExpr>, Diagnostic> { Ok(Box::new(read_expr(form.next(what)?)?)) };
+    let kind = if let Some((op, _)) = BINARY_OPS.iter().find(|(_, name)| *name == head) {
+        let left = operand(&mut form, "the left operand")?;
+        IRExprKind::BinaryOp { op: op.clone(), left, right: operand(&mut form, "the right operand")? }
+    } else if let Some((op, _)) = UNARY_OPS.iter().find(|(_, name)| *name == head) {
+        IRExprKind::UnaryOp { op: op.clone(), operand: operand(&mut form, "the operand")? }
+    } else {
+        match head {
+            "const" => {
+                let value = form.next("the value")?;
+                match value {
+                    Sexp::String(s, _) => IRExprKind::StringLiteral(s.clone()),
+                    Sexp::Char(c, _) => IRExprKind::CharLiteral(*c),
+                    Sexp::Atom(atom, _) if atom == "true" || atom == "false" => IRExprKind::BooleanLiteral(atom == "true"),
+                    Sexp::Atom(atom, _) if atom == "null" => IRExprKind::Null,
+                    _ => IRExprKind::NumberLiteral(value.number("a literal")?),
+                }
+            }
+            "load" => IRExprKind::Load(form.next("the name")?.name("the name")?),
+            "concat" => IRExprKind::Concat(read_exprs(&mut form)?),
+            "field" => {
+                let object = operand(&mut form, "the object")?;
+                IRExprKind::FieldAccess { object, field: form.next("the field")?.name("the field")? }
+            }
+            "index" => {
+                let object = operand(&mut form, "the object")?;
+                IRExprKind::Index { object, index: operand(&mut form, "the index")? }
+            }
+            "if" => {
+                let condition = operand(&mut form, "the condition")?;
+                let then = operand(&mut form, "the value if it holds")?;
+                IRExprKind::If { condition, then, else_: operand(&mut form, "the value otherwise")? }
+            }
+            "await" => IRExprKind::Await(operand(&mut form, "an expression")?),
+            "some" => IRExprKind::Option(operand(&mut form, "an expression")?),
+            "result" => {
+                let ok = operand(&mut form, "the ok value")?;
+                IRExprKind::Result { ok, err: operand(&mut form, "the error value")? }
+            }
+            "list" => IRExprKind::List(read_exprs(&mut form)?),
+            "map" => {
+                let mut pairs = Vec::new();
+                while let Some(entry) = form.next_opt() {
+                    let (head, mut entry_form) = entry.form("'(entry ...)'")?;
+                    if head != "entry" {
+                        return Err(entry.expected("'(entry ...)'"));
+                    }
+                    let key = read_expr(entry_form.next("the key")?)?;
+                    pairs.push((key, read_expr(entry_form.next("the value")?)?));
+                    entry_form.finish()?;
+                }
+                IRExprKind::Map(pairs)
+            }
+            "std_call" => {
+                let module = form.next("the module")?.name("the module")?;
+                let func = form.next("the function")?.name("the function")?;
+                IRExprKind::StdCall { module, func, args: read_exprs(&mut form)? }
+            }
+            "call" => {
+                let func = form.next("the function's name")?.name("the function's name")?;
+                IRExprKind::Call { func, args: read_exprs(&mut form)? }
+            }
+            "call_indirect" => {
+                let callee = operand(&mut form, "the callee")?;
+                IRExprKind::CallIndirect { callee, args: read_exprs(&mut form)? }
+            }
+            "method_call" => {
+                let object = operand(&mut form, "the object")?;
+                let method = form.next("the method")?.name("the method")?;
+                IRExprKind::MethodCall { object, method, args: read_exprs(&mut form)? }
+            }
+            "closure" => {
+                let params = read_params(&mut form)?;
+                IRExprKind::Closure { params, body: read_block(&mut form, "body")? }
+            }
+            "lifted_closure" => {
+                let func = form.next("the function's name")?.name("the function's name")?;
+                let mut captures = Vec::new();
+                while let Some(capture) = form.next_opt() {
+                    let (head, mut capture_form) = capture.form("'(capture ...)'")?;
+                    if head != "capture" {
+                        return Err(capture.expected("'(capture ...)'"));
+                    }
+                    let field = capture_form.next("the captured variable")?.name("the captured variable")?;
+                    captures.push((field, read_expr(capture_form.next("the captured value")?)?));
+                    capture_form.finish()?;
+                }
+                IRExprKind::LiftedClosure { func, captures }
+            }
+            "cast" => IRExprKind::Cast(operand(&mut form, "an expression")?),
+            "comprehension" => {
+                let (target, iter, filter, expr) = read_comprehension(&mut form)?;
+                IRExprKind::Comprehension { target, iter: Box::new(iter), filter: filter.map(Box::new), expr: Box::new(expr) }
+            }
+            "dom_ref" => IRExprKind::DomRef(form.next("the node")?.name("the node")?),
+            "match" => {
+                let scrutinee = operand(&mut form, "the matched value")?;
+                let mut arms = Vec::new();
+                while let Some(arm) = form.next_opt() {
+                    let (head, mut arm_form) = arm.form("'(arm ...)'")?;
+                    if head != "arm" {
+                        return Err(arm.expected("'(arm ...)'"));
+                    }
+                    let pattern = read_pattern(arm_form.next("a pattern")?)?;
+                    let guard = match arm_form.next_if_head("guard") {
+                        Some(guard) => {
+                            let (_, mut guard) = guard.form("a guard")?;
+                            let condition = read_expr(guard.next("the guard's condition")?)?;
+                            guard.finish()?;
+                            Some(condition)
+                        }
+                        None => None,
+                    };
+                    let body = read_expr(arm_form.next("the arm's value")?)?;
+                    arm_form.finish()?;
+                    arms.push(IRMatchArm { pattern, guard, body });
+                }
+                IRExprKind::Match { scrutinee, arms }
+            }
+            "try" => IRExprKind::Try(operand(&mut form, "an expression")?),
+            _ => return Err(sexp.expected("an expression")),
+        }
+    };
+    form.finish()?;
+    Ok(IRExpr::new(kind, ty))
+}
+
+fn read_pattern(sexp: &Sexp) -> Result<IRPattern, Diagnostic> {
+    if matches!(sexp, Sexp::Atom(word, _) if word == "_") {
+        return Ok(IRPattern::Wildcard);
+    }
+    let (head, mut form) = sexp.form("a pattern")?;
+    let pattern = match head {
+        "bind" => IRPattern::Binding(form.next("the name")?.name("the name")?),
+        "number" => IRPattern::NumberLiteral(form.next("a number")?.number("a number")?),
+        "string" => IRPattern::StringLiteral(form.next("a string")?.string("a string")?.to_string()),
+        "constructor" => {
+            let name = form.next("the constructor")?.name("the constructor")?;
+            let mut args = Vec::new();
+            while let Some(arg) = form.next_opt() {
+                args.push(read_pattern(arg)?);
+            }
+            IRPattern::Constructor { name, args }
+        }
+        "object" => {
+            let mut fields = Vec::new();
+            while let Some(field) = form.next_opt() {
+                let (head, mut field_form) = field.form("'(field ...)'")?;
+                if head != "field" {
+                    return Err(field.expected("'(field ...)'"));
+                }
+                let name = field_form.next("the field's name")?.name("the field's name")?;
+                fields.push((name, read_pattern(field_form.next("the field's pattern")?)?));
+                field_form.finish()?;
+            }
+            IRPattern::Object { fields }
+        }
+        "array" => {
+            let mut elements = Vec::new();
+            let mut rest = None;
+            while let Some(element) = form.next_opt() {
+                match element.form("a pattern") {
+                    Ok(("rest", mut rest_form)) => {
+                        rest = Some(rest_form.next("the rest's name")?.name("the rest's name")?);
+                        rest_form.finish()?;
+                        break;
+                    }
+                    _ => elements.push(read_pattern(element)?),
+                }
+            }
+            IRPattern::Array { elements, rest }
+        }
+        _ => return Err(sexp.expected("a pattern")),
+    };
+    form.finish()?;
+    Ok(pattern)
+}
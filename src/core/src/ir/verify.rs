@@ -0,0 +1,330 @@
+//! Checks of the invariants code generation relies on, so that a lowering or
+//! optimization bug is reported where it happens rather than as invalid WASM.
+//!
+//! The checks are that:
+//! - a function's local variables are assigned on every path before they are read;
+//! - literals and operators have the types their operations give them;
+//! - calls to functions of the module and to externs pass no more arguments than
+//!   they take (exactly as many, for externs);
+//! - `break` and `continue` are inside a loop, and a function with a result
+//!   returns on every path instead of running off its end.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::*;
+use crate::profiling;
+
+use super::generator::{IRExpr, IRExprKind, IRFunction, IRModule, IRParam, IRStmt, IRStmtKind};
+
+/// A broken invariant, in the function where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    pub function: Symbol,
+    pub message: String,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "in '{}': {}", self.function, self.message)
+    }
+}
+
+/// Checks every function of `module`, returning all the problems found.
+pub fn verify(module: &IRModule) -> Result<(), Vec<VerifyError>> {
+    let _timer = profiling::scope("ir verification");
+    let mut callees = HashMap::new();
+    for function in &module.functions {
+        callees.insert(function.name, Callee { params: function.params.len(), exact: false });
+    }
+    for ext in &module.externs {
+        callees.insert(ext.name, Callee { params: ext.params.len(), exact: true });
+    }
+
+    // A name more than one function stores to is shared state, such as a
+    // component's, which any of them may read first
+    let mut storers: HashMap<Symbol, HashSet<Symbol>> = HashMap::new();
+    for function in &module.functions {
+        let mut stored = HashSet::new();
+        stores_in(&function.body, true, &mut stored);
+        for name in stored {
+            storers.entry(name).or_default().insert(function.name);
+        }
+    }
+    let shared: HashSet<Symbol> = storers.into_iter().filter(|(_, functions)| functions.len() > 1).map(|(name, _)| name).collect();
+
+    let mut errors = Vec::new();
+    for function in &module.functions {
+        let mut verifier = Verifier { function: function.name, callees: &callees, errors: &mut errors, locals: HashSet::new(), loops: 0 };
+        verifier.function(function, &shared);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+struct Callee {
+    params: usize,
+    /// Whether fewer arguments than parameters are also an error; functions of
+    /// the module may have parameters with default values.
+    exact: bool,
+}
+
+/// Variables definitely assigned at a point of a function.
+type Defined = HashSet<Symbol>;
+
+struct Verifier<'a> {
+    function: Symbol,
+    callees: &'a HashMap<Symbol, Callee>,
+    errors: &'a mut Vec<VerifyError>,
+    /// The variables of the function or closure being checked.
+    locals: HashSet<Symbol>,
+    /// How many loops the code being checked is in.
+    loops: usize,
+}
+
+impl Verifier<'_> {
+    fn error(&mut self, message: String) {
+        self.errors.push(VerifyError { function: self.function, message });
+    }
+
+    fn function(&mut self, function: &IRFunction, shared: &HashSet<Symbol>) {
+        let mut stored = HashSet::new();
+        stores_in(&function.body, false, &mut stored);
+        self.locals = stored.into_iter().filter(|name| !shared.contains(name)).collect();
+        let mut defined: Defined = function.params.iter().map(|param| param.name).collect();
+        let falls_through = self.stmts(&function.body, &mut defined);
+        let has_result = !matches!(function.return_type, None | Some(Type::Void) | Some(Type::Any));
+        if falls_through && has_result {
+            let ty = function.return_type.as_ref().expect("has a result");
+            self.error(format!("the function can reach its end without returning a {}", ty));
+        }
+    }
+
+    /// Checks `stmts`, which run with `defined` assigned, and adds the variables
+    /// they assign. Returns whether control can run past their end; statements
+    /// after one it cannot run past are never run, and so are not checked.
+    fn stmts(&mut self, stmts: &[IRStmt], defined: &mut Defined) -> bool {
+        stmts.iter().all(|stmt| self.stmt(stmt, defined))
+    }
+
+    fn stmt(&mut self, stmt: &IRStmt, defined: &mut Defined) -> bool {
+        match &stmt.kind {
+            IRStmtKind::Call { func, args } => {
+                self.call(*func, args.len());
+                self.exprs(args, defined);
+            }
+            IRStmtKind::Store { target, value } | IRStmtKind::Reactive { name: target, expr: value } => {
+                self.expr(value, defined);
+                defined.insert(*target);
+            }
+            IRStmtKind::Expr(value) | IRStmtKind::Await(value) | IRStmtKind::Render(value) => self.expr(value, defined),
+            IRStmtKind::If { condition, then_block, else_block } => {
+                self.expr(condition, defined);
+                let mut then_defined = defined.clone();
+                let mut else_defined = defined.clone();
+                let then_falls = self.stmts(then_block, &mut then_defined);
+                let else_falls = self.stmts(else_block, &mut else_defined);
+                *defined = match (then_falls, else_falls) {
+                    (true, true) => then_defined.intersection(&else_defined).copied().collect(),
+                    (true, false) => then_defined,
+                    (false, true) => else_defined,
+                    (false, false) => return false,
+                };
+            }
+            IRStmtKind::Loop { condition, body, update } => {
+                self.expr(condition, defined);
+                // The body may not run, and `continue` skips its end, so only what
+                // was assigned before the loop is known inside and after it
+                self.loops += 1;
+                self.stmts(body, &mut defined.clone());
+                self.stmts(update, &mut defined.clone());
+                self.loops -= 1;
+                let forever = matches!(condition.kind, IRExprKind::BooleanLiteral(true));
+                return !forever || breaks_out(body) || breaks_out(update);
+            }
+            IRStmtKind::Block(stmts) => return self.stmts(stmts, defined),
+            IRStmtKind::Break | IRStmtKind::Continue => {
+                if self.loops == 0 {
+                    let keyword = if matches!(stmt.kind, IRStmtKind::Break) { "break" } else { "continue" };
+                    self.error(format!("'{}' outside of a loop", keyword));
+                }
+                return false;
+            }
+            IRStmtKind::Comprehension { target, iter, filter, expr } => {
+                self.comprehension(*target, iter, filter.as_ref(), expr, defined);
+                defined.insert(*target);
+            }
+            IRStmtKind::EventBind { .. } => {}
+            IRStmtKind::DomOp { args, .. } => self.exprs(args, defined),
+            IRStmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expr(value, defined);
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    fn exprs(&mut self, exprs: &[IRExpr], defined: &Defined) {
+        for expr in exprs {
+            self.expr(expr, defined);
+        }
+    }
+
+    fn expr(&mut self, expr: &IRExpr, defined: &Defined) {
+        match &expr.kind {
+            IRExprKind::Load(name) => {
+                if self.locals.contains(name) && !defined.contains(name) {
+                    self.error(format!("'{}' is read before it is assigned", name));
+                }
+            }
+            IRExprKind::StringLiteral(_) => self.literal(expr, Type::String),
+            IRExprKind::NumberLiteral(_) => self.literal(expr, Type::Number),
+            IRExprKind::BooleanLiteral(_) => self.literal(expr, Type::Boolean),
+            IRExprKind::CharLiteral(_) => self.literal(expr, Type::Char),
+            IRExprKind::BinaryOp { op, left, right } => {
+                if let Some(expected) = binary_type(op, &left.ty, &right.ty) {
+                    if expr.ty != expected && expr.ty != Type::Any {
+                        self.error(format!("{:?} of {} and {} gives a {}, but is typed {}", op, left.ty, right.ty, expected, expr.ty));
+                    }
+                }
+            }
+            IRExprKind::Concat(_) if expr.ty != Type::String => self.error(format!("a concatenation is typed {}", expr.ty)),
+            IRExprKind::Call { func, args } => self.call(*func, args.len()),
+            IRExprKind::Closure { params, body } => return self.closure(params, body, defined),
+            // A lifted closure's function takes its environment first
+            IRExprKind::LiftedClosure { func, .. } => match self.callees.get(func) {
+                Some(callee) if !callee.exact && callee.params > 0 => {}
+                Some(_) => self.error(format!("the closure '{}' does not take an environment", func)),
+                None => self.error(format!("the closure '{}' is not a function of the module", func)),
+            },
+            IRExprKind::Comprehension { target, iter, filter, expr } => {
+                return self.comprehension(*target, iter, filter.as_deref(), expr, defined)
+            }
+            IRExprKind::Match { scrutinee, arms } => {
+                self.expr(scrutinee, defined);
+                for arm in arms {
+                    let mut bound = defined.clone();
+                    bound.extend(arm.pattern.bindings());
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard, &bound);
+                    }
+                    self.expr(&arm.body, &bound);
+                }
+                return;
+            }
+            _ => {}
+        }
+        for child in expr.children() {
+            self.expr(child, defined);
+        }
+    }
+
+    fn literal(&mut self, expr: &IRExpr, ty: Type) {
+        if !admits(&expr.ty, &ty) {
+            self.error(format!("a {} literal is typed {}", ty, expr.ty));
+        }
+    }
+
+    fn call(&mut self, func: Symbol, args: usize) {
+        let Some(callee) = self.callees.get(&func) else { return };
+        if args > callee.params || (callee.exact && args < callee.params) {
+            self.error(format!("'{}' takes {} argument(s) but is called with {}", func, callee.params, args));
+        }
+    }
+
+    fn comprehension(&mut self, target: Symbol, iter: &IRExpr, filter: Option<&IRExpr>, expr: &IRExpr, defined: &Defined) {
+        self.expr(iter, defined);
+        let mut bound = defined.clone();
+        bound.insert(target);
+        if let Some(filter) = filter {
+            self.expr(filter, &bound);
+        }
+        self.expr(expr, &bound);
+    }
+
+    /// Checks a closure's body as a function of its own, in which the enclosing
+    /// function's variables count as assigned: it may run after they are.
+    fn closure(&mut self, params: &[IRParam], body: &[IRStmt], defined: &Defined) {
+        let mut stored = HashSet::new();
+        stores_in(body, false, &mut stored);
+        let locals = stored.into_iter().filter(|name| !self.locals.contains(name) && !defined.contains(name)).collect();
+        let mut inner: Defined = defined.iter().chain(&self.locals).copied().collect();
+        inner.extend(params.iter().map(|param| param.name));
+        let outer_locals = std::mem::replace(&mut self.locals, locals);
+        let outer_loops = std::mem::replace(&mut self.loops, 0);
+        self.stmts(body, &mut inner);
+        self.locals = outer_locals;
+        self.loops = outer_loops;
+    }
+}
+
+/// The names `stmts` store to, including in closures if `closures` is set.
+fn stores_in(stmts: &[IRStmt], closures: bool, out: &mut HashSet<Symbol>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            IRStmtKind::Store { target, .. } | IRStmtKind::Reactive { name: target, .. } | IRStmtKind::Comprehension { target, .. } => {
+                out.insert(*target);
+            }
+            _ => {}
+        }
+        if closures {
+            let mut pending = stmt.exprs();
+            while let Some(expr) = pending.pop() {
+                if let IRExprKind::Closure { body, .. } = &expr.kind {
+                    stores_in(body, true, out);
+                }
+                pending.extend(expr.children());
+            }
+        }
+        for block in stmt.blocks() {
+            stores_in(block, closures, out);
+        }
+    }
+}
+
+/// Whether `stmts` contain a `break` out of the loop they are the body of.
+fn breaks_out(stmts: &[IRStmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt.kind {
+        IRStmtKind::Break => true,
+        // A nested loop's `break` only leaves that loop
+        IRStmtKind::Loop { .. } => false,
+        _ => stmt.blocks().into_iter().any(breaks_out),
+    })
+}
+
+/// The type the analyzer gives `left op right`, where it depends only on the operator.
+fn binary_type(op: &BinaryOp, left: &Type, right: &Type) -> Option<Type> {
+    match op {
+        BinaryOp::Add => match (left, right) {
+            (Type::String, _) | (_, Type::String) => Some(Type::String),
+            (Type::Number, Type::Number) => Some(Type::Number),
+            _ => None,
+        },
+        BinaryOp::Equal
+        | BinaryOp::NotEqual
+        | BinaryOp::StrictEqual
+        | BinaryOp::StrictNotEqual
+        | BinaryOp::LessThan
+        | BinaryOp::LessThanEqual
+        | BinaryOp::GreaterThan
+        | BinaryOp::GreaterThanEqual
+        | BinaryOp::And
+        | BinaryOp::Or => Some(Type::Boolean),
+        _ => Some(Type::Number),
+    }
+}
+
+/// Whether a value of type `ty` may hold a `value` (a primitive type): where
+/// `ty` names a type the IR does not describe, it is assumed to.
+fn admits(ty: &Type, value: &Type) -> bool {
+    match ty {
+        Type::Any | Type::Custom(_) | Type::Generic { .. } => true,
+        Type::Union(members) => members.iter().any(|member| admits(member, value)),
+        Type::Ref(inner) | Type::MutRef(inner) => admits(inner, value),
+        _ => ty == value,
+    }
+}
@@ -1,10 +1,42 @@
 //! Lexer for Gigli source code
-use crate::ast::Token;
+use crate::ast::{SpannedToken, Symbol, TemplateToken, Token};
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::profiling;
+use std::ops::Range;
+use unicode_normalization::UnicodeNormalization;
+
+/// A `//` or `/* */` comment, which the lexer skips but records for tools
+/// like the formatter that must keep them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The comment's source text, including its delimiters.
+    pub text: String,
+    pub span: Span,
+}
 
 pub struct Lexer {
+    /// The text being lexed, kept so `relex` can apply edits to it.
+    source: String,
+    /// Tokens of the last successful lex; empty after an error.
+    tokens: Vec<SpannedToken>,
+    /// Comments seen by the last `tokenize`.
+    comments: Vec<Comment>,
+    /// While relexing, where tokens of the old text that follow the edit now
+    /// start. Lexing stops at the first new token starting at one of them.
+    resync_starts: Vec<usize>,
     input: Vec<char>,
     position: usize,
     current_char: Option<char>,
+    /// Byte offset of `current_char` in the source, for spans.
+    byte_position: usize,
+    /// Byte offset where the token being read starts.
+    token_start: usize,
+    /// 1-based line and column of `current_char`.
+    line: usize,
+    col: usize,
+    /// Line and column where the token being read starts.
+    token_line: usize,
+    token_col: usize,
 }
 
 impl Lexer {
@@ -12,16 +44,179 @@ impl Lexer {
         let chars: Vec<char> = input.chars().collect();
         let current_char = chars.first().copied();
         Lexer {
+            source: input.to_string(),
+            tokens: Vec::new(),
+            comments: Vec::new(),
+            resync_starts: Vec::new(),
             input: chars,
             position: 0,
             current_char,
+            byte_position: 0,
+            token_start: 0,
+            line: 1,
+            col: 1,
+            token_line: 1,
+            token_col: 1,
+        }
+    }
+
+    /// Splits the source into tokens. Errors point at the offending text.
+    pub fn tokenize(&mut self) -> Result<Vec<SpannedToken>, Diagnostic> {
+        let _timer = profiling::scope("lex");
+        self.comments.clear();
+        match self.read_tokens() {
+            Ok(tokens) => {
+                self.tokens = tokens.clone();
+                Ok(tokens)
+            }
+            Err(message) => {
+                self.tokens.clear();
+                Err(self.error_at_token(message))
+            }
+        }
+    }
+
+    /// The comments in the text, in source order, as of the last `tokenize`.
+    /// `relex` does not track comments and clears them.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Replaces the bytes in `range` of the text last lexed with `new_text` and
+    /// returns the tokens of the edited text.
+    ///
+    /// Only the edited region is lexed again: tokens before it are kept, and
+    /// lexing stops as soon as it reaches a token boundary of the old text
+    /// after it, whose remaining tokens are reused with their positions
+    /// shifted. If the last lex failed the whole text is lexed.
+    pub fn relex(&mut self, range: Range<usize>, new_text: &str) -> Result<Vec<SpannedToken>, Diagnostic> {
+        let _timer = profiling::scope("relex");
+        if range.start > range.end
+            || range.end > self.source.len()
+            || !self.source.is_char_boundary(range.start)
+            || !self.source.is_char_boundary(range.end)
+        {
+            return Err(Diagnostic::error(format!(
+                "Edit range {}..{} does not fall on character boundaries of the {}-byte text",
+                range.start,
+                range.end,
+                self.source.len()
+            )));
+        }
+        self.comments.clear();
+        let mut source = std::mem::take(&mut self.source);
+        source.replace_range(range.clone(), new_text);
+        let old_tokens = std::mem::take(&mut self.tokens);
+        if old_tokens.is_empty() {
+            *self = Lexer::new(&source);
+            return self.tokenize();
+        }
+
+        // Lexing only ever looks ahead within a token and the character after
+        // it, so a token that ends before the edit and is followed by whitespace
+        // lexes the same as it did before. Relexing restarts at the last such
+        // token, or at the start of the text if there is none.
+        let restart = old_tokens[..old_tokens.partition_point(|t| t.span.end < range.start)]
+            .iter()
+            .rposition(|t| source[t.span.end..].starts_with(char::is_whitespace))
+            .unwrap_or(0);
+        let (start, line, col) = if restart == 0 {
+            (0, 1, 1)
+        } else {
+            let token = &old_tokens[restart];
+            (token.span.start, token.line, token.col)
+        };
+
+        let edit_end = range.start + new_text.len();
+        let first_after = old_tokens.partition_point(|t| t.span.start < range.end);
+        let moved = |t: &SpannedToken| t.span.start - range.end + edit_end;
+        self.resync_starts = old_tokens[first_after..]
+            .iter()
+            .filter(|t| t.token != Token::EOF)
+            .map(moved)
+            .collect();
+
+        self.input = source.chars().collect();
+        self.position = source[..start].chars().count();
+        self.current_char = self.input.get(self.position).copied();
+        self.byte_position = start;
+        self.line = line;
+        self.col = col;
+        self.source = source;
+
+        let relexed = self.read_token_stream(false);
+        let resync_starts = std::mem::take(&mut self.resync_starts);
+        let relexed = match relexed {
+            Ok(tokens) => tokens,
+            Err(message) => return Err(self.error_at_token(message)),
+        };
+
+        let mut tokens = old_tokens;
+        let resynced = relexed
+            .last()
+            .filter(|last| resync_starts.binary_search(&last.span.start).is_ok())
+            .map(|last| (last.span.start, last.line, last.col));
+        match resynced {
+            Some((new_start, new_line, new_col)) => {
+                let matched = first_after + tokens[first_after..].iter().position(|t| moved(t) == new_start).unwrap_or(0);
+                let old = &tokens[matched];
+                let shift = Shift {
+                    bytes: new_start as isize - old.span.start as isize,
+                    lines: new_line as isize - old.line as isize,
+                    line: old.line,
+                    cols: new_col as isize - old.col as isize,
+                };
+                for token in &mut tokens[matched + 1..] {
+                    shift.apply(token);
+                }
+                tokens.splice(restart..=matched, relexed);
+            }
+            None => {
+                tokens.truncate(restart);
+                tokens.extend(relexed);
+                tokens.push(SpannedToken {
+                    token: Token::EOF,
+                    span: Span::new(self.byte_position, self.byte_position),
+                    line: self.line,
+                    col: self.col,
+                });
+            }
         }
+        self.tokens = tokens.clone();
+        Ok(tokens)
+    }
+
+    /// An error at the token being read.
+    fn error_at_token(&self, message: String) -> Diagnostic {
+        let end = self.byte_position.max(self.token_start + self.current_char.map_or(0, char::len_utf8));
+        let span = Span::new(self.token_start, end);
+        Diagnostic::error(message).with_code(codes::INVALID_TOKEN).with_primary(span, "")
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
+    fn read_tokens(&mut self) -> Result<Vec<SpannedToken>, String> {
+        let mut spanned = self.read_token_stream(false)?;
+        let mut tokens = vec![Token::EOF];
+        self.token_start = self.byte_position;
+        self.token_line = self.line;
+        self.token_col = self.col;
+        self.attach_spans(&mut tokens, &mut spanned);
+        Ok(spanned)
+    }
+
+    /// Reads tokens until the end of input or, for the expression of a
+    /// template literal's `${...}`, until its unmatched closing `}`.
+    fn read_token_stream(&mut self, interpolation: bool) -> Result<Vec<SpannedToken>, String> {
         let mut tokens = Vec::new();
+        let mut spanned = Vec::new();
+        let mut brace_depth = 0usize;
 
         while self.current_char.is_some() {
+            // Tokens read by the previous iteration end here, before any whitespace
+            self.attach_spans(&mut tokens, &mut spanned);
+            if !interpolation && self.at_resync_point(&spanned) {
+                break;
+            }
+
             // Skip whitespace
             while let Some(ch) = self.current_char {
                 if ch.is_whitespace() {
@@ -31,6 +226,12 @@ impl Lexer {
                 }
             }
 
+            self.token_start = self.byte_position;
+            self.token_line = self.line;
+            self.token_col = self.col;
+            if interpolation && brace_depth == 0 && self.current_char == Some('}') {
+                break;
+            }
             if let Some(ch) = self.current_char {
                 // --- NEW: Recognize control flow block tokens ---
                 if ch == '{' && self.peek() == Some('#') {
@@ -95,8 +296,12 @@ impl Lexer {
                 }
                 // --- END NEW ---
                 match ch {
+                    // Raw strings: r"..." or r#"..."#
+                    'r' if matches!(self.peek(), Some('"') | Some('#')) && self.starts_raw_string() => {
+                        tokens.push(self.read_raw_string()?);
+                    }
                     // Identifiers and keywords
-                    'a'..='z' | 'A'..='Z' | '_' => {
+                    c if c == '_' || unicode_ident::is_xid_start(c) => {
                         tokens.push(self.read_identifier_or_keyword()?);
                     }
                     // Numbers
@@ -104,14 +309,26 @@ impl Lexer {
                         tokens.push(self.read_number()?);
                     }
                     // Strings
+                    '"' if self.peek() == Some('"') && self.peek_nth(2) == Some('"') => {
+                        tokens.push(self.read_multiline_string()?);
+                    }
                     '"' => {
                         tokens.push(self.read_string()?);
                     }
+                    '`' => {
+                        tokens.push(self.read_template()?);
+                    }
+                    '\'' => {
+                        tokens.push(self.read_char()?);
+                    }
                     // Operators and delimiters
                     '+' => {
                         if self.peek() == Some('=') {
                             self.advance();
                             tokens.push(Token::PlusAssign);
+                        } else if self.peek() == Some('+') {
+                            self.advance();
+                            tokens.push(Token::Increment);
                         } else {
                             tokens.push(Token::Plus);
                         }
@@ -121,6 +338,9 @@ impl Lexer {
                         if self.peek() == Some('=') {
                             self.advance();
                             tokens.push(Token::MinusAssign);
+                        } else if self.peek() == Some('-') {
+                            self.advance();
+                            tokens.push(Token::Decrement);
                         } else if self.peek() == Some('>') {
                             self.advance();
                             tokens.push(Token::Arrow);
@@ -130,26 +350,56 @@ impl Lexer {
                         self.advance();
                     }
                     '*' => {
-                        tokens.push(Token::Star);
+                        if self.peek() == Some('=') {
+                            self.advance();
+                            tokens.push(Token::StarAssign);
+                        } else if self.peek() == Some('*') {
+                            self.advance();
+                            tokens.push(Token::StarStar);
+                        } else {
+                            tokens.push(Token::Star);
+                        }
                         self.advance();
                     }
                     '/' => {
                         // Check for comments
+                        let start = self.byte_position;
                         if self.peek() == Some('/') {
                             self.skip_line_comment();
+                            self.record_comment(start);
+                        } else if self.peek() == Some('*') {
+                            self.skip_block_comment()?;
+                            self.record_comment(start);
+                        } else if self.peek() == Some('=') {
+                            self.advance();
+                            self.advance();
+                            tokens.push(Token::SlashAssign);
                         } else {
                             tokens.push(Token::Slash);
                             self.advance();
                         }
                     }
                     '%' => {
-                        tokens.push(Token::Percent);
+                        if self.peek() == Some('=') {
+                            self.advance();
+                            tokens.push(Token::PercentAssign);
+                        } else {
+                            tokens.push(Token::Percent);
+                        }
                         self.advance();
                     }
                     '=' => {
                         if self.peek() == Some('=') {
                             self.advance();
-                            tokens.push(Token::Equal);
+                            if self.peek() == Some('=') {
+                                self.advance();
+                                tokens.push(Token::StrictEqual);
+                            } else {
+                                tokens.push(Token::Equal);
+                            }
+                        } else if self.peek() == Some('>') {
+                            self.advance();
+                            tokens.push(Token::FatArrow);
                         } else {
                             tokens.push(Token::Assign);
                         }
@@ -158,9 +408,48 @@ impl Lexer {
                     '!' => {
                         if self.peek() == Some('=') {
                             self.advance();
-                            tokens.push(Token::NotEqual);
+                            if self.peek() == Some('=') {
+                                self.advance();
+                                tokens.push(Token::StrictNotEqual);
+                            } else {
+                                tokens.push(Token::NotEqual);
+                            }
                         } else {
-                            return Err("Unexpected character '!'".to_string());
+                            tokens.push(Token::Not);
+                        }
+                        self.advance();
+                    }
+                    '&' => {
+                        if self.peek() == Some('&') {
+                            self.advance();
+                            tokens.push(Token::And);
+                        } else {
+                            tokens.push(Token::BitwiseAnd);
+                        }
+                        self.advance();
+                    }
+                    '|' => {
+                        if self.peek() == Some('|') {
+                            self.advance();
+                            tokens.push(Token::Or);
+                        } else {
+                            tokens.push(Token::BitwiseOr);
+                        }
+                        self.advance();
+                    }
+                    '^' => {
+                        tokens.push(Token::BitwiseXor);
+                        self.advance();
+                    }
+                    '?' => {
+                        if self.peek() == Some('?') {
+                            self.advance();
+                            tokens.push(Token::DoubleQuestion);
+                        } else if self.peek() == Some('.') {
+                            self.advance();
+                            tokens.push(Token::QuestionDot);
+                        } else {
+                            tokens.push(Token::QuestionMark);
                         }
                         self.advance();
                     }
@@ -168,6 +457,9 @@ impl Lexer {
                         if self.peek() == Some('=') {
                             self.advance();
                             tokens.push(Token::LessThanEqual);
+                        } else if self.peek() == Some('<') {
+                            self.advance();
+                            tokens.push(Token::LeftShift);
                         } else {
                             tokens.push(Token::LessThan);
                         }
@@ -177,6 +469,14 @@ impl Lexer {
                         if self.peek() == Some('=') {
                             self.advance();
                             tokens.push(Token::GreaterThanEqual);
+                        } else if self.peek() == Some('>') {
+                            self.advance();
+                            if self.peek() == Some('>') {
+                                self.advance();
+                                tokens.push(Token::UnsignedRightShift);
+                            } else {
+                                tokens.push(Token::RightShift);
+                            }
                         } else {
                             tokens.push(Token::GreaterThan);
                         }
@@ -192,10 +492,12 @@ impl Lexer {
                     }
                     '{' => {
                         tokens.push(Token::LeftBrace);
+                        brace_depth += 1;
                         self.advance();
                     }
                     '}' => {
                         tokens.push(Token::RightBrace);
+                        brace_depth = brace_depth.saturating_sub(1);
                         self.advance();
                     }
                     '[' => {
@@ -214,12 +516,39 @@ impl Lexer {
                         tokens.push(Token::Comma);
                         self.advance();
                     }
+                    '@' => {
+                        tokens.push(Token::At);
+                        self.advance();
+                    }
+                    // The `$` of a `$: name = value;` reactive statement
+                    '$' if self.peek() == Some(':') => {
+                        tokens.push(Token::Identifier(Symbol::intern("$")));
+                        self.advance();
+                    }
                     '.' => {
-                        tokens.push(Token::Dot);
+                        if self.peek() == Some('.') {
+                            self.advance();
+                            if self.peek() == Some('.') {
+                                self.advance();
+                                tokens.push(Token::Ellipsis);
+                            } else if self.peek() == Some('=') {
+                                self.advance();
+                                tokens.push(Token::DotDotEqual);
+                            } else {
+                                tokens.push(Token::DotDot);
+                            }
+                        } else {
+                            tokens.push(Token::Dot);
+                        }
                         self.advance();
                     }
                     ':' => {
-                        tokens.push(Token::Colon);
+                        if self.peek() == Some(':') {
+                            self.advance();
+                            tokens.push(Token::DoubleColon);
+                        } else {
+                            tokens.push(Token::Colon);
+                        }
                         self.advance();
                     }
                     _ => {
@@ -229,50 +558,98 @@ impl Lexer {
             }
         }
 
-        tokens.push(Token::EOF);
-        Ok(tokens)
+        self.attach_spans(&mut tokens, &mut spanned);
+        Ok(spanned)
+    }
+
+    /// Moves tokens read since the last call into `spanned`, located at the current token start.
+    fn attach_spans(&self, tokens: &mut Vec<Token>, spanned: &mut Vec<SpannedToken>) {
+        for token in tokens.drain(..) {
+            spanned.push(SpannedToken {
+                token,
+                span: Span::new(self.token_start, self.byte_position),
+                line: self.token_line,
+                col: self.token_col,
+            });
+        }
+    }
+
+    /// Whether a relex has caught up with the old tokens after the edit.
+    fn at_resync_point(&self, spanned: &[SpannedToken]) -> bool {
+        match spanned.last() {
+            Some(last) if !self.resync_starts.is_empty() => self.resync_starts.binary_search(&last.span.start).is_ok(),
+            _ => false,
+        }
     }
 
     fn read_identifier_or_keyword(&mut self) -> Result<Token, String> {
         let mut identifier = String::new();
 
         while let Some(ch) = self.current_char {
-            if ch.is_alphanumeric() || ch == '_' {
+            if ch == '_' || unicode_ident::is_xid_continue(ch) {
                 identifier.push(ch);
                 self.advance();
             } else {
                 break;
             }
         }
+        // Different code point sequences for the same text, e.g. a precomposed
+        // 'é' and 'e' plus a combining accent, must name the same identifier
+        if !identifier.is_ascii() {
+            identifier = identifier.nfc().collect();
+        }
 
         // Check if it's a keyword
         match identifier.as_str() {
-            "fn" => Ok(Token::Fn),
-            "component" => Ok(Token::Component), // NEW
-            "state" => Ok(Token::State),         // NEW
-            "struct" => Ok(Token::Struct),       // NEW
-            "enum" => Ok(Token::Enum),           // NEW
-            "on" => Ok(Token::On),
-            "style" => Ok(Token::Style),
-            "if" => Ok(Token::If),
-            "then" => Ok(Token::Then),
-            "else" => Ok(Token::Else),
-            "let" => Ok(Token::Let),
-            "mut" => Ok(Token::Mut),
-            "return" => Ok(Token::Return),
-            _ => Ok(Token::Identifier(identifier)),
+            "true" => Ok(Token::BooleanLiteral(true)),
+            "false" => Ok(Token::BooleanLiteral(false)),
+            word => Ok(Token::keyword(word).unwrap_or_else(|| Token::Identifier(Symbol::intern(word)))),
         }
     }
 
+    /// Reads a number literal: decimal with optional fraction and exponent
+    /// (`1.5e-3`), or hexadecimal, binary or octal with a `0x`, `0b` or `0o`
+    /// prefix. Digits may be separated with `_`.
     fn read_number(&mut self) -> Result<Token, String> {
-        let mut number = String::new();
+        if self.current_char == Some('0') {
+            let radix = match self.peek() {
+                Some('x') | Some('X') => Some(16),
+                Some('b') | Some('B') => Some(2),
+                Some('o') | Some('O') => Some(8),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance(); // skip '0'
+                self.advance(); // skip radix letter
+                let mut digits = String::new();
+                self.read_digits(radix, &mut digits)?;
+                return match u64::from_str_radix(&digits, radix) {
+                    Ok(n) => Ok(Token::NumberLiteral(n as f64)),
+                    Err(_) => Err(format!("Number literal is too large: {}", digits)),
+                };
+            }
+        }
 
-        while let Some(ch) = self.current_char {
-            if ch.is_digit(10) || ch == '.' {
-                number.push(ch);
+        let mut number = String::new();
+        self.read_digits(10, &mut number)?;
+        // A fraction needs a digit after the dot, so `1.method()` still lexes as a member access
+        if self.current_char == Some('.') && self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            number.push('.');
+            self.advance();
+            self.read_digits(10, &mut number)?;
+        }
+        // An exponent needs digits too, so units like `1em` in style blocks stay separate tokens
+        if matches!(self.current_char, Some('e') | Some('E')) {
+            let sign = matches!(self.peek(), Some('+') | Some('-'));
+            let digit = self.input.get(self.position + if sign { 2 } else { 1 });
+            if digit.is_some_and(|c| c.is_ascii_digit()) {
+                number.push('e');
                 self.advance();
-            } else {
-                break;
+                if sign {
+                    number.push(self.current_char.unwrap());
+                    self.advance();
+                }
+                self.read_digits(10, &mut number)?;
             }
         }
 
@@ -282,15 +659,64 @@ impl Lexer {
         }
     }
 
+    /// Reads digits in `radix` into `digits`, dropping `_` separators. A
+    /// separator must sit between two digits.
+    fn read_digits(&mut self, radix: u32, digits: &mut String) -> Result<(), String> {
+        let radix_name = match radix {
+            16 => "hexadecimal",
+            8 => "octal",
+            2 => "binary",
+            _ => "decimal",
+        };
+        let mut any = false;
+        let mut after_separator = false;
+        while let Some(ch) = self.current_char {
+            if ch == '_' {
+                if !any || after_separator {
+                    return Err("Misplaced '_' separator in number literal".to_string());
+                }
+                after_separator = true;
+            } else if ch.is_digit(radix) {
+                digits.push(ch);
+                any = true;
+                after_separator = false;
+            } else if radix != 10 && ch.is_ascii_alphanumeric() {
+                return Err(format!("Invalid digit '{}' in {} literal", ch, radix_name));
+            } else {
+                break;
+            }
+            self.advance();
+        }
+        if after_separator {
+            return Err("Number literal cannot end with '_'".to_string());
+        }
+        if !any {
+            return Err(format!("Expected {} digits in number literal", radix_name));
+        }
+        Ok(())
+    }
+
+    /// Reads a string literal. A string containing `${expr}` is lexed like a
+    /// template literal; `\$` writes a literal `$`.
     fn read_string(&mut self) -> Result<Token, String> {
+        // Lexing interpolations moves the token start; the string's own span begins here
+        let start = (self.token_start, self.token_line, self.token_col);
         let mut string = String::new();
+        let mut parts = Vec::new();
         self.advance(); // Skip opening quote
 
         while let Some(ch) = self.current_char {
             match ch {
                 '"' => {
                     self.advance(); // Skip closing quote
-                    return Ok(Token::StringLiteral(string));
+                    if parts.is_empty() {
+                        return Ok(Token::StringLiteral(string));
+                    }
+                    if !string.is_empty() {
+                        parts.push(TemplateToken::String(string));
+                    }
+                    (self.token_start, self.token_line, self.token_col) = start;
+                    return Ok(Token::TemplateLiteral(parts));
                 }
                 '\\' => {
                     self.advance();
@@ -301,11 +727,28 @@ impl Lexer {
                             'r' => string.push('\r'),
                             '\\' => string.push('\\'),
                             '"' => string.push('"'),
+                            '$' => string.push('$'),
                             _ => return Err(format!("Invalid escape sequence \\{}", escaped)),
                         }
                         self.advance();
                     }
                 }
+                '$' if self.peek() == Some('{') => {
+                    if !string.is_empty() {
+                        parts.push(TemplateToken::String(std::mem::take(&mut string)));
+                    }
+                    self.advance(); // skip '$'
+                    self.advance(); // skip '{'
+                    let tokens = self.read_token_stream(true)?;
+                    if self.current_char != Some('}') {
+                        return Err("Unterminated '${' in string literal".to_string());
+                    }
+                    if tokens.is_empty() {
+                        return Err("Empty '${}' in string literal".to_string());
+                    }
+                    self.advance(); // skip '}'
+                    parts.push(TemplateToken::Expression(tokens));
+                }
                 _ => {
                     string.push(ch);
                     self.advance();
@@ -316,6 +759,197 @@ impl Lexer {
         Err("Unterminated string literal".to_string())
     }
 
+    /// Reads a character literal such as `'a'`, `'\n'` or `'\u{1F600}'`.
+    fn read_char(&mut self) -> Result<Token, String> {
+        self.advance(); // Skip opening quote
+        let value = match self.current_char {
+            None | Some('\n') => return Err("Unterminated character literal".to_string()),
+            Some('\'') => return Err("Empty character literal".to_string()),
+            Some('\\') => {
+                self.advance();
+                match self.current_char {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some('\\') => '\\',
+                    Some('\'') => '\'',
+                    Some('"') => '"',
+                    Some('u') if self.peek() == Some('{') => {
+                        self.advance(); // skip 'u'
+                        self.advance(); // skip '{'
+                        let mut hex = String::new();
+                        while let Some(c) = self.current_char.filter(|c| c.is_ascii_hexdigit()) {
+                            hex.push(c);
+                            self.advance();
+                        }
+                        if self.current_char != Some('}') || hex.is_empty() || hex.len() > 6 {
+                            return Err("Invalid unicode escape; expected \\u{...} with 1 to 6 hex digits".to_string());
+                        }
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid unicode escape \\u{{{}}}: not a character", hex))?
+                    }
+                    Some(escaped) => return Err(format!("Invalid escape sequence \\{}", escaped)),
+                    None => return Err("Unterminated character literal".to_string()),
+                }
+            }
+            Some(ch) => ch,
+        };
+        self.advance();
+
+        match self.current_char {
+            Some('\'') => {
+                self.advance(); // Skip closing quote
+                Ok(Token::CharLiteral(value))
+            }
+            None | Some('\n') => Err("Unterminated character literal".to_string()),
+            Some(_) => Err("Character literal may only contain one character; use a string for text".to_string()),
+        }
+    }
+
+    /// Whether the `r` at the current position opens a raw string rather than an identifier.
+    fn starts_raw_string(&self) -> bool {
+        let mut n = 1;
+        while self.peek_nth(n) == Some('#') {
+            n += 1;
+        }
+        self.peek_nth(n) == Some('"')
+    }
+
+    /// Reads `r"..."`, taken verbatim with no escapes. Hashes on both sides,
+    /// as in `r#"say "hi""#`, let the string itself contain quotes.
+    fn read_raw_string(&mut self) -> Result<Token, String> {
+        self.advance(); // Skip 'r'
+        let mut hashes = 0;
+        while self.current_char == Some('#') {
+            hashes += 1;
+            self.advance();
+        }
+        self.advance(); // Skip opening quote
+
+        let mut string = String::new();
+        while let Some(ch) = self.current_char {
+            if ch == '"' && (1..=hashes).all(|n| self.peek_nth(n) == Some('#')) {
+                for _ in 0..=hashes {
+                    self.advance();
+                }
+                return Ok(Token::StringLiteral(string));
+            }
+            string.push(ch);
+            self.advance();
+        }
+
+        Err(format!("Unterminated raw string literal; expected closing \"{}", "#".repeat(hashes)))
+    }
+
+    /// Reads a `"""`-delimited string, which may span lines and contain unescaped
+    /// quotes. A newline right after the opening delimiter is dropped; escapes
+    /// work as in ordinary strings.
+    fn read_multiline_string(&mut self) -> Result<Token, String> {
+        for _ in 0..3 {
+            self.advance(); // Skip opening quotes
+        }
+        if self.current_char == Some('\r') && self.peek() == Some('\n') {
+            self.advance();
+        }
+        if self.current_char == Some('\n') {
+            self.advance();
+        }
+
+        let mut string = String::new();
+        while let Some(ch) = self.current_char {
+            match ch {
+                '"' if self.peek() == Some('"') && self.peek_nth(2) == Some('"') => {
+                    for _ in 0..3 {
+                        self.advance(); // Skip closing quotes
+                    }
+                    return Ok(Token::StringLiteral(string));
+                }
+                '\\' => {
+                    self.advance();
+                    if let Some(escaped) = self.current_char {
+                        match escaped {
+                            'n' => string.push('\n'),
+                            't' => string.push('\t'),
+                            'r' => string.push('\r'),
+                            '\\' => string.push('\\'),
+                            '"' => string.push('"'),
+                            _ => return Err(format!("Invalid escape sequence \\{}", escaped)),
+                        }
+                        self.advance();
+                    }
+                }
+                _ => {
+                    string.push(ch);
+                    self.advance();
+                }
+            }
+        }
+
+        Err("Unterminated multi-line string literal; expected closing \"\"\"".to_string())
+    }
+
+    /// Reads a backtick template literal. Each `${...}` expression is lexed in
+    /// place into its own tokens, which the parser turns into an expression.
+    fn read_template(&mut self) -> Result<Token, String> {
+        // Lexing the expressions moves the token start; the template's own span begins here
+        let start = (self.token_start, self.token_line, self.token_col);
+        self.advance(); // Skip opening backtick
+
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        loop {
+            match self.current_char {
+                None => return Err("Unterminated template literal".to_string()),
+                Some('`') => {
+                    self.advance();
+                    break;
+                }
+                Some('\\') => {
+                    self.advance();
+                    if let Some(escaped) = self.current_char {
+                        match escaped {
+                            'n' => text.push('\n'),
+                            't' => text.push('\t'),
+                            'r' => text.push('\r'),
+                            '\\' | '`' | '$' => text.push(escaped),
+                            _ => return Err(format!("Invalid escape sequence \\{}", escaped)),
+                        }
+                        self.advance();
+                    }
+                }
+                Some('$') if self.peek() == Some('{') => {
+                    if !text.is_empty() {
+                        parts.push(TemplateToken::String(std::mem::take(&mut text)));
+                    }
+                    self.advance(); // skip '$'
+                    self.advance(); // skip '{'
+                    let tokens = self.read_token_stream(true)?;
+                    if self.current_char != Some('}') {
+                        return Err("Unterminated '${' in template literal".to_string());
+                    }
+                    if tokens.is_empty() {
+                        return Err("Empty '${}' in template literal".to_string());
+                    }
+                    self.advance(); // skip '}'
+                    parts.push(TemplateToken::Expression(tokens));
+                }
+                Some(ch) => {
+                    text.push(ch);
+                    self.advance();
+                }
+            }
+        }
+        if !text.is_empty() {
+            parts.push(TemplateToken::String(text));
+        }
+
+        (self.token_start, self.token_line, self.token_col) = start;
+        Ok(Token::TemplateLiteral(parts))
+    }
+
     fn skip_line_comment(&mut self) {
         while let Some(ch) = self.current_char {
             if ch == '\n' {
@@ -325,7 +959,42 @@ impl Lexer {
         }
     }
 
+    fn record_comment(&mut self, start: usize) {
+        let span = Span::new(start, self.byte_position);
+        self.comments.push(Comment { text: self.source[start..span.end].to_string(), span });
+    }
+
+    /// Skips a `/* ... */` comment. Block comments nest, so a commented-out
+    /// region may itself contain block comments.
+    fn skip_block_comment(&mut self) -> Result<(), String> {
+        let mut depth = 0;
+        while let Some(ch) = self.current_char {
+            if ch == '/' && self.peek() == Some('*') {
+                depth += 1;
+                self.advance();
+            } else if ch == '*' && self.peek() == Some('/') {
+                depth -= 1;
+                self.advance();
+                if depth == 0 {
+                    self.advance();
+                    return Ok(());
+                }
+            }
+            self.advance();
+        }
+        Err("Unterminated block comment".to_string())
+    }
+
     fn advance(&mut self) {
+        if let Some(ch) = self.current_char {
+            self.byte_position += ch.len_utf8();
+            if ch == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+        }
         self.position += 1;
         self.current_char = self.input.get(self.position).copied();
     }
@@ -333,6 +1002,40 @@ impl Lexer {
     fn peek(&self) -> Option<char> {
         self.input.get(self.position + 1).copied()
     }
+
+    /// The character `n` places after the current one; `peek_nth(1)` is `peek()`.
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.input.get(self.position + n).copied()
+    }
+}
+
+/// How the position of a token reused by `Lexer::relex` moves.
+struct Shift {
+    bytes: isize,
+    lines: isize,
+    /// Tokens still on this line of the old text also move by `cols`.
+    line: usize,
+    cols: isize,
+}
+
+impl Shift {
+    fn apply(&self, token: &mut SpannedToken) {
+        token.span = Span::new(
+            (token.span.start as isize + self.bytes) as usize,
+            (token.span.end as isize + self.bytes) as usize,
+        );
+        if token.line == self.line {
+            token.col = (token.col as isize + self.cols) as usize;
+        }
+        token.line = (token.line as isize + self.lines) as usize;
+        if let Token::TemplateLiteral(parts) = &mut token.token {
+            for part in parts {
+                if let TemplateToken::Expression(tokens) = part {
+                    tokens.iter_mut().for_each(|token| self.apply(token));
+                }
+            }
+        }
+    }
 }
 
 pub fn lexer_stub() {
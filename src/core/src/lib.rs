@@ -4,17 +4,30 @@
 //! - Abstract Syntax Tree (AST) definitions
 //! - Lexical analyzer (lexer)
 //! - Parser
+//! - Pretty-printer
 //! - Semantic analyzer
-//! - Intermediate Representation (IR)
+//! - Typed high-level IR (HIR), the analyzer's output
+//! - Intermediate Representation (IR), its SSA form and optimization passes
+//! - Module resolution
+//! - Diagnostics shared by every phase
+//! - Identifier interning
+//! - Opt-in self-profiling
 
 pub mod ast;
+pub mod diagnostics;
 pub mod lexer;
 pub mod parser;
+pub mod printer;
+pub mod profiling;
 pub mod semantic;
+pub mod hir;
 pub mod ir;
+pub mod resolver;
+pub mod symbol;
 
 // Re-export commonly used types
 pub use ast::*;
+pub use diagnostics::{Diagnostic, Severity, Span};
 pub use ir::*;
 
 // Re-export commonly used functions
@@ -1,43 +1,95 @@
 //! Parser for Gigli source code
 use crate::ast::*;
+use crate::diagnostics::{codes, Diagnostic};
 use crate::lexer::Lexer;
+use crate::profiling;
 use std::collections::HashMap;
 use std::fs;
 
+/// HTML elements that have no content and so no closing tag.
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+
 pub struct Parser {
-    tokens: Vec<Token>,
+    tokens: Vec<SpannedToken>,
     position: usize,
     current_token: Option<Token>,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        let mut parser = Parser {
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
+        let current_token = tokens.first().map(|t| t.token.clone());
+        Parser {
             tokens,
             position: 0,
-            current_token: None,
-        };
-        parser.advance();
-        parser
+            current_token,
+        }
+    }
+
+    /// Parses a whole file. Errors point at the token where parsing stopped.
+    pub fn parse(&mut self) -> Result<AST, Diagnostic> {
+        let _timer = profiling::scope("parse");
+        self.parse_program().map_err(|message| {
+            let diagnostic = Diagnostic::from(message).with_code(codes::SYNTAX_ERROR);
+            match self.current_span() {
+                Some(span) => diagnostic.with_primary(span, ""),
+                None => diagnostic,
+            }
+        })
     }
 
-    pub fn parse(&mut self) -> Result<AST, String> {
+    /// Start of the current token, where the node being parsed begins.
+    fn span_start(&self) -> usize {
+        self.current_span().map_or(0, |span| span.start)
+    }
+
+    /// Span from `start` to the end of the last token consumed.
+    fn span_from(&self, start: usize) -> Span {
+        let end = self.position.checked_sub(1).and_then(|i| self.tokens.get(i)).map_or(start, |t| t.span.end);
+        Span::new(start, end)
+    }
+
+    /// Span of the current token, or of the last token once past the end.
+    fn current_span(&self) -> Option<Span> {
+        self.tokens.get(self.position).or(self.tokens.last()).map(|t| t.span)
+    }
+
+    fn parse_program(&mut self) -> Result<AST, String> {
         let mut functions = Vec::new();
         let mut components = Vec::new();
         let mut classes = Vec::new();
         let mut modules = Vec::new();
         let mut imports = Vec::new();
+        let mut externs = Vec::new();
 
         while self.current_token.is_some() {
+            let attributes = self.parse_attributes()?;
+            if !attributes.is_empty() && !matches!(self.current_token, Some(Token::Fn | Token::Async | Token::Private | Token::Component | Token::Class)) {
+                return Err(format!("Expected a function, component or class after an attribute, got {:?}", self.current_token));
+            }
             match &self.current_token {
-                Some(Token::Fn) => {
-                    functions.push(self.parse_function()?);
+                Some(Token::Fn) | Some(Token::Async) | Some(Token::Private) => {
+                    let mut function = self.parse_function()?;
+                    if let Some(first) = attributes.first() {
+                        function.span.start = first.span.start;
+                    }
+                    function.attributes = attributes;
+                    functions.push(function);
                 }
                 Some(Token::Component) => {
-                    components.push(self.parse_component()?);
+                    let mut component = self.parse_component()?;
+                    if let Some(first) = attributes.first() {
+                        component.span.start = first.span.start;
+                    }
+                    component.attributes = attributes;
+                    components.push(component);
                 }
                 Some(Token::Class) => {
-                    classes.push(self.parse_class()?);
+                    let mut class = self.parse_class()?;
+                    if let Some(first) = attributes.first() {
+                        class.span.start = first.span.start;
+                    }
+                    class.attributes = attributes;
+                    classes.push(class);
                 }
                 Some(Token::Module) => {
                     modules.push(self.parse_module()?);
@@ -45,6 +97,9 @@ impl Parser {
                 Some(Token::Import) => {
                     imports.push(self.parse_import()?);
                 }
+                Some(Token::Extern) => {
+                    externs.push(self.parse_extern_function()?);
+                }
                 Some(Token::EOF) => break,
                 _ => {
                     return Err(format!("Unexpected token: {:?}", self.current_token));
@@ -58,12 +113,45 @@ impl Parser {
             classes,
             modules,
             imports,
+            externs,
         })
     }
 
+    /// Parses the `@name` and `@name(args)` attributes before an item.
+    fn parse_attributes(&mut self) -> Result<Vec<Attribute>, String> {
+        let mut attributes = Vec::new();
+        while self.current_token == Some(Token::At) {
+            let start = self.span_start();
+            self.advance();
+            let name = self.expect_name("attribute name")?;
+            let mut args = Vec::new();
+            if self.current_token == Some(Token::LeftParen) {
+                self.advance();
+                while self.current_token != Some(Token::RightParen) {
+                    args.push(self.parse_expression()?);
+                    if self.current_token == Some(Token::Comma) {
+                        self.advance();
+                    } else {
+                        break;
+                    }
+                }
+                self.expect(Token::RightParen)?;
+            }
+            attributes.push(Attribute { name, args, span: self.span_from(start) });
+        }
+        Ok(attributes)
+    }
+
     fn parse_function(&mut self) -> Result<Function, String> {
+        let start = self.span_start();
+        // `private fn` keeps a top-level function out of the module's exports
+        let mut is_public = true;
+        if self.current_token == Some(Token::Private) {
+            is_public = false;
+            self.advance();
+        }
         let mut is_async = false;
-        if self.current_token == Some(Token::Identifier("async".to_string())) {
+        if self.current_token == Some(Token::Async) {
             is_async = true;
             self.advance();
         }
@@ -99,15 +187,56 @@ impl Parser {
             params,
             return_type,
             body,
-            is_public: true, // Default to public for now
+            is_public,
             is_async,
+            attributes: Vec::new(),
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses `extern "js" fn name(params): type;`.
+    fn parse_extern_function(&mut self) -> Result<ExternFunction, String> {
+        let start = self.span_start();
+        self.expect(Token::Extern)?;
+        let abi = match &self.current_token {
+            Some(Token::StringLiteral(abi)) => abi.clone(),
+            _ => return Err(format!("Expected ABI string after 'extern', got {:?}", self.current_token)),
+        };
+        self.advance();
+        self.expect(Token::Fn)?;
+        let name = self.expect_identifier()?;
+        self.expect(Token::LeftParen)?;
+
+        let mut params = Vec::new();
+        while self.current_token != Some(Token::RightParen) {
+            params.push(self.parse_parameter()?);
+            if self.current_token == Some(Token::Comma) {
+                self.advance();
+            }
+        }
+        self.expect(Token::RightParen)?;
+
+        let mut return_type = None;
+        if self.current_token == Some(Token::Colon) {
+            self.advance();
+            return_type = Some(self.parse_type()?);
+        }
+        self.expect(Token::Semicolon)?;
+
+        Ok(ExternFunction {
+            abi,
+            name,
+            params,
+            return_type,
+            span: self.span_from(start),
         })
     }
 
     fn parse_parameter(&mut self) -> Result<Parameter, String> {
+        let start = self.span_start();
         let mut is_ref = false;
         let mut is_mut_ref = false;
-        if self.current_token == Some(Token::And) {
+        if self.current_token == Some(Token::BitwiseAnd) {
             self.advance();
             if self.current_token == Some(Token::Mut) {
                 is_mut_ref = true;
@@ -116,7 +245,15 @@ impl Parser {
                 is_ref = true;
             }
         }
-        let name = self.expect_identifier()?;
+        let mut pattern = None;
+        let name = if matches!(self.current_token, Some(Token::LeftBrace) | Some(Token::LeftBracket)) {
+            let destructured = self.parse_pattern()?;
+            let name = Symbol::intern(&destructured.to_string());
+            pattern = Some(destructured);
+            name
+        } else {
+            self.expect_identifier()?
+        };
 
         let mut type_annotation = None;
         if self.current_token == Some(Token::Colon) {
@@ -136,11 +273,27 @@ impl Parser {
             default_value,
             is_ref,
             is_mut_ref,
+            pattern,
+            span: self.span_from(start),
         })
     }
 
+    /// Expects the `>` closing a type argument list. In nested arguments such as
+    /// `List<List<number>>` the lexer produces `>>`, which closes two lists, and
+    /// in `List<number>= []` it produces `>=`; the rest of the token is left.
+    fn expect_type_args_end(&mut self) -> Result<(), String> {
+        let rest = match self.current_token {
+            Some(Token::RightShift) => Token::GreaterThan,
+            Some(Token::UnsignedRightShift) => Token::RightShift,
+            Some(Token::GreaterThanEqual) => Token::Assign,
+            _ => return self.expect(Token::GreaterThan),
+        };
+        self.current_token = Some(rest);
+        Ok(())
+    }
+
     fn parse_type(&mut self) -> Result<Type, String> {
-        if self.current_token == Some(Token::And) {
+        if self.current_token == Some(Token::BitwiseAnd) {
             self.advance();
             if self.current_token == Some(Token::Mut) {
                 self.advance();
@@ -151,10 +304,11 @@ impl Parser {
         }
         match &self.current_token {
             Some(Token::Identifier(name)) => {
-                let name_clone = name.clone();
+                let name_clone = *name;
                 self.advance();
                 match name_clone.as_str() {
                     "string" => Ok(Type::String),
+                    "char" => Ok(Type::Char),
                     "number" => Ok(Type::Number),
                     "boolean" => Ok(Type::Boolean),
                     "void" => Ok(Type::Void),
@@ -162,7 +316,7 @@ impl Parser {
                     "Option" => {
                         self.expect(Token::LessThan)?;
                         let inner = self.parse_type()?;
-                        self.expect(Token::GreaterThan)?;
+                        self.expect_type_args_end()?;
                         Ok(Type::Option(Box::new(inner)))
                     },
                     "Result" => {
@@ -170,9 +324,19 @@ impl Parser {
                         let ok = self.parse_type()?;
                         self.expect(Token::Comma)?;
                         let err = self.parse_type()?;
-                        self.expect(Token::GreaterThan)?;
+                        self.expect_type_args_end()?;
                         Ok(Type::Result(Box::new(ok), Box::new(err)))
                     },
+                    _ if self.current_token == Some(Token::LessThan) => {
+                        self.advance();
+                        let mut type_args = vec![self.parse_type()?];
+                        while self.current_token == Some(Token::Comma) {
+                            self.advance();
+                            type_args.push(self.parse_type()?);
+                        }
+                        self.expect_type_args_end()?;
+                        Ok(Type::Generic { name: name_clone, type_args })
+                    },
                     _ => Ok(Type::Custom(name_clone)),
                 }
             }
@@ -181,6 +345,7 @@ impl Parser {
     }
 
     fn parse_class(&mut self) -> Result<Class, String> {
+        let start = self.span_start();
         self.expect(Token::Class)?;
         let name = self.expect_identifier()?;
         self.expect(Token::LeftBrace)?;
@@ -209,10 +374,13 @@ impl Parser {
             fields,
             methods,
             constructor,
+            attributes: Vec::new(),
+            span: self.span_from(start),
         })
     }
 
     fn parse_field(&mut self) -> Result<Field, String> {
+        let start = self.span_start();
         let mut is_public = true;
         if self.current_token == Some(Token::Private) {
             self.advance();
@@ -240,10 +408,12 @@ impl Parser {
             type_annotation,
             initial_value,
             is_public,
+            span: self.span_from(start),
         })
     }
 
     fn parse_method(&mut self) -> Result<Method, String> {
+        let start = self.span_start();
         let mut is_public = true;
         if self.current_token == Some(Token::Private) {
             self.advance();
@@ -283,10 +453,12 @@ impl Parser {
             return_type,
             body,
             is_public,
+            span: self.span_from(start),
         })
     }
 
     fn parse_constructor(&mut self) -> Result<Constructor, String> {
+        let start = self.span_start();
         self.expect(Token::Constructor)?;
         self.expect(Token::LeftParen)?;
 
@@ -306,19 +478,22 @@ impl Parser {
         }
         self.expect(Token::RightBrace)?;
 
-        Ok(Constructor { params, body })
+        Ok(Constructor { params, body, span: self.span_from(start) })
     }
 
     fn parse_component(&mut self) -> Result<ComponentNode, String> {
+        let start = self.span_start();
         self.expect(Token::Component)?;
         let name = self.expect_identifier()?;
         self.expect(Token::LeftBrace)?;
 
         let mut state_vars = Vec::new();
         let mut let_vars = Vec::new();
+        let mut reactive = Vec::new();
         let mut functions = Vec::new();
         let mut markup = Vec::new();
         let mut style = None;
+        let mut style_span = None;
 
         while self.current_token != Some(Token::RightBrace) {
             match &self.current_token {
@@ -328,15 +503,24 @@ impl Parser {
                 Some(Token::Let) => {
                     let_vars.push(self.parse_let_var()?);
                 }
-                Some(Token::Fn) => {
+                _ if self.at_identifier("$") => {
+                    reactive.push(self.parse_statement()?);
+                }
+                Some(Token::Fn) | Some(Token::Async) => {
                     functions.push(self.parse_function()?);
                 }
                 Some(Token::Style) => {
+                    let style_start = self.span_start();
                     style = Some(self.parse_style_block_raw()?);
+                    style_span = Some(self.span_from(style_start));
                 }
-                // Markup parsing: parse until the end of the component block
+                // Markup parsing: parse until the next member or the end of the component block
                 _ => {
-                    markup.append(&mut self.parse_markup()?);
+                    let mut nodes = self.parse_markup(false)?;
+                    if nodes.is_empty() {
+                        return Err(format!("Unexpected token in component '{}': {:?}", name, self.current_token));
+                    }
+                    markup.append(&mut nodes);
                 }
             }
         }
@@ -346,50 +530,185 @@ impl Parser {
             name,
             state_vars,
             let_vars,
+            reactive,
             functions,
             markup,
             style,
+            style_span,
+            attributes: Vec::new(),
+            span: self.span_from(start),
         })
     }
 
-    /// Parse a sequence of markup nodes (HTML-like, text, or control flow blocks)
-    fn parse_markup(&mut self) -> Result<Vec<MarkupNode>, String> {
+    /// Parse a sequence of markup nodes (HTML-like, text, or control flow blocks).
+    /// Inside an HTML element's content (`in_element`), bare words are text; elsewhere
+    /// they start a braced element like `div class="x" { ... }`.
+    fn parse_markup(&mut self, in_element: bool) -> Result<Vec<MarkupNode>, String> {
         let mut nodes = Vec::new();
         while let Some(token) = &self.current_token {
             match token {
-                Token::Identifier(_) | Token::StringLiteral(_) => {
-                    nodes.push(self.parse_markup_text_or_element()?);
+                // A closing tag ends the content of the enclosing element
+                Token::LessThan if self.peek() == Some(&Token::Slash) => break,
+                Token::LessThan => {
+                    nodes.push(self.parse_html_element()?);
+                }
+                Token::LeftBrace => {
+                    self.advance();
+                    let expr = self.parse_expression()?;
+                    self.expect(Token::RightBrace)?;
+                    Self::push_text(&mut nodes, expr);
                 }
                 Token::HashIf => {
-                    nodes.push(MarkupNode::IfBlock(self.parse_if_block()?));
+                    nodes.push(MarkupNode::IfBlock(self.parse_if_block(in_element)?));
                 }
                 Token::HashFor => {
-                    nodes.push(MarkupNode::ForLoop(self.parse_for_block()?));
+                    nodes.push(MarkupNode::ForLoop(self.parse_for_block(in_element)?));
                 }
-                Token::RightBrace | Token::ForwardSlashIf | Token::ForwardSlashFor | Token::HashElse => {
+                Token::RightBrace | Token::ForwardSlashIf | Token::ForwardSlashFor | Token::HashElse | Token::EOF => {
                     // End of this markup context
                     break;
                 }
-                _ => {
-                    // Skip unrecognized tokens in markup for now
-                    self.advance();
+                _ if in_element => {
+                    let text = self.parse_markup_text()?;
+                    Self::push_text(&mut nodes, text);
                 }
+                Token::Identifier(_) | Token::StringLiteral(_) => {
+                    nodes.push(self.parse_markup_text_or_element()?);
+                }
+                // The next component member
+                Token::State | Token::Let | Token::Fn | Token::Async | Token::Style => break,
+                _ => return Err(format!("Unexpected token in markup: {:?}", token)),
             }
         }
         Ok(nodes)
     }
 
+    /// Parse `<tag attr="text" attr={expr} flag>...</tag>` or `<tag ... />`.
+    /// Attribute names may be directives such as `on:click` or `bind:value`.
+    fn parse_html_element(&mut self) -> Result<MarkupNode, String> {
+        let start = self.span_start();
+        self.expect(Token::LessThan)?;
+        let tag = self.expect_name("tag name")?;
+
+        let mut attributes = HashMap::new();
+        while self.current_name().is_some() {
+            let attr_start = self.span_start();
+            let mut attr_name = self.expect_name("attribute name")?.to_string();
+            while self.current_token == Some(Token::Colon) {
+                self.advance();
+                attr_name.push(':');
+                attr_name.push_str(self.expect_name("directive name")?.as_str());
+            }
+            let value = if self.current_token == Some(Token::Assign) {
+                self.advance();
+                match self.current_token {
+                    Some(Token::StringLiteral(_)) | Some(Token::TemplateLiteral(_)) => self.parse_primary_expression()?,
+                    Some(Token::LeftBrace) => {
+                        self.advance();
+                        let value = self.parse_expression()?;
+                        self.expect(Token::RightBrace)?;
+                        value
+                    }
+                    _ => return Err(format!("Expected a string or {{expression}} for attribute '{}', got {:?}", attr_name, self.current_token)),
+                }
+            } else {
+                // Boolean attribute
+                Expr::new(ExprKind::BooleanLiteral(true), self.span_from(attr_start))
+            };
+            attributes.insert(Symbol::intern(&attr_name), value);
+        }
+
+        let self_closing = self.current_token == Some(Token::Slash);
+        if self_closing {
+            self.advance();
+        }
+        self.expect(Token::GreaterThan)?;
+
+        let mut children = Vec::new();
+        if !self_closing && !VOID_ELEMENTS.contains(&tag.as_str()) {
+            children = self.parse_markup(true)?;
+            self.expect(Token::LessThan)?;
+            self.expect(Token::Slash)?;
+            let closing = self.expect_name("closing tag name")?;
+            if closing != tag {
+                return Err(format!("Expected closing tag </{}>, found </{}>", tag, closing));
+            }
+            self.expect(Token::GreaterThan)?;
+        }
+
+        Ok(MarkupNode::Element {
+            tag,
+            attributes,
+            children,
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parse a run of text inside an element's content, up to the next tag,
+    /// `{expression}` or block. As in HTML, whitespace in the source, including
+    /// around the run, collapses to a single space.
+    fn parse_markup_text(&mut self) -> Result<Expr, String> {
+        let start = self.span_start();
+        let mut text = String::new();
+        let mut previous_end = self.position.checked_sub(1).and_then(|i| self.tokens.get(i)).map(|t| t.span.end);
+        while let Some(token) = &self.current_token {
+            if matches!(
+                token,
+                Token::LessThan | Token::LeftBrace | Token::RightBrace | Token::HashIf | Token::HashFor
+                    | Token::HashElse | Token::ForwardSlashIf | Token::ForwardSlashFor | Token::EOF
+            ) {
+                break;
+            }
+            let span = self.current_span().unwrap_or_default();
+            if previous_end.is_some_and(|end| end < span.start) {
+                text.push(' ');
+            }
+            text.push_str(&token.to_string());
+            previous_end = Some(span.end);
+            self.advance();
+        }
+        if previous_end.zip(self.current_span()).is_some_and(|(end, next)| end < next.start) {
+            text.push(' ');
+        }
+        Ok(Expr::new(ExprKind::StringLiteral(text), self.span_from(start)))
+    }
+
+    /// Appends text or an `{expression}` to markup content. Consecutive pieces
+    /// form a single text node, a template literal like `Hello, ${name}!`.
+    fn push_text(nodes: &mut Vec<MarkupNode>, expr: Expr) {
+        fn parts(expr: Expr) -> Vec<TemplatePart> {
+            match expr.kind {
+                ExprKind::StringLiteral(text) => vec![TemplatePart::String(text)],
+                ExprKind::TemplateLiteral { parts } => parts,
+                _ => vec![TemplatePart::Expression(expr)],
+            }
+        }
+        match nodes.pop() {
+            Some(MarkupNode::Text(previous)) => {
+                let span = previous.span.to(expr.span);
+                let mut merged = parts(previous);
+                merged.extend(parts(expr));
+                nodes.push(MarkupNode::Text(Expr::new(ExprKind::TemplateLiteral { parts: merged }, span)));
+            }
+            previous => {
+                nodes.extend(previous);
+                nodes.push(MarkupNode::Text(expr));
+            }
+        }
+    }
+
     /// Parse a text node or an HTML-like element
     fn parse_markup_text_or_element(&mut self) -> Result<MarkupNode, String> {
         match &self.current_token {
             Some(Token::Identifier(tag)) => {
                 // Parse as an element: <tag ...>...</tag>
-                let tag_name = tag.clone();
+                let start = self.span_start();
+                let tag_name = *tag;
                 self.advance();
                 let mut attributes = std::collections::HashMap::new();
-                // Parse attributes (identifier = expr pairs)
-                while let Some(Token::Identifier(attr)) = &self.current_token {
-                    let attr_name = attr.clone();
+                // Parse attributes (name = expr pairs); keywords like `type` and `for` are valid names
+                while let Some(attr_name) = self.current_name() {
+                    let attr_span = self.current_span().unwrap_or_default();
                     self.advance();
                     if self.current_token == Some(Token::Assign) {
                         self.advance();
@@ -397,13 +716,13 @@ impl Parser {
                         attributes.insert(attr_name, value);
                     } else {
                         // Boolean attribute
-                        attributes.insert(attr_name, Expr::BooleanLiteral(true));
+                        attributes.insert(attr_name, Expr::new(ExprKind::BooleanLiteral(true), attr_span));
                     }
                 }
                 // Children (nested markup)
                 let children = if let Some(Token::LeftBrace) = &self.current_token {
                     self.advance();
-                    let children = self.parse_markup()?;
+                    let children = self.parse_markup(false)?;
                     self.expect(Token::RightBrace)?;
                     children
                 } else {
@@ -413,10 +732,11 @@ impl Parser {
                     tag: tag_name,
                     attributes,
                     children,
+                    span: self.span_from(start),
                 })
             }
             Some(Token::StringLiteral(s)) => {
-                let expr = Expr::StringLiteral(s.clone());
+                let expr = Expr::new(ExprKind::StringLiteral(s.clone()), self.current_span().unwrap_or_default());
                 self.advance();
                 Ok(MarkupNode::Text(expr))
             }
@@ -425,40 +745,49 @@ impl Parser {
     }
 
     /// Parse an {#if ...} ... {:else} ... {/if} block
-    fn parse_if_block(&mut self) -> Result<IfBlockNode, String> {
+    fn parse_if_block(&mut self, in_element: bool) -> Result<IfBlockNode, String> {
+        let start = self.span_start();
         self.expect(Token::HashIf)?;
         let condition = self.parse_expression()?;
-        let then_branch = self.parse_markup()?;
+        self.expect(Token::RightBrace)?;
+        let then_branch = self.parse_markup(in_element)?;
         let else_branch = if self.current_token == Some(Token::HashElse) {
             self.advance();
-            Some(self.parse_markup()?)
+            self.expect(Token::RightBrace)?;
+            Some(self.parse_markup(in_element)?)
         } else {
             None
         };
         self.expect(Token::ForwardSlashIf)?;
+        self.expect(Token::RightBrace)?;
         Ok(IfBlockNode {
             condition,
             then_branch,
             else_branch,
+            span: self.span_from(start),
         })
     }
 
     /// Parse a {#for item in items} ... {/for} block
-    fn parse_for_block(&mut self) -> Result<ForLoopBlockNode, String> {
+    fn parse_for_block(&mut self, in_element: bool) -> Result<ForLoopBlockNode, String> {
+        let start = self.span_start();
         self.expect(Token::HashFor)?;
         let iterator = self.expect_identifier()?;
         self.expect(Token::In)?;
         let iterable = self.parse_expression()?;
-        let body = self.parse_markup()?;
+        self.expect(Token::RightBrace)?;
+        let body = self.parse_markup(in_element)?;
         self.expect(Token::ForwardSlashFor)?;
+        self.expect(Token::RightBrace)?;
         Ok(ForLoopBlockNode {
             iterator,
             iterable,
             body,
+            span: self.span_from(start),
         })
     }
-
     fn parse_state_var(&mut self) -> Result<StateVar, String> {
+        let start = self.span_start();
         self.expect(Token::State)?;
         let name = self.expect_identifier()?;
         let mut type_annotation = None;
@@ -469,10 +798,11 @@ impl Parser {
         self.expect(Token::Assign)?;
         let initial_value = self.parse_expression()?;
         self.expect(Token::Semicolon)?;
-        Ok(StateVar { name, type_annotation, initial_value })
+        Ok(StateVar { name, type_annotation, initial_value, span: self.span_from(start) })
     }
 
     fn parse_let_var(&mut self) -> Result<LetVar, String> {
+        let start = self.span_start();
         self.expect(Token::Let)?;
         let name = self.expect_identifier()?;
         let mut type_annotation = None;
@@ -483,7 +813,7 @@ impl Parser {
         self.expect(Token::Assign)?;
         let value = self.parse_expression()?;
         self.expect(Token::Semicolon)?;
-        Ok(LetVar { name, type_annotation, value })
+        Ok(LetVar { name, type_annotation, value, mutable: false, span: self.span_from(start) })
     }
 
     fn parse_style_block_raw(&mut self) -> Result<String, String> {
@@ -492,10 +822,17 @@ impl Parser {
         let mut css = String::new();
         if self.current_token == Some(Token::LeftBrace) {
             self.advance();
-            while self.current_token != Some(Token::RightBrace) && self.current_token != Some(Token::EOF) {
-                // This is a stub: in a real parser, we'd handle nested braces and parse CSS properly
+            // Rule bodies nest braces inside the block
+            let mut depth = 0;
+            while (depth > 0 || self.current_token != Some(Token::RightBrace)) && self.current_token != Some(Token::EOF) {
+                // This is a stub: in a real parser, we'd parse CSS properly
+                match self.current_token {
+                    Some(Token::LeftBrace) => depth += 1,
+                    Some(Token::RightBrace) => depth -= 1,
+                    _ => {}
+                }
                 if let Some(Token::Identifier(s)) = &self.current_token {
-                    css.push_str(s);
+                    css.push_str(s.as_str());
                     css.push(' ');
                 }
                 self.advance();
@@ -506,6 +843,7 @@ impl Parser {
     }
 
     fn parse_module(&mut self) -> Result<Module, String> {
+        let start = self.span_start();
         self.expect(Token::Module)?;
         let name = self.expect_identifier()?;
         self.expect(Token::LeftBrace)?;
@@ -529,10 +867,11 @@ impl Parser {
         }
         self.expect(Token::RightBrace)?;
 
-        Ok(Module { name, items })
+        Ok(Module { name, items, span: self.span_from(start) })
     }
 
     fn parse_import(&mut self) -> Result<Import, String> {
+        let start = self.span_start();
         self.expect(Token::Import)?;
         self.expect(Token::LeftBrace)?;
 
@@ -546,7 +885,7 @@ impl Parser {
         self.expect(Token::RightBrace)?;
         self.expect(Token::From)?;
 
-        let module = self.expect_identifier()?;
+        let module = self.expect_identifier()?.to_string();
 
         let mut alias = None;
         if self.current_token == Some(Token::As) {
@@ -556,23 +895,31 @@ impl Parser {
 
         self.expect(Token::Semicolon)?;
 
-        Ok(Import { module, items, alias })
+        Ok(Import { module, items, alias, span: self.span_from(start) })
     }
 
     fn parse_statement(&mut self) -> Result<Stmt, String> {
-        if self.current_token == Some(Token::Identifier("$".to_string())) {
+        let start = self.span_start();
+        let kind = self.parse_statement_kind()?;
+        Ok(Stmt::new(kind, self.span_from(start)))
+    }
+
+    fn parse_statement_kind(&mut self) -> Result<StmtKind, String> {
+        let start = self.span_start();
+        if self.at_identifier("$") {
             self.advance();
             self.expect(Token::Colon)?;
             let name = self.expect_identifier()?;
             self.expect(Token::Assign)?;
             let expr = self.parse_expression()?;
             self.expect(Token::Semicolon)?;
-            return Ok(Stmt::Reactive { name, expr });
+            return Ok(StmtKind::Reactive { name, expr });
         }
         match &self.current_token {
-            Some(Token::Let) => {
+            Some(Token::Let) | Some(Token::Mut) if self.at_let_pattern() => {
+                let mutable = self.current_token == Some(Token::Mut);
                 self.advance();
-                let name = self.expect_identifier()?;
+                let pattern = self.parse_pattern()?;
 
                 let mut type_annotation = None;
                 if self.current_token == Some(Token::Colon) {
@@ -582,11 +929,18 @@ impl Parser {
 
                 self.expect(Token::Assign)?;
                 let value = self.parse_expression()?;
+                let else_branch = if self.current_token == Some(Token::Else) {
+                    self.advance();
+                    Some(self.parse_block()?)
+                } else {
+                    None
+                };
                 self.expect(Token::Semicolon)?;
 
-                Ok(Stmt::LetVarDecl(LetVar { name, type_annotation, value }))
+                Ok(StmtKind::Destructure { pattern, type_annotation, value, else_branch, mutable })
             }
-            Some(Token::Mut) => {
+            Some(Token::Let) | Some(Token::Mut) => {
+                let mutable = self.current_token == Some(Token::Mut);
                 self.advance();
                 let name = self.expect_identifier()?;
 
@@ -600,7 +954,7 @@ impl Parser {
                 let value = self.parse_expression()?;
                 self.expect(Token::Semicolon)?;
 
-                Ok(Stmt::LetVarDecl(LetVar { name, type_annotation, value }))
+                Ok(StmtKind::LetVarDecl(LetVar { name, type_annotation, value, mutable, span: self.span_from(start) }))
             }
             Some(Token::Return) => {
                 self.advance();
@@ -610,7 +964,7 @@ impl Parser {
                     None
                 };
                 self.expect(Token::Semicolon)?;
-                Ok(Stmt::Return(value))
+                Ok(StmtKind::Return(value))
             }
             Some(Token::If) => {
                 self.advance();
@@ -637,7 +991,7 @@ impl Parser {
                     else_body = Some(body);
                 }
 
-                Ok(Stmt::If {
+                Ok(StmtKind::If {
                     condition,
                     then: then_body,
                     else_: else_body,
@@ -653,54 +1007,225 @@ impl Parser {
                 }
                 self.expect(Token::RightBrace)?;
 
-                Ok(Stmt::Loop {
+                Ok(StmtKind::Loop {
                     init: None,
                     condition: None,
                     update: None,
                     body,
                 })
             }
+            Some(Token::While) => {
+                self.advance();
+                let condition = self.parse_expression()?;
+                let body = self.parse_block()?;
+
+                Ok(StmtKind::Loop {
+                    init: None,
+                    condition: Some(condition),
+                    update: None,
+                    body,
+                })
+            }
+            Some(Token::For) => self.parse_for(),
+            Some(Token::Increment) | Some(Token::Decrement) => {
+                let op = self.current_token.clone();
+                self.advance();
+                let target = self.expect_identifier()?;
+                self.expect(Token::Semicolon)?;
+                Ok(Self::increment(target, op == Some(Token::Increment), self.span_from(start)))
+            }
             _ => {
                 let expr = self.parse_expression()?;
+                if let Some(assign) = self.parse_assignment(&expr, start)? {
+                    self.expect(Token::Semicolon)?;
+                    return Ok(assign);
+                }
+                // Like a block, a `match` used as a statement needs no semicolon
+                if matches!(expr.kind, ExprKind::Match { .. }) && self.current_token != Some(Token::Semicolon) {
+                    return Ok(StmtKind::Expr(expr));
+                }
                 self.expect(Token::Semicolon)?;
-                Ok(Stmt::Expr(expr))
+                Ok(StmtKind::Expr(expr))
             }
         }
     }
 
-    fn parse_expression(&mut self) -> Result<Expr, String> {
-        if self.current_token == Some(Token::Identifier("await".to_string())) {
+    /// Parses `for (x in items)`, `for (x of items)`, `for x in start..end` (or `..=`)
+    /// and C-style `for (init; condition; update)` loops. Ranges and C-style loops
+    /// become a `Loop` with an init, condition and update; the range end is
+    /// evaluated on every iteration, as a C-style condition would be.
+    fn parse_for(&mut self) -> Result<StmtKind, String> {
+        self.expect(Token::For)?;
+        let parenthesized = self.current_token == Some(Token::LeftParen);
+        if parenthesized {
             self.advance();
-            let expr = self.parse_expression()?;
-            return Ok(Expr::Await(Box::new(expr)));
         }
-        // List comprehension: [expr for var in iter if cond]
-        if self.current_token == Some(Token::LeftBracket) {
-            self.advance();
-            let expr = self.parse_expression()?;
-            if self.current_token == Some(Token::For) {
+
+        let is_iteration = matches!(self.current_token, Some(Token::Identifier(_)))
+            && matches!(self.peek(), Some(Token::In) | Some(Token::Of));
+        if !is_iteration {
+            if !parenthesized {
+                return Err(format!("Expected '(' or a loop variable after 'for', got {:?}", self.current_token));
+            }
+            return self.parse_c_style_for();
+        }
+
+        let variable_start = self.span_start();
+        let variable = self.expect_identifier()?;
+        let variable_span = self.span_from(variable_start);
+        let is_of = self.current_token == Some(Token::Of);
+        self.advance();
+        let iterable = self.parse_expression()?;
+
+        let range_end = match self.current_token {
+            Some(Token::DotDot) | Some(Token::DotDotEqual) if !is_of => {
+                let inclusive = self.current_token == Some(Token::DotDotEqual);
                 self.advance();
-                let target = self.expect_identifier()?;
-                self.expect(Token::In)?;
-                let iter = self.parse_expression()?;
-                let mut filter = None;
-                if self.current_token == Some(Token::If) {
-                    self.advance();
-                    filter = Some(self.parse_expression()?);
-                }
-                self.expect(Token::RightBracket)?;
-                return Ok(Expr::Comprehension {
-                    target,
-                    iter: Box::new(iter),
-                    filter: filter.map(Box::new),
-                    expr: Box::new(expr),
-                });
-            } else {
-                // Not a comprehension, fallback to array literal
-                // ... fallback logic ...
+                Some((self.parse_expression()?, inclusive))
             }
+            _ => None,
+        };
+        if parenthesized {
+            self.expect(Token::RightParen)?;
         }
-        self.parse_binary_expression(0)
+        let body = self.parse_block()?;
+
+        if let Some((end, inclusive)) = range_end {
+            let start_span = iterable.span;
+            let counter = Expr::new(ExprKind::Identifier(variable), variable_span);
+            let condition = Expr::new(
+                ExprKind::BinaryOp {
+                    left: Box::new(counter),
+                    op: if inclusive { BinaryOp::LessThanEqual } else { BinaryOp::LessThan },
+                    right: Box::new(end),
+                },
+                variable_span,
+            );
+            // The counter is advanced by the loop, so it is mutable
+            let init = LetVar { name: variable, type_annotation: None, value: iterable, mutable: true, span: variable_span.to(start_span) };
+            return Ok(StmtKind::Loop {
+                init: Some(Box::new(Stmt::new(StmtKind::LetVarDecl(init), variable_span.to(start_span)))),
+                condition: Some(condition),
+                update: Some(Box::new(Stmt::new(Self::increment(variable, true, variable_span), variable_span))),
+                body,
+            });
+        }
+
+        Ok(if is_of {
+            StmtKind::ForOf { variable, iterable, body }
+        } else {
+            StmtKind::ForIn { variable, iterable, body }
+        })
+    }
+
+    /// Parses the rest of `for (init; condition; update) { ... }` after the '('.
+    fn parse_c_style_for(&mut self) -> Result<StmtKind, String> {
+        // The initializer is a full statement, so it consumes its own ';'
+        let init = if self.current_token == Some(Token::Semicolon) {
+            self.advance();
+            None
+        } else {
+            Some(Box::new(self.parse_statement()?))
+        };
+        let condition = if self.current_token == Some(Token::Semicolon) {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+        self.expect(Token::Semicolon)?;
+        let update = if self.current_token == Some(Token::RightParen) {
+            None
+        } else {
+            Some(Box::new(self.parse_for_update()?))
+        };
+        self.expect(Token::RightParen)?;
+        let body = self.parse_block()?;
+
+        Ok(StmtKind::Loop { init, condition, update, body })
+    }
+
+    /// Parses the update clause of a C-style `for`, which has no terminating ';'.
+    fn parse_for_update(&mut self) -> Result<Stmt, String> {
+        let start = self.span_start();
+        if matches!(self.current_token, Some(Token::Increment) | Some(Token::Decrement)) {
+            let increment = self.current_token == Some(Token::Increment);
+            self.advance();
+            let target = self.expect_identifier()?;
+            return Ok(Stmt::new(Self::increment(target, increment, self.span_from(start)), self.span_from(start)));
+        }
+        let expr = self.parse_expression()?;
+        if let Some(assign) = self.parse_assignment(&expr, start)? {
+            return Ok(Stmt::new(assign, self.span_from(start)));
+        }
+        Ok(Stmt::new(StmtKind::Expr(expr), self.span_from(start)))
+    }
+
+    /// Parses what follows `target` in an assignment statement, if anything does:
+    /// `++`, `--`, `= value`, or a compound `op= value`, which desugars to
+    /// `target = target op value`. Does not consume the terminating ';'.
+    fn parse_assignment(&mut self, target: &Expr, start: usize) -> Result<Option<StmtKind>, String> {
+        let op = match self.current_token {
+            Some(Token::Increment) | Some(Token::Decrement) => None,
+            Some(Token::Assign) => None,
+            Some(Token::PlusAssign) => Some(BinaryOp::Add),
+            Some(Token::MinusAssign) => Some(BinaryOp::Subtract),
+            Some(Token::StarAssign) => Some(BinaryOp::Multiply),
+            Some(Token::SlashAssign) => Some(BinaryOp::Divide),
+            Some(Token::PercentAssign) => Some(BinaryOp::Modulo),
+            _ => return Ok(None),
+        };
+        let name = match target.kind {
+            ExprKind::Identifier(name) => name,
+            _ => return Err(format!("Only variables can be assigned to, found {:?}", target)),
+        };
+
+        if matches!(self.current_token, Some(Token::Increment) | Some(Token::Decrement)) {
+            let increment = self.current_token == Some(Token::Increment);
+            self.advance();
+            return Ok(Some(Self::increment(name, increment, self.span_from(start))));
+        }
+        self.advance();
+        let value = self.parse_expression()?;
+        let value = match op {
+            Some(op) => {
+                let span = target.span.to(value.span);
+                Expr::new(ExprKind::BinaryOp { left: Box::new(target.clone()), op, right: Box::new(value) }, span)
+            }
+            None => value,
+        };
+        Ok(Some(StmtKind::Assign { target: name, value }))
+    }
+
+    /// Parses `{ statements }`.
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.expect(Token::LeftBrace)?;
+        let mut body = Vec::new();
+        while self.current_token != Some(Token::RightBrace) {
+            body.push(self.parse_statement()?);
+        }
+        self.expect(Token::RightBrace)?;
+        Ok(body)
+    }
+
+    /// Desugars `x++;` and `x--;` (or `++x;`, `--x;`) into `x = x + 1;` or `x = x - 1;`.
+    fn increment(target: Symbol, increment: bool, span: Span) -> StmtKind {
+        let value = ExprKind::BinaryOp {
+            left: Box::new(Expr::new(ExprKind::Identifier(target), span)),
+            op: if increment { BinaryOp::Add } else { BinaryOp::Subtract },
+            right: Box::new(Expr::new(ExprKind::NumberLiteral(1.0), span)),
+        };
+        StmtKind::Assign { target, value: Expr::new(value, span) }
+    }
+
+    fn parse_expression(&mut self) -> Result<Expr, String> {
+        let start = self.span_start();
+        if self.current_token == Some(Token::Await) {
+            self.advance();
+            let expr = self.parse_expression()?;
+            return Ok(Expr::new(ExprKind::Await(Box::new(expr)), self.span_from(start)));
+        }
+        // Non-operators have precedence 0, so starting at 1 stops at them
+        self.parse_binary_expression(1)
     }
 
     fn parse_binary_expression(&mut self, min_precedence: u8) -> Result<Expr, String> {
@@ -712,89 +1237,505 @@ impl Parser {
                 break;
             }
 
-            let op = self.parse_binary_operator(token)?;
+            let op = if *token == Token::DoubleQuestion { None } else { Some(self.parse_binary_operator(token)?) };
             self.advance();
             let right = self.parse_binary_expression(precedence + 1)?;
 
-            left = Expr::BinaryOp {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
+            let span = left.span.to(right.span);
+            let kind = match op {
+                Some(op) => ExprKind::BinaryOp { left: Box::new(left), op, right: Box::new(right) },
+                None => ExprKind::Coalesce { value: Box::new(left), default: Box::new(right) },
             };
+            left = Expr::new(kind, span);
         }
 
         Ok(left)
     }
 
     fn parse_unary_expression(&mut self) -> Result<Expr, String> {
-        match &self.current_token {
+        let start = self.span_start();
+        let kind = match &self.current_token {
             Some(Token::Minus) => {
                 self.advance();
                 let operand = self.parse_unary_expression()?;
-                Ok(Expr::UnaryOp {
+                ExprKind::UnaryOp {
                     op: UnaryOp::Minus,
                     operand: Box::new(operand),
-                })
+                }
             }
             Some(Token::Not) => {
                 self.advance();
                 let operand = self.parse_unary_expression()?;
-                Ok(Expr::UnaryOp {
+                ExprKind::UnaryOp {
                     op: UnaryOp::Not,
                     operand: Box::new(operand),
-                })
+                }
             }
-            Some(Token::Identifier(_)) => {
-                let func = Box::new(self.parse_primary_expression()?);
-                if self.current_token == Some(Token::LeftParen) {
+            Some(Token::Increment) | Some(Token::Decrement) => {
+                let op = if self.current_token == Some(Token::Increment) {
+                    UnaryOp::Increment
+                } else {
+                    UnaryOp::Decrement
+                };
+                self.advance();
+                let operand = self.parse_unary_expression()?;
+                ExprKind::UnaryOp {
+                    op,
+                    operand: Box::new(operand),
+                }
+            }
+            _ => {
+                let primary = self.parse_primary_expression()?;
+                let base = self.parse_postfix_chain(primary, start)?;
+                return self.parse_power(base);
+            }
+        };
+        Ok(Expr::new(kind, self.span_from(start)))
+    }
+
+    /// Parses `base ** exponent`, if `**` follows. The exponent is itself a unary
+    /// expression, so `**` is right-associative, and it binds tighter than a unary
+    /// operator before the base: `-x ** 2` is `-(x ** 2)`.
+    fn parse_power(&mut self, base: Expr) -> Result<Expr, String> {
+        if self.current_token != Some(Token::StarStar) {
+            return Ok(base);
+        }
+        self.advance();
+        let exponent = self.parse_unary_expression()?;
+        let span = base.span.to(exponent.span);
+        let kind = ExprKind::BinaryOp { left: Box::new(base), op: BinaryOp::Power, right: Box::new(exponent) };
+        Ok(Expr::new(kind, span))
+    }
+
+    /// Applies calls `(args)`, member accesses `.name` (or `::name`), method
+    /// calls `.name(args)`, optional chaining `?.name`, indexing `[i]` and `?`
+    /// to `expr`, left to right.
+    fn parse_postfix_chain(&mut self, mut expr: Expr, start: usize) -> Result<Expr, String> {
+        loop {
+            let kind = match self.current_token {
+                Some(Token::LeftParen) => {
+                    let args = self.parse_arguments()?;
+                    ExprKind::Call { func: Box::new(expr), args }
+                }
+                Some(Token::Dot) | Some(Token::DoubleColon) => {
                     self.advance();
-                    let mut args = Vec::new();
-                    while self.current_token != Some(Token::RightParen) {
-                        args.push(self.parse_expression()?);
-                        if self.current_token == Some(Token::Comma) {
-                            self.advance();
-                        }
+                    let name = match self.current_name() {
+                        Some(name) => name,
+                        None => return Err(format!("Expected a member name after '.', got {:?}", self.current_token)),
+                    };
+                    self.advance();
+                    if self.current_token == Some(Token::LeftParen) {
+                        let args = self.parse_arguments()?;
+                        ExprKind::MethodCall { object: Box::new(expr), method: name, args }
+                    } else {
+                        ExprKind::PropertyAccess { object: Box::new(expr), property: name }
                     }
-                    self.expect(Token::RightParen)?;
-                    Ok(Expr::Call { func, args })
-                } else {
-                    Ok(*func)
                 }
+                Some(Token::LeftBracket) => {
+                    self.advance();
+                    let index = self.parse_expression()?;
+                    self.expect(Token::RightBracket)?;
+                    ExprKind::ArrayAccess { array: Box::new(expr), index: Box::new(index) }
+                }
+                Some(Token::QuestionMark) => {
+                    self.advance();
+                    ExprKind::Try(Box::new(expr))
+                }
+                Some(Token::QuestionDot) => {
+                    self.advance();
+                    let property = match self.current_name() {
+                        Some(name) => name,
+                        None => return Err(format!("Expected a member name after '?.', got {:?}", self.current_token)),
+                    };
+                    self.advance();
+                    let args = if self.current_token == Some(Token::LeftParen) { Some(self.parse_arguments()?) } else { None };
+                    ExprKind::OptionalChain { object: Box::new(expr), property, args }
+                }
+                _ => return Ok(expr),
+            };
+            expr = Expr::new(kind, self.span_from(start));
+        }
+    }
+
+    /// Parses a parenthesized, comma-separated argument list.
+    fn parse_arguments(&mut self) -> Result<Vec<Expr>, String> {
+        self.expect(Token::LeftParen)?;
+        let mut args = Vec::new();
+        while self.current_token != Some(Token::RightParen) {
+            args.push(self.parse_expression()?);
+            if self.current_token == Some(Token::Comma) {
+                self.advance();
+            } else {
+                break;
             }
-            _ => self.parse_primary_expression(),
         }
+        self.expect(Token::RightParen)?;
+        Ok(args)
     }
 
     fn parse_primary_expression(&mut self) -> Result<Expr, String> {
-        match &self.current_token {
+        let start = self.span_start();
+        let kind = match &self.current_token {
             Some(Token::NumberLiteral(n)) => {
                 let value = *n;
                 self.advance();
-                Ok(Expr::NumberLiteral(value))
+                ExprKind::NumberLiteral(value)
             }
             Some(Token::StringLiteral(s)) => {
                 let value = s.clone();
                 self.advance();
-                Ok(Expr::StringLiteral(value))
+                ExprKind::StringLiteral(value)
+            }
+            Some(Token::CharLiteral(c)) => {
+                let value = *c;
+                self.advance();
+                ExprKind::CharLiteral(value)
             }
             Some(Token::BooleanLiteral(b)) => {
                 let value = *b;
                 self.advance();
-                Ok(Expr::BooleanLiteral(value))
+                ExprKind::BooleanLiteral(value)
+            }
+            Some(Token::TemplateLiteral(parts)) => {
+                let parts = parts.clone();
+                self.advance();
+                let mut template = Vec::new();
+                for part in parts {
+                    template.push(match part {
+                        TemplateToken::String(text) => TemplatePart::String(text),
+                        TemplateToken::Expression(tokens) => TemplatePart::Expression(Self::parse_interpolation(tokens)?),
+                    });
+                }
+                ExprKind::TemplateLiteral { parts: template }
             }
             Some(Token::Identifier(name)) => {
-                let value = name.clone();
+                let value = *name;
                 self.advance();
-                Ok(Expr::Identifier(value))
+                ExprKind::Identifier(value)
             }
+            Some(Token::LeftParen) if self.at_arrow_function() => self.parse_arrow_function()?,
             Some(Token::LeftParen) => {
                 self.advance();
                 let expr = self.parse_expression()?;
                 self.expect(Token::RightParen)?;
-                Ok(expr)
+                return Ok(expr);
+            }
+            Some(Token::LeftBracket) => self.parse_array_or_comprehension()?,
+            Some(Token::LeftBrace) => self.parse_object_literal()?,
+            Some(Token::Match) => self.parse_match()?,
+            Some(Token::If) => self.parse_if_expression()?,
+            _ => return Err(format!("Unexpected token: {:?}", self.current_token)),
+        };
+        Ok(Expr::new(kind, self.span_from(start)))
+    }
+
+    /// Parses an array literal `[a, b, c]`, which may end in a trailing comma,
+    /// or a list comprehension `[expr for var in iter if cond]`.
+    fn parse_array_or_comprehension(&mut self) -> Result<ExprKind, String> {
+        self.expect(Token::LeftBracket)?;
+        if self.current_token == Some(Token::RightBracket) {
+            self.advance();
+            return Ok(ExprKind::ArrayLiteral(Vec::new()));
+        }
+
+        let first = self.parse_expression()?;
+        if self.current_token == Some(Token::For) {
+            self.advance();
+            let target = self.expect_identifier()?;
+            self.expect(Token::In)?;
+            let iter = self.parse_expression()?;
+            let mut filter = None;
+            if self.current_token == Some(Token::If) {
+                self.advance();
+                filter = Some(self.parse_expression()?);
+            }
+            self.expect(Token::RightBracket)?;
+            return Ok(ExprKind::Comprehension {
+                target,
+                iter: Box::new(iter),
+                filter: filter.map(Box::new),
+                expr: Box::new(first),
+            });
+        }
+
+        let mut elements = vec![first];
+        while self.current_token == Some(Token::Comma) {
+            self.advance();
+            if self.current_token == Some(Token::RightBracket) {
+                break;
+            }
+            elements.push(self.parse_expression()?);
+        }
+        self.expect(Token::RightBracket)?;
+        Ok(ExprKind::ArrayLiteral(elements))
+    }
+
+    /// Parses `{ key: value, "quoted": value, [computed]: value, shorthand }`,
+    /// which may end in a trailing comma.
+    fn parse_object_literal(&mut self) -> Result<ExprKind, String> {
+        self.expect(Token::LeftBrace)?;
+        let mut properties = Vec::new();
+        while self.current_token != Some(Token::RightBrace) {
+            let start = self.span_start();
+            // Only a plain identifier can stand for a variable of the same name
+            let can_be_shorthand = matches!(self.current_token, Some(Token::Identifier(_)));
+            let key = match self.current_token.clone() {
+                Some(Token::LeftBracket) => {
+                    self.advance();
+                    let key = self.parse_expression()?;
+                    self.expect(Token::RightBracket)?;
+                    PropertyKey::Computed(key)
+                }
+                Some(Token::StringLiteral(s)) => {
+                    self.advance();
+                    PropertyKey::Name(Symbol::intern(&s))
+                }
+                _ => match self.current_name() {
+                    Some(name) => {
+                        self.advance();
+                        PropertyKey::Name(name)
+                    }
+                    None => return Err(format!("Expected property name, got {:?}", self.current_token)),
+                },
+            };
+
+            let shorthand = self.current_token != Some(Token::Colon);
+            let value = if shorthand {
+                match &key {
+                    PropertyKey::Name(name) if can_be_shorthand => Expr::new(ExprKind::Identifier(*name), self.span_from(start)),
+                    _ => return Err(format!("Expected ':' after property key, got {:?}", self.current_token)),
+                }
+            } else {
+                self.advance();
+                self.parse_expression()?
+            };
+            properties.push(ObjectProperty { key, value, shorthand, span: self.span_from(start) });
+
+            if self.current_token == Some(Token::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.expect(Token::RightBrace)?;
+        Ok(ExprKind::ObjectLiteral(properties))
+    }
+
+    /// True if the parenthesized group starting at the current token is followed by `=>`.
+    fn at_arrow_function(&self) -> bool {
+        let mut depth = 0;
+        for (index, spanned) in self.tokens.iter().enumerate().skip(self.position) {
+            match spanned.token {
+                Token::LeftParen => depth += 1,
+                Token::RightParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let next = self.tokens.get(index + 1);
+                        return matches!(next, Some(t) if t.token == Token::FatArrow);
+                    }
+                }
+                Token::EOF => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Parses `(params) => expr` or `(params) => { statements }`. An expression
+    /// body becomes a single `return`.
+    fn parse_arrow_function(&mut self) -> Result<ExprKind, String> {
+        self.expect(Token::LeftParen)?;
+        let mut params = Vec::new();
+        while self.current_token != Some(Token::RightParen) {
+            params.push(self.parse_parameter()?);
+            if self.current_token == Some(Token::Comma) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.expect(Token::RightParen)?;
+        self.expect(Token::FatArrow)?;
+
+        let body = if self.current_token == Some(Token::LeftBrace) {
+            self.parse_block()?
+        } else {
+            let value = self.parse_expression()?;
+            let span = value.span;
+            vec![Stmt::new(StmtKind::Return(Some(value)), span)]
+        };
+        Ok(ExprKind::ArrowFunction { params, body })
+    }
+
+    /// Parses `if cond { a } else { b }` as an expression. The `else` branch is
+    /// required and may itself be an `if`.
+    fn parse_if_expression(&mut self) -> Result<ExprKind, String> {
+        self.expect(Token::If)?;
+        let condition = self.parse_expression()?;
+        self.expect(Token::LeftBrace)?;
+        let then = self.parse_expression()?;
+        self.expect(Token::RightBrace)?;
+        if self.current_token != Some(Token::Else) {
+            return Err(format!("An 'if' expression needs an 'else' branch, got {:?}", self.current_token));
+        }
+        self.advance();
+        let else_ = if self.current_token == Some(Token::If) {
+            let start = self.span_start();
+            let kind = self.parse_if_expression()?;
+            Expr::new(kind, self.span_from(start))
+        } else {
+            self.expect(Token::LeftBrace)?;
+            let else_ = self.parse_expression()?;
+            self.expect(Token::RightBrace)?;
+            else_
+        };
+        Ok(ExprKind::If { condition: Box::new(condition), then: Box::new(then), else_: Box::new(else_) })
+    }
+
+    /// Parses `match scrutinee { pattern [if guard] => body, ... }`.
+    fn parse_match(&mut self) -> Result<ExprKind, String> {
+        self.expect(Token::Match)?;
+        let scrutinee = self.parse_expression()?;
+        self.expect(Token::LeftBrace)?;
+
+        let mut arms = Vec::new();
+        while self.current_token != Some(Token::RightBrace) {
+            let start = self.span_start();
+            let pattern = self.parse_pattern()?;
+            let guard = if self.current_token == Some(Token::If) {
+                self.advance();
+                Some(self.parse_expression()?)
+            } else {
+                None
+            };
+            self.expect(Token::FatArrow)?;
+            let body = self.parse_expression()?;
+            arms.push(MatchArm { pattern, guard, body, span: self.span_from(start) });
+
+            if self.current_token == Some(Token::Comma) {
+                self.advance();
+            } else {
+                break;
             }
-            _ => Err(format!("Unexpected token: {:?}", self.current_token)),
         }
+        self.expect(Token::RightBrace)?;
+
+        Ok(ExprKind::Match { scrutinee: Box::new(scrutinee), arms })
+    }
+
+    /// Parses a literal, `_`, a binding, an `Option`/`Result` constructor pattern,
+    /// or an object or array destructuring pattern.
+    fn parse_pattern(&mut self) -> Result<Pattern, String> {
+        let start = self.span_start();
+        let kind = match self.current_token.clone() {
+            Some(Token::NumberLiteral(n)) => {
+                self.advance();
+                PatternKind::Number(n)
+            }
+            Some(Token::Minus) => {
+                self.advance();
+                match self.current_token {
+                    Some(Token::NumberLiteral(n)) => {
+                        self.advance();
+                        PatternKind::Number(-n)
+                    }
+                    _ => return Err(format!("Expected a number after '-' in pattern, got {:?}", self.current_token)),
+                }
+            }
+            Some(Token::StringLiteral(s)) => {
+                self.advance();
+                PatternKind::String(s)
+            }
+            Some(Token::CharLiteral(c)) => {
+                self.advance();
+                PatternKind::Char(c)
+            }
+            Some(Token::BooleanLiteral(b)) => {
+                self.advance();
+                PatternKind::Boolean(b)
+            }
+            Some(Token::Identifier(name)) => {
+                self.advance();
+                if name == "_" {
+                    PatternKind::Wildcard
+                } else if self.current_token == Some(Token::LeftParen) {
+                    self.advance();
+                    let mut args = Vec::new();
+                    while self.current_token != Some(Token::RightParen) {
+                        args.push(self.parse_pattern()?);
+                        if self.current_token == Some(Token::Comma) {
+                            self.advance();
+                        } else {
+                            break;
+                        }
+                    }
+                    self.expect(Token::RightParen)?;
+                    PatternKind::Constructor { name, args }
+                } else if name == "None" {
+                    PatternKind::Constructor { name, args: Vec::new() }
+                } else {
+                    PatternKind::Binding(name)
+                }
+            }
+            Some(Token::LeftBrace) => {
+                self.advance();
+                let mut fields = Vec::new();
+                while self.current_token != Some(Token::RightBrace) {
+                    let field_start = self.span_start();
+                    let key = self.expect_identifier()?;
+                    let pattern = if self.current_token == Some(Token::Colon) {
+                        self.advance();
+                        self.parse_pattern()?
+                    } else {
+                        Pattern { kind: PatternKind::Binding(key), span: self.span_from(field_start) }
+                    };
+                    fields.push((key, pattern));
+                    if self.current_token == Some(Token::Comma) {
+                        self.advance();
+                    } else {
+                        break;
+                    }
+                }
+                self.expect(Token::RightBrace)?;
+                PatternKind::Object { fields }
+            }
+            Some(Token::LeftBracket) => {
+                self.advance();
+                let mut elements = Vec::new();
+                let mut rest = None;
+                while self.current_token != Some(Token::RightBracket) {
+                    if self.current_token == Some(Token::Ellipsis) {
+                        self.advance();
+                        rest = Some(self.expect_identifier()?);
+                        // The rest binding must come last
+                        break;
+                    }
+                    elements.push(self.parse_pattern()?);
+                    if self.current_token == Some(Token::Comma) {
+                        self.advance();
+                    } else {
+                        break;
+                    }
+                }
+                self.expect(Token::RightBracket)?;
+                PatternKind::Array { elements, rest }
+            }
+            _ => return Err(format!("Expected a pattern, got {:?}", self.current_token)),
+        };
+        Ok(Pattern { kind, span: self.span_from(start) })
+    }
+
+    /// Parses the tokens of a template literal's `${...}` as a single expression.
+    fn parse_interpolation(mut tokens: Vec<SpannedToken>) -> Result<Expr, String> {
+        let end = tokens.last().map_or(Span::default(), |t| Span::new(t.span.end, t.span.end));
+        let (line, col) = tokens.last().map_or((1, 1), |t| (t.line, t.col));
+        tokens.push(SpannedToken { token: Token::EOF, span: end, line, col });
+        let mut parser = Parser::new(tokens);
+        let expr = parser.parse_expression()?;
+        if parser.current_token != Some(Token::EOF) {
+            return Err(format!("Unexpected token in template expression: {:?}", parser.current_token));
+        }
+        Ok(expr)
     }
 
     fn parse_binary_operator(&self, token: &Token) -> Result<BinaryOp, String> {
@@ -806,24 +1747,39 @@ impl Parser {
             Token::Percent => Ok(BinaryOp::Modulo),
             Token::Equal => Ok(BinaryOp::Equal),
             Token::NotEqual => Ok(BinaryOp::NotEqual),
+            Token::StrictEqual => Ok(BinaryOp::StrictEqual),
+            Token::StrictNotEqual => Ok(BinaryOp::StrictNotEqual),
             Token::LessThan => Ok(BinaryOp::LessThan),
             Token::LessThanEqual => Ok(BinaryOp::LessThanEqual),
             Token::GreaterThan => Ok(BinaryOp::GreaterThan),
             Token::GreaterThanEqual => Ok(BinaryOp::GreaterThanEqual),
             Token::And => Ok(BinaryOp::And),
             Token::Or => Ok(BinaryOp::Or),
+            Token::BitwiseAnd => Ok(BinaryOp::BitwiseAnd),
+            Token::BitwiseOr => Ok(BinaryOp::BitwiseOr),
+            Token::BitwiseXor => Ok(BinaryOp::BitwiseXor),
+            Token::LeftShift => Ok(BinaryOp::LeftShift),
+            Token::RightShift => Ok(BinaryOp::RightShift),
+            Token::UnsignedRightShift => Ok(BinaryOp::UnsignedRightShift),
             _ => Err(format!("Invalid binary operator: {:?}", token)),
         }
     }
 
+    /// Precedence of a left-associative binary operator; higher binds tighter.
+    /// `**` is parsed separately, by `parse_power`.
     fn get_operator_precedence(&self, token: &Token) -> u8 {
         match token {
-            Token::Or => 1,
-            Token::And => 2,
-            Token::Equal | Token::NotEqual => 3,
-            Token::LessThan | Token::LessThanEqual | Token::GreaterThan | Token::GreaterThanEqual => 4,
-            Token::Plus | Token::Minus => 5,
-            Token::Star | Token::Slash | Token::Percent => 6,
+            Token::DoubleQuestion => 1,
+            Token::Or => 2,
+            Token::And => 3,
+            Token::BitwiseOr => 4,
+            Token::BitwiseXor => 5,
+            Token::BitwiseAnd => 6,
+            Token::Equal | Token::NotEqual | Token::StrictEqual | Token::StrictNotEqual => 7,
+            Token::LessThan | Token::LessThanEqual | Token::GreaterThan | Token::GreaterThanEqual => 8,
+            Token::LeftShift | Token::RightShift | Token::UnsignedRightShift => 9,
+            Token::Plus | Token::Minus => 10,
+            Token::Star | Token::Slash | Token::Percent => 11,
             _ => 0,
         }
     }
@@ -837,10 +1793,35 @@ impl Parser {
         }
     }
 
-    fn expect_identifier(&mut self) -> Result<String, String> {
+    /// True if the current token is the identifier `name`.
+    fn at_identifier(&self, name: &str) -> bool {
+        matches!(&self.current_token, Some(Token::Identifier(ident)) if *ident == name)
+    }
+
+    /// The current token as a name where keywords are allowed too, as in markup attributes.
+    fn current_name(&self) -> Option<Symbol> {
+        match &self.current_token {
+            Some(Token::Identifier(name)) => Some(*name),
+            Some(token) => token.keyword_text().map(Symbol::intern),
+            None => None,
+        }
+    }
+
+    /// Like `expect_identifier`, but accepts keywords too, as markup and attribute names may be keywords.
+    fn expect_name(&mut self, what: &str) -> Result<Symbol, String> {
+        match self.current_name() {
+            Some(name) => {
+                self.advance();
+                Ok(name)
+            }
+            None => Err(format!("Expected {}, got {:?}", what, self.current_token)),
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<Symbol, String> {
         match &self.current_token {
             Some(Token::Identifier(name)) => {
-                let value = name.clone();
+                let value = *name;
                 self.advance();
                 Ok(value)
             }
@@ -862,15 +1843,27 @@ impl Parser {
     fn advance(&mut self) {
         self.position += 1;
         self.current_token = if self.position < self.tokens.len() {
-            Some(self.tokens[self.position].clone())
+            Some(self.tokens[self.position].token.clone())
         } else {
             None
         };
     }
 
+    /// True after `let` or `mut` when a pattern follows rather than a plain name:
+    /// `{`, `[`, or a constructor such as `Some(`.
+    fn at_let_pattern(&self) -> bool {
+        match self.peek() {
+            Some(Token::LeftBrace) | Some(Token::LeftBracket) => true,
+            Some(Token::Identifier(_)) => {
+                matches!(self.tokens.get(self.position + 2).map(|t| &t.token), Some(Token::LeftParen))
+            }
+            _ => false,
+        }
+    }
+
     fn peek(&self) -> Option<&Token> {
         if self.position + 1 < self.tokens.len() {
-            Some(&self.tokens[self.position + 1])
+            Some(&self.tokens[self.position + 1].token)
         } else {
             None
         }
@@ -0,0 +1,1199 @@
+//! Pretty-printer for Gigli source, used by `gigli fmt`
+//!
+//! The AST is turned into a document of text, possible line breaks and groups,
+//! then laid out so that each group is printed on one line if it fits in
+//! `MAX_WIDTH` columns and broken over several lines otherwise. Blocks are
+//! indented by four spaces and keep at most one blank line between members.
+//!
+//! The AST does not keep comments, so the formatter takes them from the lexer
+//! and puts each back before the item, statement or markup node it preceded, or
+//! at the end of the line it trailed. A few things the AST only records after
+//! desugaring, such as `x += 1`, ranged `for` loops and `mut`, are recovered
+//! from spans and the source.
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{Comment, Lexer};
+use crate::parser::Parser;
+use crate::profiling;
+
+/// Lines are wrapped to fit in this many columns where the syntax allows it.
+pub const MAX_WIDTH: usize = 100;
+const INDENT: usize = 4;
+
+/// HTML elements that have no content, printed as `<br />`.
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+
+/// Parses and formats a source file. Fails if the source does not parse.
+pub fn format_source(source: &str) -> Result<String, Diagnostic> {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize()?;
+    let ast = Parser::new(tokens).parse()?;
+    Ok(format_ast(&ast, source, lexer.comments()))
+}
+
+/// Formats a program parsed from `source`, putting back `comments`.
+pub fn format_ast(ast: &AST, source: &str, comments: &[Comment]) -> String {
+    let _timer = profiling::scope("format");
+    let mut printer = Printer { source, comments, next_comment: 0 };
+
+    let mut members = Vec::new();
+    members.extend(ast.imports.iter().map(Member::Import));
+    members.extend(ast.externs.iter().map(Member::Extern));
+    members.extend(ast.functions.iter().map(Member::Function));
+    members.extend(ast.classes.iter().map(Member::Class));
+    members.extend(ast.modules.iter().map(Member::Module));
+    members.extend(ast.components.iter().map(Member::Component));
+    let doc = printer.members(members, source.len());
+
+    let mut out = render(&doc, MAX_WIDTH);
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// A document to lay out.
+enum Doc {
+    Text(String),
+    /// A space, or a line break if the enclosing group is broken.
+    Line,
+    /// Nothing, or a line break if the enclosing group is broken.
+    SoftLine,
+    /// Always a line break.
+    HardLine,
+    /// Text printed only if the enclosing group is broken, like a trailing comma.
+    IfBroken(&'static str),
+    Indent(Box<Doc>),
+    /// Printed flat if it fits on the rest of the line, broken otherwise.
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+fn indent(doc: Doc) -> Doc {
+    Doc::Indent(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+/// `open item, item close`, with each item on its own line and a trailing
+/// comma if it does not fit. `padded` lists have spaces inside the delimiters.
+fn list(open: &str, items: Vec<Doc>, close: &str, padded: bool) -> Doc {
+    if items.is_empty() {
+        return text(format!("{}{}", open, close));
+    }
+    let line = || if padded { Doc::Line } else { Doc::SoftLine };
+    let mut inner = vec![line()];
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            inner.push(text(","));
+            inner.push(Doc::Line);
+        }
+        inner.push(item);
+    }
+    inner.push(Doc::IfBroken(","));
+    group(concat(vec![text(open), indent(concat(inner)), line(), text(close)]))
+}
+
+/// Text that may span several lines, such as a block comment or a style
+/// block. Indentation relative to the first line is kept.
+fn verbatim(source: &str, start: usize, raw: &str) -> Doc {
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let base = source[line_start..start].chars().take_while(|c| c.is_whitespace()).count();
+    let mut docs = Vec::new();
+    for (i, line) in raw.lines().enumerate() {
+        if i > 0 {
+            docs.push(Doc::HardLine);
+            let strip = line.chars().take(base).take_while(|c| c.is_whitespace()).count();
+            docs.push(text(line[line.char_indices().nth(strip).map_or(line.len(), |(i, _)| i)..].trim_end()));
+        } else {
+            docs.push(text(line.trim_end()));
+        }
+    }
+    concat(docs)
+}
+
+/// Lays out `doc` in `width` columns.
+fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    // (indentation, whether the enclosing group is broken, doc), in reverse order
+    let mut stack: Vec<(usize, bool, &Doc)> = vec![(0, true, doc)];
+    while let Some((indentation, broken, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                out.push_str(s);
+                column = match s.rfind('\n') {
+                    Some(i) => s[i + 1..].chars().count(),
+                    None => column + s.chars().count(),
+                };
+            }
+            Doc::Line if !broken => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if !broken => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                let trimmed = out.trim_end_matches(' ').len();
+                out.truncate(trimmed);
+                out.push('\n');
+                out.push_str(&" ".repeat(indentation));
+                column = indentation;
+            }
+            Doc::IfBroken(s) => {
+                if broken {
+                    out.push_str(s);
+                    column += s.len();
+                }
+            }
+            Doc::Indent(inner) => stack.push((indentation + INDENT, broken, inner)),
+            Doc::Group(inner) => {
+                let flat = fits(width as isize - column as isize, inner, &stack);
+                stack.push((indentation, !flat, inner));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|d| (indentation, broken, d))),
+        }
+    }
+    out
+}
+
+/// Whether `doc` printed flat, followed by what comes after it up to the next
+/// line break, fits in `remaining` columns.
+fn fits(mut remaining: isize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut pending: Vec<(bool, &Doc)> = vec![(false, doc)];
+    let mut rest = rest.iter().rev();
+    loop {
+        if remaining < 0 {
+            return false;
+        }
+        let (broken, doc) = match pending.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, broken, doc)) => (broken, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(s) => match s.find('\n') {
+                Some(i) => return remaining >= s[..i].chars().count() as isize,
+                None => remaining -= s.chars().count() as isize,
+            },
+            Doc::Line | Doc::SoftLine if broken => return true,
+            Doc::Line => remaining -= 1,
+            Doc::SoftLine => {}
+            Doc::HardLine => return true,
+            Doc::IfBroken(s) => {
+                if broken {
+                    remaining -= s.len() as isize;
+                }
+            }
+            Doc::Indent(inner) | Doc::Group(inner) => pending.push((broken, inner)),
+            Doc::Concat(docs) => pending.extend(docs.iter().rev().map(|d| (broken, d))),
+        }
+    }
+}
+
+/// Anything laid out one per line in a block: items, class and component
+/// members, statements and top-level markup.
+enum Member<'a> {
+    Import(&'a Import),
+    Extern(&'a ExternFunction),
+    Function(&'a Function),
+    Class(&'a Class),
+    Module(&'a Module),
+    Component(&'a ComponentNode),
+    Constant(&'a Constant),
+    Field(&'a Field),
+    Constructor(&'a Constructor),
+    Method(&'a Method),
+    State(&'a StateVar),
+    Let(&'a LetVar),
+    Markup(&'a MarkupNode),
+    Style(Span),
+    Stmt(&'a Stmt),
+}
+
+impl Member<'_> {
+    fn span(&self) -> Span {
+        match self {
+            Member::Import(import) => import.span,
+            Member::Extern(function) => function.span,
+            Member::Function(function) => function.span,
+            Member::Class(class) => class.span,
+            Member::Module(module) => module.span,
+            Member::Component(component) => component.span,
+            Member::Constant(constant) => constant.span,
+            Member::Field(field) => field.span,
+            Member::Constructor(constructor) => constructor.span,
+            Member::Method(method) => method.span,
+            Member::State(state) => state.span,
+            Member::Let(var) => var.span,
+            Member::Markup(node) => markup_span(node),
+            Member::Style(span) => *span,
+            Member::Stmt(stmt) => stmt.span,
+        }
+    }
+}
+
+fn markup_span(node: &MarkupNode) -> Span {
+    match node {
+        MarkupNode::Element { span, .. } => *span,
+        MarkupNode::Text(expr) => expr.span,
+        MarkupNode::IfBlock(block) => block.span,
+        MarkupNode::ForLoop(block) => block.span,
+    }
+}
+
+/// Precedence of a binary operator; higher binds tighter. Parenthesized
+/// expressions are not kept in the AST, so parentheses are put back wherever
+/// an operand binds more loosely than its operator.
+fn binary_precedence(op: &BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Or => 2,
+        BinaryOp::And => 3,
+        BinaryOp::BitwiseOr => 4,
+        BinaryOp::BitwiseXor => 5,
+        BinaryOp::BitwiseAnd => 6,
+        BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::StrictEqual | BinaryOp::StrictNotEqual => 7,
+        BinaryOp::LessThan | BinaryOp::LessThanEqual | BinaryOp::GreaterThan | BinaryOp::GreaterThanEqual => 8,
+        BinaryOp::LeftShift | BinaryOp::RightShift | BinaryOp::UnsignedRightShift => 9,
+        BinaryOp::Add | BinaryOp::Subtract => 10,
+        BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => 11,
+        BinaryOp::Power => 12,
+    }
+}
+
+/// `??` binds more loosely than any binary operator.
+const COALESCE_PRECEDENCE: u8 = 1;
+const UNARY_PRECEDENCE: u8 = 13;
+const POSTFIX_PRECEDENCE: u8 = 14;
+
+fn precedence(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::BinaryOp { op, .. } => binary_precedence(op),
+        ExprKind::Concat { .. } => binary_precedence(&BinaryOp::Add),
+        ExprKind::Coalesce { .. } => COALESCE_PRECEDENCE,
+        ExprKind::UnaryOp { .. } => UNARY_PRECEDENCE,
+        // These extend as far to the right as they can
+        ExprKind::Await(_) | ExprKind::ArrowFunction { .. } | ExprKind::TypeAssert { .. } => 0,
+        ExprKind::If { .. } | ExprKind::Match { .. } => 0,
+        _ => POSTFIX_PRECEDENCE,
+    }
+}
+
+fn binary_operator(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Modulo => "%",
+        BinaryOp::Power => "**",
+        BinaryOp::Equal => "==",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::StrictEqual => "===",
+        BinaryOp::StrictNotEqual => "!==",
+        BinaryOp::LessThan => "<",
+        BinaryOp::LessThanEqual => "<=",
+        BinaryOp::GreaterThan => ">",
+        BinaryOp::GreaterThanEqual => ">=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::BitwiseAnd => "&",
+        BinaryOp::BitwiseOr => "|",
+        BinaryOp::BitwiseXor => "^",
+        BinaryOp::LeftShift => "<<",
+        BinaryOp::RightShift => ">>",
+        BinaryOp::UnsignedRightShift => ">>>",
+    }
+}
+
+fn unary_operator(op: &UnaryOp) -> &'static str {
+    match op {
+        UnaryOp::Plus => "+",
+        UnaryOp::Minus => "-",
+        UnaryOp::Not => "!",
+        UnaryOp::BitwiseNot => "~",
+        UnaryOp::Increment => "++",
+        UnaryOp::Decrement => "--",
+        UnaryOp::TypeOf => "typeof ",
+        UnaryOp::Void => "void ",
+        UnaryOp::Delete => "delete ",
+    }
+}
+
+/// Escapes text for a double-quoted string literal.
+fn escape_string(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", escape_string(s))
+}
+
+fn char_literal(c: char) -> String {
+    match c {
+        '\n' => "'\\n'".to_string(),
+        '\t' => "'\\t'".to_string(),
+        '\r' => "'\\r'".to_string(),
+        '\0' => "'\\0'".to_string(),
+        '\\' => "'\\\\'".to_string(),
+        '\'' => "'\\''".to_string(),
+        c => format!("'{}'", c),
+    }
+}
+
+/// If `value`, assigned to `target`, is the `target + 1` or `target - 1` that
+/// `target++` or `target--` desugars to, the operator. The parser gives both
+/// operands of the desugared form the span of the whole statement.
+fn increment_operator(target: Symbol, value: &Expr) -> Option<&'static str> {
+    match &value.kind {
+        ExprKind::BinaryOp { left, op, right } if left.span == right.span => {
+            match (&left.kind, op, &right.kind) {
+                (ExprKind::Identifier(name), BinaryOp::Add, ExprKind::NumberLiteral(n)) if *name == target && *n == 1.0 => Some("++"),
+                (ExprKind::Identifier(name), BinaryOp::Subtract, ExprKind::NumberLiteral(n)) if *name == target && *n == 1.0 => Some("--"),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    comments: &'a [Comment],
+    /// The first comment not yet printed.
+    next_comment: usize,
+}
+
+impl<'a> Printer<'a> {
+    /// Lays out members one per line in source order, with the comments
+    /// before them, up to `end`.
+    fn members(&mut self, mut members: Vec<Member<'a>>, end: usize) -> Doc {
+        members.sort_by_key(|member| member.span().start);
+        let mut docs = Vec::new();
+        let mut last_end = None;
+        for member in members {
+            let span = member.span();
+            self.comments_before(span.start, &mut docs, &mut last_end);
+            self.separate(&mut docs, last_end, span.start);
+            docs.push(self.member(&member));
+            last_end = Some(span.end);
+        }
+        self.comments_before(end, &mut docs, &mut last_end);
+        concat(docs)
+    }
+
+    /// Starts a new line between members, keeping one blank line if the source has any.
+    fn separate(&self, docs: &mut Vec<Doc>, last_end: Option<usize>, start: usize) {
+        if let Some(last_end) = last_end {
+            docs.push(Doc::HardLine);
+            if last_end <= start && self.source[last_end..start].matches('\n').count() > 1 {
+                docs.push(Doc::HardLine);
+            }
+        }
+    }
+
+    /// Prints the comments that start before `position`. A comment on the same
+    /// line as the previous member stays at the end of that line.
+    fn comments_before(&mut self, position: usize, docs: &mut Vec<Doc>, last_end: &mut Option<usize>) {
+        while let Some(comment) = self.comments.get(self.next_comment).filter(|c| c.span.start < position) {
+            self.next_comment += 1;
+            let trailing = last_end.is_some_and(|end| end <= comment.span.start && !self.source[end..comment.span.start].contains('\n'));
+            if trailing {
+                docs.push(text(" "));
+            } else {
+                self.separate(docs, *last_end, comment.span.start);
+            }
+            docs.push(verbatim(self.source, comment.span.start, &comment.text));
+            *last_end = Some(comment.span.end);
+        }
+    }
+
+    /// `{ members }`, or `{}` if there is nothing inside.
+    fn braces(&mut self, members: Vec<Member<'a>>, end: usize) -> Doc {
+        let has_comments = self.comments.get(self.next_comment).is_some_and(|c| c.span.start < end);
+        if members.is_empty() && !has_comments {
+            return text("{}");
+        }
+        let body = self.members(members, end);
+        concat(vec![text("{"), indent(concat(vec![Doc::HardLine, body])), Doc::HardLine, text("}")])
+    }
+
+    fn block(&mut self, stmts: &'a [Stmt], end: usize) -> Doc {
+        self.braces(stmts.iter().map(Member::Stmt).collect(), end)
+    }
+
+    fn member(&mut self, member: &Member<'a>) -> Doc {
+        match *member {
+            Member::Import(import) => {
+                let items = import.items.iter().map(|item| text(item.to_string())).collect();
+                let mut docs = vec![text("import "), list("{", items, "}", true), text(format!(" from {}", import.module))];
+                if let Some(alias) = import.alias {
+                    docs.push(text(format!(" as {}", alias)));
+                }
+                docs.push(text(";"));
+                concat(docs)
+            }
+            Member::Extern(function) => concat(vec![
+                text(format!("extern {} fn {}", quote(&function.abi), function.name)),
+                self.params(&function.params),
+                self.type_annotation(&function.return_type),
+                text(";"),
+            ]),
+            Member::Function(function) => {
+                let keyword = if function.is_async { "async fn" } else { "fn" };
+                concat(vec![
+                    self.attributes(&function.attributes),
+                    text(format!("{}{} {}", if function.is_public { "" } else { "private " }, keyword, function.name)),
+                    self.params(&function.params),
+                    self.type_annotation(&function.return_type),
+                    text(" "),
+                    self.block(&function.body, function.span.end),
+                ])
+            }
+            Member::Class(class) => {
+                let mut members: Vec<Member> = class.fields.iter().map(Member::Field).collect();
+                members.extend(class.constructor.iter().map(Member::Constructor));
+                members.extend(class.methods.iter().map(Member::Method));
+                concat(vec![
+                    self.attributes(&class.attributes),
+                    text(format!("class {} ", class.name)),
+                    self.braces(members, class.span.end),
+                ])
+            }
+            Member::Module(module) => {
+                let members = module
+                    .items
+                    .iter()
+                    .map(|item| match item {
+                        ModuleItem::Function(function) => Member::Function(function),
+                        ModuleItem::Class(class) => Member::Class(class),
+                        ModuleItem::Component(component) => Member::Component(component),
+                        ModuleItem::Constant(constant) => Member::Constant(constant),
+                    })
+                    .collect();
+                concat(vec![text(format!("module {} ", module.name)), self.braces(members, module.span.end)])
+            }
+            Member::Component(component) => {
+                let mut members: Vec<Member> = component.state_vars.iter().map(Member::State).collect();
+                members.extend(component.let_vars.iter().map(Member::Let));
+                members.extend(component.reactive.iter().map(Member::Stmt));
+                members.extend(component.functions.iter().map(Member::Function));
+                members.extend(component.markup.iter().map(Member::Markup));
+                members.extend(component.style_span.map(Member::Style));
+                concat(vec![
+                    self.attributes(&component.attributes),
+                    text(format!("component {} ", component.name)),
+                    self.braces(members, component.span.end),
+                ])
+            }
+            Member::Constant(constant) => {
+                self.declaration("const", constant.name, &constant.type_annotation, &constant.value)
+            }
+            Member::Field(field) => {
+                let mut docs = vec![text(format!("{}{}", if field.is_public { "" } else { "private " }, field.name))];
+                docs.push(self.type_annotation(&field.type_annotation));
+                if let Some(value) = &field.initial_value {
+                    docs.push(text(" = "));
+                    docs.push(self.expr(value));
+                }
+                docs.push(text(";"));
+                concat(docs)
+            }
+            Member::Constructor(constructor) => concat(vec![
+                text("constructor"),
+                self.params(&constructor.params),
+                text(" "),
+                self.block(&constructor.body, constructor.span.end),
+            ]),
+            Member::Method(method) => concat(vec![
+                text(format!("{}fn {}", if method.is_public { "" } else { "private " }, method.name)),
+                self.params(&method.params),
+                self.type_annotation(&method.return_type),
+                text(" "),
+                self.block(&method.body, method.span.end),
+            ]),
+            Member::State(state) => self.declaration("state", state.name, &state.type_annotation, &state.initial_value),
+            Member::Let(var) => self.declaration("let", var.name, &var.type_annotation, &var.value),
+            Member::Markup(node) => self.markup(node),
+            // The style block is not parsed into the AST, so it is kept as written
+            Member::Style(span) => verbatim(self.source, span.start, &self.source[span.start..span.end]),
+            Member::Stmt(stmt) => self.stmt(stmt),
+        }
+    }
+
+    /// One `@name(args)` line per attribute.
+    fn attributes(&mut self, attributes: &'a [Attribute]) -> Doc {
+        let mut docs = Vec::new();
+        for attribute in attributes {
+            docs.push(text(format!("@{}", attribute.name)));
+            if !attribute.args.is_empty() {
+                let args = attribute.args.iter().map(|arg| self.expr(arg)).collect();
+                docs.push(list("(", args, ")", false));
+            }
+            docs.push(Doc::HardLine);
+        }
+        concat(docs)
+    }
+
+    /// `keyword name: type = value;`
+    fn declaration(&mut self, keyword: &str, name: Symbol, type_annotation: &Option<Type>, value: &'a Expr) -> Doc {
+        concat(vec![
+            text(format!("{} {}", keyword, name)),
+            self.type_annotation(type_annotation),
+            text(" = "),
+            self.expr(value),
+            text(";"),
+        ])
+    }
+
+    fn type_annotation(&self, type_annotation: &Option<Type>) -> Doc {
+        match type_annotation {
+            Some(ty) => text(format!(": {}", ty)),
+            None => text(""),
+        }
+    }
+
+    fn params(&mut self, params: &'a [Parameter]) -> Doc {
+        let params = params.iter().map(|param| self.param(param)).collect();
+        list("(", params, ")", false)
+    }
+
+    fn param(&mut self, param: &'a Parameter) -> Doc {
+        let reference = if param.is_mut_ref {
+            "&mut "
+        } else if param.is_ref {
+            "&"
+        } else {
+            ""
+        };
+        let name = match &param.pattern {
+            Some(pattern) => pattern.to_string(),
+            None => param.name.to_string(),
+        };
+        let mut docs = vec![text(format!("{}{}", reference, name)), self.type_annotation(&param.type_annotation)];
+        if let Some(default) = &param.default_value {
+            docs.push(text(" = "));
+            docs.push(self.expr(default));
+        }
+        concat(docs)
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) -> Doc {
+        match &stmt.kind {
+            StmtKind::Expr(expr) => {
+                // Like a block, a `match` statement needs no semicolon
+                let semicolon = if matches!(expr.kind, ExprKind::Match { .. }) { "" } else { ";" };
+                concat(vec![self.expr(expr), text(semicolon)])
+            }
+            StmtKind::Assign { .. } => concat(vec![self.assignment(stmt), text(";")]),
+            StmtKind::If { condition, then, else_ } => {
+                let then_end = else_.as_ref().and_then(|body| body.first()).map_or(stmt.span.end, |s| s.span.start);
+                let mut docs = vec![text("if ("), self.expr(condition), text(") "), self.block(then, then_end)];
+                if let Some(else_) = else_ {
+                    docs.push(text(" else "));
+                    docs.push(self.block(else_, stmt.span.end));
+                }
+                concat(docs)
+            }
+            StmtKind::Loop { init, condition, update, body } => {
+                let header = match (init, condition, update) {
+                    (None, None, None) => text("loop "),
+                    (None, Some(condition), None) => concat(vec![text("while "), self.expr(condition), text(" ")]),
+                    (Some(init), Some(condition), Some(update)) if self.is_range(init, condition, update) => self.range_header(init, condition),
+                    _ => {
+                        let mut docs = vec![text("for (")];
+                        match init {
+                            Some(init) => docs.push(self.stmt(init)),
+                            None => docs.push(text(";")),
+                        }
+                        docs.push(text(" "));
+                        if let Some(condition) = condition {
+                            docs.push(self.expr(condition));
+                        }
+                        docs.push(text(";"));
+                        if let Some(update) = update {
+                            docs.push(text(" "));
+                            docs.push(self.for_update(update));
+                        }
+                        docs.push(text(") "));
+                        concat(docs)
+                    }
+                };
+                concat(vec![header, self.block(body, stmt.span.end)])
+            }
+            StmtKind::ForIn { variable, iterable, body } | StmtKind::ForOf { variable, iterable, body } => {
+                let keyword = if matches!(stmt.kind, StmtKind::ForIn { .. }) { "in" } else { "of" };
+                concat(vec![
+                    text(format!("for {} {} ", variable, keyword)),
+                    self.expr(iterable),
+                    text(" "),
+                    self.block(body, stmt.span.end),
+                ])
+            }
+            StmtKind::Return(value) => match value {
+                Some(value) => concat(vec![text("return "), self.expr(value), text(";")]),
+                None => text("return;"),
+            },
+            StmtKind::StateVarDecl(state) => self.declaration("state", state.name, &state.type_annotation, &state.initial_value),
+            StmtKind::LetVarDecl(var) => {
+                let keyword = if var.mutable { "mut" } else { "let" };
+                self.declaration(keyword, var.name, &var.type_annotation, &var.value)
+            }
+            StmtKind::Destructure { pattern, type_annotation, value, else_branch, mutable } => {
+                let keyword = if *mutable { "mut" } else { "let" };
+                let mut docs = vec![
+                    text(format!("{} {}", keyword, pattern)),
+                    self.type_annotation(type_annotation),
+                    text(" = "),
+                    self.expr(value),
+                ];
+                if let Some(body) = else_branch {
+                    docs.push(text(" else "));
+                    docs.push(self.block(body, stmt.span.end));
+                }
+                docs.push(text(";"));
+                concat(docs)
+            }
+            StmtKind::Block(body) => self.block(body, stmt.span.end),
+            StmtKind::Try { body, catch, finally } => {
+                let body_end = catch.as_ref().map_or(stmt.span.end, |c| c.span.start);
+                let mut docs = vec![text("try "), self.block(body, body_end)];
+                if let Some(catch) = catch {
+                    docs.push(text(format!(" catch ({}) ", catch.error_var)));
+                    docs.push(self.block(&catch.body, catch.span.end));
+                }
+                if let Some(finally) = finally {
+                    docs.push(text(" finally "));
+                    docs.push(self.block(finally, stmt.span.end));
+                }
+                concat(docs)
+            }
+            StmtKind::Throw(value) => concat(vec![text("throw "), self.expr(value), text(";")]),
+            StmtKind::Break(label) => text(match label {
+                Some(label) => format!("break {};", label),
+                None => "break;".to_string(),
+            }),
+            StmtKind::Continue(label) => text(match label {
+                Some(label) => format!("continue {};", label),
+                None => "continue;".to_string(),
+            }),
+            StmtKind::Switch { expression, cases, default } => {
+                let mut arms = Vec::new();
+                for case in cases {
+                    arms.push(Doc::HardLine);
+                    arms.push(concat(vec![text("case "), self.expr(&case.value), text(":")]));
+                    let body = self.members(case.body.iter().map(Member::Stmt).collect(), case.span.end);
+                    arms.push(indent(concat(vec![Doc::HardLine, body])));
+                }
+                if let Some(default) = default {
+                    arms.push(Doc::HardLine);
+                    arms.push(text("default:"));
+                    let body = self.members(default.iter().map(Member::Stmt).collect(), stmt.span.end);
+                    arms.push(indent(concat(vec![Doc::HardLine, body])));
+                }
+                concat(vec![
+                    text("switch ("),
+                    self.expr(expression),
+                    text(") {"),
+                    indent(concat(arms)),
+                    Doc::HardLine,
+                    text("}"),
+                ])
+            }
+            StmtKind::Reactive { name, expr } => concat(vec![text(format!("$: {} = ", name)), self.expr(expr), text(";")]),
+            StmtKind::Comprehension { target, iter, filter, expr } => {
+                concat(vec![self.comprehension(*target, iter, filter.as_ref(), expr), text(";")])
+            }
+        }
+    }
+
+    /// An assignment without its `;`, in the form it was written: `x++`,
+    /// `x += y` or `x = y`. The parser desugars the first two to `x = x + ...`.
+    fn assignment(&mut self, stmt: &'a Stmt) -> Doc {
+        let (target, value) = match &stmt.kind {
+            StmtKind::Assign { target, value } => (*target, value),
+            _ => return self.stmt(stmt),
+        };
+        if let Some(op) = increment_operator(target, value) {
+            return text(format!("{}{}", target, op));
+        }
+        // A compound assignment's value starts at the target, where an explicit `x = x + y` does not
+        if let ExprKind::BinaryOp { left, op, right } = &value.kind {
+            let compound = matches!(left.kind, ExprKind::Identifier(name) if name == target)
+                && value.span.start == stmt.span.start
+                && matches!(op, BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo);
+            if compound {
+                return concat(vec![text(format!("{} {}= ", target, binary_operator(op))), self.expr(right)]);
+            }
+        }
+        concat(vec![text(format!("{} = ", target)), self.expr(value)])
+    }
+
+    /// The update clause of a C-style `for`, which has no `;`.
+    fn for_update(&mut self, update: &'a Stmt) -> Doc {
+        match &update.kind {
+            StmtKind::Assign { .. } => self.assignment(update),
+            StmtKind::Expr(expr) => self.expr(expr),
+            _ => self.stmt(update),
+        }
+    }
+
+    /// Whether a loop is the desugared form of `for i in start..end`, whose
+    /// condition and update both have the span of the loop variable.
+    fn is_range(&self, init: &Stmt, condition: &Expr, update: &Stmt) -> bool {
+        let variable = match &init.kind {
+            StmtKind::LetVarDecl(var) => var.name,
+            _ => return false,
+        };
+        let compares_variable = matches!(
+            &condition.kind,
+            ExprKind::BinaryOp { left, op: BinaryOp::LessThan | BinaryOp::LessThanEqual, .. }
+                if matches!(left.kind, ExprKind::Identifier(name) if name == variable)
+        );
+        let increments_variable = matches!(update.kind, StmtKind::Assign { target, .. } if target == variable);
+        compares_variable && increments_variable && condition.span == update.span
+    }
+
+    fn range_header(&mut self, init: &'a Stmt, condition: &'a Expr) -> Doc {
+        match (&init.kind, &condition.kind) {
+            (StmtKind::LetVarDecl(var), ExprKind::BinaryOp { op, right, .. }) => {
+                let range = if *op == BinaryOp::LessThanEqual { "..=" } else { ".." };
+                concat(vec![
+                    text(format!("for {} in ", var.name)),
+                    self.expr(&var.value),
+                    text(range),
+                    self.expr(right),
+                    text(" "),
+                ])
+            }
+            _ => text(""),
+        }
+    }
+
+    /// `expr`, parenthesized if it binds more loosely than `min_precedence`.
+    fn operand(&mut self, expr: &'a Expr, min_precedence: u8) -> Doc {
+        let doc = self.expr(expr);
+        if precedence(expr) < min_precedence {
+            concat(vec![text("("), doc, text(")")])
+        } else {
+            doc
+        }
+    }
+
+    /// The object of a member access. `value?` is parenthesized, as `value?.name`
+    /// would be optional chaining.
+    fn member_object(&mut self, object: &'a Expr) -> Doc {
+        let min_precedence = if matches!(object.kind, ExprKind::Try(_)) { POSTFIX_PRECEDENCE + 1 } else { POSTFIX_PRECEDENCE };
+        self.operand(object, min_precedence)
+    }
+
+    fn args(&mut self, args: &'a [Expr]) -> Doc {
+        let args = args.iter().map(|arg| self.expr(arg)).collect();
+        list("(", args, ")", false)
+    }
+
+    fn expr(&mut self, expr: &'a Expr) -> Doc {
+        match &expr.kind {
+            ExprKind::StringLiteral(s) => text(quote(s)),
+            ExprKind::CharLiteral(c) => text(char_literal(*c)),
+            ExprKind::NumberLiteral(n) => {
+                // Keep the literal as written, like `0xFF` or `1e6`
+                match self.source.get(expr.span.start..expr.span.end) {
+                    Some(written) if written.starts_with(|c: char| c.is_ascii_digit()) && !written.contains(char::is_whitespace) => text(written),
+                    _ => text(n.to_string()),
+                }
+            }
+            ExprKind::BooleanLiteral(b) => text(b.to_string()),
+            ExprKind::NullLiteral => text("null"),
+            ExprKind::UndefinedLiteral => text("undefined"),
+            ExprKind::ArrayLiteral(elements) => {
+                let elements = elements.iter().map(|e| self.expr(e)).collect();
+                list("[", elements, "]", false)
+            }
+            ExprKind::ObjectLiteral(properties) => {
+                let properties = properties.iter().map(|p| self.property(p)).collect();
+                list("{", properties, "}", true)
+            }
+            ExprKind::Identifier(name) | ExprKind::CellAccess(name) => text(name.to_string()),
+            ExprKind::BinaryOp { left, op, right } => {
+                let precedence = binary_precedence(op);
+                // `**` is right-associative, and a unary base would take the whole power
+                let (left_min, right_min) =
+                    if *op == BinaryOp::Power { (POSTFIX_PRECEDENCE, precedence) } else { (precedence, precedence + 1) };
+                concat(vec![
+                    self.operand(left, left_min),
+                    text(format!(" {} ", binary_operator(op))),
+                    self.operand(right, right_min),
+                ])
+            }
+            ExprKind::Concat { left, right } => {
+                let precedence = binary_precedence(&BinaryOp::Add);
+                concat(vec![self.operand(left, precedence), text(" + "), self.operand(right, precedence + 1)])
+            }
+            ExprKind::UnaryOp { op, operand } => {
+                let op = unary_operator(op);
+                // `- -x` must not become the `--` operator
+                let space = match &operand.kind {
+                    ExprKind::UnaryOp { op: inner, .. } if op.starts_with(|c| unary_operator(inner).starts_with(c)) => " ",
+                    _ => "",
+                };
+                // `-x ** 2` already means `-(x ** 2)`
+                let min_precedence = binary_precedence(&BinaryOp::Power);
+                concat(vec![text(format!("{}{}", op, space)), self.operand(operand, min_precedence)])
+            }
+            ExprKind::Call { func, args } => concat(vec![self.operand(func, POSTFIX_PRECEDENCE), self.args(args)]),
+            ExprKind::MethodCall { object, method, args } => concat(vec![
+                self.member_object(object),
+                text(format!(".{}", method)),
+                self.args(args),
+            ]),
+            ExprKind::If { condition, then, else_ } => {
+                let mut docs = vec![text("if "), self.expr(condition), text(" { "), self.expr(then), text(" } else ")];
+                if matches!(else_.kind, ExprKind::If { .. }) {
+                    docs.push(self.expr(else_));
+                } else {
+                    docs.extend([text("{ "), self.expr(else_), text(" }")]);
+                }
+                concat(docs)
+            }
+            ExprKind::PropertyAccess { object, property } => {
+                concat(vec![self.member_object(object), text(format!(".{}", property))])
+            }
+            ExprKind::ArrayAccess { array, index } => {
+                concat(vec![self.operand(array, POSTFIX_PRECEDENCE), text("["), self.expr(index), text("]")])
+            }
+            ExprKind::TemplateLiteral { parts } => {
+                let mut docs = vec![text("\"")];
+                for part in parts {
+                    match part {
+                        TemplatePart::String(s) => docs.push(text(escape_string(s))),
+                        TemplatePart::Expression(e) => docs.extend([text("${"), self.expr(e), text("}")]),
+                    }
+                }
+                docs.push(text("\""));
+                concat(docs)
+            }
+            ExprKind::ArrowFunction { params, body } => {
+                let params = self.params(params);
+                // An expression body is parsed into a `return` with the expression's own span
+                let body = match body.as_slice() {
+                    [Stmt { kind: StmtKind::Return(Some(value)), span }] if *span == value.span => self.expr(value),
+                    _ => self.block(body, expr.span.end),
+                };
+                concat(vec![params, text(" => "), body])
+            }
+            ExprKind::New { class, args } => concat(vec![text("new "), self.operand(class, POSTFIX_PRECEDENCE), self.args(args)]),
+            ExprKind::TypeAssert { value, type_ } => {
+                concat(vec![self.operand(value, UNARY_PRECEDENCE), text(format!(" as {}", type_))])
+            }
+            ExprKind::Await(value) => concat(vec![text("await "), self.expr(value)]),
+            ExprKind::Try(value) => concat(vec![self.operand(value, POSTFIX_PRECEDENCE), text("?")]),
+            ExprKind::OptionalChain { object, property, args } => {
+                let mut docs = vec![self.member_object(object), text(format!("?.{}", property))];
+                docs.extend(args.as_deref().map(|args| self.args(args)));
+                concat(docs)
+            }
+            ExprKind::Coalesce { value, default } => concat(vec![
+                self.operand(value, COALESCE_PRECEDENCE),
+                text(" ?? "),
+                self.operand(default, COALESCE_PRECEDENCE + 1),
+            ]),
+            ExprKind::Comprehension { target, iter, filter, expr } => {
+                self.comprehension(*target, iter, filter.as_deref(), expr)
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                if arms.is_empty() {
+                    return concat(vec![text("match "), self.expr(scrutinee), text(" {}")]);
+                }
+                let mut docs = Vec::new();
+                for arm in arms {
+                    docs.push(Doc::HardLine);
+                    docs.push(text(arm.pattern.to_string()));
+                    if let Some(guard) = &arm.guard {
+                        docs.push(text(" if "));
+                        docs.push(self.expr(guard));
+                    }
+                    docs.push(text(" => "));
+                    docs.push(self.expr(&arm.body));
+                    docs.push(text(","));
+                }
+                concat(vec![text("match "), self.expr(scrutinee), text(" {"), indent(concat(docs)), Doc::HardLine, text("}")])
+            }
+        }
+    }
+
+    fn property(&mut self, property: &'a ObjectProperty) -> Doc {
+        let key = match &property.key {
+            PropertyKey::Name(name) if property.shorthand => return text(name.to_string()),
+            PropertyKey::Name(name) if is_identifier(name.as_str()) => text(name.to_string()),
+            PropertyKey::Name(name) => text(quote(name.as_str())),
+            PropertyKey::Computed(key) => concat(vec![text("["), self.expr(key), text("]")]),
+        };
+        concat(vec![key, text(": "), self.expr(&property.value)])
+    }
+
+    fn comprehension(&mut self, target: Symbol, iter: &'a Expr, filter: Option<&'a Expr>, expr: &'a Expr) -> Doc {
+        let mut docs = vec![text("["), self.expr(expr), text(format!(" for {} in ", target)), self.expr(iter)];
+        if let Some(filter) = filter {
+            docs.push(text(" if "));
+            docs.push(self.expr(filter));
+        }
+        docs.push(text("]"));
+        group(concat(docs))
+    }
+
+    /// A markup node at the top level of a component or of a block in it,
+    /// where text is written as string literals.
+    fn markup(&mut self, node: &'a MarkupNode) -> Doc {
+        match node {
+            MarkupNode::Element { .. } => self.element(node),
+            MarkupNode::Text(expr) => {
+                let parts = match &expr.kind {
+                    ExprKind::StringLiteral(s) => vec![text(quote(s))],
+                    ExprKind::TemplateLiteral { parts } => parts
+                        .iter()
+                        .map(|part| match part {
+                            TemplatePart::String(s) => text(quote(s)),
+                            TemplatePart::Expression(e) => concat(vec![text("{"), self.expr(e), text("}")]),
+                        })
+                        .collect(),
+                    _ => vec![concat(vec![text("{"), self.expr(expr), text("}")])],
+                };
+                let mut docs = Vec::new();
+                for (i, part) in parts.into_iter().enumerate() {
+                    if i > 0 {
+                        docs.push(text(" "));
+                    }
+                    docs.push(part);
+                }
+                concat(docs)
+            }
+            MarkupNode::IfBlock(block) => {
+                let then_end = block.else_branch.as_ref().and_then(|b| b.first()).map_or(block.span.end, |n| markup_span(n).start);
+                let mut docs = vec![text("{#if "), self.expr(&block.condition), text("}"), self.markup_block(&block.then_branch, then_end)];
+                if let Some(else_branch) = &block.else_branch {
+                    docs.push(Doc::HardLine);
+                    docs.push(text("{:else}"));
+                    docs.push(self.markup_block(else_branch, block.span.end));
+                }
+                docs.push(Doc::HardLine);
+                docs.push(text("{/if}"));
+                concat(docs)
+            }
+            MarkupNode::ForLoop(block) => concat(vec![
+                text(format!("{{#for {} in ", block.iterator)),
+                self.expr(&block.iterable),
+                text("}"),
+                self.markup_block(&block.body, block.span.end),
+                Doc::HardLine,
+                text("{/for}"),
+            ]),
+        }
+    }
+
+    fn markup_block(&mut self, nodes: &'a [MarkupNode], end: usize) -> Doc {
+        let body = self.members(nodes.iter().map(Member::Markup).collect(), end);
+        indent(concat(vec![Doc::HardLine, body]))
+    }
+
+    /// An element, always written in the `<tag ...>...</tag>` form.
+    fn element(&mut self, node: &'a MarkupNode) -> Doc {
+        self.element_piece(node).0
+    }
+
+    /// An element, and whether its content contains a block, which puts
+    /// each piece of the content on its own line.
+    fn element_piece(&mut self, node: &'a MarkupNode) -> (Doc, bool) {
+        let (tag, attributes, children) = match node {
+            MarkupNode::Element { tag, attributes, children, .. } => (*tag, attributes, children),
+            _ => return (self.markup(node), false),
+        };
+        // Attributes are kept in a map; the spans of their values give the written order
+        let mut attributes: Vec<(&Symbol, &'a Expr)> = attributes.iter().collect();
+        attributes.sort_by_key(|(_, value)| value.span.start);
+        let mut open = vec![text(format!("<{}", tag))];
+        let attrs_empty = attributes.is_empty();
+        let mut attrs = Vec::new();
+        for (name, value) in attributes {
+            attrs.push(Doc::Line);
+            attrs.push(self.attribute(*name, value));
+        }
+        open.push(indent(concat(attrs)));
+
+        let is_component = tag.as_str().starts_with(|c: char| c.is_uppercase());
+        if children.is_empty() && (is_component || VOID_ELEMENTS.contains(&tag.as_str())) {
+            open.push(Doc::Line);
+            open.push(text("/>"));
+            return (group(concat(open)), false);
+        }
+        // Without attributes, long content must not break the tag itself
+        let open = if attrs_empty {
+            text(format!("<{}>", tag))
+        } else {
+            open.push(Doc::SoftLine);
+            open.push(text(">"));
+            group(concat(open))
+        };
+        let (content, broken) = self.inline(children, false);
+        (group(concat(vec![open, content, text(format!("</{}>", tag))])), broken)
+    }
+
+    fn attribute(&mut self, name: Symbol, value: &'a Expr) -> Doc {
+        // A boolean attribute's value has the span of its name
+        if matches!(value.kind, ExprKind::BooleanLiteral(true)) && self.source.get(value.span.start..value.span.end) == Some(name.as_str()) {
+            return text(name.to_string());
+        }
+        match &value.kind {
+            ExprKind::StringLiteral(_) | ExprKind::TemplateLiteral { .. } => concat(vec![text(format!("{}=", name)), self.expr(value)]),
+            _ => concat(vec![text(format!("{}={{", name)), self.expr(value), text("}")]),
+        }
+    }
+
+    /// The content of an element, or of a block inside one, where text is
+    /// written bare. Lines only break where the text already has whitespace,
+    /// or between tags and blocks, so breaking does not change the text.
+    /// The content of a block, and content with a block in it, is always broken;
+    /// returns whether it is.
+    fn inline(&mut self, nodes: &'a [MarkupNode], force_broken: bool) -> (Doc, bool) {
+        let mut pieces = Vec::new();
+        for node in nodes {
+            let piece = match node {
+                MarkupNode::Text(expr) => {
+                    let (doc, leading, trailing) = self.inline_text(expr);
+                    Piece { doc, leading, trailing, is_text: true, broken: false }
+                }
+                MarkupNode::Element { .. } => {
+                    let (doc, broken) = self.element_piece(node);
+                    Piece { doc, leading: false, trailing: false, is_text: false, broken }
+                }
+                MarkupNode::IfBlock(block) => {
+                    let mut docs = vec![text("{#if "), self.expr(&block.condition), text("}"), self.inline(&block.then_branch, true).0];
+                    if let Some(else_branch) = &block.else_branch {
+                        docs.push(text("{:else}"));
+                        docs.push(self.inline(else_branch, true).0);
+                    }
+                    docs.push(text("{/if}"));
+                    Piece { doc: concat(docs), leading: false, trailing: false, is_text: false, broken: true }
+                }
+                MarkupNode::ForLoop(block) => {
+                    let doc = concat(vec![
+                        text(format!("{{#for {} in ", block.iterator)),
+                        self.expr(&block.iterable),
+                        text("}"),
+                        self.inline(&block.body, true).0,
+                        text("{/for}"),
+                    ]);
+                    Piece { doc, leading: false, trailing: false, is_text: false, broken: true }
+                }
+            };
+            pieces.push(piece);
+        }
+        if pieces.is_empty() {
+            return (text(""), false);
+        }
+        let broken = force_broken || pieces.iter().any(|piece| piece.broken);
+        let boundary = |before: Option<&Piece>, after: Option<&Piece>| {
+            let space = before.is_some_and(|p| p.trailing) || after.is_some_and(|p| p.leading);
+            let text_touches = before.is_some_and(|p| p.is_text) || after.is_some_and(|p| p.is_text);
+            if !space && text_touches {
+                text("")
+            } else if broken {
+                Doc::HardLine
+            } else if space {
+                Doc::Line
+            } else {
+                Doc::SoftLine
+            }
+        };
+        let mut inner = vec![boundary(None, pieces.first())];
+        for i in 0..pieces.len() {
+            if i > 0 {
+                inner.push(boundary(pieces.get(i - 1), pieces.get(i)));
+            }
+            inner.push(std::mem::replace(&mut pieces[i].doc, text("")));
+        }
+        let last = boundary(pieces.last(), None);
+        (concat(vec![indent(concat(inner)), last]), broken)
+    }
+
+    /// Bare markup text, without the whitespace around it, and whether there was any.
+    fn inline_text(&mut self, expr: &'a Expr) -> (Doc, bool, bool) {
+        let parts = match &expr.kind {
+            ExprKind::TemplateLiteral { parts } => parts.iter().map(Some).collect(),
+            ExprKind::StringLiteral(_) => vec![None],
+            _ => return (concat(vec![text("{"), self.expr(expr), text("}")]), false, false),
+        };
+        let mut docs = Vec::new();
+        let mut leading = false;
+        let mut trailing = false;
+        for (i, part) in parts.iter().enumerate() {
+            let string = match (part, &expr.kind) {
+                (Some(TemplatePart::String(s)), _) | (None, ExprKind::StringLiteral(s)) => Some(s.as_str()),
+                _ => None,
+            };
+            match (part, string) {
+                (_, Some(s)) => {
+                    let mut s = s;
+                    if i == 0 && s.starts_with(' ') {
+                        leading = true;
+                        s = s.trim_start();
+                    }
+                    if i == parts.len() - 1 && s.ends_with(' ') {
+                        trailing = true;
+                        s = s.trim_end();
+                    }
+                    docs.push(text(s));
+                }
+                (Some(TemplatePart::Expression(e)), None) => docs.extend([text("{"), self.expr(e), text("}")]),
+                _ => {}
+            }
+        }
+        (concat(docs), leading, trailing)
+    }
+}
+
+/// A piece of element content.
+struct Piece {
+    doc: Doc,
+    /// Whether whitespace separates it from the previous and next pieces.
+    leading: bool,
+    trailing: bool,
+    is_text: bool,
+    /// Whether it is laid out over several lines regardless of width.
+    broken: bool,
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c == '_' || unicode_ident::is_xid_start(c))
+        && chars.all(unicode_ident::is_xid_continue)
+        && name != "true"
+        && name != "false"
+}
@@ -0,0 +1,266 @@
+//! Opt-in self-profiling for the compiler
+//!
+//! Passes wrap their work in `scope("name")`. While profiling is disabled a
+//! scope costs one atomic load; once `enable` is called every scope records its
+//! duration and, if the binary installs `CountingAllocator`, the allocations
+//! made inside it. Recorded events can be summarized with `timings_report` or
+//! exported with `chrome_trace` for chrome://tracing and Perfetto.
+
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// A completed scope.
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub name: &'static str,
+    /// Free-form detail, e.g. the file being compiled.
+    pub detail: Option<String>,
+    /// Offset from when profiling was enabled.
+    pub start: Duration,
+    pub duration: Duration,
+    pub thread: u64,
+    /// Nesting depth on its thread; top-level scopes are 0.
+    pub depth: usize,
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+}
+
+struct State {
+    epoch: Instant,
+    events: Vec<Event>,
+}
+
+fn state() -> &'static Mutex<State> {
+    static STATE: OnceLock<Mutex<State>> = OnceLock::new();
+    STATE.get_or_init(|| {
+        Mutex::new(State {
+            epoch: Instant::now(),
+            events: Vec::new(),
+        })
+    })
+}
+
+thread_local! {
+    static DEPTH: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    static THREAD_ID: u64 = {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    };
+}
+
+/// Starts recording scopes. Events recorded before a later `reset` are kept.
+pub fn enable() {
+    state().lock().unwrap().epoch = Instant::now();
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Discards every recorded event.
+pub fn reset() {
+    state().lock().unwrap().events.clear();
+}
+
+/// Returns a copy of every recorded event, in the order scopes finished.
+pub fn events() -> Vec<Event> {
+    state().lock().unwrap().events.clone()
+}
+
+/// Times the enclosing block until the returned guard is dropped:
+///
+/// ```ignore
+/// let _timer = profiling::scope("parse");
+/// ```
+pub fn scope(name: &'static str) -> Scope {
+    Scope::new(name, None)
+}
+
+/// Like `scope`, with a detail string shown in traces.
+pub fn scope_with(name: &'static str, detail: impl Into<String>) -> Scope {
+    Scope::new(name, if is_enabled() { Some(detail.into()) } else { None })
+}
+
+/// Guard returned by `scope`; records an event when dropped.
+pub struct Scope {
+    active: Option<ActiveScope>,
+}
+
+struct ActiveScope {
+    name: &'static str,
+    detail: Option<String>,
+    started: Instant,
+    depth: usize,
+    allocations: u64,
+    allocated_bytes: u64,
+}
+
+impl Scope {
+    fn new(name: &'static str, detail: Option<String>) -> Self {
+        if !is_enabled() {
+            return Scope { active: None };
+        }
+        let depth = DEPTH.with(|d| d.replace(d.get() + 1));
+        Scope {
+            active: Some(ActiveScope {
+                name,
+                detail,
+                started: Instant::now(),
+                depth,
+                allocations: ALLOCATIONS.load(Ordering::Relaxed),
+                allocated_bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+            }),
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let active = match self.active.take() {
+            Some(active) => active,
+            None => return,
+        };
+        let duration = active.started.elapsed();
+        DEPTH.with(|d| d.set(active.depth));
+        // Read the counters before taking the lock, whose bookkeeping allocates.
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - active.allocations;
+        let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - active.allocated_bytes;
+        let thread = THREAD_ID.with(|id| *id);
+        let mut state = state().lock().unwrap();
+        let start = active.started.saturating_duration_since(state.epoch);
+        state.events.push(Event {
+            name: active.name,
+            detail: active.detail,
+            start,
+            duration,
+            thread,
+            depth: active.depth,
+            allocations,
+            allocated_bytes,
+        });
+    }
+}
+
+/// A global allocator that counts allocations for profiling scopes. Install it
+/// in a binary with `#[global_allocator]`; without it allocation counts stay 0.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Summarizes recorded events per scope name, slowest first.
+pub fn timings_report() -> String {
+    struct Total {
+        count: usize,
+        time: Duration,
+        allocations: u64,
+        bytes: u64,
+        depth: usize,
+    }
+    let events = events();
+    let mut order = Vec::new();
+    let mut totals: HashMap<&str, Total> = HashMap::new();
+    for event in &events {
+        let total = totals.entry(event.name).or_insert_with(|| {
+            order.push(event.name);
+            Total { count: 0, time: Duration::ZERO, allocations: 0, bytes: 0, depth: event.depth }
+        });
+        total.count += 1;
+        total.time += event.duration;
+        total.allocations += event.allocations;
+        total.bytes += event.allocated_bytes;
+        total.depth = total.depth.min(event.depth);
+    }
+    order.sort_by(|a, b| totals[b].time.cmp(&totals[a].time));
+
+    let mut out = format!("{:<28} {:>6} {:>12} {:>10} {:>12}\n", "pass", "count", "time", "allocs", "bytes");
+    for name in order {
+        let total = &totals[name];
+        let label = format!("{}{}", "  ".repeat(total.depth), name);
+        out.push_str(&format!(
+            "{:<28} {:>6} {:>9.3} ms {:>10} {:>12}\n",
+            label,
+            total.count,
+            total.time.as_secs_f64() * 1000.0,
+            total.allocations,
+            total.bytes
+        ));
+    }
+    out
+}
+
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    cat: &'static str,
+    ph: &'static str,
+    ts: u64,
+    dur: u64,
+    pid: u32,
+    tid: u64,
+    args: TraceArgs,
+}
+
+#[derive(Serialize)]
+struct TraceArgs {
+    allocations: u64,
+    allocated_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+/// Exports recorded events in the Chrome trace event format, as complete ("X") events.
+pub fn chrome_trace() -> String {
+    let pid = std::process::id();
+    let events: Vec<TraceEvent> = events()
+        .into_iter()
+        .map(|event| TraceEvent {
+            name: event.name,
+            cat: "gigli",
+            ph: "X",
+            ts: event.start.as_micros() as u64,
+            dur: event.duration.as_micros() as u64,
+            pid,
+            tid: event.thread,
+            args: TraceArgs {
+                allocations: event.allocations,
+                allocated_bytes: event.allocated_bytes,
+                detail: event.detail,
+            },
+        })
+        .collect();
+    serde_json::to_string(&events).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Writes `chrome_trace` to a file.
+pub fn write_chrome_trace(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    std::fs::write(path, chrome_trace())
+}
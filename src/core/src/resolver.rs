@@ -0,0 +1,490 @@
+//! Module resolution for Gigli imports
+//!
+//! `import { X } from module;` names the file `module.gx`, next to the importing
+//! file or in the `src` directory. `ModuleResolver::load` parses an entry file
+//! and the modules it imports, recursively, and rejects import cycles.
+//! `SymbolIndex` records where every top-level item of a set of files is
+//! declared, for lookups across modules such as go-to-definition.
+//!
+//! The files of a program are laid out one after another, each starting past
+//! the end of those read before it, and spans in their ASTs are offsets into
+//! that layout, so the file an item came from is known from its span once the
+//! files are combined. `ProgramSources` renders diagnostics against the right file.
+
+use crate::ast::*;
+use crate::diagnostics::{codes, Diagnostic, LineIndex, Span};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::profiling;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Source file extension for Gigli modules.
+pub const SOURCE_EXTENSION: &str = "gx";
+
+/// Maps `import { .. } from module;` paths to `.gx` files on disk.
+pub struct ModuleResolver {
+    src_root: PathBuf,
+}
+
+impl ModuleResolver {
+    pub fn new(src_root: impl Into<PathBuf>) -> Self {
+        ModuleResolver { src_root: src_root.into() }
+    }
+
+    /// A resolver for a program whose entry file is `entry`, rooted at the
+    /// nearest enclosing `src` directory, or else at the entry's directory.
+    pub fn for_entry(entry: &Path) -> Self {
+        let dir = entry.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let src_root = dir.ancestors().find(|ancestor| ancestor.file_name().is_some_and(|name| name == "src")).unwrap_or(dir);
+        ModuleResolver::new(src_root)
+    }
+
+    pub fn src_root(&self) -> &Path {
+        &self.src_root
+    }
+
+    /// Resolves a module name relative to the importing file first, then to the source root.
+    pub fn resolve(&self, from: &Path, module: &str) -> Option<PathBuf> {
+        let file_name = format!("{}.{}", module, SOURCE_EXTENSION);
+        let candidates = from
+            .parent()
+            .map(|dir| dir.join(&file_name))
+            .into_iter()
+            .chain(std::iter::once(self.src_root.join(&file_name)));
+        for candidate in candidates {
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+
+    /// Loads `entry` and every module it imports, directly or not, from disk.
+    pub fn load(&self, entry: &Path) -> Result<Program, LoadError> {
+        self.load_with(entry, &mut |path| {
+            std::fs::read_to_string(path)
+                .map_err(|e| Diagnostic::error(format!("Failed to read '{}'", path.display())).with_note(e.to_string()))
+        })
+    }
+
+    /// Like `load`, reading each file's source with `read`, which may also
+    /// transform it, as the `pre-parse` plugin hook does.
+    pub fn load_with(&self, entry: &Path, read: &mut dyn FnMut(&Path) -> Result<String, Diagnostic>) -> Result<Program, LoadError> {
+        let _timer = profiling::scope("load modules");
+        let mut loader = Loader { resolver: self, read, files: Vec::new(), loaded: HashMap::new(), in_progress: Vec::new(), next_base: 0 };
+        loader.load(entry.to_path_buf())?;
+        Ok(Program { files: loader.files })
+    }
+}
+
+/// A source file of a program.
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub source: String,
+    /// Where the file starts in the program's layout: spans in `ast` are
+    /// offsets into `source` plus `base`.
+    pub base: usize,
+    pub ast: AST,
+}
+
+/// An entry file and the modules it imports.
+pub struct Program {
+    /// Every file after the modules it imports, so the entry file is last.
+    pub files: Vec<SourceFile>,
+}
+
+impl Program {
+    pub fn entry(&self) -> &SourceFile {
+        self.files.last().expect("a program has an entry file")
+    }
+
+    /// Merges the items of every file into one AST for semantic analysis and
+    /// code generation, and returns the sources to render its diagnostics
+    /// with. Imports have been resolved, so it has none.
+    ///
+    /// Private functions are only visible in their own module, so one named
+    /// like an item of another file is renamed after its module, e.g.
+    /// `helper` in `utils.gx` becomes `helper$utils`, and the two do not clash.
+    pub fn combine(self) -> (AST, ProgramSources) {
+        let renames = self.private_renames();
+        let mut combined = AST {
+            modules: Vec::new(),
+            functions: Vec::new(),
+            classes: Vec::new(),
+            components: Vec::new(),
+            imports: Vec::new(),
+            externs: Vec::new(),
+        };
+        let mut sources = ProgramSources { files: Vec::new() };
+        for (file, renames) in self.files.into_iter().zip(renames) {
+            let ast = if renames.is_empty() {
+                file.ast
+            } else {
+                parse_at(&file.source, file.base, &renames).expect("a file that parsed still parses with functions renamed")
+            };
+            combined.modules.extend(ast.modules);
+            combined.functions.extend(ast.functions);
+            combined.classes.extend(ast.classes);
+            combined.components.extend(ast.components);
+            combined.externs.extend(ast.externs);
+            sources.files.push(ProgramFile { path: file.path, source: file.source, base: file.base });
+        }
+        (combined, sources)
+    }
+
+    /// New names for the private functions of each module, other than the
+    /// entry file, that another file defines an item named like.
+    fn private_renames(&self) -> Vec<HashMap<Symbol, Symbol>> {
+        let entry = self.files.len() - 1;
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                if i == entry {
+                    return HashMap::new();
+                }
+                let module = file.path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+                file.ast
+                    .functions
+                    .iter()
+                    .filter(|f| !f.is_public)
+                    .filter(|f| self.files.iter().enumerate().any(|(j, other)| j != i && defines(&other.ast, f.name)))
+                    .map(|f| (f.name, Symbol::intern(&format!("{}${}", f.name, module))))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Indexes the top-level items of every file.
+    pub fn symbol_index(&self) -> SymbolIndex {
+        let mut index = SymbolIndex::default();
+        for file in &self.files {
+            index.add_items(&file.path, &file.ast, file.base);
+        }
+        index
+    }
+}
+
+/// The sources of a combined program, to render diagnostics about its AST with.
+pub struct ProgramSources {
+    /// In the order of `Program::files`, so the entry file is last.
+    files: Vec<ProgramFile>,
+}
+
+struct ProgramFile {
+    path: PathBuf,
+    source: String,
+    base: usize,
+}
+
+impl ProgramSources {
+    /// The file the span is in, or the entry file for diagnostics without one.
+    fn file_of(&self, span: Option<Span>) -> &ProgramFile {
+        span.and_then(|span| self.files.iter().find(|file| (file.base..=file.base + file.source.len()).contains(&span.start)))
+            .or(self.files.last())
+            .expect("a program has an entry file")
+    }
+
+    /// Renders `diagnostic` against the file its primary span is in. Labels in
+    /// other files, such as an earlier definition, become notes giving their location.
+    pub fn render(&self, diagnostic: &Diagnostic, color: bool) -> String {
+        let file = self.file_of(diagnostic.primary_span());
+        let mut local = diagnostic.clone();
+        local.labels.clear();
+        for label in &diagnostic.labels {
+            let other = self.file_of(Some(label.span));
+            if std::ptr::eq(other, file) {
+                local.labels.push(label.clone());
+            } else {
+                let (line, col) = LineIndex::new(&other.source).line_col(label.span.relative_to(other.base).start);
+                let message = if label.message.is_empty() { "see" } else { &label.message };
+                local.notes.push(format!("{} ({}:{}:{})", message, other.path.display(), line + 1, col + 1));
+            }
+        }
+        local.suggestions.retain(|suggestion| std::ptr::eq(self.file_of(Some(suggestion.span)), file));
+        local.relative_to(file.base).render(&file.path.display().to_string(), &file.source, color)
+    }
+}
+
+/// The kinds of top-level item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Function,
+    Component,
+    Class,
+    Extern,
+}
+
+/// A top-level item and the file declaring it.
+#[derive(Debug, Clone)]
+pub struct IndexedItem {
+    pub name: Symbol,
+    pub kind: ItemKind,
+    pub path: PathBuf,
+    pub span: Span,
+    /// Whether other modules can import it.
+    pub exported: bool,
+}
+
+/// The top-level items of a set of files, by name.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    items: HashMap<Symbol, Vec<IndexedItem>>,
+}
+
+impl SymbolIndex {
+    /// Adds the items of the file at `path`, replacing any indexed before for it.
+    pub fn add_file(&mut self, path: &Path, ast: &AST) {
+        self.add_items(path, ast, 0);
+    }
+
+    /// Like `add_file`, for a file whose spans start `base` bytes in.
+    fn add_items(&mut self, path: &Path, ast: &AST, base: usize) {
+        self.remove_file(path);
+        let mut add = |name: Symbol, kind, span: Span, exported| {
+            let item = IndexedItem { name, kind, path: path.to_path_buf(), span: span.relative_to(base), exported };
+            self.items.entry(name).or_default().push(item);
+        };
+        for function in &ast.functions {
+            add(function.name, ItemKind::Function, function.span, function.is_public);
+        }
+        for component in &ast.components {
+            add(component.name, ItemKind::Component, component.span, true);
+        }
+        for class in &ast.classes {
+            add(class.name, ItemKind::Class, class.span, true);
+        }
+        for function in &ast.externs {
+            add(function.name, ItemKind::Extern, function.span, false);
+        }
+    }
+
+    pub fn remove_file(&mut self, path: &Path) {
+        for items in self.items.values_mut() {
+            items.retain(|item| item.path != path);
+        }
+        self.items.retain(|_, items| !items.is_empty());
+    }
+
+    /// Every item named `name`, in any file.
+    pub fn lookup(&self, name: &str) -> &[IndexedItem] {
+        self.items.get(&Symbol::intern(name)).map_or(&[], Vec::as_slice)
+    }
+
+    /// The item named `name` declared in the file at `path`.
+    pub fn find(&self, path: &Path, name: &str) -> Option<&IndexedItem> {
+        self.lookup(name).iter().find(|item| item.path == path)
+    }
+
+    /// The items declared in the file at `path`, in no particular order.
+    pub fn items_in<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a IndexedItem> + 'a {
+        self.items.values().flatten().filter(move |item| item.path == path)
+    }
+}
+
+/// Errors loading a program, all in one file.
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: PathBuf,
+    /// The file's source, to render the diagnostics with; empty if it could not be read.
+    pub source: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+struct Loader<'a> {
+    resolver: &'a ModuleResolver,
+    read: &'a mut dyn FnMut(&Path) -> Result<String, Diagnostic>,
+    files: Vec<SourceFile>,
+    /// Index in `files` of each file loaded so far, by canonical path.
+    loaded: HashMap<PathBuf, usize>,
+    /// The chain of imports being loaded, to detect cycles.
+    in_progress: Vec<PathBuf>,
+    /// Where the next file read starts in the program's layout.
+    next_base: usize,
+}
+
+impl Loader<'_> {
+    /// Loads `path` after the modules it imports and returns its index in `files`.
+    fn load(&mut self, path: PathBuf) -> Result<usize, LoadError> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let fail = |source: &str, diagnostics: Vec<Diagnostic>| LoadError { path: path.clone(), source: source.to_string(), diagnostics };
+
+        let source = (self.read)(&path).map_err(|diagnostic| fail("", vec![diagnostic]))?;
+        let base = self.next_base;
+        self.next_base += source.len() + 1;
+        let ast = parse_at(&source, base, &HashMap::new()).map_err(|diagnostic| fail(&source, vec![diagnostic]))?;
+
+        self.in_progress.push(key.clone());
+        let mut modules = HashMap::new();
+        for import in &ast.imports {
+            let target = match self.resolver.resolve(&path, &import.module) {
+                Some(target) => target,
+                // Reported by `check_imports` below
+                None => continue,
+            };
+            let target_key = target.canonicalize().unwrap_or_else(|_| target.clone());
+            if let Some(position) = self.in_progress.iter().position(|p| *p == target_key) {
+                let chain = self.in_progress[position..]
+                    .iter()
+                    .chain(std::iter::once(&target_key))
+                    .map(|p| p.file_name().map_or_else(|| p.display().to_string(), |name| name.to_string_lossy().into_owned()))
+                    .collect::<Vec<_>>();
+                let diagnostic = Diagnostic::error(format!("Import cycle: {}", chain.join(" -> ")))
+                    .with_code(codes::IMPORT_CYCLE)
+                    .with_primary(import.span, "")
+                    .with_note("modules cannot import each other, directly or through other modules");
+                return Err(fail(&source, vec![diagnostic.relative_to(base)]));
+            }
+            let index = match self.loaded.get(&target_key) {
+                Some(&index) => index,
+                None => self.load(target)?,
+            };
+            modules.insert(import.module.clone(), index);
+        }
+        self.in_progress.pop();
+
+        let errors = check_imports(&ast, &|module| modules.get(module).map(|&index| &self.files[index].ast));
+        if !errors.is_empty() {
+            return Err(fail(&source, errors.into_iter().map(|diagnostic| diagnostic.relative_to(base)).collect()));
+        }
+
+        self.files.push(SourceFile { path, source, base, ast });
+        self.loaded.insert(key, self.files.len() - 1);
+        Ok(self.files.len() - 1)
+    }
+}
+
+/// Parses a file whose spans start `base` bytes into the program's layout,
+/// renaming identifiers as `renames` says, except after `.`. Diagnostics are
+/// relative to the file.
+fn parse_at(source: &str, base: usize, renames: &HashMap<Symbol, Symbol>) -> Result<AST, Diagnostic> {
+    let mut tokens = Lexer::new(source).tokenize()?;
+    let mut after_dot = false;
+    for token in &mut tokens {
+        token.span = token.span.offset(base);
+        if let Token::Identifier(name) = &mut token.token {
+            if !after_dot {
+                *name = renames.get(name).copied().unwrap_or(*name);
+            }
+        }
+        after_dot = matches!(token.token, Token::Dot | Token::QuestionDot);
+    }
+    Parser::new(tokens).parse().map_err(|diagnostic| diagnostic.relative_to(base))
+}
+
+/// Returns the names a module makes available to importers: its components,
+/// classes and functions that are not `private`.
+pub fn exported_names(ast: &AST) -> Vec<&str> {
+    let mut names = Vec::new();
+    names.extend(ast.functions.iter().filter(|f| f.is_public).map(|f| f.name.as_str()));
+    names.extend(ast.components.iter().map(|c| c.name.as_str()));
+    names.extend(ast.classes.iter().map(|c| c.name.as_str()));
+    names
+}
+
+/// Checks a module's imports against the modules they resolve to.
+///
+/// `lookup` returns the parsed AST for an imported module name, or `None` if it cannot be found.
+pub fn check_imports<'a>(ast: &AST, lookup: &dyn Fn(&str) -> Option<&'a AST>) -> Vec<Diagnostic> {
+    let _timer = profiling::scope("resolve imports");
+    let mut errors = Vec::new();
+    let mut imported_components = Vec::new();
+    let mut imported: HashMap<Symbol, &str> = HashMap::new();
+
+    for import in &ast.imports {
+        for item in &import.items {
+            if let Some(first) = imported.insert(*item, &import.module) {
+                let note = if first == import.module { format!("it is already imported from '{}'", first) } else { format!("it is also imported from '{}'", first) };
+                errors.push(
+                    Diagnostic::error(format!("'{}' is imported more than once", item))
+                        .with_code(codes::DUPLICATE_IMPORT)
+                        .with_primary(import.span, "")
+                        .with_note(note),
+                );
+            } else if defines(ast, *item) {
+                errors.push(
+                    Diagnostic::error(format!("'{}' is imported from '{}', but this file also defines it", item, import.module))
+                        .with_code(codes::DUPLICATE_IMPORT)
+                        .with_primary(import.span, ""),
+                );
+            }
+        }
+        let target = match lookup(&import.module) {
+            Some(target) => target,
+            None => {
+                errors.push(Diagnostic::error(format!("Cannot find module '{}'", import.module)).with_code(codes::UNRESOLVED_IMPORT).with_primary(import.span, ""));
+                continue;
+            }
+        };
+        let exports = exported_names(target);
+        for item in &import.items {
+            if target.functions.iter().any(|f| f.name == *item && !f.is_public) {
+                errors.push(
+                    Diagnostic::error(format!("'{}' is private to module '{}'", item, import.module))
+                        .with_code(codes::PRIVATE_IMPORT)
+                        .with_primary(import.span, "")
+                        .with_note(format!("remove `private` from '{}' to export it", item)),
+                );
+            } else if !exports.contains(&item.as_str()) {
+                errors.push(
+                    Diagnostic::error(format!("Module '{}' has no export named '{}'", import.module, item))
+                        .with_code(codes::UNRESOLVED_IMPORT)
+                        .with_primary(import.span, ""),
+                );
+            } else if let Some(component) = target.components.iter().find(|c| &c.name == item) {
+                imported_components.push(component);
+            }
+        }
+    }
+
+    for component in &ast.components {
+        for node in &component.markup {
+            check_component_props(node, &imported_components, &mut errors);
+        }
+    }
+    errors
+}
+
+/// Whether `ast` declares a top-level item named `name`.
+fn defines(ast: &AST, name: Symbol) -> bool {
+    ast.functions.iter().any(|f| f.name == name)
+        || ast.components.iter().any(|c| c.name == name)
+        || ast.classes.iter().any(|c| c.name == name)
+        || ast.externs.iter().any(|f| f.name == name)
+}
+
+/// Checks that attributes passed to `components` name one of their state variables.
+pub(crate) fn check_component_props(node: &MarkupNode, components: &[&ComponentNode], errors: &mut Vec<Diagnostic>) {
+    match node {
+        MarkupNode::Element { tag, attributes, children, span } => {
+            if let Some(component) = components.iter().find(|c| &c.name == tag) {
+                let mut names: Vec<&Symbol> = attributes.keys().collect();
+                names.sort();
+                for name in names {
+                    if !component.state_vars.iter().any(|s| &s.name == name) {
+                        errors.push(
+                            Diagnostic::error(format!("Component '{}' has no prop named '{}'", tag, name))
+                                .with_code(codes::UNKNOWN_PROP)
+                                .with_primary(*span, ""),
+                        );
+                    }
+                }
+            }
+            for child in children {
+                check_component_props(child, components, errors);
+            }
+        }
+        MarkupNode::Text(_) => {}
+        MarkupNode::IfBlock(block) => {
+            for child in block.then_branch.iter().chain(block.else_branch.iter().flatten()) {
+                check_component_props(child, components, errors);
+            }
+        }
+        MarkupNode::ForLoop(block) => {
+            for child in &block.body {
+                check_component_props(child, components, errors);
+            }
+        }
+    }
+}
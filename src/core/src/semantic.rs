@@ -1,200 +1,1645 @@
 //! Semantic analysis for Gigli
 
 use crate::ast::*;
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::hir::{self, Hir};
+use crate::profiling;
+use crate::resolver::check_component_props;
 use std::collections::HashMap;
 
 pub struct SemanticAnalyzer {
-    pub errors: Vec<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// `extern "js"` declarations, by name, for checking calls against their signatures.
+    externs: HashMap<Symbol, ExternFunction>,
+    /// `@deprecated` functions and components, with their messages, for warning at each use.
+    deprecated: HashMap<Symbol, Option<String>>,
+    /// Declared return types of top-level functions, for checking `?` on their results.
+    return_types: HashMap<Symbol, Type>,
+    /// `async` functions, with where each first awaits, for checking calls and schedulers.
+    async_functions: HashMap<Symbol, Option<Span>>,
+    /// Whether an `on:event` handler is being checked; the runtime starts handlers without awaiting them.
+    in_handler: bool,
+    /// The declared types of the identifiers that were read, by span, from the
+    /// declarations the scope stack resolved them to.
+    identifier_types: HashMap<Span, Type>,
 }
 
 impl SemanticAnalyzer {
     pub fn new() -> Self {
-        Self { errors: Vec::new() }
+        Self {
+            diagnostics: Vec::new(),
+            externs: HashMap::new(),
+            deprecated: HashMap::new(),
+            return_types: HashMap::new(),
+            async_functions: HashMap::new(),
+            in_handler: false,
+            identifier_types: HashMap::new(),
+        }
+    }
+
+    /// True if any error (not just warnings) was reported.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
     }
 
-    pub fn analyze(&mut self, ast: &AST) {
-        let mut global_vars = HashMap::new();
+    fn error(&mut self, code: &str, span: Span, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic::error(message).with_code(code).with_primary(span, ""));
+    }
+
+    /// Checks `ast`, collecting diagnostics, and lowers it to HIR for code
+    /// generation. The HIR is only meaningful if no errors were reported.
+    pub fn analyze(&mut self, ast: &AST) -> Hir {
+        let _timer = profiling::scope("semantic");
+        self.check_duplicate_items(ast);
+        let mut scopes = ScopeStack::new();
+        for name in GLOBALS.iter().chain(STD_MODULES) {
+            scopes.declare(Symbol::intern(name), None, BindingKind::Global, Span::default());
+        }
+        for ext in &ast.externs {
+            self.check_extern(ext);
+            scopes.declare(ext.name, Some(function_type(&ext.params, ext.return_type.as_ref())), BindingKind::Function, ext.span);
+            scopes.mark_used(ext.name);
+        }
+        for import in &ast.imports {
+            for item in &import.items {
+                scopes.declare(*item, None, BindingKind::Global, import.span);
+            }
+        }
         for func in &ast.functions {
-            self.check_function(func);
+            self.check_attributes(&func.attributes, true);
+            if let Some(test) = Attribute::find(&func.attributes, "test") {
+                if !func.params.is_empty() {
+                    self.error(codes::INVALID_ATTRIBUTE, test.span, format!("Test function '{}' must not take parameters", func.name));
+                }
+            }
+            self.note_deprecated(func.name, &func.attributes);
+            if let Some(return_type) = &func.return_type {
+                self.return_types.insert(func.name, return_type.clone());
+            }
+            self.note_async(func);
+            scopes.declare(func.name, Some(function_type(&func.params, func.return_type.as_ref())), BindingKind::Function, func.span);
+            // Public, exported and test functions are used from outside the
+            // module, and `main` by the runtime, as the entry point
+            let entry_point = func.name.as_str() == "main";
+            if entry_point || func.is_public || Attribute::find(&func.attributes, "export").is_some() || Attribute::find(&func.attributes, "test").is_some() {
+                scopes.mark_used(func.name);
+            }
+        }
+        for component in &ast.components {
+            self.check_attributes(&component.attributes, false);
+            self.note_deprecated(component.name, &component.attributes);
+            scopes.declare(component.name, None, BindingKind::Global, component.span);
+        }
+        for class in &ast.classes {
+            self.check_attributes(&class.attributes, false);
         }
+        for func in &ast.functions {
+            self.check_function(func, &mut scopes);
+        }
+        let components: Vec<&ComponentNode> = ast.components.iter().collect();
         for component in &ast.components {
-            self.check_component(component, &mut global_vars);
+            self.check_component(component, &mut scopes);
+            for node in &component.markup {
+                check_component_props(node, &components, &mut self.diagnostics);
+            }
         }
+        self.report_unused(scopes);
         // TODO: Add checks for classes, modules, etc.
+        hir::lower(ast)
+    }
+
+    /// Reports top-level items that share a name, since a name can only refer to
+    /// one of them, and class members that do.
+    fn check_duplicate_items(&mut self, ast: &AST) {
+        let mut items: Vec<(Symbol, Span, &str)> = Vec::new();
+        items.extend(ast.externs.iter().map(|ext| (ext.name, ext.span, "an extern function")));
+        items.extend(ast.functions.iter().map(|f| (f.name, f.span, "a function")));
+        items.extend(ast.components.iter().map(|c| (c.name, c.span, "a component")));
+        items.extend(ast.classes.iter().map(|class| (class.name, class.span, "a class")));
+        self.report_duplicates(items);
+        for class in &ast.classes {
+            let mut members: Vec<(Symbol, Span, &str)> = Vec::new();
+            members.extend(class.fields.iter().map(|field| (field.name, field.span, "a field")));
+            members.extend(class.methods.iter().map(|method| (method.name, method.span, "a method")));
+            self.report_duplicates(members);
+        }
+    }
+
+    /// Reports each of `definitions` whose name an earlier one already has, with both locations.
+    fn report_duplicates(&mut self, mut definitions: Vec<(Symbol, Span, &str)>) {
+        definitions.sort_by_key(|(_, span, _)| span.start);
+        let mut first: HashMap<Symbol, (Span, &str)> = HashMap::new();
+        for (name, span, what) in definitions {
+            match first.get(&name) {
+                Some(&(previous, previous_what)) => self.diagnostics.push(
+                    Diagnostic::error(format!("'{}' is defined more than once", name))
+                        .with_code(codes::DUPLICATE_DEFINITION)
+                        .with_primary(span, format!("defined again here as {}", what))
+                        .with_label(previous, format!("first defined here as {}", previous_what)),
+                ),
+                None => {
+                    first.insert(name, (span, what));
+                }
+            }
+        }
+    }
+
+    /// Checks that each attribute is known, appears once, applies to this kind
+    /// of item, and has at most one string literal argument.
+    fn check_attributes(&mut self, attributes: &[Attribute], on_function: bool) {
+        let mut seen = Vec::new();
+        for attribute in attributes {
+            if seen.contains(&attribute.name) {
+                self.error(codes::INVALID_ATTRIBUTE, attribute.span, format!("Duplicate attribute '@{}'", attribute.name));
+                continue;
+            }
+            seen.push(attribute.name);
+            let (function_only, max_args) = match attribute.name.as_str() {
+                // `@export("name")` exports the function under another name
+                "export" => (true, 1),
+                "test" => (true, 0),
+                "deprecated" => (false, 1),
+                _ => {
+                    self.error(codes::INVALID_ATTRIBUTE, attribute.span, format!("Unknown attribute '@{}'; expected @export, @test or @deprecated", attribute.name));
+                    continue;
+                }
+            };
+            if function_only && !on_function {
+                self.error(codes::INVALID_ATTRIBUTE, attribute.span, format!("'@{}' can only be used on functions", attribute.name));
+            }
+            if attribute.args.len() > max_args {
+                self.error(codes::INVALID_ATTRIBUTE, attribute.span, format!("'@{}' takes {} argument(s) but {} were given", attribute.name, max_args, attribute.args.len()));
+            } else if attribute.args.iter().any(|arg| !matches!(arg.kind, ExprKind::StringLiteral(_))) {
+                self.error(codes::INVALID_ATTRIBUTE, attribute.span, format!("The argument of '@{}' must be a string literal", attribute.name));
+            }
+        }
+    }
+
+    fn note_async(&mut self, func: &Function) {
+        if func.is_async {
+            self.async_functions.insert(func.name, first_await(&func.body));
+        }
+    }
+
+    /// Whether `name` refers to an `async` function, rather than to something shadowing one.
+    /// Gives where the function first awaits, if it does.
+    fn async_function(&self, name: Symbol, scopes: &ScopeStack) -> Option<Option<Span>> {
+        match scopes.lookup(name) {
+            Some(Binding { kind: BindingKind::Function, .. }) => self.async_functions.get(&name).copied(),
+            _ => None,
+        }
+    }
+
+    fn note_deprecated(&mut self, name: Symbol, attributes: &[Attribute]) {
+        if let Some(deprecated) = Attribute::find(attributes, "deprecated") {
+            self.deprecated.insert(name, deprecated.string_arg().map(str::to_string));
+        }
+    }
+
+    /// Warns about a use of a `@deprecated` function or component.
+    fn check_deprecated(&mut self, name: Symbol, span: Span) {
+        if let Some(message) = self.deprecated.get(&name) {
+            let mut warning = Diagnostic::warning(format!("'{}' is deprecated", name)).with_code(codes::DEPRECATED).with_primary(span, "");
+            if let Some(message) = message {
+                warning = warning.with_note(message.clone());
+            }
+            self.diagnostics.push(warning);
+        }
     }
 
-    fn check_component(&mut self, component: &ComponentNode, global_vars: &mut HashMap<String, Option<Type>>) {
-        let mut local_vars = global_vars.clone();
+    fn check_component(&mut self, component: &ComponentNode, scopes: &mut ScopeStack) {
+        scopes.push();
+        // Functions are declared first, since state, derived values and markup can all call them
+        for func in &component.functions {
+            self.declare(scopes, func.name, Some(function_type(&func.params, func.return_type.as_ref())), BindingKind::Function, func.span);
+            self.note_async(func);
+        }
         // Register state vars (reactive)
         for state in &component.state_vars {
-            local_vars.insert(state.name.clone(), state.type_annotation.clone());
+            self.check_expr(&state.initial_value, scopes, false);
+            self.declare(scopes, state.name, state.type_annotation.clone(), BindingKind::State, state.span);
         }
-        // Register let vars (derived)
+        // Derived values are recomputed when what they read changes, so their
+        // order does not matter: all are declared before any is checked
         for letv in &component.let_vars {
-            // Check if let depends on any state var (reactivity)
-            let mut depends_on_state = false;
-            self.check_expr_reactivity(&letv.value, &local_vars, &component.state_vars, &mut depends_on_state);
-            if depends_on_state {
-                // Mark as derived reactive (could store this info in a real implementation)
+            self.declare(scopes, letv.name, letv.type_annotation.clone(), BindingKind::Let, letv.span);
+        }
+        for stmt in &component.reactive {
+            if let StmtKind::Reactive { name, .. } = &stmt.kind {
+                // `$: name = ...` declares `name` unless it is a state variable or derived value
+                if !scopes.contains(*name) {
+                    scopes.declare(*name, None, BindingKind::Let, stmt.span);
+                }
             }
-            local_vars.insert(letv.name.clone(), letv.type_annotation.clone());
         }
-        // Check functions
+        for letv in &component.let_vars {
+            self.check_expr(&letv.value, scopes, false);
+        }
+        for stmt in &component.reactive {
+            if let StmtKind::Reactive { expr, .. } = &stmt.kind {
+                self.check_expr(expr, scopes, false);
+            }
+        }
+        self.check_reactive_cycles(component);
         for func in &component.functions {
-            self.check_function(func);
+            self.check_function(func, scopes);
         }
         // Check markup
         for node in &component.markup {
-            self.check_markup(node, &local_vars);
+            self.check_markup(node, scopes);
         }
+        scopes.pop();
     }
 
-    fn check_markup(&mut self, node: &MarkupNode, vars: &HashMap<String, Option<Type>>) {
+    fn check_markup(&mut self, node: &MarkupNode, scopes: &mut ScopeStack) {
         match node {
-            MarkupNode::Element { tag:_, attributes, children } => {
-                for expr in attributes.values() {
-                    self.check_expr(expr, &mut vars.clone(), false);
+            MarkupNode::Element { tag, attributes, children, span } => {
+                self.check_deprecated(*tag, *span);
+                // HTML tags are lowercase, so a capitalized tag names a component
+                if tag.as_str().starts_with(|c: char| c.is_ascii_uppercase()) && !scopes.contains(*tag) {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!("Cannot find component '{}'", tag))
+                            .with_code(codes::UNKNOWN_COMPONENT)
+                            .with_primary(*span, "")
+                            .with_note(format!("declare `component {}` in this file, or import it", tag)),
+                    );
+                }
+                for (name, expr) in attributes {
+                    let handler = name.as_str().starts_with("on:");
+                    self.in_handler = handler;
+                    self.check_expr(expr, scopes, false);
+                    self.in_handler = false;
+                    if handler {
+                        self.check_event_handler(*name, expr, scopes);
+                    } else if name.as_str().starts_with("bind:") {
+                        // The element writes the bound value back
+                        if let ExprKind::Identifier(target) = &expr.kind {
+                            self.check_assignable(*target, expr.span, scopes);
+                        }
+                    }
                 }
                 for child in children {
-                    self.check_markup(child, vars);
+                    self.check_markup(child, scopes);
                 }
             }
             MarkupNode::Text(expr) => {
-                self.check_expr(expr, &mut vars.clone(), false);
+                self.check_expr(expr, scopes, false);
             }
             MarkupNode::IfBlock(ifblock) => {
-                self.check_expr(&ifblock.condition, &mut vars.clone(), false);
+                self.check_expr(&ifblock.condition, scopes, false);
                 for n in &ifblock.then_branch {
-                    self.check_markup(n, vars);
+                    self.check_markup(n, scopes);
                 }
                 if let Some(else_branch) = &ifblock.else_branch {
                     for n in else_branch {
-                        self.check_markup(n, vars);
+                        self.check_markup(n, scopes);
                     }
                 }
             }
             MarkupNode::ForLoop(forblock) => {
-                self.check_expr(&forblock.iterable, &mut vars.clone(), false);
-                let mut loop_vars = vars.clone();
-                loop_vars.insert(forblock.iterator.clone(), None);
+                self.check_expr(&forblock.iterable, scopes, false);
+                // The iterator is only in scope for the loop body
+                scopes.push();
+                scopes.declare(forblock.iterator, None, BindingKind::LoopVariable, forblock.span);
                 for n in &forblock.body {
-                    self.check_markup(n, &loop_vars);
+                    self.check_markup(n, scopes);
                 }
+                scopes.pop();
             }
         }
     }
 
-    /// Recursively check if an expression depends on any state variable
-    fn check_expr_reactivity(&mut self, expr: &Expr, vars: &HashMap<String, Option<Type>>, state_vars: &[StateVar], found: &mut bool) {
-        match expr {
-            Expr::Identifier(name) => {
-                if state_vars.iter().any(|s| &s.name == name) {
-                    *found = true;
+    /// Checks that an `on:event` handler is something that can be called: a
+    /// function name, an arrow function or an expression producing one. An
+    /// `async` function is a valid handler; the runtime starts it without waiting.
+    /// Modifiers after the event name must be ones the runtime knows, and for a
+    /// DOM event with a known event type the handler may take at most one
+    /// parameter, of that type.
+    fn check_event_handler(&mut self, attribute: Symbol, handler: &Expr, scopes: &ScopeStack) {
+        let mut segments = attribute.as_str().split(':').skip(1);
+        let event = segments.next().unwrap_or_default();
+        for modifier in segments {
+            if !EVENT_MODIFIERS.contains(&modifier) {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Unknown modifier '{}' on the '{}' handler", modifier, event))
+                        .with_code(codes::INVALID_HANDLER)
+                        .with_primary(handler.span, "")
+                        .with_note(format!("the valid modifiers are {}", EVENT_MODIFIERS.join(", "))),
+                );
+            }
+        }
+
+        let params = match &handler.kind {
+            ExprKind::Identifier(name) => match scopes.lookup(*name) {
+                Some(Binding { ty: Some(Type::Function { params, .. }), .. }) => params.clone(),
+                Some(Binding { ty: Some(ty), .. }) if *ty != Type::Any => {
+                    let message = format!("The handler for '{}' must be a function, found {}", event, ty);
+                    self.error(codes::INVALID_HANDLER, handler.span, message);
+                    return;
+                }
+                _ => return,
+            },
+            ExprKind::ArrowFunction { params, .. } => match function_type(params, None) {
+                Type::Function { params, .. } => params,
+                _ => return,
+            },
+            _ => {
+                if let Some(found) = literal_type(handler) {
+                    let message = format!("The handler for '{}' must be a function, found {}", event, found);
+                    self.error(codes::INVALID_HANDLER, handler.span, message);
                 }
+                return;
             }
-            Expr::BinaryOp { left, right, .. } => {
-                self.check_expr_reactivity(left, vars, state_vars, found);
-                self.check_expr_reactivity(right, vars, state_vars, found);
+        };
+        let Some(expected) = dom_event_type(event) else { return };
+        if params.len() > 1 {
+            self.diagnostics.push(
+                Diagnostic::error(format!("The handler for '{}' takes {} parameters, but is called with one", event, params.len()))
+                    .with_code(codes::INVALID_HANDLER)
+                    .with_primary(handler.span, "")
+                    .with_note(format!("'{}' handlers receive a single {}", event, expected)),
+            );
+        } else if let Some(param) = params.first() {
+            let param = match param {
+                Type::Ref(inner) | Type::MutRef(inner) => inner,
+                _ => param,
+            };
+            if !matches!(param, Type::Any) && *param != Type::Custom(Symbol::intern(expected)) {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("The handler for '{}' expects {}, but '{}' events pass {}", event, param, event, expected))
+                        .with_code(codes::TYPE_MISMATCH)
+                        .with_primary(handler.span, "")
+                        .with_note(format!("declare the parameter as `{}`, or leave it out", expected)),
+                );
             }
-            Expr::UnaryOp { operand, .. } => {
-                self.check_expr_reactivity(operand, vars, state_vars, found);
+        }
+    }
+
+    /// Reports derived values and `$:` statements that depend on each other in a
+    /// cycle, which would recompute each other forever.
+    fn check_reactive_cycles(&mut self, component: &ComponentNode) {
+        // Each derived value, with where it is declared and the names its value reads
+        let mut derived: Vec<(Symbol, Span, Vec<Symbol>)> = component
+            .let_vars
+            .iter()
+            .map(|letv| (letv.name, letv.span, reads(&letv.value)))
+            .collect();
+        for stmt in &component.reactive {
+            if let StmtKind::Reactive { name, expr } = &stmt.kind {
+                derived.push((*name, stmt.span, reads(expr)));
             }
-            Expr::Call { func, args } => {
-                self.check_expr_reactivity(func, vars, state_vars, found);
-                for arg in args {
-                    self.check_expr_reactivity(arg, vars, state_vars, found);
+        }
+        let index: HashMap<Symbol, usize> = derived.iter().enumerate().map(|(i, (name, ..))| (*name, i)).collect();
+        let edges: Vec<Vec<usize>> = derived
+            .iter()
+            .map(|(_, _, reads)| reads.iter().filter_map(|name| index.get(name).copied()).collect())
+            .collect();
+
+        // Depth-first search; a node found again while still on the path closes a cycle
+        let mut done = vec![false; derived.len()];
+        let mut path = Vec::new();
+        let mut cycles = Vec::new();
+        for start in 0..derived.len() {
+            find_cycles(start, &edges, &mut done, &mut path, &mut cycles);
+        }
+        for cycle in cycles {
+            let chain = cycle
+                .iter()
+                .chain(cycle.first())
+                .map(|&i| derived[i].0.to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            let mut diagnostic = Diagnostic::error(format!("Cyclic reactive dependency: {}", chain)).with_code(codes::REACTIVE_CYCLE);
+            for (i, (&from, &to)) in cycle.iter().zip(cycle.iter().cycle().skip(1)).enumerate() {
+                let label = format!("'{}' reads '{}'", derived[from].0, derived[to].0);
+                diagnostic = if i == 0 {
+                    diagnostic.with_primary(derived[from].1, label)
+                } else {
+                    diagnostic.with_label(derived[from].1, label)
+                };
+            }
+            self.diagnostics.push(diagnostic.with_note("each value is recomputed when the next one changes, so they would update each other forever"));
+        }
+    }
+
+    /// Checks an `extern "js"` declaration: its ABI, and that every type in its
+    /// signature can cross the JS boundary.
+    fn check_extern(&mut self, ext: &ExternFunction) {
+        if ext.abi != "js" {
+            self.error(codes::INVALID_EXTERN, ext.span, format!("Unsupported ABI \"{}\" for extern function '{}'; only \"js\" is supported", ext.abi, ext.name));
+        }
+        for param in &ext.params {
+            match &param.type_annotation {
+                None => self.error(codes::INVALID_EXTERN, param.span, format!("Parameter '{}' of extern function '{}' needs a type annotation", param.name, ext.name)),
+                Some(ty) if !is_ffi_type(ty) => self.error(codes::INVALID_EXTERN, param.span, format!(
+                    "Parameter '{}' of extern function '{}' has type {}, which cannot be passed to JS; use number, boolean or string",
+                    param.name, ext.name, ty
+                )),
+                Some(_) => {}
+            }
+            if param.default_value.is_some() || param.is_ref || param.is_mut_ref {
+                self.error(codes::INVALID_EXTERN, param.span, format!("Parameter '{}' of extern function '{}' cannot have a default value or be a reference", param.name, ext.name));
+            }
+        }
+        if let Some(ty) = &ext.return_type {
+            if !is_ffi_type(ty) && !matches!(ty, Type::Void) {
+                self.error(codes::INVALID_EXTERN, ext.span, format!(
+                    "Extern function '{}' returns {}, which cannot be passed from JS; use number, boolean, string or void",
+                    ext.name, ty
+                ));
+            }
+        }
+        self.externs.insert(ext.name, ext.clone());
+    }
+
+    /// Checks a call to an extern function: argument count, and the types of literal arguments.
+    fn check_extern_call(&mut self, ext: &ExternFunction, call: Span, args: &[Expr]) {
+        if args.len() != ext.params.len() {
+            self.error(codes::ARGUMENT_COUNT, call, format!(
+                "Extern function '{}' takes {} argument(s) but {} were given",
+                ext.name,
+                ext.params.len(),
+                args.len()
+            ));
+            return;
+        }
+        for (param, arg) in ext.params.iter().zip(args) {
+            let found = match &arg.kind {
+                ExprKind::NumberLiteral(_) => Type::Number,
+                ExprKind::StringLiteral(_) | ExprKind::TemplateLiteral { .. } => Type::String,
+                ExprKind::BooleanLiteral(_) => Type::Boolean,
+                ExprKind::CharLiteral(_) => Type::Char,
+                _ => continue,
+            };
+            if let Some(expected) = &param.type_annotation {
+                if std::mem::discriminant(expected) != std::mem::discriminant(&found) {
+                    self.error(codes::TYPE_MISMATCH, arg.span, format!(
+                        "Argument '{}' of extern function '{}' expects {}, found {}",
+                        param.name, ext.name, expected, found
+                    ));
                 }
             }
-            Expr::ArrayLiteral(items) => {
-                for item in items {
-                    self.check_expr_reactivity(item, vars, state_vars, found);
+        }
+    }
+
+    fn check_function(&mut self, func: &Function, scopes: &mut ScopeStack) {
+        scopes.push();
+        self.declare_params(&func.params, scopes);
+        self.check_block(&func.body, scopes, func.is_async);
+        scopes.pop();
+        self.check_body(func, &func.body, &HashMap::new());
+        self.check_returns(func);
+    }
+
+    /// Checks the `return` statements of `func` against its return type, and
+    /// that a function returning a value cannot reach the end of its body.
+    /// A function without a return type returns `void`.
+    fn check_returns(&mut self, func: &Function) {
+        let mut returns = Vec::new();
+        collect_returns(&func.body, &mut returns);
+        let declared = match &func.return_type {
+            None | Some(Type::Void) => None,
+            Some(ty) => Some(ty),
+        };
+        let Some(declared) = declared else {
+            for (span, value) in returns {
+                if value.is_some() {
+                    let note = match &func.return_type {
+                        Some(_) => format!("'{}' returns void", func.name),
+                        None => format!("'{}' has no return type; declare one with `fn {}(...): T`", func.name, func.name),
+                    };
+                    self.diagnostics.push(
+                        Diagnostic::error(format!("'{}' does not return a value, but this `return` has one", func.name))
+                            .with_code(codes::TYPE_MISMATCH)
+                            .with_primary(span, "")
+                            .with_note(note),
+                    );
                 }
             }
-            Expr::ObjectLiteral(props) => {
-                for prop in props {
-                    self.check_expr_reactivity(&prop.value, vars, state_vars, found);
+            return;
+        };
+        for (span, value) in returns {
+            let Some(value) = value else {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("'{}' returns {}, but this `return` has no value", func.name, declared))
+                        .with_code(codes::MISSING_RETURN)
+                        .with_primary(span, ""),
+                );
+                continue;
+            };
+            let found = literal_type(value).or_else(|| self.known_type(value));
+            if let Some(found) = found {
+                let comparable = |ty: &Type| matches!(ty, Type::Number | Type::String | Type::Boolean | Type::Char);
+                if comparable(declared) && comparable(&found) && std::mem::discriminant(declared) != std::mem::discriminant(&found) {
+                    self.error(codes::TYPE_MISMATCH, value.span, format!("'{}' returns {}, but this value is {}", func.name, declared, found));
+                }
+            }
+        }
+        if !matches!(declared, Type::Any) && !always_returns(&func.body) {
+            let end = Span::new(func.span.end.saturating_sub(1), func.span.end);
+            self.diagnostics.push(
+                Diagnostic::error(format!("'{}' returns {}, but it can reach the end of its body without returning", func.name, declared))
+                    .with_code(codes::MISSING_RETURN)
+                    .with_primary(end, "")
+                    .with_note("end every path with `return` or `throw`"),
+            );
+        }
+    }
+
+    /// Declares parameters, or the bindings of destructured ones, in the innermost scope.
+    fn declare_params(&mut self, params: &[Parameter], scopes: &mut ScopeStack) {
+        for param in params {
+            // `&mut x: T` and `x: &mut T` are the same
+            let kind = match &param.type_annotation {
+                _ if param.is_mut_ref => BindingKind::MutRefParam,
+                Some(Type::MutRef(_)) => BindingKind::MutRefParam,
+                _ if param.is_ref => BindingKind::RefParam,
+                Some(Type::Ref(_)) => BindingKind::RefParam,
+                _ => BindingKind::Param,
+            };
+            match &param.pattern {
+                Some(pattern) => for name in pattern.bindings() { self.declare(scopes, name, None, kind, param.span); },
+                None => self.declare(scopes, param.name, param.type_annotation.clone(), kind, param.span),
+            }
+        }
+    }
+
+    /// Checks the `?`, `?.` and `??` operators in `stmts` and the blocks nested in them.
+    /// `narrowed` holds the variants that the conditions around `stmts` prove variables have.
+    fn check_body(&mut self, func: &Function, stmts: &[Stmt], narrowed: &HashMap<Symbol, &'static str>) {
+        let mut narrowed = narrowed.clone();
+        for stmt in stmts {
+            // A loop body runs again after its own assignments
+            if matches!(stmt.kind, StmtKind::Loop { .. } | StmtKind::ForIn { .. } | StmtKind::ForOf { .. }) {
+                forget(&mut narrowed, std::slice::from_ref(stmt));
+            }
+            for expr in stmt.exprs() {
+                self.check_operators(func, expr, &narrowed);
+            }
+            match &stmt.kind {
+                StmtKind::If { condition, then, else_ } => {
+                    self.check_body(func, then, &narrow(&narrowed, condition, true));
+                    if let Some(else_) = else_ {
+                        self.check_body(func, else_, &narrow(&narrowed, condition, false));
+                    }
+                }
+                _ => for block in stmt.blocks() {
+                    self.check_body(func, block, &narrowed);
+                },
+            }
+            forget(&mut narrowed, std::slice::from_ref(stmt));
+            if let Some((condition, holds)) = narrows_after(stmt) {
+                narrowed = narrow(&narrowed, condition, holds);
+            }
+        }
+    }
+
+    /// Checks the `?`, `?.` and `??` operators in `expr` against the types that are known.
+    fn check_operators(&mut self, func: &Function, expr: &Expr, narrowed: &HashMap<Symbol, &'static str>) {
+        match &expr.kind {
+            ExprKind::Try(value) => self.check_try(func, expr, value),
+            ExprKind::OptionalChain { object, .. } => self.check_optional(object, narrowed, "`?.` is not needed"),
+            ExprKind::Coalesce { value, default } => {
+                self.check_optional(value, narrowed, "the default of `??` is never used");
+                if let (Some(Type::Option(inner)), Some(found)) = (self.known_type(value), literal_type(default)) {
+                    if matches!(*inner, Type::Number | Type::String | Type::Boolean | Type::Char)
+                        && std::mem::discriminant(inner.as_ref()) != std::mem::discriminant(&found)
+                    {
+                        self.error(codes::TYPE_MISMATCH, default.span, format!("The default of `??` is {}, but the Option holds {}", found, inner));
+                    }
                 }
             }
             _ => {}
         }
+        // A branch only runs when its condition, or the arm's pattern, holds
+        match &expr.kind {
+            ExprKind::If { condition, then, else_ } => {
+                self.check_operators(func, condition, narrowed);
+                self.check_operators(func, then, &narrow(narrowed, condition, true));
+                self.check_operators(func, else_, &narrow(narrowed, condition, false));
+            }
+            ExprKind::BinaryOp { left, op: op @ (BinaryOp::And | BinaryOp::Or), right } => {
+                self.check_operators(func, left, narrowed);
+                self.check_operators(func, right, &narrow(narrowed, left, *op == BinaryOp::And));
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                self.check_operators(func, scrutinee, narrowed);
+                for arm in arms {
+                    let mut narrowed = narrowed.clone();
+                    if let (ExprKind::Identifier(name), Some(variant)) = (&scrutinee.kind, pattern_variant(&arm.pattern)) {
+                        narrowed.insert(*name, variant);
+                    }
+                    for child in arm.guard.iter().chain(std::iter::once(&arm.body)) {
+                        self.check_operators(func, child, &narrowed);
+                    }
+                }
+            }
+            _ => for child in expr.children() {
+                self.check_operators(func, child, narrowed);
+            },
+        }
     }
 
-    fn check_function(&mut self, _func: &Function) {
-        // TODO: Implement function semantic checks
+    /// Warns when the value before `?.` or `??` has a known type that is never
+    /// `None`, or is a variable that a condition around it proves is `Some`.
+    fn check_optional(&mut self, value: &Expr, narrowed: &HashMap<Symbol, &'static str>, consequence: &str) {
+        if let ExprKind::Identifier(name) = &value.kind {
+            if narrowed.get(name) == Some(&"Some") {
+                self.diagnostics.push(
+                    Diagnostic::warning(format!("'{}' is always Some here, so {}", name, consequence))
+                        .with_code(codes::NEVER_NONE)
+                        .with_primary(value.span, "")
+                        .with_note("a condition or `match` arm around this code already checked it"),
+                );
+                return;
+            }
+        }
+        let Some(ty) = self.known_type(value) else { return };
+        let never_none = matches!(
+            ty,
+            Type::Number | Type::String | Type::Boolean | Type::Char | Type::Array(_) | Type::Result(..) | Type::Function { .. }
+        );
+        if never_none {
+            self.diagnostics.push(
+                Diagnostic::warning(format!("This value has type {}, which is never None, so {}", ty, consequence))
+                    .with_code(codes::NEVER_NONE)
+                    .with_primary(value.span, ""),
+            );
+        }
+    }
+
+    /// Checks that a `?` is in a function that returns `Result` or `Option`, and
+    /// applied to the same kind of value where its type is known.
+    fn check_try(&mut self, func: &Function, expr: &Expr, value: &Expr) {
+        {
+            let kind = |ty: Option<&Type>| match ty {
+                Some(Type::Result(..)) => Some("Result"),
+                Some(Type::Option(_)) => Some("Option"),
+                _ => None,
+            };
+            let returns = kind(func.return_type.as_ref());
+            let found = self.known_type(value);
+            let found_kind = kind(found.as_ref());
+            if returns.is_none() {
+                let returned = func.return_type.as_ref().map_or("nothing".to_string(), |t| t.to_string());
+                self.diagnostics.push(
+                    Diagnostic::error("The `?` operator can only be used in a function that returns Result or Option")
+                        .with_code(codes::INVALID_TRY)
+                        .with_primary(expr.span, "")
+                        .with_note(format!("'{}' returns {}", func.name, returned)),
+                );
+            } else if let Some(found) = found {
+                if found_kind.is_none() {
+                    self.error(codes::INVALID_TRY, value.span, format!("The `?` operator needs a Result or Option, found {}", found));
+                } else if found_kind != returns {
+                    self.error(codes::INVALID_TRY, value.span, format!(
+                        "Cannot use `?` on a value of type {} in '{}', which returns {}",
+                        found,
+                        func.name,
+                        func.return_type.as_ref().map_or(String::new(), |t| t.to_string())
+                    ));
+                }
+            }
+        }
     }
 
-    fn check_stmt(&mut self, stmt: &Stmt, vars: &mut HashMap<String, Option<Type>>, in_async: bool) {
-        match stmt {
-            Stmt::Expr(expr) => { self.check_expr(expr, vars, in_async); },
-            Stmt::Return(Some(expr)) => { self.check_expr(expr, vars, in_async); },
-            Stmt::StateVarDecl(state) => {
-                self.check_expr(&state.initial_value, vars, in_async);
-                vars.insert(state.name.clone(), state.type_annotation.clone());
+    /// The declared type of `expr`, for a variable declared with one or a call to a top-level function.
+    fn known_type(&self, expr: &Expr) -> Option<Type> {
+        match &expr.kind {
+            ExprKind::Identifier(_) => self.identifier_types.get(&expr.span).cloned(),
+            ExprKind::Call { func: callee, .. } => match &callee.kind {
+                ExprKind::Identifier(name) => self.return_types.get(name).cloned(),
+                _ => None,
             },
-            Stmt::LetVarDecl(letv) => {
-                self.check_expr(&letv.value, vars, in_async);
-                if vars.contains_key(&letv.name) {
-                    self.errors.push(format!("Cannot reassign to immutable let variable '{}'.", letv.name));
+            _ => None,
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt, scopes: &mut ScopeStack, in_async: bool) {
+        match &stmt.kind {
+            StmtKind::Expr(expr) | StmtKind::Throw(expr) => self.check_expr(expr, scopes, in_async),
+            StmtKind::Return(Some(expr)) => self.check_expr(expr, scopes, in_async),
+            StmtKind::StateVarDecl(state) => {
+                self.check_expr(&state.initial_value, scopes, in_async);
+                self.declare(scopes, state.name, state.type_annotation.clone(), BindingKind::State, state.span);
+            },
+            StmtKind::LetVarDecl(letv) => {
+                // The value is checked first, so `let x = x + 1;` refers to an outer `x`
+                self.check_expr(&letv.value, scopes, in_async);
+                let kind = if letv.mutable { BindingKind::Mut } else { BindingKind::Let };
+                self.declare(scopes, letv.name, letv.type_annotation.clone(), kind, letv.span);
+            },
+            StmtKind::Assign { target, value } => {
+                self.check_expr(value, scopes, in_async);
+                self.check_assignable(*target, stmt.span, scopes);
+            },
+            StmtKind::Destructure { pattern, value, else_branch, mutable, .. } => {
+                self.check_expr(value, scopes, in_async);
+                self.check_pattern(pattern);
+                self.check_let_else(stmt, pattern, else_branch.as_deref());
+                // The bindings are not in scope in the `else` block, which runs when they did not match
+                if let Some(body) = else_branch {
+                    self.check_block(body, scopes, in_async);
+                }
+                let kind = if *mutable { BindingKind::Mut } else { BindingKind::Let };
+                for name in pattern.bindings() {
+                    self.declare(scopes, name, None, kind, pattern.span);
                 }
-                vars.insert(letv.name.clone(), letv.type_annotation.clone());
             },
-            Stmt::Reactive { name, expr } => {
-                self.check_expr(expr, vars, in_async);
-                if !vars.contains_key(name) {
-                    self.errors.push(format!("Reactive variable '${}' not declared", name));
+            StmtKind::Reactive { name, expr } => {
+                self.check_expr(expr, scopes, in_async);
+                if !scopes.contains(*name) {
+                    self.error(codes::UNDECLARED_VARIABLE, stmt.span, format!("Reactive variable '${}' not declared", name));
                 }
             },
-            Stmt::Comprehension { target, iter, filter, expr } => {
-                self.check_expr(iter, vars, in_async);
-                if let Some(f) = filter { self.check_expr(f, vars, in_async); }
-                self.check_expr(expr, vars, in_async);
-                vars.insert(target.clone(), None); // Assume type inference for now
+            StmtKind::Comprehension { target, iter, filter, expr } => {
+                self.check_comprehension(*target, iter, filter.as_ref(), expr, scopes, in_async);
             },
-            Stmt::Block(stmts) => for s in stmts { self.check_stmt(s, vars, in_async); },
-            // TODO: Add more statement checks (If, Loop, For, etc.)
-            _ => {}
+            StmtKind::Block(stmts) => self.check_block(stmts, scopes, in_async),
+            StmtKind::If { condition, then, else_ } => {
+                self.check_expr(condition, scopes, in_async);
+                self.check_block(then, scopes, in_async);
+                if let Some(else_) = else_ { self.check_block(else_, scopes, in_async); }
+            },
+            StmtKind::Loop { init, condition, update, body } => {
+                // The initializer's variables are scoped to the loop, and the body's to one iteration
+                scopes.push();
+                match init.as_deref() {
+                    // The update advances the counter of `for (let i = 0; ...; i++)`, so it is mutable
+                    Some(Stmt { kind: StmtKind::LetVarDecl(letv), .. }) => {
+                        self.check_expr(&letv.value, scopes, in_async);
+                        self.declare(scopes, letv.name, letv.type_annotation.clone(), BindingKind::Mut, letv.span);
+                    }
+                    Some(init) => self.check_stmt(init, scopes, in_async),
+                    None => {}
+                }
+                if let Some(c) = condition { self.check_expr(c, scopes, in_async); }
+                self.check_block(body, scopes, in_async);
+                if let Some(update) = update { self.check_stmt(update, scopes, in_async); }
+                scopes.pop();
+            },
+            StmtKind::ForIn { variable, iterable, body } | StmtKind::ForOf { variable, iterable, body } => {
+                self.check_expr(iterable, scopes, in_async);
+                scopes.push();
+                scopes.declare(*variable, None, BindingKind::LoopVariable, stmt.span);
+                self.check_block(body, scopes, in_async);
+                scopes.pop();
+            },
+            StmtKind::Try { body, catch, finally } => {
+                self.check_block(body, scopes, in_async);
+                if let Some(catch) = catch {
+                    scopes.push();
+                    scopes.declare(catch.error_var, None, BindingKind::Let, catch.span);
+                    self.check_block(&catch.body, scopes, in_async);
+                    scopes.pop();
+                }
+                if let Some(finally) = finally { self.check_block(finally, scopes, in_async); }
+            },
+            StmtKind::Switch { expression, cases, default } => {
+                self.check_expr(expression, scopes, in_async);
+                for (i, case) in cases.iter().enumerate() {
+                    self.check_expr(&case.value, scopes, in_async);
+                    self.check_block(&case.body, scopes, in_async);
+                    let repeated = literal_key(&case.value)
+                        .is_some_and(|key| cases[..i].iter().any(|earlier| literal_key(&earlier.value).as_ref() == Some(&key)));
+                    if repeated {
+                        self.diagnostics.push(
+                            Diagnostic::warning("Unreachable case: an earlier case has the same value")
+                                .with_code(codes::UNREACHABLE_PATTERN)
+                                .with_primary(case.value.span, ""),
+                        );
+                    }
+                }
+                if let Some(default) = default { self.check_block(default, scopes, in_async); }
+            },
+            StmtKind::Return(None) | StmtKind::Break(_) | StmtKind::Continue(_) => {}
+        }
+    }
+
+    /// Checks `stmts` in a new scope, so their declarations end with the block.
+    fn check_block(&mut self, stmts: &[Stmt], scopes: &mut ScopeStack, in_async: bool) {
+        scopes.push();
+        for s in stmts { self.check_stmt(s, scopes, in_async); }
+        scopes.pop();
+    }
+
+    /// Declares `name` in the innermost scope. Shadowing a name from an enclosing
+    /// scope is allowed, but declaring it twice in the same scope is an error.
+    fn declare(&mut self, scopes: &mut ScopeStack, name: Symbol, ty: Option<Type>, kind: BindingKind, span: Span) {
+        if let Some(previous) = scopes.declare(name, ty, kind, span) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("'{}' is already declared in this scope", name))
+                    .with_code(codes::DUPLICATE_DEFINITION)
+                    .with_primary(span, "declared again here")
+                    .with_label(previous, "first declared here"),
+            );
         }
     }
 
-    fn check_expr(&mut self, expr: &Expr, vars: &mut HashMap<String, Option<Type>>, in_async: bool) {
-        match expr {
-            Expr::Await(inner) => {
+    /// Warns about variables, parameters and private functions that are never
+    /// read. Names starting with `_` are left out, so they can be unused on purpose.
+    fn report_unused(&mut self, scopes: ScopeStack) {
+        let mut unused: Vec<(Symbol, Binding)> = scopes
+            .into_declarations()
+            .filter(|(name, binding)| !binding.used && !name.as_str().starts_with('_'))
+            .collect();
+        unused.sort_by_key(|(name, binding)| (binding.span.start, *name));
+        for (name, binding) in unused {
+            let (code, what) = match binding.kind {
+                BindingKind::Let | BindingKind::Mut => (codes::UNUSED_VARIABLE, "variable"),
+                BindingKind::State => (codes::UNUSED_VARIABLE, "state variable"),
+                BindingKind::Param | BindingKind::RefParam | BindingKind::MutRefParam => (codes::UNUSED_PARAMETER, "parameter"),
+                BindingKind::Function => (codes::UNUSED_FUNCTION, "function"),
+                BindingKind::LoopVariable | BindingKind::Global => continue,
+            };
+            self.diagnostics.push(
+                Diagnostic::warning(format!("Unused {} '{}'", what, name))
+                    .with_code(code)
+                    .with_primary(binding.span, "")
+                    .with_note(format!("if this is intentional, name it '_{}'", name)),
+            );
+        }
+    }
+
+    /// Checks that `name` is declared and can be assigned to: a `mut` variable,
+    /// a state variable or a `&mut` parameter.
+    fn check_assignable(&mut self, name: Symbol, span: Span, scopes: &mut ScopeStack) {
+        let Some(binding) = scopes.lookup(name) else {
+            self.error(codes::UNDECLARED_VARIABLE, span, format!("Assignment to undeclared variable '{}'", name));
+            return;
+        };
+        let (what, fix) = match binding.kind {
+            // Writing through a `&mut` parameter is what it is for
+            BindingKind::MutRefParam => return scopes.mark_used(name),
+            BindingKind::Mut | BindingKind::State => return,
+            BindingKind::Let => ("a `let` variable", Some("declare it with `mut` to allow assignment")),
+            BindingKind::Param | BindingKind::RefParam => ("a parameter", Some("take it as `&mut` to change the caller's value")),
+            BindingKind::LoopVariable => ("a loop variable", None),
+            BindingKind::Function => ("a function", None),
+            BindingKind::Global => ("not a variable", None),
+        };
+        let mut diagnostic = Diagnostic::error(format!("Cannot assign to '{}', which is {}", name, what))
+            .with_code(codes::IMMUTABLE_ASSIGNMENT)
+            .with_primary(span, "");
+        if let Some(fix) = fix {
+            diagnostic = diagnostic.with_note(fix);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Checks the arguments passed to `&mut` parameters of `callee`: each must
+    /// be a place in a mutable variable, and that variable must not also be
+    /// passed as another argument of the same call.
+    fn check_borrows(&mut self, callee: Symbol, args: &[Expr], scopes: &ScopeStack) {
+        let Some(Binding { ty: Some(Type::Function { params, .. }), .. }) = scopes.lookup(callee) else { return };
+        let roots: Vec<Option<Symbol>> = args.iter().map(place_root).collect();
+        for (i, (param, arg)) in params.iter().zip(args).enumerate() {
+            if !matches!(param, Type::MutRef(_)) {
+                continue;
+            }
+            let Some(root) = roots[i] else {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Argument {} of '{}' is passed as `&mut`, so it must be a variable", i + 1, callee))
+                        .with_code(codes::INVALID_BORROW)
+                        .with_primary(arg.span, "this is a temporary value")
+                        .with_note("store the value in a `mut` variable and pass that"),
+                );
+                continue;
+            };
+            let kind = scopes.lookup(root).map(|binding| binding.kind);
+            if let Some(BindingKind::Let | BindingKind::Param | BindingKind::RefParam | BindingKind::LoopVariable) = kind {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Cannot pass '{}' as `&mut` to '{}', because '{}' is not mutable", root, callee, root))
+                        .with_code(codes::INVALID_BORROW)
+                        .with_primary(arg.span, "")
+                        .with_note(format!("declare '{}' with `mut`, or take it as a `&mut` parameter", root)),
+                );
+            }
+            // While `f` holds a `&mut` to the variable, no other argument may refer to it
+            // (reported once, at the first `&mut` of the pair)
+            let earlier_mut = |j: usize| j < i && matches!(params.get(j), Some(Type::MutRef(_)));
+            if let Some(j) = (0..roots.len()).find(|&j| j != i && roots[j] == Some(root) && !earlier_mut(j)) {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("'{}' is passed to '{}' as `&mut` and as another argument", root, callee))
+                        .with_code(codes::ALIASED_BORROW)
+                        .with_primary(arg.span, "passed as `&mut` here")
+                        .with_label(args[j].span, "and also here"),
+                );
+            }
+        }
+    }
+
+    /// Checks that a method known to change its receiver is not called through
+    /// a parameter that was not passed as `&mut`.
+    fn check_mutating_call(&mut self, object: &Expr, method: Symbol, scopes: &ScopeStack) {
+        let ExprKind::Identifier(name) = &object.kind else { return };
+        if !MUTATING_METHODS.contains(&method.as_str()) {
+            return;
+        }
+        if let Some(Binding { kind: BindingKind::Param | BindingKind::RefParam, .. }) = scopes.lookup(*name) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("Cannot call '{}' on parameter '{}', which is not `&mut`", method, name))
+                    .with_code(codes::IMMUTABLE_ASSIGNMENT)
+                    .with_primary(object.span, "")
+                    .with_note(format!("'{}' changes the value it is called on; take the parameter as `&mut`", method)),
+            );
+        }
+    }
+
+    /// Checks a comprehension; its target is only in scope for the filter and the element.
+    fn check_comprehension(&mut self, target: Symbol, iter: &Expr, filter: Option<&Expr>, expr: &Expr, scopes: &mut ScopeStack, in_async: bool) {
+        self.check_expr(iter, scopes, in_async);
+        scopes.push();
+        scopes.declare(target, None, BindingKind::LoopVariable, iter.span);
+        if let Some(f) = filter { self.check_expr(f, scopes, in_async); }
+        self.check_expr(expr, scopes, in_async);
+        scopes.pop();
+    }
+
+    fn check_expr(&mut self, expr: &Expr, scopes: &mut ScopeStack, in_async: bool) {
+        match &expr.kind {
+            ExprKind::Await(inner) => {
                 if !in_async {
-                    self.errors.push("'await' used outside of async function".to_string());
+                    let mut error = Diagnostic::error("'await' used outside of async function")
+                        .with_code(codes::AWAIT_OUTSIDE_ASYNC)
+                        .with_primary(expr.span, "");
+                    if self.in_handler {
+                        error = error.with_note("arrow functions cannot be async; move the handler into an `async fn` and pass it by name");
+                    }
+                    self.diagnostics.push(error);
                 }
-                self.check_expr(inner, vars, in_async);
+                match &inner.kind {
+                    // Checked here, so the awaited call is not reported as unawaited
+                    ExprKind::Call { func, args } => self.check_call(inner, func, args, scopes, in_async, true),
+                    _ => self.check_expr(inner, scopes, in_async),
+                }
+            },
+            ExprKind::Comprehension { target, iter, filter, expr } => {
+                self.check_comprehension(*target, iter, filter.as_deref(), expr, scopes, in_async);
             },
-            Expr::Comprehension { target, iter, filter, expr } => {
-                self.check_expr(iter, vars, in_async);
-                if let Some(f) = filter { self.check_expr(f, vars, in_async); }
-                self.check_expr(expr, vars, in_async);
-                vars.insert(target.clone(), None);
+            ExprKind::Call { func, args } => self.check_call(expr, func, args, scopes, in_async, false),
+            ExprKind::MethodCall { object, method, args } => {
+                self.check_regex_literal(object, method.as_str(), args);
+                self.check_interval(object, *method, args, scopes);
+                self.check_mutating_call(object, *method, scopes);
+                self.check_expr(object, scopes, in_async);
+                for arg in args { self.check_expr(arg, scopes, in_async); }
             },
-            Expr::Call { func, args } => {
-                self.check_expr(func, vars, in_async);
-                for arg in args { self.check_expr(arg, vars, in_async); }
+            ExprKind::New { class, args } => {
+                self.check_regex_literal(class, "new", args);
+                for arg in args { self.check_expr(arg, scopes, in_async); }
             },
-            Expr::Identifier(name) => {
-                if !vars.contains_key(name) {
-                    self.errors.push(format!("Use of undeclared variable '{}'", name));
+            ExprKind::Identifier(name) => {
+                if !scopes.read(*name) {
+                    self.error(codes::UNDECLARED_VARIABLE, expr.span, format!("Use of undeclared variable '{}'", name));
                 }
+                if let Some(ty) = scopes.lookup(*name).and_then(|binding| binding.ty.clone()) {
+                    self.identifier_types.insert(expr.span, ty);
+                }
+            },
+            ExprKind::BinaryOp { left, right, .. } => {
+                self.check_expr(left, scopes, in_async);
+                self.check_expr(right, scopes, in_async);
+            },
+            ExprKind::UnaryOp { operand, .. } | ExprKind::Try(operand) => self.check_expr(operand, scopes, in_async),
+            ExprKind::OptionalChain { object, args, .. } => {
+                self.check_expr(object, scopes, in_async);
+                for arg in args.iter().flatten() { self.check_expr(arg, scopes, in_async); }
+            },
+            ExprKind::Coalesce { value, default } => {
+                self.check_expr(value, scopes, in_async);
+                self.check_expr(default, scopes, in_async);
+            },
+            ExprKind::TemplateLiteral { parts } => for part in parts {
+                if let TemplatePart::Expression(e) = part { self.check_expr(e, scopes, in_async); }
+            },
+            ExprKind::ArrayAccess { array, index } => {
+                self.check_expr(array, scopes, in_async);
+                self.check_expr(index, scopes, in_async);
             },
-            Expr::BinaryOp { left, right, .. } => {
-                self.check_expr(left, vars, in_async);
-                self.check_expr(right, vars, in_async);
+            ExprKind::If { condition, then, else_ } => {
+                self.check_expr(condition, scopes, in_async);
+                self.check_expr(then, scopes, in_async);
+                self.check_expr(else_, scopes, in_async);
             },
-            Expr::UnaryOp { operand, .. } => self.check_expr(operand, vars, in_async),
-            Expr::If { condition, then, else_ } => {
-                self.check_expr(condition, vars, in_async);
-                self.check_expr(then, vars, in_async);
-                self.check_expr(else_, vars, in_async);
+            ExprKind::ArrayLiteral(items) => for item in items { self.check_expr(item, scopes, in_async); },
+            ExprKind::ObjectLiteral(props) => for prop in props {
+                if let PropertyKey::Computed(key) = &prop.key { self.check_expr(key, scopes, in_async); }
+                self.check_expr(&prop.value, scopes, in_async);
             },
-            // Option/Result support can be added here in the future
-            Expr::ArrayLiteral(items) => for item in items { self.check_expr(item, vars, in_async); },
-            Expr::ObjectLiteral(props) => for prop in props { self.check_expr(&prop.value, vars, in_async); },
-            // TODO: Add more expression checks as needed
+            ExprKind::ArrowFunction { params, body } => {
+                scopes.push();
+                self.declare_params(params, scopes);
+                self.check_block(body, scopes, false);
+                scopes.pop();
+            },
+            ExprKind::Match { scrutinee, arms } => {
+                self.check_expr(scrutinee, scopes, in_async);
+                for arm in arms {
+                    self.check_pattern(&arm.pattern);
+                    // Pattern bindings are only in scope for their own arm
+                    scopes.push();
+                    for name in arm.pattern.bindings() {
+                        scopes.declare(name, None, BindingKind::Let, arm.pattern.span);
+                    }
+                    if let Some(guard) = &arm.guard { self.check_expr(guard, scopes, in_async); }
+                    self.check_expr(&arm.body, scopes, in_async);
+                    scopes.pop();
+                }
+                self.check_exhaustive(expr.span, arms);
+            },
+            _ => for child in expr.children() { self.check_expr(child, scopes, in_async); },
+        }
+    }
+
+    /// Checks a call `expr` of `func`, which is awaited if `awaited`.
+    fn check_call(&mut self, expr: &Expr, func: &Expr, args: &[Expr], scopes: &mut ScopeStack, in_async: bool, awaited: bool) {
+        if let ExprKind::Identifier(name) = &func.kind {
+            self.check_deprecated(*name, func.span);
+            if let Some(ext) = self.externs.get(name).cloned() {
+                self.check_extern_call(&ext, expr.span, args);
+                for arg in args { self.check_expr(arg, scopes, in_async); }
+                return;
+            }
+        }
+        if let ExprKind::PropertyAccess { object, property } = &func.kind {
+            self.check_regex_literal(object, property.as_str(), args);
+            self.check_interval(object, *property, args, scopes);
+        }
+        self.check_expr(func, scopes, in_async);
+        for arg in args { self.check_expr(arg, scopes, in_async); }
+        if let ExprKind::Identifier(name) = &func.kind {
+            self.check_borrows(*name, args, scopes);
+            // An `on:event` handler may start async work without waiting for it
+            if !awaited && !self.in_handler && self.async_function(*name, scopes).is_some() {
+                let note = if in_async {
+                    "add `await` to wait for it to finish; without it, the code after the call runs first and its result is dropped"
+                } else {
+                    "the calling function is not async, so the call runs in the background; make it an `async fn` to await the call"
+                };
+                self.diagnostics.push(
+                    Diagnostic::warning(format!("'{}' is async, but this call does not await it", name))
+                        .with_code(codes::UNAWAITED_ASYNC)
+                        .with_primary(expr.span, "")
+                        .with_note(note),
+                );
+            }
+        }
+    }
+
+    /// Checks the handler passed to `time.interval(ms, handler)` against how the
+    /// runtime schedules it: a run starts every `ms` milliseconds whether or not
+    /// the previous one has finished, so an async handler that awaits can overlap itself.
+    fn check_interval(&mut self, object: &Expr, property: Symbol, args: &[Expr], scopes: &ScopeStack) {
+        let ExprKind::Identifier(module) = &object.kind else { return };
+        if *module != "time" || property != "interval" || !matches!(scopes.lookup(*module), Some(Binding { kind: BindingKind::Global, .. })) {
+            return;
+        }
+        let Some(handler) = args.get(1) else { return };
+        let ExprKind::Identifier(name) = &handler.kind else { return };
+        if let Some(Some(await_span)) = self.async_function(*name, scopes) {
+            self.diagnostics.push(
+                Diagnostic::warning(format!("'{}' is async, but `time.interval` does not wait for it to finish", name))
+                    .with_code(codes::OVERLAPPING_INTERVAL)
+                    .with_primary(handler.span, "")
+                    .with_label(await_span, format!("'{}' may still be waiting here when the next tick starts it again", name))
+                    .with_note("runs can overlap; to run one at a time, loop in an `async fn` that awaits each run and then a delay"),
+            );
+        }
+    }
+
+    /// Checks that the arms of a `match` cover every value, and warns about arms
+    /// that earlier ones already cover. Guarded arms may not match, so they cover nothing.
+    fn check_exhaustive(&mut self, span: Span, arms: &[MatchArm]) {
+        let mut covered: Vec<&Pattern> = Vec::new();
+        for arm in arms {
+            if covers(&covered, &arm.pattern) {
+                self.diagnostics.push(
+                    Diagnostic::warning("Unreachable match arm")
+                        .with_code(codes::UNREACHABLE_PATTERN)
+                        .with_primary(arm.pattern.span, "")
+                        .with_note("earlier arms already match every value this one does"),
+                );
+            }
+            if arm.guard.is_none() {
+                covered.push(&arm.pattern);
+            }
+        }
+        if let Some(missing) = missing_pattern(&covered) {
+            self.diagnostics.push(
+                Diagnostic::error(format!("Non-exhaustive match: `{}` is not covered", missing))
+                    .with_code(codes::NON_EXHAUSTIVE)
+                    .with_primary(span, "")
+                    .with_note(match missing.as_str() {
+                        "_" => "add a `_` arm".to_string(),
+                        _ => format!("add an arm for `{}`, or a `_` arm", missing),
+                    }),
+            );
+        }
+    }
+
+    /// Checks that constructor patterns name `Some`, `None`, `Ok` or `Err` with the right number of fields.
+    fn check_pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Object { fields } => {
+                for (_, field) in fields {
+                    self.check_pattern(field);
+                }
+            }
+            PatternKind::Array { elements, .. } => {
+                for element in elements {
+                    self.check_pattern(element);
+                }
+            }
             _ => {}
         }
+        if let PatternKind::Constructor { name, args } = &pattern.kind {
+            let arity = match name.as_str() {
+                "Some" | "Ok" | "Err" => 1,
+                "None" => 0,
+                _ => {
+                    self.error(codes::UNKNOWN_PATTERN, pattern.span, format!("Unknown pattern constructor '{}'; expected Some, None, Ok or Err", name));
+                    return;
+                }
+            };
+            if args.len() != arity {
+                self.error(codes::PATTERN_ARITY, pattern.span, format!("Pattern '{}' takes {} field(s) but {} were given", name, arity, args.len()));
+            }
+            for arg in args {
+                self.check_pattern(arg);
+            }
+        }
+    }
+
+    /// Checks the refutability of a `let` pattern: a pattern that can fail to match
+    /// needs an `else` block, and that block must not fall through to the bindings.
+    fn check_let_else(&mut self, stmt: &Stmt, pattern: &Pattern, else_branch: Option<&[Stmt]>) {
+        let Some(body) = else_branch else {
+            if pattern.is_refutable() {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("Refutable pattern '{}' in `let` needs an `else` block", pattern))
+                        .with_code(codes::REFUTABLE_PATTERN)
+                        .with_primary(pattern.span, "some values do not match this pattern")
+                        .with_note(format!("use `let {} = value else {{ return; }};` or a `match`", pattern)),
+                );
+            }
+            return;
+        };
+        if !pattern.is_refutable() {
+            self.diagnostics.push(
+                Diagnostic::warning(format!("Irrefutable pattern '{}' in `let`; the `else` block never runs", pattern))
+                    .with_code(codes::IRREFUTABLE_LET_ELSE)
+                    .with_primary(pattern.span, ""),
+            );
+        }
+        let diverges = matches!(
+            body.last().map(|s| &s.kind),
+            Some(StmtKind::Return(_) | StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Throw(_))
+        );
+        if !diverges {
+            self.error(codes::LET_ELSE_FALLTHROUGH, stmt.span, "The `else` block of a `let` must end with `return`, `break`, `continue` or `throw`");
+        }
+    }
+
+    /// Validates regex patterns passed as string literals, so invalid ones become compile errors.
+    fn check_regex_literal(&mut self, receiver: &Expr, method: &str, args: &[Expr]) {
+        let is_regex = matches!(&receiver.kind, ExprKind::Identifier(name) if name == "regex" || name == "Regex");
+        if !is_regex || !matches!(method, "new" | "validate") {
+            return;
+        }
+        if let Some(Expr { kind: ExprKind::StringLiteral(pattern), span }) = args.first() {
+            if let Err(e) = regex_syntax::Parser::new().parse(pattern) {
+                let reason = match &e {
+                    regex_syntax::Error::Parse(e) => e.kind().to_string(),
+                    regex_syntax::Error::Translate(e) => e.kind().to_string(),
+                    _ => e.to_string(),
+                };
+                self.error(codes::INVALID_REGEX, *span, format!("Invalid regex pattern \"{}\": {}", pattern, reason));
+            }
+        }
+    }
+}
+
+/// The variables in scope while checking, one map per nested scope with the
+/// innermost last. Lookups search from the inside out, so inner declarations
+/// shadow outer ones until their scope is popped.
+struct ScopeStack {
+    scopes: Vec<HashMap<Symbol, Binding>>,
+    /// Declarations from popped scopes, kept for the unused-name warnings.
+    finished: Vec<(Symbol, Binding)>,
+}
+
+/// A declared name: its type, if known, what declared it, and whether it was read.
+struct Binding {
+    ty: Option<Type>,
+    kind: BindingKind,
+    span: Span,
+    used: bool,
+}
+
+/// What declared a name, which decides whether it can be assigned to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BindingKind {
+    Let,
+    Mut,
+    State,
+    Param,
+    RefParam,
+    MutRefParam,
+    LoopVariable,
+    Function,
+    /// A runtime name, an import or a component.
+    Global,
+}
+
+impl ScopeStack {
+    fn new() -> Self {
+        Self { scopes: vec![HashMap::new()], finished: Vec::new() }
+    }
+
+    fn push(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn pop(&mut self) {
+        debug_assert!(self.scopes.len() > 1, "popped the outermost scope");
+        if let Some(scope) = self.scopes.pop() {
+            self.finished.extend(scope);
+        }
+    }
+
+    /// Declares `name` in the innermost scope, returning where it was already
+    /// declared there, if it was. The replaced declaration is kept for the unused-name warnings.
+    fn declare(&mut self, name: Symbol, ty: Option<Type>, kind: BindingKind, span: Span) -> Option<Span> {
+        let scope = self.scopes.last_mut().expect("the outermost scope is never popped");
+        let previous = scope.insert(name, Binding { ty, kind, span, used: false })?;
+        let previous_span = previous.span;
+        self.finished.push((name, previous));
+        Some(previous_span)
+    }
+
+    /// Marks the innermost declaration of `name` as read, returning false if it is not in scope.
+    fn read(&mut self, name: Symbol) -> bool {
+        self.mark_used(name);
+        self.contains(name)
+    }
+
+    fn mark_used(&mut self, name: Symbol) {
+        if let Some(binding) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(&name)) {
+            binding.used = true;
+        }
+    }
+
+    /// Every declaration, from popped scopes and those still open.
+    fn into_declarations(self) -> impl Iterator<Item = (Symbol, Binding)> {
+        self.finished.into_iter().chain(self.scopes.into_iter().flatten())
+    }
+
+    /// The innermost declaration of `name`, if it is in scope.
+    fn lookup(&self, name: Symbol) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    fn contains(&self, name: Symbol) -> bool {
+        self.lookup(name).is_some()
+    }
+}
+
+/// The names read by `expr`, leaving out the bodies of arrow functions, which
+/// only run when called.
+fn reads(expr: &Expr) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    let mut pending = vec![expr];
+    while let Some(expr) = pending.pop() {
+        if let ExprKind::Identifier(name) = &expr.kind {
+            names.push(*name);
+        }
+        pending.extend(expr.children().into_iter().rev());
+    }
+    names
+}
+
+/// Visits `node` and the nodes reachable from it, adding each cycle found to
+/// `cycles` as the path around it. Finished nodes are not visited again, so
+/// each cycle is reported once.
+fn find_cycles(node: usize, edges: &[Vec<usize>], done: &mut [bool], path: &mut Vec<usize>, cycles: &mut Vec<Vec<usize>>) {
+    if done[node] {
+        return;
+    }
+    if let Some(position) = path.iter().position(|&n| n == node) {
+        cycles.push(path[position..].to_vec());
+        return;
+    }
+    path.push(node);
+    for &next in &edges[node] {
+        find_cycles(next, edges, done, path, cycles);
+    }
+    path.pop();
+    done[node] = true;
+}
+
+/// The first value, written as a pattern, that none of `patterns` matches.
+/// Values that are not `Option`, `Result` or boolean can only be covered by a
+/// binding or `_`.
+fn missing_pattern(patterns: &[&Pattern]) -> Option<String> {
+    if patterns.iter().any(|p| !p.is_refutable()) {
+        return None;
+    }
+    let constructor = patterns.iter().find_map(|p| match &p.kind {
+        PatternKind::Constructor { name, .. } => Some(name.as_str()),
+        _ => None,
+    });
+    let variants: &[&str] = match constructor {
+        Some("Some" | "None") => &["Some", "None"],
+        Some("Ok" | "Err") => &["Ok", "Err"],
+        _ if patterns.iter().any(|p| matches!(p.kind, PatternKind::Boolean(_))) => {
+            return [true, false]
+                .into_iter()
+                .find(|b| !patterns.iter().any(|p| matches!(p.kind, PatternKind::Boolean(v) if v == *b)))
+                .map(|b| b.to_string());
+        }
+        _ => return Some("_".to_string()),
+    };
+    for variant in variants {
+        if *variant == "None" {
+            if !patterns.iter().any(|p| matches!(&p.kind, PatternKind::Constructor { name, .. } if name == "None")) {
+                return Some("None".to_string());
+            }
+            continue;
+        }
+        let fields = constructor_fields(patterns, variant);
+        if fields.is_empty() {
+            return Some(format!("{}(_)", variant));
+        }
+        if let Some(missing) = missing_pattern(&fields) {
+            return Some(format!("{}({})", variant, missing));
+        }
+    }
+    None
+}
+
+/// True if every value `pattern` matches is matched by one of `earlier`.
+fn covers(earlier: &[&Pattern], pattern: &Pattern) -> bool {
+    if earlier.iter().any(|p| !p.is_refutable()) {
+        return true;
+    }
+    match &pattern.kind {
+        PatternKind::Wildcard | PatternKind::Binding(_) => missing_pattern(earlier).is_none(),
+        PatternKind::Constructor { name, args } => match args.first() {
+            None => earlier.iter().any(|p| matches!(&p.kind, PatternKind::Constructor { name: other, .. } if other == name)),
+            Some(field) => {
+                let fields = constructor_fields(earlier, name.as_str());
+                !fields.is_empty() && covers(&fields, field)
+            }
+        },
+        PatternKind::Number(_) | PatternKind::String(_) | PatternKind::Char(_) | PatternKind::Boolean(_) => {
+            earlier.iter().any(|p| pattern_literal(p).is_some() && pattern_literal(p) == pattern_literal(pattern))
+        }
+        // Objects and arrays are not compared, so they are never reported as unreachable
+        PatternKind::Object { .. } | PatternKind::Array { .. } => false,
+    }
+}
+
+/// The field patterns of the `variant(field)` patterns among `patterns`.
+fn constructor_fields<'a>(patterns: &[&'a Pattern], variant: &str) -> Vec<&'a Pattern> {
+    patterns
+        .iter()
+        .filter_map(|p| match &p.kind {
+            PatternKind::Constructor { name, args } if name == variant => args.first(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A literal pattern's value as text, for comparing patterns.
+fn pattern_literal(pattern: &Pattern) -> Option<String> {
+    match &pattern.kind {
+        PatternKind::Number(_) | PatternKind::String(_) | PatternKind::Char(_) | PatternKind::Boolean(_) => Some(pattern.to_string()),
+        _ => None,
+    }
+}
+
+/// A literal expression's value as text, for finding repeated `switch` cases.
+fn literal_key(expr: &Expr) -> Option<String> {
+    match &expr.kind {
+        ExprKind::NumberLiteral(n) => Some(n.to_string()),
+        ExprKind::StringLiteral(s) => Some(format!("{:?}", s)),
+        ExprKind::CharLiteral(c) => Some(format!("{:?}", c)),
+        ExprKind::BooleanLiteral(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Methods that change the value they are called on.
+const MUTATING_METHODS: &[&str] = &["push", "pop", "shift", "unshift", "splice", "sort", "reverse", "fill", "insert", "remove", "clear", "set"];
+
+/// Names the runtime provides to every module.
+pub(crate) const GLOBALS: &[&str] = &["print", "console", "Math", "Date", "JSON", "window", "document", "Some", "None", "Ok", "Err"];
+
+/// Standard library modules, whose functions are called as `module.function(...)`.
+pub(crate) const STD_MODULES: &[&str] = &[
+    "io", "time", "datetime", "task", "stream", "reactive", "css", "string", "regex", "http", "env", "process", "random", "crypto",
+    "encoding", "uuid",
+];
+
+/// The type of a function with these parameters and return type; missing
+/// annotations are `any`, and `&mut x: T` becomes `&mut T`.
+pub(crate) fn function_type(params: &[Parameter], return_type: Option<&Type>) -> Type {
+    let param_type = |p: &Parameter| {
+        let ty = p.type_annotation.clone().unwrap_or(Type::Any);
+        match ty {
+            Type::Ref(_) | Type::MutRef(_) => ty,
+            _ if p.is_mut_ref => Type::MutRef(Box::new(ty)),
+            _ if p.is_ref => Type::Ref(Box::new(ty)),
+            _ => ty,
+        }
+    };
+    Type::Function {
+        params: params.iter().map(param_type).collect(),
+        return_type: Box::new(return_type.cloned().unwrap_or(Type::Void)),
+    }
+}
+
+/// Where `stmts` first use `await`, leaving out arrow functions, which cannot.
+fn first_await(stmts: &[Stmt]) -> Option<Span> {
+    stmts.iter().find_map(|stmt| {
+        let mut pending: Vec<&Expr> = stmt.exprs().into_iter().rev().collect();
+        while let Some(expr) = pending.pop() {
+            if let ExprKind::Await(_) = expr.kind {
+                return Some(expr.span);
+            }
+            pending.extend(expr.children().into_iter().rev());
+        }
+        stmt.blocks().into_iter().find_map(first_await)
+    })
+}
+
+/// The `return` statements in `stmts` and the blocks nested in them, but not
+/// in arrow functions.
+fn collect_returns<'a>(stmts: &'a [Stmt], out: &mut Vec<(Span, Option<&'a Expr>)>) {
+    for stmt in stmts {
+        if let StmtKind::Return(value) = &stmt.kind {
+            out.push((stmt.span, value.as_ref()));
+        }
+        for block in stmt.blocks() {
+            collect_returns(block, out);
+        }
+    }
+}
+
+/// The variables whose `Option` or `Result` variant `condition` proves when it
+/// evaluates to `holds`: `x.is_some()` proves `x` is `Some` when true and `None`
+/// when false, and `!`, `&&` and `||` combine what their operands prove.
+pub(crate) fn narrowings(condition: &Expr, holds: bool) -> Vec<(Symbol, &'static str)> {
+    match &condition.kind {
+        ExprKind::MethodCall { object, method, args } if args.is_empty() => {
+            let ExprKind::Identifier(name) = &object.kind else { return Vec::new() };
+            let (when_true, when_false) = match method.as_str() {
+                "is_some" => ("Some", "None"),
+                "is_none" => ("None", "Some"),
+                "is_ok" => ("Ok", "Err"),
+                "is_err" => ("Err", "Ok"),
+                _ => return Vec::new(),
+            };
+            vec![(*name, if holds { when_true } else { when_false })]
+        }
+        ExprKind::UnaryOp { op: UnaryOp::Not, operand } => narrowings(operand, !holds),
+        // Both sides of `a && b` hold when it does, and neither side of `a || b` when it does not
+        ExprKind::BinaryOp { left, op: BinaryOp::And, right } if holds => [narrowings(left, true), narrowings(right, true)].concat(),
+        ExprKind::BinaryOp { left, op: BinaryOp::Or, right } if !holds => [narrowings(left, false), narrowings(right, false)].concat(),
+        _ => Vec::new(),
+    }
+}
+
+/// For an `if` with a branch that always returns, its condition and the value
+/// it has in the code after the `if`, which only the other branch reaches.
+pub(crate) fn narrows_after(stmt: &Stmt) -> Option<(&Expr, bool)> {
+    let StmtKind::If { condition, then, else_ } = &stmt.kind else { return None };
+    if always_returns(then) {
+        Some((condition, false))
+    } else if else_.as_deref().is_some_and(always_returns) {
+        Some((condition, true))
+    } else {
+        None
+    }
+}
+
+/// `narrowed`, plus what `condition` proves when it evaluates to `holds`.
+fn narrow(narrowed: &HashMap<Symbol, &'static str>, condition: &Expr, holds: bool) -> HashMap<Symbol, &'static str> {
+    let mut narrowed = narrowed.clone();
+    narrowed.extend(narrowings(condition, holds));
+    narrowed
+}
+
+/// Drops what is known about the variables `stmts` assign to.
+fn forget(narrowed: &mut HashMap<Symbol, &'static str>, stmts: &[Stmt]) {
+    for name in assigned(stmts) {
+        narrowed.remove(&name);
+    }
+}
+
+/// The variant a `match` arm's pattern proves its scrutinee has.
+pub(crate) fn pattern_variant(pattern: &Pattern) -> Option<&'static str> {
+    match &pattern.kind {
+        PatternKind::Constructor { name, .. } => ["Some", "None", "Ok", "Err"].into_iter().find(|variant| name == variant),
+        _ => None,
+    }
+}
+
+/// The type of the value inside `variant` of a value of type `ty`.
+pub(crate) fn variant_type(ty: &Type, variant: &str) -> Option<Type> {
+    match (ty, variant) {
+        (Type::Option(inner), "Some") | (Type::Result(inner, _), "Ok") | (Type::Result(_, inner), "Err") => Some(inner.as_ref().clone()),
+        _ => None,
+    }
+}
+
+/// The variables assigned in `stmts` and the blocks nested in them.
+pub(crate) fn assigned(stmts: &[Stmt]) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    for stmt in stmts {
+        if let StmtKind::Assign { target, .. } | StmtKind::Reactive { name: target, .. } = &stmt.kind {
+            names.push(*target);
+        }
+        for block in stmt.blocks() {
+            names.extend(assigned(block));
+        }
+    }
+    names
+}
+
+/// Whether every path through `stmts` ends in `return` or `throw`.
+pub(crate) fn always_returns(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match &stmt.kind {
+        StmtKind::Return(_) | StmtKind::Throw(_) => true,
+        StmtKind::Block(body) => always_returns(body),
+        StmtKind::If { then, else_: Some(else_), .. } => always_returns(then) && always_returns(else_),
+        StmtKind::Switch { cases, default: Some(default), .. } => {
+            cases.iter().all(|case| always_returns(&case.body)) && always_returns(default)
+        }
+        StmtKind::Try { body, catch, finally } => {
+            finally.as_deref().is_some_and(always_returns)
+                || (always_returns(body) && catch.as_ref().map_or(true, |catch| always_returns(&catch.body)))
+        }
+        // `while (true)` only ends through a `break`
+        StmtKind::Loop { condition, body, .. } => {
+            matches!(condition.as_ref().map(|c| &c.kind), None | Some(ExprKind::BooleanLiteral(true))) && !breaks(body)
+        }
+        _ => false,
+    })
+}
+
+/// Whether a `break` in `stmts` can leave the loop they are the body of.
+fn breaks(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match &stmt.kind {
+        StmtKind::Break(_) => true,
+        // An unlabeled `break` in a nested loop leaves only that loop
+        StmtKind::Loop { body, .. } | StmtKind::ForIn { body, .. } | StmtKind::ForOf { body, .. } => labeled_break(body),
+        _ => stmt.blocks().into_iter().any(breaks),
+    })
+}
+
+fn labeled_break(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| matches!(stmt.kind, StmtKind::Break(Some(_))) || stmt.blocks().into_iter().any(labeled_break))
+}
+
+/// The variable a place expression such as `a`, `a.b` or `a[i]` belongs to.
+fn place_root(expr: &Expr) -> Option<Symbol> {
+    match &expr.kind {
+        ExprKind::Identifier(name) => Some(*name),
+        ExprKind::PropertyAccess { object, .. } => place_root(object),
+        ExprKind::ArrayAccess { array, .. } => place_root(array),
+        _ => None,
+    }
+}
+
+/// Types that can cross the `extern "js"` boundary.
+fn is_ffi_type(ty: &Type) -> bool {
+    matches!(ty, Type::Number | Type::Boolean | Type::String)
+}
+
+/// Modifiers that may follow the event name of a handler, as in `on:submit:prevent`.
+const EVENT_MODIFIERS: &[&str] = &["prevent", "preventDefault", "stop", "stopPropagation", "once"];
+
+/// The type of event the runtime passes to handlers of a DOM event, for the
+/// events `std::browser::events` describes.
+fn dom_event_type(event: &str) -> Option<&'static str> {
+    match event {
+        "click" | "dblclick" | "mousedown" | "mouseup" | "mousemove" | "mouseenter" | "mouseleave" => Some("MouseEvent"),
+        "keydown" | "keyup" => Some("KeyboardEvent"),
+        "input" | "change" => Some("InputEvent"),
+        _ => None,
+    }
+}
+
+/// The primitive type of a literal expression.
+fn literal_type(expr: &Expr) -> Option<Type> {
+    match &expr.kind {
+        ExprKind::NumberLiteral(_) => Some(Type::Number),
+        ExprKind::StringLiteral(_) => Some(Type::String),
+        ExprKind::BooleanLiteral(_) => Some(Type::Boolean),
+        ExprKind::CharLiteral(_) => Some(Type::Char),
+        _ => None,
     }
 }
 
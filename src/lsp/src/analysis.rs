@@ -0,0 +1,286 @@
+//! Cross-file analysis for the Gigli language server: diagnostics, go-to-definition and rename
+
+use crate::workspace::{parse_source, Workspace};
+use gigli_core::ast::{SpannedToken, Symbol, TemplateToken, Token, AST};
+use gigli_core::diagnostics::{self, LineIndex, Severity, Span};
+use gigli_core::hir::{self, DefId, DefKind, Hir};
+use gigli_core::lexer::Lexer;
+use gigli_core::parser::Parser;
+use gigli_core::resolver::check_imports;
+use gigli_core::semantic::SemanticAnalyzer;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::*;
+
+/// Runs the lexer, parser and semantic analyzer over a source string, plus
+/// import checks against the rest of the workspace when the file path is known.
+pub fn diagnose(workspace: &Workspace, path: Option<&Path>, source: &str) -> Vec<Diagnostic> {
+    let index = LineIndex::new(source);
+    let ast = match parse_source(source) {
+        Ok(ast) => ast,
+        Err(e) => return vec![to_lsp_diagnostic(&e, &index)],
+    };
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.analyze(&ast);
+    let mut messages = analyzer.diagnostics;
+
+    if let Some(path) = path {
+        let resolver = workspace.resolver();
+        messages.extend(workspace.with_index(|index| {
+            check_imports(&ast, &|module| {
+                resolver
+                    .resolve(path, module)
+                    .and_then(|target| index.get(&target))
+                    .and_then(|file| file.ast.as_ref())
+            })
+        }));
+    }
+    messages.iter().map(|d| to_lsp_diagnostic(d, &index)).collect()
+}
+
+fn span_range(span: Span, index: &LineIndex) -> Range {
+    let (start_line, start_col) = index.line_col_utf16(span.start);
+    let (end_line, end_col) = index.line_col_utf16(span.end);
+    Range::new(
+        Position::new(start_line as u64, start_col as u64),
+        Position::new(end_line as u64, end_col as u64),
+    )
+}
+
+/// Converts a compiler diagnostic, placing it at its primary span. Notes and
+/// suggestions are appended to the message.
+fn to_lsp_diagnostic(diagnostic: &diagnostics::Diagnostic, index: &LineIndex) -> Diagnostic {
+    let range = diagnostic.primary_span().map(|span| span_range(span, index)).unwrap_or_default();
+    let severity = match diagnostic.severity {
+        Severity::Error => DiagnosticSeverity::Error,
+        Severity::Warning => DiagnosticSeverity::Warning,
+        Severity::Note => DiagnosticSeverity::Information,
+        Severity::Help => DiagnosticSeverity::Hint,
+    };
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+    for suggestion in &diagnostic.suggestions {
+        message.push_str(&format!("\nhelp: {}: `{}`", suggestion.message, suggestion.replacement));
+    }
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        code: diagnostic.code.clone().map(NumberOrString::String),
+        source: Some("gigli".to_string()),
+        message,
+        ..Diagnostic::default()
+    }
+}
+
+/// Finds where the name under the cursor is declared, following imports into other files.
+pub fn find_definition(workspace: &Workspace, uri: &Url, position: Position) -> Option<Location> {
+    let path = uri.to_file_path().ok()?;
+    let source = workspace.source_of(&path)?;
+    let bindings = Bindings::new(&source)?;
+    let def = bindings.def_at(&source, position)?;
+    if let Some(target) = bindings.imported_from(workspace, &path, def) {
+        let name = bindings.hir.name(def);
+        let text = workspace.source_of(&target)?;
+        let declared = Bindings::new(&text).and_then(|target| target.declarations.get(&target.top_level(name)?).copied());
+        let span = declared.or_else(|| Some(workspace.symbol_index().find(&target, name.as_str())?.span))?;
+        return Some(Location::new(Url::from_file_path(target).ok()?, span_range(span, &LineIndex::new(&text))));
+    }
+    let span = bindings.declarations.get(&def)?;
+    Some(Location::new(uri.clone(), span_range(*span, &LineIndex::new(&source))))
+}
+
+/// Renames the definition of the name under the cursor and every reference to
+/// it. A top-level item is renamed in its defining file and every file
+/// importing it; anything else only where it is in scope.
+pub fn rename(workspace: &Workspace, uri: &Url, position: Position, new_name: &str) -> Option<WorkspaceEdit> {
+    let path = uri.to_file_path().ok()?;
+    let source = workspace.source_of(&path)?;
+    let bindings = Bindings::new(&source)?;
+    let def = bindings.def_at(&source, position)?;
+    let name = bindings.hir.name(def);
+
+    let mut changes = HashMap::new();
+    let mut edit = |file: &Path, text: &str, bindings: &Bindings, def: DefId| {
+        let index = LineIndex::new(text);
+        let edits: Vec<TextEdit> = bindings
+            .references(def)
+            .map(|span| TextEdit::new(span_range(span, &index), new_name.to_string()))
+            .collect();
+        if let (false, Ok(file_uri)) = (edits.is_empty(), Url::from_file_path(file)) {
+            changes.insert(file_uri, edits);
+        }
+    };
+    if bindings.top_level(name) != Some(def) {
+        edit(&path, &source, &bindings, def);
+        return Some(WorkspaceEdit { changes: Some(changes), ..WorkspaceEdit::default() });
+    }
+
+    let def_path = bindings.imported_from(workspace, &path, def).unwrap_or_else(|| path.clone());
+    let mut files = vec![def_path.clone(), path.clone()];
+    files.extend(importers_of(workspace, &def_path, name));
+    files.sort();
+    files.dedup();
+    for file in files {
+        let text = match workspace.source_of(&file) {
+            Some(text) => text,
+            None => continue,
+        };
+        if let Some(bindings) = Bindings::new(&text) {
+            if let Some(def) = bindings.top_level(name) {
+                edit(&file, &text, &bindings, def);
+            }
+        }
+    }
+    Some(WorkspaceEdit {
+        changes: Some(changes),
+        ..WorkspaceEdit::default()
+    })
+}
+
+/// Returns every indexed file that imports `name` from the module at `def_path`.
+fn importers_of(workspace: &Workspace, def_path: &Path, name: Symbol) -> Vec<PathBuf> {
+    let resolver = workspace.resolver();
+    workspace.with_index(|index| {
+        index
+            .iter()
+            .filter(|(file, entry)| {
+                entry.ast.as_ref().map_or(false, |ast| {
+                    ast.imports.iter().any(|import| {
+                        import.items.contains(&name) && resolver.resolve(file, &import.module).as_deref() == Some(def_path)
+                    })
+                })
+            })
+            .map(|(file, _)| file.clone())
+            .collect()
+    })
+}
+
+/// An identifier token. `tag` is set for the name of a markup tag.
+struct Identifier {
+    name: Symbol,
+    span: Span,
+    tag: bool,
+}
+
+/// Collects the identifier tokens in `tokens`, including those inside template
+/// literals. Comments, strings and template text are not tokens of their own.
+fn collect_identifiers(tokens: &[SpannedToken], identifiers: &mut Vec<Identifier>) {
+    for (i, token) in tokens.iter().enumerate() {
+        match &token.token {
+            Token::Identifier(name) => {
+                let tag = match i.checked_sub(1).map(|prev| &tokens[prev].token) {
+                    Some(Token::LessThan) => true,
+                    Some(Token::Slash) => i >= 2 && tokens[i - 2].token == Token::LessThan,
+                    _ => false,
+                };
+                identifiers.push(Identifier { name: *name, span: token.span, tag });
+            }
+            Token::TemplateLiteral(parts) => {
+                for part in parts {
+                    if let TemplateToken::Expression(tokens) = part {
+                        collect_identifiers(tokens, identifiers);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The identifiers of a file, each bound to the definition it names the way
+/// semantic analysis scopes them, so a local shadowing a name is told apart
+/// from it.
+struct Bindings {
+    ast: AST,
+    hir: Hir,
+    /// Identifiers naming a definition, in source order.
+    names: Vec<(Span, DefId)>,
+    /// The identifier each definition is declared by. Names that are neither
+    /// declared nor imported have none.
+    declarations: HashMap<DefId, Span>,
+}
+
+impl Bindings {
+    fn new(source: &str) -> Option<Self> {
+        let tokens = Lexer::new(source).tokenize().ok()?;
+        let mut identifiers = Vec::new();
+        collect_identifiers(&tokens, &mut identifiers);
+        let ast = Parser::new(tokens).parse().ok()?;
+        let hir = hir::lower(&ast);
+
+        // Definitions and references carry the span of the whole construct;
+        // the name is the first identifier in it spelled like the definition
+        let name_in = |name: Symbol, span: Span| {
+            identifiers
+                .iter()
+                .find(|ident| ident.name == name && span.start <= ident.span.start && ident.span.end <= span.end)
+                .map(|ident| ident.span)
+        };
+        let declarations: HashMap<DefId, Span> = hir
+            .definitions()
+            .filter(|(_, def)| def.kind != DefKind::Global || ast.imports.iter().any(|import| import.span == def.span))
+            .filter_map(|(id, def)| Some((id, name_in(def.name, def.span)?)))
+            .collect();
+        let mut names: Vec<(Span, DefId)> = declarations.iter().map(|(id, span)| (*span, *id)).collect();
+        names.extend(hir.references.iter().filter_map(|(span, id)| Some((name_in(hir.name(*id), *span)?, *id))));
+        let mut bindings = Bindings { ast, hir, names, declarations };
+
+        // A tag naming a component refers to it, whether declared or imported
+        for ident in identifiers.iter().filter(|ident| ident.tag) {
+            let component = bindings
+                .top_level(ident.name)
+                .filter(|id| matches!(bindings.hir.def(*id).kind, DefKind::Component | DefKind::Global));
+            if let Some(id) = component {
+                bindings.names.push((ident.span, id));
+            }
+        }
+        bindings.names.sort_by_key(|(span, _)| span.start);
+        bindings.names.dedup_by_key(|(span, _)| span.start);
+        Some(bindings)
+    }
+
+    /// The definition named by the identifier touching `position`.
+    fn def_at(&self, source: &str, position: Position) -> Option<DefId> {
+        let index = LineIndex::new(source);
+        self.names.iter().find_map(|(span, id)| {
+            let range = span_range(*span, &index);
+            let touches = range.start.line == position.line
+                && range.start.character <= position.character
+                && position.character <= range.end.character;
+            touches.then_some(*id)
+        })
+    }
+
+    /// The spans of every identifier naming `def`, its declaration included.
+    fn references(&self, def: DefId) -> impl Iterator<Item = Span> + '_ {
+        self.names.iter().filter(move |(_, id)| *id == def).map(|(span, _)| *span)
+    }
+
+    /// The definition of `name` at the top level of the file: a function,
+    /// component or extern declared there, or an item it imports.
+    fn top_level(&self, name: Symbol) -> Option<DefId> {
+        let items = self.ast.functions.iter().filter(|f| f.name == name).map(|f| f.span);
+        let items = items
+            .chain(self.ast.components.iter().filter(|c| c.name == name).map(|c| c.span))
+            .chain(self.ast.externs.iter().filter(|e| e.name == name).map(|e| e.span))
+            .chain(self.ast.imports.iter().filter(|i| i.items.contains(&name)).map(|i| i.span))
+            .collect::<Vec<_>>();
+        self.hir
+            .definitions()
+            .find(|(_, def)| def.name == name && items.contains(&def.span))
+            .map(|(id, _)| id)
+    }
+
+    /// The file of the module `def` is imported from, if it is an imported
+    /// item of the file at `path` and the module resolves.
+    fn imported_from(&self, workspace: &Workspace, path: &Path, def: DefId) -> Option<PathBuf> {
+        let def = self.hir.def(def);
+        if def.kind != DefKind::Global {
+            return None;
+        }
+        let import = self.ast.imports.iter().find(|import| import.span == def.span && import.items.contains(&def.name))?;
+        workspace.resolver().resolve(path, &import.module)
+    }
+}
@@ -0,0 +1,69 @@
+//! In-memory text documents for the Gigli language server
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+/// An open document, stored as a rope so incremental edits stay cheap on large files.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub rope: Rope,
+    pub version: Option<i64>,
+}
+
+impl Document {
+    pub fn new(text: &str, version: Option<i64>) -> Self {
+        Document {
+            rope: Rope::from_str(text),
+            version,
+        }
+    }
+
+    /// Applies a single change event; a change without a range replaces the whole text.
+    pub fn apply_change(&mut self, change: &TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let start = self.position_to_char(range.start);
+                let end = self.position_to_char(range.end);
+                self.rope.remove(start..end);
+                self.rope.insert(start, &change.text);
+            }
+            None => {
+                self.rope = Rope::from_str(&change.text);
+            }
+        }
+    }
+
+    /// Converts an LSP range into the byte range of the text it covers.
+    pub fn byte_range(&self, range: Range) -> std::ops::Range<usize> {
+        let start = self.rope.char_to_byte(self.position_to_char(range.start));
+        let end = self.rope.char_to_byte(self.position_to_char(range.end));
+        start..end.max(start)
+    }
+
+    /// Converts an LSP position (line, UTF-16 column) into a char index, clamped to the document.
+    pub fn position_to_char(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        if line >= self.rope.len_lines() {
+            return self.rope.len_chars();
+        }
+        let line_start = self.rope.line_to_char(line);
+        let line_end = line_start + self.rope.line(line).len_chars();
+        let line_start_cu = self.rope.char_to_utf16_cu(line_start);
+        let target_cu = line_start_cu + position.character as usize;
+        let max_cu = self.rope.char_to_utf16_cu(line_end);
+        self.rope.utf16_cu_to_char(target_cu.min(max_cu))
+    }
+
+    /// Converts a char index back into an LSP position.
+    pub fn char_to_position(&self, char_idx: usize) -> Position {
+        let char_idx = char_idx.min(self.rope.len_chars());
+        let line = self.rope.char_to_line(char_idx);
+        let line_start = self.rope.line_to_char(line);
+        let character = self.rope.char_to_utf16_cu(char_idx) - self.rope.char_to_utf16_cu(line_start);
+        Position::new(line as u64, character as u64)
+    }
+
+    pub fn text(&self) -> String {
+        self.rope.to_string()
+    }
+}
@@ -0,0 +1,6 @@
+//! Language server for the Gigli programming language
+
+pub mod analysis;
+pub mod document;
+pub mod lsp;
+pub mod workspace;
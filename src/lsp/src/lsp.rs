@@ -1,17 +1,129 @@
 //! Language Server Protocol implementation for Gigli
 
-use anyhow::Result;
-use tower_lsp::{LspService, Server};
+use crate::analysis;
+use crate::workspace::Workspace;
+use std::sync::Arc;
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::*;
+use tower_lsp::{Client, LanguageServer, LspService, Server};
 
-pub struct GigliLanguageServer;
+pub struct GigliLanguageServer {
+    workspace: Arc<Workspace>,
+}
 
 impl GigliLanguageServer {
     pub fn new() -> Self {
-        Self
+        Self {
+            workspace: Arc::new(Workspace::new()),
+        }
+    }
+
+    /// Re-analyzes every open document and publishes its diagnostics.
+    ///
+    /// All open documents are refreshed because an edit in one file can change
+    /// cross-file errors (missing exports, component props) in its importers.
+    fn publish_all_diagnostics(&self, client: &Client) {
+        for uri in self.workspace.open_uris() {
+            let doc = match self.workspace.document(&uri) {
+                Some(doc) => doc,
+                None => continue,
+            };
+            let path = uri.to_file_path().ok();
+            let diagnostics = analysis::diagnose(&self.workspace, path.as_deref(), &doc.text());
+            client.publish_diagnostics(uri, diagnostics, doc.version);
+        }
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for GigliLanguageServer {
+    fn initialize(&self, _: &Client, params: InitializeParams) -> Result<InitializeResult> {
+        let root = params.root_uri.and_then(|uri| uri.to_file_path().ok());
+        self.workspace.set_root(root);
+        Ok(InitializeResult {
+            server_info: None,
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::Incremental,
+                )),
+                definition_provider: Some(true),
+                rename_provider: Some(RenameProviderCapability::Simple(true)),
+                ..ServerCapabilities::default()
+            },
+        })
+    }
+
+    async fn initialized(&self, client: &Client, _: InitializedParams) {
+        // Index the whole workspace off the request loop so cross-file features work immediately.
+        let workspace = Arc::clone(&self.workspace);
+        let indexed = tokio::task::spawn_blocking(move || workspace.index_root()).await;
+        if let Ok(count) = indexed {
+            client.log_message(MessageType::Info, format!("Indexed {} Gigli files", count));
+        }
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, client: &Client, params: DidOpenTextDocumentParams) {
+        let doc = params.text_document;
+        self.workspace.open(doc.uri.clone(), &doc.text, Some(doc.version));
+        self.publish_all_diagnostics(client);
+    }
+
+    async fn did_change(&self, client: &Client, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.workspace.change(&uri, &params.content_changes, params.text_document.version);
+        self.publish_all_diagnostics(client);
+    }
+
+    async fn did_close(&self, client: &Client, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.workspace.close(&uri);
+        client.publish_diagnostics(uri, Vec::new(), None);
+        self.publish_all_diagnostics(client);
+    }
+
+    async fn did_change_watched_files(&self, client: &Client, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            let path = match change.uri.to_file_path() {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            match change.typ {
+                FileChangeType::Created | FileChangeType::Changed => self.workspace.index_file(&path),
+                FileChangeType::Deleted => self.workspace.remove_file(&path),
+            }
+        }
+        self.publish_all_diagnostics(client);
+    }
+
+    async fn goto_definition(&self, params: TextDocumentPositionParams) -> Result<Option<GotoDefinitionResponse>> {
+        let location = analysis::find_definition(&self.workspace, &params.text_document.uri, params.position);
+        Ok(location.map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = params.text_document_position;
+        Ok(analysis::rename(
+            &self.workspace,
+            &position.text_document.uri,
+            position.position,
+            &params.new_name,
+        ))
     }
 }
 
 pub fn run() {
-    // Simple LSP implementation for now
-    println!("GigliOptix LSP starting...");
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start tokio runtime");
+    runtime.block_on(async {
+        let stdin = tokio::io::stdin();
+        let stdout = tokio::io::stdout();
+        let (service, messages) = LspService::new(GigliLanguageServer::new());
+        Server::new(stdin, stdout)
+            .interleave(messages)
+            .serve(service)
+            .await;
+    });
 }
@@ -0,0 +1,221 @@
+//! Workspace state for the Gigli language server: open documents plus a parsed index of every `.gx` file
+
+use crate::document::Document;
+use gigli_core::ast::{SpannedToken, AST};
+use gigli_core::diagnostics::Diagnostic;
+use gigli_core::lexer::Lexer;
+use gigli_core::parser::Parser;
+use gigli_core::resolver::{ModuleResolver, SymbolIndex};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tower_lsp::lsp_types::{TextDocumentContentChangeEvent, Url};
+
+/// Parse result for a single file in the workspace.
+#[derive(Debug)]
+pub struct FileIndex {
+    pub ast: Option<AST>,
+    pub error: Option<Diagnostic>,
+}
+
+impl FileIndex {
+    pub fn from_source(source: &str) -> Self {
+        Self::from_tokens(Lexer::new(source).tokenize())
+    }
+
+    /// Parses the result of lexing a file.
+    pub fn from_tokens(tokens: Result<Vec<SpannedToken>, Diagnostic>) -> Self {
+        match tokens.and_then(|tokens| Parser::new(tokens).parse()) {
+            Ok(ast) => FileIndex { ast: Some(ast), error: None },
+            Err(e) => FileIndex { ast: None, error: Some(e) },
+        }
+    }
+}
+
+/// The lexer of an open document, relexed incrementally as it is edited.
+struct LexedDocument {
+    lexer: Lexer,
+    tokens: Result<Vec<SpannedToken>, Diagnostic>,
+}
+
+impl LexedDocument {
+    fn new(text: &str) -> Self {
+        let mut lexer = Lexer::new(text);
+        let tokens = lexer.tokenize();
+        LexedDocument { lexer, tokens }
+    }
+}
+
+/// Lexes and parses a source string into an AST.
+pub fn parse_source(source: &str) -> Result<AST, Diagnostic> {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize()?;
+    let mut parser = Parser::new(tokens);
+    parser.parse()
+}
+
+#[derive(Default)]
+pub struct Workspace {
+    root: RwLock<Option<PathBuf>>,
+    documents: RwLock<HashMap<Url, Document>>,
+    lexed: RwLock<HashMap<Url, LexedDocument>>,
+    index: RwLock<HashMap<PathBuf, FileIndex>>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_root(&self, root: Option<PathBuf>) {
+        *self.root.write().unwrap() = root;
+    }
+
+    pub fn root(&self) -> Option<PathBuf> {
+        self.root.read().unwrap().clone()
+    }
+
+    /// Returns a resolver rooted at the workspace's `src/` directory (or the root itself).
+    pub fn resolver(&self) -> ModuleResolver {
+        let root = self.root().unwrap_or_default();
+        let src = root.join("src");
+        ModuleResolver::new(if src.is_dir() { src } else { root })
+    }
+
+    pub fn open(&self, uri: Url, text: &str, version: Option<i64>) {
+        self.documents.write().unwrap().insert(uri.clone(), Document::new(text, version));
+        self.lexed.write().unwrap().insert(uri.clone(), LexedDocument::new(text));
+        self.reindex_open(&uri);
+    }
+
+    /// Applies incremental edits to an open document and refreshes its index entry.
+    /// Ranged edits relex only the tokens they touch.
+    pub fn change(&self, uri: &Url, changes: &[TextDocumentContentChangeEvent], version: Option<i64>) {
+        {
+            let mut documents = self.documents.write().unwrap();
+            let mut lexed_documents = self.lexed.write().unwrap();
+            let doc = documents
+                .entry(uri.clone())
+                .or_insert_with(|| Document::new("", version));
+            let lexed = lexed_documents.entry(uri.clone()).or_insert_with(|| LexedDocument::new(&doc.text()));
+            for change in changes {
+                let edit = change.range.map(|range| doc.byte_range(range));
+                doc.apply_change(change);
+                match edit {
+                    Some(range) => lexed.tokens = lexed.lexer.relex(range, &change.text),
+                    None => *lexed = LexedDocument::new(&change.text),
+                }
+            }
+            doc.version = version;
+        }
+        self.reindex_open(uri);
+    }
+
+    /// Closes a document; its on-disk contents become the indexed version again.
+    pub fn close(&self, uri: &Url) {
+        self.documents.write().unwrap().remove(uri);
+        self.lexed.write().unwrap().remove(uri);
+        if let Ok(path) = uri.to_file_path() {
+            self.index_file(&path);
+        }
+    }
+
+    pub fn document(&self, uri: &Url) -> Option<Document> {
+        self.documents.read().unwrap().get(uri).cloned()
+    }
+
+    pub fn open_uris(&self) -> Vec<Url> {
+        self.documents.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Returns the current text of a file, preferring an open buffer over the disk contents.
+    pub fn source_of(&self, path: &Path) -> Option<String> {
+        if let Ok(uri) = Url::from_file_path(path) {
+            if let Some(doc) = self.documents.read().unwrap().get(&uri) {
+                return Some(doc.text());
+            }
+        }
+        fs::read_to_string(path).ok()
+    }
+
+    fn reindex_open(&self, uri: &Url) {
+        let tokens = match self.lexed.read().unwrap().get(uri) {
+            Some(lexed) => lexed.tokens.clone(),
+            None => return,
+        };
+        if let Ok(path) = uri.to_file_path() {
+            self.index.write().unwrap().insert(path, FileIndex::from_tokens(tokens));
+        }
+    }
+
+    /// Indexes a file from disk, unless an open buffer for it takes precedence.
+    pub fn index_file(&self, path: &Path) {
+        if let Ok(uri) = Url::from_file_path(path) {
+            if self.documents.read().unwrap().contains_key(&uri) {
+                return;
+            }
+        }
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                self.index.write().unwrap().insert(path.to_path_buf(), FileIndex::from_source(&source));
+            }
+            Err(_) => self.remove_file(path),
+        }
+    }
+
+    pub fn remove_file(&self, path: &Path) {
+        self.index.write().unwrap().remove(path);
+    }
+
+    /// Walks the workspace root and indexes every `.gx` file found.
+    pub fn index_root(&self) -> usize {
+        let root = match self.root() {
+            Some(root) => root,
+            None => return 0,
+        };
+        let mut files = Vec::new();
+        collect_sources(&root, &mut files);
+        for file in &files {
+            self.index_file(file);
+        }
+        files.len()
+    }
+
+    /// Indexes the top-level items of every file that parses.
+    pub fn symbol_index(&self) -> SymbolIndex {
+        let mut symbols = SymbolIndex::default();
+        for (path, file) in self.index.read().unwrap().iter() {
+            if let Some(ast) = &file.ast {
+                symbols.add_file(path, ast);
+            }
+        }
+        symbols
+    }
+
+    /// Runs `f` with read access to the workspace index.
+    pub fn with_index<R>(&self, f: impl FnOnce(&HashMap<PathBuf, FileIndex>) -> R) -> R {
+        f(&self.index.read().unwrap())
+    }
+}
+
+/// Recursively collects `.gx` files, skipping hidden directories and build output.
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if path.is_dir() {
+            if name.starts_with('.') || name == "target" || name == "dist" || name == "node_modules" {
+                continue;
+            }
+            collect_sources(&path, files);
+        } else if path.extension().map_or(false, |ext| ext == "gx") {
+            files.push(path);
+        }
+    }
+}
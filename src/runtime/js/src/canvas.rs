@@ -0,0 +1,68 @@
+//! Canvas and animation-frame bindings shared by the std crate and generated code
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, Element, HtmlCanvasElement, HtmlImageElement};
+
+pub use web_sys::CanvasRenderingContext2d;
+
+/// Gets the 2D context of a `<canvas>` element.
+pub fn context_2d(canvas: &Element) -> Result<CanvasRenderingContext2d, JsValue> {
+    canvas
+        .dyn_ref::<HtmlCanvasElement>()
+        .ok_or_else(|| JsValue::from_str("element is not a <canvas>"))?
+        .get_context("2d")?
+        .ok_or_else(|| JsValue::from_str("2d context unavailable"))?
+        .dyn_into()
+}
+
+/// Draws an `<img>` or `<canvas>` element at the given position, optionally scaled.
+pub fn draw_image(
+    context: &CanvasRenderingContext2d,
+    image: &Element,
+    x: f64,
+    y: f64,
+    size: Option<(f64, f64)>,
+) -> Result<(), JsValue> {
+    match (image.dyn_ref::<HtmlImageElement>(), image.dyn_ref::<HtmlCanvasElement>(), size) {
+        (Some(img), _, None) => context.draw_image_with_html_image_element(img, x, y),
+        (Some(img), _, Some((w, h))) => context.draw_image_with_html_image_element_and_dw_and_dh(img, x, y, w, h),
+        (None, Some(canvas), None) => context.draw_image_with_html_canvas_element(canvas, x, y),
+        (None, Some(canvas), Some((w, h))) => {
+            context.draw_image_with_html_canvas_element_and_dw_and_dh(canvas, x, y, w, h)
+        }
+        (None, None, _) => Err(JsValue::from_str("drawImage needs an <img> or <canvas>")),
+    }
+}
+
+/// Calls `callback` with the frame timestamp before the next repaint.
+pub fn request_animation_frame(callback: Box<dyn FnOnce(f64)>) -> Result<i32, JsValue> {
+    let closure = Closure::once_into_js(callback);
+    window()
+        .ok_or_else(|| JsValue::from_str("no window available"))?
+        .request_animation_frame(closure.unchecked_ref())
+}
+
+/// Calls `callback` on every animation frame until it returns false.
+pub fn animation_loop(callback: Box<dyn FnMut(f64) -> bool>) -> Result<(), JsValue> {
+    let callback = Rc::new(RefCell::new(callback));
+    let frame: Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>> = Rc::new(RefCell::new(None));
+    let next = frame.clone();
+    *frame.borrow_mut() = Some(Closure::wrap(Box::new(move |timestamp: f64| {
+        if (callback.borrow_mut())(timestamp) {
+            if let (Some(w), Some(closure)) = (window(), next.borrow().as_ref()) {
+                let _ = w.request_animation_frame(closure.as_ref().unchecked_ref());
+            }
+        } else {
+            // Break the Rc cycle so the closure is dropped.
+            next.borrow_mut().take();
+        }
+    }) as Box<dyn FnMut(f64)>));
+    let first = frame.borrow();
+    window()
+        .ok_or_else(|| JsValue::from_str("no window available"))?
+        .request_animation_frame(first.as_ref().unwrap().as_ref().unchecked_ref())?;
+    Ok(())
+}
@@ -0,0 +1,42 @@
+//! Async clipboard bindings shared by the std crate and generated code
+//!
+//! `navigator.clipboard` is reached through reflection because web-sys only
+//! exposes it behind its unstable-API flag.
+
+use js_sys::{Function, Promise, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::window;
+
+fn clipboard() -> Result<JsValue, JsValue> {
+    let navigator = window().ok_or_else(|| JsValue::from_str("no window available"))?.navigator();
+    let clipboard = Reflect::get(&navigator, &JsValue::from_str("clipboard"))?;
+    if clipboard.is_undefined() {
+        return Err(JsValue::from_str("the clipboard API is unavailable (it needs a secure context)"));
+    }
+    Ok(clipboard)
+}
+
+fn call(method: &str, args: &[JsValue]) -> Result<Promise, JsValue> {
+    let clipboard = clipboard()?;
+    let function: Function = Reflect::get(&clipboard, &JsValue::from_str(method))?.dyn_into()?;
+    let result = match args {
+        [] => function.call0(&clipboard)?,
+        [arg] => function.call1(&clipboard, arg)?,
+        _ => return Err(JsValue::from_str("unsupported clipboard call")),
+    };
+    result.dyn_into()
+}
+
+/// Reads the clipboard as text with `navigator.clipboard.readText`.
+pub async fn read_text() -> Result<String, JsValue> {
+    let text = JsFuture::from(call("readText", &[])?).await?;
+    Ok(text.as_string().unwrap_or_default())
+}
+
+/// Replaces the clipboard contents with `navigator.clipboard.writeText`.
+pub async fn write_text(text: &str) -> Result<(), JsValue> {
+    JsFuture::from(call("writeText", &[JsValue::from_str(text)])?).await?;
+    Ok(())
+}
@@ -0,0 +1,24 @@
+//! Clock bindings shared by the std crate and generated code
+
+use web_sys::window;
+
+/// Milliseconds since the Unix epoch, from `Date.now()`.
+pub fn date_now() -> f64 {
+    js_sys::Date::now()
+}
+
+/// Monotonic milliseconds from `performance.now()`, falling back to `Date.now()`
+/// where no window is available (e.g. workers without a performance object).
+pub fn performance_now() -> f64 {
+    window()
+        .and_then(|w| w.performance())
+        .map(|p| p.now())
+        .unwrap_or_else(date_now)
+}
+
+/// The local UTC offset in minutes east of UTC at the given timestamp, from
+/// `Date.prototype.getTimezoneOffset` (which reports minutes west).
+pub fn timezone_offset_minutes(timestamp_ms: f64) -> i32 {
+    let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(timestamp_ms));
+    -(date.get_timezone_offset() as i32)
+}
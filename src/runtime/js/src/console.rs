@@ -0,0 +1,39 @@
+//! `console` bindings shared by the std crate and generated code
+
+use wasm_bindgen::JsValue;
+use web_sys::console;
+
+/// Writes a message with `console.log`.
+pub fn log(message: &str) {
+    console::log_1(&JsValue::from_str(message));
+}
+
+/// Writes a message with `console.warn`.
+pub fn warn(message: &str) {
+    console::warn_1(&JsValue::from_str(message));
+}
+
+/// Writes a message with `console.error`.
+pub fn error(message: &str) {
+    console::error_1(&JsValue::from_str(message));
+}
+
+/// Writes a message with `console.info`.
+pub fn info(message: &str) {
+    console::info_1(&JsValue::from_str(message));
+}
+
+/// Writes a message with `console.debug`.
+pub fn debug(message: &str) {
+    console::debug_1(&JsValue::from_str(message));
+}
+
+/// Starts an indented, labelled group with `console.group`.
+pub fn group(label: &str) {
+    console::group_1(&JsValue::from_str(label));
+}
+
+/// Ends the innermost group with `console.groupEnd`.
+pub fn group_end() {
+    console::group_end();
+}
@@ -0,0 +1,95 @@
+//! DOM helpers shared by the std crate and generated code
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, Document, HtmlElement, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement};
+
+pub use web_sys::{Element, Event};
+
+/// Returns the current document.
+pub fn document() -> Result<Document, JsValue> {
+    window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| JsValue::from_str("no document available"))
+}
+
+pub fn get_element_by_id(id: &str) -> Option<Element> {
+    document().ok()?.get_element_by_id(id)
+}
+
+pub fn query_selector(selector: &str) -> Result<Option<Element>, JsValue> {
+    document()?.query_selector(selector)
+}
+
+pub fn query_selector_all(selector: &str) -> Result<Vec<Element>, JsValue> {
+    let nodes = document()?.query_selector_all(selector)?;
+    Ok((0..nodes.length())
+        .filter_map(|i| nodes.item(i))
+        .filter_map(|node| node.dyn_into::<Element>().ok())
+        .collect())
+}
+
+pub fn create_element(tag: &str) -> Result<Element, JsValue> {
+    document()?.create_element(tag)
+}
+
+/// Returns the document body as an element.
+pub fn body() -> Option<Element> {
+    document().ok()?.body().map(Into::into)
+}
+
+pub fn append_child(parent: &Element, child: &Element) -> Result<(), JsValue> {
+    parent.append_child(child).map(|_| ())
+}
+
+/// Reads the `value` of an input, textarea or select element; None for other elements.
+pub fn value(element: &Element) -> Option<String> {
+    if let Some(input) = element.dyn_ref::<HtmlInputElement>() {
+        Some(input.value())
+    } else if let Some(textarea) = element.dyn_ref::<HtmlTextAreaElement>() {
+        Some(textarea.value())
+    } else {
+        element.dyn_ref::<HtmlSelectElement>().map(|select| select.value())
+    }
+}
+
+/// Sets the `value` of an input, textarea or select element; returns false for other elements.
+pub fn set_value(element: &Element, value: &str) -> bool {
+    if let Some(input) = element.dyn_ref::<HtmlInputElement>() {
+        input.set_value(value);
+    } else if let Some(textarea) = element.dyn_ref::<HtmlTextAreaElement>() {
+        textarea.set_value(value);
+    } else if let Some(select) = element.dyn_ref::<HtmlSelectElement>() {
+        select.set_value(value);
+    } else {
+        return false;
+    }
+    true
+}
+
+/// Attaches an event listener for the lifetime of the page.
+pub fn add_listener(element: &Element, event: &str, callback: Box<dyn FnMut(Event)>) -> Result<(), JsValue> {
+    let closure = Closure::wrap(callback);
+    element.add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())?;
+    closure.forget();
+    Ok(())
+}
+
+/// Sets an inline style property, e.g. `("background-color", "red")`.
+pub fn set_style_property(element: &Element, property: &str, value: &str) -> Result<(), JsValue> {
+    element
+        .dyn_ref::<HtmlElement>()
+        .ok_or_else(|| JsValue::from_str("element has no inline style"))?
+        .style()
+        .set_property(property, value)
+}
+
+/// Appends a `<style>` element with the given CSS to the document head.
+pub fn inject_style(css: &str) -> Result<(), JsValue> {
+    let document = document()?;
+    let style = document.create_element("style")?;
+    style.set_text_content(Some(css));
+    let head = document.head().ok_or_else(|| JsValue::from_str("document has no <head>"))?;
+    head.append_child(&style)?;
+    Ok(())
+}
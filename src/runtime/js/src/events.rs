@@ -0,0 +1,12 @@
+//! Event helpers shared by the std crate and generated code
+
+use wasm_bindgen::JsCast;
+use web_sys::Element;
+
+pub use web_sys::{Event, KeyboardEvent, MouseEvent};
+
+/// The `value` of the event's target, for events fired by form controls.
+pub fn target_value(event: &Event) -> Option<String> {
+    let target = event.target()?.dyn_into::<Element>().ok()?;
+    crate::dom::value(&target)
+}
@@ -0,0 +1,55 @@
+//! `fetch` bindings shared by the std crate and generated code
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Request, RequestInit, Response};
+
+/// A completed fetch with the body already read as text.
+#[derive(Debug, Clone)]
+pub struct FetchResponse {
+    pub status: u16,
+    /// The browser joins the values of a repeated header into one.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Performs a request through the browser's `fetch` and waits for the full body.
+pub async fn fetch(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: Option<&str>,
+) -> Result<FetchResponse, JsValue> {
+    let init = RequestInit::new();
+    init.set_method(method);
+    if let Some(body) = body {
+        init.set_body(&JsValue::from_str(body));
+    }
+    let request = Request::new_with_str_and_init(url, &init)?;
+    for (name, value) in headers {
+        request.headers().set(name, value)?;
+    }
+
+    let window = window().ok_or_else(|| JsValue::from_str("fetch requires a window"))?;
+    let response: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await?
+        .dyn_into()?;
+
+    let mut response_headers = Vec::new();
+    if let Some(entries) = js_sys::try_iter(&response.headers())? {
+        for entry in entries {
+            let pair: js_sys::Array = entry?.dyn_into()?;
+            if let (Some(name), Some(value)) = (pair.get(0).as_string(), pair.get(1).as_string()) {
+                response_headers.push((name, value));
+            }
+        }
+    }
+    let body = JsFuture::from(response.text()?).await?.as_string().unwrap_or_default();
+
+    Ok(FetchResponse {
+        status: response.status(),
+        headers: response_headers,
+        body,
+    })
+}
@@ -0,0 +1,104 @@
+//! Geolocation bindings shared by the std crate and generated code
+//!
+//! Positions are read through reflection so the bindings do not depend on the
+//! web-sys name of the position types, which changed between releases.
+
+use js_sys::{Promise, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Geolocation};
+
+/// A position fix: coordinates in degrees, distances in meters, speed in m/s.
+#[derive(Debug, Clone)]
+pub struct GeoPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub accuracy: f64,
+    pub altitude: Option<f64>,
+    pub heading: Option<f64>,
+    pub speed: Option<f64>,
+    pub timestamp: f64,
+}
+
+/// A `GeolocationPositionError`: code 1 permission denied, 2 unavailable, 3 timeout.
+#[derive(Debug, Clone)]
+pub struct GeoError {
+    pub code: u16,
+    pub message: String,
+}
+
+fn geolocation() -> Result<Geolocation, GeoError> {
+    window()
+        .ok_or_else(|| JsValue::from_str("no window available"))
+        .and_then(|w| w.navigator().geolocation())
+        .map_err(|e| GeoError {
+            code: 2,
+            message: e.as_string().unwrap_or_else(|| "geolocation is unavailable".to_string()),
+        })
+}
+
+fn number(object: &JsValue, key: &str) -> Option<f64> {
+    Reflect::get(object, &JsValue::from_str(key)).ok()?.as_f64()
+}
+
+fn to_position(value: &JsValue) -> GeoPosition {
+    let coords = Reflect::get(value, &JsValue::from_str("coords")).unwrap_or(JsValue::UNDEFINED);
+    GeoPosition {
+        latitude: number(&coords, "latitude").unwrap_or(0.0),
+        longitude: number(&coords, "longitude").unwrap_or(0.0),
+        accuracy: number(&coords, "accuracy").unwrap_or(0.0),
+        altitude: number(&coords, "altitude"),
+        heading: number(&coords, "heading"),
+        speed: number(&coords, "speed"),
+        timestamp: number(value, "timestamp").unwrap_or(0.0),
+    }
+}
+
+fn to_error(value: &JsValue) -> GeoError {
+    GeoError {
+        code: number(value, "code").unwrap_or(2.0) as u16,
+        message: Reflect::get(value, &JsValue::from_str("message"))
+            .ok()
+            .and_then(|m| m.as_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Resolves with the device's current position.
+pub async fn current_position() -> Result<GeoPosition, GeoError> {
+    let geolocation = geolocation()?;
+    let mut started = Ok(());
+    let promise = Promise::new(&mut |resolve, reject| {
+        started = geolocation.get_current_position_with_error_callback(&resolve, Some(&reject));
+    });
+    started.map_err(|e| to_error(&e))?;
+    JsFuture::from(promise)
+        .await
+        .map(|position| to_position(&position))
+        .map_err(|e| to_error(&e))
+}
+
+/// Calls `callback` with every position update or error, returning the watch id.
+pub fn watch_position(callback: Box<dyn FnMut(Result<GeoPosition, GeoError>)>) -> Result<i32, GeoError> {
+    let callback = std::rc::Rc::new(std::cell::RefCell::new(callback));
+    let on_error = callback.clone();
+    let success = Closure::wrap(Box::new(move |position: JsValue| {
+        (callback.borrow_mut())(Ok(to_position(&position)));
+    }) as Box<dyn FnMut(JsValue)>);
+    let error = Closure::wrap(Box::new(move |error: JsValue| {
+        (on_error.borrow_mut())(Err(to_error(&error)));
+    }) as Box<dyn FnMut(JsValue)>);
+    let id = geolocation()?
+        .watch_position_with_error_callback(success.as_ref().unchecked_ref(), Some(error.as_ref().unchecked_ref()))
+        .map_err(|e| to_error(&e))?;
+    success.forget();
+    error.forget();
+    Ok(id)
+}
+
+pub fn clear_watch(id: i32) {
+    if let Ok(geolocation) = geolocation() {
+        geolocation.clear_watch(id);
+    }
+}
@@ -0,0 +1,37 @@
+//! History and location bindings shared by the std crate and generated code
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{window, History, Location, Window};
+
+fn current_window() -> Result<Window, JsValue> {
+    window().ok_or_else(|| JsValue::from_str("no window available"))
+}
+
+pub fn history() -> Result<History, JsValue> {
+    current_window()?.history()
+}
+
+pub fn location() -> Result<Location, JsValue> {
+    Ok(current_window()?.location())
+}
+
+/// Pushes a new history entry with a string state.
+pub fn push_state(state: &str, url: &str) -> Result<(), JsValue> {
+    history()?.push_state_with_url(&JsValue::from_str(state), "", Some(url))
+}
+
+/// Replaces the current history entry with a string state.
+pub fn replace_state(state: &str, url: &str) -> Result<(), JsValue> {
+    history()?.replace_state_with_url(&JsValue::from_str(state), "", Some(url))
+}
+
+/// Calls `callback` with the entry's string state (if any) on every `popstate`.
+pub fn on_popstate(mut callback: Box<dyn FnMut(Option<String>)>) -> Result<(), JsValue> {
+    let closure = Closure::wrap(Box::new(move |event: web_sys::PopStateEvent| {
+        callback(event.state().as_string());
+    }) as Box<dyn FnMut(_)>);
+    current_window()?.add_event_listener_with_callback("popstate", closure.as_ref().unchecked_ref())?;
+    closure.forget();
+    Ok(())
+}
@@ -7,6 +7,20 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{window, Document, Element, Event, HtmlElement};
 
+pub mod canvas;
+pub mod clipboard;
+pub mod clock;
+pub mod console;
+pub mod dom;
+pub mod events;
+pub mod fetch;
+pub mod geolocation;
+pub mod history;
+pub mod notification;
+pub mod storage;
+pub mod task;
+pub mod websocket;
+
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
 #[cfg(feature = "wee_alloc")]
@@ -0,0 +1,37 @@
+//! Notification bindings shared by the std crate and generated code
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{NotificationOptions, NotificationPermission};
+
+pub use web_sys::Notification;
+
+/// The current permission: `"granted"`, `"denied"` or `"default"`.
+pub fn permission() -> &'static str {
+    match Notification::permission() {
+        NotificationPermission::Granted => "granted",
+        NotificationPermission::Denied => "denied",
+        _ => "default",
+    }
+}
+
+/// Asks the user for permission, resolving to the resulting permission string.
+pub async fn request_permission() -> Result<String, JsValue> {
+    let result = JsFuture::from(Notification::request_permission()?).await?;
+    Ok(result.as_string().unwrap_or_else(|| "default".to_string()))
+}
+
+/// Shows a notification; empty `body`, `icon` or `tag` are left unset.
+pub fn show(title: &str, body: &str, icon: &str, tag: &str) -> Result<Notification, JsValue> {
+    let options = NotificationOptions::new();
+    if !body.is_empty() {
+        options.set_body(body);
+    }
+    if !icon.is_empty() {
+        options.set_icon(icon);
+    }
+    if !tag.is_empty() {
+        options.set_tag(tag);
+    }
+    Notification::new_with_options(title, &options)
+}
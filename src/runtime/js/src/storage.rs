@@ -0,0 +1,34 @@
+//! Web Storage bindings shared by the std crate and generated code
+
+use wasm_bindgen::prelude::*;
+use web_sys::window;
+
+pub use web_sys::Storage;
+
+/// Returns `window.localStorage`, or an error where storage is unavailable or blocked.
+pub fn local_storage() -> Result<Storage, JsValue> {
+    window()
+        .ok_or_else(|| JsValue::from_str("no window available"))?
+        .local_storage()?
+        .ok_or_else(|| JsValue::from_str("localStorage is unavailable"))
+}
+
+/// Returns `window.sessionStorage`, or an error where storage is unavailable or blocked.
+pub fn session_storage() -> Result<Storage, JsValue> {
+    window()
+        .ok_or_else(|| JsValue::from_str("no window available"))?
+        .session_storage()?
+        .ok_or_else(|| JsValue::from_str("sessionStorage is unavailable"))
+}
+
+/// Lists the keys of a storage area in index order.
+pub fn keys(storage: &Storage) -> Result<Vec<String>, JsValue> {
+    let len = storage.length()?;
+    let mut keys = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        if let Some(key) = storage.key(i)? {
+            keys.push(key);
+        }
+    }
+    Ok(keys)
+}
@@ -0,0 +1,23 @@
+//! Task scheduling bindings shared by the std crate and generated code
+
+use std::future::Future;
+use std::pin::Pin;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+/// Runs a future to completion on the JS microtask queue.
+pub fn spawn_local(future: Pin<Box<dyn Future<Output = ()>>>) {
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Resolves after `ms` milliseconds, using `setTimeout`.
+pub async fn sleep(ms: i32) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or_else(|| JsValue::from_str("no window available"))?;
+    let mut scheduled = Ok(0);
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        scheduled = window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms);
+    });
+    scheduled?;
+    JsFuture::from(promise).await?;
+    Ok(())
+}
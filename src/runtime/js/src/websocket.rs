@@ -0,0 +1,55 @@
+//! WebSocket bindings shared by the std crate and generated code
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{BinaryType, CloseEvent, MessageEvent};
+
+pub use web_sys::WebSocket;
+
+/// A received message: text frames arrive as strings, binary frames as bytes.
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// Opens a connection; binary messages are delivered as `ArrayBuffer`s.
+pub fn connect(url: &str) -> Result<WebSocket, JsValue> {
+    let socket = WebSocket::new(url)?;
+    socket.set_binary_type(BinaryType::Arraybuffer);
+    Ok(socket)
+}
+
+pub fn on_message(socket: &WebSocket, mut callback: Box<dyn FnMut(Message)>) {
+    let closure = Closure::wrap(Box::new(move |event: MessageEvent| {
+        let data = event.data();
+        if let Some(text) = data.as_string() {
+            callback(Message::Text(text));
+        } else if let Some(buffer) = data.dyn_ref::<js_sys::ArrayBuffer>() {
+            callback(Message::Binary(js_sys::Uint8Array::new(buffer).to_vec()));
+        }
+    }) as Box<dyn FnMut(_)>);
+    socket.set_onmessage(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+}
+
+pub fn on_open(socket: &WebSocket, mut callback: Box<dyn FnMut()>) {
+    let closure = Closure::wrap(Box::new(move |_: web_sys::Event| callback()) as Box<dyn FnMut(_)>);
+    socket.set_onopen(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+}
+
+/// Calls `callback` with the close code, reason and whether the close was clean.
+pub fn on_close(socket: &WebSocket, mut callback: Box<dyn FnMut(u16, String, bool)>) {
+    let closure = Closure::wrap(Box::new(move |event: CloseEvent| {
+        callback(event.code(), event.reason(), event.was_clean());
+    }) as Box<dyn FnMut(_)>);
+    socket.set_onclose(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+}
+
+/// The browser reports no details for WebSocket errors, so `callback` takes none.
+pub fn on_error(socket: &WebSocket, mut callback: Box<dyn FnMut()>) {
+    let closure = Closure::wrap(Box::new(move |_: web_sys::Event| callback()) as Box<dyn FnMut(_)>);
+    socket.set_onerror(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+}
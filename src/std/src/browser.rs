@@ -1,30 +1,27 @@
 //! Standard library: Browser APIs for Gigli
 
-/// Provides DOM manipulation functions for GigliOptix programs targeting the web.
-pub mod dom {
-    /// Sets the inner HTML of an element by id.
-    pub fn set_inner_html(_id: &str, _html: &str) {
-        // TODO: Implement via WASM/JS interop
-    }
+pub mod canvas;
+pub mod clipboard;
+pub mod console;
+pub mod dom;
+pub mod events;
+pub mod fetch;
+pub mod geolocation;
+pub mod history;
+pub mod location;
+pub mod notification;
+pub mod storage;
+pub mod websocket;
 
-    /// Gets the value of an input element by id.
-    pub fn get_input_value(_id: &str) -> String {
-        // TODO: Implement via WASM/JS interop
-        String::new()
-    }
+pub use fetch::fetch;
 
-    /// Adds an event listener to an element by id.
-    pub fn add_event_listener(_id: &str, _event: &str, _callback: fn()) {
-        // TODO: Implement via WASM/JS interop
-    }
-}
+pub use crate::css;
 
-/// Provides CSS manipulation functions.
-pub mod css {
-    /// Sets a CSS property on an element by id.
-    pub fn set_property(_id: &str, _property: &str, _value: &str) {
-        // TODO: Implement via WASM/JS interop
-    }
+/// Describes an error a browser API threw: its message if it threw a string,
+/// its debug form otherwise.
+#[cfg(feature = "wasm")]
+pub(crate) fn js_error(e: wasm_bindgen::JsValue) -> String {
+    e.as_string().unwrap_or_else(|| format!("{:?}", e))
 }
 
 /// Provides browser window and document APIs.
@@ -0,0 +1,386 @@
+//! 2D canvas drawing for Gigli programs.
+//!
+//! Drawing calls are described as `DrawOp` values and applied to the context by
+//! the runtime's canvas bindings. Native targets have no canvas, so a `Canvas`
+//! can only be obtained on the web.
+
+use super::dom::{self, Element};
+
+/// A single drawing operation on a 2D context.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawOp {
+    FillRect { x: f64, y: f64, width: f64, height: f64 },
+    StrokeRect { x: f64, y: f64, width: f64, height: f64 },
+    ClearRect { x: f64, y: f64, width: f64, height: f64 },
+    BeginPath,
+    ClosePath,
+    MoveTo { x: f64, y: f64 },
+    LineTo { x: f64, y: f64 },
+    Rect { x: f64, y: f64, width: f64, height: f64 },
+    Arc { x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64 },
+    Fill,
+    Stroke,
+    FillText { text: String, x: f64, y: f64 },
+    StrokeText { text: String, x: f64, y: f64 },
+    Translate { x: f64, y: f64 },
+    Rotate { angle: f64 },
+    Scale { x: f64, y: f64 },
+    SetTransform { a: f64, b: f64, c: f64, d: f64, e: f64, f: f64 },
+    ResetTransform,
+    Save,
+    Restore,
+    FillStyle(String),
+    StrokeStyle(String),
+    LineWidth(f64),
+    Font(String),
+    GlobalAlpha(f64),
+}
+
+/// A `<canvas>` element with its 2D rendering context.
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    element: Element,
+    context: backend::Context,
+}
+
+impl Canvas {
+    /// Wraps a `<canvas>` element.
+    pub fn from_element(element: &Element) -> Result<Canvas, String> {
+        let context = backend::context_2d(element)?;
+        Ok(Canvas {
+            element: element.clone(),
+            context,
+        })
+    }
+
+    /// Finds a `<canvas>` element by id.
+    pub fn by_id(id: &str) -> Result<Canvas, String> {
+        let element = dom::get_element_by_id(id).ok_or_else(|| format!("No element with id '{}'", id))?;
+        Canvas::from_element(&element)
+    }
+
+    pub fn element(&self) -> &Element {
+        &self.element
+    }
+
+    /// The canvas width in pixels.
+    pub fn width(&self) -> f64 {
+        backend::size(&self.context).0
+    }
+
+    /// The canvas height in pixels.
+    pub fn height(&self) -> f64 {
+        backend::size(&self.context).1
+    }
+
+    /// Applies a drawing operation.
+    pub fn draw(&self, op: DrawOp) {
+        backend::apply(&self.context, op);
+    }
+
+    pub fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.draw(DrawOp::FillRect { x, y, width, height });
+    }
+
+    pub fn stroke_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.draw(DrawOp::StrokeRect { x, y, width, height });
+    }
+
+    pub fn clear_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.draw(DrawOp::ClearRect { x, y, width, height });
+    }
+
+    /// Clears the whole canvas, ignoring the current transform.
+    pub fn clear(&self) {
+        let (width, height) = (self.width(), self.height());
+        self.save();
+        self.draw(DrawOp::ResetTransform);
+        self.clear_rect(0.0, 0.0, width, height);
+        self.restore();
+    }
+
+    pub fn begin_path(&self) {
+        self.draw(DrawOp::BeginPath);
+    }
+
+    pub fn close_path(&self) {
+        self.draw(DrawOp::ClosePath);
+    }
+
+    pub fn move_to(&self, x: f64, y: f64) {
+        self.draw(DrawOp::MoveTo { x, y });
+    }
+
+    pub fn line_to(&self, x: f64, y: f64) {
+        self.draw(DrawOp::LineTo { x, y });
+    }
+
+    pub fn rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.draw(DrawOp::Rect { x, y, width, height });
+    }
+
+    /// Adds a clockwise arc to the path; angles are in radians.
+    pub fn arc(&self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        self.draw(DrawOp::Arc { x, y, radius, start_angle, end_angle });
+    }
+
+    pub fn fill(&self) {
+        self.draw(DrawOp::Fill);
+    }
+
+    pub fn stroke(&self) {
+        self.draw(DrawOp::Stroke);
+    }
+
+    pub fn fill_text(&self, text: &str, x: f64, y: f64) {
+        self.draw(DrawOp::FillText { text: text.to_string(), x, y });
+    }
+
+    pub fn stroke_text(&self, text: &str, x: f64, y: f64) {
+        self.draw(DrawOp::StrokeText { text: text.to_string(), x, y });
+    }
+
+    /// The width in pixels of `text` in the current font.
+    pub fn measure_text(&self, text: &str) -> f64 {
+        backend::measure_text(&self.context, text)
+    }
+
+    /// Draws an `<img>` or `<canvas>` element at its natural size.
+    pub fn draw_image(&self, image: &Element, x: f64, y: f64) -> Result<(), String> {
+        backend::draw_image(&self.context, image, x, y, None)
+    }
+
+    /// Draws an `<img>` or `<canvas>` element scaled to the given size.
+    pub fn draw_image_scaled(&self, image: &Element, x: f64, y: f64, width: f64, height: f64) -> Result<(), String> {
+        backend::draw_image(&self.context, image, x, y, Some((width, height)))
+    }
+
+    pub fn translate(&self, x: f64, y: f64) {
+        self.draw(DrawOp::Translate { x, y });
+    }
+
+    /// Rotates by `angle` radians clockwise.
+    pub fn rotate(&self, angle: f64) {
+        self.draw(DrawOp::Rotate { angle });
+    }
+
+    pub fn scale(&self, x: f64, y: f64) {
+        self.draw(DrawOp::Scale { x, y });
+    }
+
+    pub fn set_transform(&self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        self.draw(DrawOp::SetTransform { a, b, c, d, e, f });
+    }
+
+    pub fn reset_transform(&self) {
+        self.draw(DrawOp::ResetTransform);
+    }
+
+    /// Pushes the current styles and transform.
+    pub fn save(&self) {
+        self.draw(DrawOp::Save);
+    }
+
+    /// Pops the styles and transform saved by `save`.
+    pub fn restore(&self) {
+        self.draw(DrawOp::Restore);
+    }
+
+    /// Sets the fill color, e.g. `#ff0000` or `rgba(0, 0, 0, 0.5)`.
+    pub fn set_fill_style(&self, color: &str) {
+        self.draw(DrawOp::FillStyle(color.to_string()));
+    }
+
+    pub fn set_stroke_style(&self, color: &str) {
+        self.draw(DrawOp::StrokeStyle(color.to_string()));
+    }
+
+    pub fn set_line_width(&self, width: f64) {
+        self.draw(DrawOp::LineWidth(width));
+    }
+
+    /// Sets the font as a CSS font shorthand, e.g. `16px sans-serif`.
+    pub fn set_font(&self, font: &str) {
+        self.draw(DrawOp::Font(font.to_string()));
+    }
+
+    pub fn set_global_alpha(&self, alpha: f64) {
+        self.draw(DrawOp::GlobalAlpha(alpha));
+    }
+}
+
+/// Calls `handler` once with the frame timestamp (milliseconds) before the next repaint.
+pub fn request_animation_frame<F>(handler: F) -> Result<(), String>
+where
+    F: FnOnce(f64) + 'static,
+{
+    backend::request_animation_frame(Box::new(handler))
+}
+
+/// Calls `handler` with the frame timestamp on every animation frame until it returns false.
+pub fn animation_loop<F>(handler: F) -> Result<(), String>
+where
+    F: FnMut(f64) -> bool + 'static,
+{
+    backend::animation_loop(Box::new(handler))
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use super::DrawOp;
+    use crate::browser::dom::Element;
+    use crate::browser::js_error;
+    use gigli_runtime_js::canvas;
+    use wasm_bindgen::JsValue;
+
+    pub type Context = canvas::CanvasRenderingContext2d;
+
+    pub fn context_2d(element: &Element) -> Result<Context, String> {
+        canvas::context_2d(element.handle()).map_err(js_error)
+    }
+
+    pub fn size(context: &Context) -> (f64, f64) {
+        context
+            .canvas()
+            .map(|c| (c.width() as f64, c.height() as f64))
+            .unwrap_or((0.0, 0.0))
+    }
+
+    pub fn apply(ctx: &Context, op: DrawOp) {
+        // The fallible calls only reject non-finite arguments; like the browser, ignore them.
+        let _ = match op {
+            DrawOp::FillRect { x, y, width, height } => {
+                ctx.fill_rect(x, y, width, height);
+                Ok(())
+            }
+            DrawOp::StrokeRect { x, y, width, height } => {
+                ctx.stroke_rect(x, y, width, height);
+                Ok(())
+            }
+            DrawOp::ClearRect { x, y, width, height } => {
+                ctx.clear_rect(x, y, width, height);
+                Ok(())
+            }
+            DrawOp::BeginPath => {
+                ctx.begin_path();
+                Ok(())
+            }
+            DrawOp::ClosePath => {
+                ctx.close_path();
+                Ok(())
+            }
+            DrawOp::MoveTo { x, y } => {
+                ctx.move_to(x, y);
+                Ok(())
+            }
+            DrawOp::LineTo { x, y } => {
+                ctx.line_to(x, y);
+                Ok(())
+            }
+            DrawOp::Rect { x, y, width, height } => {
+                ctx.rect(x, y, width, height);
+                Ok(())
+            }
+            DrawOp::Arc { x, y, radius, start_angle, end_angle } => ctx.arc(x, y, radius, start_angle, end_angle),
+            DrawOp::Fill => {
+                ctx.fill();
+                Ok(())
+            }
+            DrawOp::Stroke => {
+                ctx.stroke();
+                Ok(())
+            }
+            DrawOp::FillText { text, x, y } => ctx.fill_text(&text, x, y),
+            DrawOp::StrokeText { text, x, y } => ctx.stroke_text(&text, x, y),
+            DrawOp::Translate { x, y } => ctx.translate(x, y),
+            DrawOp::Rotate { angle } => ctx.rotate(angle),
+            DrawOp::Scale { x, y } => ctx.scale(x, y),
+            DrawOp::SetTransform { a, b, c, d, e, f } => ctx.set_transform(a, b, c, d, e, f),
+            DrawOp::ResetTransform => ctx.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0),
+            DrawOp::Save => {
+                ctx.save();
+                Ok(())
+            }
+            DrawOp::Restore => {
+                ctx.restore();
+                Ok(())
+            }
+            DrawOp::FillStyle(color) => {
+                ctx.set_fill_style(&JsValue::from_str(&color));
+                Ok(())
+            }
+            DrawOp::StrokeStyle(color) => {
+                ctx.set_stroke_style(&JsValue::from_str(&color));
+                Ok(())
+            }
+            DrawOp::LineWidth(width) => {
+                ctx.set_line_width(width);
+                Ok(())
+            }
+            DrawOp::Font(font) => {
+                ctx.set_font(&font);
+                Ok(())
+            }
+            DrawOp::GlobalAlpha(alpha) => {
+                ctx.set_global_alpha(alpha);
+                Ok(())
+            }
+        };
+    }
+
+    pub fn measure_text(ctx: &Context, text: &str) -> f64 {
+        ctx.measure_text(text).map(|m| m.width()).unwrap_or(0.0)
+    }
+
+    pub fn draw_image(ctx: &Context, image: &Element, x: f64, y: f64, size: Option<(f64, f64)>) -> Result<(), String> {
+        canvas::draw_image(ctx, image.handle(), x, y, size).map_err(js_error)
+    }
+
+    pub fn request_animation_frame(handler: Box<dyn FnOnce(f64)>) -> Result<(), String> {
+        canvas::request_animation_frame(handler).map(|_| ()).map_err(js_error)
+    }
+
+    pub fn animation_loop(handler: Box<dyn FnMut(f64) -> bool>) -> Result<(), String> {
+        canvas::animation_loop(handler).map_err(js_error)
+    }
+}
+
+/// No canvas exists off the web, so `Context` is uninhabited.
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use super::DrawOp;
+    use crate::browser::dom::Element;
+
+    #[derive(Debug, Clone)]
+    pub enum Context {}
+
+    const NO_CANVAS: &str = "Canvas is only available on the web target";
+
+    pub fn context_2d(element: &Element) -> Result<Context, String> {
+        match *element.handle() {}
+    }
+
+    pub fn size(context: &Context) -> (f64, f64) {
+        match *context {}
+    }
+
+    pub fn apply(context: &Context, _op: DrawOp) {
+        match *context {}
+    }
+
+    pub fn measure_text(context: &Context, _text: &str) -> f64 {
+        match *context {}
+    }
+
+    pub fn draw_image(context: &Context, _image: &Element, _x: f64, _y: f64, _size: Option<(f64, f64)>) -> Result<(), String> {
+        match *context {}
+    }
+
+    pub fn request_animation_frame(_handler: Box<dyn FnOnce(f64)>) -> Result<(), String> {
+        Err(NO_CANVAS.to_string())
+    }
+
+    pub fn animation_loop(_handler: Box<dyn FnMut(f64) -> bool>) -> Result<(), String> {
+        Err(NO_CANVAS.to_string())
+    }
+}
@@ -0,0 +1,46 @@
+//! Clipboard text access for Gigli programs.
+//!
+//! On the web this is the async clipboard API, which needs a secure context and
+//! usually a user gesture. Native targets use an in-memory clipboard.
+
+/// Reads the clipboard as text.
+pub async fn read_text() -> Result<String, String> {
+    backend::read_text().await
+}
+
+/// Replaces the clipboard contents with `text`.
+pub async fn write_text(text: &str) -> Result<(), String> {
+    backend::write_text(text).await
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use crate::browser::js_error;
+    use gigli_runtime_js::clipboard;
+
+    pub async fn read_text() -> Result<String, String> {
+        clipboard::read_text().await.map_err(js_error)
+    }
+
+    pub async fn write_text(text: &str) -> Result<(), String> {
+        clipboard::write_text(text).await.map_err(js_error)
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use std::cell::RefCell;
+
+    thread_local! {
+        static CLIPBOARD: RefCell<String> = RefCell::new(String::new());
+    }
+
+    pub async fn read_text() -> Result<String, String> {
+        Ok(CLIPBOARD.with(|c| c.borrow().clone()))
+    }
+
+    pub async fn write_text(text: &str) -> Result<(), String> {
+        CLIPBOARD.with(|c| *c.borrow_mut() = text.to_string());
+        Ok(())
+    }
+}
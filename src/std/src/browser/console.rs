@@ -0,0 +1,184 @@
+//! Levelled console logging for Gigli programs.
+//!
+//! Messages go to `console.*` on the web and to stderr on native targets, where
+//! groups are shown by indentation. Messages below the minimum level are dropped.
+
+use crate::time::time::{Duration, Instant};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Message severity, from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+struct State {
+    min_level: Level,
+    timers: HashMap<String, Instant>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State {
+        min_level: Level::Debug,
+        timers: HashMap::new(),
+    });
+}
+
+/// Drops messages below `level`. Everything is shown by default.
+pub fn set_level(level: Level) {
+    STATE.with(|s| s.borrow_mut().min_level = level);
+}
+
+pub fn level() -> Level {
+    STATE.with(|s| s.borrow().min_level)
+}
+
+/// Writes a message at the given level.
+pub fn write(level: Level, message: &str) {
+    if level >= self::level() {
+        backend::write(level, message);
+    }
+}
+
+/// Writes a plain message, at info level.
+pub fn log(message: &str) {
+    write(Level::Info, message);
+}
+
+pub fn info(message: &str) {
+    write(Level::Info, message);
+}
+
+pub fn warn(message: &str) {
+    write(Level::Warn, message);
+}
+
+pub fn error(message: &str) {
+    write(Level::Error, message);
+}
+
+pub fn debug(message: &str) {
+    write(Level::Debug, message);
+}
+
+/// Logs a value with its pretty-printed `Debug` representation.
+pub fn dir<T: fmt::Debug>(value: &T) {
+    log(&format_value(value));
+}
+
+/// Logs a value as pretty-printed JSON.
+pub fn json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(text) => log(&text),
+        Err(e) => error(&format!("console::json: {}", e)),
+    }
+}
+
+/// Formats a value the way `dir` logs it: multi-line `Debug` output.
+pub fn format_value<T: fmt::Debug>(value: &T) -> String {
+    format!("{:#?}", value)
+}
+
+/// Starts a labelled group; following messages are nested until `group_end`.
+pub fn group(label: &str) {
+    backend::group(label);
+}
+
+/// Ends the innermost group.
+pub fn group_end() {
+    backend::group_end();
+}
+
+/// Starts a timer under `label`, restarting it if it already runs.
+pub fn time(label: &str) {
+    STATE.with(|s| s.borrow_mut().timers.insert(label.to_string(), Instant::now()));
+}
+
+/// Stops the timer under `label`, logging and returning its elapsed time.
+pub fn time_end(label: &str) -> Option<Duration> {
+    let start = STATE.with(|s| s.borrow_mut().timers.remove(label));
+    match start {
+        Some(start) => {
+            let elapsed = start.elapsed();
+            log(&format!("{}: {}", label, elapsed));
+            Some(elapsed)
+        }
+        None => {
+            warn(&format!("Timer '{}' does not exist", label));
+            None
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use super::Level;
+    use gigli_runtime_js::console;
+
+    pub fn write(level: Level, message: &str) {
+        match level {
+            Level::Debug => console::debug(message),
+            Level::Info => console::info(message),
+            Level::Warn => console::warn(message),
+            Level::Error => console::error(message),
+        }
+    }
+
+    pub fn group(label: &str) {
+        console::group(label);
+    }
+
+    pub fn group_end() {
+        console::group_end();
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use super::Level;
+    use std::cell::Cell;
+
+    thread_local! {
+        static DEPTH: Cell<usize> = Cell::new(0);
+    }
+
+    fn indent() -> String {
+        "  ".repeat(DEPTH.with(Cell::get))
+    }
+
+    /// Info messages are written bare; other levels are tagged.
+    pub fn write(level: Level, message: &str) {
+        let indent = indent();
+        let tag = if level == Level::Info { String::new() } else { format!("[{}] ", level) };
+        for line in message.split('\n') {
+            eprintln!("{}{}{}", indent, tag, line);
+        }
+    }
+
+    pub fn group(label: &str) {
+        eprintln!("{}{}", indent(), label);
+        DEPTH.with(|d| d.set(d.get() + 1));
+    }
+
+    pub fn group_end() {
+        DEPTH.with(|d| d.set(d.get().saturating_sub(1)));
+    }
+}
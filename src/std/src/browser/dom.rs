@@ -0,0 +1,410 @@
+//! DOM manipulation for GigliOptix programs targeting the web.
+//!
+//! Everything goes through `gigli_runtime_js::dom`, the same layer generated code
+//! uses. Native targets have no document: queries find nothing and updates by id
+//! are no-ops, so shared code still compiles and runs.
+
+use crate::list::List;
+use crate::reactive::{Effect, Signal};
+use std::fmt;
+
+/// A DOM element.
+#[derive(Debug, Clone)]
+pub struct Element {
+    handle: backend::Handle,
+}
+
+impl Element {
+    /// The lower-case tag name, e.g. `div`.
+    pub fn tag_name(&self) -> String {
+        backend::tag_name(&self.handle)
+    }
+
+    pub fn id(&self) -> String {
+        self.get_attribute("id").unwrap_or_default()
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<String> {
+        backend::get_attribute(&self.handle, name)
+    }
+
+    pub fn set_attribute(&self, name: &str, value: &str) -> Result<(), String> {
+        backend::set_attribute(&self.handle, name, value)
+    }
+
+    pub fn remove_attribute(&self, name: &str) -> Result<(), String> {
+        backend::remove_attribute(&self.handle, name)
+    }
+
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.get_attribute(name).is_some()
+    }
+
+    pub fn add_class(&self, class: &str) -> Result<(), String> {
+        backend::add_class(&self.handle, class)
+    }
+
+    pub fn remove_class(&self, class: &str) -> Result<(), String> {
+        backend::remove_class(&self.handle, class)
+    }
+
+    /// Toggles a class, returning true if it is now present.
+    pub fn toggle_class(&self, class: &str) -> Result<bool, String> {
+        backend::toggle_class(&self.handle, class)
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        backend::has_class(&self.handle, class)
+    }
+
+    pub fn text_content(&self) -> String {
+        backend::text_content(&self.handle)
+    }
+
+    pub fn set_text_content(&self, text: &str) {
+        backend::set_text_content(&self.handle, text);
+    }
+
+    pub fn inner_html(&self) -> String {
+        backend::inner_html(&self.handle)
+    }
+
+    pub fn set_inner_html(&self, html: &str) {
+        backend::set_inner_html(&self.handle, html);
+    }
+
+    /// The `value` of an input, textarea or select element; None for other elements.
+    pub fn value(&self) -> Option<String> {
+        backend::value(&self.handle)
+    }
+
+    /// Sets the `value` of an input, textarea or select element.
+    pub fn set_value(&self, value: &str) -> Result<(), String> {
+        backend::set_value(&self.handle, value)
+    }
+
+    /// Sets an inline style property, e.g. `set_style("color", "red")`.
+    pub fn set_style(&self, property: &str, value: &str) -> Result<(), String> {
+        backend::set_style(&self.handle, property, value)
+    }
+
+    pub fn append_child(&self, child: &Element) -> Result<(), String> {
+        backend::append_child(&self.handle, &child.handle)
+    }
+
+    /// Removes the element from its parent.
+    pub fn remove(&self) {
+        backend::remove(&self.handle);
+    }
+
+    /// Finds the first descendant matching a CSS selector.
+    pub fn query_selector(&self, selector: &str) -> Result<Option<Element>, String> {
+        backend::query_selector_in(&self.handle, selector).map(|handle| handle.map(|handle| Element { handle }))
+    }
+
+    pub(crate) fn handle(&self) -> &backend::Handle {
+        &self.handle
+    }
+
+    /// Calls `callback` every time the event fires on this element.
+    pub fn on<F: FnMut() + 'static>(&self, event: &str, callback: F) -> Result<(), String> {
+        backend::add_listener(&self.handle, event, Box::new(callback))
+    }
+}
+
+/// Finds an element by id.
+pub fn get_element_by_id(id: &str) -> Option<Element> {
+    backend::get_element_by_id(id).map(|handle| Element { handle })
+}
+
+/// Finds the first element matching a CSS selector.
+pub fn query_selector(selector: &str) -> Result<Option<Element>, String> {
+    backend::query_selector(selector).map(|handle| handle.map(|handle| Element { handle }))
+}
+
+/// Finds every element matching a CSS selector, in document order.
+pub fn query_selector_all(selector: &str) -> Result<List<Element>, String> {
+    let mut list = List::new();
+    for handle in backend::query_selector_all(selector)? {
+        list.push(Element { handle });
+    }
+    Ok(list)
+}
+
+/// Creates a detached element with the given tag.
+pub fn create_element(tag: &str) -> Result<Element, String> {
+    backend::create_element(tag).map(|handle| Element { handle })
+}
+
+/// The document body.
+pub fn body() -> Option<Element> {
+    backend::body().map(|handle| Element { handle })
+}
+
+/// Sets the inner HTML of an element by id.
+pub fn set_inner_html(id: &str, html: &str) {
+    if let Some(element) = get_element_by_id(id) {
+        element.set_inner_html(html);
+    }
+}
+
+/// Gets the value of an input element by id.
+pub fn get_input_value(id: &str) -> String {
+    get_element_by_id(id).and_then(|element| element.value()).unwrap_or_default()
+}
+
+/// Sets the value of an input element by id.
+pub fn set_input_value(id: &str, value: &str) {
+    if let Some(element) = get_element_by_id(id) {
+        let _ = element.set_value(value);
+    }
+}
+
+/// Adds an event listener to an element by id.
+pub fn add_event_listener(id: &str, event: &str, callback: fn()) {
+    if let Some(element) = get_element_by_id(id) {
+        let _ = element.on(event, callback);
+    }
+}
+
+/// Keeps the text content of an element by id showing `source`. Dispose the
+/// returned effect to stop.
+pub fn bind_text<T: fmt::Display + 'static>(id: &str, source: &Signal<T>) -> Effect {
+    let id = id.to_string();
+    let source = source.clone();
+    Effect::new(move || {
+        let text = source.with(T::to_string);
+        if let Some(element) = get_element_by_id(&id) {
+            element.set_text_content(&text);
+        }
+    })
+}
+
+/// Binds the value of an input element by id to `value` both ways: the
+/// element shows the signal, and typing into it sets the signal.
+pub fn bind_input_value(id: &str, value: &Signal<String>) -> Effect {
+    let signal = value.clone();
+    let input = id.to_string();
+    if let Some(element) = get_element_by_id(id) {
+        let _ = element.on("input", move || {
+            signal.set_if_changed(get_input_value(&input));
+        });
+    }
+    let id = id.to_string();
+    let signal = value.clone();
+    Effect::new(move || set_input_value(&id, &signal.get()))
+}
+
+#[cfg(feature = "wasm")]
+pub(crate) mod backend {
+    use crate::browser::js_error;
+    use gigli_runtime_js::dom;
+
+    pub type Handle = dom::Element;
+
+    pub fn tag_name(h: &Handle) -> String {
+        h.tag_name().to_lowercase()
+    }
+
+    pub fn get_attribute(h: &Handle, name: &str) -> Option<String> {
+        h.get_attribute(name)
+    }
+
+    pub fn set_attribute(h: &Handle, name: &str, value: &str) -> Result<(), String> {
+        h.set_attribute(name, value).map_err(js_error)
+    }
+
+    pub fn remove_attribute(h: &Handle, name: &str) -> Result<(), String> {
+        h.remove_attribute(name).map_err(js_error)
+    }
+
+    pub fn add_class(h: &Handle, class: &str) -> Result<(), String> {
+        h.class_list().add_1(class).map_err(js_error)
+    }
+
+    pub fn remove_class(h: &Handle, class: &str) -> Result<(), String> {
+        h.class_list().remove_1(class).map_err(js_error)
+    }
+
+    pub fn toggle_class(h: &Handle, class: &str) -> Result<bool, String> {
+        h.class_list().toggle(class).map_err(js_error)
+    }
+
+    pub fn has_class(h: &Handle, class: &str) -> bool {
+        h.class_list().contains(class)
+    }
+
+    pub fn text_content(h: &Handle) -> String {
+        h.text_content().unwrap_or_default()
+    }
+
+    pub fn set_text_content(h: &Handle, text: &str) {
+        h.set_text_content(Some(text));
+    }
+
+    pub fn inner_html(h: &Handle) -> String {
+        h.inner_html()
+    }
+
+    pub fn set_inner_html(h: &Handle, html: &str) {
+        h.set_inner_html(html);
+    }
+
+    pub fn value(h: &Handle) -> Option<String> {
+        dom::value(h)
+    }
+
+    pub fn set_value(h: &Handle, value: &str) -> Result<(), String> {
+        if dom::set_value(h, value) {
+            Ok(())
+        } else {
+            Err(format!("<{}> has no value", tag_name(h)))
+        }
+    }
+
+    pub fn set_style(h: &Handle, property: &str, value: &str) -> Result<(), String> {
+        dom::set_style_property(h, property, value).map_err(js_error)
+    }
+
+    pub fn append_child(parent: &Handle, child: &Handle) -> Result<(), String> {
+        dom::append_child(parent, child).map_err(js_error)
+    }
+
+    pub fn remove(h: &Handle) {
+        h.remove();
+    }
+
+    pub fn query_selector_in(h: &Handle, selector: &str) -> Result<Option<Handle>, String> {
+        h.query_selector(selector).map_err(js_error)
+    }
+
+    pub fn add_listener(h: &Handle, event: &str, mut callback: Box<dyn FnMut()>) -> Result<(), String> {
+        dom::add_listener(h, event, Box::new(move |_| callback())).map_err(js_error)
+    }
+
+    pub fn get_element_by_id(id: &str) -> Option<Handle> {
+        dom::get_element_by_id(id)
+    }
+
+    pub fn query_selector(selector: &str) -> Result<Option<Handle>, String> {
+        dom::query_selector(selector).map_err(js_error)
+    }
+
+    pub fn query_selector_all(selector: &str) -> Result<Vec<Handle>, String> {
+        dom::query_selector_all(selector).map_err(js_error)
+    }
+
+    pub fn create_element(tag: &str) -> Result<Handle, String> {
+        dom::create_element(tag).map_err(js_error)
+    }
+
+    pub fn body() -> Option<Handle> {
+        dom::body()
+    }
+}
+
+/// Without a document no `Element` can exist, so `Handle` is uninhabited and the
+/// per-element functions are statically unreachable.
+#[cfg(not(feature = "wasm"))]
+pub(crate) mod backend {
+    #[derive(Debug, Clone)]
+    pub enum Handle {}
+
+    const NO_DOM: &str = "The DOM is only available on the web target";
+
+    pub fn tag_name(h: &Handle) -> String {
+        match *h {}
+    }
+
+    pub fn get_attribute(h: &Handle, _name: &str) -> Option<String> {
+        match *h {}
+    }
+
+    pub fn set_attribute(h: &Handle, _name: &str, _value: &str) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn remove_attribute(h: &Handle, _name: &str) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn add_class(h: &Handle, _class: &str) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn remove_class(h: &Handle, _class: &str) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn toggle_class(h: &Handle, _class: &str) -> Result<bool, String> {
+        match *h {}
+    }
+
+    pub fn has_class(h: &Handle, _class: &str) -> bool {
+        match *h {}
+    }
+
+    pub fn text_content(h: &Handle) -> String {
+        match *h {}
+    }
+
+    pub fn set_text_content(h: &Handle, _text: &str) {
+        match *h {}
+    }
+
+    pub fn inner_html(h: &Handle) -> String {
+        match *h {}
+    }
+
+    pub fn set_inner_html(h: &Handle, _html: &str) {
+        match *h {}
+    }
+
+    pub fn value(h: &Handle) -> Option<String> {
+        match *h {}
+    }
+
+    pub fn set_value(h: &Handle, _value: &str) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn set_style(h: &Handle, _property: &str, _value: &str) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn append_child(parent: &Handle, _child: &Handle) -> Result<(), String> {
+        match *parent {}
+    }
+
+    pub fn remove(h: &Handle) {
+        match *h {}
+    }
+
+    pub fn query_selector_in(h: &Handle, _selector: &str) -> Result<Option<Handle>, String> {
+        match *h {}
+    }
+
+    pub fn add_listener(h: &Handle, _event: &str, _callback: Box<dyn FnMut()>) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn get_element_by_id(_id: &str) -> Option<Handle> {
+        None
+    }
+
+    pub fn query_selector(_selector: &str) -> Result<Option<Handle>, String> {
+        Ok(None)
+    }
+
+    pub fn query_selector_all(_selector: &str) -> Result<Vec<Handle>, String> {
+        Ok(Vec::new())
+    }
+
+    pub fn create_element(_tag: &str) -> Result<Handle, String> {
+        Err(NO_DOM.to_string())
+    }
+
+    pub fn body() -> Option<Handle> {
+        None
+    }
+}
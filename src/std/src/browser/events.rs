@@ -0,0 +1,178 @@
+//! Typed DOM events for Gigli programs.
+//!
+//! Handlers receive a snapshot of the event's details rather than the live
+//! JavaScript object, so they can be plain Rust closures.
+
+use super::dom::Element;
+
+/// Modifier keys held while the event fired.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub alt: bool,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub meta: bool,
+}
+
+/// A mouse event such as `click`, `mousedown` or `mousemove`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MouseEvent {
+    pub event_type: String,
+    /// Coordinates relative to the viewport.
+    pub client_x: i32,
+    pub client_y: i32,
+    /// Coordinates relative to the target element's padding edge.
+    pub offset_x: i32,
+    pub offset_y: i32,
+    /// The button that changed state: 0 main, 1 auxiliary, 2 secondary.
+    pub button: i16,
+    /// Bitmask of the buttons currently held.
+    pub buttons: u16,
+    pub modifiers: Modifiers,
+}
+
+/// A keyboard event such as `keydown` or `keyup`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardEvent {
+    pub event_type: String,
+    /// The logical key, e.g. `a`, `Enter` or `ArrowUp`.
+    pub key: String,
+    /// The physical key, e.g. `KeyA`, independent of layout.
+    pub code: String,
+    /// True when the key is held down and auto-repeating.
+    pub repeat: bool,
+    pub modifiers: Modifiers,
+}
+
+/// An `input` or `change` event from a form control.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputEvent {
+    pub event_type: String,
+    /// The control's value after the change.
+    pub value: String,
+}
+
+/// Calls `handler` with mouse details every time `event` fires on the element.
+pub fn on_mouse<F>(element: &Element, event: &str, handler: F) -> Result<(), String>
+where
+    F: FnMut(MouseEvent) + 'static,
+{
+    backend::on_mouse(element, event, Box::new(handler))
+}
+
+pub fn on_click<F>(element: &Element, handler: F) -> Result<(), String>
+where
+    F: FnMut(MouseEvent) + 'static,
+{
+    on_mouse(element, "click", handler)
+}
+
+/// Calls `handler` with key details every time `event` (`keydown` or `keyup`) fires on the element.
+pub fn on_key<F>(element: &Element, event: &str, handler: F) -> Result<(), String>
+where
+    F: FnMut(KeyboardEvent) + 'static,
+{
+    backend::on_key(element, event, Box::new(handler))
+}
+
+pub fn on_keydown<F>(element: &Element, handler: F) -> Result<(), String>
+where
+    F: FnMut(KeyboardEvent) + 'static,
+{
+    on_key(element, "keydown", handler)
+}
+
+/// Calls `handler` with the control's new value on every `input` event.
+pub fn on_input<F>(element: &Element, handler: F) -> Result<(), String>
+where
+    F: FnMut(InputEvent) + 'static,
+{
+    backend::on_input(element, "input", Box::new(handler))
+}
+
+/// Calls `handler` with the control's committed value on every `change` event.
+pub fn on_change<F>(element: &Element, handler: F) -> Result<(), String>
+where
+    F: FnMut(InputEvent) + 'static,
+{
+    backend::on_input(element, "change", Box::new(handler))
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use super::*;
+    use crate::browser::js_error;
+    use gigli_runtime_js::{dom, events};
+    use wasm_bindgen::JsCast;
+
+    pub fn on_mouse(element: &Element, event: &str, mut handler: Box<dyn FnMut(MouseEvent)>) -> Result<(), String> {
+        let listener = move |e: events::Event| {
+            if let Some(e) = e.dyn_ref::<events::MouseEvent>() {
+                handler(MouseEvent {
+                    event_type: e.type_(),
+                    client_x: e.client_x(),
+                    client_y: e.client_y(),
+                    offset_x: e.offset_x(),
+                    offset_y: e.offset_y(),
+                    button: e.button(),
+                    buttons: e.buttons(),
+                    modifiers: Modifiers {
+                        alt: e.alt_key(),
+                        ctrl: e.ctrl_key(),
+                        shift: e.shift_key(),
+                        meta: e.meta_key(),
+                    },
+                });
+            }
+        };
+        dom::add_listener(element.handle(), event, Box::new(listener)).map_err(js_error)
+    }
+
+    pub fn on_key(element: &Element, event: &str, mut handler: Box<dyn FnMut(KeyboardEvent)>) -> Result<(), String> {
+        let listener = move |e: events::Event| {
+            if let Some(e) = e.dyn_ref::<events::KeyboardEvent>() {
+                handler(KeyboardEvent {
+                    event_type: e.type_(),
+                    key: e.key(),
+                    code: e.code(),
+                    repeat: e.repeat(),
+                    modifiers: Modifiers {
+                        alt: e.alt_key(),
+                        ctrl: e.ctrl_key(),
+                        shift: e.shift_key(),
+                        meta: e.meta_key(),
+                    },
+                });
+            }
+        };
+        dom::add_listener(element.handle(), event, Box::new(listener)).map_err(js_error)
+    }
+
+    pub fn on_input(element: &Element, event: &str, mut handler: Box<dyn FnMut(InputEvent)>) -> Result<(), String> {
+        let listener = move |e: events::Event| {
+            handler(InputEvent {
+                event_type: e.type_(),
+                value: events::target_value(&e).unwrap_or_default(),
+            });
+        };
+        dom::add_listener(element.handle(), event, Box::new(listener)).map_err(js_error)
+    }
+}
+
+/// Elements cannot exist off the web, so registration is statically unreachable.
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use super::*;
+
+    pub fn on_mouse(element: &Element, _event: &str, _handler: Box<dyn FnMut(MouseEvent)>) -> Result<(), String> {
+        match *element.handle() {}
+    }
+
+    pub fn on_key(element: &Element, _event: &str, _handler: Box<dyn FnMut(KeyboardEvent)>) -> Result<(), String> {
+        match *element.handle() {}
+    }
+
+    pub fn on_input(element: &Element, _event: &str, _handler: Box<dyn FnMut(InputEvent)>) -> Result<(), String> {
+        match *element.handle() {}
+    }
+}
@@ -0,0 +1,118 @@
+//! `fetch` for Gigli programs, returning Gigli's own Result and Option types.
+//!
+//! Requests go through `std::http`, which uses the runtime's fetch bindings on
+//! the web target and a plain HTTP/1.1 socket on native targets.
+
+use crate::http::{Method, Request};
+use crate::map::Map;
+use crate::option::Option;
+use crate::result::Result;
+use serde::de::DeserializeOwned;
+
+/// Options for a fetch call, mirroring the JavaScript `RequestInit` dictionary.
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    pub method: Method,
+    pub headers: Vec<(String, String)>,
+    pub body: std::option::Option<String>,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        FetchOptions {
+            method: Method::Get,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+}
+
+impl FetchOptions {
+    /// A GET request with no headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_string());
+        self
+    }
+}
+
+/// A completed fetch.
+#[derive(Debug, Clone)]
+pub struct FetchResponse {
+    status: u16,
+    headers: Map<String, String>,
+    set_cookies: Vec<String>,
+    body: String,
+}
+
+impl FetchResponse {
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns true for 2xx status codes.
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Gets a header value by case-insensitive name.
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.headers.get(&name.to_ascii_lowercase()).cloned().into()
+    }
+
+    /// All response headers, with lower-cased names. The values of a repeated
+    /// header are joined with `, `, except for `Set-Cookie`.
+    pub fn headers(&self) -> &Map<String, String> {
+        &self.headers
+    }
+
+    /// The value of each `Set-Cookie` header; always empty on the web.
+    pub fn set_cookies(&self) -> &[String] {
+        &self.set_cookies
+    }
+
+    pub fn text(&self) -> String {
+        self.body.clone()
+    }
+
+    /// Decodes the body as JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, String> {
+        serde_json::from_str(&self.body)
+            .map_err(|e| format!("Invalid JSON response: {}", e))
+            .into()
+    }
+}
+
+/// Fetches a URL. Network failures are errors; HTTP error statuses are not,
+/// so check `ok()` on the response.
+pub async fn fetch(url: &str, options: FetchOptions) -> Result<FetchResponse, String> {
+    let mut request = Request::new(options.method, url);
+    for (name, value) in &options.headers {
+        request = request.header(name, value);
+    }
+    if let Some(body) = &options.body {
+        request = request.body(body);
+    }
+    match request.send().await {
+        Ok(response) => Result::Ok(FetchResponse {
+            status: response.status,
+            headers: response.headers,
+            set_cookies: response.set_cookies,
+            body: response.body,
+        }),
+        Err(e) => Result::Err(e.to_string()),
+    }
+}
@@ -0,0 +1,139 @@
+//! Device location for Gigli programs.
+//!
+//! The browser asks the user for permission on first use. Native targets have
+//! no location source and report `PositionErrorKind::Unavailable`.
+
+use std::fmt;
+
+/// A position fix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    /// Degrees north of the equator.
+    pub latitude: f64,
+    /// Degrees east of Greenwich.
+    pub longitude: f64,
+    /// Accuracy radius of latitude/longitude, in meters.
+    pub accuracy: f64,
+    /// Meters above the WGS84 ellipsoid, if known.
+    pub altitude: Option<f64>,
+    /// Degrees clockwise from true north, if moving.
+    pub heading: Option<f64>,
+    /// Meters per second, if known.
+    pub speed: Option<f64>,
+    /// Milliseconds since the Unix epoch when the fix was taken.
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionErrorKind {
+    PermissionDenied,
+    Unavailable,
+    Timeout,
+}
+
+/// Why a position could not be obtained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionError {
+    pub kind: PositionErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            PositionErrorKind::PermissionDenied => "permission denied",
+            PositionErrorKind::Unavailable => "position unavailable",
+            PositionErrorKind::Timeout => "timed out",
+        };
+        if self.message.is_empty() {
+            write!(f, "Geolocation failed: {}", kind)
+        } else {
+            write!(f, "Geolocation failed: {}: {}", kind, self.message)
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+/// Identifies a watch started with `watch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchId(i32);
+
+/// Gets the device's current position.
+pub async fn current_position() -> Result<Position, PositionError> {
+    backend::current_position().await
+}
+
+/// Calls `handler` with every position update (or error) until `clear_watch`.
+pub fn watch<F>(handler: F) -> Result<WatchId, PositionError>
+where
+    F: FnMut(Result<Position, PositionError>) + 'static,
+{
+    backend::watch(Box::new(handler)).map(WatchId)
+}
+
+/// Stops a watch started with `watch`.
+pub fn clear_watch(id: WatchId) {
+    backend::clear_watch(id.0);
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use super::{Position, PositionError, PositionErrorKind};
+    use gigli_runtime_js::geolocation::{self, GeoError, GeoPosition};
+
+    fn position(p: GeoPosition) -> Position {
+        Position {
+            latitude: p.latitude,
+            longitude: p.longitude,
+            accuracy: p.accuracy,
+            altitude: p.altitude,
+            heading: p.heading,
+            speed: p.speed,
+            timestamp: p.timestamp as u64,
+        }
+    }
+
+    fn error(e: GeoError) -> PositionError {
+        let kind = match e.code {
+            1 => PositionErrorKind::PermissionDenied,
+            3 => PositionErrorKind::Timeout,
+            _ => PositionErrorKind::Unavailable,
+        };
+        PositionError { kind, message: e.message }
+    }
+
+    pub async fn current_position() -> Result<Position, PositionError> {
+        geolocation::current_position().await.map(position).map_err(error)
+    }
+
+    pub fn watch(mut handler: Box<dyn FnMut(Result<Position, PositionError>)>) -> Result<i32, PositionError> {
+        geolocation::watch_position(Box::new(move |update| handler(update.map(position).map_err(error)))).map_err(error)
+    }
+
+    pub fn clear_watch(id: i32) {
+        geolocation::clear_watch(id);
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use super::{Position, PositionError, PositionErrorKind};
+
+    fn unavailable() -> PositionError {
+        PositionError {
+            kind: PositionErrorKind::Unavailable,
+            message: "Geolocation is only available on the web target".to_string(),
+        }
+    }
+
+    pub async fn current_position() -> Result<Position, PositionError> {
+        Err(unavailable())
+    }
+
+    pub fn watch(_handler: Box<dyn FnMut(Result<Position, PositionError>)>) -> Result<i32, PositionError> {
+        Err(unavailable())
+    }
+
+    pub fn clear_watch(_id: i32) {}
+}
@@ -0,0 +1,139 @@
+//! Session history for Gigli programs, the foundation for client-side routing.
+//!
+//! States are strings; store structured state as JSON. On native targets an
+//! in-memory stack stands in for the browser history so routing logic can be tested.
+
+/// Pushes a new entry, changing the URL without reloading the page.
+pub fn push_state(state: &str, url: &str) -> Result<(), String> {
+    backend::push_state(state, url)
+}
+
+/// Replaces the current entry's state and URL.
+pub fn replace_state(state: &str, url: &str) -> Result<(), String> {
+    backend::replace_state(state, url)
+}
+
+/// Moves one entry back, firing `popstate`.
+pub fn back() {
+    backend::go(-1);
+}
+
+/// Moves one entry forward, firing `popstate`.
+pub fn forward() {
+    backend::go(1);
+}
+
+/// Moves `delta` entries through the history, firing `popstate`.
+pub fn go(delta: i32) {
+    backend::go(delta);
+}
+
+/// Calls `handler` with the new entry's state whenever the user navigates
+/// with back/forward (or `back`, `forward` and `go` are called).
+pub fn on_popstate<F>(handler: F) -> Result<(), String>
+where
+    F: FnMut(Option<String>) + 'static,
+{
+    backend::on_popstate(Box::new(handler))
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use crate::browser::js_error;
+    use gigli_runtime_js::history;
+
+    pub fn push_state(state: &str, url: &str) -> Result<(), String> {
+        history::push_state(state, url).map_err(js_error)
+    }
+
+    pub fn replace_state(state: &str, url: &str) -> Result<(), String> {
+        history::replace_state(state, url).map_err(js_error)
+    }
+
+    pub fn go(delta: i32) {
+        if let Ok(h) = history::history() {
+            let _ = h.go_with_delta(delta);
+        }
+    }
+
+    pub fn on_popstate(handler: Box<dyn FnMut(Option<String>)>) -> Result<(), String> {
+        history::on_popstate(handler).map_err(js_error)
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+pub(crate) mod backend {
+    use std::cell::RefCell;
+
+    struct Session {
+        /// (state, url) entries; the first is the initial page.
+        entries: Vec<(Option<String>, String)>,
+        index: usize,
+        listeners: Vec<Box<dyn FnMut(Option<String>)>>,
+    }
+
+    thread_local! {
+        static SESSION: RefCell<Session> = RefCell::new(Session {
+            entries: vec![(None, "/".to_string())],
+            index: 0,
+            listeners: Vec::new(),
+        });
+    }
+
+    /// The URL of the current entry, read by `browser::location` on native targets.
+    pub fn current_url() -> String {
+        SESSION.with(|s| {
+            let s = s.borrow();
+            s.entries[s.index].1.clone()
+        })
+    }
+
+    pub fn push_state(state: &str, url: &str) -> Result<(), String> {
+        SESSION.with(|s| {
+            let mut s = s.borrow_mut();
+            let index = s.index + 1;
+            s.entries.truncate(index);
+            s.entries.push((Some(state.to_string()), url.to_string()));
+            s.index = index;
+        });
+        Ok(())
+    }
+
+    pub fn replace_state(state: &str, url: &str) -> Result<(), String> {
+        SESSION.with(|s| {
+            let mut s = s.borrow_mut();
+            let index = s.index;
+            s.entries[index] = (Some(state.to_string()), url.to_string());
+        });
+        Ok(())
+    }
+
+    pub fn go(delta: i32) {
+        let state = SESSION.with(|s| {
+            let mut s = s.borrow_mut();
+            let target = s.index as i64 + delta as i64;
+            if delta == 0 || target < 0 || target >= s.entries.len() as i64 {
+                return None;
+            }
+            s.index = target as usize;
+            Some(s.entries[s.index].0.clone())
+        });
+        if let Some(state) = state {
+            // Take the listeners out while calling them so a handler may navigate again.
+            let mut listeners = SESSION.with(|s| std::mem::take(&mut s.borrow_mut().listeners));
+            for listener in listeners.iter_mut() {
+                listener(state.clone());
+            }
+            SESSION.with(|s| {
+                let mut s = s.borrow_mut();
+                listeners.append(&mut s.listeners);
+                s.listeners = listeners;
+            });
+        }
+    }
+
+    pub fn on_popstate(handler: Box<dyn FnMut(Option<String>)>) -> Result<(), String> {
+        SESSION.with(|s| s.borrow_mut().listeners.push(handler));
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//! The current page URL for Gigli programs.
+//!
+//! On native targets the URL is the current entry of the in-memory history
+//! used by `browser::history`, served from `http://localhost`.
+
+use crate::map::Map;
+
+/// The full URL, e.g. `https://example.com/users?id=3#top`.
+pub fn href() -> String {
+    backend::href()
+}
+
+/// The scheme, host and port, e.g. `https://example.com`.
+pub fn origin() -> String {
+    let href = href();
+    let after_scheme = href.find("://").map(|i| i + 3).unwrap_or(0);
+    match href[after_scheme..].find('/') {
+        Some(i) => href[..after_scheme + i].to_string(),
+        None => href,
+    }
+}
+
+/// The path, e.g. `/users`.
+pub fn pathname() -> String {
+    let rest = path_and_rest();
+    let (path, _, _) = split_path(&rest);
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// The query string including the leading `?`, or empty.
+pub fn search() -> String {
+    split_path(&path_and_rest()).1.to_string()
+}
+
+/// The fragment including the leading `#`, or empty.
+pub fn hash() -> String {
+    split_path(&path_and_rest()).2.to_string()
+}
+
+/// The decoded query parameters. Later duplicates win.
+pub fn query_params() -> Map<String, String> {
+    let mut params = Map::new();
+    for pair in search().trim_start_matches('?').split('&').filter(|p| !p.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.insert(decode_component(name), decode_component(value));
+    }
+    params
+}
+
+/// Gets a single decoded query parameter.
+pub fn query_param(name: &str) -> Option<String> {
+    query_params().get(&name.to_string()).cloned()
+}
+
+/// Navigates to a new URL, loading a new page.
+pub fn assign(url: &str) -> Result<(), String> {
+    backend::assign(url)
+}
+
+/// Reloads the current page.
+pub fn reload() {
+    backend::reload();
+}
+
+/// Everything after the origin: path, query and fragment.
+fn path_and_rest() -> String {
+    let href = href();
+    href[origin().len()..].to_string()
+}
+
+/// Splits `/path?query#hash` into its three parts, keeping the `?` and `#` prefixes.
+fn split_path(url: &str) -> (&str, &str, &str) {
+    let (before_hash, hash) = match url.find('#') {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    let (path, query) = match before_hash.find('?') {
+        Some(i) => before_hash.split_at(i),
+        None => (before_hash, ""),
+    };
+    (path, query, hash)
+}
+
+/// Decodes `%XX` escapes and `+` as space, as in form-encoded query strings.
+fn decode_component(text: &str) -> String {
+    let hex = |b: u8| (b as char).to_digit(16);
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(high), Some(low)) => {
+                    out.push((high * 16 + low) as u8);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use crate::browser::js_error;
+    use gigli_runtime_js::history;
+
+    pub fn href() -> String {
+        history::location().and_then(|l| l.href()).unwrap_or_default()
+    }
+
+    pub fn assign(url: &str) -> Result<(), String> {
+        history::location()
+            .and_then(|l| l.assign(url))
+            .map_err(js_error)
+    }
+
+    pub fn reload() {
+        if let Ok(l) = history::location() {
+            let _ = l.reload();
+        }
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use crate::browser::history::backend as session;
+
+    const ORIGIN: &str = "http://localhost";
+
+    pub fn href() -> String {
+        let url = session::current_url();
+        if url.contains("://") {
+            url
+        } else if url.starts_with('/') {
+            format!("{}{}", ORIGIN, url)
+        } else {
+            format!("{}/{}", ORIGIN, url)
+        }
+    }
+
+    /// Without page loads, assigning behaves like pushing a history entry.
+    pub fn assign(url: &str) -> Result<(), String> {
+        session::push_state("", url)
+    }
+
+    pub fn reload() {}
+}
@@ -0,0 +1,111 @@
+//! Desktop notifications for Gigli programs.
+//!
+//! Showing a notification requires the user's permission, requested with
+//! `request_permission`. Native targets never grant it.
+
+/// Whether the page may show notifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Granted,
+    Denied,
+    /// The user has not decided yet; `request_permission` will ask.
+    Default,
+}
+
+impl Permission {
+    fn parse(value: &str) -> Permission {
+        match value {
+            "granted" => Permission::Granted,
+            "denied" => Permission::Denied,
+            _ => Permission::Default,
+        }
+    }
+}
+
+/// Optional notification content.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NotificationOptions {
+    pub body: String,
+    /// URL of an icon image.
+    pub icon: String,
+    /// Notifications with the same tag replace each other.
+    pub tag: String,
+}
+
+impl NotificationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+
+    pub fn icon(mut self, icon: &str) -> Self {
+        self.icon = icon.to_string();
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = tag.to_string();
+        self
+    }
+}
+
+/// The current permission, without prompting.
+pub fn permission() -> Permission {
+    Permission::parse(&backend::permission())
+}
+
+/// Prompts the user if they have not decided yet, returning the outcome.
+pub async fn request_permission() -> Result<Permission, String> {
+    backend::request_permission().await.map(|p| Permission::parse(&p))
+}
+
+/// Shows a notification. Fails unless permission has been granted.
+pub fn show(title: &str, options: &NotificationOptions) -> Result<(), String> {
+    match permission() {
+        Permission::Granted => backend::show(title, options),
+        Permission::Denied => Err("Notification permission was denied".to_string()),
+        Permission::Default => Err("Notification permission has not been requested".to_string()),
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use super::NotificationOptions;
+    use crate::browser::js_error;
+    use gigli_runtime_js::notification;
+
+    pub fn permission() -> String {
+        notification::permission().to_string()
+    }
+
+    pub async fn request_permission() -> Result<String, String> {
+        notification::request_permission().await.map_err(js_error)
+    }
+
+    pub fn show(title: &str, options: &NotificationOptions) -> Result<(), String> {
+        notification::show(title, &options.body, &options.icon, &options.tag)
+            .map(|_| ())
+            .map_err(js_error)
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use super::NotificationOptions;
+
+    pub fn permission() -> String {
+        "denied".to_string()
+    }
+
+    pub async fn request_permission() -> Result<String, String> {
+        Err("Notifications are only available on the web target".to_string())
+    }
+
+    pub fn show(_title: &str, _options: &NotificationOptions) -> Result<(), String> {
+        Err("Notifications are only available on the web target".to_string())
+    }
+}
@@ -0,0 +1,192 @@
+//! Web Storage (`localStorage` / `sessionStorage`) for Gigli programs.
+//!
+//! On native targets both areas are in-memory maps that live for the current
+//! thread, so storage-dependent code can be exercised in tests.
+
+use crate::list::List;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Which storage area to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StorageKind {
+    /// Persists across browser sessions.
+    Local,
+    /// Cleared when the tab is closed.
+    Session,
+}
+
+/// A handle to one storage area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Storage {
+    kind: StorageKind,
+}
+
+impl Storage {
+    /// The persistent `localStorage` area.
+    pub fn local() -> Self {
+        Storage { kind: StorageKind::Local }
+    }
+
+    /// The per-tab `sessionStorage` area.
+    pub fn session() -> Self {
+        Storage { kind: StorageKind::Session }
+    }
+
+    pub fn kind(&self) -> StorageKind {
+        self.kind
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        backend::get(self.kind, key)
+    }
+
+    /// Stores a value; fails if the quota is exceeded or storage is blocked.
+    pub fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        backend::set(self.kind, key, value)
+    }
+
+    pub fn remove(&self, key: &str) {
+        backend::remove(self.kind, key);
+    }
+
+    pub fn clear(&self) {
+        backend::clear(self.kind);
+    }
+
+    pub fn keys(&self) -> List<String> {
+        let mut list = List::new();
+        for key in backend::keys(self.kind) {
+            list.push(key);
+        }
+        list
+    }
+
+    pub fn len(&self) -> usize {
+        backend::keys(self.kind).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Reads and decodes a JSON value; Ok(None) if the key is missing.
+    pub fn get_json<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, String> {
+        match self.get(key) {
+            Some(text) => serde_json::from_str(&text)
+                .map(Some)
+                .map_err(|e| format!("Invalid JSON in storage key '{}': {}", key, e)),
+            None => Ok(None),
+        }
+    }
+
+    /// Encodes a value as JSON and stores it.
+    pub fn set_json<T: Serialize>(&self, key: &str, value: &T) -> Result<(), String> {
+        let text = serde_json::to_string(value).map_err(|e| format!("Failed to encode '{}': {}", key, e))?;
+        self.set(key, &text)
+    }
+}
+
+/// Gets a value from `localStorage`.
+pub fn get(key: &str) -> Option<String> {
+    Storage::local().get(key)
+}
+
+/// Stores a value in `localStorage`.
+pub fn set(key: &str, value: &str) -> Result<(), String> {
+    Storage::local().set(key, value)
+}
+
+/// Removes a value from `localStorage`.
+pub fn remove(key: &str) {
+    Storage::local().remove(key);
+}
+
+/// Lists the keys in `localStorage`.
+pub fn keys() -> List<String> {
+    Storage::local().keys()
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use crate::browser::js_error;
+    use super::StorageKind;
+    use gigli_runtime_js::storage;
+    use wasm_bindgen::JsValue;
+
+    fn area(kind: StorageKind) -> Result<storage::Storage, JsValue> {
+        match kind {
+            StorageKind::Local => storage::local_storage(),
+            StorageKind::Session => storage::session_storage(),
+        }
+    }
+
+    pub fn get(kind: StorageKind, key: &str) -> Option<String> {
+        area(kind).ok()?.get_item(key).ok()?
+    }
+
+    pub fn set(kind: StorageKind, key: &str, value: &str) -> Result<(), String> {
+        area(kind)
+            .and_then(|s| s.set_item(key, value))
+            .map_err(js_error)
+    }
+
+    pub fn remove(kind: StorageKind, key: &str) {
+        if let Ok(s) = area(kind) {
+            let _ = s.remove_item(key);
+        }
+    }
+
+    pub fn clear(kind: StorageKind) {
+        if let Ok(s) = area(kind) {
+            let _ = s.clear();
+        }
+    }
+
+    pub fn keys(kind: StorageKind) -> Vec<String> {
+        area(kind).and_then(|s| storage::keys(&s)).unwrap_or_default()
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use super::StorageKind;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    thread_local! {
+        static AREAS: RefCell<BTreeMap<(StorageKind, String), String>> = RefCell::new(BTreeMap::new());
+    }
+
+    pub fn get(kind: StorageKind, key: &str) -> Option<String> {
+        AREAS.with(|areas| areas.borrow().get(&(kind, key.to_string())).cloned())
+    }
+
+    pub fn set(kind: StorageKind, key: &str, value: &str) -> Result<(), String> {
+        AREAS.with(|areas| areas.borrow_mut().insert((kind, key.to_string()), value.to_string()));
+        Ok(())
+    }
+
+    pub fn remove(kind: StorageKind, key: &str) {
+        AREAS.with(|areas| areas.borrow_mut().remove(&(kind, key.to_string())));
+    }
+
+    pub fn clear(kind: StorageKind) {
+        AREAS.with(|areas| areas.borrow_mut().retain(|(k, _), _| *k != kind));
+    }
+
+    pub fn keys(kind: StorageKind) -> Vec<String> {
+        AREAS.with(|areas| {
+            areas
+                .borrow()
+                .keys()
+                .filter(|(k, _)| *k == kind)
+                .map(|(_, key)| key.clone())
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,217 @@
+//! WebSocket connections for Gigli programs.
+//!
+//! Callbacks are registered on the socket and fire as the connection opens,
+//! receives messages, errors or closes. Native targets have no browser
+//! sockets, so `connect` fails there.
+
+/// A message received from or sent to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// How a connection closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseEvent {
+    /// The close code, e.g. 1000 for a normal closure.
+    pub code: u16,
+    pub reason: String,
+    /// False if the connection dropped without a closing handshake.
+    pub was_clean: bool,
+}
+
+/// The connection state, mirroring `WebSocket.readyState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadyState {
+    Connecting,
+    Open,
+    Closing,
+    Closed,
+}
+
+/// An open (or opening) WebSocket connection.
+#[derive(Debug, Clone)]
+pub struct WebSocket {
+    handle: backend::Handle,
+}
+
+impl WebSocket {
+    /// Starts connecting to a `ws://` or `wss://` URL.
+    pub fn connect(url: &str) -> Result<WebSocket, String> {
+        if !(url.starts_with("ws://") || url.starts_with("wss://")) {
+            return Err(format!("Invalid WebSocket URL '{}': expected ws:// or wss://", url));
+        }
+        backend::connect(url).map(|handle| WebSocket { handle })
+    }
+
+    pub fn url(&self) -> String {
+        backend::url(&self.handle)
+    }
+
+    pub fn ready_state(&self) -> ReadyState {
+        backend::ready_state(&self.handle)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.ready_state() == ReadyState::Open
+    }
+
+    /// Sends a text frame. Fails if the connection is not open.
+    pub fn send(&self, text: &str) -> Result<(), String> {
+        backend::send(&self.handle, Message::Text(text.to_string()))
+    }
+
+    /// Sends a binary frame. Fails if the connection is not open.
+    pub fn send_bytes(&self, bytes: &[u8]) -> Result<(), String> {
+        backend::send(&self.handle, Message::Binary(bytes.to_vec()))
+    }
+
+    /// Closes the connection normally (code 1000).
+    pub fn close(&self) -> Result<(), String> {
+        backend::close(&self.handle, 1000, "")
+    }
+
+    /// Closes the connection with a code (1000 or 3000-4999) and reason.
+    pub fn close_with(&self, code: u16, reason: &str) -> Result<(), String> {
+        if code != 1000 && !(3000..=4999).contains(&code) {
+            return Err(format!("Invalid close code {}: expected 1000 or 3000-4999", code));
+        }
+        backend::close(&self.handle, code, reason)
+    }
+
+    /// Calls `handler` once the connection is open.
+    pub fn on_open<F: FnMut() + 'static>(&self, handler: F) {
+        backend::on_open(&self.handle, Box::new(handler));
+    }
+
+    /// Calls `handler` with every message received.
+    pub fn on_message<F: FnMut(Message) + 'static>(&self, handler: F) {
+        backend::on_message(&self.handle, Box::new(handler));
+    }
+
+    /// Calls `handler` when the connection closes, cleanly or not.
+    pub fn on_close<F: FnMut(CloseEvent) + 'static>(&self, handler: F) {
+        backend::on_close(&self.handle, Box::new(handler));
+    }
+
+    /// Calls `handler` when the connection fails; a close event follows.
+    pub fn on_error<F: FnMut() + 'static>(&self, handler: F) {
+        backend::on_error(&self.handle, Box::new(handler));
+    }
+}
+
+/// Starts connecting to a `ws://` or `wss://` URL.
+pub fn connect(url: &str) -> Result<WebSocket, String> {
+    WebSocket::connect(url)
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use crate::browser::js_error;
+    use super::{CloseEvent, Message, ReadyState};
+    use gigli_runtime_js::websocket;
+
+    pub type Handle = websocket::WebSocket;
+
+    pub fn connect(url: &str) -> Result<Handle, String> {
+        websocket::connect(url).map_err(js_error)
+    }
+
+    pub fn url(h: &Handle) -> String {
+        h.url()
+    }
+
+    pub fn ready_state(h: &Handle) -> ReadyState {
+        match h.ready_state() {
+            Handle::CONNECTING => ReadyState::Connecting,
+            Handle::OPEN => ReadyState::Open,
+            Handle::CLOSING => ReadyState::Closing,
+            _ => ReadyState::Closed,
+        }
+    }
+
+    pub fn send(h: &Handle, message: Message) -> Result<(), String> {
+        match message {
+            Message::Text(text) => h.send_with_str(&text),
+            Message::Binary(bytes) => h.send_with_u8_array(&bytes),
+        }
+        .map_err(js_error)
+    }
+
+    pub fn close(h: &Handle, code: u16, reason: &str) -> Result<(), String> {
+        h.close_with_code_and_reason(code, reason).map_err(js_error)
+    }
+
+    pub fn on_open(h: &Handle, handler: Box<dyn FnMut()>) {
+        websocket::on_open(h, handler);
+    }
+
+    pub fn on_message(h: &Handle, mut handler: Box<dyn FnMut(Message)>) {
+        websocket::on_message(
+            h,
+            Box::new(move |message| {
+                handler(match message {
+                    websocket::Message::Text(text) => Message::Text(text),
+                    websocket::Message::Binary(bytes) => Message::Binary(bytes),
+                })
+            }),
+        );
+    }
+
+    pub fn on_close(h: &Handle, mut handler: Box<dyn FnMut(CloseEvent)>) {
+        websocket::on_close(
+            h,
+            Box::new(move |code, reason, was_clean| handler(CloseEvent { code, reason, was_clean })),
+        );
+    }
+
+    pub fn on_error(h: &Handle, handler: Box<dyn FnMut()>) {
+        websocket::on_error(h, handler);
+    }
+}
+
+/// No connection can be opened off the web, so `Handle` is uninhabited.
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use super::{CloseEvent, Message, ReadyState};
+
+    #[derive(Debug, Clone)]
+    pub enum Handle {}
+
+    pub fn connect(_url: &str) -> Result<Handle, String> {
+        Err("WebSockets are only available on the web target".to_string())
+    }
+
+    pub fn url(h: &Handle) -> String {
+        match *h {}
+    }
+
+    pub fn ready_state(h: &Handle) -> ReadyState {
+        match *h {}
+    }
+
+    pub fn send(h: &Handle, _message: Message) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn close(h: &Handle, _code: u16, _reason: &str) -> Result<(), String> {
+        match *h {}
+    }
+
+    pub fn on_open(h: &Handle, _handler: Box<dyn FnMut()>) {
+        match *h {}
+    }
+
+    pub fn on_message(h: &Handle, _handler: Box<dyn FnMut(Message)>) {
+        match *h {}
+    }
+
+    pub fn on_close(h: &Handle, _handler: Box<dyn FnMut(CloseEvent)>) {
+        match *h {}
+    }
+
+    pub fn on_error(h: &Handle, _handler: Box<dyn FnMut()>) {
+        match *h {}
+    }
+}
@@ -0,0 +1,100 @@
+//! Standard library: SortedMap<K, V> for Gigli
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+#[derive(Debug, Clone)]
+pub struct SortedMap<K, V> {
+    data: BTreeMap<K, V>,
+}
+
+impl<K: std::cmp::Ord, V> SortedMap<K, V> {
+    /// Creates a new empty map.
+    pub fn new() -> Self {
+        SortedMap { data: BTreeMap::new() }
+    }
+
+    /// Inserts a key-value pair into the map.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.data.insert(key, value);
+    }
+
+    /// Gets a reference to the value for the given key.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.data.get(key)
+    }
+
+    /// Gets a mutable reference to the value for the given key.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.data.get_mut(key)
+    }
+
+    /// Removes a key from the map, returning the value if it existed.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.data.remove(key)
+    }
+
+    /// Returns true if the map contains the given key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.data.contains_key(key)
+    }
+
+    /// Returns the number of key-value pairs in the map.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns true if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.data.first_key_value()
+    }
+
+    /// Returns the entry with the largest key.
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.data.last_key_value()
+    }
+
+    /// Returns the entry with the largest key less than or equal to the given key.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        self.data.range((Bound::Unbounded, Bound::Included(key))).next_back()
+    }
+
+    /// Returns the entry with the smallest key greater than or equal to the given key.
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        self.data.range((Bound::Included(key), Bound::Unbounded)).next()
+    }
+
+    /// Returns an ordered iterator over the entries with keys in `from..to`.
+    pub fn range(&self, from: &K, to: &K) -> std::collections::btree_map::Range<K, V> {
+        if from > to {
+            // BTreeMap::range panics on inverted bounds; an empty range is friendlier.
+            return self.data.range((Bound::Included(from), Bound::Excluded(from)));
+        }
+        self.data.range((Bound::Included(from), Bound::Excluded(to)))
+    }
+
+    /// Returns an iterator over the entries in ascending key order.
+    pub fn iter(&self) -> std::collections::btree_map::Iter<K, V> {
+        self.data.iter()
+    }
+
+    /// Returns a mutable iterator over the entries in ascending key order.
+    pub fn iter_mut(&mut self) -> std::collections::btree_map::IterMut<K, V> {
+        self.data.iter_mut()
+    }
+
+    /// Returns an iterator over the keys in ascending order.
+    pub fn keys(&self) -> std::collections::btree_map::Keys<K, V> {
+        self.data.keys()
+    }
+
+    /// Returns an iterator over the values in ascending key order.
+    pub fn values(&self) -> std::collections::btree_map::Values<K, V> {
+        self.data.values()
+    }
+}
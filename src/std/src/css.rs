@@ -0,0 +1,400 @@
+//! Typed CSS for Gigli programs.
+//!
+//! Styles are built from typed values (`Length`, `Color`, `Duration`) so units
+//! are checked at compile time, then turned into generated classes or keyframe
+//! animations and injected into the page. On native targets injected CSS is
+//! collected in memory and can be read back with `injected_css`, e.g. for
+//! server-side rendering.
+
+use crate::time::time::Duration;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+
+/// A CSS length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Px(f64),
+    Em(f64),
+    Rem(f64),
+    Percent(f64),
+    Vw(f64),
+    Vh(f64),
+    Auto,
+}
+
+impl Length {
+    pub const ZERO: Length = Length::Px(0.0);
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Length::Px(v) if *v == 0.0 => write!(f, "0"),
+            Length::Px(v) => write!(f, "{}px", v),
+            Length::Em(v) => write!(f, "{}em", v),
+            Length::Rem(v) => write!(f, "{}rem", v),
+            Length::Percent(v) => write!(f, "{}%", v),
+            Length::Vw(v) => write!(f, "{}vw", v),
+            Length::Vh(v) => write!(f, "{}vh", v),
+            Length::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+pub fn px(value: f64) -> Length {
+    Length::Px(value)
+}
+
+pub fn em(value: f64) -> Length {
+    Length::Em(value)
+}
+
+pub fn rem(value: f64) -> Length {
+    Length::Rem(value)
+}
+
+pub fn percent(value: f64) -> Length {
+    Length::Percent(value)
+}
+
+/// A CSS color.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Color {
+    Rgb(u8, u8, u8),
+    /// Red, green, blue and an alpha between 0 and 1.
+    Rgba(u8, u8, u8, f64),
+    /// A named color or keyword such as `transparent` or `currentColor`.
+    Named(String),
+}
+
+impl Color {
+    /// Parses `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+    pub fn hex(text: &str) -> Result<Color, String> {
+        let digits = text
+            .strip_prefix('#')
+            .ok_or_else(|| format!("Invalid hex color '{}': expected a leading '#'", text))?;
+        let nibble = |c: char| c.to_digit(16).map(|d| d as u8);
+        let values: Option<Vec<u8>> = match digits.len() {
+            3 | 4 => digits.chars().map(|c| nibble(c).map(|d| d * 17)).collect(),
+            6 | 8 => digits
+                .as_bytes()
+                .chunks(2)
+                .map(|pair| Some(nibble(pair[0] as char)? * 16 + nibble(pair[1] as char)?))
+                .collect(),
+            _ => None,
+        };
+        match values.as_deref() {
+            Some([r, g, b]) => Ok(Color::Rgb(*r, *g, *b)),
+            Some([r, g, b, a]) => Ok(Color::Rgba(*r, *g, *b, *a as f64 / 255.0)),
+            _ => Err(format!("Invalid hex color '{}'", text)),
+        }
+    }
+
+    pub fn named(name: &str) -> Color {
+        Color::Named(name.to_string())
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            Color::Rgba(r, g, b, a) => write!(f, "rgba({}, {}, {}, {})", r, g, b, a.clamp(0.0, 1.0)),
+            Color::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// The `display` property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Display {
+    None,
+    Block,
+    Inline,
+    InlineBlock,
+    Flex,
+    Grid,
+}
+
+impl fmt::Display for Display {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match self {
+            Display::None => "none",
+            Display::Block => "block",
+            Display::Inline => "inline",
+            Display::InlineBlock => "inline-block",
+            Display::Flex => "flex",
+            Display::Grid => "grid",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+/// How many times an animation runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Iterations {
+    Count(f64),
+    Infinite,
+}
+
+impl fmt::Display for Iterations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Iterations::Count(n) => write!(f, "{}", n),
+            Iterations::Infinite => write!(f, "infinite"),
+        }
+    }
+}
+
+/// Formats a duration the way CSS expects, in milliseconds.
+fn time_value(duration: Duration) -> String {
+    format!("{}ms", duration.as_millis())
+}
+
+/// An ordered list of property declarations.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+    declarations: Vec<(String, String)>,
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets any property; later values for the same property replace earlier ones.
+    pub fn property(mut self, name: &str, value: impl fmt::Display) -> Self {
+        let value = value.to_string();
+        match self.declarations.iter_mut().find(|(n, _)| n == name) {
+            Some(existing) => existing.1 = value,
+            None => self.declarations.push((name.to_string(), value)),
+        }
+        self
+    }
+
+    pub fn width(self, value: Length) -> Self {
+        self.property("width", value)
+    }
+
+    pub fn height(self, value: Length) -> Self {
+        self.property("height", value)
+    }
+
+    pub fn margin(self, value: Length) -> Self {
+        self.property("margin", value)
+    }
+
+    pub fn padding(self, value: Length) -> Self {
+        self.property("padding", value)
+    }
+
+    /// Sets `gap` between flex or grid items.
+    pub fn gap(self, value: Length) -> Self {
+        self.property("gap", value)
+    }
+
+    pub fn font_size(self, value: Length) -> Self {
+        self.property("font-size", value)
+    }
+
+    /// Sets `font-weight`, e.g. 400 for normal or 700 for bold.
+    pub fn font_weight(self, weight: u16) -> Self {
+        self.property("font-weight", weight)
+    }
+
+    pub fn border_radius(self, value: Length) -> Self {
+        self.property("border-radius", value)
+    }
+
+    /// Sets a solid border.
+    pub fn border(self, width: Length, color: Color) -> Self {
+        self.property("border", format!("{} solid {}", width, color))
+    }
+
+    pub fn color(self, value: Color) -> Self {
+        self.property("color", value)
+    }
+
+    pub fn background(self, value: Color) -> Self {
+        self.property("background-color", value)
+    }
+
+    pub fn display(self, value: Display) -> Self {
+        self.property("display", value)
+    }
+
+    /// Sets `opacity`, clamped to 0..=1.
+    pub fn opacity(self, value: f64) -> Self {
+        self.property("opacity", value.clamp(0.0, 1.0))
+    }
+
+    /// Sets `transition` for a property, e.g. `transition("opacity", Duration::from_millis(200))`.
+    pub fn transition(self, property: &str, duration: Duration) -> Self {
+        self.property("transition", format!("{} {}", property, time_value(duration)))
+    }
+
+    /// Runs a keyframe animation registered with `keyframes`.
+    pub fn animation(self, name: &str, duration: Duration, iterations: Iterations) -> Self {
+        self.property("animation", format!("{} {} {}", name, time_value(duration), iterations))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.declarations.is_empty()
+    }
+
+    /// The declarations as `name: value;` pairs.
+    pub fn to_css(&self) -> String {
+        self.declarations
+            .iter()
+            .map(|(name, value)| format!("{}: {};", name, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A selector with its style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub selector: String,
+    pub style: Style,
+}
+
+impl Rule {
+    pub fn new(selector: &str, style: Style) -> Self {
+        Rule {
+            selector: selector.to_string(),
+            style,
+        }
+    }
+
+    pub fn to_css(&self) -> String {
+        format!("{} {{ {} }}", self.selector, self.style.to_css())
+    }
+}
+
+/// A named `@keyframes` animation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframes {
+    pub name: String,
+    frames: Vec<(f64, Style)>,
+}
+
+impl Keyframes {
+    pub fn new(name: &str) -> Self {
+        Keyframes {
+            name: name.to_string(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Adds a frame at `percent` (clamped to 0..=100) of the animation.
+    pub fn frame(mut self, percent: f64, style: Style) -> Self {
+        self.frames.push((percent.clamp(0.0, 100.0), style));
+        self
+    }
+
+    pub fn from(self, style: Style) -> Self {
+        self.frame(0.0, style)
+    }
+
+    pub fn to(self, style: Style) -> Self {
+        self.frame(100.0, style)
+    }
+
+    pub fn to_css(&self) -> String {
+        let mut frames = self.frames.clone();
+        frames.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let body = frames
+            .iter()
+            .map(|(percent, style)| format!("{}% {{ {} }}", percent, style.to_css()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("@keyframes {} {{ {} }}", self.name, body)
+    }
+}
+
+thread_local! {
+    /// Generated names already injected, so each rule is added to the page once.
+    static INJECTED: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+/// FNV-1a, used to derive stable class names from declarations.
+fn fnv1a(text: &str) -> u32 {
+    text.bytes()
+        .fold(0x811c_9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193))
+}
+
+fn inject_once(key: &str, css: &str) -> Result<(), String> {
+    if INJECTED.with(|i| i.borrow().contains(key)) {
+        return Ok(());
+    }
+    inject(css)?;
+    INJECTED.with(|i| i.borrow_mut().insert(key.to_string()));
+    Ok(())
+}
+
+/// Generates a class for `style`, injecting its rule the first time, and
+/// returns the class name. Equal styles share a class.
+pub fn class(style: &Style) -> Result<String, String> {
+    let declarations = style.to_css();
+    let name = format!("g-{:08x}", fnv1a(&declarations));
+    inject_once(&name, &Rule::new(&format!(".{}", name), style.clone()).to_css())?;
+    Ok(name)
+}
+
+/// Injects a `@keyframes` animation once and returns its name for `Style::animation`.
+pub fn keyframes(animation: &Keyframes) -> Result<String, String> {
+    inject_once(&format!("@keyframes {}", animation.name), &animation.to_css())?;
+    Ok(animation.name.clone())
+}
+
+/// Adds a stylesheet to the page through the runtime, the same path used for
+/// component `style` blocks.
+pub fn inject(css: &str) -> Result<(), String> {
+    backend::inject(css)
+}
+
+/// Every stylesheet injected so far, in order. Always empty on the web, where
+/// the CSS lives in the document instead.
+pub fn injected_css() -> String {
+    backend::injected_css()
+}
+
+/// Sets an inline style property on an element by id.
+pub fn set_property(id: &str, property: &str, value: &str) {
+    if let Some(element) = crate::browser::dom::get_element_by_id(id) {
+        let _ = element.set_style(property, value);
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use gigli_runtime_js::dom;
+
+    pub fn inject(css: &str) -> Result<(), String> {
+        dom::inject_style(css).map_err(|e| e.as_string().unwrap_or_else(|| format!("{:?}", e)))
+    }
+
+    pub fn injected_css() -> String {
+        String::new()
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use std::cell::RefCell;
+
+    thread_local! {
+        static SHEETS: RefCell<Vec<String>> = RefCell::new(Vec::new());
+    }
+
+    pub fn inject(css: &str) -> Result<(), String> {
+        SHEETS.with(|s| s.borrow_mut().push(css.to_string()));
+        Ok(())
+    }
+
+    pub fn injected_css() -> String {
+        SHEETS.with(|s| s.borrow().join("\n"))
+    }
+}
@@ -0,0 +1,526 @@
+//! Standard library: Calendar dates and times for Gigli
+//!
+//! A `DateTime` is an instant (milliseconds since the Unix epoch) paired with the
+//! UTC offset used to display it. Calendar fields are computed in that offset.
+
+use crate::time::time::{self, civil_from_days, days_from_civil, format_offset, Duration};
+
+/// Days of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Weekday::Monday => "Monday",
+            Weekday::Tuesday => "Tuesday",
+            Weekday::Wednesday => "Wednesday",
+            Weekday::Thursday => "Thursday",
+            Weekday::Friday => "Friday",
+            Weekday::Saturday => "Saturday",
+            Weekday::Sunday => "Sunday",
+        }
+    }
+
+    fn from_days(days: i64) -> Weekday {
+        // 1970-01-01 was a Thursday.
+        match (days + 3).rem_euclid(7) {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+}
+
+/// An instant in time together with the UTC offset it is displayed in.
+///
+/// Ordering compares instants first, so the same moment in two offsets sorts together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+    timestamp: i64,
+    offset_minutes: i32,
+}
+
+impl DateTime {
+    /// Creates a UTC date-time from milliseconds since the Unix epoch.
+    pub fn from_timestamp(timestamp: i64) -> Self {
+        DateTime { timestamp, offset_minutes: 0 }
+    }
+
+    /// Returns the current time in UTC.
+    pub fn now() -> Self {
+        DateTime::from_timestamp(time::now() as i64)
+    }
+
+    /// Returns the current time in the local timezone.
+    pub fn now_local() -> Self {
+        DateTime::now().to_local()
+    }
+
+    /// Creates a UTC date-time from calendar components.
+    pub fn from_ymd_hms(year: i64, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Result<Self, String> {
+        if !(1..=12).contains(&month) {
+            return Err(format!("Invalid month: {}", month));
+        }
+        if day == 0 || day > days_in_month(year, month) {
+            return Err(format!("Invalid day {} for {}-{:02}", day, year, month));
+        }
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(format!("Invalid time: {:02}:{:02}:{:02}", hour, minute, second));
+        }
+        let days = days_from_civil(year, month, day);
+        let secs = days * 86_400 + hour as i64 * 3600 + minute as i64 * 60 + second as i64;
+        Ok(DateTime::from_timestamp(secs * 1000))
+    }
+
+    /// Parses an ISO-8601 date or date-time such as `2024-02-29`, `2024-02-29T23:59:59Z`
+    /// or `2024-02-29 23:59:59.123+05:30`. A missing offset is taken as UTC.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid ISO-8601 date-time: {}", s);
+        let s = s.trim();
+        let (date, rest) = s.split_at(s.find(['T', 't', ' ']).unwrap_or(s.len()));
+        let mut date_parts = date.splitn(3, '-');
+        let year = parse_number(date_parts.next(), 4).ok_or_else(invalid)?;
+        let month = parse_number(date_parts.next(), 2).ok_or_else(invalid)? as u32;
+        let day = parse_number(date_parts.next(), 2).ok_or_else(invalid)? as u32;
+        if rest.is_empty() {
+            return DateTime::from_ymd_hms(year, month, day, 0, 0, 0);
+        }
+
+        let rest = &rest[1..];
+        let offset_start = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+        let (clock, offset) = rest.split_at(offset_start);
+        let (clock, fraction) = match clock.split_once('.') {
+            Some((clock, fraction)) => (clock, Some(fraction)),
+            None => (clock, None),
+        };
+        let mut clock_parts = clock.splitn(3, ':');
+        let hour = parse_number(clock_parts.next(), 2).ok_or_else(invalid)? as u32;
+        let minute = parse_number(clock_parts.next(), 2).ok_or_else(invalid)? as u32;
+        let second = match clock_parts.next() {
+            Some(part) => parse_number(Some(part), 2).ok_or_else(invalid)? as u32,
+            None => 0,
+        };
+        let millis = match fraction {
+            Some(digits) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+                format!("{:0<3}", &digits[..digits.len().min(3)]).parse::<i64>().map_err(|_| invalid())?
+            }
+            Some(_) => return Err(invalid()),
+            None => 0,
+        };
+        let offset_minutes = match offset {
+            "" | "Z" | "z" => 0,
+            _ => parse_offset(offset).ok_or_else(invalid)?,
+        };
+
+        let utc = DateTime::from_ymd_hms(year, month, day, hour, minute, second)?;
+        Ok(DateTime {
+            timestamp: utc.timestamp + millis - offset_minutes as i64 * 60_000,
+            offset_minutes,
+        })
+    }
+
+    /// Milliseconds since the Unix epoch.
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    /// The UTC offset, in minutes east of UTC, that calendar fields are computed in.
+    pub fn offset_minutes(&self) -> i32 {
+        self.offset_minutes
+    }
+
+    fn local_millis(&self) -> i64 {
+        self.timestamp + self.offset_minutes as i64 * 60_000
+    }
+
+    fn days(&self) -> i64 {
+        self.local_millis().div_euclid(86_400_000)
+    }
+
+    pub fn year(&self) -> i64 {
+        civil_from_days(self.days()).0
+    }
+
+    pub fn month(&self) -> u32 {
+        civil_from_days(self.days()).1
+    }
+
+    pub fn day(&self) -> u32 {
+        civil_from_days(self.days()).2
+    }
+
+    pub fn hour(&self) -> u32 {
+        (self.local_millis().rem_euclid(86_400_000) / 3_600_000) as u32
+    }
+
+    pub fn minute(&self) -> u32 {
+        (self.local_millis().rem_euclid(3_600_000) / 60_000) as u32
+    }
+
+    pub fn second(&self) -> u32 {
+        (self.local_millis().rem_euclid(60_000) / 1000) as u32
+    }
+
+    pub fn millisecond(&self) -> u32 {
+        self.local_millis().rem_euclid(1000) as u32
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        Weekday::from_days(self.days())
+    }
+
+    /// Day of the year, starting at 1 for January 1st.
+    pub fn day_of_year(&self) -> u32 {
+        (self.days() - days_from_civil(self.year(), 1, 1) + 1) as u32
+    }
+
+    /// Returns the date-time a duration later.
+    pub fn add(&self, duration: Duration) -> Self {
+        DateTime {
+            timestamp: self.timestamp + duration.as_millis() as i64,
+            offset_minutes: self.offset_minutes,
+        }
+    }
+
+    /// Returns the date-time a duration earlier.
+    pub fn sub(&self, duration: Duration) -> Self {
+        DateTime {
+            timestamp: self.timestamp - duration.as_millis() as i64,
+            offset_minutes: self.offset_minutes,
+        }
+    }
+
+    /// Adds whole calendar days, which may be negative.
+    pub fn add_days(&self, days: i64) -> Self {
+        DateTime {
+            timestamp: self.timestamp + days * 86_400_000,
+            offset_minutes: self.offset_minutes,
+        }
+    }
+
+    /// Adds calendar months, which may be negative. The day is clamped to the
+    /// length of the target month, so Jan 31 + 1 month is Feb 28 (or 29).
+    pub fn add_months(&self, months: i64) -> Self {
+        let (year, month, day) = civil_from_days(self.days());
+        let total = year * 12 + (month as i64 - 1) + months;
+        let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+        let day = day.min(days_in_month(year, month));
+        let time_of_day = self.local_millis().rem_euclid(86_400_000);
+        let local = days_from_civil(year, month, day) * 86_400_000 + time_of_day;
+        DateTime {
+            timestamp: local - self.offset_minutes as i64 * 60_000,
+            offset_minutes: self.offset_minutes,
+        }
+    }
+
+    /// Returns the time elapsed since an earlier date-time, or zero if `earlier` is later.
+    pub fn duration_since(&self, earlier: &DateTime) -> Duration {
+        Duration::from_millis((self.timestamp - earlier.timestamp).max(0) as u64)
+    }
+
+    /// Returns the same instant displayed in UTC.
+    pub fn to_utc(&self) -> Self {
+        self.with_offset(0)
+    }
+
+    /// Returns the same instant displayed in the local timezone.
+    pub fn to_local(&self) -> Self {
+        self.with_offset(local::offset_minutes(self.timestamp))
+    }
+
+    /// Returns the same instant displayed at a fixed UTC offset (minutes east).
+    pub fn with_offset(&self, offset_minutes: i32) -> Self {
+        DateTime {
+            timestamp: self.timestamp,
+            offset_minutes,
+        }
+    }
+
+    /// Formats as ISO-8601, e.g. `2024-02-29T23:59:59Z` or `2024-02-29T23:59:59.123+05:30`.
+    pub fn to_iso_string(&self) -> String {
+        let fraction = if self.millisecond() == 0 { "" } else { ".%L" };
+        let offset = if self.offset_minutes == 0 {
+            "Z".to_string()
+        } else {
+            format_offset(self.offset_minutes, ":")
+        };
+        format!("{}{}", self.format(&format!("%Y-%m-%dT%H:%M:%S{}", fraction)), offset)
+    }
+
+    /// Formats with strftime-style directives; see `std::time::format`.
+    pub fn format(&self, fmt: &str) -> String {
+        time::format_with_offset(self.timestamp, self.offset_minutes, fmt)
+    }
+}
+
+impl std::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_iso_string())
+    }
+}
+
+/// Returns true for Gregorian leap years.
+pub fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Returns the number of days in a month (1-12) of the given year.
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+fn parse_number(part: Option<&str>, digits: usize) -> Option<i64> {
+    let part = part?;
+    if part.len() != digits || !part.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    part.parse().ok()
+}
+
+/// Parses `+HH:MM`, `+HHMM` or `+HH` into minutes east of UTC.
+fn parse_offset(offset: &str) -> Option<i32> {
+    let sign = match offset.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits: String = offset[1..].chars().filter(|c| *c != ':').collect();
+    let (hours, minutes) = match digits.len() {
+        2 => (parse_number(Some(&digits), 2)?, 0),
+        4 => (parse_number(Some(&digits[..2]), 2)?, parse_number(Some(&digits[2..]), 2)?),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes) as i32)
+}
+
+#[cfg(feature = "wasm")]
+mod local {
+    pub fn offset_minutes(timestamp: i64) -> i32 {
+        gigli_runtime_js::clock::timezone_offset_minutes(timestamp as f64)
+    }
+}
+
+/// Native local time comes from `TZ` or `/etc/localtime` (TZif), without libc.
+#[cfg(not(feature = "wasm"))]
+mod local {
+    use super::days_in_month;
+    use crate::time::time::days_from_civil;
+
+    /// A POSIX TZ rule such as `EST5EDT,M3.2.0,M11.1.0` (offsets in seconds east).
+    struct PosixRule {
+        std_offset: i64,
+        dst: Option<(i64, Transition, Transition)>,
+    }
+
+    /// An `Mm.w.d/time` transition date: month, week (5 = last), weekday (0 = Sunday).
+    struct Transition {
+        month: u32,
+        week: u32,
+        weekday: u32,
+        seconds: i64,
+    }
+
+    pub fn offset_minutes(timestamp: i64) -> i32 {
+        let secs = timestamp.div_euclid(1000);
+        let offset = match std::env::var("TZ") {
+            Ok(tz) if !tz.is_empty() => zone_offset(tz.trim_start_matches(':'), secs),
+            _ => tzif_offset(&std::fs::read("/etc/localtime").unwrap_or_default(), secs),
+        };
+        (offset.unwrap_or(0) / 60) as i32
+    }
+
+    fn zone_offset(tz: &str, secs: i64) -> Option<i64> {
+        if let Some(offset) = parse_posix(tz).map(|rule| rule_offset(&rule, secs)) {
+            return Some(offset);
+        }
+        let data = std::fs::read(std::path::Path::new("/usr/share/zoneinfo").join(tz)).ok()?;
+        tzif_offset(&data, secs)
+    }
+
+    fn read_i64(data: &[u8], at: usize, wide: bool) -> Option<i64> {
+        if wide {
+            Some(i64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+        } else {
+            Some(i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as i64)
+        }
+    }
+
+    /// Looks up the offset for a Unix time in TZif data, using the footer rule for
+    /// times after the last recorded transition.
+    fn tzif_offset(data: &[u8], secs: i64) -> Option<i64> {
+        if data.get(..4)? != b"TZif" {
+            return None;
+        }
+        let counts = |base: usize| -> Option<[usize; 6]> {
+            let mut counts = [0usize; 6];
+            for (i, count) in counts.iter_mut().enumerate() {
+                *count = read_i64(data, base + 20 + i * 4, false)? as usize;
+            }
+            Some(counts)
+        };
+        let [isut, isstd, leap, time, typ, chars] = counts(0)?;
+        let mut base = 0;
+        let mut wide = false;
+        if data[4] >= b'2' {
+            // Skip the 32-bit block and read the 64-bit one that follows.
+            base = 44 + time * 5 + typ * 6 + chars + leap * 8 + isstd + isut;
+            wide = true;
+        }
+        let [isut, isstd, leap, time, typ, chars] = counts(base)?;
+        let width = if wide { 8 } else { 4 };
+        let times_at = base + 44;
+        let indices_at = times_at + time * width;
+        let types_at = indices_at + time;
+        let type_offset = |index: usize| read_i64(data, types_at + index * 6, false);
+
+        let footer_at = types_at + typ * 6 + chars + leap * (width + 4) + isstd + isut;
+        let footer = data
+            .get(footer_at..)
+            .and_then(|rest| std::str::from_utf8(rest).ok())
+            .map(|rest| rest.trim_matches('\n'))
+            .and_then(parse_posix);
+
+        let mut current = None;
+        for i in 0..time {
+            if read_i64(data, times_at + i * width, wide)? > secs {
+                break;
+            }
+            current = Some(i);
+        }
+        match current {
+            Some(i) if i + 1 == time && footer.is_some() => footer.map(|rule| rule_offset(&rule, secs)),
+            Some(i) => type_offset(*data.get(indices_at + i)? as usize),
+            None if time == 0 && footer.is_some() => footer.map(|rule| rule_offset(&rule, secs)),
+            None => type_offset(0),
+        }
+    }
+
+    fn rule_offset(rule: &PosixRule, secs: i64) -> i64 {
+        let Some((dst_offset, start, end)) = &rule.dst else {
+            return rule.std_offset;
+        };
+        let year = super::DateTime::from_timestamp(secs * 1000).year();
+        let start_at = transition_time(year, start) - rule.std_offset;
+        let end_at = transition_time(year, end) - dst_offset;
+        let in_dst = if start_at < end_at {
+            secs >= start_at && secs < end_at
+        } else {
+            // Southern hemisphere: DST spans the new year.
+            secs < end_at || secs >= start_at
+        };
+        if in_dst {
+            *dst_offset
+        } else {
+            rule.std_offset
+        }
+    }
+
+    /// Local wall-clock seconds since the epoch at which a transition happens in `year`.
+    fn transition_time(year: i64, t: &Transition) -> i64 {
+        let first = days_from_civil(year, t.month, 1);
+        let first_weekday = (first + 4).rem_euclid(7) as u32;
+        let mut day = 1 + (t.weekday + 7 - first_weekday) % 7 + (t.week - 1) * 7;
+        while day > days_in_month(year, t.month) {
+            day -= 7;
+        }
+        (first + day as i64 - 1) * 86_400 + t.seconds
+    }
+
+    fn parse_posix(tz: &str) -> Option<PosixRule> {
+        let mut rest = tz;
+        skip_name(&mut rest)?;
+        let std_offset = -parse_hms(&mut rest)?;
+        if rest.is_empty() {
+            return Some(PosixRule { std_offset, dst: None });
+        }
+        skip_name(&mut rest)?;
+        let dst_offset = if rest.starts_with(',') {
+            std_offset + 3600
+        } else {
+            -parse_hms(&mut rest)?
+        };
+        let (start, end) = rest.strip_prefix(',')?.split_once(',')?;
+        Some(PosixRule {
+            std_offset,
+            dst: Some((dst_offset, parse_transition(start)?, parse_transition(end)?)),
+        })
+    }
+
+    fn skip_name(rest: &mut &str) -> Option<()> {
+        let len = if let Some(quoted) = rest.strip_prefix('<') {
+            quoted.find('>')? + 2
+        } else {
+            rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len())
+        };
+        if len < 3 {
+            return None;
+        }
+        *rest = &rest[len..];
+        Some(())
+    }
+
+    /// Parses `[+-]hh[:mm[:ss]]` into seconds.
+    fn parse_hms(rest: &mut &str) -> Option<i64> {
+        let end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == ':' || c == '+' || c == '-'))
+            .unwrap_or(rest.len());
+        let (text, remaining) = rest.split_at(end);
+        *rest = remaining;
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => (-1, digits),
+            None => (1, text.trim_start_matches('+')),
+        };
+        let mut secs = 0;
+        for (i, part) in digits.split(':').enumerate() {
+            let value: i64 = part.parse().ok()?;
+            secs += value * [3600, 60, 1].get(i)?;
+        }
+        Some(sign * secs)
+    }
+
+    fn parse_transition(spec: &str) -> Option<Transition> {
+        let (date, time) = match spec.split_once('/') {
+            Some((date, time)) => (date, Some(time)),
+            None => (spec, None),
+        };
+        let mut fields = date.strip_prefix('M')?.split('.');
+        let month = fields.next()?.parse().ok()?;
+        let week = fields.next()?.parse().ok()?;
+        let weekday = fields.next()?.parse().ok()?;
+        if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+            return None;
+        }
+        let seconds = match time {
+            Some(mut time) => parse_hms(&mut time)?,
+            None => 7200,
+        };
+        Some(Transition {
+            month,
+            week,
+            weekday,
+            seconds,
+        })
+    }
+}
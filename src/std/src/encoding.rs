@@ -0,0 +1,105 @@
+//! Standard library: Binary-to-text encodings for Gigli
+
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes bytes as standard base64 with `=` padding.
+pub fn base64_encode(data: &[u8]) -> String {
+    encode_base64(data, STANDARD, true)
+}
+
+/// Encodes bytes as URL-safe base64 without padding, as used in JWTs.
+pub fn base64url_encode(data: &[u8]) -> String {
+    encode_base64(data, URL_SAFE, false)
+}
+
+/// Decodes standard base64. Padding is optional; whitespace is ignored.
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    decode_base64(text, STANDARD)
+}
+
+/// Decodes URL-safe base64. Padding is optional; whitespace is ignored.
+pub fn base64url_decode(text: &str) -> Result<Vec<u8>, String> {
+    decode_base64(text, URL_SAFE)
+}
+
+fn encode_base64(data: &[u8], alphabet: &[u8; 64], padding: bool) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..=chunk.len() {
+            out.push(alphabet[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        if padding {
+            for _ in chunk.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(text: &str, alphabet: &[u8; 64]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    let mut padding = 0;
+    for (i, c) in text.bytes().enumerate() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        if c == b'=' {
+            padding += 1;
+            continue;
+        }
+        if padding > 0 {
+            return Err(format!("Invalid base64: data after padding at offset {}", i));
+        }
+        let value = alphabet
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| format!("Invalid base64 character '{}' at offset {}", c as char, i))?;
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    if bits >= 6 || padding > 2 {
+        return Err("Invalid base64: truncated input".to_string());
+    }
+    Ok(out)
+}
+
+/// Encodes bytes as lowercase hex.
+pub fn hex_encode(data: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(data.len() * 2);
+    for byte in data {
+        out.push(DIGITS[(byte >> 4) as usize] as char);
+        out.push(DIGITS[(byte & 0xf) as usize] as char);
+    }
+    out
+}
+
+/// Decodes hex in either case.
+pub fn hex_decode(text: &str) -> Result<Vec<u8>, String> {
+    if text.len() % 2 != 0 {
+        return Err("Invalid hex: odd number of digits".to_string());
+    }
+    text.as_bytes()
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| {
+            let digit = |c: u8| (c as char).to_digit(16);
+            match (digit(pair[0]), digit(pair[1])) {
+                (Some(high), Some(low)) => Ok((high * 16 + low) as u8),
+                _ => Err(format!("Invalid hex digit at offset {}", i * 2)),
+            }
+        })
+        .collect()
+}
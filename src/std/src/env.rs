@@ -0,0 +1,125 @@
+//! Standard library: Environment for Gigli
+//!
+//! On native targets this wraps the process environment. The web has no process
+//! environment, so on WASM the variables are the ones injected at build time via
+//! `GIGLI_PUBLIC_ENV` (newline-separated `KEY=value` pairs); `set_var` and
+//! `remove_var` only affect the running page.
+
+use crate::list::List;
+use crate::map::Map;
+
+/// Returns the command-line arguments, starting with the program name.
+/// Always empty on the web.
+pub fn args() -> List<String> {
+    let mut list = List::new();
+    #[cfg(not(feature = "wasm"))]
+    for arg in std::env::args() {
+        list.push(arg);
+    }
+    list
+}
+
+/// Gets an environment variable.
+pub fn var(name: &str) -> Option<String> {
+    #[cfg(not(feature = "wasm"))]
+    return std::env::var(name).ok();
+    #[cfg(feature = "wasm")]
+    return web::VARS.with(|vars| vars.borrow().get(name).cloned());
+}
+
+/// Gets an environment variable, or the given default if it is unset.
+pub fn var_or(name: &str, default: &str) -> String {
+    var(name).unwrap_or_else(|| default.to_string())
+}
+
+/// Sets an environment variable for the current process (or page).
+pub fn set_var(name: &str, value: &str) {
+    #[cfg(not(feature = "wasm"))]
+    std::env::set_var(name, value);
+    #[cfg(feature = "wasm")]
+    web::VARS.with(|vars| vars.borrow_mut().insert(name.to_string(), value.to_string()));
+}
+
+/// Removes an environment variable from the current process (or page).
+pub fn remove_var(name: &str) {
+    #[cfg(not(feature = "wasm"))]
+    std::env::remove_var(name);
+    #[cfg(feature = "wasm")]
+    web::VARS.with(|vars| vars.borrow_mut().remove(name));
+}
+
+/// Returns all environment variables.
+pub fn vars() -> Map<String, String> {
+    let mut map = Map::new();
+    #[cfg(not(feature = "wasm"))]
+    for (name, value) in std::env::vars() {
+        map.insert(name, value);
+    }
+    #[cfg(feature = "wasm")]
+    web::VARS.with(|vars| {
+        for (name, value) in vars.borrow().iter() {
+            map.insert(name.clone(), value.clone());
+        }
+    });
+    map
+}
+
+/// Returns the current working directory. Not available on the web.
+pub fn current_dir() -> Result<String, String> {
+    #[cfg(not(feature = "wasm"))]
+    return std::env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .map_err(|e| format!("Failed to get current directory: {}", e));
+    #[cfg(feature = "wasm")]
+    return Err("The current directory is not available in WASM".to_string());
+}
+
+/// Changes the current working directory. Not available on the web.
+pub fn set_current_dir(path: &str) -> Result<(), String> {
+    #[cfg(not(feature = "wasm"))]
+    return std::env::set_current_dir(path).map_err(|e| format!("Failed to change directory to {}: {}", path, e));
+    #[cfg(feature = "wasm")]
+    return Err(format!("The current directory is not available in WASM: {}", path));
+}
+
+/// The operating system: `linux`, `macos`, `windows`, ..., or `web` in WASM.
+pub fn os() -> &'static str {
+    if cfg!(feature = "wasm") {
+        "web"
+    } else {
+        std::env::consts::OS
+    }
+}
+
+/// The CPU architecture, e.g. `x86_64`, `aarch64` or `wasm32`.
+pub fn arch() -> &'static str {
+    std::env::consts::ARCH
+}
+
+/// The platform family: `unix`, `windows`, or `web` in WASM.
+pub fn family() -> &'static str {
+    if cfg!(feature = "wasm") {
+        "web"
+    } else {
+        std::env::consts::FAMILY
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod web {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    thread_local! {
+        pub static VARS: RefCell<HashMap<String, String>> = RefCell::new(injected());
+    }
+
+    fn injected() -> HashMap<String, String> {
+        option_env!("GIGLI_PUBLIC_ENV")
+            .unwrap_or("")
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+            .collect()
+    }
+}
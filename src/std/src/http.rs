@@ -0,0 +1,377 @@
+//! Standard library: HTTP client for Gigli
+//!
+//! One API for both targets: requests go through the browser's `fetch` on the
+//! web target and over a plain HTTP/1.1 socket on native targets. The native
+//! transport does not speak TLS, so `https://` URLs are only supported on the web.
+
+use crate::map::Map;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Errors produced while building, sending or decoding a request.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HttpError {
+    InvalidUrl(String),
+    Network(String),
+    Unsupported(String),
+    InvalidResponse(String),
+    Json(String),
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpError::InvalidUrl(msg) => write!(f, "invalid URL: {}", msg),
+            HttpError::Network(msg) => write!(f, "network error: {}", msg),
+            HttpError::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+            HttpError::InvalidResponse(msg) => write!(f, "invalid response: {}", msg),
+            HttpError::Json(msg) => write!(f, "JSON error: {}", msg),
+        }
+    }
+}
+
+/// HTTP request methods.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Head,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Head => "HEAD",
+        }
+    }
+}
+
+/// A completed HTTP response.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    /// Header names are lower-cased. The values of a repeated header are
+    /// joined with `, `, except for `Set-Cookie`.
+    pub headers: Map<String, String>,
+    /// The value of each `Set-Cookie` header, which cannot be joined like the
+    /// others. Always empty on the web, where the browser keeps them to itself.
+    pub set_cookies: Vec<String>,
+    pub body: String,
+}
+
+impl Response {
+    /// Returns true for 2xx status codes.
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Gets a header value by case-insensitive name.
+    pub fn header(&self, name: &str) -> Option<&String> {
+        self.headers.get(&name.to_ascii_lowercase())
+    }
+
+    /// Returns the body as text.
+    pub fn text(&self) -> &str {
+        &self.body
+    }
+
+    /// Decodes the body as JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, HttpError> {
+        serde_json::from_str(&self.body).map_err(|e| HttpError::Json(e.to_string()))
+    }
+}
+
+/// A request builder.
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    query: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+impl Request {
+    /// Creates a request with no headers, query parameters or body.
+    pub fn new(method: Method, url: &str) -> Self {
+        Request {
+            method,
+            url: url.to_string(),
+            headers: Vec::new(),
+            query: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Adds a header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds a query parameter; it is percent-encoded when the request is sent.
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets a raw text body.
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_string());
+        self
+    }
+
+    /// Serializes a value as the JSON body and sets the `Content-Type` header.
+    pub fn json<T: Serialize>(mut self, value: &T) -> Result<Self, HttpError> {
+        let body = serde_json::to_string(value).map_err(|e| HttpError::Json(e.to_string()))?;
+        self.body = Some(body);
+        Ok(self.header("Content-Type", "application/json"))
+    }
+
+    /// Returns the URL with query parameters appended.
+    pub fn full_url(&self) -> String {
+        if self.query.is_empty() {
+            return self.url.clone();
+        }
+        let query = self
+            .query
+            .iter()
+            .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        format!("{}{}{}", self.url, separator, query)
+    }
+
+    /// Sends the request and waits for the complete response.
+    ///
+    /// On native targets the socket is used from a thread of its own, and the
+    /// request fails if connecting, or any single read or write, takes longer
+    /// than 30 seconds.
+    pub async fn send(self) -> Result<Response, HttpError> {
+        transport::send(&self).await
+    }
+}
+
+/// Sends a GET request.
+pub async fn get(url: &str) -> Result<Response, HttpError> {
+    Request::new(Method::Get, url).send().await
+}
+
+/// Sends a POST request with a text body.
+pub async fn post(url: &str, body: &str) -> Result<Response, HttpError> {
+    Request::new(Method::Post, url).body(body).send().await
+}
+
+/// Sends a PUT request with a text body.
+pub async fn put(url: &str, body: &str) -> Result<Response, HttpError> {
+    Request::new(Method::Put, url).body(body).send().await
+}
+
+/// Sends a DELETE request.
+pub async fn delete(url: &str) -> Result<Response, HttpError> {
+    Request::new(Method::Delete, url).send().await
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Collects response headers by lower-cased name, joining the values of a
+/// repeated header as HTTP allows. `Set-Cookie` values may contain commas, so
+/// they are returned separately.
+fn collect_headers(headers: Vec<(String, String)>) -> (Map<String, String>, Vec<String>) {
+    let mut map: Map<String, String> = Map::new();
+    let mut set_cookies = Vec::new();
+    for (name, value) in headers {
+        let name = name.to_ascii_lowercase();
+        if name == "set-cookie" {
+            set_cookies.push(value);
+        } else if let Some(joined) = map.get_mut(&name) {
+            joined.push_str(", ");
+            joined.push_str(&value);
+        } else {
+            map.insert(name, value);
+        }
+    }
+    (map, set_cookies)
+}
+
+#[cfg(feature = "wasm")]
+mod transport {
+    use super::*;
+    use crate::browser::js_error;
+
+    pub async fn send(request: &Request) -> Result<Response, HttpError> {
+        let response = gigli_runtime_js::fetch::fetch(
+            request.method.as_str(),
+            &request.full_url(),
+            &request.headers,
+            request.body.as_deref(),
+        )
+        .await
+        .map_err(|e| HttpError::Network(js_error(e)))?;
+        let (headers, set_cookies) = collect_headers(response.headers);
+        Ok(Response {
+            status: response.status,
+            headers,
+            set_cookies,
+            body: response.body,
+        })
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod transport {
+    use super::*;
+    use crate::task::channel;
+    use std::io::{Read, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::time::Duration;
+
+    /// How long connecting, and each read or write, may take.
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub async fn send(request: &Request) -> Result<Response, HttpError> {
+        // The socket calls block, so they run on their own thread while the
+        // calling task waits for the result without holding up the others
+        let request = request.clone();
+        let (sender, mut receiver) = channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(exchange(&request));
+        });
+        match receiver.recv().await {
+            Some(result) => result,
+            None => Err(HttpError::Network("the request thread panicked".to_string())),
+        }
+    }
+
+    /// Sends `request` and reads the whole response, blocking until it is done.
+    fn exchange(request: &Request) -> Result<Response, HttpError> {
+        let url = request.full_url();
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None if url.starts_with("https://") => {
+                return Err(HttpError::Unsupported("https is only available on the web target".to_string()))
+            }
+            None => return Err(HttpError::InvalidUrl(url)),
+        };
+        let (authority, path) = match rest.find(|c| c == '/' || c == '?') {
+            Some(idx) if rest[idx..].starts_with('?') => (&rest[..idx], format!("/{}", &rest[idx..])),
+            Some(idx) => (&rest[..idx], rest[idx..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        if authority.is_empty() {
+            return Err(HttpError::InvalidUrl(url.clone()));
+        }
+        let address = if authority.contains(':') {
+            authority.to_string()
+        } else {
+            format!("{}:80", authority)
+        };
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+            request.method.as_str(),
+            path,
+            authority
+        );
+        for (name, value) in &request.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        let body = request.body.as_deref().unwrap_or("");
+        if request.body.is_some() {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        head.push_str("\r\n");
+
+        let network = |e: std::io::Error| HttpError::Network(e.to_string());
+        let mut stream = connect(&address).map_err(network)?;
+        stream.set_read_timeout(Some(TIMEOUT)).map_err(network)?;
+        stream.set_write_timeout(Some(TIMEOUT)).map_err(network)?;
+        stream.write_all(head.as_bytes()).map_err(network)?;
+        stream.write_all(body.as_bytes()).map_err(network)?;
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).map_err(network)?;
+        parse_response(&raw)
+    }
+
+    /// Connects to the first address `address` resolves to that accepts
+    /// within the timeout.
+    fn connect(address: &str) -> std::io::Result<TcpStream> {
+        let mut last_error = None;
+        for socket in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&socket, TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("{} has no address", address))))
+    }
+
+    fn parse_response(raw: &[u8]) -> Result<Response, HttpError> {
+        let invalid = |msg: &str| HttpError::InvalidResponse(msg.to_string());
+        let split = raw
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .ok_or_else(|| invalid("missing header terminator"))?;
+        let head = String::from_utf8_lossy(&raw[..split]);
+        let mut lines = head.split("\r\n");
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| invalid("malformed status line"))?;
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        let chunked = headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("transfer-encoding") && value.eq_ignore_ascii_case("chunked")
+        });
+        let payload = &raw[split + 4..];
+        let body = if chunked { decode_chunked(payload)? } else { payload.to_vec() };
+        let (headers, set_cookies) = collect_headers(headers);
+        Ok(Response {
+            status,
+            headers,
+            set_cookies,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+    }
+
+    fn decode_chunked(mut payload: &[u8]) -> Result<Vec<u8>, HttpError> {
+        let invalid = || HttpError::InvalidResponse("malformed chunked body".to_string());
+        let mut body = Vec::new();
+        loop {
+            let line_end = payload.windows(2).position(|w| w == b"\r\n").ok_or_else(invalid)?;
+            let size_line = String::from_utf8_lossy(&payload[..line_end]);
+            let size_hex = size_line.split(';').next().unwrap_or("").trim();
+            let size = usize::from_str_radix(size_hex, 16).map_err(|_| invalid())?;
+            payload = &payload[line_end + 2..];
+            if size == 0 {
+                return Ok(body);
+            }
+            if payload.len() < size + 2 {
+                return Err(invalid());
+            }
+            body.extend_from_slice(&payload[..size]);
+            payload = &payload[size + 2..];
+        }
+    }
+}
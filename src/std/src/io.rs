@@ -1,27 +1,134 @@
 //! Standard library: IO for Gigli
+//!
+//! Native targets use the filesystem and standard streams. In WASM there is no
+//! filesystem or stdin, so those functions return an error, while printing is
+//! routed to the browser console.
 
 pub mod io {
+    use crate::list::List;
+
+    #[cfg(feature = "wasm")]
+    const NO_FS: &str = "File I/O is not available in WASM";
+
     /// Reads the contents of a file into a string.
-    pub fn read_to_string(_path: &str) -> String {
-        // TODO: Implement for native targets; not available in WASM
-        unimplemented!("File I/O is not available in WASM");
+    pub fn read_to_string(path: &str) -> Result<String, String> {
+        #[cfg(not(feature = "wasm"))]
+        return std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e));
+        #[cfg(feature = "wasm")]
+        return Err(format!("{}: {}", NO_FS, path));
+    }
+
+    /// Writes a string to a file, replacing any existing contents.
+    pub fn write_string(path: &str, contents: &str) -> Result<(), String> {
+        #[cfg(not(feature = "wasm"))]
+        return std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path, e));
+        #[cfg(feature = "wasm")]
+        {
+            let _ = contents;
+            return Err(format!("{}: {}", NO_FS, path));
+        }
+    }
+
+    /// Appends a string to a file, creating it if it does not exist.
+    pub fn append_string(path: &str, contents: &str) -> Result<(), String> {
+        #[cfg(not(feature = "wasm"))]
+        {
+            use std::io::Write;
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+            return file
+                .write_all(contents.as_bytes())
+                .map_err(|e| format!("Failed to append to {}: {}", path, e));
+        }
+        #[cfg(feature = "wasm")]
+        {
+            let _ = contents;
+            return Err(format!("{}: {}", NO_FS, path));
+        }
+    }
+
+    /// Removes a file.
+    pub fn remove_file(path: &str) -> Result<(), String> {
+        #[cfg(not(feature = "wasm"))]
+        return std::fs::remove_file(path).map_err(|e| format!("Failed to remove {}: {}", path, e));
+        #[cfg(feature = "wasm")]
+        return Err(format!("{}: {}", NO_FS, path));
+    }
+
+    /// Returns true if a file or directory exists at the given path.
+    pub fn exists(path: &str) -> bool {
+        #[cfg(not(feature = "wasm"))]
+        return std::path::Path::new(path).exists();
+        #[cfg(feature = "wasm")]
+        {
+            let _ = path;
+            return false;
+        }
+    }
+
+    /// Lists the entry names in a directory, sorted alphabetically.
+    pub fn read_dir(path: &str) -> Result<List<String>, String> {
+        #[cfg(not(feature = "wasm"))]
+        {
+            let entries = std::fs::read_dir(path).map_err(|e| format!("Failed to read directory {}: {}", path, e))?;
+            let mut names = List::new();
+            for entry in entries {
+                let entry = entry.map_err(|e| format!("Failed to read directory {}: {}", path, e))?;
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+            names.sort();
+            return Ok(names);
+        }
+        #[cfg(feature = "wasm")]
+        return Err(format!("{}: {}", NO_FS, path));
+    }
+
+    /// Reads a line from standard input, without the trailing newline.
+    /// Returns an empty string at end of input.
+    pub fn read_line() -> Result<String, String> {
+        #[cfg(not(feature = "wasm"))]
+        {
+            let mut line = String::new();
+            std::io::stdin()
+                .read_line(&mut line)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            let trimmed = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(trimmed);
+            return Ok(line);
+        }
+        #[cfg(feature = "wasm")]
+        return Err("Stdin is not available in WASM".to_string());
     }
 
-    /// Writes a string to a file.
-    pub fn write_string(_path: &str, _contents: &str) {
-        // TODO: Implement for native targets; not available in WASM
-        unimplemented!("File I/O is not available in WASM");
+    /// Writes a string to standard output. In WASM, logs it to the console.
+    pub fn print(s: &str) {
+        #[cfg(not(feature = "wasm"))]
+        {
+            use std::io::Write;
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(s.as_bytes());
+            let _ = stdout.flush();
+        }
+        #[cfg(feature = "wasm")]
+        gigli_runtime_js::console::log(s);
     }
 
-    /// Reads a line from standard input.
-    pub fn read_line() -> String {
-        // TODO: Implement for native targets; not available in WASM
-        unimplemented!("Stdin is not available in WASM");
+    /// Writes a string and a newline to standard output. In WASM, logs it to the console.
+    pub fn println(s: &str) {
+        #[cfg(not(feature = "wasm"))]
+        println!("{}", s);
+        #[cfg(feature = "wasm")]
+        gigli_runtime_js::console::log(s);
     }
 
-    /// Writes a string to standard output.
-    pub fn print(_s: &str) {
-        // TODO: Implement for native targets; in WASM, use console.log
-        unimplemented!("Stdout is not available in WASM");
+    /// Writes a string and a newline to standard error. In WASM, logs it with `console.error`.
+    pub fn eprintln(s: &str) {
+        #[cfg(not(feature = "wasm"))]
+        eprintln!("{}", s);
+        #[cfg(feature = "wasm")]
+        gigli_runtime_js::console::error(s);
     }
 }
@@ -0,0 +1,299 @@
+//! Standard library: Lazy iteration for Gigli collections
+//!
+//! Adapters do no work until a consuming method (`fold`, `count`, `collect`, ...)
+//! pulls values through them, so chains like `list.gigli_iter().filter(..).map(..)`
+//! never allocate intermediate collections.
+
+use crate::list::List;
+use crate::map::Map;
+use crate::set::Set;
+
+/// The iterator protocol shared by std collections and comprehension lowering.
+pub trait GigliIterator: Sized {
+    type Item;
+
+    /// Advances the iterator and returns the next value.
+    fn next(&mut self) -> Option<Self::Item>;
+
+    /// Lazily applies `f` to every value.
+    fn map<U, F>(self, f: F) -> MapIter<Self, F>
+    where
+        F: FnMut(Self::Item) -> U,
+    {
+        MapIter { inner: self, f }
+    }
+
+    /// Lazily keeps only the values matching the predicate.
+    fn filter<F>(self, predicate: F) -> Filter<Self, F>
+    where
+        F: FnMut(&Self::Item) -> bool,
+    {
+        Filter { inner: self, predicate }
+    }
+
+    /// Yields at most `n` values.
+    fn take(self, n: usize) -> Take<Self> {
+        Take { inner: self, remaining: n }
+    }
+
+    /// Skips the first `n` values.
+    fn skip(self, n: usize) -> Skip<Self> {
+        Skip { inner: self, remaining: n }
+    }
+
+    /// Pairs values with those of another iterator, stopping at the shorter one.
+    fn zip<J: GigliIterator>(self, other: J) -> Zip<Self, J> {
+        Zip { a: self, b: other }
+    }
+
+    /// Pairs each value with its index.
+    fn enumerate(self) -> Enumerate<Self> {
+        Enumerate { inner: self, index: 0 }
+    }
+
+    /// Yields the values of this iterator followed by those of another.
+    fn chain<J>(self, other: J) -> Chain<Self, J>
+    where
+        J: GigliIterator<Item = Self::Item>,
+    {
+        Chain { a: Some(self), b: other }
+    }
+
+    /// Reduces the values to a single accumulator.
+    fn fold<A, F>(mut self, init: A, mut f: F) -> A
+    where
+        F: FnMut(A, Self::Item) -> A,
+    {
+        let mut acc = init;
+        while let Some(value) = self.next() {
+            acc = f(acc, value);
+        }
+        acc
+    }
+
+    /// Calls `f` on every value.
+    fn for_each<F>(self, mut f: F)
+    where
+        F: FnMut(Self::Item),
+    {
+        self.fold((), |_, value| f(value));
+    }
+
+    /// Consumes the iterator and returns the number of values.
+    fn count(self) -> usize {
+        self.fold(0, |n, _| n + 1)
+    }
+
+    /// Returns the first value matching the predicate.
+    fn find<F>(mut self, mut predicate: F) -> Option<Self::Item>
+    where
+        F: FnMut(&Self::Item) -> bool,
+    {
+        while let Some(value) = self.next() {
+            if predicate(&value) {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Collects the values into a List, Map or Set.
+    fn collect<C: FromGigliIterator<Self::Item>>(self) -> C {
+        C::from_gigli_iter(self)
+    }
+
+    /// Adapts this iterator to the Rust `Iterator` trait, e.g. for use in `for` loops.
+    fn into_std(self) -> IntoStd<Self> {
+        IntoStd { inner: self }
+    }
+}
+
+/// Collections that can be built from a `GigliIterator`.
+pub trait FromGigliIterator<T> {
+    fn from_gigli_iter<I: GigliIterator<Item = T>>(iter: I) -> Self;
+}
+
+impl<T> FromGigliIterator<T> for List<T> {
+    fn from_gigli_iter<I: GigliIterator<Item = T>>(iter: I) -> Self {
+        iter.fold(List::new(), |mut list, value| {
+            list.push(value);
+            list
+        })
+    }
+}
+
+impl<T: std::cmp::Eq + std::hash::Hash> FromGigliIterator<T> for Set<T> {
+    fn from_gigli_iter<I: GigliIterator<Item = T>>(iter: I) -> Self {
+        iter.fold(Set::new(), |mut set, value| {
+            set.insert(value);
+            set
+        })
+    }
+}
+
+impl<K: std::cmp::Eq + std::hash::Hash, V> FromGigliIterator<(K, V)> for Map<K, V> {
+    fn from_gigli_iter<I: GigliIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.fold(Map::new(), |mut map, (key, value)| {
+            map.insert(key, value);
+            map
+        })
+    }
+}
+
+/// Wraps any Rust iterator as a `GigliIterator`.
+pub struct FromStd<I> {
+    inner: I,
+}
+
+impl<I: Iterator> GigliIterator for FromStd<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// Creates a `GigliIterator` from anything iterable.
+pub fn from_iter<I: IntoIterator>(iterable: I) -> FromStd<I::IntoIter> {
+    FromStd { inner: iterable.into_iter() }
+}
+
+/// Yields the integers in `start..end`; this is what `for i in 0..10` lowers to.
+pub fn range(start: i64, end: i64) -> FromStd<std::ops::Range<i64>> {
+    from_iter(start..end)
+}
+
+/// Adapter returned by `GigliIterator::into_std`.
+pub struct IntoStd<I> {
+    inner: I,
+}
+
+impl<I: GigliIterator> Iterator for IntoStd<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// Adapter returned by `GigliIterator::map`.
+pub struct MapIter<I, F> {
+    inner: I,
+    f: F,
+}
+
+impl<U, I: GigliIterator, F: FnMut(I::Item) -> U> GigliIterator for MapIter<I, F> {
+    type Item = U;
+
+    fn next(&mut self) -> Option<U> {
+        self.inner.next().map(&mut self.f)
+    }
+}
+
+/// Adapter returned by `GigliIterator::filter`.
+pub struct Filter<I, F> {
+    inner: I,
+    predicate: F,
+}
+
+impl<I: GigliIterator, F: FnMut(&I::Item) -> bool> GigliIterator for Filter<I, F> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        while let Some(value) = self.inner.next() {
+            if (self.predicate)(&value) {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+/// Adapter returned by `GigliIterator::take`.
+pub struct Take<I> {
+    inner: I,
+    remaining: usize,
+}
+
+impl<I: GigliIterator> GigliIterator for Take<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.inner.next()
+    }
+}
+
+/// Adapter returned by `GigliIterator::skip`.
+pub struct Skip<I> {
+    inner: I,
+    remaining: usize,
+}
+
+impl<I: GigliIterator> GigliIterator for Skip<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            self.inner.next()?;
+        }
+        self.inner.next()
+    }
+}
+
+/// Adapter returned by `GigliIterator::zip`.
+pub struct Zip<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A: GigliIterator, B: GigliIterator> GigliIterator for Zip<A, B> {
+    type Item = (A::Item, B::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let a = self.a.next()?;
+        let b = self.b.next()?;
+        Some((a, b))
+    }
+}
+
+/// Adapter returned by `GigliIterator::enumerate`.
+pub struct Enumerate<I> {
+    inner: I,
+    index: usize,
+}
+
+impl<I: GigliIterator> GigliIterator for Enumerate<I> {
+    type Item = (usize, I::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.inner.next()?;
+        let index = self.index;
+        self.index += 1;
+        Some((index, value))
+    }
+}
+
+/// Adapter returned by `GigliIterator::chain`.
+pub struct Chain<A, B> {
+    a: Option<A>,
+    b: B,
+}
+
+impl<A: GigliIterator, B: GigliIterator<Item = A::Item>> GigliIterator for Chain<A, B> {
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<A::Item> {
+        if let Some(a) = &mut self.a {
+            match a.next() {
+                Some(value) => return Some(value),
+                None => self.a = None,
+            }
+        }
+        self.b.next()
+    }
+}
@@ -3,20 +3,46 @@
 //! This crate provides the standard library functionality including:
 //! - Browser APIs
 //! - Core data structures and algorithms
-//! - I/O operations
+//! - String utilities and regular expressions
+//! - I/O operations and HTTP
 //! - System interfaces
+//!
+//! The `wasm` feature builds it for the web target, where browser APIs are
+//! backed by the JavaScript runtime; without it, the native implementations
+//! are used.
 
 pub mod browser;
+pub mod css;
 pub mod list;
 pub mod map;
+pub mod set;
+pub mod btree;
+pub mod iter;
 pub mod option;
 pub mod result;
 pub mod io;
 pub mod time;
+pub mod datetime;
+pub mod task;
+pub mod stream;
+pub mod reactive;
+pub mod env;
+pub mod random;
+pub mod crypto;
+pub mod encoding;
+pub mod uuid;
+#[cfg(not(feature = "wasm"))]
+pub mod process;
+pub mod string;
+pub mod regex;
+pub mod http;
 
 // Re-export commonly used types
 pub use browser::*;
 pub use list::*;
 pub use map::*;
+pub use set::*;
+pub use btree::*;
+pub use iter::{FromGigliIterator, GigliIterator};
 pub use option::*;
 pub use result::*;
@@ -1,5 +1,7 @@
 //! Standard library: List<T> for Gigli
 
+use crate::iter::{self, FromStd};
+
 #[derive(Debug, Clone)]
 pub struct List<T> {
     data: Vec<T>,
@@ -58,6 +60,169 @@ impl<T> List<T> {
         self.data.iter_mut()
     }
 
+    /// Returns a lazy `GigliIterator` over references to the elements.
+    pub fn gigli_iter(&self) -> FromStd<std::slice::Iter<T>> {
+        iter::from_iter(self.data.iter())
+    }
+
+    /// Gets a reference to the first element, if any.
+    pub fn first(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    /// Gets a reference to the last element, if any.
+    pub fn last(&self) -> Option<&T> {
+        self.data.last()
+    }
+
+    /// Inserts an element at the given index, shifting later elements right.
+    /// Indices past the end append to the list.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let index = index.min(self.data.len());
+        self.data.insert(index, value);
+    }
+
+    /// Removes and returns the element at the given index, if it exists.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index < self.data.len() {
+            Some(self.data.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Removes all elements from the list.
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns true if the list contains the given value.
+    pub fn contains(&self, value: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.data.contains(value)
+    }
+
+    /// Returns the first element matching the predicate.
+    pub fn find<F>(&self, mut f: F) -> Option<&T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.data.iter().find(|x| f(x))
+    }
+
+    /// Returns the index of the first element matching the predicate.
+    pub fn position<F>(&self, f: F) -> Option<usize>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.data.iter().position(f)
+    }
+
+    /// Sorts the list in ascending order. The sort is stable.
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.data.sort();
+    }
+
+    /// Sorts the list with a comparator. The sort is stable.
+    pub fn sort_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&T, &T) -> std::cmp::Ordering,
+    {
+        self.data.sort_by(compare);
+    }
+
+    /// Sorts the list by a key extracted from each element. The sort is stable.
+    pub fn sort_by_key<K, F>(&mut self, f: F)
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.data.sort_by_key(f);
+    }
+
+    /// Swaps the elements at two indices. Out-of-range indices leave the list unchanged.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        if a < self.data.len() && b < self.data.len() {
+            self.data.swap(a, b);
+        }
+    }
+
+    /// Reverses the order of the elements in place.
+    pub fn reverse(&mut self) {
+        self.data.reverse();
+    }
+
+    /// Removes consecutive duplicate elements.
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.data.dedup();
+    }
+
+    /// Reduces the list to a single value, starting from an initial accumulator.
+    pub fn fold<A, F>(&self, init: A, f: F) -> A
+    where
+        F: FnMut(A, &T) -> A,
+    {
+        self.data.iter().fold(init, f)
+    }
+
+    /// Reduces the list using its first element as the initial accumulator.
+    /// Returns None for an empty list.
+    pub fn reduce<F>(&self, mut f: F) -> Option<T>
+    where
+        T: Clone,
+        F: FnMut(T, &T) -> T,
+    {
+        let (first, rest) = self.data.split_first()?;
+        Some(rest.iter().fold(first.clone(), |acc, x| f(acc, x)))
+    }
+
+    /// Returns a new List with the elements in `start..end`, clamped to the list bounds.
+    pub fn slice(&self, start: usize, end: usize) -> List<T>
+    where
+        T: Clone,
+    {
+        let end = end.min(self.data.len());
+        let start = start.min(end);
+        List {
+            data: self.data[start..end].to_vec(),
+        }
+    }
+
+    /// Returns a new List with the elements of this list followed by those of another.
+    pub fn concat(&self, other: &List<T>) -> List<T>
+    where
+        T: Clone,
+    {
+        let mut data = self.data.clone();
+        data.extend(other.data.iter().cloned());
+        List { data }
+    }
+
+    /// Splits the list into lists of `size` elements; the last chunk may be shorter.
+    pub fn chunks(&self, size: usize) -> List<List<T>>
+    where
+        T: Clone,
+    {
+        if size == 0 {
+            return List::new();
+        }
+        List {
+            data: self
+                .data
+                .chunks(size)
+                .map(|chunk| List { data: chunk.to_vec() })
+                .collect(),
+        }
+    }
+
     /// Filters the list, returning a new List with elements that match the predicate.
     pub fn filter<F>(&self, mut f: F) -> List<T>
     where
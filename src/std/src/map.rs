@@ -1,8 +1,12 @@
 //! Standard library: Map<K, V> for Gigli
 
+use crate::iter::{self, FromStd};
+use crate::list::List;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent, bound(deserialize = "K: Deserialize<'de> + Eq + std::hash::Hash, V: Deserialize<'de>"))]
 pub struct Map<K, V> {
     data: HashMap<K, V>,
 }
@@ -33,6 +37,14 @@ impl<K: std::cmp::Eq + std::hash::Hash, V> Map<K, V> {
         self.data.remove(key)
     }
 
+    /// Returns the value for the given key, inserting the result of `f` if it is missing.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.data.entry(key).or_insert_with(f)
+    }
+
     /// Returns true if the map contains the given key.
     pub fn contains_key(&self, key: &K) -> bool {
         self.data.contains_key(key)
@@ -57,4 +69,123 @@ impl<K: std::cmp::Eq + std::hash::Hash, V> Map<K, V> {
     pub fn iter_mut(&mut self) -> std::collections::hash_map::IterMut<K, V> {
         self.data.iter_mut()
     }
+
+    /// Returns a lazy `GigliIterator` over the key-value pairs.
+    pub fn gigli_iter(&self) -> FromStd<std::collections::hash_map::Iter<K, V>> {
+        iter::from_iter(self.data.iter())
+    }
+
+    /// Collects the keys into a list, in unspecified order.
+    pub fn keys(&self) -> List<K>
+    where
+        K: Clone,
+    {
+        self.data.keys().cloned().fold(List::new(), |mut list, key| {
+            list.push(key);
+            list
+        })
+    }
+
+    /// Collects the values into a list, in unspecified order.
+    pub fn values(&self) -> List<V>
+    where
+        V: Clone,
+    {
+        self.data.values().cloned().fold(List::new(), |mut list, value| {
+            list.push(value);
+            list
+        })
+    }
+
+    /// Collects the key-value pairs into a list, in unspecified order.
+    pub fn entries(&self) -> List<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.data.iter().fold(List::new(), |mut list, (key, value)| {
+            list.push((key.clone(), value.clone()));
+            list
+        })
+    }
+
+    /// Creates a map from a list of key-value pairs; later pairs win on duplicate keys.
+    pub fn from_pairs(pairs: &List<(K, V)>) -> Map<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        Map {
+            data: pairs.iter().cloned().collect(),
+        }
+    }
+
+    /// Moves every entry of another map into this one, overwriting existing keys.
+    pub fn extend(&mut self, other: Map<K, V>) {
+        self.data.extend(other.data);
+    }
+
+    /// Moves every entry of another map into this one, combining values with `f`
+    /// when a key exists in both.
+    pub fn merge<F>(&mut self, other: Map<K, V>, mut f: F)
+    where
+        F: FnMut(&K, V, V) -> V,
+    {
+        for (key, value) in other.data {
+            let merged = match self.data.remove(&key) {
+                Some(existing) => f(&key, existing, value),
+                None => value,
+            };
+            self.data.insert(key, merged);
+        }
+    }
+
+    /// Keeps only the entries matching the predicate.
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.data.retain(f);
+    }
+
+    /// Filters the map, returning a new Map with entries that match the predicate.
+    pub fn filter<F>(&self, mut f: F) -> Map<K, V>
+    where
+        K: Clone,
+        V: Clone,
+        F: FnMut(&K, &V) -> bool,
+    {
+        Map {
+            data: self
+                .data
+                .iter()
+                .filter(|(k, v)| f(k, v))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
+    /// Maps the values, returning a new Map with the same keys.
+    pub fn map<U, F>(&self, mut f: F) -> Map<K, U>
+    where
+        K: Clone,
+        F: FnMut(&K, &V) -> U,
+    {
+        Map {
+            data: self.data.iter().map(|(k, v)| (k.clone(), f(k, v))).collect(),
+        }
+    }
+}
+
+impl<K: std::fmt::Display, V: std::fmt::Display> std::fmt::Display for Map<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.data.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", key, value)?;
+        }
+        write!(f, "}}")
+    }
 }
@@ -1,5 +1,7 @@
 //! Standard library: Option<T> for Gigli
 
+use crate::result::Result;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Option<T> {
     Some(T),
@@ -54,4 +56,41 @@ impl<T> Option<T> {
             Option::None => Option::None,
         }
     }
+
+    /// Converts to a Result, mapping None to Err(err).
+    pub fn ok_or<E>(self, err: E) -> Result<T, E> {
+        match self {
+            Option::Some(v) => Result::Ok(v),
+            Option::None => Result::Err(err),
+        }
+    }
+
+    /// Converts to a Result, mapping None to the error computed by f.
+    pub fn ok_or_else<E, F>(self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> E,
+    {
+        match self {
+            Option::Some(v) => Result::Ok(v),
+            Option::None => Result::Err(f()),
+        }
+    }
+}
+
+impl<T> From<std::option::Option<T>> for Option<T> {
+    fn from(option: std::option::Option<T>) -> Self {
+        match option {
+            Some(v) => Option::Some(v),
+            None => Option::None,
+        }
+    }
+}
+
+impl<T> From<Option<T>> for std::option::Option<T> {
+    fn from(option: Option<T>) -> Self {
+        match option {
+            Option::Some(v) => Some(v),
+            Option::None => None,
+        }
+    }
 }
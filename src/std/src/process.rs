@@ -0,0 +1,238 @@
+//! Standard library: Processes for Gigli (native targets only)
+//!
+//! Spawned processes always have piped stdin, stdout and stderr so their output
+//! can be captured as text.
+
+use crate::list::List;
+use std::io::{Read, Write};
+use std::process::Stdio;
+
+/// The exit status of a finished process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus {
+    /// The exit code, or None if the process was terminated by a signal.
+    pub code: Option<i32>,
+}
+
+impl ExitStatus {
+    /// Returns true if the process exited with code 0.
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl From<std::process::ExitStatus> for ExitStatus {
+    fn from(status: std::process::ExitStatus) -> Self {
+        ExitStatus { code: status.code() }
+    }
+}
+
+/// The captured result of a finished process.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// A process builder.
+#[derive(Debug, Clone)]
+pub struct Command {
+    program: String,
+    args: List<String>,
+    envs: Vec<(String, String)>,
+    current_dir: Option<String>,
+    stdin: Option<String>,
+}
+
+impl Command {
+    /// Creates a command for the given program, looked up on the PATH.
+    pub fn new(program: &str) -> Self {
+        Command {
+            program: program.to_string(),
+            args: List::new(),
+            envs: Vec::new(),
+            current_dir: None,
+            stdin: None,
+        }
+    }
+
+    /// Adds an argument.
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Adds several arguments.
+    pub fn args(mut self, args: &[&str]) -> Self {
+        for arg in args {
+            self.args.push(arg.to_string());
+        }
+        self
+    }
+
+    /// Sets an environment variable for the child.
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.envs.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the child's working directory.
+    pub fn current_dir(mut self, dir: &str) -> Self {
+        self.current_dir = Some(dir.to_string());
+        self
+    }
+
+    /// Text written to the child's stdin by `output` and `status`.
+    pub fn stdin(mut self, input: &str) -> Self {
+        self.stdin = Some(input.to_string());
+        self
+    }
+
+    /// Starts the process without waiting for it.
+    pub fn spawn(&self) -> Result<Child, String> {
+        let mut command = std::process::Command::new(&self.program);
+        command
+            .args(self.args.iter())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for (name, value) in &self.envs {
+            command.env(name, value);
+        }
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        let inner = command
+            .spawn()
+            .map_err(|e| format!("Failed to spawn {}: {}", self.program, e))?;
+        Ok(Child { inner })
+    }
+
+    /// Runs the process to completion, capturing stdout and stderr.
+    pub fn output(&self) -> Result<Output, String> {
+        let mut child = self.spawn()?;
+        // Feed stdin from another thread so a child that fills its stdout pipe
+        // before reading all of its input cannot deadlock us.
+        let writer = match (self.stdin.clone(), child.inner.stdin.take()) {
+            (Some(input), Some(mut stdin)) => Some(std::thread::spawn(move || stdin.write_all(input.as_bytes()))),
+            _ => None,
+        };
+        let output = child.wait_with_output()?;
+        if let Some(writer) = writer {
+            // A child that exits without reading its input is not an error.
+            let _ = writer.join();
+        }
+        Ok(output)
+    }
+
+    /// Runs the process to completion and returns its exit status.
+    pub fn status(&self) -> Result<ExitStatus, String> {
+        self.output().map(|output| output.status)
+    }
+}
+
+/// A running process.
+#[derive(Debug)]
+pub struct Child {
+    inner: std::process::Child,
+}
+
+impl Child {
+    /// The operating system process id.
+    pub fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    /// Writes text to the child's stdin.
+    pub fn write_stdin(&mut self, input: &str) -> Result<(), String> {
+        let stdin = self.inner.stdin.as_mut().ok_or("The child's stdin is closed")?;
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("Failed to write to stdin: {}", e))
+    }
+
+    /// Closes the child's stdin, signalling end of input.
+    pub fn close_stdin(&mut self) {
+        self.inner.stdin.take();
+    }
+
+    /// Reads a chunk of the child's stdout, blocking until data is available.
+    /// Returns an empty string once the stream is closed.
+    pub fn read_stdout(&mut self) -> Result<String, String> {
+        let stdout = self.inner.stdout.as_mut().ok_or("The child's stdout is closed")?;
+        let mut buffer = [0u8; 8192];
+        let n = stdout
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read stdout: {}", e))?;
+        Ok(String::from_utf8_lossy(&buffer[..n]).into_owned())
+    }
+
+    /// Returns the exit status if the process has finished, without blocking.
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, String> {
+        self.inner
+            .try_wait()
+            .map(|status| status.map(ExitStatus::from))
+            .map_err(|e| format!("Failed to poll process: {}", e))
+    }
+
+    /// Waits for the process to exit, discarding any unread output. The output
+    /// is read while waiting, so a child filling a pipe cannot block forever.
+    pub fn wait(&mut self) -> Result<ExitStatus, String> {
+        self.close_stdin();
+        let stdout = self.inner.stdout.take().map(discard);
+        let stderr = self.inner.stderr.take().map(discard);
+        let status = self
+            .inner
+            .wait()
+            .map(ExitStatus::from)
+            .map_err(|e| format!("Failed to wait for process: {}", e));
+        for reader in stdout.into_iter().chain(stderr) {
+            let _ = reader.join();
+        }
+        status
+    }
+
+    /// Waits for the process to exit and collects the rest of its output.
+    pub fn wait_with_output(mut self) -> Result<Output, String> {
+        self.close_stdin();
+        let output = self
+            .inner
+            .wait_with_output()
+            .map_err(|e| format!("Failed to wait for process: {}", e))?;
+        Ok(Output {
+            status: output.status.into(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    /// Kills the process.
+    pub fn kill(&mut self) -> Result<(), String> {
+        self.inner
+            .kill()
+            .map_err(|e| format!("Failed to kill process {}: {}", self.inner.id(), e))
+    }
+}
+
+/// Reads `pipe` to its end on another thread, throwing the data away.
+fn discard(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let _ = std::io::copy(&mut pipe, &mut std::io::sink());
+    })
+}
+
+/// Runs a program with arguments to completion, capturing its output.
+pub fn run(program: &str, args: &[&str]) -> Result<Output, String> {
+    Command::new(program).args(args).output()
+}
+
+/// The current process id.
+pub fn id() -> u32 {
+    std::process::id()
+}
+
+/// Exits the current process with the given code.
+pub fn exit(code: i32) -> ! {
+    std::process::exit(code)
+}
@@ -0,0 +1,188 @@
+//! Standard library: Random numbers for Gigli
+//!
+//! `Rng` is xoshiro256** seeded through SplitMix64, implemented in plain integer
+//! arithmetic so a given seed produces the same sequence on WASM and native.
+//! It is not cryptographically secure.
+
+use crate::list::List;
+use std::cell::RefCell;
+
+/// A seedable pseudo-random number generator.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// Creates a generator with a fixed seed, for reproducible sequences.
+    pub fn seeded(seed: u64) -> Self {
+        let mut sm = seed;
+        let mut state = [0u64; 4];
+        for word in state.iter_mut() {
+            *word = splitmix64(&mut sm);
+        }
+        Rng { state }
+    }
+
+    /// Creates a generator seeded from the clock.
+    pub fn from_entropy() -> Self {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let now = crate::time::time::now();
+        let mut seed = now ^ COUNTER.fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed);
+        #[cfg(not(feature = "wasm"))]
+        {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.subsec_nanos() as u64)
+                .unwrap_or(0);
+            seed ^= nanos.rotate_left(32) ^ std::process::id() as u64;
+        }
+        #[cfg(feature = "wasm")]
+        {
+            seed ^= gigli_runtime_js::clock::performance_now().to_bits();
+        }
+        Rng::seeded(seed)
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// Returns the next 32 random bits.
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns true with the given probability.
+    pub fn next_bool(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    /// Returns an integer in `min..max`, or `min` if the range is empty.
+    pub fn int_range(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min {
+            return min;
+        }
+        let span = max.wrapping_sub(min) as u64;
+        // Reject the top partial bucket so every value is equally likely.
+        let zone = u64::MAX - (u64::MAX - span + 1) % span;
+        loop {
+            let value = self.next_u64();
+            if value <= zone {
+                return min.wrapping_add((value % span) as i64);
+            }
+        }
+    }
+
+    /// Returns a float in `min..max`.
+    pub fn float_range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.next_f64()
+    }
+
+    /// Fills a buffer with random bytes.
+    pub fn fill_bytes(&mut self, buffer: &mut [u8]) {
+        for chunk in buffer.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    /// Returns `n` random bytes.
+    pub fn bytes(&mut self, n: usize) -> List<u8> {
+        let mut buffer = vec![0u8; n];
+        self.fill_bytes(&mut buffer);
+        let mut list = List::new();
+        for byte in buffer {
+            list.push(byte);
+        }
+        list
+    }
+
+    /// Shuffles a list in place (Fisher-Yates).
+    pub fn shuffle<T>(&mut self, list: &mut List<T>) {
+        for i in (1..list.len()).rev() {
+            let j = self.int_range(0, i as i64 + 1) as usize;
+            list.swap(i, j);
+        }
+    }
+
+    /// Returns a random element of a list, or None if it is empty.
+    pub fn choice<'a, T>(&mut self, list: &'a List<T>) -> Option<&'a T> {
+        if list.is_empty() {
+            return None;
+        }
+        list.get(self.int_range(0, list.len() as i64) as usize)
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+thread_local! {
+    static THREAD_RNG: RefCell<Rng> = RefCell::new(Rng::from_entropy());
+}
+
+fn with_rng<R>(f: impl FnOnce(&mut Rng) -> R) -> R {
+    THREAD_RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+/// Reseeds the shared generator, making the module functions reproducible.
+pub fn seed(seed: u64) {
+    with_rng(|rng| *rng = Rng::seeded(seed));
+}
+
+/// Returns a float in `[0, 1)` from the shared generator.
+pub fn random() -> f64 {
+    with_rng(|rng| rng.next_f64())
+}
+
+/// Returns an integer in `min..max` from the shared generator.
+pub fn int_range(min: i64, max: i64) -> i64 {
+    with_rng(|rng| rng.int_range(min, max))
+}
+
+/// Returns a float in `min..max` from the shared generator.
+pub fn float_range(min: f64, max: f64) -> f64 {
+    with_rng(|rng| rng.float_range(min, max))
+}
+
+/// Returns `n` random bytes from the shared generator.
+pub fn bytes(n: usize) -> List<u8> {
+    with_rng(|rng| rng.bytes(n))
+}
+
+/// Fills a buffer with random bytes from the shared generator.
+pub fn fill_bytes(buffer: &mut [u8]) {
+    with_rng(|rng| rng.fill_bytes(buffer));
+}
+
+/// Shuffles a list in place using the shared generator.
+pub fn shuffle<T>(list: &mut List<T>) {
+    with_rng(|rng| rng.shuffle(list));
+}
+
+/// Returns a random element of a list using the shared generator.
+pub fn choice<T>(list: &List<T>) -> Option<&T> {
+    with_rng(|rng| rng.choice(list))
+}
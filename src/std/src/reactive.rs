@@ -0,0 +1,425 @@
+//! Reactive primitives for Gigli programs.
+//!
+//! A `Signal` holds a value; a `Computed` derives a value from signals and other
+//! computeds; an `Effect` re-runs whenever something it read changes.
+//! Dependencies are tracked automatically while a computed or effect runs.
+//!
+//! Updates propagate in two phases: a change first marks every dependent
+//! computed stale and queues dependent effects, then the queued effects run
+//! once each, so an effect never sees a half-updated graph. `batch` defers the
+//! second phase until several changes have been made.
+//!
+//! The graph is single-threaded and has no target-specific code, so the same
+//! implementation backs web and native programs. The DOM bindings in
+//! `browser::dom` (`bind_text`, `bind_input_value`) are built on it.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+trait Observer {
+    fn id(&self) -> usize;
+    /// The sources read during the last run.
+    fn sources(&self) -> &RefCell<Vec<Weak<Source>>>;
+    /// Called when a source changes: computeds mark themselves stale, effects queue.
+    fn stale(self: Rc<Self>);
+    /// Re-runs a queued effect.
+    fn run(self: Rc<Self>);
+}
+
+/// Effects are held strongly by their sources so they keep running after their
+/// handle is dropped; computeds are held weakly so unused ones can be freed.
+enum Link {
+    Strong(Rc<dyn Observer>),
+    Weak(Weak<dyn Observer>),
+}
+
+impl Link {
+    fn upgrade(&self) -> Option<Rc<dyn Observer>> {
+        match self {
+            Link::Strong(observer) => Some(observer.clone()),
+            Link::Weak(observer) => observer.upgrade(),
+        }
+    }
+}
+
+/// Something observers can depend on: the change notifier of a signal or computed.
+struct Source {
+    observers: RefCell<Vec<(usize, Link)>>,
+}
+
+thread_local! {
+    static NEXT_ID: Cell<usize> = Cell::new(0);
+    /// The observer currently running, if any; `None` entries come from `untrack`.
+    static CURRENT: RefCell<Vec<Option<(Rc<dyn Observer>, bool)>>> = RefCell::new(Vec::new());
+    static PENDING: RefCell<Vec<Rc<dyn Observer>>> = RefCell::new(Vec::new());
+    static BATCH_DEPTH: Cell<usize> = Cell::new(0);
+    static FLUSHING: Cell<bool> = Cell::new(false);
+}
+
+fn next_id() -> usize {
+    NEXT_ID.with(|id| {
+        id.set(id.get() + 1);
+        id.get()
+    })
+}
+
+impl Source {
+    fn new() -> Rc<Source> {
+        Rc::new(Source {
+            observers: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Records the running observer, if any, as dependent on this source.
+    fn track(self: &Rc<Self>) {
+        let current = CURRENT.with(|c| c.borrow().last().cloned().flatten());
+        if let Some((observer, strong)) = current {
+            let id = observer.id();
+            let mut observers = self.observers.borrow_mut();
+            if observers.iter().any(|(other, _)| *other == id) {
+                return;
+            }
+            observer.sources().borrow_mut().push(Rc::downgrade(self));
+            let link = if strong {
+                Link::Strong(observer)
+            } else {
+                Link::Weak(Rc::downgrade(&observer))
+            };
+            observers.push((id, link));
+        }
+    }
+
+    /// Marks every dependent stale without running effects.
+    fn mark(&self) {
+        let observers: Vec<Rc<dyn Observer>> = {
+            let mut observers = self.observers.borrow_mut();
+            observers.retain(|(_, link)| link.upgrade().is_some());
+            observers.iter().filter_map(|(_, link)| link.upgrade()).collect()
+        };
+        for observer in observers {
+            observer.stale();
+        }
+    }
+
+    fn notify(&self) {
+        self.mark();
+        flush();
+    }
+
+    fn remove(&self, id: usize) {
+        self.observers.borrow_mut().retain(|(other, _)| *other != id);
+    }
+}
+
+/// Unsubscribes an observer from everything it read, ready for a fresh run.
+fn clear_sources(observer: &dyn Observer) {
+    let sources = std::mem::take(&mut *observer.sources().borrow_mut());
+    for source in sources.iter().filter_map(Weak::upgrade) {
+        source.remove(observer.id());
+    }
+}
+
+/// Runs `f` as `observer`, recording the sources it reads.
+fn run_tracked<R>(observer: Rc<dyn Observer>, strong: bool, f: impl FnOnce() -> R) -> R {
+    clear_sources(&*observer);
+    CURRENT.with(|c| c.borrow_mut().push(Some((observer, strong))));
+    let result = f();
+    CURRENT.with(|c| c.borrow_mut().pop());
+    result
+}
+
+/// Runs queued effects until none remain, unless inside a batch or already flushing.
+fn flush() {
+    if BATCH_DEPTH.with(Cell::get) > 0 || FLUSHING.with(Cell::get) {
+        return;
+    }
+    FLUSHING.with(|f| f.set(true));
+    loop {
+        let pending = PENDING.with(|p| std::mem::take(&mut *p.borrow_mut()));
+        if pending.is_empty() {
+            break;
+        }
+        for effect in pending {
+            effect.run();
+        }
+    }
+    FLUSHING.with(|f| f.set(false));
+}
+
+/// Makes several changes at once; effects run once afterwards.
+pub fn batch<R>(f: impl FnOnce() -> R) -> R {
+    BATCH_DEPTH.with(|d| d.set(d.get() + 1));
+    let result = f();
+    BATCH_DEPTH.with(|d| d.set(d.get() - 1));
+    flush();
+    result
+}
+
+/// Runs `f` without recording dependencies for the current computed or effect.
+pub fn untrack<R>(f: impl FnOnce() -> R) -> R {
+    CURRENT.with(|c| c.borrow_mut().push(None));
+    let result = f();
+    CURRENT.with(|c| c.borrow_mut().pop());
+    result
+}
+
+struct SignalInner<T> {
+    value: RefCell<T>,
+    source: Rc<Source>,
+}
+
+/// A reactive value. Clones share the same value.
+pub struct Signal<T> {
+    inner: Rc<SignalInner<T>>,
+}
+
+impl<T: 'static> Signal<T> {
+    pub fn new(value: T) -> Self {
+        Signal {
+            inner: Rc::new(SignalInner {
+                value: RefCell::new(value),
+                source: Source::new(),
+            }),
+        }
+    }
+
+    /// Reads the value, tracking it as a dependency.
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+
+    /// Reads the value by reference, tracking it as a dependency.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.inner.source.track();
+        f(&self.inner.value.borrow())
+    }
+
+    /// Reads the value without tracking it.
+    pub fn get_untracked(&self) -> T
+    where
+        T: Clone,
+    {
+        self.inner.value.borrow().clone()
+    }
+
+    /// Replaces the value and notifies dependents.
+    pub fn set(&self, value: T) {
+        *self.inner.value.borrow_mut() = value;
+        self.inner.source.notify();
+    }
+
+    /// Replaces the value only if it differs, returning whether it changed.
+    pub fn set_if_changed(&self, value: T) -> bool
+    where
+        T: PartialEq,
+    {
+        if *self.inner.value.borrow() == value {
+            return false;
+        }
+        self.set(value);
+        true
+    }
+
+    /// Modifies the value in place and notifies dependents.
+    pub fn update(&self, f: impl FnOnce(&mut T)) {
+        f(&mut self.inner.value.borrow_mut());
+        self.inner.source.notify();
+    }
+
+    /// Calls `handler` with the new value after every change (not with the
+    /// current value). Dispose the returned effect to stop.
+    ///
+    /// The handler gets a copy of the value, so it may set the signal itself.
+    pub fn subscribe(&self, mut handler: impl FnMut(&T) + 'static) -> Effect
+    where
+        T: Clone,
+    {
+        let signal = self.clone();
+        let mut first = true;
+        Effect::new(move || {
+            let value = signal.get();
+            if !std::mem::take(&mut first) {
+                untrack(|| handler(&value));
+            }
+        })
+    }
+}
+
+impl<T> Clone for Signal<T> {
+    fn clone(&self) -> Self {
+        Signal {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Signal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Signal").field(&self.inner.value.borrow()).finish()
+    }
+}
+
+struct ComputedNode<T> {
+    id: usize,
+    compute: Box<dyn Fn() -> T>,
+    value: RefCell<Option<T>>,
+    dirty: Cell<bool>,
+    source: Rc<Source>,
+    sources: RefCell<Vec<Weak<Source>>>,
+}
+
+impl<T: 'static> Observer for ComputedNode<T> {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn sources(&self) -> &RefCell<Vec<Weak<Source>>> {
+        &self.sources
+    }
+
+    fn stale(self: Rc<Self>) {
+        if !self.dirty.replace(true) {
+            self.source.mark();
+        }
+    }
+
+    fn run(self: Rc<Self>) {}
+}
+
+/// A value derived from other reactive values, recomputed lazily when read
+/// after a dependency changed. Clones share the same cache.
+pub struct Computed<T> {
+    node: Rc<ComputedNode<T>>,
+}
+
+impl<T: Clone + 'static> Computed<T> {
+    pub fn new(compute: impl Fn() -> T + 'static) -> Self {
+        Computed {
+            node: Rc::new(ComputedNode {
+                id: next_id(),
+                compute: Box::new(compute),
+                value: RefCell::new(None),
+                dirty: Cell::new(true),
+                source: Source::new(),
+                sources: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Reads the value, recomputing it if stale, and tracks it as a dependency.
+    pub fn get(&self) -> T {
+        self.node.source.track();
+        if self.node.dirty.get() {
+            let node = self.node.clone();
+            let value = run_tracked(node.clone(), false, || (node.compute)());
+            *self.node.value.borrow_mut() = Some(value);
+            self.node.dirty.set(false);
+        }
+        self.node.value.borrow().clone().expect("computed value is set after recomputing")
+    }
+
+    /// Calls `handler` with the new value after every change. Dispose the
+    /// returned effect to stop.
+    pub fn subscribe(&self, mut handler: impl FnMut(&T) + 'static) -> Effect {
+        let computed = self.clone();
+        let mut first = true;
+        Effect::new(move || {
+            let value = computed.get();
+            if !std::mem::take(&mut first) {
+                untrack(|| handler(&value));
+            }
+        })
+    }
+}
+
+impl<T> Clone for Computed<T> {
+    fn clone(&self) -> Self {
+        Computed {
+            node: self.node.clone(),
+        }
+    }
+}
+
+struct EffectNode {
+    id: usize,
+    run: RefCell<Box<dyn FnMut()>>,
+    disposed: Cell<bool>,
+    sources: RefCell<Vec<Weak<Source>>>,
+}
+
+impl Observer for EffectNode {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn sources(&self) -> &RefCell<Vec<Weak<Source>>> {
+        &self.sources
+    }
+
+    fn stale(self: Rc<Self>) {
+        if self.disposed.get() {
+            return;
+        }
+        PENDING.with(|p| {
+            let mut pending = p.borrow_mut();
+            if !pending.iter().any(|other| other.id() == self.id) {
+                pending.push(self);
+            }
+        });
+    }
+
+    fn run(self: Rc<Self>) {
+        if self.disposed.get() {
+            return;
+        }
+        let node = self.clone();
+        run_tracked(self, true, || (node.run.borrow_mut())());
+    }
+}
+
+/// A side effect that runs immediately and again whenever anything it read
+/// changes. Dropping the handle does not stop it; call `dispose`.
+pub struct Effect {
+    node: Rc<EffectNode>,
+}
+
+impl Effect {
+    pub fn new(f: impl FnMut() + 'static) -> Self {
+        let node = Rc::new(EffectNode {
+            id: next_id(),
+            run: RefCell::new(Box::new(f)),
+            disposed: Cell::new(false),
+            sources: RefCell::new(Vec::new()),
+        });
+        // Batched, so that an effect writing a signal it read is re-run
+        // after this run rather than from inside it
+        batch(|| node.clone().run());
+        Effect { node }
+    }
+
+    /// Stops the effect; it will not run again.
+    pub fn dispose(&self) {
+        self.node.disposed.set(true);
+        clear_sources(&*self.node);
+    }
+
+    pub fn is_disposed(&self) -> bool {
+        self.node.disposed.get()
+    }
+}
+
+pub fn signal<T: 'static>(value: T) -> Signal<T> {
+    Signal::new(value)
+}
+
+pub fn computed<T: Clone + 'static>(compute: impl Fn() -> T + 'static) -> Computed<T> {
+    Computed::new(compute)
+}
+
+pub fn effect(f: impl FnMut() + 'static) -> Effect {
+    Effect::new(f)
+}
@@ -0,0 +1,133 @@
+//! Standard library: Regular expressions for Gigli
+//!
+//! Backed by the `regex` crate, which runs in linear time and never backtracks.
+//! Match positions are char indices, consistent with `std::string`.
+
+use crate::list::List;
+use crate::map::Map;
+
+/// Error returned when a pattern fails to compile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexError {
+    pub message: String,
+}
+
+impl std::fmt::Display for RegexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid regex: {}", self.message)
+    }
+}
+
+/// A single match within a string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Capture groups from a successful match.
+#[derive(Debug, Clone)]
+pub struct Captures {
+    /// Positional groups; index 0 is the whole match. Unmatched optional groups are `None`.
+    pub groups: List<Option<String>>,
+    /// Named groups that participated in the match.
+    pub named: Map<String, String>,
+}
+
+impl Captures {
+    /// Gets the text of a positional group.
+    pub fn get(&self, index: usize) -> Option<&String> {
+        self.groups.get(index).and_then(|g| g.as_ref())
+    }
+
+    /// Gets the text of a named group.
+    pub fn name(&self, name: &str) -> Option<&String> {
+        self.named.get(&name.to_string())
+    }
+}
+
+/// A compiled regular expression.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    inner: ::regex::Regex,
+}
+
+impl Regex {
+    /// Compiles a pattern.
+    pub fn new(pattern: &str) -> Result<Regex, RegexError> {
+        ::regex::Regex::new(pattern)
+            .map(|inner| Regex { inner })
+            .map_err(|e| RegexError { message: e.to_string() })
+    }
+
+    /// Returns the source pattern.
+    pub fn as_str(&self) -> &str {
+        self.inner.as_str()
+    }
+
+    /// Returns true if the pattern matches anywhere in the text.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.inner.is_match(text)
+    }
+
+    /// Returns the first match in the text, if any.
+    pub fn find(&self, text: &str) -> Option<Match> {
+        self.inner.find(text).map(|m| to_match(text, m.start(), m.end()))
+    }
+
+    /// Returns every non-overlapping match in the text.
+    pub fn find_all(&self, text: &str) -> List<Match> {
+        let mut list = List::new();
+        for m in self.inner.find_iter(text) {
+            list.push(to_match(text, m.start(), m.end()));
+        }
+        list
+    }
+
+    /// Returns the capture groups of the first match, if any.
+    pub fn captures(&self, text: &str) -> Option<Captures> {
+        let caps = self.inner.captures(text)?;
+        let mut groups = List::new();
+        for group in caps.iter() {
+            groups.push(group.map(|m| m.as_str().to_string()));
+        }
+        let mut named = Map::new();
+        for name in self.inner.capture_names().flatten() {
+            if let Some(m) = caps.name(name) {
+                named.insert(name.to_string(), m.as_str().to_string());
+            }
+        }
+        Some(Captures { groups, named })
+    }
+
+    /// Replaces every match; `$1` or `$name` in the replacement refer to capture groups.
+    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+        self.inner.replace_all(text, replacement).into_owned()
+    }
+
+    /// Splits the text on every match.
+    pub fn split(&self, text: &str) -> List<String> {
+        let mut list = List::new();
+        for part in self.inner.split(text) {
+            list.push(part.to_string());
+        }
+        list
+    }
+}
+
+/// Checks that a pattern compiles without keeping the compiled program.
+pub fn validate(pattern: &str) -> Result<(), RegexError> {
+    Regex::new(pattern).map(|_| ())
+}
+
+/// Converts byte offsets from the engine into char-indexed match data.
+fn to_match(text: &str, start: usize, end: usize) -> Match {
+    let char_start = text[..start].chars().count();
+    let matched = &text[start..end];
+    Match {
+        start: char_start,
+        end: char_start + matched.chars().count(),
+        text: matched.to_string(),
+    }
+}
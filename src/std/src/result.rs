@@ -1,5 +1,7 @@
 //! Standard library: Result<T, E> for Gigli
 
+use crate::option::Option;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Result<T, E> {
     Ok(T),
@@ -25,6 +27,22 @@ impl<T, E> Result<T, E> {
         }
     }
 
+    /// Unwraps the value, panicking with the given message if Err.
+    pub fn expect(self, msg: &str) -> T {
+        match self {
+            Result::Ok(v) => v,
+            Result::Err(_) => panic!("{}", msg),
+        }
+    }
+
+    /// Unwraps the error, panicking if Ok.
+    pub fn unwrap_err(self) -> E {
+        match self {
+            Result::Ok(_) => panic!("called `Result::unwrap_err()` on an `Ok` value"),
+            Result::Err(e) => e,
+        }
+    }
+
     /// Unwraps the value or returns the provided default.
     pub fn unwrap_or(self, default: T) -> T {
         match self {
@@ -65,4 +83,98 @@ impl<T, E> Result<T, E> {
             Result::Err(e) => Result::Err(e),
         }
     }
+
+    /// Unwraps the value or computes a fallback from the error.
+    pub fn unwrap_or_else<F>(self, f: F) -> T
+    where
+        F: FnOnce(E) -> T,
+    {
+        match self {
+            Result::Ok(v) => v,
+            Result::Err(e) => f(e),
+        }
+    }
+
+    /// Returns the result if Ok, otherwise calls f with the error and returns the result.
+    pub fn or_else<F, FN>(self, f: FN) -> Result<T, F>
+    where
+        FN: FnOnce(E) -> Result<T, F>,
+    {
+        match self {
+            Result::Ok(v) => Result::Ok(v),
+            Result::Err(e) => f(e),
+        }
+    }
+
+    /// Returns `other` if the result is Ok, otherwise returns this Err.
+    pub fn and<U>(self, other: Result<U, E>) -> Result<U, E> {
+        match self {
+            Result::Ok(_) => other,
+            Result::Err(e) => Result::Err(e),
+        }
+    }
+
+    /// Returns the result if Ok, otherwise returns `other`.
+    pub fn or<F>(self, other: Result<T, F>) -> Result<T, F> {
+        match self {
+            Result::Ok(v) => Result::Ok(v),
+            Result::Err(_) => other,
+        }
+    }
+
+    /// Calls f with a reference to the contained value if Ok, then returns the result unchanged.
+    pub fn inspect<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&T),
+    {
+        if let Result::Ok(v) = &self {
+            f(v);
+        }
+        self
+    }
+
+    /// Calls f with a reference to the error if Err, then returns the result unchanged.
+    pub fn inspect_err<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&E),
+    {
+        if let Result::Err(e) = &self {
+            f(e);
+        }
+        self
+    }
+
+    /// Converts to an Option of the value, discarding the error.
+    pub fn ok(self) -> Option<T> {
+        match self {
+            Result::Ok(v) => Option::Some(v),
+            Result::Err(_) => Option::None,
+        }
+    }
+
+    /// Converts to an Option of the error, discarding the value.
+    pub fn err(self) -> Option<E> {
+        match self {
+            Result::Ok(_) => Option::None,
+            Result::Err(e) => Option::Some(e),
+        }
+    }
+}
+
+impl<T, E> From<std::result::Result<T, E>> for Result<T, E> {
+    fn from(result: std::result::Result<T, E>) -> Self {
+        match result {
+            Ok(v) => Result::Ok(v),
+            Err(e) => Result::Err(e),
+        }
+    }
+}
+
+impl<T, E> From<Result<T, E>> for std::result::Result<T, E> {
+    fn from(result: Result<T, E>) -> Self {
+        match result {
+            Result::Ok(v) => Ok(v),
+            Result::Err(e) => Err(e),
+        }
+    }
 }
@@ -0,0 +1,114 @@
+//! Standard library: Set<T> for Gigli
+
+use crate::iter::{self, FromStd};
+use crate::list::List;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone)]
+pub struct Set<T> {
+    data: HashSet<T>,
+}
+
+impl<T: std::cmp::Eq + std::hash::Hash> Set<T> {
+    /// Creates a new empty set.
+    pub fn new() -> Self {
+        Set { data: HashSet::new() }
+    }
+
+    /// Adds a value to the set, returning true if it was not already present.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.data.insert(value)
+    }
+
+    /// Removes a value from the set, returning true if it was present.
+    pub fn remove(&mut self, value: &T) -> bool {
+        self.data.remove(value)
+    }
+
+    /// Returns true if the set contains the given value.
+    pub fn contains(&self, value: &T) -> bool {
+        self.data.contains(value)
+    }
+
+    /// Returns the number of values in the set.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns true if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Removes all values from the set.
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns an iterator over the values.
+    pub fn iter(&self) -> std::collections::hash_set::Iter<T> {
+        self.data.iter()
+    }
+
+    /// Returns a lazy `GigliIterator` over the values.
+    pub fn gigli_iter(&self) -> FromStd<std::collections::hash_set::Iter<T>> {
+        iter::from_iter(self.data.iter())
+    }
+
+    /// Returns a new set with the values in either set.
+    pub fn union(&self, other: &Set<T>) -> Set<T>
+    where
+        T: Clone,
+    {
+        Set {
+            data: self.data.union(&other.data).cloned().collect(),
+        }
+    }
+
+    /// Returns a new set with the values in both sets.
+    pub fn intersection(&self, other: &Set<T>) -> Set<T>
+    where
+        T: Clone,
+    {
+        Set {
+            data: self.data.intersection(&other.data).cloned().collect(),
+        }
+    }
+
+    /// Returns a new set with the values in this set but not in the other.
+    pub fn difference(&self, other: &Set<T>) -> Set<T>
+    where
+        T: Clone,
+    {
+        Set {
+            data: self.data.difference(&other.data).cloned().collect(),
+        }
+    }
+
+    /// Returns true if every value in this set is also in the other.
+    pub fn is_subset(&self, other: &Set<T>) -> bool {
+        self.data.is_subset(&other.data)
+    }
+
+    /// Creates a set from the values of a list, dropping duplicates.
+    pub fn from_list(list: &List<T>) -> Set<T>
+    where
+        T: Clone,
+    {
+        Set {
+            data: list.iter().cloned().collect(),
+        }
+    }
+
+    /// Collects the values into a list, in unspecified order.
+    pub fn to_list(&self) -> List<T>
+    where
+        T: Clone,
+    {
+        let mut list = List::new();
+        for value in &self.data {
+            list.push(value.clone());
+        }
+        list
+    }
+}
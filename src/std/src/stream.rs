@@ -0,0 +1,208 @@
+//! Async streams for Gigli programs.
+//!
+//! A `Stream<T>` is an async iterator: `next().await` yields values until the
+//! source ends. Streams can be built from iterators, channels, timers, DOM
+//! events and WebSocket messages, and transformed with the usual adapters.
+
+use std::future::{poll_fn, Future};
+use std::task::{Context, Poll};
+
+use crate::browser::dom::Element;
+use crate::browser::events::{self, InputEvent, KeyboardEvent, MouseEvent};
+use crate::browser::websocket::{Message, WebSocket};
+use crate::list::List;
+use crate::task::{self, Receiver};
+use crate::time::time::Duration;
+
+type PollNext<T> = Box<dyn FnMut(&mut Context<'_>) -> Poll<Option<T>>>;
+
+/// An asynchronous sequence of values.
+pub struct Stream<T> {
+    poll_next: PollNext<T>,
+}
+
+impl<T: 'static> Stream<T> {
+    /// Builds a stream from a poll function, which returns `Ready(None)` when done.
+    pub fn from_poll<F>(poll_next: F) -> Stream<T>
+    where
+        F: FnMut(&mut Context<'_>) -> Poll<Option<T>> + 'static,
+    {
+        Stream {
+            poll_next: Box::new(poll_next),
+        }
+    }
+
+    /// A stream that ends immediately.
+    pub fn empty() -> Stream<T> {
+        Stream::from_poll(|_| Poll::Ready(None))
+    }
+
+    /// Polls for the next value.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        (self.poll_next)(cx)
+    }
+
+    /// Waits for the next value, or None once the stream has ended.
+    pub async fn next(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    pub fn map<U: 'static, F>(mut self, mut f: F) -> Stream<U>
+    where
+        F: FnMut(T) -> U + 'static,
+    {
+        Stream::from_poll(move |cx| self.poll_next(cx).map(|item| item.map(&mut f)))
+    }
+
+    pub fn filter<F>(mut self, mut predicate: F) -> Stream<T>
+    where
+        F: FnMut(&T) -> bool + 'static,
+    {
+        Stream::from_poll(move |cx| loop {
+            match self.poll_next(cx) {
+                Poll::Ready(Some(item)) if !predicate(&item) => continue,
+                other => return other,
+            }
+        })
+    }
+
+    /// Ends the stream after `count` values.
+    pub fn take(mut self, count: usize) -> Stream<T> {
+        let mut remaining = count;
+        Stream::from_poll(move |cx| {
+            if remaining == 0 {
+                return Poll::Ready(None);
+            }
+            let item = self.poll_next(cx);
+            if let Poll::Ready(Some(_)) = item {
+                remaining -= 1;
+            }
+            item
+        })
+    }
+
+    /// Groups values into lists of `size`; the last list may be shorter.
+    pub fn buffer(mut self, size: usize) -> Stream<List<T>> {
+        let size = size.max(1);
+        let mut pending = List::new();
+        let mut done = false;
+        Stream::from_poll(move |cx| {
+            while !done {
+                match self.poll_next(cx) {
+                    Poll::Ready(Some(item)) => {
+                        pending.push(item);
+                        if pending.len() == size {
+                            return Poll::Ready(Some(std::mem::replace(&mut pending, List::new())));
+                        }
+                    }
+                    Poll::Ready(None) => done = true,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            if pending.is_empty() {
+                Poll::Ready(None)
+            } else {
+                Poll::Ready(Some(std::mem::replace(&mut pending, List::new())))
+            }
+        })
+    }
+
+    /// Calls `f` with every value until the stream ends.
+    pub async fn for_each<F: FnMut(T)>(mut self, mut f: F) {
+        while let Some(item) = self.next().await {
+            f(item);
+        }
+    }
+
+    /// Awaits `f` for every value in turn until the stream ends.
+    pub async fn for_each_async<F, Fut>(mut self, mut f: F)
+    where
+        F: FnMut(T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        while let Some(item) = self.next().await {
+            f(item).await;
+        }
+    }
+
+    /// Collects every value until the stream ends.
+    pub async fn collect(mut self) -> List<T> {
+        let mut list = List::new();
+        while let Some(item) = self.next().await {
+            list.push(item);
+        }
+        list
+    }
+}
+
+/// Yields the values of an iterator.
+pub fn from_iter<I>(items: I) -> Stream<I::Item>
+where
+    I: IntoIterator,
+    I::IntoIter: 'static,
+    I::Item: 'static,
+{
+    let mut items = items.into_iter();
+    Stream::from_poll(move |_| Poll::Ready(items.next()))
+}
+
+/// Yields the messages of a channel until every sender is dropped.
+pub fn from_channel<T: 'static>(mut receiver: Receiver<T>) -> Stream<T> {
+    Stream::from_poll(move |cx| receiver.poll_recv(cx))
+}
+
+/// Builds a stream from a callback-based source. `register` receives a
+/// function that pushes a value into the stream.
+pub fn from_callback<T, R>(register: R) -> Result<Stream<T>, String>
+where
+    T: 'static,
+    R: FnOnce(Box<dyn FnMut(T)>) -> Result<(), String>,
+{
+    let (sender, receiver) = task::channel();
+    register(Box::new(move |value| {
+        let _ = sender.send(value);
+    }))?;
+    Ok(from_channel(receiver))
+}
+
+/// Yields 0, 1, 2, ... once every `period`, forever.
+pub fn interval(period: Duration) -> Stream<u64> {
+    let (sender, receiver) = task::channel();
+    task::spawn(async move {
+        let mut tick = 0u64;
+        loop {
+            task::sleep(period).await;
+            // Stop once the stream has been dropped.
+            if sender.send(tick).is_err() {
+                break;
+            }
+            tick += 1;
+        }
+    });
+    from_channel(receiver)
+}
+
+/// Yields every mouse event of the given type fired on the element.
+pub fn mouse_events(element: &Element, event: &str) -> Result<Stream<MouseEvent>, String> {
+    from_callback(|push| events::on_mouse(element, event, push))
+}
+
+/// Yields every `keydown` or `keyup` event fired on the element.
+pub fn key_events(element: &Element, event: &str) -> Result<Stream<KeyboardEvent>, String> {
+    from_callback(|push| events::on_key(element, event, push))
+}
+
+/// Yields the control's value on every `input` event.
+pub fn input_events(element: &Element) -> Result<Stream<InputEvent>, String> {
+    from_callback(|push| events::on_input(element, push))
+}
+
+/// Yields every message received on the socket. The stream never ends on its
+/// own; combine it with `WebSocket::on_close` to stop consuming.
+pub fn websocket_messages(socket: &WebSocket) -> Stream<Message> {
+    let (sender, receiver) = task::channel();
+    socket.on_message(move |message| {
+        let _ = sender.send(message);
+    });
+    from_channel(receiver)
+}
@@ -0,0 +1,183 @@
+//! Standard library: String utilities for Gigli
+//!
+//! All indices are char indices, not byte offsets, so slicing never splits a character.
+
+use crate::list::List;
+
+/// Splits a string on every occurrence of the separator.
+pub fn split(s: &str, separator: &str) -> List<String> {
+    let mut list = List::new();
+    if separator.is_empty() {
+        for ch in s.chars() {
+            list.push(ch.to_string());
+        }
+    } else {
+        for part in s.split(separator) {
+            list.push(part.to_string());
+        }
+    }
+    list
+}
+
+/// Joins a list of strings, placing the separator between each element.
+pub fn join(parts: &List<String>, separator: &str) -> String {
+    parts.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(separator)
+}
+
+/// Removes leading and trailing whitespace.
+pub fn trim(s: &str) -> String {
+    s.trim().to_string()
+}
+
+/// Removes leading whitespace.
+pub fn trim_start(s: &str) -> String {
+    s.trim_start().to_string()
+}
+
+/// Removes trailing whitespace.
+pub fn trim_end(s: &str) -> String {
+    s.trim_end().to_string()
+}
+
+/// Returns true if the string starts with the given prefix.
+pub fn starts_with(s: &str, prefix: &str) -> bool {
+    s.starts_with(prefix)
+}
+
+/// Returns true if the string ends with the given suffix.
+pub fn ends_with(s: &str, suffix: &str) -> bool {
+    s.ends_with(suffix)
+}
+
+/// Returns true if the string contains the given substring.
+pub fn contains(s: &str, needle: &str) -> bool {
+    s.contains(needle)
+}
+
+/// Replaces every occurrence of `from` with `to`.
+pub fn replace(s: &str, from: &str, to: &str) -> String {
+    s.replace(from, to)
+}
+
+/// Converts the string to upper case.
+pub fn to_upper(s: &str) -> String {
+    s.to_uppercase()
+}
+
+/// Converts the string to lower case.
+pub fn to_lower(s: &str) -> String {
+    s.to_lowercase()
+}
+
+/// Returns the number of characters in the string.
+pub fn len(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// Returns true if the string is empty.
+pub fn is_empty(s: &str) -> bool {
+    s.is_empty()
+}
+
+/// Pads the start of the string with `fill` until it is `width` characters long.
+pub fn pad_start(s: &str, width: usize, fill: char) -> String {
+    let count = width.saturating_sub(len(s));
+    let mut padded: String = std::iter::repeat(fill).take(count).collect();
+    padded.push_str(s);
+    padded
+}
+
+/// Pads the end of the string with `fill` until it is `width` characters long.
+pub fn pad_end(s: &str, width: usize, fill: char) -> String {
+    let count = width.saturating_sub(len(s));
+    let mut padded = s.to_string();
+    padded.extend(std::iter::repeat(fill).take(count));
+    padded
+}
+
+/// Repeats the string `count` times.
+pub fn repeat(s: &str, count: usize) -> String {
+    s.repeat(count)
+}
+
+/// Returns the characters of the string as a list.
+pub fn chars(s: &str) -> List<char> {
+    let mut list = List::new();
+    for ch in s.chars() {
+        list.push(ch);
+    }
+    list
+}
+
+/// Returns the character at the given index, if any.
+pub fn char_at(s: &str, index: usize) -> Option<char> {
+    s.chars().nth(index)
+}
+
+/// Returns the substring between the `start` and `end` char indices, clamped to the string.
+pub fn slice(s: &str, start: usize, end: usize) -> String {
+    let end = end.max(start);
+    s.chars().skip(start).take(end - start).collect()
+}
+
+/// Returns the char index of the first occurrence of `needle`, if any.
+pub fn index_of(s: &str, needle: &str) -> Option<usize> {
+    s.find(needle).map(|byte_idx| s[..byte_idx].chars().count())
+}
+
+/// Formats a template, replacing `{}` with the next argument and `{n}` with the n-th one.
+///
+/// `{{` and `}}` produce literal braces. Returns an error for unknown or unterminated placeholders.
+pub fn format(template: &str, args: &[String]) -> Result<String, String> {
+    let mut next = 0;
+    interpolate(template, |name| {
+        let index = if name.is_empty() {
+            next += 1;
+            next - 1
+        } else {
+            name.parse::<usize>().ok()?
+        };
+        args.get(index).cloned()
+    })
+}
+
+/// Formats a template, replacing `{name}` placeholders with the value returned by `lookup`.
+///
+/// This is what `"Hello, {name}!"`-style strings in Gigli code lower to.
+pub fn interpolate<F>(template: &str, mut lookup: F) -> Result<String, String>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("Unterminated placeholder '{{{}'", name)),
+                    }
+                }
+                let name = name.trim();
+                match lookup(name) {
+                    Some(value) => result.push_str(&value),
+                    None => return Err(format!("No value for placeholder '{{{}}}'", name)),
+                }
+            }
+            '}' => return Err("Unmatched '}' in format string".to_string()),
+            _ => result.push(ch),
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,460 @@
+//! Tasks and channels for Gigli programs.
+//!
+//! `spawn` runs a future concurrently and returns a `JoinHandle` to await its
+//! result. On the web tasks run on the JS microtask queue; on native targets
+//! they run on a shared pool of worker threads, so spawned futures must be
+//! `Send` there (see `MaybeSend`). Channels work the same on both.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::time::time::Duration;
+
+/// `Send` on native targets, where tasks move between threads; implemented by
+/// every type on the web, where everything runs on one thread.
+#[cfg(not(feature = "wasm"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(feature = "wasm"))]
+impl<T: Send> MaybeSend for T {}
+
+#[cfg(feature = "wasm")]
+pub trait MaybeSend {}
+#[cfg(feature = "wasm")]
+impl<T> MaybeSend for T {}
+
+struct JoinState<T> {
+    result: Option<Result<T, String>>,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+/// Awaits the output of a spawned task. Resolves to an error if the task panicked.
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// True once the task has finished.
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().unwrap().finished
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Owned by the running task; records its output, or an error if the task is
+/// dropped before finishing (which happens when it panics).
+struct Completion<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+impl<T> Completion<T> {
+    fn finish(&self, result: Result<T, String>) {
+        let mut state = self.state.lock().unwrap();
+        if !state.finished {
+            state.finished = true;
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        let finished = self.state.lock().map(|s| s.finished).unwrap_or(true);
+        if !finished {
+            self.finish(Err("Task panicked".to_string()));
+        }
+    }
+}
+
+/// Runs `future` concurrently with the caller.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + MaybeSend + 'static,
+    F::Output: MaybeSend + 'static,
+{
+    let state = Arc::new(Mutex::new(JoinState {
+        result: None,
+        finished: false,
+        waker: None,
+    }));
+    let completion = Completion { state: state.clone() };
+    backend::spawn(Box::pin(async move {
+        let output = future.await;
+        completion.finish(Ok(output));
+    }));
+    JoinHandle { state }
+}
+
+/// Completes after `duration`, without blocking other tasks.
+pub async fn sleep(duration: Duration) {
+    backend::sleep(duration).await;
+}
+
+/// Lets other tasks run before continuing.
+pub async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// The result of `select`: which future finished first, with its output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Waits for whichever of two futures finishes first; the other is dropped.
+/// When both are ready at once, `a` wins.
+pub async fn select<A: Future, B: Future>(a: A, b: B) -> Either<A::Output, B::Output> {
+    let mut a = Box::pin(a);
+    let mut b = Box::pin(b);
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = a.as_mut().poll(cx) {
+            return Poll::Ready(Either::Left(output));
+        }
+        if let Poll::Ready(output) = b.as_mut().poll(cx) {
+            return Poll::Ready(Either::Right(output));
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// Waits for the first of several futures to finish, returning its index and
+/// output, or None if there are none.
+pub async fn select_all<F: Future>(futures: Vec<F>) -> Option<(usize, F::Output)> {
+    if futures.is_empty() {
+        return None;
+    }
+    let mut futures: Vec<Pin<Box<F>>> = futures.into_iter().map(Box::pin).collect();
+    let (index, output) = poll_fn(|cx| {
+        for (index, future) in futures.iter_mut().enumerate() {
+            if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                return Poll::Ready((index, output));
+            }
+        }
+        Poll::Pending
+    })
+    .await;
+    Some((index, output))
+}
+
+/// Runs `future` with a time limit, returning None if it does not finish in time.
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    match select(future, sleep(duration)).await {
+        Either::Left(output) => Some(output),
+        Either::Right(()) => None,
+    }
+}
+
+/// Runs a future to completion on the current thread, blocking until it is done.
+#[cfg(not(feature = "wasm"))]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    backend::block_on(future)
+}
+
+struct Shared<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+    waker: Option<Waker>,
+}
+
+/// Returned by `Sender::send` when the receiver is gone, giving the value back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Channel receiver was dropped")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No message is waiting, but senders remain.
+    Empty,
+    /// No message is waiting and every sender was dropped.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "Channel is empty"),
+            TryRecvError::Disconnected => write!(f, "Channel is disconnected"),
+        }
+    }
+}
+
+/// The sending half of a channel. Clone it to send from several tasks.
+pub struct Sender<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+/// The receiving half of a channel.
+pub struct Receiver<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+/// Creates an unbounded multi-producer, single-consumer channel.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Mutex::new(Shared {
+        queue: VecDeque::new(),
+        senders: 1,
+        receiver_alive: true,
+        waker: None,
+    }));
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+impl<T> Sender<T> {
+    /// Queues a message. Fails, returning the value, if the receiver was dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.receiver_alive {
+            return Err(SendError(value));
+        }
+        shared.queue.push_back(value);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// True if the receiver was dropped.
+    pub fn is_closed(&self) -> bool {
+        !self.shared.lock().unwrap().receiver_alive
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().senders += 1;
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.senders -= 1;
+            if shared.senders == 0 {
+                if let Some(waker) = shared.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Waits for the next message. Returns None once the channel is empty and
+    /// every sender was dropped.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Polls for the next message, registering `cx`'s waker if none is waiting.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(value) = shared.queue.pop_front() {
+            Poll::Ready(Some(value))
+        } else if shared.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            shared.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Takes the next message if one is waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.queue.pop_front() {
+            Some(value) => Ok(value),
+            None if shared.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// The number of messages waiting.
+    pub fn len(&self) -> usize {
+        self.shared.lock().unwrap().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.receiver_alive = false;
+            shared.queue.clear();
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod backend {
+    use crate::time::time::Duration;
+    use std::future::Future;
+    use std::pin::Pin;
+
+    pub fn spawn(future: Pin<Box<dyn Future<Output = ()>>>) {
+        gigli_runtime_js::task::spawn_local(future);
+    }
+
+    pub async fn sleep(duration: Duration) {
+        let ms = duration.as_millis().min(i32::MAX as u64) as i32;
+        let _ = gigli_runtime_js::task::sleep(ms).await;
+    }
+}
+
+/// A small work-stealing-free executor: tasks sit on a shared queue and any
+/// idle worker polls them; a woken task is pushed back onto the queue.
+#[cfg(not(feature = "wasm"))]
+mod backend {
+    use crate::time::time::Duration;
+    use std::future::{poll_fn, Future};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::pin::Pin;
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex, OnceLock};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+
+    type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    struct Task {
+        /// None once the task has finished (or panicked).
+        future: Mutex<Option<BoxFuture>>,
+    }
+
+    impl Wake for Task {
+        fn wake(self: Arc<Self>) {
+            schedule(self);
+        }
+    }
+
+    static QUEUE: OnceLock<Mutex<mpsc::Sender<Arc<Task>>>> = OnceLock::new();
+
+    fn schedule(task: Arc<Task>) {
+        let queue = QUEUE.get_or_init(start_workers);
+        let _ = queue.lock().unwrap().send(task);
+    }
+
+    fn start_workers() -> Mutex<mpsc::Sender<Arc<Task>>> {
+        let (sender, receiver) = mpsc::channel::<Arc<Task>>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        for index in 0..workers {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("gigli-task-{}", index))
+                .spawn(move || loop {
+                    let task = match receiver.lock().unwrap().recv() {
+                        Ok(task) => task,
+                        Err(_) => break,
+                    };
+                    run(task);
+                })
+                .expect("failed to start task worker");
+        }
+        Mutex::new(sender)
+    }
+
+    fn run(task: Arc<Task>) {
+        let mut slot = task.future.lock().unwrap();
+        if let Some(future) = slot.as_mut() {
+            let waker = Waker::from(task.clone());
+            let mut cx = Context::from_waker(&waker);
+            match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(&mut cx))) {
+                Ok(Poll::Pending) => {}
+                // Dropping the future lets its `Completion` report the panic.
+                Ok(Poll::Ready(())) | Err(_) => *slot = None,
+            }
+        }
+    }
+
+    pub fn spawn(future: BoxFuture) {
+        schedule(Arc::new(Task {
+            future: Mutex::new(Some(future)),
+        }));
+    }
+
+    pub async fn sleep(duration: Duration) {
+        let state = Arc::new(Mutex::new((false, None::<Waker>)));
+        let timer = state.clone();
+        thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_nanos(duration.as_nanos()));
+            let mut timer = timer.lock().unwrap();
+            timer.0 = true;
+            if let Some(waker) = timer.1.take() {
+                waker.wake();
+            }
+        });
+        poll_fn(|cx| {
+            let mut state = state.lock().unwrap();
+            if state.0 {
+                Poll::Ready(())
+            } else {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+}
@@ -1,21 +1,252 @@
 //! Standard library: Time for Gigli
+//!
+//! Timestamps are milliseconds since the Unix epoch. Native targets read the
+//! system clock; WASM reads `Date.now()` and `performance.now()` through the runtime.
 
 pub mod time {
-    /// Returns the current system time as a string.
-    pub fn now() -> String {
-        // TODO: Implement for WASM/JS interop
-        unimplemented!("Time is not available in WASM");
+    use std::ops::{Add, Sub};
+
+    /// A span of time with nanosecond precision.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+    pub struct Duration {
+        nanos: u64,
+    }
+
+    impl Duration {
+        pub const ZERO: Duration = Duration { nanos: 0 };
+
+        pub fn from_secs(secs: u64) -> Self {
+            Duration { nanos: secs.saturating_mul(1_000_000_000) }
+        }
+
+        pub fn from_millis(millis: u64) -> Self {
+            Duration { nanos: millis.saturating_mul(1_000_000) }
+        }
+
+        pub fn from_micros(micros: u64) -> Self {
+            Duration { nanos: micros.saturating_mul(1_000) }
+        }
+
+        pub fn from_nanos(nanos: u64) -> Self {
+            Duration { nanos }
+        }
+
+        /// Creates a duration from fractional seconds; negative values clamp to zero.
+        pub fn from_secs_f64(secs: f64) -> Self {
+            Duration { nanos: (secs.max(0.0) * 1e9) as u64 }
+        }
+
+        pub fn as_secs(&self) -> u64 {
+            self.nanos / 1_000_000_000
+        }
+
+        pub fn as_millis(&self) -> u64 {
+            self.nanos / 1_000_000
+        }
+
+        pub fn as_micros(&self) -> u64 {
+            self.nanos / 1_000
+        }
+
+        pub fn as_nanos(&self) -> u64 {
+            self.nanos
+        }
+
+        pub fn as_secs_f64(&self) -> f64 {
+            self.nanos as f64 / 1e9
+        }
+
+        /// Subtracts another duration, stopping at zero instead of underflowing.
+        pub fn saturating_sub(self, other: Duration) -> Duration {
+            Duration { nanos: self.nanos.saturating_sub(other.nanos) }
+        }
+    }
+
+    impl Add for Duration {
+        type Output = Duration;
+
+        fn add(self, other: Duration) -> Duration {
+            Duration { nanos: self.nanos.saturating_add(other.nanos) }
+        }
+    }
+
+    impl Sub for Duration {
+        type Output = Duration;
+
+        fn sub(self, other: Duration) -> Duration {
+            self.saturating_sub(other)
+        }
+    }
+
+    impl std::fmt::Display for Duration {
+        /// Formats with the largest fitting unit, e.g. `1.5s`, `250ms`, `12µs`.
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let nanos = self.nanos as f64;
+            if self.nanos >= 1_000_000_000 {
+                write!(f, "{}s", trim_float(nanos / 1e9))
+            } else if self.nanos >= 1_000_000 {
+                write!(f, "{}ms", trim_float(nanos / 1e6))
+            } else if self.nanos >= 1_000 {
+                write!(f, "{}µs", trim_float(nanos / 1e3))
+            } else {
+                write!(f, "{}ns", self.nanos)
+            }
+        }
+    }
+
+    fn trim_float(value: f64) -> String {
+        let s = format!("{:.3}", value);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+
+    /// A monotonic point in time, for measuring elapsed durations.
+    #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+    pub struct Instant {
+        #[cfg(not(feature = "wasm"))]
+        inner: std::time::Instant,
+        #[cfg(feature = "wasm")]
+        millis: f64,
+    }
+
+    impl Instant {
+        pub fn now() -> Self {
+            #[cfg(not(feature = "wasm"))]
+            return Instant { inner: std::time::Instant::now() };
+            #[cfg(feature = "wasm")]
+            return Instant { millis: gigli_runtime_js::clock::performance_now() };
+        }
+
+        /// Returns the time elapsed since an earlier instant, or zero if `earlier` is later.
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            #[cfg(not(feature = "wasm"))]
+            return Duration::from_nanos(self.inner.saturating_duration_since(earlier.inner).as_nanos() as u64);
+            #[cfg(feature = "wasm")]
+            return Duration::from_secs_f64((self.millis - earlier.millis) / 1e3);
+        }
+
+        /// Returns the time elapsed since this instant was created.
+        pub fn elapsed(&self) -> Duration {
+            Instant::now().duration_since(*self)
+        }
+    }
+
+    /// Returns the current time in milliseconds since the Unix epoch.
+    pub fn now() -> u64 {
+        #[cfg(not(feature = "wasm"))]
+        return std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        #[cfg(feature = "wasm")]
+        return gigli_runtime_js::clock::date_now() as u64;
     }
 
     /// Sleeps for the given number of milliseconds.
-    pub fn sleep(_ms: u64) {
-        // TODO: Implement for native targets; not available in WASM
-        unimplemented!("Sleep is not available in WASM");
+    ///
+    /// The browser cannot block its main thread, so in WASM this returns immediately;
+    /// use `std::task` timers there instead.
+    pub fn sleep(ms: u64) {
+        #[cfg(not(feature = "wasm"))]
+        std::thread::sleep(std::time::Duration::from_millis(ms));
+        #[cfg(feature = "wasm")]
+        let _ = ms;
+    }
+
+    const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+    const MONTHS: [&str; 12] = [
+        "January", "February", "March", "April", "May", "June",
+        "July", "August", "September", "October", "November", "December",
+    ];
+
+    /// Formats a timestamp (milliseconds since the Unix epoch) in UTC.
+    ///
+    /// Supports the strftime directives `%Y %y %m %d %e %H %I %M %S %L %p %j %a %A %b %B %s %z %%`,
+    /// plus the shorthands `%F` (`%Y-%m-%d`) and `%T` (`%H:%M:%S`). `%L` is milliseconds.
+    /// Unknown directives are copied through unchanged.
+    pub fn format(timestamp: u64, fmt: &str) -> String {
+        format_with_offset(timestamp as i64, 0, fmt)
+    }
+
+    /// Formats a timestamp as wall-clock time at the given UTC offset (minutes east).
+    pub(crate) fn format_with_offset(timestamp: i64, offset_minutes: i32, fmt: &str) -> String {
+        let local = timestamp + offset_minutes as i64 * 60_000;
+        let millis = local.rem_euclid(1000);
+        let days = local.div_euclid(86_400_000);
+        let day_secs = local.rem_euclid(86_400_000) / 1000;
+        let (hour, minute, second) = (day_secs / 3600, (day_secs % 3600) / 60, day_secs % 60);
+        let (year, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7) as usize;
+        let day_of_year = days - days_from_civil(year, 1, 1) + 1;
+
+        let mut out = String::new();
+        let mut chars = fmt.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => out.push_str(&year.to_string()),
+                Some('y') => out.push_str(&format!("{:02}", year.rem_euclid(100))),
+                Some('m') => out.push_str(&format!("{:02}", month)),
+                Some('d') => out.push_str(&format!("{:02}", day)),
+                Some('e') => out.push_str(&format!("{:>2}", day)),
+                Some('H') => out.push_str(&format!("{:02}", hour)),
+                Some('I') => out.push_str(&format!("{:02}", if hour % 12 == 0 { 12 } else { hour % 12 })),
+                Some('M') => out.push_str(&format!("{:02}", minute)),
+                Some('S') => out.push_str(&format!("{:02}", second)),
+                Some('L') => out.push_str(&format!("{:03}", millis)),
+                Some('p') => out.push_str(if hour < 12 { "AM" } else { "PM" }),
+                Some('j') => out.push_str(&format!("{:03}", day_of_year)),
+                Some('a') => out.push_str(&WEEKDAYS[weekday][..3]),
+                Some('A') => out.push_str(WEEKDAYS[weekday]),
+                Some('b') => out.push_str(&MONTHS[month as usize - 1][..3]),
+                Some('B') => out.push_str(MONTHS[month as usize - 1]),
+                Some('s') => out.push_str(&timestamp.div_euclid(1000).to_string()),
+                Some('z') => out.push_str(&format_offset(offset_minutes, "")),
+                Some('F') => out.push_str(&format!("{}-{:02}-{:02}", year, month, day)),
+                Some('T') => out.push_str(&format!("{:02}:{:02}:{:02}", hour, minute, second)),
+                Some('%') => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+        out
+    }
+
+    /// Formats a UTC offset as `+HHMM`, or `+HH:MM` with a `:` separator.
+    pub(crate) fn format_offset(offset_minutes: i32, separator: &str) -> String {
+        let sign = if offset_minutes < 0 { '-' } else { '+' };
+        let abs = offset_minutes.unsigned_abs();
+        format!("{}{:02}{}{:02}", sign, abs / 60, separator, abs % 60)
+    }
+
+    /// Converts days since 1970-01-01 to a (year, month, day) civil date.
+    pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
     }
 
-    /// Formats a timestamp as a string.
-    pub fn format(_timestamp: u64, _fmt: &str) -> String {
-        // TODO: Implement for native targets; not available in WASM
-        unimplemented!("Date formatting is not available in WASM");
+    /// Converts a civil date to days since 1970-01-01.
+    pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = month as i64;
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
     }
 }
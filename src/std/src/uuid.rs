@@ -0,0 +1,158 @@
+//! Standard library: UUIDs for Gigli
+//!
+//! Random bits come from `std::random`, so UUIDs are unique in practice but not
+//! suitable as unguessable secrets.
+
+use crate::random;
+use std::cell::Cell;
+
+/// A 128-bit universally unique identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid {
+    bytes: [u8; 16],
+}
+
+thread_local! {
+    /// The timestamp and counter of the last v7 UUID, keeping ids from the
+    /// same millisecond in creation order.
+    static LAST_V7: Cell<(u64, u16)> = const { Cell::new((0, 0)) };
+}
+
+impl Uuid {
+    /// The all-zero UUID.
+    pub fn nil() -> Self {
+        Uuid { bytes: [0; 16] }
+    }
+
+    /// Creates a UUID from raw bytes without checking its version.
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Uuid { bytes }
+    }
+
+    /// Generates a random (version 4) UUID.
+    pub fn new_v4() -> Self {
+        let mut bytes = [0u8; 16];
+        random::fill_bytes(&mut bytes);
+        Uuid::with_version(bytes, 4)
+    }
+
+    /// Generates a time-ordered (version 7) UUID: a 48-bit millisecond timestamp
+    /// followed by a 12-bit sequence and random bits, so ids sort by creation time.
+    pub fn new_v7() -> Self {
+        let now = crate::time::time::now();
+        let (millis, sequence) = LAST_V7.with(|last| {
+            let (last_millis, last_sequence) = last.get();
+            let next = if now <= last_millis && last_sequence < 0x0fff {
+                (last_millis, last_sequence + 1)
+            } else if now <= last_millis {
+                // Sequence exhausted: borrow the next millisecond.
+                (last_millis + 1, 0)
+            } else {
+                // Start each millisecond at a random point in the lower half so
+                // there is room left to count up.
+                (now, random::int_range(0, 0x0800) as u16)
+            };
+            last.set(next);
+            next
+        });
+        let mut bytes = [0u8; 16];
+        random::fill_bytes(&mut bytes[8..]);
+        bytes[..6].copy_from_slice(&millis.to_be_bytes()[2..]);
+        bytes[6] = (sequence >> 8) as u8;
+        bytes[7] = sequence as u8;
+        Uuid::with_version(bytes, 7)
+    }
+
+    fn with_version(mut bytes: [u8; 16], version: u8) -> Self {
+        bytes[6] = (bytes[6] & 0x0f) | (version << 4);
+        // RFC 9562 variant (10xx).
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        Uuid { bytes }
+    }
+
+    /// Parses the hyphenated form (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`), optionally
+    /// wrapped in braces or prefixed with `urn:uuid:`, or 32 bare hex digits.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid UUID: {}", text);
+        let mut s = text.trim();
+        s = s.strip_prefix("urn:uuid:").unwrap_or(s);
+        if let Some(inner) = s.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')) {
+            s = inner;
+        }
+        let hex: String = match s.len() {
+            32 => s.to_string(),
+            36 => {
+                let groups: Vec<&str> = s.split('-').collect();
+                let lengths: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+                if lengths != [8, 4, 4, 4, 12] {
+                    return Err(invalid());
+                }
+                groups.concat()
+            }
+            _ => return Err(invalid()),
+        };
+        let decoded = crate::encoding::hex_decode(&hex).map_err(|_| invalid())?;
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&decoded);
+        Ok(Uuid { bytes })
+    }
+
+    /// Returns true if the text parses as a UUID.
+    pub fn is_valid(text: &str) -> bool {
+        Uuid::parse(text).is_ok()
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.bytes
+    }
+
+    /// The version number from the UUID's version field (4 for random, 7 for time-ordered).
+    pub fn version(&self) -> u8 {
+        self.bytes[6] >> 4
+    }
+
+    pub fn is_nil(&self) -> bool {
+        self.bytes == [0; 16]
+    }
+
+    /// For v7 UUIDs, the embedded creation time in milliseconds since the Unix epoch.
+    pub fn timestamp(&self) -> Option<u64> {
+        if self.version() != 7 {
+            return None;
+        }
+        let mut millis = [0u8; 8];
+        millis[2..].copy_from_slice(&self.bytes[..6]);
+        Some(u64::from_be_bytes(millis))
+    }
+
+    /// Formats as 32 hex digits without hyphens.
+    pub fn to_simple_string(&self) -> String {
+        crate::encoding::hex_encode(&self.bytes)
+    }
+}
+
+impl std::fmt::Display for Uuid {
+    /// Formats in the lowercase hyphenated form.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = self.to_simple_string();
+        write!(f, "{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+}
+
+impl std::str::FromStr for Uuid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Uuid::parse(s)
+    }
+}
+
+/// Generates a random (v4) UUID string.
+pub fn v4() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Generates a time-ordered (v7) UUID string.
+pub fn v7() -> String {
+    Uuid::new_v7().to_string()
+}